@@ -0,0 +1,318 @@
+//! CPU cost of the ways the demo talks to the GPU: uploading buffers,
+//! creating bind groups and encoding draws. Run with `cargo bench`, the
+//! benchmarks are skipped without an adapter.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::executor::block_on;
+use wgpu::{
+    util::{make_spirv, BufferInitDescriptor, DeviceExt, StagingBelt},
+    vertex_attr_array, AddressMode, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferDescriptor, BufferSize, BufferUsage, Color, ColorStateDescriptor, ColorWrite,
+    CommandEncoderDescriptor, Device, Extent3d, FilterMode, IndexFormat, InputStepMode, LoadOp,
+    Maintain, Operations, PipelineLayoutDescriptor, PrimitiveTopology, ProgrammableStageDescriptor,
+    Queue, RenderPassColorAttachmentDescriptor, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, SamplerDescriptor, ShaderStage, TextureComponentType,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsage, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexBufferDescriptor, VertexStateDescriptor,
+};
+use wgpu_test::{adapter, shaders};
+
+/// Sizes of the uploaded buffers, from a uniform block to a large mesh.
+const UPLOAD_SIZES: [u64; 3] = [256, 64 * 1024, 4 * 1024 * 1024];
+
+/// Draws encoded into a single pass.
+const DRAW_COUNTS: [u32; 3] = [100, 1000, 10000];
+
+/// Uniforms of each draw are 256 bytes apart, the offset alignment.
+const UNIFORM_STRIDE: u64 = 256;
+
+const FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// The shared headless device, `None` without an adapter.
+fn device() -> Option<(Device, Queue)> {
+    let device = adapter::headless_device();
+    if device.is_none() {
+        eprintln!("No adapter found, skipping");
+    }
+    device
+}
+
+/// Uploads into a new buffer, with `write_buffer` and through a staging belt,
+/// each waiting for the upload to finish.
+fn upload(c: &mut Criterion) {
+    let (device, queue) = match device() {
+        Some(device) => device,
+        None => return,
+    };
+    let mut group = c.benchmark_group("upload");
+    for &size in &UPLOAD_SIZES {
+        let data = vec![0x55u8; size as usize];
+        let target = device.create_buffer(&BufferDescriptor {
+            label: Some("Upload target"),
+            size,
+            usage: BufferUsage::VERTEX | BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        group.throughput(Throughput::Bytes(size));
+
+        group.bench_with_input(
+            BenchmarkId::new("create_buffer_init", size),
+            &data,
+            |b, data| {
+                b.iter(|| {
+                    let buffer = device.create_buffer_init(&BufferInitDescriptor {
+                        label: Some("Upload"),
+                        contents: data,
+                        usage: BufferUsage::VERTEX,
+                    });
+                    queue.submit(None);
+                    device.poll(Maintain::Wait);
+                    buffer
+                })
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("write_buffer", size), &data, |b, data| {
+            b.iter(|| {
+                queue.write_buffer(&target, 0, data);
+                queue.submit(None);
+                device.poll(Maintain::Wait);
+            })
+        });
+        // chunks are reused once recalled, as they would be frame to frame
+        let mut belt = StagingBelt::new(size);
+        group.bench_with_input(BenchmarkId::new("staging_belt", size), &data, |b, data| {
+            b.iter(|| {
+                let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Staging belt upload"),
+                });
+                let size = BufferSize::new(size).unwrap();
+                belt.write_buffer(&mut encoder, &target, 0, size, &device)
+                    .copy_from_slice(data);
+                belt.finish();
+                queue.submit(Some(encoder.finish()));
+                let recall = belt.recall();
+                device.poll(Maintain::Wait);
+                block_on(recall);
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Bind group of a uniform buffer, a texture and a sampler, like most of the
+/// effects have.
+fn bind_groups(c: &mut Criterion) {
+    let (device, _) = match device() {
+        Some(device) => device,
+        None => return,
+    };
+    let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Benchmark bind group layout"),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::SampledTexture {
+                    dimension: TextureViewDimension::D2,
+                    component_type: TextureComponentType::Float,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Sampler { comparison: false },
+                count: None,
+            },
+        ],
+    });
+    let uniform = uniform_buffer(&device, 1);
+    let (_texture, view) = target(&device, 256, TextureUsage::SAMPLED);
+    let sampler = device.create_sampler(&SamplerDescriptor {
+        label: Some("Benchmark sampler"),
+        address_mode_u: AddressMode::ClampToEdge,
+        address_mode_v: AddressMode::ClampToEdge,
+        address_mode_w: AddressMode::ClampToEdge,
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..Default::default()
+    });
+    c.bench_function("create_bind_group", |b| {
+        b.iter(|| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Benchmark bind group"),
+                layout: &layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(uniform.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(&view),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&sampler),
+                    },
+                ],
+            })
+        })
+    });
+}
+
+/// A pass of N draws, each with its uniforms at a dynamic offset, encoded but
+/// not submitted.
+fn encoding(c: &mut Criterion) {
+    let (device, _) = match device() {
+        Some(device) => device,
+        None => return,
+    };
+    let max_draws = *DRAW_COUNTS.iter().max().unwrap();
+    let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Benchmark bind group layout"),
+        entries: &[BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStage::VERTEX,
+            ty: BindingType::UniformBuffer {
+                dynamic: true,
+                min_binding_size: BufferSize::new(64),
+            },
+            count: None,
+        }],
+    });
+    let uniform = uniform_buffer(&device, max_draws as u64);
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Benchmark bind group"),
+        layout: &layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: BindingResource::Buffer(uniform.slice(..64)),
+        }],
+    });
+    let pipeline = gizmo_pipeline(&device, &layout);
+    let vertices = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Benchmark vertices"),
+        contents: bytemuck::cast_slice(&[0.0f32; 3 * 7]),
+        usage: BufferUsage::VERTEX,
+    });
+    let (_texture, view) = target(&device, 64, TextureUsage::OUTPUT_ATTACHMENT);
+
+    let mut group = c.benchmark_group("encoding");
+    for &draws in &DRAW_COUNTS {
+        group.throughput(Throughput::Elements(draws as _));
+        group.bench_with_input(BenchmarkId::new("draws", draws), &draws, |b, &draws| {
+            b.iter(|| {
+                let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Benchmark draws"),
+                });
+                {
+                    let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                        color_attachments: &[RenderPassColorAttachmentDescriptor {
+                            attachment: &view,
+                            resolve_target: None,
+                            ops: Operations {
+                                load: LoadOp::Clear(Color::BLACK),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+                    pass.set_pipeline(&pipeline);
+                    pass.set_vertex_buffer(0, vertices.slice(..));
+                    for i in 0..draws {
+                        let offset = (i as u64 * UNIFORM_STRIDE) as u32;
+                        pass.set_bind_group(0, &bind_group, &[offset]);
+                        pass.draw(0..3, 0..1);
+                    }
+                }
+                encoder.finish()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Uniforms of `count` draws, `UNIFORM_STRIDE` bytes apart.
+fn uniform_buffer(device: &Device, count: u64) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("Benchmark uniforms"),
+        size: count * UNIFORM_STRIDE,
+        usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn target(device: &Device, size: u32, usage: TextureUsage) -> (wgpu::Texture, TextureView) {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("Benchmark texture"),
+        size: Extent3d {
+            width: size,
+            height: size,
+            depth: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: FORMAT,
+        usage,
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    (texture, view)
+}
+
+/// The pipeline of the gizmos, drawing triangles.
+fn gizmo_pipeline(device: &Device, layout: &BindGroupLayout) -> RenderPipeline {
+    let vert_module = device.create_shader_module(make_spirv(shaders::GIZMO_VERT));
+    let frag_module = device.create_shader_module(make_spirv(shaders::GIZMO_FRAG));
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Benchmark pipeline layout"),
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Benchmark pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex_stage: ProgrammableStageDescriptor {
+            module: &vert_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(ProgrammableStageDescriptor {
+            module: &frag_module,
+            entry_point: "main",
+        }),
+        rasterization_state: None,
+        primitive_topology: PrimitiveTopology::TriangleList,
+        color_states: &[ColorStateDescriptor {
+            format: FORMAT,
+            color_blend: BlendDescriptor::REPLACE,
+            alpha_blend: BlendDescriptor::REPLACE,
+            write_mask: ColorWrite::ALL,
+        }],
+        depth_stencil_state: None,
+        vertex_state: VertexStateDescriptor {
+            index_format: IndexFormat::Uint16,
+            vertex_buffers: &[VertexBufferDescriptor {
+                stride: 7 * 4,
+                step_mode: InputStepMode::Vertex,
+                attributes: &vertex_attr_array![0 => Float3, 1 => Float4],
+            }],
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
+
+criterion_group!(benches, upload, bind_groups, encoding);
+criterion_main!(benches);
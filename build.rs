@@ -1,22 +1,216 @@
-use std::process::Command;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-fn compile_shader(input: &str, output: &str) {
-    let status = Command::new("glslangValidator")
-        .args(&["-V", "-o", output, input])
+/// Directory compiled shaders are found in, and extensions of their stages.
+const SHADERS_DIR: &str = "src/shaders";
+const STAGES: [&str; 3] = ["vert", "frag", "comp"];
+
+/// Shaders also compiled with some defines, as the source, the name of the
+/// variant and its defines. The SPIR-V is named after the variant, so
+/// `shader_oit.frag` is `SHADER_OIT_FRAG`.
+const VARIANTS: &[(&str, &str, &[&str])] = &[
+    ("shader.frag", "shader_oit.frag", &["OIT=1"]),
+    ("pulling.vert", "pulling_storage.vert", &["PULLED=1"]),
+];
+
+/// Set to compile every shader, even those with up to date SPIR-V.
+const REBUILD_VAR: &str = "REBUILD_SHADERS";
+
+/// Shaders are GLSL, as in `foo.frag`, or HLSL with an extra extension, as
+/// in `foo.frag.hlsl`.
+#[derive(Clone, Copy, PartialEq)]
+enum Language {
+    Glsl,
+    Hlsl,
+}
+
+impl Language {
+    fn compiler(self) -> &'static str {
+        match self {
+            Language::Glsl => "glslangValidator",
+            Language::Hlsl => "dxc",
+        }
+    }
+}
+
+/// Language and stage of `path`, `None` if it isn't a shader.
+fn shader_kind(path: &Path) -> Option<(Language, &'static str)> {
+    let name = path.file_name()?.to_str()?;
+    let (language, name) = match name.strip_suffix(".hlsl") {
+        Some(name) => (Language::Hlsl, name),
+        None => (Language::Glsl, name),
+    };
+    let extension = Path::new(name).extension()?;
+    let stage = STAGES.iter().find(|&&stage| extension == stage)?;
+    Some((language, stage))
+}
+
+fn compile_shader(input: &Path, output: &Path, language: Language, stage: &str, defines: &[&str]) {
+    let mut command = Command::new(language.compiler());
+    for define in defines {
+        match language {
+            Language::Glsl => command.arg(format!("-D{}", define)),
+            Language::Hlsl => command.arg("-D").arg(define),
+        };
+    }
+    match language {
+        Language::Glsl => command.arg("-V").arg("-o").arg(output).arg(input),
+        Language::Hlsl => {
+            let profile = match stage {
+                "vert" => "vs_6_0",
+                "frag" => "ps_6_0",
+                _ => "cs_6_0",
+            };
+            command
+                .args(["-spirv", "-T", profile, "-E", "main", "-Fo"])
+                .arg(output)
+                .arg(input)
+        }
+    };
+    let status = command
         .spawn()
-        .expect("Error launching SPIRV validator")
+        .expect("Error launching shader compiler")
         .wait()
         .unwrap();
 
-    assert!(status.success());
+    assert!(status.success(), "Error compiling {}", input.display());
+}
+
+/// Whether the compiler of `language` can be run at all.
+fn has_compiler(language: Language) -> bool {
+    Command::new(language.compiler())
+        .arg("--version")
+        .output()
+        .is_ok()
+}
+
+/// Whether `output` is missing or older than `input`.
+fn is_stale(input: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(input), modified(output)) {
+        (Some(input), Some(output)) => input > output,
+        _ => true,
+    }
+}
+
+/// Shaders under `dir` and its subdirectories, in path order.
+fn find_shaders(dir: &Path, shaders: &mut Vec<PathBuf>) {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .expect("Error reading shaders directory")
+        .map(|entry| entry.expect("Error reading shaders directory").path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            find_shaders(&path, shaders);
+        } else if shader_kind(&path).is_some() {
+            shaders.push(path);
+        }
+    }
+}
+
+/// Name of the constant of a shader, `shadows/point.frag` is `SHADOWS_POINT_FRAG`
+/// and `blur.comp.hlsl` is `BLUR_COMP_HLSL`.
+fn const_name(shader: &Path) -> String {
+    let relative = shader.strip_prefix(SHADERS_DIR).unwrap();
+    relative
+        .to_string_lossy()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
 }
 
 fn main() {
-    println!("cargo:rerun-if-changed=src/shader.vert");
-    println!("cargo:rerun-if-changed=src/shader.frag");
+    println!("cargo:rerun-if-changed={}", SHADERS_DIR);
+    println!("cargo:rerun-if-env-changed={}", REBUILD_VAR);
+    let mut sources = Vec::new();
+    find_shaders(Path::new(SHADERS_DIR), &mut sources);
+    // every shader as its source, the path it's named after and its defines
+    let mut shaders: Vec<_> = sources
+        .iter()
+        .map(|source| (source.clone(), source.clone(), &[][..]))
+        .collect();
+    for &(source, variant, defines) in VARIANTS {
+        let dir = Path::new(SHADERS_DIR);
+        shaders.push((dir.join(source), dir.join(variant), defines));
+    }
+
+    // without a compiler the committed SPIR-V is used, shaders can't be
+    // modified but everything else can still be built
+    let rebuild = env::var_os(REBUILD_VAR).is_some();
+    let mut compilers = Vec::new();
+    for &language in &[Language::Glsl, Language::Hlsl] {
+        let used = sources
+            .iter()
+            .any(|s| shader_kind(s).unwrap().0 == language);
+        let available = used && has_compiler(language);
+        if used && !available {
+            let compiler = language.compiler();
+            assert!(
+                !rebuild,
+                "{} is set but {} isn't in the PATH",
+                REBUILD_VAR, compiler
+            );
+            println!(
+                "cargo:warning={} isn't in the PATH, using the committed SPIR-V \
+                 (changes to the shaders have no effect)",
+                compiler
+            );
+        }
+        compilers.push((language, available));
+    }
 
-    // comment these lines if you don't have `glslangValidator` in your PATH
-    // (you won't be able to modify the shaders though)
-    compile_shader("src/shader.vert", "src/shader.vert.spv");
-    compile_shader("src/shader.frag", "src/shader.frag.spv");
+    // SPIR-V is written next to the sources, and a module with the bytes of
+    // every shader is generated for `shaders.rs` to include
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let mut module = String::new();
+    let mut all = String::new();
+    for (source, shader, defines) in &shaders {
+        println!("cargo:rerun-if-changed={}", source.display());
+        let mut output = shader.clone().into_os_string();
+        output.push(".spv");
+        let output = PathBuf::from(output);
+        let (language, stage) = shader_kind(source).unwrap();
+        let available = compilers.contains(&(language, true));
+        if available && (rebuild || is_stale(source, &output)) {
+            compile_shader(source, &output, language, stage, defines);
+        } else if !output.exists() {
+            panic!(
+                "{} has no SPIR-V and {} isn't in the PATH",
+                shader.display(),
+                language.compiler()
+            );
+        }
+        module.push_str(&format!(
+            "/// `{}`\npub const {}: &[u8] = include_bytes!({:?});\n",
+            shader.strip_prefix(SHADERS_DIR).unwrap().display(),
+            const_name(shader),
+            root.join(&output),
+        ));
+        all.push_str(&format!(
+            "    ({:?}, {}),\n",
+            shader
+                .strip_prefix(SHADERS_DIR)
+                .unwrap()
+                .display()
+                .to_string(),
+            const_name(shader),
+        ));
+    }
+    // only the tests go through every shader
+    module.push_str(&format!(
+        "/// Path and SPIR-V of every shader.\n#[allow(dead_code)]\npub const ALL: &[(&str, &[u8])] = &[\n{}];\n",
+        all
+    ));
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("shaders.rs"), module).expect("Error writing shaders module");
 }
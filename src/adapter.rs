@@ -0,0 +1,123 @@
+//! Adapter enumeration and selection.
+//!
+//! Adapters are listed in the order wgpu enumerates them, which is stable
+//! between runs, so their index can be passed back with `--adapter`.
+//!
+//! Without an index wgpu picks an adapter that can present to the window,
+//! from any backend. If it finds none, backends are fallen back through in
+//! the order of [`FALLBACK_ORDER`], hardware adapters first and software
+//! ones last.
+use log::{info, warn};
+use wgpu::{
+    Adapter, AdapterInfo, BackendBit, Device, DeviceDescriptor, DeviceType, Instance,
+    PowerPreference, Queue, RequestAdapterOptions, Surface,
+};
+
+/// Backends adapters are enumerated from.
+pub const BACKENDS: BackendBit =
+    BackendBit::from_bits_truncate(BackendBit::PRIMARY.bits() | BackendBit::SECONDARY.bits());
+
+/// Backends in the order they're fallen back through.
+const FALLBACK_ORDER: [BackendBit; 5] = [
+    BackendBit::VULKAN,
+    BackendBit::METAL,
+    BackendBit::DX12,
+    BackendBit::DX11,
+    BackendBit::GL,
+];
+
+/// Name, backend and device type of an adapter, on one line.
+pub fn describe(info: &AdapterInfo) -> String {
+    format!("{} ({:?}, {:?})", info.name, info.backend, info.device_type)
+}
+
+/// Infos of every adapter of every backend.
+pub fn enumerate(instance: &Instance) -> Vec<AdapterInfo> {
+    instance
+        .enumerate_adapters(BACKENDS)
+        .map(|adapter| adapter.get_info())
+        .collect()
+}
+
+/// Picks the adapter at `index` of [`enumerate`], or lets wgpu pick one that
+/// can present to `surface` if there's no index, falling back through the
+/// backends if it can't. Returns the adapter and its index, if it's one of
+/// the enumerated ones. An `index` out of range is the same as none.
+pub fn select(
+    instance: &Instance,
+    surface: &Surface,
+    index: Option<usize>,
+) -> (Adapter, Option<usize>) {
+    if let Some(index) = index {
+        match instance.enumerate_adapters(BACKENDS).nth(index) {
+            Some(adapter) => {
+                info!(
+                    "Selected adapter {}: {}",
+                    index,
+                    describe(&adapter.get_info())
+                );
+                return (adapter, Some(index));
+            }
+            None => warn!("There's no adapter {}, using the default one", index),
+        }
+    }
+    let requested = futures::executor::block_on(instance.request_adapter(&RequestAdapterOptions {
+        power_preference: PowerPreference::Default,
+        compatible_surface: Some(surface),
+    }));
+    let adapter = match requested {
+        Some(adapter) => {
+            info!("Selected {}", describe(&adapter.get_info()));
+            adapter
+        }
+        None => {
+            let adapter = fallback(instance).expect("Couldn't create adapter");
+            warn!(
+                "No adapter can present to the window, falling back to {}",
+                describe(&adapter.get_info())
+            );
+            adapter
+        }
+    };
+    let info = adapter.get_info();
+    if info.device_type == DeviceType::Cpu {
+        warn!("{} is a software adapter, expect it to be slow", info.name);
+    }
+    let index = enumerate(instance).iter().position(|other| *other == info);
+    (adapter, index)
+}
+
+/// The first hardware adapter of the backends in [`FALLBACK_ORDER`], or the
+/// first software one if there's none.
+fn fallback(instance: &Instance) -> Option<Adapter> {
+    let mut software = None;
+    for &backend in &FALLBACK_ORDER {
+        for adapter in instance.enumerate_adapters(backend) {
+            if adapter.get_info().device_type != DeviceType::Cpu {
+                return Some(adapter);
+            }
+            software = software.or(Some(adapter));
+        }
+        info!("No hardware adapter of {:?}", backend);
+    }
+    software
+}
+
+/// A device of the first adapter found, without a window to present to.
+/// `None` without an adapter, so tests and benchmarks can skip themselves.
+pub fn headless_device() -> Option<(Device, Queue)> {
+    let instance = Instance::new(BACKENDS);
+    let adapter = futures::executor::block_on(instance.request_adapter(&RequestAdapterOptions {
+        power_preference: PowerPreference::Default,
+        compatible_surface: None,
+    }))?;
+    let device = futures::executor::block_on(adapter.request_device(
+        &DeviceDescriptor {
+            shader_validation: true,
+            ..Default::default()
+        },
+        None,
+    ))
+    .expect("Error requesting device");
+    Some(device)
+}
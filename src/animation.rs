@@ -0,0 +1,440 @@
+//! Node hierarchies animated by keyframed clips.
+//!
+//! Clips animate the local transforms of nodes and the weights of the morph
+//! targets of their meshes; skins turn the world transforms of their joints
+//! into the matrices the vertex shaders blend skinned vertices with.
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Quat, Vec3};
+use imgui::{im_str, ComboBox, ImString, Slider, Ui};
+
+/// Joints and weights of a skinned vertex, as read by the vertex shaders.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Pod, Zeroable)]
+pub struct SkinVertex {
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
+}
+
+/// Offsets of a vertex in a morph target, as read by the vertex shaders.
+/// Vectors have a zero `w`.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Pod, Zeroable)]
+pub struct MorphDelta {
+    pub position: [f32; 4],
+    pub normal: [f32; 4],
+    pub tangent: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+pub struct Node {
+    pub parent: Option<usize>,
+    /// Local transform when nothing animates it.
+    pub rest: Transform,
+}
+
+pub struct Skin {
+    /// Nodes of the joints.
+    pub joints: Vec<usize>,
+    /// Transforms from the space of the mesh to the space of each joint,
+    /// in its bind pose.
+    pub inverse_bind: Vec<Mat4>,
+    /// Whether the only joint is the node of an unskinned mesh, which moves
+    /// with it.
+    pub rigid: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+    /// Weights of the morph targets.
+    Weights,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    /// Keyframes have an in-tangent, a value and an out-tangent.
+    CubicSpline,
+}
+
+/// Keyframes of one property of a node.
+pub struct Channel {
+    pub node: usize,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    /// Time of each keyframe in seconds, increasing.
+    pub times: Vec<f32>,
+    /// Values of the keyframes, `width` numbers each. Three values per
+    /// keyframe with cubic spline interpolation.
+    pub values: Vec<f32>,
+    pub width: usize,
+}
+
+impl Channel {
+    fn sample(&self, time: f32) -> Vec<f32> {
+        let per_key = match self.interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        };
+        let element = |i: usize| &self.values[i * self.width..(i + 1) * self.width];
+        let value = |key: usize| element(key * per_key + per_key / 2);
+        let last = self.times.len() - 1;
+        // after the last keyframe or before the first one, the value is held
+        let next = self
+            .times
+            .iter()
+            .position(|&t| t > time)
+            .unwrap_or(last + 1);
+        if next == 0 {
+            return value(0).to_vec();
+        }
+        if next > last {
+            return value(last).to_vec();
+        }
+        let key = next - 1;
+        let dt = self.times[next] - self.times[key];
+        let t = (time - self.times[key]) / dt;
+        match self.interpolation {
+            Interpolation::Step => value(key).to_vec(),
+            Interpolation::Linear if self.property == Property::Rotation => {
+                let from = quat(value(key));
+                let rotation: [f32; 4] = from.slerp(quat(value(next)), t).into();
+                rotation.to_vec()
+            }
+            Interpolation::Linear => (value(key).iter().zip(value(next)))
+                .map(|(a, b)| a + (b - a) * t)
+                .collect(),
+            Interpolation::CubicSpline => {
+                let out_tangent = element(key * 3 + 2);
+                let in_tangent = element(next * 3);
+                let (t2, t3) = (t * t, t * t * t);
+                (0..self.width)
+                    .map(|i| {
+                        value(key)[i] * (2.0 * t3 - 3.0 * t2 + 1.0)
+                            + out_tangent[i] * dt * (t3 - 2.0 * t2 + t)
+                            + value(next)[i] * (-2.0 * t3 + 3.0 * t2)
+                            + in_tangent[i] * dt * (t3 - t2)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+fn vec3(value: &[f32]) -> Vec3 {
+    Vec3::new(value[0], value[1], value[2])
+}
+
+fn quat(value: &[f32]) -> Quat {
+    Quat::from_xyzw(value[0], value[1], value[2], value[3])
+}
+
+/// Kind of animation a keyframe belongs to.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub enum Animates {
+    /// Joints of a skin.
+    Joints,
+    MorphTargets,
+    /// Transforms of nodes that aren't joints.
+    Nodes,
+}
+
+pub struct Clip {
+    pub name: String,
+    /// Time of the last keyframe.
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+/// Nodes of a posed skeleton.
+pub struct Pose {
+    /// World transform of every node.
+    pub worlds: Vec<Mat4>,
+    /// Morph target weights of nodes whose weights are animated.
+    pub weights: Vec<Option<Vec<f32>>>,
+}
+
+/// Nodes of a scene with their skins and clips.
+pub struct Skeleton {
+    pub nodes: Vec<Node>,
+    pub skins: Vec<Skin>,
+    pub clips: Vec<Clip>,
+}
+
+impl Skeleton {
+    /// Pose at `time` seconds into `clip`, or the rest pose without a clip.
+    pub fn pose(&self, clip: Option<usize>, time: f32) -> Pose {
+        let mut locals: Vec<_> = self.nodes.iter().map(|node| node.rest).collect();
+        let mut weights = vec![None; self.nodes.len()];
+        if let Some(clip) = clip.and_then(|clip| self.clips.get(clip)) {
+            for channel in &clip.channels {
+                let value = channel.sample(time);
+                let local = &mut locals[channel.node];
+                match channel.property {
+                    Property::Translation => local.translation = vec3(&value),
+                    Property::Rotation => local.rotation = quat(&value).normalize(),
+                    Property::Scale => local.scale = vec3(&value),
+                    Property::Weights => weights[channel.node] = Some(value),
+                }
+            }
+        }
+
+        let mut worlds: Vec<Option<Mat4>> = vec![None; self.nodes.len()];
+        for node in 0..self.nodes.len() {
+            // parents first, walking up until a node whose world is known
+            let mut chain = vec![node];
+            while let Some(parent) = self.nodes[*chain.last().unwrap()].parent {
+                if worlds[parent].is_some() || chain.contains(&parent) {
+                    break;
+                }
+                chain.push(parent);
+            }
+            for &i in chain.iter().rev() {
+                let parent = self.nodes[i].parent.and_then(|parent| worlds[parent]);
+                worlds[i] = Some(parent.unwrap_or_else(Mat4::identity) * locals[i].matrix());
+            }
+        }
+        Pose {
+            worlds: worlds.into_iter().map(Option::unwrap).collect(),
+            weights,
+        }
+    }
+
+    /// Times of the keyframes of `clip` and what they animate, sorted and
+    /// without repeats.
+    pub fn keyframes(&self, clip: &Clip) -> Vec<(f32, Animates)> {
+        let mut keyframes: Vec<_> = clip
+            .channels
+            .iter()
+            .flat_map(|channel| {
+                let animates = match channel.property {
+                    Property::Weights => Animates::MorphTargets,
+                    _ if self
+                        .skins
+                        .iter()
+                        .any(|skin| !skin.rigid && skin.joints.contains(&channel.node)) =>
+                    {
+                        Animates::Joints
+                    }
+                    _ => Animates::Nodes,
+                };
+                channel.times.iter().map(move |&time| (time, animates))
+            })
+            .collect();
+        keyframes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        keyframes.dedup();
+        keyframes
+    }
+
+    /// Joint matrices of `skin`, from the world transforms of a pose.
+    pub fn joint_matrices(&self, skin: usize, worlds: &[Mat4]) -> Vec<Mat4> {
+        let skin = &self.skins[skin];
+        skin.joints
+            .iter()
+            .zip(&skin.inverse_bind)
+            .map(|(&joint, inverse_bind)| worlds[joint] * *inverse_bind)
+            .collect()
+    }
+}
+
+/// Clip of a skeleton played by the timeline.
+pub struct Player {
+    /// Clip being played, the rest pose if `None`.
+    pub clip: Option<usize>,
+}
+
+impl Player {
+    /// Plays the first clip, if there's any.
+    pub fn new(skeleton: &Skeleton) -> Self {
+        Self {
+            clip: if skeleton.clips.is_empty() {
+                None
+            } else {
+                Some(0)
+            },
+        }
+    }
+
+    pub fn clip<'a>(&self, skeleton: &'a Skeleton) -> Option<&'a Clip> {
+        self.clip.and_then(|clip| skeleton.clips.get(clip))
+    }
+
+    /// Seconds into the clip at the current time of `timeline`.
+    pub fn time(&self, skeleton: &Skeleton, timeline: &Timeline) -> f32 {
+        self.clip(skeleton)
+            .map_or(0.0, |clip| timeline.clip_time(clip.duration))
+    }
+}
+
+/// Loop modes selectable from the UI.
+pub const LOOP_MODES: [LoopMode; 3] = [LoopMode::Loop, LoopMode::Once, LoopMode::PingPong];
+
+/// What clips do after their last keyframe.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LoopMode {
+    /// Start over from the first keyframe.
+    Loop,
+    /// Hold the last keyframe.
+    Once,
+    /// Play backwards to the first keyframe, then forwards again.
+    PingPong,
+}
+
+impl LoopMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            LoopMode::Loop => "Loop",
+            LoopMode::Once => "Once",
+            LoopMode::PingPong => "Ping-pong",
+        }
+    }
+}
+
+/// Playback shared by every clip in the scene, so skeletal, morph target
+/// and node animations stay in sync.
+pub struct Timeline {
+    /// Seconds since the clips started.
+    pub time: f32,
+    pub playing: bool,
+    /// Playback rate, 1 is real time.
+    pub speed: f32,
+    pub loop_mode: LoopMode,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            playing: true,
+            speed: 1.0,
+            loop_mode: LoopMode::Loop,
+        }
+    }
+}
+
+impl Timeline {
+    /// Advances the time by `dt` seconds of real time. Playing once stops at
+    /// `length`, the duration of the longest clip.
+    pub fn advance(&mut self, dt: f32, length: f32) {
+        if self.playing {
+            self.time += dt * self.speed;
+        }
+        if self.loop_mode == LoopMode::Once && self.time >= length {
+            self.time = length;
+            self.playing = false;
+        }
+    }
+
+    /// Seconds into a clip lasting `duration`.
+    pub fn clip_time(&self, duration: f32) -> f32 {
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        match self.loop_mode {
+            LoopMode::Loop => self.time.rem_euclid(duration),
+            LoopMode::Once => self.time.min(duration),
+            LoopMode::PingPong => {
+                let time = self.time.rem_euclid(2.0 * duration);
+                if time > duration {
+                    2.0 * duration - time
+                } else {
+                    time
+                }
+            }
+        }
+    }
+    /// Controls of the playback of clips up to `length` long, with their
+    /// `keyframes` marked under the time.
+    pub fn ui(&mut self, ui: &Ui, length: f32, keyframes: &[(f32, Animates)]) {
+        let label = if self.playing {
+            im_str!("Pause")
+        } else {
+            im_str!("Play")
+        };
+        if ui.button(label, [60.0, 0.0]) {
+            // playing once from the end starts over
+            if !self.playing && self.clip_time(length) >= length {
+                self.time = 0.0;
+            }
+            self.playing = !self.playing;
+        }
+        ui.same_line(0.0);
+        if ui.button(im_str!("Rewind"), [0.0, 0.0]) {
+            self.time = 0.0;
+        }
+        let names: Vec<_> = LOOP_MODES.iter().map(|m| ImString::new(m.name())).collect();
+        let names: Vec<_> = names.iter().collect();
+        let mut index = LOOP_MODES
+            .iter()
+            .position(|&m| m == self.loop_mode)
+            .unwrap();
+        if ComboBox::new(im_str!("Loop mode")).build_simple_string(ui, &mut index, &names) {
+            self.loop_mode = LOOP_MODES[index];
+        }
+        Slider::new(im_str!("Speed"))
+            .range(0.0..=4.0)
+            .display_format(im_str!("%.2fx"))
+            .build(ui, &mut self.speed);
+
+        // the time of the longest clip, with the keyframes of every clip
+        // marked under it
+        let width = ui.calc_item_width();
+        let mut time = self.clip_time(length);
+        if Slider::new(im_str!("Time"))
+            .range(0.0..=length)
+            .display_format(im_str!("%.2f s"))
+            .build(ui, &mut time)
+        {
+            self.time = time;
+        }
+        let [x, y] = ui.cursor_screen_pos();
+        ui.dummy([width, 8.0]);
+        let color = |animates| match animates {
+            Animates::Joints => [0.4, 0.7, 1.0, 1.0],
+            Animates::MorphTargets => [0.4, 1.0, 0.5, 1.0],
+            Animates::Nodes => [1.0, 0.7, 0.3, 1.0],
+        };
+        let draw_list = ui.get_window_draw_list();
+        let marker_x = |t: f32| x + width * (t / length.max(f32::EPSILON));
+        for &(t, animates) in keyframes {
+            let x = marker_x(t);
+            draw_list
+                .add_line([x, y], [x, y + 6.0], color(animates))
+                .build();
+        }
+        let x = marker_x(time);
+        draw_list
+            .add_line([x, y - 2.0], [x, y + 8.0], [1.0, 1.0, 1.0, 1.0])
+            .thickness(2.0)
+            .build();
+        ui.text_colored(color(Animates::Joints), "Skeletal");
+        ui.same_line(0.0);
+        ui.text_colored(color(Animates::MorphTargets), "Morph targets");
+        ui.same_line(0.0);
+        ui.text_colored(color(Animates::Nodes), "Nodes");
+    }
+}
@@ -0,0 +1,73 @@
+//! Command line arguments.
+use std::path::PathBuf;
+
+const USAGE: &str = "Usage: wgpu-test [--bench <frames>] [--compare <dir>] [--trace <dir>] \
+                     [--environment <file>] [--adapter <index>] [--list-adapters] \
+                     [--record <file> | --replay <file>] [<scene.json>]";
+
+#[derive(Clone, Default)]
+pub struct Args {
+    /// Number of frames to run in benchmark mode.
+    pub bench: Option<u32>,
+    /// Directory of the reference images to compare the rendered frames with.
+    pub compare: Option<PathBuf>,
+    /// Directory to record a wgpu API trace into, which can be replayed with
+    /// wgpu's player. wgpu must be built with its `trace` feature
+    /// (`cargo run --features wgpu/trace -- --trace <dir>`).
+    pub trace: Option<PathBuf>,
+    /// Radiance HDR or OpenEXR environment map, for the skybox and the image
+    /// based lighting.
+    pub environment: Option<PathBuf>,
+    /// Index of the adapter to use, as listed by `--list-adapters`.
+    pub adapter: Option<usize>,
+    /// Prints the adapters and exits.
+    pub list_adapters: bool,
+    /// File to record the input into, see `replay`.
+    pub record: Option<PathBuf>,
+    /// File of recorded input to play back, instead of the input of the user.
+    pub replay: Option<PathBuf>,
+    /// Scene file opened instead of the default scene, like one saved from
+    /// the File menu.
+    pub scene: Option<PathBuf>,
+}
+
+impl Args {
+    /// Parses the arguments of the process. Exits printing the usage if they
+    /// aren't valid.
+    pub fn parse() -> Self {
+        let mut args = Self::default();
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--bench" => {
+                    let frames = iter.next().and_then(|frames| frames.parse().ok());
+                    args.bench = Some(frames.unwrap_or_else(|| usage()));
+                }
+                "--compare" => args.compare = Some(iter.next().unwrap_or_else(|| usage()).into()),
+                "--trace" => args.trace = Some(iter.next().unwrap_or_else(|| usage()).into()),
+                "--environment" => {
+                    args.environment = Some(iter.next().unwrap_or_else(|| usage()).into())
+                }
+                "--adapter" => {
+                    let index = iter.next().and_then(|index| index.parse().ok());
+                    args.adapter = Some(index.unwrap_or_else(|| usage()));
+                }
+                "--list-adapters" => args.list_adapters = true,
+                "--record" => args.record = Some(iter.next().unwrap_or_else(|| usage()).into()),
+                "--replay" => args.replay = Some(iter.next().unwrap_or_else(|| usage()).into()),
+                _ if !arg.starts_with('-') && args.scene.is_none() => args.scene = Some(arg.into()),
+                _ => usage(),
+            }
+        }
+        // a replay recording itself would overwrite its input
+        if args.record.is_some() && args.replay.is_some() {
+            usage();
+        }
+        args
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(1)
+}
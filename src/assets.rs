@@ -0,0 +1,102 @@
+//! Loading of assets picked at runtime (dropped onto the window...), by file
+//! extension.
+use crate::{
+    capabilities::Capabilities,
+    compressed::{self, CompressedError, CompressedImage},
+    gltf::{Gltf, GltfError},
+    lut::{Lut, LutError},
+    mesh::MeshData,
+    profiler,
+    scene_file::{SceneFile, SceneFileError},
+    texture,
+};
+use log::info;
+use std::{fmt, path::Path};
+
+pub enum Asset {
+    /// RGBA8 image.
+    Image {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    },
+    /// Block compressed image, only loaded as such if the device supports it.
+    Compressed(CompressedImage),
+    Mesh(MeshData),
+    /// Meshes with materials.
+    Scene(Gltf),
+    /// Color grading table, only `.cube` files (strips are loaded as images).
+    Lut(Lut),
+    /// Scene saved from the app, replacing the current one.
+    SceneFile(SceneFile),
+}
+
+#[derive(Debug)]
+pub enum AssetError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Obj(tobj::LoadError),
+    Compressed(CompressedError),
+    Gltf(GltfError),
+    Lut(LutError),
+    SceneFile(SceneFileError),
+    UnknownExtension,
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::Io(err) => write!(f, "{}", err),
+            AssetError::Image(err) => write!(f, "{}", err),
+            AssetError::Obj(err) => write!(f, "{}", err),
+            AssetError::Compressed(err) => write!(f, "{}", err),
+            AssetError::Gltf(err) => write!(f, "{}", err),
+            AssetError::Lut(err) => write!(f, "{}", err),
+            AssetError::SceneFile(err) => write!(f, "{}", err),
+            AssetError::UnknownExtension => write!(f, "Unknown file extension"),
+        }
+    }
+}
+
+/// Loads the asset at `path`. Compressed textures the device can't sample
+/// (without BC textures in its `capabilities`) are decompressed into images.
+pub fn load<P: AsRef<Path>>(path: P, capabilities: &Capabilities) -> Result<Asset, AssetError> {
+    let _scope = profiler::scope("Load asset");
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase);
+    match extension.as_deref() {
+        Some("png") | Some("hdr") => {
+            let (width, height, pixels) = texture::load_rgba8(path).map_err(AssetError::Image)?;
+            Ok(Asset::Image {
+                width,
+                height,
+                pixels,
+            })
+        }
+        Some("dds") | Some("ktx2") => {
+            let image = compressed::load(path).map_err(AssetError::Compressed)?;
+            if capabilities.bc_textures && image.gpu_levels() > 0 {
+                return Ok(Asset::Compressed(image));
+            }
+            info!("Decompressing {} on the CPU", path.display());
+            let pixels = image.decompress().map_err(AssetError::Compressed)?;
+            Ok(Asset::Image {
+                width: image.width,
+                height: image.height,
+                pixels,
+            })
+        }
+        Some("obj") => MeshData::load_obj(path)
+            .map(Asset::Mesh)
+            .map_err(AssetError::Obj),
+        Some("gltf") | Some("glb") => Gltf::load(path).map(Asset::Scene).map_err(AssetError::Gltf),
+        Some("cube") => Lut::load(path).map(Asset::Lut).map_err(AssetError::Lut),
+        Some("json") => SceneFile::load(path)
+            .map(Asset::SceneFile)
+            .map_err(AssetError::SceneFile),
+        _ => Err(AssetError::UnknownExtension),
+    }
+}
@@ -0,0 +1,129 @@
+//! Benchmark mode.
+//!
+//! Runs a fixed number of frames along a fixed camera path and reports
+//! percentiles of the CPU and GPU frame times, so the performance of different
+//! builds can be compared.
+use crate::camera::FlyCamera;
+use glam::Vec3;
+use std::time::Duration;
+
+/// File the statistics are saved to at the end of the benchmark.
+pub const OUTPUT_PATH: &str = "bench.json";
+
+/// Frames rendered before anything is recorded, to let the pipelines and the
+/// swap chain settle in.
+const WARMUP_FRAMES: u32 = 10;
+
+/// Radius of the orbit followed by the camera.
+const ORBIT_RADIUS: f32 = 6.0;
+
+pub struct Bench {
+    frames: u32,
+    frame: u32,
+    cpu: Vec<Duration>,
+    gpu: Vec<Duration>,
+}
+
+impl Bench {
+    pub fn new(frames: u32) -> Self {
+        Self {
+            frames,
+            frame: 0,
+            cpu: Vec::with_capacity(frames as _),
+            gpu: Vec::with_capacity(frames as _),
+        }
+    }
+
+    /// Camera of the current frame. It orbits the scene once over the whole
+    /// benchmark.
+    pub fn camera(&self) -> FlyCamera {
+        let t = self.frame as f32 / (WARMUP_FRAMES + self.frames) as f32;
+        let (sin, cos) = (t * std::f32::consts::TAU).sin_cos();
+        let eye = Vec3::new(ORBIT_RADIUS * sin, 2.0, ORBIT_RADIUS * cos);
+        FlyCamera::looking_at(eye, Vec3::zero())
+    }
+
+    /// Records the times of the frame that just finished.
+    pub fn record(&mut self, cpu: Duration, gpu: Option<Duration>) {
+        if self.frame >= WARMUP_FRAMES {
+            self.cpu.push(cpu);
+            self.gpu.extend(gpu);
+        }
+        self.frame += 1;
+    }
+
+    pub fn finished(&self) -> bool {
+        self.cpu.len() as u32 >= self.frames
+    }
+
+    /// Statistics of the recorded frames, as JSON.
+    pub fn report(&self) -> String {
+        format!(
+            "{{\n  \"frames\": {},\n  \"cpu_ms\": {},\n  \"gpu_ms\": {}\n}}\n",
+            self.cpu.len(),
+            statistics(&self.cpu),
+            statistics(&self.gpu),
+        )
+    }
+}
+
+fn statistics(samples: &[Duration]) -> String {
+    let mut ms: Vec<_> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+    ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    // nearest rank, the smallest sample with at least p% of them at or below it
+    let percentile = |p: f64| {
+        let rank = (p / 100.0 * ms.len() as f64).ceil() as usize;
+        ms.get(rank.max(1) - 1).copied().unwrap_or(0.0)
+    };
+    let mean = if ms.is_empty() {
+        0.0
+    } else {
+        ms.iter().sum::<f64>() / ms.len() as f64
+    };
+    format!(
+        "{{ \"mean\": {:.3}, \"p50\": {:.3}, \"p95\": {:.3}, \"p99\": {:.3}, \"max\": {:.3} }}",
+        mean,
+        percentile(50.0),
+        percentile(95.0),
+        percentile(99.0),
+        ms.last().copied().unwrap_or(0.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn stats(ms: &[u64]) -> Value {
+        let samples: Vec<_> = ms.iter().map(|&ms| Duration::from_millis(ms)).collect();
+        serde_json::from_str(&statistics(&samples)).unwrap()
+    }
+
+    fn expected(mean: f64, p50: f64, p95: f64, p99: f64, max: f64) -> Value {
+        json!({ "mean": mean, "p50": p50, "p95": p95, "p99": p99, "max": max })
+    }
+
+    #[test]
+    fn percentiles_are_nearest_rank() {
+        let mut hundred: Vec<u64> = (1..=100).collect();
+        hundred.reverse();
+        assert_eq!(stats(&hundred), expected(50.5, 50.0, 95.0, 99.0, 100.0));
+        // ranks round up, so with ten samples p95 and p99 are the largest
+        let ten = [4, 9, 1, 7, 10, 2, 6, 3, 8, 5];
+        assert_eq!(stats(&ten), expected(5.5, 5.0, 10.0, 10.0, 10.0));
+    }
+
+    #[test]
+    fn single_samples_are_every_percentile() {
+        assert_eq!(stats(&[7]), expected(7.0, 7.0, 7.0, 7.0, 7.0));
+    }
+
+    #[test]
+    fn empty_runs_are_zero() {
+        assert_eq!(stats(&[]), expected(0.0, 0.0, 0.0, 0.0, 0.0));
+        let report: Value = serde_json::from_str(&Bench::new(0).report()).unwrap();
+        assert_eq!(report["frames"], 0);
+        assert_eq!(report["gpu_ms"], expected(0.0, 0.0, 0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,286 @@
+//! Playground of blend states, with overlapping translucent quads drawn over a
+//! checkerboard.
+//!
+//! The factors and operations of the color and alpha blending are picked from
+//! a window, and the pipeline of the quads is rebuilt whenever they change.
+use crate::{draw_stats::CountedPass, shaders};
+use imgui::{im_str, ColorEdit, ComboBox, ImStr, ImString, Ui, Window};
+use wgpu::{
+    util::make_spirv, BlendDescriptor, BlendFactor, BlendOperation, Color, ColorStateDescriptor,
+    ColorWrite, Device, IndexFormat, PipelineLayout, PipelineLayoutDescriptor, PrimitiveTopology,
+    ProgrammableStageDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderModule,
+    TextureFormat, VertexStateDescriptor,
+};
+
+pub const FACTORS: [BlendFactor; 13] = [
+    BlendFactor::Zero,
+    BlendFactor::One,
+    BlendFactor::SrcColor,
+    BlendFactor::OneMinusSrcColor,
+    BlendFactor::SrcAlpha,
+    BlendFactor::OneMinusSrcAlpha,
+    BlendFactor::DstColor,
+    BlendFactor::OneMinusDstColor,
+    BlendFactor::DstAlpha,
+    BlendFactor::OneMinusDstAlpha,
+    BlendFactor::SrcAlphaSaturated,
+    BlendFactor::BlendColor,
+    BlendFactor::OneMinusBlendColor,
+];
+
+pub const OPERATIONS: [BlendOperation; 5] = [
+    BlendOperation::Add,
+    BlendOperation::Subtract,
+    BlendOperation::ReverseSubtract,
+    BlendOperation::Min,
+    BlendOperation::Max,
+];
+
+pub fn factor_name(factor: BlendFactor) -> &'static str {
+    match factor {
+        BlendFactor::Zero => "Zero",
+        BlendFactor::One => "One",
+        BlendFactor::SrcColor => "Src color",
+        BlendFactor::OneMinusSrcColor => "One minus src color",
+        BlendFactor::SrcAlpha => "Src alpha",
+        BlendFactor::OneMinusSrcAlpha => "One minus src alpha",
+        BlendFactor::DstColor => "Dst color",
+        BlendFactor::OneMinusDstColor => "One minus dst color",
+        BlendFactor::DstAlpha => "Dst alpha",
+        BlendFactor::OneMinusDstAlpha => "One minus dst alpha",
+        BlendFactor::SrcAlphaSaturated => "Src alpha saturated",
+        BlendFactor::BlendColor => "Blend color",
+        BlendFactor::OneMinusBlendColor => "One minus blend color",
+    }
+}
+
+pub fn operation_name(operation: BlendOperation) -> &'static str {
+    match operation {
+        BlendOperation::Add => "Add",
+        BlendOperation::Subtract => "Subtract",
+        BlendOperation::ReverseSubtract => "Reverse subtract",
+        BlendOperation::Min => "Min",
+        BlendOperation::Max => "Max",
+    }
+}
+
+/// Blending of the quads, the one of the blended objects of the scene to
+/// begin with.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlendState {
+    pub color: BlendDescriptor,
+    pub alpha: BlendDescriptor,
+    /// Constant of the `BlendColor` factors.
+    pub blend_color: [f32; 4],
+}
+
+impl Default for BlendState {
+    fn default() -> Self {
+        Self {
+            color: BlendDescriptor {
+                src_factor: BlendFactor::SrcAlpha,
+                dst_factor: BlendFactor::OneMinusSrcAlpha,
+                operation: BlendOperation::Add,
+            },
+            alpha: BlendDescriptor::REPLACE,
+            blend_color: [1.0; 4],
+        }
+    }
+}
+
+pub struct BlendPlayground {
+    format: TextureFormat,
+    vert_module: ShaderModule,
+    frag_module: ShaderModule,
+    layout: PipelineLayout,
+    background: RenderPipeline,
+    pipeline: RenderPipeline,
+    state: BlendState,
+}
+
+impl BlendPlayground {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::BLEND_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::BLEND_FRAG));
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Blend playground pipeline layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let state = BlendState::default();
+        let create = |label, color, alpha| {
+            create_pipeline(
+                device,
+                label,
+                &layout,
+                &vert_module,
+                &frag_module,
+                format,
+                color,
+                alpha,
+            )
+        };
+        let background = create(
+            "Blend playground background pipeline",
+            BlendDescriptor::REPLACE,
+            BlendDescriptor::REPLACE,
+        );
+        let pipeline = create(
+            "Blend playground pipeline",
+            state.color.clone(),
+            state.alpha.clone(),
+        );
+        Self {
+            format,
+            vert_module,
+            frag_module,
+            layout,
+            background,
+            pipeline,
+            state,
+        }
+    }
+
+    pub fn state(&self) -> &BlendState {
+        &self.state
+    }
+
+    /// Rebuilds the pipeline of the quads if the blending changed.
+    pub fn set_state(&mut self, device: &Device, state: BlendState) {
+        if state.color != self.state.color || state.alpha != self.state.alpha {
+            self.pipeline = create_pipeline(
+                device,
+                "Blend playground pipeline",
+                &self.layout,
+                &self.vert_module,
+                &self.frag_module,
+                self.format,
+                state.color.clone(),
+                state.alpha.clone(),
+            );
+        }
+        self.state = state;
+    }
+
+    /// Window editing the blend state.
+    pub fn ui(&mut self, ui: &Ui, device: &Device) {
+        let mut state = self.state.clone();
+        Window::new(im_str!("Blend state"))
+            .always_auto_resize(true)
+            .build(ui, || {
+                ui.text("Color");
+                descriptor_ui(ui, "color", &mut state.color);
+                ui.separator();
+                ui.text("Alpha");
+                descriptor_ui(ui, "alpha", &mut state.alpha);
+                ui.separator();
+                ColorEdit::new(im_str!("Blend color"), &mut state.blend_color).build(ui);
+                if ui.button(im_str!("Reset"), [0.0, 0.0]) {
+                    state = BlendState::default();
+                }
+            });
+        self.set_state(device, state);
+    }
+
+    /// Draws the checkerboard over the whole color target of the pass, then
+    /// the quads.
+    pub fn draw<'a>(&'a self, pass: &mut CountedPass<'a>) {
+        let [r, g, b, a] = self.state.blend_color;
+        pass.set_blend_color(Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: a as f64,
+        });
+        pass.set_pipeline(&self.background);
+        pass.draw(0..4, 0..1);
+        pass.set_pipeline(&self.pipeline);
+        pass.draw(0..4, 1..4);
+    }
+}
+
+/// Combos of the factors and operation of `descriptor`, with ids suffixed by
+/// `id` so both descriptors can be edited in the same window.
+fn descriptor_ui(ui: &Ui, id: &str, descriptor: &mut BlendDescriptor) {
+    let factor_names: Vec<ImString> = FACTORS
+        .iter()
+        .map(|&factor| ImString::new(factor_name(factor)))
+        .collect();
+    let factor_names: Vec<&ImStr> = factor_names.iter().map(|name| name.as_ref()).collect();
+    let operation_names: Vec<ImString> = OPERATIONS
+        .iter()
+        .map(|&operation| ImString::new(operation_name(operation)))
+        .collect();
+    let operation_names: Vec<&ImStr> = operation_names.iter().map(|name| name.as_ref()).collect();
+
+    let mut src = FACTORS
+        .iter()
+        .position(|&f| f == descriptor.src_factor)
+        .unwrap_or(0);
+    if ComboBox::new(&im_str!("Source##{}", id)).build_simple_string(ui, &mut src, &factor_names) {
+        descriptor.src_factor = FACTORS[src];
+    }
+    let mut dst = FACTORS
+        .iter()
+        .position(|&f| f == descriptor.dst_factor)
+        .unwrap_or(0);
+    if ComboBox::new(&im_str!("Destination##{}", id)).build_simple_string(
+        ui,
+        &mut dst,
+        &factor_names,
+    ) {
+        descriptor.dst_factor = FACTORS[dst];
+    }
+    let mut operation = OPERATIONS
+        .iter()
+        .position(|&o| o == descriptor.operation)
+        .unwrap_or(0);
+    if ComboBox::new(&im_str!("Operation##{}", id)).build_simple_string(
+        ui,
+        &mut operation,
+        &operation_names,
+    ) {
+        descriptor.operation = OPERATIONS[operation];
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_pipeline(
+    device: &Device,
+    label: &str,
+    layout: &PipelineLayout,
+    vert_module: &ShaderModule,
+    frag_module: &ShaderModule,
+    format: TextureFormat,
+    color_blend: BlendDescriptor,
+    alpha_blend: BlendDescriptor,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex_stage: ProgrammableStageDescriptor {
+            module: vert_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(ProgrammableStageDescriptor {
+            module: frag_module,
+            entry_point: "main",
+        }),
+        rasterization_state: None,
+        primitive_topology: PrimitiveTopology::TriangleStrip,
+        color_states: &[ColorStateDescriptor {
+            format,
+            color_blend,
+            alpha_blend,
+            write_mask: ColorWrite::ALL,
+        }],
+        depth_stencil_state: None,
+        vertex_state: VertexStateDescriptor {
+            index_format: IndexFormat::Uint16,
+            vertex_buffers: &[],
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
@@ -0,0 +1,306 @@
+//! Bloom over the HDR scene, added to it by the composite of `post`.
+//!
+//! What is brighter than the threshold is downsampled into a chain of levels,
+//! each half the size of the previous one, then upsampled back up the chain
+//! with every level blurred and added over the next larger one. Small, very
+//! bright spots thus glow over a wide area, without the cost of wide blurs.
+use crate::{
+    draw_stats,
+    layout::Layout,
+    memory::{self, Category, Tracked},
+    pass::PassBuilder,
+    ping_pong::PingPong,
+    post, shaders,
+};
+use imgui::{im_str, Slider, Ui};
+use wgpu::{
+    util::{make_spirv, BufferInitDescriptor},
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    BlendFactor, BlendOperation, Buffer, BufferDescriptor, BufferSize, BufferUsage, Color,
+    CommandEncoder, Device, Extent3d, FilterMode, LoadOp, Queue, RenderPipeline, Sampler,
+    SamplerDescriptor, ShaderStage, Texture, TextureDescriptor, TextureDimension, TextureUsage,
+    TextureView, TextureViewDescriptor,
+};
+
+/// Most levels of the chain, the first one being half the size of the scene.
+const MAX_LEVELS: usize = 6;
+
+gpu_struct! {
+    struct BloomUniforms {
+        threshold: f32,
+        radius: f32,
+    }
+}
+
+/// How the scene blooms, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct BloomParams {
+    pub enabled: bool,
+    /// Weight of the bloom added to the scene.
+    pub intensity: f32,
+    /// Brightness under which nothing glows, in HDR units.
+    pub threshold: f32,
+    /// Spread of the upsampling filter, in texels of each level.
+    pub radius: f32,
+}
+
+impl Default for BloomParams {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            intensity: 0.5,
+            threshold: 1.0,
+            radius: 1.0,
+        }
+    }
+}
+
+impl BloomParams {
+    pub fn ui(&mut self, ui: &Ui) {
+        ui.checkbox(im_str!("Bloom"), &mut self.enabled);
+        Slider::new(im_str!("Intensity"))
+            .range(0.0..=2.0)
+            .build(ui, &mut self.intensity);
+        Slider::new(im_str!("Threshold"))
+            .range(0.0..=4.0)
+            .build(ui, &mut self.threshold);
+        Slider::new(im_str!("Radius"))
+            .range(0.5..=4.0)
+            .build(ui, &mut self.radius);
+    }
+}
+
+/// Chain of levels bloomed from the HDR targets of the scene.
+pub struct BloomTargets {
+    levels: Vec<(Tracked<Texture>, TextureView)>,
+    /// Bind groups of the first level, reading either HDR target.
+    first: [BindGroup; 2],
+    /// Bind groups of the passes writing the rest of the levels, down and
+    /// up the chain.
+    down: Vec<BindGroup>,
+    up: Vec<BindGroup>,
+}
+
+impl BloomTargets {
+    /// The bloom, once the chain is upsampled back to the first level.
+    pub fn view(&self) -> &TextureView {
+        &self.levels[0].1
+    }
+
+    /// Weight of the first level for the bloom to add up to `params`.
+    pub fn intensity(&self, params: &BloomParams) -> f32 {
+        // every level adds up in the first one
+        params.intensity / self.levels.len() as f32
+    }
+}
+
+pub struct Bloom {
+    uniform: Tracked<Buffer>,
+    /// Same uniforms with no threshold, for the levels after the first one.
+    passthrough: Tracked<Buffer>,
+    sampler: Sampler,
+    layout: BindGroupLayout,
+    down_pipeline: RenderPipeline,
+    up_pipeline: RenderPipeline,
+}
+
+impl Bloom {
+    pub fn new(device: &Device) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let down_module = device.create_shader_module(make_spirv(shaders::BLOOM_DOWN_FRAG));
+        let up_module = device.create_shader_module(make_spirv(shaders::BLOOM_UP_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Bloom uniforms"),
+                size: BloomUniforms::STD140.size as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let passthrough = memory::create_buffer_init(
+            device,
+            Category::Uniforms,
+            &BufferInitDescriptor {
+                label: Some("Bloom passthrough uniforms"),
+                contents: &BloomUniforms {
+                    threshold: 0.0,
+                    radius: 1.0,
+                }
+                .std140_bytes(),
+                usage: BufferUsage::UNIFORM,
+            },
+        );
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Bloom sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Bloom bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(BloomUniforms::STD140.size as _),
+                    },
+                    count: None,
+                },
+                post::texture_entry(1),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+
+        let down_pipeline = post::fullscreen_pipeline(
+            device,
+            "Bloom downsample pipeline",
+            &[&layout],
+            &vert_module,
+            &down_module,
+            post::HDR_FORMAT,
+            BlendDescriptor::REPLACE,
+        );
+        // added over what was downsampled into the level
+        let up_pipeline = post::fullscreen_pipeline(
+            device,
+            "Bloom upsample pipeline",
+            &[&layout],
+            &vert_module,
+            &up_module,
+            post::HDR_FORMAT,
+            BlendDescriptor {
+                src_factor: BlendFactor::One,
+                dst_factor: BlendFactor::One,
+                operation: BlendOperation::Add,
+            },
+        );
+
+        Self {
+            uniform,
+            passthrough,
+            sampler,
+            layout,
+            down_pipeline,
+            up_pipeline,
+        }
+    }
+
+    /// Levels for the scene drawn into `hdr`.
+    pub fn targets(&self, device: &Device, hdr: &PingPong) -> BloomTargets {
+        let (width, height) = hdr.size();
+        let mut levels = Vec::new();
+        while levels.len() < MAX_LEVELS && width.min(height) >> (levels.len() + 1) > 1 {
+            let shift = levels.len() + 1;
+            let texture = memory::create_texture(
+                device,
+                Category::RenderTargets,
+                &TextureDescriptor {
+                    label: Some(&format!("Bloom level {}", levels.len())),
+                    size: Extent3d {
+                        width: width >> shift,
+                        height: height >> shift,
+                        depth: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: post::HDR_FORMAT,
+                    usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::SAMPLED,
+                },
+            );
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            levels.push((texture, view));
+        }
+
+        let bind_group = |uniform: &Buffer, source: &TextureView| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Bloom bind group"),
+                layout: &self.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(uniform.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            })
+        };
+        // only the first level is thresholded, the rest downsample it further
+        let first = hdr.directions(|source, _| bind_group(&self.uniform, source));
+        let down = levels[..levels.len() - 1]
+            .iter()
+            .map(|(_, view)| bind_group(&self.passthrough, view))
+            .collect();
+        let up = levels[1..]
+            .iter()
+            .map(|(_, view)| bind_group(&self.uniform, view))
+            .collect();
+
+        BloomTargets {
+            levels,
+            first,
+            down,
+            up,
+        }
+    }
+
+    /// Records the bloom of the scene last written to `hdr` in `targets`.
+    pub fn run(
+        &self,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        params: &BloomParams,
+        targets: &BloomTargets,
+        hdr: &PingPong,
+    ) {
+        let uniforms = BloomUniforms {
+            threshold: params.threshold,
+            radius: params.radius,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, &uniforms.std140_bytes());
+
+        let levels = &targets.levels;
+        let sources = std::iter::once(hdr.current(&targets.first)).chain(&targets.down);
+        for ((_, view), bind_group) in levels.iter().zip(sources) {
+            let mut pass = PassBuilder::new()
+                .color(view, post::HDR_FORMAT, LoadOp::Clear(Color::BLACK), true)
+                .begin(encoder);
+            pass.push_debug_group("Bloom downsample");
+            pass.set_pipeline(&self.down_pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+            pass.pop_debug_group();
+        }
+        let smaller = levels[..levels.len() - 1].iter().zip(&targets.up);
+        for ((_, view), bind_group) in smaller.rev() {
+            let mut pass = PassBuilder::new()
+                .color(view, post::HDR_FORMAT, LoadOp::Load, true)
+                .begin(encoder);
+            pass.push_debug_group("Bloom upsample");
+            pass.set_pipeline(&self.up_pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+            pass.pop_debug_group();
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Camera bookmarks.
+//!
+//! Named camera poses, recalled with a smooth transition from wherever the
+//! camera is. Played back, the camera goes from one bookmark to the next in a
+//! loop, stopping at each one for a while.
+use crate::camera::CameraPose;
+use glam::Vec3;
+use imgui::{im_str, ImString, Slider, Ui};
+
+#[derive(Clone)]
+pub struct Bookmark {
+    pub name: String,
+    pub pose: CameraPose,
+}
+
+impl Bookmark {
+    /// Bookmark as saved in the settings file, the pose followed by the name.
+    pub fn to_line(&self) -> String {
+        let pose = &self.pose;
+        format!(
+            "{} {} {} {} {} {} {}",
+            pose.position.x,
+            pose.position.y,
+            pose.position.z,
+            pose.yaw,
+            pose.pitch,
+            pose.fov_y,
+            self.name
+        )
+    }
+
+    /// Parses a line of [`Bookmark::to_line`].
+    pub fn parse_line(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(7, ' ');
+        let mut floats = [0.0; 6];
+        for float in &mut floats {
+            *float = parts.next()?.parse().ok()?;
+        }
+        let [x, y, z, yaw, pitch, fov_y] = floats;
+        Some(Self {
+            name: parts.next().unwrap_or_default().to_string(),
+            pose: CameraPose {
+                position: Vec3::new(x, y, z),
+                yaw,
+                pitch,
+                fov_y,
+            },
+        })
+    }
+}
+
+struct Transition {
+    from: CameraPose,
+    /// Index of the bookmark the camera is moving to.
+    to: usize,
+    elapsed: f32,
+}
+
+pub struct Bookmarks {
+    pub list: Vec<Bookmark>,
+    /// Seconds going from one pose to another.
+    pub transition_time: f32,
+    /// Seconds stopped at each bookmark while playing back.
+    pub hold_time: f32,
+    transition: Option<Transition>,
+    /// Bookmark gone to last.
+    last: Option<usize>,
+    playing: bool,
+}
+
+impl Bookmarks {
+    pub fn new(list: Vec<Bookmark>) -> Self {
+        Self {
+            list,
+            transition_time: 1.5,
+            hold_time: 1.0,
+            transition: None,
+            last: None,
+            playing: false,
+        }
+    }
+
+    /// Starts moving from `from` to the bookmark at `index`.
+    pub fn go_to(&mut self, index: usize, from: CameraPose) {
+        if index < self.list.len() {
+            self.transition = Some(Transition {
+                from,
+                to: index,
+                elapsed: 0.0,
+            });
+            self.last = Some(index);
+        }
+    }
+
+    /// Goes to the bookmark after the last one gone to, or the first one.
+    pub fn go_to_next(&mut self, from: CameraPose) {
+        let next = self.last.map_or(0, |last| last + 1);
+        self.go_to(next % self.list.len().max(1), from);
+    }
+
+    /// Plays back every bookmark in a loop, starting with the first one.
+    pub fn play(&mut self, from: CameraPose) {
+        if !self.list.is_empty() {
+            self.playing = true;
+            self.go_to(0, from);
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Stops any transition and the playback, leaving the camera where it is.
+    pub fn stop(&mut self) {
+        self.transition = None;
+        self.playing = false;
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.list.remove(index);
+        self.last = None;
+        self.stop();
+    }
+
+    /// Advances the transition by `dt` seconds, returning the pose the camera
+    /// should take if it's moving.
+    pub fn update(&mut self, dt: f32) -> Option<CameraPose> {
+        let transition = self.transition.as_mut()?;
+        transition.elapsed += dt;
+        let to = self.list[transition.to].pose;
+        let t = (transition.elapsed / self.transition_time.max(f32::EPSILON)).min(1.0);
+        // eased in and out
+        let pose = transition.from.lerp(&to, t * t * (3.0 - 2.0 * t));
+
+        if !self.playing {
+            if t >= 1.0 {
+                self.transition = None;
+            }
+        } else if transition.elapsed >= self.transition_time + self.hold_time {
+            let next = (transition.to + 1) % self.list.len();
+            *transition = Transition {
+                from: to,
+                to: next,
+                elapsed: 0.0,
+            };
+            self.last = Some(next);
+        }
+        Some(pose)
+    }
+    /// Controls adding bookmarks at the current `pose`, named after `name`,
+    /// going to them and playing them back. `key` is the one saving a
+    /// bookmark.
+    pub fn ui(&mut self, ui: &Ui, name: &mut ImString, pose: CameraPose, key: &str) {
+        ui.input_text(im_str!("##Bookmark name"), name).build();
+        ui.same_line(0.0);
+        if ui.button(&im_str!("Add bookmark ({})", key), [0.0, 0.0]) {
+            let bookmark = match name.to_str() {
+                "" => format!("Bookmark {}", self.list.len() + 1),
+                name => name.to_string(),
+            };
+            self.list.push(Bookmark {
+                name: bookmark,
+                pose,
+            });
+            name.clear();
+        }
+        let mut go_to = None;
+        let mut remove = None;
+        for (i, bookmark) in self.list.iter_mut().enumerate() {
+            if ui.small_button(&im_str!("Go##{}", i)) {
+                go_to = Some(i);
+            }
+            ui.same_line(0.0);
+            if ui.small_button(&im_str!("Update##{}", i)) {
+                bookmark.pose = pose;
+            }
+            ui.same_line(0.0);
+            if ui.small_button(&im_str!("Remove##{}", i)) {
+                remove = Some(i);
+            }
+            ui.same_line(0.0);
+            ui.text(&bookmark.name);
+        }
+        if let Some(i) = go_to {
+            self.go_to(i, pose);
+        }
+        if let Some(i) = remove {
+            self.remove(i);
+        }
+        ui.separator();
+        Slider::new(im_str!("Transition seconds"))
+            .range(0.1..=5.0)
+            .build(ui, &mut self.transition_time);
+        Slider::new(im_str!("Hold seconds"))
+            .range(0.0..=5.0)
+            .build(ui, &mut self.hold_time);
+        if self.is_playing() {
+            if ui.button(im_str!("Stop"), [0.0, 0.0]) {
+                self.stop();
+            }
+        } else if ui.button(im_str!("Play in a loop"), [0.0, 0.0]) {
+            self.play(pose);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Fly camera.
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// Radians rotated per pixel of mouse motion.
+const SENSITIVITY: f32 = 0.003;
+
+/// Keeps the camera from flipping over when looking straight up or down.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+/// Position, orientation and field of view of a camera.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CameraPose {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Vertical field of view, in radians.
+    pub fov_y: f32,
+}
+
+impl CameraPose {
+    /// Interpolates towards `other`, turning the shortest way around.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let tau = std::f32::consts::TAU;
+        let yaw =
+            (other.yaw - self.yaw + std::f32::consts::PI).rem_euclid(tau) - std::f32::consts::PI;
+        Self {
+            position: self.position.lerp(other.position, t),
+            yaw: self.yaw + yaw * t,
+            pitch: self.pitch + (other.pitch - self.pitch) * t,
+            fov_y: self.fov_y + (other.fov_y - self.fov_y) * t,
+        }
+    }
+}
+
+/// First person camera, rotated with the mouse and moved with the keyboard.
+#[derive(Clone)]
+pub struct FlyCamera {
+    pub position: Vec3,
+    /// Rotation around the world up axis, zero looking down `-z`.
+    yaw: f32,
+    /// Rotation above the horizon.
+    pitch: f32,
+}
+
+impl FlyCamera {
+    /// Creates a camera at `position`, looking in the direction of the `yaw`
+    /// and `pitch` angles (see [`FlyCamera::angles`]).
+    pub fn new(position: Vec3, yaw: f32, pitch: f32) -> Self {
+        Self {
+            position,
+            yaw,
+            pitch: pitch.clamp(-MAX_PITCH, MAX_PITCH),
+        }
+    }
+
+    pub fn looking_at(position: Vec3, target: Vec3) -> Self {
+        let direction = (target - position).normalize();
+        Self {
+            position,
+            yaw: direction.x.atan2(-direction.z),
+            pitch: direction.y.asin(),
+        }
+    }
+
+    /// Rotation around the world up axis (zero looking down `-z`) and above
+    /// the horizon, in radians.
+    pub fn angles(&self) -> (f32, f32) {
+        (self.yaw, self.pitch)
+    }
+
+    /// Pose of the camera when rendered with a field of view of `fov_y`.
+    pub fn pose(&self, fov_y: f32) -> CameraPose {
+        CameraPose {
+            position: self.position,
+            yaw: self.yaw,
+            pitch: self.pitch,
+            fov_y,
+        }
+    }
+
+    /// Moves and rotates the camera to `pose`, the field of view is up to
+    /// the caller.
+    pub fn set_pose(&mut self, pose: &CameraPose) {
+        *self = Self::new(pose.position, pose.yaw, pose.pitch);
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        Vec3::new(sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch)
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(
+            self.position,
+            self.position + self.forward(),
+            Vec3::unit_y(),
+        )
+    }
+
+    /// Rotates the camera by a relative mouse motion, in pixels.
+    pub fn look(&mut self, dx: i32, dy: i32) {
+        self.yaw += dx as f32 * SENSITIVITY;
+        self.pitch = (self.pitch - dy as f32 * SENSITIVITY).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Moves the camera relative to where it's looking at. `direction` is
+    /// right in `x`, world up in `y` and forward in `z`.
+    pub fn fly(&mut self, direction: Vec3, distance: f32) {
+        let forward = self.forward();
+        let right = forward.cross(Vec3::unit_y()).normalize();
+        let offset = right * direction.x + Vec3::unit_y() * direction.y + forward * direction.z;
+        if offset.length_squared() > 0.0 {
+            self.position += offset.normalize() * distance;
+        }
+    }
+}
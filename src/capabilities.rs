@@ -0,0 +1,73 @@
+//! Optional features of the device, requested only if the adapter has them.
+//!
+//! What was granted is kept in [`Capabilities`], which everything that has a
+//! fallback checks instead of the features of the device. The default, with
+//! nothing granted, is what every adapter can do.
+use log::info;
+use wgpu::{Adapter, Features, Limits};
+
+/// Bytes of push constants asked for, the least Vulkan guarantees.
+const PUSH_CONSTANT_SIZE: u32 = 128;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Capabilities {
+    /// Bytes of push constants, 0 without them.
+    pub push_constant_size: u32,
+    /// GPU timings, without which only CPU scopes are profiled.
+    pub timestamp_queries: bool,
+    /// Sampling BC compressed textures, which are otherwise decompressed on
+    /// the CPU.
+    pub bc_textures: bool,
+    /// Line and point polygon modes, without which meshes are only filled.
+    pub non_fill_polygon_mode: bool,
+}
+
+impl Capabilities {
+    /// What `adapter` has of what's asked for.
+    pub fn negotiate(adapter: &Adapter) -> Self {
+        let features = adapter.features();
+        let push_constant_size = if features.contains(Features::PUSH_CONSTANTS) {
+            adapter
+                .limits()
+                .max_push_constant_size
+                .min(PUSH_CONSTANT_SIZE)
+        } else {
+            0
+        };
+        let capabilities = Self {
+            push_constant_size,
+            // neither is exposed by wgpu yet
+            timestamp_queries: false,
+            non_fill_polygon_mode: false,
+            bc_textures: features.contains(Features::TEXTURE_COMPRESSION_BC),
+        };
+        info!("Capabilities: {:?}", capabilities);
+        capabilities
+    }
+
+    /// Features to request the device with.
+    pub fn features(&self) -> Features {
+        let mut features = Features::empty();
+        features.set(Features::PUSH_CONSTANTS, self.push_constant_size > 0);
+        features.set(Features::TEXTURE_COMPRESSION_BC, self.bc_textures);
+        features
+    }
+
+    /// Limits to request the device with.
+    pub fn limits(&self) -> Limits {
+        Limits {
+            max_push_constant_size: self.push_constant_size,
+            ..Limits::default()
+        }
+    }
+
+    /// Name and whether it was granted of every capability, for the UI.
+    pub fn list(&self) -> [(&'static str, bool); 4] {
+        [
+            ("Push constants", self.push_constant_size > 0),
+            ("Timestamp queries", self.timestamp_queries),
+            ("BC textures", self.bc_textures),
+            ("Non-fill polygon mode", self.non_fill_polygon_mode),
+        ]
+    }
+}
@@ -0,0 +1,202 @@
+//! Clustered culling of the unshadowed lights.
+//!
+//! The view frustum is split into a grid of clusters (tiles of the screen,
+//! sliced exponentially in depth) and a compute pass lists the lights whose
+//! range reaches each of them, so the lit shader only loops over the lights
+//! of the cluster of its fragment. Fragments outside of the grid, drawn by
+//! another camera like the second viewport's, loop over every light.
+use crate::{
+    draw_stats,
+    layout::Layout,
+    memory::{self, Category, Tracked},
+    shaders,
+};
+use glam::Mat4;
+use wgpu::{
+    util::make_spirv, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferDescriptor, BufferSize,
+    BufferUsage, CommandEncoder, ComputePipeline, ComputePipelineDescriptor, Device,
+    PipelineLayoutDescriptor, ProgrammableStageDescriptor, Queue, ShaderStage,
+};
+
+/// Tiles of the screen and depth slices, must match `cluster_lights.comp`
+/// and `shader.frag`.
+pub const CLUSTERS: [u32; 3] = [16, 9, 24];
+const CLUSTER_COUNT: u32 = CLUSTERS[0] * CLUSTERS[1] * CLUSTERS[2];
+
+/// Lights listed per cluster, any more that reach it are left out.
+pub const MAX_CLUSTER_LIGHTS: u32 = 64;
+
+/// Must match the `local_size_x` of `cluster_lights.comp`.
+const WORKGROUP_SIZE: u32 = 64;
+
+gpu_struct! {
+    /// Uniform block of the culling pass, also read by the lit shader to
+    /// find the cluster of a fragment.
+    struct ClusterUniforms {
+        view: Mat4,
+        projection: Mat4,
+        inverse_projection: Mat4,
+        near: f32,
+        far: f32,
+        light_count: u32,
+        enabled: bool,
+    }
+}
+
+pub struct Clusters {
+    /// Every light is shaded by every fragment if not.
+    pub enabled: bool,
+    pub uniform: Tracked<Buffer>,
+    /// Number of lights of every cluster.
+    pub counts: Tracked<Buffer>,
+    /// Indices of the lights of every cluster, `MAX_CLUSTER_LIGHTS` each.
+    pub indices: Tracked<Buffer>,
+    bind_group: BindGroup,
+    pipeline: ComputePipeline,
+}
+
+impl Clusters {
+    /// Creates the buffers to cull the lights of the storage buffer `lights`.
+    pub fn new(device: &Device, lights: &Buffer) -> Self {
+        let module = device.create_shader_module(make_spirv(shaders::CLUSTER_LIGHTS_COMP));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Cluster uniforms"),
+                size: ClusterUniforms::STD140.size as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let counts = memory::create_buffer(
+            device,
+            Category::Storage,
+            &BufferDescriptor {
+                label: Some("Cluster light counts"),
+                size: (CLUSTER_COUNT * 4) as _,
+                usage: BufferUsage::STORAGE,
+                mapped_at_creation: false,
+            },
+        );
+        let indices = memory::create_buffer(
+            device,
+            Category::Storage,
+            &BufferDescriptor {
+                label: Some("Cluster light indices"),
+                size: (CLUSTER_COUNT * MAX_CLUSTER_LIGHTS * 4) as _,
+                usage: BufferUsage::STORAGE,
+                mapped_at_creation: false,
+            },
+        );
+
+        let storage = |binding, readonly| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStage::COMPUTE,
+            ty: BindingType::StorageBuffer {
+                dynamic: false,
+                min_binding_size: None,
+                readonly,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Cluster culling bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(ClusterUniforms::STD140.size as _),
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, false),
+                storage(3, false),
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Cluster culling bind group"),
+            layout: &layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(uniform.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Buffer(lights.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Buffer(counts.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::Buffer(indices.slice(..)),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Cluster culling pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Cluster culling pipeline"),
+            layout: Some(&pipeline_layout),
+            compute_stage: ProgrammableStageDescriptor {
+                module: &module,
+                entry_point: "main",
+            },
+        });
+
+        Self {
+            enabled: true,
+            uniform,
+            counts,
+            indices,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    /// Uploads the camera the clusters are fit to, with depth from `near` to
+    /// `far`, and the number of lights.
+    pub fn update(
+        &self,
+        queue: &Queue,
+        view: Mat4,
+        projection: Mat4,
+        near: f32,
+        far: f32,
+        light_count: u32,
+    ) {
+        let uniforms = ClusterUniforms {
+            view,
+            projection,
+            inverse_projection: projection.inverse(),
+            near,
+            far,
+            light_count,
+            enabled: self.enabled,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, &uniforms.std140_bytes());
+    }
+
+    /// Records the culling pass, before the lit shader reads the lists.
+    pub fn cull(&self, encoder: &mut CommandEncoder) {
+        if !self.enabled {
+            return;
+        }
+        let mut pass = encoder.begin_compute_pass();
+        pass.push_debug_group("Light clusters");
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch(CLUSTER_COUNT.div_ceil(WORKGROUP_SIZE), 1, 1);
+        pass.pop_debug_group();
+    }
+}
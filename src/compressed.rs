@@ -0,0 +1,444 @@
+//! Block compressed textures (BC1 to BC7) in DDS and KTX2 containers.
+//!
+//! The blocks are uploaded as they are when the device has
+//! `TEXTURE_COMPRESSION_BC`. Otherwise BC1 to BC5 can be decompressed on the
+//! CPU. There's no CPU decoder for BC6H and BC7, their many block modes aren't
+//! worth it for a fallback, so loading them fails with `NoDecoder` on devices
+//! without the feature.
+use std::{fmt, io, path::Path};
+use wgpu::{Extent3d, Origin3d, Queue, Texture, TextureCopyView, TextureDataLayout, TextureFormat};
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n',
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BcFormat {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+    Bc6h,
+    Bc7,
+}
+
+impl BcFormat {
+    fn block_bytes(self) -> u32 {
+        match self {
+            BcFormat::Bc1 | BcFormat::Bc4 => 8,
+            _ => 16,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CompressedError {
+    Io(io::Error),
+    Invalid(&'static str),
+    /// DXGI format (DDS) or Vulkan format (KTX2) that isn't block compressed.
+    UnsupportedFormat(u32),
+    NoDecoder(BcFormat),
+}
+
+impl fmt::Display for CompressedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompressedError::Io(err) => write!(f, "{}", err),
+            CompressedError::Invalid(reason) => write!(f, "{}", reason),
+            CompressedError::UnsupportedFormat(format) => {
+                write!(f, "Unsupported texture format {}", format)
+            }
+            CompressedError::NoDecoder(format) => write!(
+                f,
+                "There's no CPU decoder for {:?}, it needs TEXTURE_COMPRESSION_BC",
+                format
+            ),
+        }
+    }
+}
+
+pub struct CompressedImage {
+    pub width: u32,
+    pub height: u32,
+    pub format: BcFormat,
+    pub srgb: bool,
+    /// Blocks of every mip level, the largest one first.
+    levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    pub fn texture_format(&self) -> TextureFormat {
+        match (self.format, self.srgb) {
+            (BcFormat::Bc1, false) => TextureFormat::Bc1RgbaUnorm,
+            (BcFormat::Bc1, true) => TextureFormat::Bc1RgbaUnormSrgb,
+            (BcFormat::Bc2, false) => TextureFormat::Bc2RgbaUnorm,
+            (BcFormat::Bc2, true) => TextureFormat::Bc2RgbaUnormSrgb,
+            (BcFormat::Bc3, false) => TextureFormat::Bc3RgbaUnorm,
+            (BcFormat::Bc3, true) => TextureFormat::Bc3RgbaUnormSrgb,
+            (BcFormat::Bc4, _) => TextureFormat::Bc4RUnorm,
+            (BcFormat::Bc5, _) => TextureFormat::Bc5RgUnorm,
+            (BcFormat::Bc6h, _) => TextureFormat::Bc6hRgbUfloat,
+            (BcFormat::Bc7, false) => TextureFormat::Bc7RgbaUnorm,
+            (BcFormat::Bc7, true) => TextureFormat::Bc7RgbaUnormSrgb,
+        }
+    }
+
+    fn level_size(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// Number of mip levels that can be uploaded. Copies have to be made of
+    /// whole blocks, so it stops at the first level that isn't a multiple of
+    /// 4 texels wide and high.
+    pub fn gpu_levels(&self) -> u32 {
+        (0..self.levels.len())
+            .take_while(|&level| {
+                let (width, height) = self.level_size(level);
+                width % 4 == 0 && height % 4 == 0
+            })
+            .count() as _
+    }
+
+    /// Size of the uploaded levels, in bytes.
+    pub fn gpu_size(&self) -> u64 {
+        let levels = self.gpu_levels() as usize;
+        self.levels[..levels].iter().map(|l| l.len() as u64).sum()
+    }
+
+    /// Copies the first `gpu_levels` levels into `texture`, which must have the
+    /// format of `texture_format`.
+    pub fn upload(&self, queue: &Queue, texture: &Texture) {
+        for level in 0..self.gpu_levels() as usize {
+            let (width, height) = self.level_size(level);
+            queue.write_texture(
+                TextureCopyView {
+                    texture,
+                    mip_level: level as _,
+                    origin: Origin3d::ZERO,
+                },
+                &self.levels[level],
+                TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: width / 4 * self.format.block_bytes(),
+                    rows_per_image: height,
+                },
+                Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+            );
+        }
+    }
+
+    /// Decompresses the largest level into RGBA8 pixels.
+    pub fn decompress(&self) -> Result<Vec<u8>, CompressedError> {
+        let decode: fn(&[u8]) -> [[u8; 4]; 16] = match self.format {
+            BcFormat::Bc1 => |block| color_block(block, true),
+            BcFormat::Bc2 => bc2,
+            BcFormat::Bc3 => bc3,
+            BcFormat::Bc4 => bc4,
+            BcFormat::Bc5 => bc5,
+            format => return Err(CompressedError::NoDecoder(format)),
+        };
+        let (width, height) = (self.width as usize, self.height as usize);
+        let blocks_wide = width.div_ceil(4);
+        let mut pixels = vec![0; 4 * width * height];
+        let blocks = self.levels[0].chunks(self.format.block_bytes() as _);
+        for (i, block) in blocks.enumerate() {
+            let texels = decode(block);
+            for (j, texel) in texels.iter().enumerate() {
+                // blocks at the edges may be partially outside of the image
+                let x = i % blocks_wide * 4 + j % 4;
+                let y = i / blocks_wide * 4 + j / 4;
+                if x < width && y < height {
+                    let offset = 4 * (y * width + x);
+                    pixels[offset..offset + 4].copy_from_slice(texel);
+                }
+            }
+        }
+        Ok(pixels)
+    }
+}
+
+/// Loads a DDS or KTX2 file, telling them apart by their contents.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<CompressedImage, CompressedError> {
+    let bytes = std::fs::read(path).map_err(CompressedError::Io)?;
+    if bytes.starts_with(b"DDS ") {
+        parse_dds(&bytes)
+    } else if bytes.starts_with(&KTX2_IDENTIFIER) {
+        parse_ktx2(&bytes)
+    } else {
+        Err(CompressedError::Invalid("Not a DDS or KTX2 file"))
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, CompressedError> {
+    let bytes = bytes
+        .get(offset..offset + 4)
+        .ok_or(CompressedError::Invalid("Truncated header"))?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Result<u64, CompressedError> {
+    Ok(u32_at(bytes, offset)? as u64 | (u32_at(bytes, offset + 4)? as u64) << 32)
+}
+
+fn level(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], CompressedError> {
+    offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or(CompressedError::Invalid("Truncated mip level"))
+}
+
+/// Bytes of the blocks of mip level `i` of a `width`x`height` image, if they
+/// fit in memory at all.
+fn level_len(width: u32, height: u32, i: u32, format: BcFormat) -> Result<usize, CompressedError> {
+    let blocks_wide = width.checked_shr(i).unwrap_or(0).max(1).div_ceil(4) as usize;
+    let blocks_high = height.checked_shr(i).unwrap_or(0).max(1).div_ceil(4) as usize;
+    blocks_wide
+        .checked_mul(blocks_high)
+        .and_then(|blocks| blocks.checked_mul(format.block_bytes() as _))
+        .ok_or(CompressedError::Invalid("Mip level too large"))
+}
+
+fn parse_dds(bytes: &[u8]) -> Result<CompressedImage, CompressedError> {
+    let height = u32_at(bytes, 12)?;
+    let width = u32_at(bytes, 16)?;
+    let mip_count = u32_at(bytes, 28)?.max(1);
+    // legacy four character codes, or the extended header with a DXGI format
+    let four_cc = bytes
+        .get(84..88)
+        .ok_or(CompressedError::Invalid("Truncated header"))?;
+    let (format, srgb, mut offset) = match four_cc {
+        b"DXT1" => (BcFormat::Bc1, false, 128),
+        b"DXT3" => (BcFormat::Bc2, false, 128),
+        b"DXT5" => (BcFormat::Bc3, false, 128),
+        b"ATI1" | b"BC4U" => (BcFormat::Bc4, false, 128),
+        b"ATI2" | b"BC5U" => (BcFormat::Bc5, false, 128),
+        b"DX10" => {
+            let (format, srgb) = match u32_at(bytes, 128)? {
+                71 => (BcFormat::Bc1, false),
+                72 => (BcFormat::Bc1, true),
+                74 => (BcFormat::Bc2, false),
+                75 => (BcFormat::Bc2, true),
+                77 => (BcFormat::Bc3, false),
+                78 => (BcFormat::Bc3, true),
+                80 => (BcFormat::Bc4, false),
+                83 => (BcFormat::Bc5, false),
+                95 => (BcFormat::Bc6h, false),
+                98 => (BcFormat::Bc7, false),
+                99 => (BcFormat::Bc7, true),
+                other => return Err(CompressedError::UnsupportedFormat(other)),
+            };
+            (format, srgb, 148)
+        }
+        _ => return Err(CompressedError::UnsupportedFormat(u32_at(bytes, 84)?)),
+    };
+
+    let mut levels = Vec::new();
+    for i in 0..mip_count {
+        let len = level_len(width, height, i, format)?;
+        levels.push(level(bytes, offset, len)?.to_vec());
+        offset += len;
+    }
+    Ok(CompressedImage {
+        width,
+        height,
+        format,
+        srgb,
+        levels,
+    })
+}
+
+fn parse_ktx2(bytes: &[u8]) -> Result<CompressedImage, CompressedError> {
+    let (format, srgb) = match u32_at(bytes, 12)? {
+        131 | 133 => (BcFormat::Bc1, false),
+        132 | 134 => (BcFormat::Bc1, true),
+        135 => (BcFormat::Bc2, false),
+        136 => (BcFormat::Bc2, true),
+        137 => (BcFormat::Bc3, false),
+        138 => (BcFormat::Bc3, true),
+        139 => (BcFormat::Bc4, false),
+        141 => (BcFormat::Bc5, false),
+        143 => (BcFormat::Bc6h, false),
+        145 => (BcFormat::Bc7, false),
+        146 => (BcFormat::Bc7, true),
+        other => return Err(CompressedError::UnsupportedFormat(other)),
+    };
+    let width = u32_at(bytes, 20)?;
+    let height = u32_at(bytes, 24)?;
+    if u32_at(bytes, 28)? > 0 || u32_at(bytes, 32)? > 1 || u32_at(bytes, 36)? != 1 {
+        return Err(CompressedError::Invalid(
+            "Only 2D KTX2 textures are supported",
+        ));
+    }
+    let level_count = u32_at(bytes, 40)?.max(1);
+    if u32_at(bytes, 44)? != 0 {
+        return Err(CompressedError::Invalid(
+            "Supercompressed KTX2 files aren't supported",
+        ));
+    }
+
+    // the level index follows the header, the largest level first
+    let mut levels = Vec::new();
+    for i in 0..level_count as usize {
+        let offset = u64_at(bytes, 80 + 24 * i)? as usize;
+        let len = u64_at(bytes, 88 + 24 * i)? as usize;
+        // decompressing and uploading expect whole levels of blocks
+        if len != level_len(width, height, i as _, format)? {
+            return Err(CompressedError::Invalid("Mip level of the wrong size"));
+        }
+        levels.push(level(bytes, offset, len)?.to_vec());
+    }
+    Ok(CompressedImage {
+        width,
+        height,
+        format,
+        srgb,
+        levels,
+    })
+}
+
+fn rgb565(color: u16) -> [u32; 3] {
+    let (r, g, b) = (color >> 11, (color >> 5) & 63, color & 31);
+    [
+        r as u32 * 255 / 31,
+        g as u32 * 255 / 63,
+        b as u32 * 255 / 31,
+    ]
+}
+
+/// BC1 block, also the color of BC2 and BC3 blocks, where `punch_through`
+/// alpha isn't used.
+fn color_block(block: &[u8], punch_through: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |wa: u32, wb: u32| {
+        let channel = |i: usize| ((a[i] * wa + b[i] * wb) / (wa + wb)) as u8;
+        [channel(0), channel(1), channel(2), 255]
+    };
+    let palette = if !punch_through || c0 > c1 {
+        [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)]
+    } else {
+        [mix(1, 0), mix(0, 1), mix(1, 1), [0, 0, 0, 0]]
+    };
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[(indices >> (2 * i) & 3) as usize];
+    }
+    texels
+}
+
+/// Single channel block of BC3 alpha, and BC4 and BC5 channels.
+fn channel_block(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut indices = 0u64;
+    for (i, &byte) in block[2..8].iter().enumerate() {
+        indices |= (byte as u64) << (8 * i);
+    }
+    let mix = |w0: u32, w1: u32| (a0 * w0 + a1 * w1) / (w0 + w1);
+    let palette = if a0 > a1 {
+        [
+            a0,
+            a1,
+            mix(6, 1),
+            mix(5, 2),
+            mix(4, 3),
+            mix(3, 4),
+            mix(2, 5),
+            mix(1, 6),
+        ]
+    } else {
+        [a0, a1, mix(4, 1), mix(3, 2), mix(2, 3), mix(1, 4), 0, 255]
+    };
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[(indices >> (3 * i) & 7) as usize] as u8;
+    }
+    values
+}
+
+fn bc2(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = color_block(&block[8..], false);
+    // explicit 4 bit alpha
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = (block[i / 2] >> (4 * (i % 2)) & 15) * 17;
+    }
+    texels
+}
+
+fn bc3(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = color_block(&block[8..], false);
+    for (texel, alpha) in texels.iter_mut().zip(&channel_block(&block[..8])) {
+        texel[3] = *alpha;
+    }
+    texels
+}
+
+fn bc4(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = [[0, 0, 0, 255]; 16];
+    for (texel, r) in texels.iter_mut().zip(&channel_block(block)) {
+        texel[0] = *r;
+    }
+    texels
+}
+
+fn bc5(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = bc4(&block[..8]);
+    for (texel, g) in texels.iter_mut().zip(&channel_block(&block[8..])) {
+        texel[1] = *g;
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_dds, parse_ktx2, CompressedError, KTX2_IDENTIFIER};
+
+    /// Header and level index of an 8x8 BC1 KTX2 file with one level of `len`
+    /// bytes, followed by `data`.
+    fn ktx2(len: u64, data: &[u8]) -> Vec<u8> {
+        let mut bytes = KTX2_IDENTIFIER.to_vec();
+        for value in &[131, 1, 8, 8, 0, 0, 1, 1, 0] {
+            bytes.extend_from_slice(&u32::to_le_bytes(*value));
+        }
+        bytes.resize(80, 0);
+        bytes.extend_from_slice(&u64::to_le_bytes(104));
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn ktx2_levels_are_whole() {
+        // four blocks of 8 bytes
+        let image = parse_ktx2(&ktx2(32, &[0; 32])).unwrap();
+        assert_eq!(image.levels[0].len(), 32);
+        assert!(matches!(
+            parse_ktx2(&ktx2(24, &[0; 32])),
+            Err(CompressedError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse_ktx2(&ktx2(u64::MAX, &[0; 32])),
+            Err(CompressedError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn huge_dds_is_invalid() {
+        let mut bytes = b"DDS ".to_vec();
+        bytes.resize(128, 0);
+        bytes[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        bytes[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        bytes[84..88].copy_from_slice(b"DXT1");
+        assert!(matches!(
+            parse_dds(&bytes),
+            Err(CompressedError::Invalid(_))
+        ));
+    }
+}
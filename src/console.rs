@@ -0,0 +1,154 @@
+//! Log console.
+//!
+//! Log records are still written to stderr by `env_logger`, but they are also
+//! captured into a ring buffer so they can be browsed from an imgui window.
+use imgui::{im_str, ChildWindow, ComboBox, ImString, Ui, Window};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+/// Number of records kept by the console. Older ones are discarded.
+const CAPACITY: usize = 1024;
+
+/// Records of this crate up to this level are always captured, even if
+/// `env_logger` filters them out of stderr.
+const CAPTURE_LEVEL: LevelFilter = LevelFilter::Info;
+
+const LEVELS: [LevelFilter; 5] = [
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+struct Entry {
+    level: Level,
+    target: String,
+    message: String,
+}
+
+type Entries = Arc<Mutex<VecDeque<Entry>>>;
+
+fn captured(metadata: &Metadata) -> bool {
+    metadata.level() <= CAPTURE_LEVEL && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+}
+
+struct ConsoleLogger {
+    inner: env_logger::Logger,
+    entries: Entries,
+}
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata) || captured(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+        if self.inner.matches(record) || captured(record.metadata()) {
+            let mut entries = self.entries.lock().unwrap();
+            if entries.len() == CAPACITY {
+                entries.pop_front();
+            }
+            entries.push_back(Entry {
+                level: record.level(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            });
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Console window state.
+pub struct Console {
+    entries: Entries,
+    level: usize,
+    search: ImString,
+    autoscroll: bool,
+}
+
+impl Console {
+    /// Installs the console as the global logger, wrapping the logger built by
+    /// `builder`.
+    pub fn init(mut builder: env_logger::Builder) -> Self {
+        let inner = builder.build();
+        let entries = Entries::default();
+        log::set_max_level(inner.filter().max(CAPTURE_LEVEL));
+        log::set_boxed_logger(Box::new(ConsoleLogger {
+            inner,
+            entries: entries.clone(),
+        }))
+        .expect("Error setting logger");
+
+        Self {
+            entries,
+            level: 2,
+            search: ImString::with_capacity(64),
+            autoscroll: true,
+        }
+    }
+
+    pub fn window(&mut self, ui: &Ui) {
+        let entries = &self.entries;
+        let level = &mut self.level;
+        let search = &mut self.search;
+        let autoscroll = &mut self.autoscroll;
+        Window::new(im_str!("Console")).build(ui, || {
+            ComboBox::new(im_str!("Level")).build_simple_string(
+                ui,
+                level,
+                &[
+                    im_str!("Error"),
+                    im_str!("Warn"),
+                    im_str!("Info"),
+                    im_str!("Debug"),
+                    im_str!("Trace"),
+                ],
+            );
+            ui.input_text(im_str!("Search"), search).build();
+            ui.checkbox(im_str!("Autoscroll"), autoscroll);
+            ui.same_line(0.0);
+            if ui.small_button(im_str!("Clear")) {
+                entries.lock().unwrap().clear();
+            }
+            ui.separator();
+
+            let max_level = LEVELS[*level];
+            let search = search.to_str().to_lowercase();
+            ChildWindow::new(im_str!("Records")).build(ui, || {
+                for entry in entries.lock().unwrap().iter() {
+                    if entry.level > max_level {
+                        continue;
+                    }
+                    if !search.is_empty() && !entry.message.to_lowercase().contains(&search) {
+                        continue;
+                    }
+                    let color = match entry.level {
+                        Level::Error => [1.0, 0.3, 0.3, 1.0],
+                        Level::Warn => [1.0, 0.8, 0.3, 1.0],
+                        Level::Info => [1.0, 1.0, 1.0, 1.0],
+                        Level::Debug => [0.6, 0.8, 1.0, 1.0],
+                        Level::Trace => [0.6, 0.6, 0.6, 1.0],
+                    };
+                    ui.text_colored(
+                        color,
+                        format!("[{}] {}: {}", entry.level, entry.target, entry.message),
+                    );
+                }
+                // only follow new records if already at the bottom
+                if *autoscroll && ui.scroll_y() >= ui.scroll_max_y() {
+                    ui.set_scroll_here_y_with_ratio(1.0);
+                }
+            });
+        });
+    }
+}
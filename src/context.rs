@@ -0,0 +1,162 @@
+//! Device, queue and swap chain of the window, which everything else is
+//! created from.
+//!
+//! Frames are acquired through a shared reference, so the device and queue
+//! can be borrowed for the whole run while the swap chain keeps changing. It's
+//! recreated when the window is resized, and when acquiring a frame finds it
+//! outdated or lost.
+use crate::{adapter, capabilities::Capabilities};
+use log::{info, warn};
+use sdl2::video::Window;
+use std::{
+    cell::{Cell, RefCell},
+    path::Path,
+};
+use wgpu::{
+    Device, DeviceDescriptor, Instance, PresentMode, Queue, Surface, SwapChain,
+    SwapChainDescriptor, SwapChainError, SwapChainFrame, TextureFormat, TextureUsage,
+};
+
+/// Format of the frames of the swap chain.
+pub const FORMAT: TextureFormat = TextureFormat::Bgra8UnormSrgb;
+
+pub struct RendererContext {
+    pub instance: Instance,
+    /// Index of the adapter in `adapter::enumerate`, if it's one of them.
+    pub adapter_index: Option<usize>,
+    /// Optional features the device was created with.
+    pub capabilities: Capabilities,
+    pub device: Device,
+    pub queue: Queue,
+    surface: Surface,
+    swap_chain: RefCell<SwapChain>,
+    present_mode: Cell<PresentMode>,
+    width: Cell<u32>,
+    height: Cell<u32>,
+}
+
+impl RendererContext {
+    /// Presents to `window` from the adapter at `adapter_index`, or the one
+    /// wgpu picks. API calls are recorded to `trace` if there's one.
+    pub fn new(
+        window: &Window,
+        adapter_index: Option<usize>,
+        trace: Option<&Path>,
+        present_mode: PresentMode,
+    ) -> Self {
+        let instance = Instance::new(adapter::BACKENDS);
+        let surface = unsafe { instance.create_surface(window) };
+        let (adapter, adapter_index) = adapter::select(&instance, &surface, adapter_index);
+        info!("Adapter info: {:?}", adapter.get_info());
+        info!("Adapter features: {:?}", adapter.features());
+        info!("Adapter limits: {:?}", adapter.limits());
+
+        if let Some(dir) = trace {
+            std::fs::create_dir_all(dir).expect("Error creating trace directory");
+            info!("Recording API trace to {}", dir.display());
+        }
+        let capabilities = Capabilities::negotiate(&adapter);
+        let (device, queue) = futures::executor::block_on(adapter.request_device(
+            &DeviceDescriptor {
+                features: capabilities.features(),
+                limits: capabilities.limits(),
+                shader_validation: true,
+            },
+            trace,
+        ))
+        .expect("Error requesting device");
+        info!("Device limits: {:?}", device.limits());
+        info!("Device features: {:?}", device.features());
+
+        // on HiDPI displays the drawable is larger than the window (in screen
+        // coordinates), so the swap chain is created at the drawable size.
+        let (width, height) = window.drawable_size();
+        let swap_chain = create_swap_chain(&device, &surface, width, height, present_mode);
+        Self {
+            instance,
+            adapter_index,
+            capabilities,
+            device,
+            queue,
+            surface,
+            swap_chain: RefCell::new(swap_chain),
+            present_mode: Cell::new(present_mode),
+            width: Cell::new(width),
+            height: Cell::new(height),
+        }
+    }
+
+    /// Size of the frames, in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width.get(), self.height.get())
+    }
+
+    /// Recreates the swap chain at `width`x`height`, the drawable size of the
+    /// window, if it isn't that size already. A minimized window has no
+    /// drawable, and keeps the last swap chain.
+    pub fn resize(&self, width: u32, height: u32) {
+        if width == 0 || height == 0 || (width, height) == self.size() {
+            return;
+        }
+        info!("Resizing the swap chain to {}x{}", width, height);
+        self.width.set(width);
+        self.height.set(height);
+        self.recreate_swap_chain();
+    }
+
+    /// Recreates the swap chain if `present_mode` isn't the current one.
+    pub fn set_present_mode(&self, present_mode: PresentMode) {
+        if present_mode == self.present_mode.get() {
+            return;
+        }
+        self.present_mode.set(present_mode);
+        self.recreate_swap_chain();
+    }
+
+    /// Acquires the next frame, waiting for one if there's none available.
+    /// On an outdated or lost swap chain it's recreated before returning the
+    /// error, so the frame can be skipped and the next one acquired; on a
+    /// timeout there's nothing to do but skip it.
+    pub fn begin_frame(&self) -> Result<SwapChainFrame, SwapChainError> {
+        let frame = self.swap_chain.borrow_mut().get_current_frame();
+        if let Err(err @ (SwapChainError::Outdated | SwapChainError::Lost)) = &frame {
+            warn!("Recreating the swap chain ({})", err);
+            self.recreate_swap_chain();
+        }
+        frame
+    }
+
+    /// Presents `frame`, once the commands drawing it are submitted.
+    pub fn end_frame(&self, frame: SwapChainFrame) {
+        drop(frame);
+    }
+
+    fn recreate_swap_chain(&self) {
+        *self.swap_chain.borrow_mut() = create_swap_chain(
+            &self.device,
+            &self.surface,
+            self.width.get(),
+            self.height.get(),
+            self.present_mode.get(),
+        );
+    }
+}
+
+fn create_swap_chain(
+    device: &Device,
+    surface: &Surface,
+    width: u32,
+    height: u32,
+    present_mode: PresentMode,
+) -> SwapChain {
+    device.create_swap_chain(
+        surface,
+        &SwapChainDescriptor {
+            usage: TextureUsage::OUTPUT_ATTACHMENT,
+            format: FORMAT,
+            width,
+            height,
+            present_mode,
+        },
+    )
+}
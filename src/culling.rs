@@ -0,0 +1,291 @@
+//! GPU frustum culling.
+//!
+//! A compute pass tests the bounding sphere of every instance against the
+//! frustum planes and appends the ones that survive to a compacted instance
+//! buffer, counting them directly into the arguments of an indirect draw.
+use crate::{
+    draw_stats,
+    memory::{self, Category, Tracked},
+    shaders,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec4};
+use wgpu::{
+    util::{make_spirv, BufferInitDescriptor},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferAddress, BufferDescriptor,
+    BufferSize, BufferUsage, CommandEncoder, ComputePipeline, ComputePipelineDescriptor, Device,
+    PipelineLayoutDescriptor, ProgrammableStageDescriptor, Queue, ShaderStage,
+};
+
+/// Must match the `local_size_x` of `cull.comp`.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Instance of the culled mesh.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct Instance {
+    /// Position in `xyz` and uniform scale in `w`.
+    pub position: [f32; 4],
+    /// Layer of the material array.
+    pub material: u32,
+    pub _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Cull {
+    planes: [[f32; 4]; 6],
+    count: u32,
+    radius: f32,
+    _pad: [u32; 2],
+}
+
+/// Bytes of the indirect draw arguments, to read them back.
+pub const INDIRECT_SIZE: BufferAddress = std::mem::size_of::<DrawIndexedIndirect>() as _;
+
+/// Arguments of `draw_indexed_indirect`.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+pub struct GpuCulling {
+    count: u32,
+    index_count: u32,
+    radius: f32,
+    uniform: Tracked<Buffer>,
+    bind_group: BindGroup,
+    pipeline: ComputePipeline,
+    /// All the instances, in the order they were created.
+    pub instances: Tracked<Buffer>,
+    /// Instances that passed the last culling pass, packed at the beginning.
+    pub visible: Tracked<Buffer>,
+    /// Indirect draw arguments of the visible instances.
+    pub indirect: Tracked<Buffer>,
+}
+
+impl GpuCulling {
+    /// Creates the buffers to cull `instances` of a mesh with `index_count`
+    /// indices whose bounding sphere at unit scale has the given `radius`.
+    pub fn new(device: &Device, instances: &[Instance], index_count: u32, radius: f32) -> Self {
+        let module = device.create_shader_module(make_spirv(shaders::CULL_COMP));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Culling uniforms"),
+                size: std::mem::size_of::<Cull>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let instances_buffer = memory::create_buffer_init(
+            device,
+            Category::Storage,
+            &BufferInitDescriptor {
+                label: Some("Culling instances"),
+                contents: bytemuck::cast_slice(instances),
+                usage: BufferUsage::VERTEX | BufferUsage::STORAGE,
+            },
+        );
+        let visible = memory::create_buffer(
+            device,
+            Category::Storage,
+            &BufferDescriptor {
+                label: Some("Visible instances"),
+                size: std::mem::size_of_val(instances) as _,
+                usage: BufferUsage::VERTEX | BufferUsage::STORAGE,
+                mapped_at_creation: false,
+            },
+        );
+        let indirect = memory::create_buffer(
+            device,
+            Category::Storage,
+            &BufferDescriptor {
+                label: Some("Culling indirect args"),
+                size: std::mem::size_of::<DrawIndexedIndirect>() as _,
+                usage: BufferUsage::INDIRECT
+                    | BufferUsage::STORAGE
+                    | BufferUsage::COPY_SRC
+                    | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        let storage = |binding, readonly| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStage::COMPUTE,
+            ty: BindingType::StorageBuffer {
+                dynamic: false,
+                min_binding_size: None,
+                readonly,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Culling bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(std::mem::size_of::<Cull>() as _),
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, false),
+                storage(3, false),
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Culling bind group"),
+            layout: &layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(uniform.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Buffer(instances_buffer.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Buffer(visible.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::Buffer(indirect.slice(..)),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Culling pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Culling pipeline"),
+            layout: Some(&pipeline_layout),
+            compute_stage: ProgrammableStageDescriptor {
+                module: &module,
+                entry_point: "main",
+            },
+        });
+
+        Self {
+            count: instances.len() as _,
+            index_count,
+            radius,
+            uniform,
+            bind_group,
+            pipeline,
+            instances: instances_buffer,
+            visible,
+            indirect,
+        }
+    }
+
+    /// Number of instances before culling.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Uploads the frustum and resets the visible instance count.
+    pub fn update(&self, queue: &Queue, view_projection: Mat4) {
+        let cull = Cull {
+            planes: frustum_planes(view_projection),
+            count: self.count,
+            radius: self.radius,
+            _pad: [0; 2],
+        };
+        let draw = DrawIndexedIndirect {
+            index_count: self.index_count,
+            instance_count: 0,
+            first_index: 0,
+            base_vertex: 0,
+            first_instance: 0,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, bytemuck::bytes_of(&cull));
+        draw_stats::write_buffer(queue, &self.indirect, 0, bytemuck::bytes_of(&draw));
+    }
+
+    /// Number of instances that passed a culling pass, in `indirect` bytes
+    /// copied from [`GpuCulling::indirect`].
+    pub fn visible_count(indirect: &[u8]) -> u32 {
+        let mut draw = DrawIndexedIndirect::zeroed();
+        bytemuck::bytes_of_mut(&mut draw).copy_from_slice(&indirect[..INDIRECT_SIZE as usize]);
+        draw.instance_count
+    }
+
+    /// Counts the `instances` (the ones the buffers were created from) that
+    /// pass the same test as `cull.comp`, to verify the culling pass.
+    pub fn cpu_visible_count(&self, instances: &[Instance], view_projection: Mat4) -> u32 {
+        let planes = frustum_planes(view_projection);
+        let visible = instances.iter().filter(|instance| {
+            let instance = Vec4::from(instance.position);
+            let radius = instance.w * self.radius;
+            planes.iter().all(|plane| {
+                let plane = Vec4::from(*plane);
+                plane.truncate().dot(instance.truncate()) + plane.w >= -radius
+            })
+        });
+        visible.count() as _
+    }
+
+    /// Records the culling pass. The visible instances can be drawn with
+    /// `draw_indexed_indirect` once it's done.
+    pub fn cull(&self, encoder: &mut CommandEncoder) {
+        let mut pass = encoder.begin_compute_pass();
+        pass.push_debug_group("Frustum culling");
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch(self.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        pass.pop_debug_group();
+    }
+}
+
+/// Planes of the frustum of a view-projection matrix (with 0 to 1 depth),
+/// with normals pointing inwards.
+fn frustum_planes(view_projection: Mat4) -> [[f32; 4]; 6] {
+    let m = view_projection.transpose();
+    let (x, y, z, w) = (m.x_axis, m.y_axis, m.z_axis, m.w_axis);
+    let planes = [w + x, w - x, w + y, w - y, z, w - z];
+    let mut normalized = [[0.0; 4]; 6];
+    for (plane, normalized) in planes.iter().zip(&mut normalized) {
+        *normalized = (*plane / plane.truncate().length()).into();
+    }
+    normalized
+}
+
+/// Scatters `count` instances randomly inside of a box, with one of the first
+/// `materials` layers each.
+pub fn scatter(count: usize, min: Vec4, max: Vec4, materials: u32) -> Vec<Instance> {
+    // xorshift, there's no need for anything better here
+    let mut state = 0x2545_f491u32;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32
+    };
+    (0..count)
+        .map(|_| {
+            let t = Vec4::new(random(), random(), random(), random());
+            let material = (random() * materials as f32) as u32;
+            Instance {
+                position: (min + (max - min) * t).into(),
+                material: material.min(materials - 1),
+                _pad: [0; 3],
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,59 @@
+//! Debug views of the lit shader, which replace the lighting with one of the
+//! inputs to it.
+
+/// Debug views selectable from the UI.
+pub const DEBUG_VIEWS: [DebugView; 10] = [
+    DebugView::Final,
+    DebugView::Albedo,
+    DebugView::Normals,
+    DebugView::RoughnessMetalness,
+    DebugView::Depth,
+    DebugView::Uvs,
+    DebugView::Overdraw,
+    DebugView::MipLevel,
+    DebugView::LightClusters,
+    DebugView::Lod,
+];
+
+/// Same values as the defines of the shader.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DebugView {
+    /// Lit and tone mapped.
+    Final = 0,
+    Albedo = 1,
+    /// World space, after normal mapping.
+    Normals = 2,
+    /// Roughness in green, metalness in blue.
+    RoughnessMetalness = 3,
+    /// Distance along the view direction, linear up to the depth range.
+    Depth = 4,
+    Uvs = 5,
+    /// Fragments drawn on every pixel, without depth testing.
+    Overdraw = 6,
+    /// Mip level of the base color texture, blue for the full size one to
+    /// red for the sixth and smaller ones.
+    MipLevel = 7,
+    /// Lights of the cluster of every fragment, from blue to red for a full
+    /// list, grey outside of the clusters.
+    LightClusters = 8,
+    /// Albedo tinted by the level of detail drawn, from blue for the full
+    /// mesh to yellow for the coarsest.
+    Lod = 9,
+}
+
+impl DebugView {
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Final => "Final",
+            DebugView::Albedo => "Albedo",
+            DebugView::Normals => "Normals",
+            DebugView::RoughnessMetalness => "Roughness/metalness",
+            DebugView::Depth => "Depth (linear)",
+            DebugView::Uvs => "UVs",
+            DebugView::Overdraw => "Overdraw heatmap",
+            DebugView::MipLevel => "Mip level",
+            DebugView::LightClusters => "Light clusters",
+            DebugView::Lod => "Level of detail",
+        }
+    }
+}
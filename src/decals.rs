@@ -0,0 +1,394 @@
+//! Decals projected onto the surfaces inside their boxes.
+//!
+//! Each decal is a box drawn after the opaque objects. Its fragments find the
+//! surface behind them from the depth buffer, and keep the part of it inside
+//! the box, textured down the Y axis of the box. Only the back faces of the
+//! boxes are drawn, without a depth test, so they still cover the surfaces
+//! with the camera inside of them.
+use crate::{
+    draw_stats::{self, CountedPass},
+    layout::{self, Layout},
+    memory::{self, Category, Tracked},
+    post, shaders, texture,
+};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use imgui::{im_str, AngleSlider, ColorEdit, Drag, Ui, Window};
+use wgpu::{
+    util::make_spirv, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    BlendFactor, BlendOperation, Buffer, BufferDescriptor, BufferSize, BufferUsage,
+    ColorStateDescriptor, ColorWrite, CullMode, Device, FilterMode, FrontFace, IndexFormat,
+    PipelineLayoutDescriptor, PrimitiveTopology, ProgrammableStageDescriptor, Queue,
+    RasterizationStateDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler,
+    SamplerDescriptor, ShaderStage, Texture, TextureFormat, TextureView, TextureViewDescriptor,
+    VertexStateDescriptor,
+};
+
+/// Maximum number of decals drawn, the rest are left out.
+pub const MAX_DECALS: usize = 64;
+
+const DECAL_SIZE: u32 = 256;
+
+/// Decal projected from above its box.
+#[derive(Clone, Debug)]
+pub struct Decal {
+    pub position: Vec3,
+    /// Yaw, pitch and roll in radians.
+    pub angles: Vec3,
+    pub size: Vec3,
+    /// Color multiplying the texture, and its opacity.
+    pub color: [f32; 4],
+}
+
+impl Decal {
+    /// Unit sized decal projected down over `position`.
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            angles: Vec3::zero(),
+            size: Vec3::one(),
+            color: [1.0, 0.4, 0.1, 1.0],
+        }
+    }
+
+    pub fn rotation(&self) -> Quat {
+        Quat::from_rotation_ypr(self.angles.x, self.angles.y, self.angles.z)
+    }
+
+    /// Transform of the unit cube into the box.
+    pub fn transform(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.size, self.rotation(), self.position)
+    }
+
+    pub fn ui(&mut self, ui: &Ui) {
+        let mut position: [f32; 3] = self.position.into();
+        if Drag::new(im_str!("Position"))
+            .speed(0.05)
+            .build_array(ui, &mut position)
+        {
+            self.position = position.into();
+        }
+        let (mut yaw, mut pitch, mut roll) = (self.angles.x, self.angles.y, self.angles.z);
+        AngleSlider::new(im_str!("Yaw"))
+            .range_degrees(-180.0..=180.0)
+            .build(ui, &mut yaw);
+        AngleSlider::new(im_str!("Pitch"))
+            .range_degrees(-180.0..=180.0)
+            .build(ui, &mut pitch);
+        AngleSlider::new(im_str!("Roll"))
+            .range_degrees(-180.0..=180.0)
+            .build(ui, &mut roll);
+        self.angles = Vec3::new(yaw, pitch, roll);
+        let mut size: [f32; 3] = self.size.into();
+        if Drag::new(im_str!("Size"))
+            .speed(0.02)
+            .range(0.05..=20.0)
+            .build_array(ui, &mut size)
+        {
+            self.size = size.into();
+        }
+        ColorEdit::new(im_str!("Color"), &mut self.color).build(ui);
+    }
+}
+
+/// Window adding and removing `decals`, editing the `selected` one. New
+/// decals are placed at `target`, and the window tells when they are
+/// `hidden`.
+pub fn window(
+    ui: &Ui,
+    decals: &mut Vec<Decal>,
+    selected: &mut Option<usize>,
+    show: &mut bool,
+    target: Vec3,
+    hidden: bool,
+) {
+    Window::new(im_str!("Decals"))
+        .always_auto_resize(true)
+        .build(ui, || {
+            ui.checkbox(im_str!("Show decals"), show);
+            if decals.len() < MAX_DECALS && ui.button(im_str!("Add decal"), [0.0, 0.0]) {
+                // where the camera looks
+                decals.push(Decal::new(target));
+                *selected = Some(decals.len() - 1);
+            }
+            ui.same_line(0.0);
+            if ui.button(im_str!("Clear"), [0.0, 0.0]) {
+                decals.clear();
+                *selected = None;
+            }
+            ui.text(format!("{} of {} decals", decals.len(), MAX_DECALS));
+            if hidden {
+                ui.text_disabled("(hidden with the overdraw view)");
+            }
+
+            let mut removed = None;
+            for (i, decal) in decals.iter_mut().enumerate() {
+                let id = ui.push_id(i as i32);
+                ui.separator();
+                let mut is_selected = *selected == Some(i);
+                if ui.checkbox(im_str!("Selected"), &mut is_selected) {
+                    *selected = if is_selected { Some(i) } else { None };
+                }
+                if is_selected {
+                    decal.ui(ui);
+                    if ui.button(im_str!("Remove"), [0.0, 0.0]) {
+                        removed = Some(i);
+                    }
+                }
+                id.pop(ui);
+            }
+            if let Some(i) = removed {
+                decals.remove(i);
+                *selected = None;
+            }
+        });
+}
+
+gpu_struct! {
+    struct DecalUniforms {
+        view_projection: Mat4,
+        inverse_view_projection: Mat4,
+        viewport: Vec2,
+    }
+}
+
+gpu_struct! {
+    struct DecalInstance {
+        model: Mat4,
+        inverse_model: Mat4,
+        color: Vec4,
+    }
+}
+
+pub struct Decals {
+    uniform: Tracked<Buffer>,
+    instances: Tracked<Buffer>,
+    _texture: Tracked<Texture>,
+    _sampler: Sampler,
+    bind_group: BindGroup,
+    depth_layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+    count: u32,
+}
+
+impl Decals {
+    pub fn new(device: &Device, queue: &Queue, format: TextureFormat) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::DECAL_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::DECAL_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Decal uniforms"),
+                size: DecalUniforms::STD140.size as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let instances = memory::create_buffer(
+            device,
+            Category::Storage,
+            &BufferDescriptor {
+                label: Some("Decal instances"),
+                size: (MAX_DECALS * DecalInstance::STD430.size) as _,
+                usage: BufferUsage::STORAGE | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let texture = texture::create_rgba8_mipmapped(
+            device,
+            queue,
+            "Decal texture",
+            TextureFormat::Rgba8UnormSrgb,
+            DECAL_SIZE,
+            DECAL_SIZE,
+            &texture::decal(DECAL_SIZE),
+        );
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Decal sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Decal bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::VERTEX | ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(DecalUniforms::STD140.size as _),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::VERTEX | ShaderStage::FRAGMENT,
+                    ty: BindingType::StorageBuffer {
+                        dynamic: false,
+                        min_binding_size: None,
+                        readonly: true,
+                    },
+                    count: None,
+                },
+                post::texture_entry(2),
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Decal bind group"),
+            layout: &layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(uniform.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Buffer(instances.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        // the depth buffer changes with the size of the window
+        let depth_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Decal depth bind group layout"),
+            entries: &[post::texture_entry(0)],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Decal pipeline layout"),
+            bind_group_layouts: &[&layout, &depth_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Decal pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex_stage: ProgrammableStageDescriptor {
+                module: &vert_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(ProgrammableStageDescriptor {
+                module: &frag_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(RasterizationStateDescriptor {
+                front_face: FrontFace::Ccw,
+                cull_mode: CullMode::Front,
+                ..Default::default()
+            }),
+            primitive_topology: PrimitiveTopology::TriangleList,
+            color_states: &[ColorStateDescriptor {
+                format,
+                color_blend: BlendDescriptor {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::OneMinusSrcAlpha,
+                    operation: BlendOperation::Add,
+                },
+                // the alpha of the HDR target is left as it is
+                alpha_blend: BlendDescriptor {
+                    src_factor: BlendFactor::Zero,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                write_mask: ColorWrite::ALL,
+            }],
+            depth_stencil_state: None,
+            vertex_state: VertexStateDescriptor {
+                index_format: IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        Self {
+            uniform,
+            instances,
+            _texture: texture,
+            _sampler: sampler,
+            bind_group,
+            depth_layout,
+            pipeline,
+            count: 0,
+        }
+    }
+
+    /// Bind group of the depth buffer the decals are projected onto, to be
+    /// created again along with it.
+    pub fn depth_bind_group(&self, device: &Device, depth: &TextureView) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Decal depth bind group"),
+            layout: &self.depth_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(depth),
+            }],
+        })
+    }
+
+    /// Uploads the decals, seen through `view_projection` over a
+    /// `width`x`height` target.
+    pub fn update(
+        &mut self,
+        queue: &Queue,
+        view_projection: Mat4,
+        width: u32,
+        height: u32,
+        decals: &[Decal],
+    ) {
+        let uniforms = DecalUniforms {
+            view_projection,
+            inverse_view_projection: view_projection.inverse(),
+            viewport: Vec2::new(width as f32, height as f32),
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, &uniforms.std140_bytes());
+        let instances: Vec<DecalInstance> = decals
+            .iter()
+            .take(MAX_DECALS)
+            .map(|decal| {
+                let model = decal.transform();
+                DecalInstance {
+                    model,
+                    inverse_model: model.inverse(),
+                    color: decal.color.into(),
+                }
+            })
+            .collect();
+        if !instances.is_empty() {
+            draw_stats::write_buffer(queue, &self.instances, 0, &layout::std430_array(&instances));
+        }
+        self.count = instances.len() as u32;
+    }
+
+    /// Draws the decals over the color target of the pass, which must have no
+    /// depth attachment since `depth` is sampled.
+    pub fn draw<'a>(&'a self, pass: &mut CountedPass<'a>, depth: &'a BindGroup) {
+        if self.count == 0 {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_bind_group(1, depth, &[]);
+        pass.draw(0..36, 0..self.count);
+    }
+}
@@ -0,0 +1,154 @@
+//! Native file dialogs.
+//!
+//! The dialog of the desktop runs as a separate program: zenity or kdialog
+//! on Linux and the BSDs, AppleScript on macOS and PowerShell on Windows.
+//! Opening one blocks until it's closed, like any modal dialog.
+use log::warn;
+use std::{io::ErrorKind, path::PathBuf, process::Command};
+
+/// Kind of file a dialog opens.
+pub struct Filter {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+}
+
+pub const MODELS: Filter = Filter {
+    name: "Models",
+    extensions: &["obj"],
+};
+
+pub const TEXTURES: Filter = Filter {
+    name: "Textures",
+    extensions: &["png", "hdr", "dds", "ktx2"],
+};
+
+pub const LUTS: Filter = Filter {
+    name: "Color grading tables",
+    extensions: &["cube", "png"],
+};
+
+pub const SCENES: Filter = Filter {
+    name: "Scenes",
+    extensions: &["gltf", "glb", "json"],
+};
+
+/// Scenes saved from the app.
+pub const SCENE_FILES: Filter = Filter {
+    name: "Scene files",
+    extensions: &["json"],
+};
+
+/// Asks for a file to open. `None` if the dialog was cancelled, or if there's
+/// no dialog program to run.
+pub fn open_file(title: &str, filter: &Filter) -> Option<PathBuf> {
+    run(commands(title, filter, false))
+}
+
+/// Asks for a file to save to, confirming if it's overwritten. `None` like
+/// [`open_file`].
+pub fn save_file(title: &str, filter: &Filter) -> Option<PathBuf> {
+    run(commands(title, filter, true))
+}
+
+fn run(commands: Vec<Command>) -> Option<PathBuf> {
+    for mut command in commands {
+        match command.output() {
+            Ok(output) => {
+                // cancelling exits with an error status
+                let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                if !output.status.success() || path.is_empty() {
+                    return None;
+                }
+                return Some(PathBuf::from(path));
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => {
+                warn!("Error opening file dialog: {}", err);
+                return None;
+            }
+        }
+    }
+    warn!("There's no file dialog program, install zenity or kdialog");
+    None
+}
+
+/// Programs that can show the dialog, in order of preference.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn commands(title: &str, filter: &Filter, save: bool) -> Vec<Command> {
+    let patterns: Vec<_> = filter
+        .extensions
+        .iter()
+        .map(|ext| format!("*.{}", ext))
+        .collect();
+    let patterns = patterns.join(" ");
+    let mut zenity = Command::new("zenity");
+    zenity
+        .arg("--file-selection")
+        .arg(format!("--title={}", title))
+        .arg(format!("--file-filter={} | {}", filter.name, patterns));
+    if save {
+        zenity.arg("--save").arg("--confirm-overwrite");
+    }
+    let mut kdialog = Command::new("kdialog");
+    kdialog
+        .arg("--title")
+        .arg(title)
+        .arg(if save {
+            "--getsavefilename"
+        } else {
+            "--getopenfilename"
+        })
+        .arg(".")
+        .arg(format!("{}|{}", patterns, filter.name));
+    vec![zenity, kdialog]
+}
+
+#[cfg(target_os = "macos")]
+fn commands(title: &str, filter: &Filter, save: bool) -> Vec<Command> {
+    let title = title.replace('"', "\\\"");
+    let script = if save {
+        // there's no filter when choosing a new file name
+        format!("POSIX path of (choose file name with prompt \"{}\")", title)
+    } else {
+        let types: Vec<_> = filter
+            .extensions
+            .iter()
+            .map(|ext| format!("\"{}\"", ext))
+            .collect();
+        format!(
+            "POSIX path of (choose file with prompt \"{}\" of type {{{}}})",
+            title,
+            types.join(", ")
+        )
+    };
+    let mut osascript = Command::new("osascript");
+    osascript.arg("-e").arg(script);
+    vec![osascript]
+}
+
+#[cfg(windows)]
+fn commands(title: &str, filter: &Filter, save: bool) -> Vec<Command> {
+    let patterns: Vec<_> = filter
+        .extensions
+        .iter()
+        .map(|ext| format!("*.{}", ext))
+        .collect();
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; \
+         $dialog = New-Object System.Windows.Forms.{}; \
+         $dialog.Title = '{}'; \
+         $dialog.Filter = '{}|{}'; \
+         if ($dialog.ShowDialog() -eq 'OK') {{ $dialog.FileName }} else {{ exit 1 }}",
+        if save {
+            "SaveFileDialog"
+        } else {
+            "OpenFileDialog"
+        },
+        title.replace('\'', "''"),
+        filter.name,
+        patterns.join(";")
+    );
+    let mut powershell = Command::new("powershell");
+    powershell.arg("-NoProfile").arg("-Command").arg(script);
+    vec![powershell]
+}
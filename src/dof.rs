@@ -0,0 +1,331 @@
+//! Depth of field, from the depth buffer of the scene.
+//!
+//! The radius of the circle of confusion of every pixel is computed from its
+//! depth first, negative in front of the focus plane and positive behind it.
+//! The scene is then blurred at half size by gathering samples along a
+//! spiral, each weighted by how much its own circle covers the pixel, and
+//! the blur is blended over the sharp scene where it's out of focus.
+use crate::{
+    draw_stats,
+    layout::Layout,
+    memory::{self, Category, Tracked},
+    pass::PassBuilder,
+    ping_pong::PingPong,
+    post::{self, PostFrame},
+    shaders,
+};
+use imgui::{im_str, Slider, SliderFlags, Ui};
+use wgpu::{
+    util::make_spirv, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferDescriptor, BufferSize, BufferUsage, Color, CommandEncoder, Device, Extent3d,
+    FilterMode, LoadOp, Queue, RenderPipeline, Sampler, SamplerDescriptor, ShaderStage, Texture,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsage, TextureView,
+    TextureViewDescriptor,
+};
+
+const COC_FORMAT: TextureFormat = TextureFormat::R16Float;
+
+/// Radius of the largest circle of confusion, in pixels of the scene.
+const MAX_RADIUS: f32 = 16.0;
+
+gpu_struct! {
+    struct DofUniforms {
+        near: f32,
+        far: f32,
+        focus_distance: f32,
+        aperture: f32,
+        max_radius: f32,
+    }
+}
+
+/// Focus of the camera, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct DofParams {
+    pub enabled: bool,
+    /// Distance in focus, along the view direction.
+    pub focus_distance: f32,
+    /// How quickly the scene blurs away from the focus distance, the largest
+    /// blur being reached with `aperture` times closer or further.
+    pub aperture: f32,
+    /// Focuses on the object under the cursor every frame.
+    pub autofocus: bool,
+}
+
+impl Default for DofParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            focus_distance: 5.0,
+            aperture: 0.5,
+            autofocus: false,
+        }
+    }
+}
+
+impl DofParams {
+    /// Controls of the focus, at distances up to `far_plane`.
+    pub fn ui(&mut self, ui: &Ui, far_plane: f32) {
+        ui.checkbox(im_str!("Depth of field"), &mut self.enabled);
+        Slider::new(im_str!("Focus distance"))
+            .range(0.1..=far_plane)
+            .flags(SliderFlags::LOGARITHMIC)
+            .build(ui, &mut self.focus_distance);
+        Slider::new(im_str!("Aperture"))
+            .range(0.0..=2.0)
+            .build(ui, &mut self.aperture);
+        ui.checkbox(im_str!("Autofocus"), &mut self.autofocus);
+    }
+}
+
+pub struct DofTargets {
+    _coc: Tracked<Texture>,
+    coc_view: TextureView,
+    _blurred: Tracked<Texture>,
+    blurred_view: TextureView,
+    coc: BindGroup,
+    /// Bind groups of the blur and the composite, reading either HDR target.
+    blur: [BindGroup; 2],
+    composite: [BindGroup; 2],
+}
+
+pub struct DepthOfField {
+    uniform: Tracked<Buffer>,
+    sampler: Sampler,
+    coc_layout: BindGroupLayout,
+    blur_layout: BindGroupLayout,
+    composite_layout: BindGroupLayout,
+    coc_pipeline: RenderPipeline,
+    blur_pipeline: RenderPipeline,
+    composite_pipeline: RenderPipeline,
+}
+
+impl DepthOfField {
+    pub fn new(device: &Device) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let coc_module = device.create_shader_module(make_spirv(shaders::DOF_COC_FRAG));
+        let blur_module = device.create_shader_module(make_spirv(shaders::DOF_BLUR_FRAG));
+        let composite_module = device.create_shader_module(make_spirv(shaders::DOF_COMPOSITE_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Depth of field uniforms"),
+                size: DofUniforms::STD140.size as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Depth of field sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        // every pass reads one more texture than the previous one: the depth
+        // or scene, the circles of confusion and the blur
+        let entries = [
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: BufferSize::new(DofUniforms::STD140.size as _),
+                },
+                count: None,
+            },
+            post::texture_entry(1),
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Sampler { comparison: false },
+                count: None,
+            },
+            post::texture_entry(3),
+            post::texture_entry(4),
+        ];
+        let layout = |label, len| {
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries: &entries[..len],
+            })
+        };
+        let coc_layout = layout("Circle of confusion bind group layout", 3);
+        let blur_layout = layout("Depth of field blur bind group layout", 4);
+        let composite_layout = layout("Depth of field composite bind group layout", 5);
+
+        let pipeline = |label, layout, frag_module, format| {
+            post::fullscreen_pipeline(
+                device,
+                label,
+                &[layout],
+                &vert_module,
+                frag_module,
+                format,
+                BlendDescriptor::REPLACE,
+            )
+        };
+        let coc_pipeline = pipeline(
+            "Circle of confusion pipeline",
+            &coc_layout,
+            &coc_module,
+            COC_FORMAT,
+        );
+        let blur_pipeline = pipeline(
+            "Depth of field blur pipeline",
+            &blur_layout,
+            &blur_module,
+            post::HDR_FORMAT,
+        );
+        let composite_pipeline = pipeline(
+            "Depth of field composite pipeline",
+            &composite_layout,
+            &composite_module,
+            post::HDR_FORMAT,
+        );
+
+        Self {
+            uniform,
+            sampler,
+            coc_layout,
+            blur_layout,
+            composite_layout,
+            coc_pipeline,
+            blur_pipeline,
+            composite_pipeline,
+        }
+    }
+
+    /// Targets for the scene drawn into `hdr`, with `depth` as the view of
+    /// its depth buffer.
+    pub fn targets(&self, device: &Device, hdr: &PingPong, depth: &TextureView) -> DofTargets {
+        let (width, height) = hdr.size();
+        let target = |label, width, height, format| {
+            let texture = memory::create_texture(
+                device,
+                Category::RenderTargets,
+                &TextureDescriptor {
+                    label: Some(label),
+                    size: Extent3d {
+                        width,
+                        height,
+                        depth: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format,
+                    usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::SAMPLED,
+                },
+            );
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            (texture, view)
+        };
+        let (coc, coc_view) = target("Circles of confusion", width, height, COC_FORMAT);
+        let (blurred, blurred_view) = target(
+            "Depth of field blur",
+            (width / 2).max(1),
+            (height / 2).max(1),
+            post::HDR_FORMAT,
+        );
+
+        // the uniforms and sampler, then `views` from binding 1, skipping the sampler
+        let bind_group = |layout: &BindGroupLayout, views: &[&TextureView]| {
+            let view = |binding, view| BindGroupEntry {
+                binding,
+                resource: BindingResource::TextureView(view),
+            };
+            let mut entries = vec![
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(self.uniform.slice(..)),
+                },
+                view(1, views[0]),
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+            ];
+            entries.extend((3..).zip(&views[1..]).map(|(binding, &v)| view(binding, v)));
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Depth of field bind group"),
+                layout,
+                entries: &entries,
+            })
+        };
+        let coc_bind_group = bind_group(&self.coc_layout, &[depth]);
+        let blur = hdr.directions(|source, _| bind_group(&self.blur_layout, &[source, &coc_view]));
+        let composite = hdr.directions(|source, _| {
+            bind_group(&self.composite_layout, &[source, &coc_view, &blurred_view])
+        });
+
+        DofTargets {
+            _coc: coc,
+            coc_view,
+            _blurred: blurred,
+            blurred_view,
+            coc: coc_bind_group,
+            blur,
+            composite,
+        }
+    }
+
+    /// Records the scene last written to `hdr` with depth of field into the
+    /// other target.
+    pub fn run(
+        &self,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        params: &DofParams,
+        frame: PostFrame,
+        targets: &DofTargets,
+        hdr: &PingPong,
+    ) {
+        let uniforms = DofUniforms {
+            near: frame.near,
+            far: frame.far,
+            focus_distance: params.focus_distance,
+            aperture: params.aperture,
+            max_radius: MAX_RADIUS,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, &uniforms.std140_bytes());
+
+        let passes = [
+            (
+                "Circle of confusion",
+                &targets.coc_view,
+                COC_FORMAT,
+                &self.coc_pipeline,
+                &targets.coc,
+            ),
+            (
+                "Depth of field blur",
+                &targets.blurred_view,
+                post::HDR_FORMAT,
+                &self.blur_pipeline,
+                hdr.current(&targets.blur),
+            ),
+            (
+                "Depth of field composite",
+                hdr.write(),
+                post::HDR_FORMAT,
+                &self.composite_pipeline,
+                hdr.current(&targets.composite),
+            ),
+        ];
+        for (label, view, format, pipeline, bind_group) in passes {
+            let mut pass = PassBuilder::new()
+                .color(view, format, LoadOp::Clear(Color::BLACK), true)
+                .begin(encoder);
+            pass.push_debug_group(label);
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+            pass.pop_debug_group();
+        }
+    }
+}
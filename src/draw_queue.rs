@@ -0,0 +1,82 @@
+//! Objects of the scene split into the queues they are drawn in.
+//!
+//! Opaque and masked objects are drawn first, writing depth, with the
+//! selected ones last so no other object overwrites their stencil values.
+//! Blended objects come after them without writing depth, sorted back to
+//! front by the view depth of the center of their bounds, as glTF meshes are
+//! placed by their vertices rather than the position of the object. Those
+//! blended in any order (see `oit`) have their own queue.
+use crate::{material::AlphaMode, mesh::MeshData, scene::Scene};
+use glam::Mat4;
+use std::cmp::Ordering;
+
+#[derive(Default)]
+pub struct DrawQueues {
+    pub opaque: Vec<usize>,
+    pub transparent: Vec<usize>,
+    pub order_independent: Vec<usize>,
+}
+
+impl DrawQueues {
+    /// Queues of the objects of `scene`, seen by a camera with `view`. Every
+    /// blended object is sorted without `order_independent`, for passes with
+    /// no targets to blend in any order.
+    pub fn new(scene: &Scene, meshes: &[MeshData], view: Mat4, order_independent: bool) -> Self {
+        let mut queues = Self::default();
+        let mut depths = Vec::new();
+        for (i, object) in scene.objects.iter().enumerate() {
+            match object.material.alpha_mode {
+                AlphaMode::Opaque | AlphaMode::Mask(_) => queues.opaque.push(i),
+                AlphaMode::Blend if order_independent && object.material.order_independent => {
+                    queues.order_independent.push(i)
+                }
+                AlphaMode::Blend => {
+                    let (min, max) = meshes[object.mesh].bounds();
+                    let center = object.model().transform_point3((min + max) * 0.5);
+                    // the camera looks down -Z
+                    depths.push((i, -view.transform_point3(center).z));
+                }
+            }
+        }
+        queues.opaque.sort_by_key(|&i| scene.objects[i].selected);
+        depths.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        queues.transparent = depths.into_iter().map(|(i, _)| i).collect();
+        queues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DrawQueues;
+    use crate::{
+        material::AlphaMode,
+        mesh::MeshData,
+        scene::{Object, Scene},
+    };
+    use glam::{Mat4, Vec3};
+
+    #[test]
+    fn blended_back_to_front() {
+        let object = |z: f32, alpha_mode, selected| {
+            let mut object = Object::new("Object", 0, Vec3::new(0.0, 0.0, z), Vec3::unit_y(), 0.0);
+            object.material.alpha_mode = alpha_mode;
+            object.selected = selected;
+            object
+        };
+        let scene = Scene {
+            objects: vec![
+                object(-1.0, AlphaMode::Blend, false),
+                object(-2.0, AlphaMode::Opaque, true),
+                object(-3.0, AlphaMode::Blend, false),
+                object(-4.0, AlphaMode::Mask(0.5), false),
+                object(-2.0, AlphaMode::Blend, false),
+            ],
+        };
+        let meshes = [MeshData::triangle()];
+        let view = Mat4::look_at_rh(Vec3::zero(), -Vec3::unit_z(), Vec3::unit_y());
+        let queues = DrawQueues::new(&scene, &meshes, view, true);
+        // masked objects are opaque, the selected one last
+        assert_eq!(queues.opaque, vec![3, 1]);
+        assert_eq!(queues.transparent, vec![2, 4, 0]);
+    }
+}
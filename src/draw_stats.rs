@@ -0,0 +1,279 @@
+//! Per frame counts of draw calls, state changes and buffer uploads.
+//!
+//! Render passes are recorded through [`CountedPass`], which counts what goes
+//! through it on the way to the wrapped pass, render bundles through
+//! [`CountedBundle`], counted every time they're executed, and buffers are
+//! written with [`write_buffer`]. Every call to [`new_frame`] keeps the counts of the frame
+//! that ended, which are the ones shown.
+//!
+//! Triangles are counted as if everything was drawn as triangle lists, and
+//! indirect draws only count as draw calls, their arguments being on the GPU.
+use std::{ops::Range, sync::Mutex};
+use wgpu::{
+    BindGroup, Buffer, BufferAddress, BufferSlice, Color, Device, DynamicOffset, Queue,
+    RenderBundle, RenderBundleDescriptor, RenderBundleEncoder, RenderBundleEncoderDescriptor,
+    RenderPass, RenderPipeline,
+};
+
+static STATS: Mutex<(DrawStats, DrawStats)> = Mutex::new((DrawStats::new(), DrawStats::new()));
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct DrawStats {
+    pub draws: u32,
+    pub triangles: u64,
+    pub pipelines: u32,
+    pub bind_groups: u32,
+    pub uploads: u32,
+    pub upload_bytes: u64,
+}
+
+impl DrawStats {
+    const fn new() -> Self {
+        Self {
+            draws: 0,
+            triangles: 0,
+            pipelines: 0,
+            bind_groups: 0,
+            uploads: 0,
+            upload_bytes: 0,
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.draws += other.draws;
+        self.triangles += other.triangles;
+        self.pipelines += other.pipelines;
+        self.bind_groups += other.bind_groups;
+        self.uploads += other.uploads;
+        self.upload_bytes += other.upload_bytes;
+    }
+}
+
+/// Ends the frame being counted.
+pub fn new_frame() {
+    let mut stats = STATS.lock().unwrap();
+    stats.1 = std::mem::take(&mut stats.0);
+}
+
+/// Counts of the last frame that ended.
+pub fn last_frame() -> DrawStats {
+    STATS.lock().unwrap().1
+}
+
+/// `queue.write_buffer`, counted as an upload.
+pub fn write_buffer(queue: &Queue, buffer: &Buffer, offset: BufferAddress, data: &[u8]) {
+    count_upload(data.len());
+    queue.write_buffer(buffer, offset, data);
+}
+
+/// Counts an upload of `bytes` that doesn't go through [`write_buffer`].
+pub(crate) fn count_upload(bytes: usize) {
+    let mut stats = STATS.lock().unwrap();
+    stats.0.uploads += 1;
+    stats.0.upload_bytes += bytes as u64;
+}
+
+/// Commands recorded the same way into passes and bundles.
+pub trait RenderEncoder<'a> {
+    fn set_pipeline(&mut self, pipeline: &'a RenderPipeline);
+    fn set_bind_group(&mut self, index: u32, bind_group: &'a BindGroup, offsets: &[DynamicOffset]);
+    fn set_vertex_buffer(&mut self, slot: u32, buffer_slice: BufferSlice<'a>);
+    fn set_index_buffer(&mut self, buffer_slice: BufferSlice<'a>);
+    fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>);
+    fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>);
+}
+
+/// Render pass whose counts are added to the frame when it's dropped, so
+/// recording doesn't lock anything.
+pub struct CountedPass<'a> {
+    pass: RenderPass<'a>,
+    stats: DrawStats,
+}
+
+impl<'a> CountedPass<'a> {
+    pub fn new(pass: RenderPass<'a>) -> Self {
+        Self {
+            pass,
+            stats: DrawStats::default(),
+        }
+    }
+
+    /// The wrapped pass, for code recording passes by itself (like imgui),
+    /// whose commands aren't counted.
+    pub fn inner(&mut self) -> &mut RenderPass<'a> {
+        &mut self.pass
+    }
+
+    pub fn set_pipeline(&mut self, pipeline: &'a RenderPipeline) {
+        self.stats.pipelines += 1;
+        self.pass.set_pipeline(pipeline);
+    }
+
+    pub fn set_bind_group(
+        &mut self,
+        index: u32,
+        bind_group: &'a BindGroup,
+        offsets: &[DynamicOffset],
+    ) {
+        self.stats.bind_groups += 1;
+        self.pass.set_bind_group(index, bind_group, offsets);
+    }
+
+    pub fn set_vertex_buffer(&mut self, slot: u32, buffer_slice: BufferSlice<'a>) {
+        self.pass.set_vertex_buffer(slot, buffer_slice);
+    }
+
+    pub fn set_index_buffer(&mut self, buffer_slice: BufferSlice<'a>) {
+        self.pass.set_index_buffer(buffer_slice);
+    }
+
+    pub fn set_viewport(&mut self, x: f32, y: f32, w: f32, h: f32, min_depth: f32, max_depth: f32) {
+        self.pass.set_viewport(x, y, w, h, min_depth, max_depth);
+    }
+
+    pub fn set_scissor_rect(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        self.pass.set_scissor_rect(x, y, width, height);
+    }
+
+    pub fn set_stencil_reference(&mut self, reference: u32) {
+        self.pass.set_stencil_reference(reference);
+    }
+
+    pub fn set_blend_color(&mut self, color: Color) {
+        self.pass.set_blend_color(color);
+    }
+
+    pub fn push_debug_group(&mut self, label: &str) {
+        self.pass.push_debug_group(label);
+    }
+
+    pub fn pop_debug_group(&mut self) {
+        self.pass.pop_debug_group();
+    }
+
+    pub fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
+        self.count_draw(vertices.len(), instances.len());
+        self.pass.draw(vertices, instances);
+    }
+
+    pub fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        self.count_draw(indices.len(), instances.len());
+        self.pass.draw_indexed(indices, base_vertex, instances);
+    }
+
+    pub fn draw_indexed_indirect(&mut self, indirect_buffer: &'a Buffer, offset: BufferAddress) {
+        self.stats.draws += 1;
+        self.pass.draw_indexed_indirect(indirect_buffer, offset);
+    }
+
+    /// Executes `bundles`, counting what they were recorded with. The
+    /// pipeline, bind groups and buffers have to be set again afterwards.
+    pub fn execute_bundles(&mut self, bundles: &'a [FinishedBundle]) {
+        for bundle in bundles {
+            self.stats.add(&bundle.stats);
+        }
+        self.pass
+            .execute_bundles(bundles.iter().map(|bundle| &bundle.bundle));
+    }
+
+    fn count_draw(&mut self, vertices: usize, instances: usize) {
+        self.stats.draws += 1;
+        self.stats.triangles += (vertices / 3 * instances) as u64;
+    }
+}
+
+impl<'a> RenderEncoder<'a> for CountedPass<'a> {
+    fn set_pipeline(&mut self, pipeline: &'a RenderPipeline) {
+        CountedPass::set_pipeline(self, pipeline);
+    }
+
+    fn set_bind_group(&mut self, index: u32, bind_group: &'a BindGroup, offsets: &[DynamicOffset]) {
+        CountedPass::set_bind_group(self, index, bind_group, offsets);
+    }
+
+    fn set_vertex_buffer(&mut self, slot: u32, buffer_slice: BufferSlice<'a>) {
+        CountedPass::set_vertex_buffer(self, slot, buffer_slice);
+    }
+
+    fn set_index_buffer(&mut self, buffer_slice: BufferSlice<'a>) {
+        CountedPass::set_index_buffer(self, buffer_slice);
+    }
+
+    fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
+        CountedPass::draw(self, vertices, instances);
+    }
+
+    fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        CountedPass::draw_indexed(self, indices, base_vertex, instances);
+    }
+}
+
+impl Drop for CountedPass<'_> {
+    fn drop(&mut self) {
+        STATS.lock().unwrap().0.add(&self.stats);
+    }
+}
+
+/// Render bundle being recorded, whose counts are kept with it.
+pub struct CountedBundle<'a> {
+    encoder: RenderBundleEncoder<'a>,
+    stats: DrawStats,
+}
+
+impl<'a> CountedBundle<'a> {
+    pub fn new(device: &'a Device, desc: &RenderBundleEncoderDescriptor) -> Self {
+        Self {
+            encoder: device.create_render_bundle_encoder(desc),
+            stats: DrawStats::default(),
+        }
+    }
+
+    pub fn finish(self, label: Option<&str>) -> FinishedBundle {
+        FinishedBundle {
+            bundle: self.encoder.finish(&RenderBundleDescriptor { label }),
+            stats: self.stats,
+        }
+    }
+
+    fn count_draw(&mut self, vertices: usize, instances: usize) {
+        self.stats.draws += 1;
+        self.stats.triangles += (vertices / 3 * instances) as u64;
+    }
+}
+
+impl<'a> RenderEncoder<'a> for CountedBundle<'a> {
+    fn set_pipeline(&mut self, pipeline: &'a RenderPipeline) {
+        self.stats.pipelines += 1;
+        self.encoder.set_pipeline(pipeline);
+    }
+
+    fn set_bind_group(&mut self, index: u32, bind_group: &'a BindGroup, offsets: &[DynamicOffset]) {
+        self.stats.bind_groups += 1;
+        self.encoder.set_bind_group(index, bind_group, offsets);
+    }
+
+    fn set_vertex_buffer(&mut self, slot: u32, buffer_slice: BufferSlice<'a>) {
+        self.encoder.set_vertex_buffer(slot, buffer_slice);
+    }
+
+    fn set_index_buffer(&mut self, buffer_slice: BufferSlice<'a>) {
+        self.encoder.set_index_buffer(buffer_slice);
+    }
+
+    fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
+        self.count_draw(vertices.len(), instances.len());
+        self.encoder.draw(vertices, instances);
+    }
+
+    fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        self.count_draw(indices.len(), instances.len());
+        self.encoder.draw_indexed(indices, base_vertex, instances);
+    }
+}
+
+/// Recorded render bundle, and the counts added to the frames it's executed
+/// in.
+pub struct FinishedBundle {
+    bundle: RenderBundle,
+    stats: DrawStats,
+}
@@ -0,0 +1,342 @@
+//! Auto exposure, from a luminance histogram of the HDR scene.
+//!
+//! A first compute pass bins the log2 luminance of every pixel, counting in
+//! workgroup memory before adding to the histogram. A second one averages
+//! the histogram (clearing it for the next frame) and eases the luminance
+//! the camera is adapted to towards it, then writes the exposure that takes
+//! that luminance to middle grey. The composite scales the scene by it
+//! before tone mapping, the exposure never leaving the GPU.
+use crate::{
+    draw_stats,
+    layout::Layout,
+    memory::{self, Category, Tracked},
+    ping_pong::PingPong,
+    shaders,
+};
+use bytemuck::{Pod, Zeroable};
+use imgui::{im_str, Slider, SliderFlags, Ui};
+use wgpu::{
+    util::{make_spirv, BufferInitDescriptor},
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+    BufferDescriptor, BufferSize, BufferUsage, CommandEncoder, ComputePipeline,
+    ComputePipelineDescriptor, Device, FilterMode, PipelineLayoutDescriptor,
+    ProgrammableStageDescriptor, Queue, Sampler, SamplerDescriptor, ShaderModule, ShaderStage,
+    TextureComponentType, TextureViewDimension,
+};
+
+/// Must match the `local_size_x` and `local_size_y` of `histogram.comp`.
+const WORKGROUP_SIZE: u32 = 16;
+
+/// Bins of the histogram, the first one for black pixels.
+const BINS: usize = 256;
+
+/// Range of the histogram, in log2 luminance.
+const MIN_LOG_LUMINANCE: f32 = -10.0;
+const MAX_LOG_LUMINANCE: f32 = 4.0;
+
+gpu_struct! {
+    struct ExposureUniforms {
+        min_log_luminance: f32,
+        log_luminance_range: f32,
+        adaptation: f32,
+        compensation: f32,
+        manual_exposure: f32,
+        manual: bool,
+    }
+}
+
+/// Luminance the camera is adapted to, and the exposure it was given.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct ExposureState {
+    luminance: f32,
+    exposure: f32,
+}
+
+/// Exposure of the camera, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ExposureParams {
+    /// Unset to use `manual` instead of the histogram.
+    pub auto: bool,
+    /// Added to the auto exposure, in EV.
+    pub compensation: f32,
+    /// How quickly the auto exposure adapts, per second.
+    pub speed: f32,
+    /// Exposure without auto exposure, in EV.
+    pub manual: f32,
+}
+
+impl Default for ExposureParams {
+    fn default() -> Self {
+        Self {
+            auto: true,
+            compensation: 0.0,
+            speed: 1.5,
+            manual: 0.0,
+        }
+    }
+}
+
+impl ExposureParams {
+    /// Controls of the auto exposure, or of the manual one without it.
+    pub fn ui(&mut self, ui: &Ui) {
+        ui.checkbox(im_str!("Auto exposure"), &mut self.auto);
+        if self.auto {
+            Slider::new(im_str!("Compensation (EV)"))
+                .range(-4.0..=4.0)
+                .build(ui, &mut self.compensation);
+            Slider::new(im_str!("Adaptation speed"))
+                .range(0.1..=10.0)
+                .flags(SliderFlags::LOGARITHMIC)
+                .build(ui, &mut self.speed);
+        } else {
+            Slider::new(im_str!("Exposure (EV)"))
+                .range(-8.0..=8.0)
+                .build(ui, &mut self.manual);
+        }
+    }
+}
+
+pub struct ExposureTargets {
+    /// Bind groups of the histogram, reading either HDR target.
+    histogram: [BindGroup; 2],
+    workgroups: (u32, u32),
+}
+
+pub struct AutoExposure {
+    uniform: Tracked<Buffer>,
+    histogram: Tracked<Buffer>,
+    state: Tracked<Buffer>,
+    sampler: Sampler,
+    histogram_layout: BindGroupLayout,
+    histogram_pipeline: ComputePipeline,
+    average_bind_group: BindGroup,
+    average_pipeline: ComputePipeline,
+}
+
+impl AutoExposure {
+    pub fn new(device: &Device) -> Self {
+        let histogram_module = device.create_shader_module(make_spirv(shaders::HISTOGRAM_COMP));
+        let average_module = device.create_shader_module(make_spirv(shaders::EXPOSURE_COMP));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Exposure uniforms"),
+                size: ExposureUniforms::STD140.size as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        // zeroed, as the average pass leaves it
+        let histogram = memory::create_buffer_init(
+            device,
+            Category::Storage,
+            &BufferInitDescriptor {
+                label: Some("Luminance histogram"),
+                contents: bytemuck::cast_slice(&[0u32; BINS]),
+                usage: BufferUsage::STORAGE,
+            },
+        );
+        // not adapted to anything yet
+        let state = memory::create_buffer_init(
+            device,
+            Category::Storage,
+            &BufferInitDescriptor {
+                label: Some("Exposure"),
+                contents: bytemuck::bytes_of(&ExposureState {
+                    luminance: 0.0,
+                    exposure: 1.0,
+                }),
+                usage: BufferUsage::STORAGE,
+            },
+        );
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Histogram sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let uniform_entry = BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStage::COMPUTE,
+            ty: BindingType::UniformBuffer {
+                dynamic: false,
+                min_binding_size: BufferSize::new(ExposureUniforms::STD140.size as _),
+            },
+            count: None,
+        };
+        let storage = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStage::COMPUTE,
+            ty: BindingType::StorageBuffer {
+                dynamic: false,
+                min_binding_size: None,
+                readonly: false,
+            },
+            count: None,
+        };
+        let histogram_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Histogram bind group layout"),
+            entries: &[
+                uniform_entry.clone(),
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::SampledTexture {
+                        dimension: TextureViewDimension::D2,
+                        component_type: TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+                storage(3),
+            ],
+        });
+        let average_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Exposure bind group layout"),
+            entries: &[uniform_entry, storage(1), storage(2)],
+        });
+        let average_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Exposure bind group"),
+            layout: &average_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(uniform.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Buffer(histogram.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Buffer(state.slice(..)),
+                },
+            ],
+        });
+
+        let pipeline = |label, layout, module: &ShaderModule| {
+            let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                compute_stage: ProgrammableStageDescriptor {
+                    module,
+                    entry_point: "main",
+                },
+            })
+        };
+        let histogram_pipeline =
+            pipeline("Histogram pipeline", &histogram_layout, &histogram_module);
+        let average_pipeline = pipeline("Exposure pipeline", &average_layout, &average_module);
+
+        Self {
+            uniform,
+            histogram,
+            state,
+            sampler,
+            histogram_layout,
+            histogram_pipeline,
+            average_bind_group,
+            average_pipeline,
+        }
+    }
+
+    /// Exposure the composite scales the scene by, a `float luminance` and a
+    /// `float exposure`.
+    pub fn buffer(&self) -> &Buffer {
+        &self.state
+    }
+
+    /// Targets for the scene drawn into `hdr`.
+    pub fn targets(&self, device: &Device, hdr: &PingPong) -> ExposureTargets {
+        let histogram = hdr.directions(|source, _| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Histogram bind group"),
+                layout: &self.histogram_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(self.uniform.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::Buffer(self.histogram.slice(..)),
+                    },
+                ],
+            })
+        });
+        let (width, height) = hdr.size();
+        ExposureTargets {
+            histogram,
+            workgroups: (
+                width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+            ),
+        }
+    }
+
+    /// Records the exposure of the scene last written to `hdr`, `time_delta`
+    /// seconds after the previous one.
+    pub fn run(
+        &self,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        params: &ExposureParams,
+        time_delta: f32,
+        targets: &ExposureTargets,
+        hdr: &PingPong,
+    ) {
+        let uniforms = ExposureUniforms {
+            min_log_luminance: MIN_LOG_LUMINANCE,
+            log_luminance_range: MAX_LOG_LUMINANCE - MIN_LOG_LUMINANCE,
+            adaptation: 1.0 - (-time_delta * params.speed).exp(),
+            compensation: params.compensation,
+            manual_exposure: params.manual,
+            manual: !params.auto,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, &uniforms.std140_bytes());
+
+        // the histogram keeps being built with manual exposure, so switching
+        // back to auto exposure doesn't start from an old luminance
+        let mut pass = encoder.begin_compute_pass();
+        pass.push_debug_group("Luminance histogram");
+        pass.set_pipeline(&self.histogram_pipeline);
+        pass.set_bind_group(0, hdr.current(&targets.histogram), &[]);
+        let (x, y) = targets.workgroups;
+        pass.dispatch(x, y, 1);
+        pass.pop_debug_group();
+        drop(pass);
+        // in a pass of its own, to see the whole histogram
+        let mut pass = encoder.begin_compute_pass();
+        pass.push_debug_group("Exposure");
+        pass.set_pipeline(&self.average_pipeline);
+        pass.set_bind_group(0, &self.average_bind_group, &[]);
+        pass.dispatch(1, 1, 1);
+        pass.pop_debug_group();
+    }
+}
@@ -0,0 +1,277 @@
+//! Minimal OpenEXR reader.
+//!
+//! Only what's needed for environment maps: single part scanline images with
+//! half, float or uint channels, either uncompressed or with RLE, ZIPS or ZIP
+//! compression.
+use std::{fmt, io, path::Path};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+/// Version flags of images that aren't single part scanline images.
+const TILED: u32 = 0x200;
+const NON_IMAGE: u32 = 0x800;
+const MULTIPART: u32 = 0x1000;
+
+#[derive(Debug)]
+pub enum ExrError {
+    Io(io::Error),
+    Invalid(&'static str),
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for ExrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExrError::Io(err) => write!(f, "{}", err),
+            ExrError::Invalid(reason) => write!(f, "{}", reason),
+            ExrError::Unsupported(what) => write!(f, "{} aren't supported", what),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PixelType {
+    Uint,
+    Half,
+    Float,
+}
+
+impl PixelType {
+    fn size(self) -> usize {
+        match self {
+            PixelType::Half => 2,
+            _ => 4,
+        }
+    }
+}
+
+struct Channel {
+    name: String,
+    pixel_type: PixelType,
+}
+
+/// Reads the bytes of a file front to back.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ExrError> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + len)
+            .ok_or(ExrError::Invalid("Truncated file"))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, ExrError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, ExrError> {
+        Ok(self.u32()? as i32)
+    }
+
+    fn u64(&mut self) -> Result<u64, ExrError> {
+        Ok(self.u32()? as u64 | (self.u32()? as u64) << 32)
+    }
+
+    fn string(&mut self) -> Result<String, ExrError> {
+        let rest = &self.bytes[self.offset.min(self.bytes.len())..];
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(ExrError::Invalid("Unterminated string"))?;
+        let string = String::from_utf8_lossy(&rest[..len]).into_owned();
+        self.offset += len + 1;
+        Ok(string)
+    }
+}
+
+/// Loads the RGB channels of an image (or the luminance, as gray), returning
+/// its size and linear pixels, row by row from the top.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<(u32, u32, Vec<[f32; 3]>), ExrError> {
+    let bytes = std::fs::read(path).map_err(ExrError::Io)?;
+    let mut reader = Reader {
+        bytes: &bytes,
+        offset: 0,
+    };
+    if reader.take(4)? != MAGIC {
+        return Err(ExrError::Invalid("Not an OpenEXR file"));
+    }
+    let version = reader.u32()?;
+    if version & TILED != 0 {
+        return Err(ExrError::Unsupported("Tiled images"));
+    }
+    if version & (NON_IMAGE | MULTIPART) != 0 {
+        return Err(ExrError::Unsupported("Deep and multipart images"));
+    }
+
+    let mut channels = Vec::new();
+    let mut compression = None;
+    let mut data_window = None;
+    loop {
+        let name = reader.string()?;
+        if name.is_empty() {
+            break;
+        }
+        let _type = reader.string()?;
+        let size = reader.u32()? as usize;
+        let mut value = Reader {
+            bytes: reader.take(size)?,
+            offset: 0,
+        };
+        match name.as_str() {
+            "channels" => loop {
+                let name = value.string()?;
+                if name.is_empty() {
+                    break;
+                }
+                let pixel_type = match value.u32()? {
+                    0 => PixelType::Uint,
+                    1 => PixelType::Half,
+                    2 => PixelType::Float,
+                    _ => return Err(ExrError::Invalid("Unknown pixel type")),
+                };
+                // linear flag and reserved bytes
+                value.take(4)?;
+                if value.i32()? != 1 || value.i32()? != 1 {
+                    return Err(ExrError::Unsupported("Subsampled channels"));
+                }
+                channels.push(Channel { name, pixel_type });
+            },
+            "compression" => compression = Some(value.take(1)?[0]),
+            "dataWindow" => {
+                let (x_min, y_min) = (value.i32()?, value.i32()?);
+                let (x_max, y_max) = (value.i32()?, value.i32()?);
+                data_window = Some((x_min, y_min, x_max, y_max));
+            }
+            _ => {}
+        }
+    }
+
+    let (x_min, y_min, x_max, y_max) = data_window.ok_or(ExrError::Invalid("No data window"))?;
+    let width = (x_max - x_min + 1) as usize;
+    let height = (y_max - y_min + 1) as usize;
+    let lines_per_chunk = match compression {
+        Some(0) | Some(1) | Some(2) => 1,
+        Some(3) => 16,
+        Some(_) => return Err(ExrError::Unsupported("PIZ, PXR24, B44 and DWA compression")),
+        None => return Err(ExrError::Invalid("No compression attribute")),
+    };
+
+    // channels are stored in the order of the list, which is sorted by name
+    let find = |wanted: &str| {
+        channels
+            .iter()
+            .position(|c| c.name.rsplit('.').next() == Some(wanted))
+    };
+    let rgb = match (find("R"), find("G"), find("B"), find("Y")) {
+        (Some(r), Some(g), Some(b), _) => [r, g, b],
+        (_, _, _, Some(y)) => [y, y, y],
+        _ => return Err(ExrError::Invalid("No RGB or luminance channels")),
+    };
+    // byte offset of each channel in a line
+    let mut channel_offsets = Vec::with_capacity(channels.len());
+    let mut line_size = 0;
+    for channel in &channels {
+        channel_offsets.push(line_size);
+        line_size += channel.pixel_type.size() * width;
+    }
+
+    let chunks = height.div_ceil(lines_per_chunk);
+    let mut offsets = Vec::with_capacity(chunks);
+    for _ in 0..chunks {
+        offsets.push(reader.u64()? as usize);
+    }
+
+    let mut pixels = vec![[0.0; 3]; width * height];
+    for offset in offsets {
+        let mut chunk = Reader {
+            bytes: &bytes,
+            offset,
+        };
+        let first_line = (chunk.i32()? - y_min) as usize;
+        let size = chunk.u32()? as usize;
+        let lines = lines_per_chunk.min(height.saturating_sub(first_line));
+        let expected = lines * line_size;
+        let data = chunk.take(size)?;
+        // chunks that compression wouldn't make smaller are stored as they are
+        let data = if compression == Some(0) || size == expected {
+            data.to_vec()
+        } else if compression == Some(1) {
+            reorder(&unrle(data)?)
+        } else {
+            let inflated = miniz_oxide::inflate::decompress_to_vec_zlib(data)
+                .map_err(|_| ExrError::Invalid("Corrupt ZIP data"))?;
+            reorder(&inflated)
+        };
+        if data.len() < expected {
+            return Err(ExrError::Invalid("Truncated chunk"));
+        }
+
+        for line in 0..lines {
+            let y = first_line + line;
+            let line = &data[line * line_size..(line + 1) * line_size];
+            for (channel, &index) in rgb.iter().enumerate() {
+                let pixel_type = channels[index].pixel_type;
+                let values = &line[channel_offsets[index]..];
+                for x in 0..width {
+                    let v = &values[x * pixel_type.size()..];
+                    pixels[y * width + x][channel] = match pixel_type {
+                        PixelType::Half => {
+                            half::f16::from_bits(u16::from_le_bytes([v[0], v[1]])).to_f32()
+                        }
+                        PixelType::Float => f32::from_le_bytes([v[0], v[1], v[2], v[3]]),
+                        PixelType::Uint => u32::from_le_bytes([v[0], v[1], v[2], v[3]]) as f32,
+                    };
+                }
+            }
+        }
+    }
+    Ok((width as _, height as _, pixels))
+}
+
+/// Undoes the run length encoding of RLE compression.
+fn unrle(data: &[u8]) -> Result<Vec<u8>, ExrError> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let count = data[i] as i8;
+        i += 1;
+        if count < 0 {
+            let len = -(count as i32) as usize;
+            let literal = data
+                .get(i..i + len)
+                .ok_or(ExrError::Invalid("Corrupt RLE data"))?;
+            out.extend_from_slice(literal);
+            i += len;
+        } else {
+            let value = *data.get(i).ok_or(ExrError::Invalid("Corrupt RLE data"))?;
+            out.extend(std::iter::repeat_n(value, count as usize + 1));
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+/// Undoes the delta predictor and the split of the bytes into two halves,
+/// shared by RLE and ZIP compression.
+fn reorder(data: &[u8]) -> Vec<u8> {
+    let mut deltas = data.to_vec();
+    for i in 1..deltas.len() {
+        deltas[i] = deltas[i - 1].wrapping_add(deltas[i]).wrapping_sub(128);
+    }
+    let (first, second) = deltas.split_at(deltas.len().div_ceil(2));
+    let mut out = Vec::with_capacity(data.len());
+    for (i, &a) in first.iter().enumerate() {
+        out.push(a);
+        if let Some(&b) = second.get(i) {
+            out.push(b);
+        }
+    }
+    out
+}
@@ -0,0 +1,295 @@
+//! Compute image filters.
+use crate::{
+    draw_stats,
+    memory::{self, Category, Tracked},
+    ping_pong::PingPong,
+    shaders,
+};
+use bytemuck::{Pod, Zeroable};
+use imgui::{im_str, ComboBox, ImString, Slider, Ui};
+use wgpu::{
+    util::make_spirv, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferDescriptor, BufferSize,
+    BufferUsage, CommandEncoder, ComputePipeline, ComputePipelineDescriptor, Device, Extent3d,
+    Origin3d, PipelineLayoutDescriptor, ProgrammableStageDescriptor, Queue, SamplerDescriptor,
+    ShaderStage, Texture, TextureComponentType, TextureCopyView, TextureFormat, TextureUsage,
+    TextureView, TextureViewDimension,
+};
+
+/// Must match the `local_size` of `filter.comp`.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Format of the filtered images.
+pub const FILTER_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// Convolution kernels, in the same order as the constants in `filter.comp`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    GaussianBlur,
+    Sobel,
+    Sharpen,
+}
+
+impl Kernel {
+    pub const ALL: [Kernel; 3] = [Kernel::GaussianBlur, Kernel::Sobel, Kernel::Sharpen];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kernel::GaussianBlur => "Gaussian blur",
+            Kernel::Sobel => "Sobel",
+            Kernel::Sharpen => "Sharpen",
+        }
+    }
+}
+
+/// Parameters of the filter, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct FilterParams {
+    pub kernel: Kernel,
+    /// Radius of the blur, in pixels.
+    pub radius: i32,
+    /// Standard deviation of the blur, in pixels.
+    pub sigma: f32,
+    /// Strength of the edges (sobel) or the sharpening.
+    pub strength: f32,
+    /// Times the kernel is applied, each time to the result of the last.
+    pub iterations: i32,
+}
+
+impl Default for FilterParams {
+    fn default() -> Self {
+        Self {
+            kernel: Kernel::GaussianBlur,
+            radius: 4,
+            sigma: 2.0,
+            strength: 1.0,
+            iterations: 1,
+        }
+    }
+}
+
+impl FilterParams {
+    /// Controls of the kernel, only those it uses.
+    pub fn ui(&mut self, ui: &Ui) {
+        let names: Vec<_> = Kernel::ALL
+            .iter()
+            .map(|k| ImString::new(k.name()))
+            .collect();
+        let names: Vec<_> = names.iter().collect();
+        let mut kernel = Kernel::ALL.iter().position(|&k| k == self.kernel).unwrap();
+        if ComboBox::new(im_str!("Kernel")).build_simple_string(ui, &mut kernel, &names) {
+            self.kernel = Kernel::ALL[kernel];
+        }
+        match self.kernel {
+            Kernel::GaussianBlur => {
+                Slider::new(im_str!("Radius"))
+                    .range(0..=16)
+                    .build(ui, &mut self.radius);
+                Slider::new(im_str!("Sigma"))
+                    .range(0.5..=8.0)
+                    .build(ui, &mut self.sigma);
+            }
+            Kernel::Sobel | Kernel::Sharpen => {
+                Slider::new(im_str!("Strength"))
+                    .range(0.0..=4.0)
+                    .build(ui, &mut self.strength);
+            }
+        }
+        Slider::new(im_str!("Iterations"))
+            .range(1..=8)
+            .build(ui, &mut self.iterations);
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct FilterUniforms {
+    kernel: u32,
+    radius: i32,
+    sigma: f32,
+    strength: f32,
+}
+
+/// Filters a source texture into a texture of the same size.
+pub struct ImageFilter {
+    width: u32,
+    height: u32,
+    uniform: Tracked<Buffer>,
+    /// Reads the source and writes the first target.
+    first: BindGroup,
+    /// Read one target and write the other.
+    chain: [BindGroup; 2],
+    targets: PingPong,
+    pipeline: ComputePipeline,
+}
+
+impl ImageFilter {
+    pub fn new(device: &Device, source: &TextureView, width: u32, height: u32) -> Self {
+        let module = device.create_shader_module(make_spirv(shaders::FILTER_COMP));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Filter uniforms"),
+                size: std::mem::size_of::<FilterUniforms>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let sampler = device.create_sampler(&SamplerDescriptor::default());
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Filter bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(
+                            std::mem::size_of::<FilterUniforms>() as _
+                        ),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::SampledTexture {
+                        dimension: TextureViewDimension::D2,
+                        component_type: TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        dimension: TextureViewDimension::D2,
+                        format: FILTER_FORMAT,
+                        readonly: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = |source: &TextureView, output: &TextureView| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Filter bind group"),
+                layout: &layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(uniform.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(output),
+                    },
+                ],
+            })
+        };
+        let targets = PingPong::new(
+            device,
+            "Filter target",
+            width,
+            height,
+            FILTER_FORMAT,
+            TextureUsage::SAMPLED | TextureUsage::STORAGE | TextureUsage::COPY_SRC,
+        );
+        let first = bind_group(source, targets.write());
+        let chain = targets.directions(bind_group);
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Filter pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Filter pipeline"),
+            layout: Some(&pipeline_layout),
+            compute_stage: ProgrammableStageDescriptor {
+                module: &module,
+                entry_point: "main",
+            },
+        });
+
+        Self {
+            width,
+            height,
+            uniform,
+            first,
+            chain,
+            targets,
+            pipeline,
+        }
+    }
+
+    /// Records the filter passes with the given parameters, and the copy of
+    /// their result to `output`, a [`FILTER_FORMAT`] texture of the size of
+    /// the source with the `COPY_DST` usage.
+    pub fn run(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        params: &FilterParams,
+        output: &Texture,
+    ) {
+        let uniforms = FilterUniforms {
+            kernel: params.kernel as u32,
+            radius: params.radius,
+            sigma: params.sigma,
+            strength: params.strength,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, bytemuck::bytes_of(&uniforms));
+
+        let mut pass = encoder.begin_compute_pass();
+        pass.push_debug_group("Image filter");
+        pass.set_pipeline(&self.pipeline);
+        self.targets.reset();
+        for i in 0..params.iterations.max(1) {
+            let bind_group = if i == 0 {
+                &self.first
+            } else {
+                self.targets.current(&self.chain)
+            };
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch(
+                self.width.div_ceil(WORKGROUP_SIZE),
+                self.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+            self.targets.swap();
+        }
+        pass.pop_debug_group();
+        drop(pass);
+
+        let copy_view = |texture| TextureCopyView {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+        };
+        encoder.copy_texture_to_texture(
+            copy_view(self.targets.read_texture()),
+            copy_view(output),
+            Extent3d {
+                width: self.width,
+                height: self.height,
+                depth: 1,
+            },
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! Analytic fog, from the depth buffer of the scene.
+//!
+//! The distance to every pixel is found from its depth, and the fog grows
+//! with it linearly or exponentially. With a height falloff the density
+//! thins out above the fog height, averaged along the way to the pixel in
+//! closed form. The sky is at the far plane, and only fogged if asked to.
+use crate::{
+    draw_stats,
+    layout::Layout,
+    memory::{self, Category, Tracked},
+    pass::PassBuilder,
+    ping_pong::PingPong,
+    post::{self, PostFrame},
+    shaders,
+};
+use glam::{Mat4, Vec3};
+use imgui::{im_str, ColorEdit, ComboBox, ImString, Slider, SliderFlags, Ui};
+use wgpu::{
+    util::make_spirv, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferDescriptor, BufferSize, BufferUsage, Color, CommandEncoder, Device, FilterMode,
+    LoadOp, Queue, RenderPipeline, Sampler, SamplerDescriptor, ShaderStage, TextureView,
+};
+
+/// Fog modes selectable from the UI.
+pub const FOG_MODES: [FogMode; 3] = [FogMode::Linear, FogMode::Exp, FogMode::Exp2];
+
+/// Same values as the defines of the shader.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FogMode {
+    /// From none at the start distance to full at the end distance.
+    Linear = 0,
+    Exp = 1,
+    /// Thinner close to the start distance, then thicker than `Exp`.
+    Exp2 = 2,
+}
+
+impl FogMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            FogMode::Linear => "Linear",
+            FogMode::Exp => "Exponential",
+            FogMode::Exp2 => "Exponential squared",
+        }
+    }
+}
+
+gpu_struct! {
+    struct FogUniforms {
+        inverse_view_projection: Mat4,
+        eye: Vec3,
+        mode: u32,
+        color: Vec3,
+        density: f32,
+        start: f32,
+        end: f32,
+        height: f32,
+        height_falloff: f32,
+        sky: bool,
+    }
+}
+
+/// Fog of the scene, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct FogParams {
+    pub enabled: bool,
+    pub mode: FogMode,
+    /// In HDR units, as the sky is.
+    pub color: Vec3,
+    /// Density of the exponential modes, per unit of distance.
+    pub density: f32,
+    /// Distance the fog starts at, and the one linear fog is full at.
+    pub start: f32,
+    pub end: f32,
+    /// Height above which the fog thins out, by `height_falloff` per unit.
+    /// Zero is the same density everywhere.
+    pub height: f32,
+    pub height_falloff: f32,
+    /// Fogs the sky as if it was at the far plane.
+    pub sky: bool,
+}
+
+impl Default for FogParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: FogMode::Exp,
+            color: Vec3::new(0.5, 0.6, 0.7),
+            density: 0.05,
+            start: 0.0,
+            end: 50.0,
+            height: 0.0,
+            height_falloff: 0.0,
+            sky: true,
+        }
+    }
+}
+
+impl FogParams {
+    /// Controls of the fog, for distances up to `far_plane`.
+    pub fn ui(&mut self, ui: &Ui, far_plane: f32) {
+        ui.checkbox(im_str!("Fog"), &mut self.enabled);
+        let names: Vec<_> = FOG_MODES.iter().map(|m| ImString::new(m.name())).collect();
+        let names: Vec<_> = names.iter().collect();
+        let mut index = FOG_MODES.iter().position(|&m| m == self.mode).unwrap();
+        if ComboBox::new(im_str!("Mode")).build_simple_string(ui, &mut index, &names) {
+            self.mode = FOG_MODES[index];
+        }
+        let mut color: [f32; 3] = self.color.into();
+        if ColorEdit::new(im_str!("Fog color"), &mut color)
+            .hdr(true)
+            .build(ui)
+        {
+            self.color = color.into();
+        }
+        if self.mode == FogMode::Linear {
+            Slider::new(im_str!("Start"))
+                .range(0.0..=far_plane)
+                .build(ui, &mut self.start);
+            Slider::new(im_str!("End"))
+                .range(0.0..=far_plane)
+                .build(ui, &mut self.end);
+        } else {
+            Slider::new(im_str!("Density"))
+                .range(0.001..=1.0)
+                .flags(SliderFlags::LOGARITHMIC)
+                .build(ui, &mut self.density);
+            Slider::new(im_str!("Start"))
+                .range(0.0..=far_plane)
+                .build(ui, &mut self.start);
+        }
+        Slider::new(im_str!("Fog height"))
+            .range(-10.0..=10.0)
+            .build(ui, &mut self.height);
+        Slider::new(im_str!("Height falloff"))
+            .range(0.0..=2.0)
+            .build(ui, &mut self.height_falloff);
+        ui.checkbox(im_str!("Fog the sky"), &mut self.sky);
+    }
+}
+
+pub struct FogTargets {
+    /// Bind groups of the pass, reading either HDR target.
+    fog: [BindGroup; 2],
+}
+
+pub struct Fog {
+    uniform: Tracked<Buffer>,
+    sampler: Sampler,
+    layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+}
+
+impl Fog {
+    pub fn new(device: &Device) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::FOG_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Fog uniforms"),
+                size: FogUniforms::STD140.size as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Fog sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Fog bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(FogUniforms::STD140.size as _),
+                    },
+                    count: None,
+                },
+                post::texture_entry(1),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+                // the depth
+                post::texture_entry(3),
+            ],
+        });
+        let pipeline = post::fullscreen_pipeline(
+            device,
+            "Fog pipeline",
+            &[&layout],
+            &vert_module,
+            &frag_module,
+            post::HDR_FORMAT,
+            BlendDescriptor::REPLACE,
+        );
+
+        Self {
+            uniform,
+            sampler,
+            layout,
+            pipeline,
+        }
+    }
+
+    /// Targets for the scene drawn into `hdr`, with `depth` as the view of
+    /// its depth buffer.
+    pub fn targets(&self, device: &Device, hdr: &PingPong, depth: &TextureView) -> FogTargets {
+        let fog = hdr.directions(|source, _| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Fog bind group"),
+                layout: &self.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(self.uniform.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(depth),
+                    },
+                ],
+            })
+        });
+        FogTargets { fog }
+    }
+
+    /// Records the scene last written to `hdr` with fog into the other
+    /// target.
+    pub fn run(
+        &self,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        params: &FogParams,
+        frame: PostFrame,
+        targets: &FogTargets,
+        hdr: &PingPong,
+    ) {
+        let uniforms = FogUniforms {
+            inverse_view_projection: frame.view_projection.inverse(),
+            eye: frame.eye,
+            mode: params.mode as u32,
+            color: params.color,
+            density: params.density,
+            start: params.start,
+            end: params.end,
+            height: params.height,
+            height_falloff: params.height_falloff,
+            sky: params.sky,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, &uniforms.std140_bytes());
+
+        let mut pass = PassBuilder::new()
+            .color(
+                hdr.write(),
+                post::HDR_FORMAT,
+                LoadOp::Clear(Color::BLACK),
+                true,
+            )
+            .begin(encoder);
+        pass.push_debug_group("Fog");
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, hdr.current(&targets.fog), &[]);
+        pass.draw(0..3, 0..1);
+        pass.pop_debug_group();
+    }
+}
@@ -0,0 +1,282 @@
+//! Resources the CPU writes every frame, one set for every frame in flight.
+//!
+//! Every frame context has a staging buffer uniforms are written into, the
+//! arena they're copied to, bound with dynamic offsets, and a buffer results
+//! of the GPU are copied into to be read back. The ring cycles through the
+//! contexts, and starting a frame on one first waits until the GPU is done
+//! with the last frame that used it. Writes never touch what a submitted frame
+//! still reads, and results are read back without waiting, as long as the GPU
+//! isn't more frames behind than there are contexts.
+//!
+//! wgpu doesn't have query sets yet, the readback buffer is where they would
+//! be resolved to.
+use crate::{
+    draw_stats,
+    latency::Samples,
+    memory::{self, Category, Tracked},
+};
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferAddress, BufferAsyncError,
+    BufferDescriptor, BufferSize, BufferUsage, CommandEncoder, Device, DynamicOffset, Maintain,
+    MapMode, ShaderStage,
+};
+
+/// Bytes of uniforms a frame can upload.
+const ARENA_SIZE: BufferAddress = 64 * 1024;
+
+/// Bytes bound at every offset of the arena, the most an upload can be. A
+/// multiple of the offset alignment.
+pub const BINDING_SIZE: BufferAddress = 256;
+
+/// Bytes a frame can read back.
+pub const READBACK_SIZE: BufferAddress = 256;
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), BufferAsyncError>> + Send>>;
+
+/// Resources of one frame in flight.
+struct FrameContext {
+    staging: Tracked<Buffer>,
+    /// Mapping of the staging buffer, started once the frame is submitted.
+    staging_mapping: Option<MapFuture>,
+    staging_mapped: bool,
+    arena: Tracked<Buffer>,
+    bind_group: BindGroup,
+    /// Bytes of the arena uploaded this frame.
+    used: BufferAddress,
+    readback: Tracked<Buffer>,
+    /// Bytes copied into the readback buffer, and its mapping once submitted.
+    read: Option<(BufferAddress, Option<MapFuture>)>,
+}
+
+impl FrameContext {
+    fn new(device: &Device, layout: &BindGroupLayout, index: usize) -> Self {
+        let staging = memory::create_buffer(
+            device,
+            Category::Staging,
+            &BufferDescriptor {
+                label: Some(&format!("Frame {} staging", index)),
+                size: ARENA_SIZE,
+                usage: BufferUsage::MAP_WRITE | BufferUsage::COPY_SRC,
+                mapped_at_creation: true,
+            },
+        );
+        let arena = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some(&format!("Frame {} uniforms", index)),
+                size: ARENA_SIZE,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some(&format!("Frame {} bind group", index)),
+            layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(arena.slice(..BINDING_SIZE)),
+            }],
+        });
+        let readback = memory::create_buffer(
+            device,
+            Category::Staging,
+            &BufferDescriptor {
+                label: Some(&format!("Frame {} readback", index)),
+                size: READBACK_SIZE,
+                usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        Self {
+            staging,
+            staging_mapping: None,
+            staging_mapped: true,
+            arena,
+            bind_group,
+            used: 0,
+            readback,
+            read: None,
+        }
+    }
+}
+
+pub struct FrameRing {
+    layout: BindGroupLayout,
+    contexts: Vec<FrameContext>,
+    current: usize,
+    /// Milliseconds waiting for the GPU to be done with a context, at the
+    /// start of every frame.
+    pub waits: Samples,
+    /// Frames that had to wait.
+    pub stalls: u32,
+}
+
+impl FrameRing {
+    /// Creates `frames` contexts, at least one.
+    pub fn new(device: &Device, frames: usize) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Frame uniforms layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::VERTEX | ShaderStage::FRAGMENT | ShaderStage::COMPUTE,
+                ty: BindingType::UniformBuffer {
+                    dynamic: true,
+                    min_binding_size: BufferSize::new(BINDING_SIZE),
+                },
+                count: None,
+            }],
+        });
+        let contexts = (0..frames.max(1))
+            .map(|i| FrameContext::new(device, &layout, i))
+            .collect();
+        Self {
+            layout,
+            contexts,
+            current: 0,
+            waits: Samples::default(),
+            stalls: 0,
+        }
+    }
+
+    /// Creates `frames` contexts again, keeping the layout the pipelines were
+    /// created with. What the old ones would have read back is dropped.
+    pub fn resize(&mut self, device: &Device, frames: usize) {
+        self.contexts = (0..frames.max(1))
+            .map(|i| FrameContext::new(device, &self.layout, i))
+            .collect();
+        self.current = 0;
+    }
+
+    pub fn frames(&self) -> usize {
+        self.contexts.len()
+    }
+
+    /// Layout of [`FrameRing::bind_group`], a uniform buffer with a dynamic
+    /// offset at binding 0.
+    pub fn layout(&self) -> &BindGroupLayout {
+        &self.layout
+    }
+
+    /// Starts a frame on the next context, waiting until the GPU is done with
+    /// it. Returns what the last frame on it read back.
+    pub fn begin(&mut self, device: &Device) -> Option<Vec<u8>> {
+        let start = Instant::now();
+        self.current = (self.current + 1) % self.contexts.len();
+        let context = &mut self.contexts[self.current];
+        let mut waited = false;
+        if let Some(mapping) = context.staging_mapping.take() {
+            let (result, wait) = finish(device, mapping);
+            result.expect("Error mapping frame staging buffer");
+            context.staging_mapped = true;
+            waited |= wait;
+        }
+        context.used = 0;
+        let read = match context.read.take() {
+            Some((size, Some(mapping))) => {
+                let (result, wait) = finish(device, mapping);
+                waited |= wait;
+                result.ok().map(|()| {
+                    let bytes =
+                        context.readback.slice(..).get_mapped_range()[..size as usize].to_vec();
+                    context.readback.unmap();
+                    bytes
+                })
+            }
+            _ => None,
+        };
+        if waited {
+            self.stalls += 1;
+        }
+        self.waits.push(start.elapsed().as_secs_f32() * 1000.0);
+        read
+    }
+
+    /// Writes `data` into the arena of this frame, returning the offset to
+    /// bind [`FrameRing::bind_group`] at.
+    pub fn upload(&mut self, data: &[u8]) -> DynamicOffset {
+        let context = &mut self.contexts[self.current];
+        let size = data.len() as BufferAddress;
+        assert!(size <= BINDING_SIZE, "Frame upload of {} bytes", size);
+        assert!(
+            context.used + BINDING_SIZE <= ARENA_SIZE,
+            "Frame uniform arena is full"
+        );
+        assert!(context.staging_mapped, "Frame upload after flushing");
+        let offset = context.used;
+        context
+            .staging
+            .slice(offset..offset + size)
+            .get_mapped_range_mut()
+            .copy_from_slice(data);
+        context.used += BINDING_SIZE;
+        draw_stats::count_upload(data.len());
+        offset as _
+    }
+
+    /// Bind group of the arena of this frame.
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.contexts[self.current].bind_group
+    }
+
+    /// Copies `size` bytes of `buffer` from `offset`, to be read back once the
+    /// ring comes round to this context again. Only one copy is kept a frame.
+    pub fn read_back(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        buffer: &Buffer,
+        offset: BufferAddress,
+        size: BufferAddress,
+    ) {
+        assert!(size <= READBACK_SIZE, "Frame readback of {} bytes", size);
+        let context = &mut self.contexts[self.current];
+        encoder.copy_buffer_to_buffer(buffer, offset, &context.readback, 0, size);
+        context.read = Some((size, None));
+    }
+
+    /// Copies the uniforms uploaded this frame into its arena. Must be
+    /// recorded before the passes reading them.
+    pub fn flush(&mut self, encoder: &mut CommandEncoder) {
+        let context = &mut self.contexts[self.current];
+        if context.used == 0 {
+            return;
+        }
+        context.staging.unmap();
+        context.staging_mapped = false;
+        encoder.copy_buffer_to_buffer(&context.staging, 0, &context.arena, 0, context.used);
+    }
+
+    /// Starts mapping the buffers of this frame again. Must be called after
+    /// submitting the encoder passed to [`FrameRing::flush`].
+    pub fn submitted(&mut self) {
+        let context = &mut self.contexts[self.current];
+        if !context.staging_mapped {
+            let mapping = context.staging.slice(..).map_async(MapMode::Write);
+            context.staging_mapping = Some(Box::pin(mapping));
+        }
+        if let Some((_, mapping @ None)) = &mut context.read {
+            *mapping = Some(Box::pin(
+                context.readback.slice(..).map_async(MapMode::Read),
+            ));
+        }
+    }
+}
+
+/// Waits for `mapping`, returning whether the GPU wasn't done yet.
+fn finish(device: &Device, mut mapping: MapFuture) -> (Result<(), BufferAsyncError>, bool) {
+    let waker = futures::task::noop_waker();
+    let mut context = Context::from_waker(&waker);
+    device.poll(Maintain::Poll);
+    if let Poll::Ready(result) = mapping.as_mut().poll(&mut context) {
+        return (result, false);
+    }
+    device.poll(Maintain::Wait);
+    (futures::executor::block_on(mapping), true)
+}
@@ -0,0 +1,105 @@
+//! Fast approximate antialiasing, as a post-process of the HDR scene.
+//!
+//! Edges are found from the luma of the pixel and its diagonal neighbors,
+//! then the pixel is blurred along them. Luma is tone mapped first, so edges
+//! are found as they end up on screen even though the scene is still HDR.
+use crate::{pass::PassBuilder, ping_pong::PingPong, post, shaders};
+use wgpu::{
+    util::make_spirv, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Color, CommandEncoder, Device, FilterMode, LoadOp, RenderPipeline, Sampler, SamplerDescriptor,
+    ShaderStage,
+};
+
+pub struct FxaaTargets {
+    /// Bind groups of the pass, reading either HDR target.
+    fxaa: [BindGroup; 2],
+}
+
+pub struct Fxaa {
+    sampler: Sampler,
+    layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+}
+
+impl Fxaa {
+    pub fn new(device: &Device) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::FXAA_FRAG));
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("FXAA sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("FXAA bind group layout"),
+            entries: &[
+                post::texture_entry(0),
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline = post::fullscreen_pipeline(
+            device,
+            "FXAA pipeline",
+            &[&layout],
+            &vert_module,
+            &frag_module,
+            post::HDR_FORMAT,
+            BlendDescriptor::REPLACE,
+        );
+
+        Self {
+            sampler,
+            layout,
+            pipeline,
+        }
+    }
+
+    /// Targets for the scene drawn into `hdr`.
+    pub fn targets(&self, device: &Device, hdr: &PingPong) -> FxaaTargets {
+        let fxaa = hdr.directions(|source, _| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("FXAA bind group"),
+                layout: &self.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            })
+        });
+        FxaaTargets { fxaa }
+    }
+
+    /// Records the scene last written to `hdr`, antialiased, into the other
+    /// target.
+    pub fn run(&self, encoder: &mut CommandEncoder, targets: &FxaaTargets, hdr: &PingPong) {
+        let mut pass = PassBuilder::new()
+            .color(
+                hdr.write(),
+                post::HDR_FORMAT,
+                LoadOp::Clear(Color::BLACK),
+                true,
+            )
+            .begin(encoder);
+        pass.push_debug_group("FXAA");
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, hdr.current(&targets.fxaa), &[]);
+        pass.draw(0..3, 0..1);
+        pass.pop_debug_group();
+    }
+}
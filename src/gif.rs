@@ -0,0 +1,217 @@
+//! Animated GIF recording.
+//!
+//! Frames are captured offscreen at a fixed rate for a fixed duration,
+//! downscaled and kept in memory. Once the last one is captured, each frame
+//! gets its own palette of 256 colors (quantized with NeuQuant) and the clip
+//! is encoded as a looping GIF89a.
+use color_quant::NeuQuant;
+use image::{imageops::FilterType, RgbaImage};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Downscaling factors selectable from the UI.
+pub const SCALES: [u32; 3] = [1, 2, 4];
+
+/// Bits per palette index, GIF palettes have at most 256 colors.
+const MIN_CODE_SIZE: u8 = 8;
+
+/// LZW codes are at most 12 bits long.
+const MAX_CODES: u16 = 4096;
+
+#[derive(Clone, Copy)]
+pub struct GifSettings {
+    pub fps: u32,
+    pub seconds: f32,
+    /// Index into [`SCALES`].
+    pub scale: usize,
+}
+
+impl Default for GifSettings {
+    fn default() -> Self {
+        Self {
+            fps: 15,
+            seconds: 3.0,
+            scale: 1,
+        }
+    }
+}
+
+pub struct GifRecorder {
+    width: u32,
+    height: u32,
+    fps: u32,
+    frame_count: usize,
+    frames: Vec<Vec<u8>>,
+    last_capture: Option<Instant>,
+}
+
+impl GifRecorder {
+    /// Starts recording frames of the window, which is `width`x`height`.
+    pub fn new(width: u32, height: u32, settings: &GifSettings) -> Self {
+        let scale = SCALES[settings.scale];
+        Self {
+            width: (width / scale).max(1),
+            height: (height / scale).max(1),
+            fps: settings.fps,
+            frame_count: ((settings.fps as f32 * settings.seconds).round() as usize).max(1),
+            frames: Vec::new(),
+            last_capture: None,
+        }
+    }
+
+    /// Whether the frame rendered at `now` should be captured.
+    pub fn wants_frame(&self, now: Instant) -> bool {
+        match self.last_capture {
+            Some(last) => now - last >= Duration::from_secs(1) / self.fps,
+            None => true,
+        }
+    }
+
+    /// Adds a frame from RGBA8 pixels of the window, captured at `now`.
+    pub fn add_frame(&mut self, now: Instant, width: u32, height: u32, pixels: Vec<u8>) {
+        let pixels = if width == self.width && height == self.height {
+            pixels
+        } else {
+            let image = RgbaImage::from_raw(width, height, pixels).expect("Error reading pixels");
+            image::imageops::resize(&image, self.width, self.height, FilterType::Triangle)
+                .into_raw()
+        };
+        self.frames.push(pixels);
+        self.last_capture = Some(now);
+    }
+
+    /// Frames captured so far and in total.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.frames.len(), self.frame_count)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.frames.len() >= self.frame_count
+    }
+
+    /// Encodes the frames captured so far as a looping GIF.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut gif = Vec::new();
+        gif.extend_from_slice(b"GIF89a");
+        // logical screen, without a global palette
+        gif.extend_from_slice(&(self.width as u16).to_le_bytes());
+        gif.extend_from_slice(&(self.height as u16).to_le_bytes());
+        gif.extend_from_slice(&[0, 0, 0]);
+        // loop forever
+        gif.extend_from_slice(&[0x21, 0xff, 11]);
+        gif.extend_from_slice(b"NETSCAPE2.0");
+        gif.extend_from_slice(&[3, 1, 0, 0, 0]);
+
+        // in hundredths of a second
+        let delay = ((100.0 / self.fps as f32).round() as u16).max(2);
+        for frame in &self.frames {
+            let quant = NeuQuant::new(10, 256, frame);
+            let indices: Vec<u8> = frame
+                .chunks(4)
+                .map(|pixel| quant.index_of(pixel) as u8)
+                .collect();
+            let mut palette = quant.color_map_rgb();
+            palette.resize(3 * 256, 0);
+
+            // graphic control extension, frames replace each other
+            gif.extend_from_slice(&[0x21, 0xf9, 4, 0x04]);
+            gif.extend_from_slice(&delay.to_le_bytes());
+            gif.extend_from_slice(&[0, 0]);
+            // image descriptor with a local palette of 2^8 colors
+            gif.push(0x2c);
+            gif.extend_from_slice(&[0, 0, 0, 0]);
+            gif.extend_from_slice(&(self.width as u16).to_le_bytes());
+            gif.extend_from_slice(&(self.height as u16).to_le_bytes());
+            gif.push(0x80 | (MIN_CODE_SIZE - 1));
+            gif.extend_from_slice(&palette);
+
+            gif.push(MIN_CODE_SIZE);
+            for block in lzw(&indices).chunks(255) {
+                gif.push(block.len() as u8);
+                gif.extend_from_slice(block);
+            }
+            gif.push(0);
+        }
+        gif.push(0x3b);
+        gif
+    }
+}
+
+/// Writes codes of a variable number of bits, least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    len: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.bits |= (code as u32) << self.len;
+        self.len += size;
+        while self.len >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compresses palette `indices` with the variable length LZW of GIF.
+fn lzw(indices: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut writer = BitWriter {
+        bytes: Vec::new(),
+        bits: 0,
+        len: 0,
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut size = MIN_CODE_SIZE as u32 + 1;
+    // codes get a bit longer once the decoder's table outgrows them, which
+    // lags one code behind the encoder's
+    let emit = |writer: &mut BitWriter, code: u16, next: u16, size: &mut u32| {
+        if next as u32 > 1 << *size && *size < 12 {
+            *size += 1;
+        }
+        writer.write(code, *size);
+    };
+
+    writer.write(clear, size);
+    let mut prefix = match indices.first() {
+        Some(&index) => index as u16,
+        None => {
+            writer.write(end, size);
+            return writer.finish();
+        }
+    };
+    for &index in &indices[1..] {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        emit(&mut writer, prefix, next, &mut size);
+        table.insert((prefix, index), next);
+        next += 1;
+        if next == MAX_CODES {
+            emit(&mut writer, clear, next, &mut size);
+            table.clear();
+            next = end + 1;
+            size = MIN_CODE_SIZE as u32 + 1;
+        }
+        prefix = index as u16;
+    }
+    emit(&mut writer, prefix, next, &mut size);
+    // the decoder adds an entry for the last code too
+    emit(&mut writer, end, next + 1, &mut size);
+    writer.finish()
+}
@@ -0,0 +1,230 @@
+//! Debug lines drawn over the scene.
+//!
+//! Lines are queued during the frame and uploaded all at once, like the
+//! labels of `TextRenderer`. They aren't hidden by geometry.
+use crate::{
+    draw_stats::{self, CountedPass},
+    memory::{self, Category, Tracked},
+    shaders,
+    vertex::VertexLayout,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use wgpu::{
+    util::make_spirv, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor, BlendFactor,
+    BlendOperation, Buffer, BufferDescriptor, BufferSize, BufferUsage, ColorStateDescriptor,
+    ColorWrite, CompareFunction, DepthStencilStateDescriptor, Device, IndexFormat, InputStepMode,
+    PipelineLayoutDescriptor, PrimitiveTopology, ProgrammableStageDescriptor, Queue,
+    RenderPipeline, RenderPipelineDescriptor, ShaderStage, StencilStateDescriptor, TextureFormat,
+    VertexStateDescriptor,
+};
+
+/// Maximum number of lines per frame, the rest are dropped.
+const MAX_LINES: usize = 4096;
+
+/// Segments of circles.
+const CIRCLE_SEGMENTS: usize = 32;
+
+vertex_struct! {
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    struct GizmoVertex {
+        position: [f32; 3],
+        color: [f32; 4],
+    }
+}
+
+pub struct Gizmos {
+    vertices: Vec<GizmoVertex>,
+    vertex_count: u32,
+    vertex_buffer: Tracked<Buffer>,
+    uniform: Tracked<Buffer>,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+}
+
+impl Gizmos {
+    pub fn new(device: &Device, color_format: TextureFormat, depth_format: TextureFormat) -> Self {
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Gizmo uniforms"),
+                size: std::mem::size_of::<Mat4>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let vertex_buffer = memory::create_buffer(
+            device,
+            Category::Meshes,
+            &BufferDescriptor {
+                label: Some("Gizmo vertices"),
+                size: (2 * MAX_LINES * std::mem::size_of::<GizmoVertex>()) as _,
+                usage: BufferUsage::VERTEX | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Gizmo bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::VERTEX,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: BufferSize::new(std::mem::size_of::<Mat4>() as _),
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Gizmo bind group"),
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(uniform.slice(..)),
+            }],
+        });
+
+        let vert_module = device.create_shader_module(make_spirv(shaders::GIZMO_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::GIZMO_FRAG));
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Gizmo pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Gizmo pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex_stage: ProgrammableStageDescriptor {
+                module: &vert_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(ProgrammableStageDescriptor {
+                module: &frag_module,
+                entry_point: "main",
+            }),
+            rasterization_state: None,
+            primitive_topology: PrimitiveTopology::LineList,
+            color_states: &[ColorStateDescriptor {
+                format: color_format,
+                color_blend: BlendDescriptor {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::OneMinusSrcAlpha,
+                    operation: BlendOperation::Add,
+                },
+                alpha_blend: BlendDescriptor::REPLACE,
+                write_mask: ColorWrite::ALL,
+            }],
+            depth_stencil_state: Some(DepthStencilStateDescriptor {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilStateDescriptor::default(),
+            }),
+            vertex_state: VertexStateDescriptor {
+                index_format: IndexFormat::Uint16,
+                vertex_buffers: &[GizmoVertex::buffer_descriptor(InputStepMode::Vertex)],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        Self {
+            vertices: Vec::new(),
+            vertex_count: 0,
+            vertex_buffer,
+            uniform,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    /// Queues a line from `a` to `b`.
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: [f32; 4]) {
+        if self.vertices.len() + 2 > 2 * MAX_LINES {
+            return;
+        }
+        self.vertices.extend_from_slice(&[
+            GizmoVertex {
+                position: a.into(),
+                color,
+            },
+            GizmoVertex {
+                position: b.into(),
+                color,
+            },
+        ]);
+    }
+
+    /// Queues a circle around `center`, on the plane perpendicular to
+    /// `normal`.
+    pub fn circle(&mut self, center: Vec3, normal: Vec3, radius: f32, color: [f32; 4]) {
+        let normal = normal.normalize();
+        let other = if normal.y.abs() < 0.99 {
+            Vec3::unit_y()
+        } else {
+            Vec3::unit_x()
+        };
+        let u = normal.cross(other).normalize() * radius;
+        let v = normal.cross(u);
+        let point = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+            center + u * angle.cos() + v * angle.sin()
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+
+    /// Queues the edges of a box of `half_extents` around the origin,
+    /// moved by `transform`.
+    pub fn cuboid(&mut self, transform: Mat4, half_extents: Vec3, color: [f32; 4]) {
+        let corner = |i: usize| {
+            let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+            let offset = Vec3::new(sign(1), sign(2), sign(4)) * half_extents;
+            transform.transform_point3(offset)
+        };
+        // corners one bit apart share an edge
+        for i in 0..8 {
+            for &bit in &[1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    /// Uploads the queued lines, which are cleared for the next frame.
+    pub fn prepare(&mut self, queue: &Queue, view_projection: Mat4) {
+        draw_stats::write_buffer(
+            queue,
+            &self.uniform,
+            0,
+            bytemuck::bytes_of(&view_projection.to_cols_array_2d()),
+        );
+        if !self.vertices.is_empty() {
+            draw_stats::write_buffer(
+                queue,
+                &self.vertex_buffer,
+                0,
+                bytemuck::cast_slice(&self.vertices),
+            );
+        }
+        self.vertex_count = self.vertices.len() as _;
+        self.vertices.clear();
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut CountedPass<'a>) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.draw(0..self.vertex_count, 0..1);
+    }
+}
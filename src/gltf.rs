@@ -0,0 +1,1130 @@
+//! glTF 2.0 scenes, `.gltf` files (with embedded or external buffers) and
+//! `.glb` files.
+//!
+//! Node transforms are baked into the vertices and every primitive becomes a
+//! mesh of its own, except for skinned meshes which are left in the space of
+//! their skin. Meshes of animated nodes are attached to their node by a skin
+//! with a single joint, so the animations move them too. Images are decoded to
+//! RGBA8 by the `image` crate, in the formats it's built with, and sampled
+//! with the material sampler whatever the file says.
+//! Cameras and extensions are ignored.
+use crate::{
+    animation::{
+        Channel, Clip, Interpolation, MorphDelta, Node, Property, Skeleton, Skin, SkinVertex,
+        Transform,
+    },
+    material::{AlphaMode, MaterialArrayBuilder, PbrMaterial},
+    mesh::{MeshData, Vertex},
+};
+use glam::{Mat4, Quat, Vec3, Vec4};
+use log::warn;
+use serde_json::Value as Json;
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_JSON: u32 = 0x4e4f_534a;
+const GLB_BIN: u32 = 0x004e_4942;
+const TRIANGLES: usize = 4;
+/// Nodes nested deeper than this are assumed to be a cycle.
+const MAX_DEPTH: usize = 64;
+/// Values of an accessor without a buffer view, which are all zeros and
+/// aren't bounded by the size of a buffer.
+const MAX_ZEROS: usize = 1 << 24;
+
+#[derive(Debug)]
+pub enum GltfError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Io(err) => write!(f, "{}", err),
+            GltfError::Json(err) => write!(f, "{}", err),
+            GltfError::Invalid(what) => write!(f, "Invalid glTF: {}", what),
+        }
+    }
+}
+
+fn invalid<T>(what: impl Into<String>) -> Result<T, GltfError> {
+    Err(GltfError::Invalid(what.into()))
+}
+
+/// RGBA8 image.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Material of the file, textures are indices of [`Gltf::images`].
+pub struct Material {
+    /// Factors and flags, without textures.
+    pub pbr: PbrMaterial,
+    pub base_color_texture: Option<usize>,
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub emissive_texture: Option<usize>,
+    pub occlusion_texture: Option<usize>,
+}
+
+impl Default for Material {
+    /// The default material of the spec.
+    fn default() -> Self {
+        Self {
+            pbr: PbrMaterial {
+                metallic: 1.0,
+                roughness: 1.0,
+                double_sided: false,
+                ..Default::default()
+            },
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            emissive_texture: None,
+            occlusion_texture: None,
+        }
+    }
+}
+
+pub struct Primitive {
+    pub name: String,
+    /// Mesh in scene space.
+    pub mesh: MeshData,
+    /// Index of [`Gltf::materials`], the default material if `None`.
+    pub material: Option<usize>,
+    /// Skin of the skeleton deforming the mesh.
+    pub skin: Option<usize>,
+    /// Joints and weights of each vertex, if there's a skin.
+    pub skin_vertices: Vec<SkinVertex>,
+    /// Node of the mesh, whose morph target weights might be animated.
+    pub node: usize,
+    /// Offsets of every vertex in each morph target, one target after the
+    /// other.
+    pub morph_deltas: Vec<MorphDelta>,
+    /// Names and default weights of the morph targets.
+    pub morph_names: Vec<String>,
+    pub morph_weights: Vec<f32>,
+}
+
+pub struct Gltf {
+    pub primitives: Vec<Primitive>,
+    pub materials: Vec<Material>,
+    /// Images that couldn't be decoded are `None`.
+    pub images: Vec<Option<Image>>,
+    /// Nodes, skins and animations, if there are skins or animations.
+    pub skeleton: Option<Skeleton>,
+}
+
+impl Gltf {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, GltfError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(GltfError::Io)?;
+        let (json, bin) = if bytes.starts_with(GLB_MAGIC) {
+            split_glb(&bytes)?
+        } else {
+            (bytes.as_slice(), None)
+        };
+        // nesting is limited by serde_json
+        let json: Json = serde_json::from_slice(json).map_err(GltfError::Json)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let buffers = json["buffers"]
+            .elements()
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| match buffer["uri"].as_str() {
+                Some(uri) => read_uri(dir, uri),
+                None if i == 0 => bin
+                    .map(<[u8]>::to_vec)
+                    .ok_or_else(|| GltfError::Invalid("buffer without data".into())),
+                None => invalid("buffer without data"),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let file = File {
+            json: &json,
+            buffers,
+        };
+
+        let images = json["images"]
+            .elements()
+            .iter()
+            .enumerate()
+            .map(|(i, image)| match file.image(dir, image) {
+                Ok(image) => Some(image),
+                Err(err) => {
+                    warn!("Error loading image {} of {}: {}", i, path.display(), err);
+                    None
+                }
+            })
+            .collect();
+        let materials = json["materials"]
+            .elements()
+            .iter()
+            .map(|material| file.material(material))
+            .collect::<Result<_, _>>()?;
+
+        let mut primitives = Vec::new();
+        let mut attached = Vec::new();
+        for node in file.root_nodes() {
+            let root = Parent {
+                world: Mat4::identity(),
+                animated: false,
+                depth: 0,
+            };
+            file.add_node(node, &root, &mut primitives, &mut attached)?;
+        }
+        let skeleton =
+            if json["skins"].elements().is_empty() && json["animations"].elements().is_empty() {
+                None
+            } else {
+                Some(file.skeleton(&attached)?)
+            };
+        Ok(Self {
+            primitives,
+            materials,
+            images,
+            skeleton,
+        })
+    }
+
+    /// Adds the textures of every material to the material arrays, colors to
+    /// `colors` and data to `data`, returning the materials with the layers
+    /// of their textures. Images shared by several materials are added once.
+    pub fn import_materials(
+        &self,
+        colors: &mut MaterialArrayBuilder,
+        data: &mut MaterialArrayBuilder,
+    ) -> Vec<PbrMaterial> {
+        let mut layers = HashMap::new();
+        let mut layer = |image: Option<usize>, srgb: bool| {
+            let image = image?;
+            let pixels = self.images.get(image)?.as_ref()?;
+            let layer = layers.entry((image, srgb)).or_insert_with(|| {
+                let array = if srgb { &mut *colors } else { &mut *data };
+                array.add(pixels.width, pixels.height, pixels.pixels.clone())
+            });
+            Some(*layer)
+        };
+        self.materials
+            .iter()
+            .map(|material| PbrMaterial {
+                base_color_texture: layer(material.base_color_texture, true),
+                metallic_roughness_texture: layer(material.metallic_roughness_texture, false),
+                normal_texture: layer(material.normal_texture, false),
+                emissive_texture: layer(material.emissive_texture, true),
+                occlusion_texture: layer(material.occlusion_texture, false),
+                ..material.pbr.clone()
+            })
+            .collect()
+    }
+}
+
+/// Local transform of a node, from its matrix or its TRS properties.
+fn local_transform(node: &Json) -> Transform {
+    if let Some(matrix) = node["matrix"].as_f32_array::<16>() {
+        return Transform::from_matrix(Mat4::from_cols_array(&matrix));
+    }
+    let [tx, ty, tz] = node["translation"].as_f32_array().unwrap_or([0.0; 3]);
+    let [x, y, z, w] = node["rotation"]
+        .as_f32_array()
+        .unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    let [sx, sy, sz] = node["scale"].as_f32_array().unwrap_or([1.0; 3]);
+    Transform {
+        translation: Vec3::new(tx, ty, tz),
+        rotation: Quat::from_xyzw(x, y, z, w),
+        scale: Vec3::new(sx, sy, sz),
+    }
+}
+
+/// JSON and binary chunks of a `.glb` file.
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), GltfError> {
+    let word = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    if word(4) != Some(2) {
+        return invalid("only version 2 is supported");
+    }
+    let length = (word(8).unwrap_or(0) as usize).min(bytes.len());
+    let (mut json, mut bin) = (None, None);
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let (size, kind) = (word(offset).unwrap() as usize, word(offset + 4).unwrap());
+        let chunk = bytes
+            .get(offset + 8..offset + 8 + size)
+            .ok_or_else(|| GltfError::Invalid("truncated chunk".into()))?;
+        match kind {
+            GLB_JSON => json = Some(chunk),
+            GLB_BIN => bin = Some(chunk),
+            _ => {}
+        }
+        offset += 8 + size;
+    }
+    match json {
+        Some(json) => Ok((json, bin)),
+        None => invalid("no JSON chunk"),
+    }
+}
+
+/// Contents of a `data:` URI or of a file relative to `dir`.
+fn read_uri(dir: &Path, uri: &str) -> Result<Vec<u8>, GltfError> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        return match data.split_once(";base64,") {
+            Some((_, base64)) => decode_base64(base64),
+            None => invalid("data URIs must be base64 encoded"),
+        };
+    }
+    let path: PathBuf = dir.join(percent_decode(uri));
+    std::fs::read(path).map_err(GltfError::Io)
+}
+
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, GltfError> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            b' ' | b'\n' | b'\r' | b'\t' => continue,
+            _ => return invalid("invalid base64"),
+        };
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+/// What a node inherits from its parent.
+struct Parent {
+    world: Mat4,
+    /// Whether an animation moves the parent or one of its ancestors.
+    animated: bool,
+    depth: usize,
+}
+
+/// JSON of a file and the contents of its buffers.
+struct File<'a> {
+    json: &'a Json,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl File<'_> {
+    /// Nodes of the default scene, or every node without a parent if there
+    /// are no scenes.
+    fn root_nodes(&self) -> Vec<usize> {
+        let nodes = self.json["nodes"].elements();
+        let scenes = self.json["scenes"].elements();
+        if !scenes.is_empty() {
+            let scene = self.json["scene"].as_usize().unwrap_or(0);
+            let roots = scenes.get(scene).map_or(&[][..], |s| s["nodes"].elements());
+            return roots.iter().filter_map(Json::as_usize).collect();
+        }
+        let children: Vec<_> = nodes
+            .iter()
+            .flat_map(|node| node["children"].elements())
+            .filter_map(Json::as_usize)
+            .collect();
+        (0..nodes.len()).filter(|i| !children.contains(i)).collect()
+    }
+
+    /// Adds the primitives of a node and its children. Nodes whose meshes are
+    /// attached to them by a skin of their own are added to `attached`.
+    fn add_node(
+        &self,
+        index: usize,
+        parent: &Parent,
+        primitives: &mut Vec<Primitive>,
+        attached: &mut Vec<usize>,
+    ) -> Result<(), GltfError> {
+        if parent.depth > MAX_DEPTH {
+            return invalid("node hierarchy is too deep");
+        }
+        let nodes = self.json["nodes"].elements();
+        let node = nodes
+            .get(index)
+            .ok_or_else(|| GltfError::Invalid("missing node".into()))?;
+        let world = parent.world * local_transform(node).matrix();
+        let animated = parent.animated || self.is_animated(index);
+        // skinned meshes are placed by their joints, not by their node
+        let skin = node["skin"].as_usize();
+        let unbaked = skin.is_some() || animated;
+        let mesh_transform = if unbaked { Mat4::identity() } else { world };
+
+        if let Some(mesh) = node["mesh"].as_usize() {
+            let mesh = self.json["meshes"].elements().get(mesh);
+            let mesh = mesh.ok_or_else(|| GltfError::Invalid("missing mesh".into()))?;
+            let name = node["name"]
+                .as_str()
+                .or_else(|| mesh["name"].as_str())
+                .unwrap_or("glTF mesh");
+            let list = mesh["primitives"].elements();
+            for (i, primitive) in list.iter().enumerate() {
+                let mode = primitive["mode"].as_usize().unwrap_or(TRIANGLES);
+                if mode != TRIANGLES {
+                    warn!(
+                        "Skipping primitive of {}, only triangles are supported",
+                        name
+                    );
+                    continue;
+                }
+                let mesh_data = self.primitive_mesh(primitive, mesh_transform)?;
+                let mut skin_vertices = match skin {
+                    Some(skin) => {
+                        let skin = self.json["skins"].elements().get(skin);
+                        let skin = skin.ok_or_else(|| GltfError::Invalid("missing skin".into()))?;
+                        let joints = skin["joints"].elements().len();
+                        self.skin_vertices(primitive, joints, mesh_data.vertices.len())?
+                    }
+                    None => Vec::new(),
+                };
+                let mut primitive_skin = skin.filter(|_| !skin_vertices.is_empty());
+                if unbaked && primitive_skin.is_none() {
+                    let joint = SkinVertex {
+                        joints: [0; 4],
+                        weights: [1.0, 0.0, 0.0, 0.0],
+                    };
+                    skin_vertices = vec![joint; mesh_data.vertices.len()];
+                    // attached skins come after the ones of the file
+                    let attachment = match attached.iter().position(|&n| n == index) {
+                        Some(attachment) => attachment,
+                        None => {
+                            attached.push(index);
+                            attached.len() - 1
+                        }
+                    };
+                    let skins = self.json["skins"].elements().len();
+                    primitive_skin = Some(skins + attachment);
+                }
+                let morph_deltas =
+                    self.morph_deltas(primitive, mesh_transform, mesh_data.vertices.len())?;
+                let targets = primitive["targets"].elements().len();
+                // default weights are given per mesh, names as extras
+                let morph_weights = (0..targets)
+                    .map(|t| mesh["weights"].elements().get(t).and_then(Json::as_f32))
+                    .map(|weight| weight.unwrap_or(0.0))
+                    .collect();
+                let names = mesh["extras"]["targetNames"].elements();
+                let morph_names = (0..targets)
+                    .map(|t| names.get(t).and_then(Json::as_str).map(str::to_string))
+                    .enumerate()
+                    .map(|(t, name)| name.unwrap_or_else(|| format!("Target {}", t)))
+                    .collect();
+                primitives.push(Primitive {
+                    name: if list.len() > 1 {
+                        format!("{} {}", name, i)
+                    } else {
+                        name.to_string()
+                    },
+                    mesh: mesh_data,
+                    material: primitive["material"].as_usize(),
+                    skin: primitive_skin,
+                    skin_vertices,
+                    node: index,
+                    morph_deltas,
+                    morph_names,
+                    morph_weights,
+                });
+            }
+        }
+
+        let parent = Parent {
+            world,
+            animated,
+            depth: parent.depth + 1,
+        };
+        for child in node["children"].elements() {
+            let child = child.as_usize().unwrap_or(usize::MAX);
+            self.add_node(child, &parent, primitives, attached)?;
+        }
+        Ok(())
+    }
+
+    /// Whether an animation moves the node.
+    fn is_animated(&self, node: usize) -> bool {
+        let channels = self.json["animations"]
+            .elements()
+            .iter()
+            .flat_map(|animation| animation["channels"].elements());
+        channels
+            .map(|channel| &channel["target"])
+            .filter(|target| target["path"].as_str() != Some("weights"))
+            .any(|target| target["node"].as_usize() == Some(node))
+    }
+
+    /// Deltas of the morph targets of a primitive of `count` vertices, in the
+    /// same space as the vertices.
+    fn morph_deltas(
+        &self,
+        primitive: &Json,
+        transform: Mat4,
+        count: usize,
+    ) -> Result<Vec<MorphDelta>, GltfError> {
+        let normal_matrix = transform.inverse().transpose();
+        let mut deltas = Vec::new();
+        for target in primitive["targets"].elements() {
+            let attribute = |name| match target[name].as_usize() {
+                Some(accessor) => {
+                    let accessor = self.accessor(accessor)?;
+                    if accessor.count() != count {
+                        return invalid("morph target of a different length");
+                    }
+                    Ok(Some(accessor))
+                }
+                None => Ok(None),
+            };
+            let positions = attribute("POSITION")?;
+            let normals = attribute("NORMAL")?;
+            let tangents = attribute("TANGENT")?;
+            let delta = |accessor: &Option<Accessor>, matrix: Mat4, i| match accessor {
+                Some(accessor) => Ok(matrix.transform_vector3(accessor.vec3(i)?.into())),
+                None => Ok(Vec3::zero()),
+            };
+            for i in 0..count {
+                deltas.push(MorphDelta {
+                    position: delta(&positions, transform, i)?.extend(0.0).into(),
+                    normal: delta(&normals, normal_matrix, i)?.extend(0.0).into(),
+                    tangent: delta(&tangents, transform, i)?.extend(0.0).into(),
+                });
+            }
+        }
+        Ok(deltas)
+    }
+
+    fn primitive_mesh(&self, primitive: &Json, world: Mat4) -> Result<MeshData, GltfError> {
+        let attributes = &primitive["attributes"];
+        let positions = match attributes["POSITION"].as_usize() {
+            Some(accessor) => self.accessor(accessor)?,
+            None => return invalid("primitive without positions"),
+        };
+        let count = positions.count();
+        // every attribute has an element per vertex
+        let attribute = |name| match attributes[name].as_usize() {
+            Some(accessor) => {
+                let accessor = self.accessor(accessor)?;
+                if accessor.count() != count {
+                    return invalid(format!("{} of a different length than POSITION", name));
+                }
+                Ok(Some(accessor))
+            }
+            None => Ok(None),
+        };
+        let normals = attribute("NORMAL")?;
+        let tangents = attribute("TANGENT")?;
+        let uvs = attribute("TEXCOORD_0")?;
+        let colors = attribute("COLOR_0")?;
+
+        let normal_matrix = world.inverse().transpose();
+        // mirroring transforms flip the winding and the bitangent
+        let mirrored = world.determinant() < 0.0;
+        let mut vertices = vec![Vertex::default(); count];
+        for (i, vertex) in vertices.iter_mut().enumerate() {
+            let position = Vec3::from(positions.vec3(i)?);
+            vertex.position = world.transform_point3(position).into();
+            if let Some(normals) = &normals {
+                let normal = normal_matrix.transform_vector3(normals.vec3(i)?.into());
+                vertex.normal = normal.normalize().into();
+            }
+            if let Some(tangents) = &tangents {
+                let [x, y, z, w] = tangents.vec4(i, 1.0)?;
+                let tangent = world.transform_vector3(Vec3::new(x, y, z)).normalize();
+                let w = if mirrored { -w } else { w };
+                vertex.tangent = [tangent.x, tangent.y, tangent.z, w];
+            }
+            if let Some(uvs) = &uvs {
+                let values = uvs.element(i)?;
+                vertex.uv = [values[0], values[1]];
+            }
+            vertex.color = match &colors {
+                Some(colors) => colors.vec3(i)?,
+                None => [1.0; 3],
+            };
+        }
+
+        let mut indices = match primitive["indices"].as_usize() {
+            Some(accessor) => {
+                let indices = self.accessor(accessor)?;
+                let indices: Vec<_> = indices.values.iter().map(|&i| i as u32).collect();
+                if indices.iter().any(|&i| i as usize >= count) {
+                    return invalid("index out of bounds");
+                }
+                indices
+            }
+            None => (0..count as u32).collect(),
+        };
+        indices.truncate(indices.len() / 3 * 3);
+        if mirrored {
+            for triangle in indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+
+        let mut mesh = MeshData { vertices, indices };
+        if normals.is_none() {
+            mesh.compute_normals();
+        }
+        if tangents.is_none() {
+            mesh.compute_tangents();
+        }
+        Ok(mesh)
+    }
+
+    /// Joints and weights of the `count` vertices of a primitive skinned by
+    /// `joint_count` joints, normalized so the weights add up to one. Empty
+    /// without them.
+    fn skin_vertices(
+        &self,
+        primitive: &Json,
+        joint_count: usize,
+        count: usize,
+    ) -> Result<Vec<SkinVertex>, GltfError> {
+        let attributes = &primitive["attributes"];
+        let (joints, weights) = match (
+            attributes["JOINTS_0"].as_usize(),
+            attributes["WEIGHTS_0"].as_usize(),
+        ) {
+            (Some(joints), Some(weights)) => (self.accessor(joints)?, self.accessor(weights)?),
+            _ => return Ok(Vec::new()),
+        };
+        if joints.count() != count || weights.count() != count {
+            return invalid("joints and weights of a different length than POSITION");
+        }
+        if joints
+            .values
+            .iter()
+            .any(|&joint| joint as usize >= joint_count)
+        {
+            return invalid("joint index out of bounds");
+        }
+        (0..count)
+            .map(|i| {
+                let [a, b, c, d] = joints.element(i)?;
+                let weights = Vec4::from(weights.element(i)?);
+                let sum = weights.dot(Vec4::one());
+                Ok(SkinVertex {
+                    joints: [a as u32, b as u32, c as u32, d as u32],
+                    weights: if sum > 0.0 { weights / sum } else { weights }.into(),
+                })
+            })
+            .collect()
+    }
+
+    /// Every node, with the skins and animations that reference them, and
+    /// the skins attaching meshes to the `attached` nodes.
+    fn skeleton(&self, attached: &[usize]) -> Result<Skeleton, GltfError> {
+        let json_nodes = self.json["nodes"].elements();
+        let mut nodes: Vec<_> = json_nodes
+            .iter()
+            .map(|node| Node {
+                parent: None,
+                rest: local_transform(node),
+            })
+            .collect();
+        for (i, node) in json_nodes.iter().enumerate() {
+            for child in node["children"].elements() {
+                if let Some(child) = child.as_usize().and_then(|child| nodes.get_mut(child)) {
+                    child.parent = Some(i);
+                }
+            }
+        }
+        let node_index = |value: &Json| {
+            value
+                .as_usize()
+                .filter(|&i| i < nodes.len())
+                .ok_or_else(|| GltfError::Invalid("missing node".into()))
+        };
+
+        let mut skins = Vec::new();
+        for skin in self.json["skins"].elements() {
+            let joints = skin["joints"]
+                .elements()
+                .iter()
+                .map(node_index)
+                .collect::<Result<Vec<_>, _>>()?;
+            let inverse_bind = match skin["inverseBindMatrices"].as_usize() {
+                Some(accessor) => {
+                    let matrices = self.accessor(accessor)?;
+                    (0..matrices.count())
+                        .map(|i| matrices.mat4(i))
+                        .collect::<Result<_, _>>()?
+                }
+                None => vec![Mat4::identity(); joints.len()],
+            };
+            if inverse_bind.len() < joints.len() {
+                return invalid("missing inverse bind matrices");
+            }
+            skins.push(Skin {
+                joints,
+                inverse_bind,
+                rigid: false,
+            });
+        }
+        skins.extend(attached.iter().map(|&node| Skin {
+            joints: vec![node],
+            inverse_bind: vec![Mat4::identity()],
+            rigid: true,
+        }));
+
+        let mut clips = Vec::new();
+        for (i, animation) in self.json["animations"].elements().iter().enumerate() {
+            let samplers = animation["samplers"].elements();
+            let mut channels = Vec::new();
+            for channel in animation["channels"].elements() {
+                let target = &channel["target"];
+                let property = match target["path"].as_str() {
+                    Some("translation") => Property::Translation,
+                    Some("rotation") => Property::Rotation,
+                    Some("scale") => Property::Scale,
+                    Some("weights") => Property::Weights,
+                    // extensions
+                    _ => continue,
+                };
+                let sampler = channel["sampler"].as_usize().and_then(|i| samplers.get(i));
+                let sampler =
+                    sampler.ok_or_else(|| GltfError::Invalid("missing sampler".into()))?;
+                let interpolation = match sampler["interpolation"].as_str() {
+                    None | Some("LINEAR") => Interpolation::Linear,
+                    Some("STEP") => Interpolation::Step,
+                    Some("CUBICSPLINE") => Interpolation::CubicSpline,
+                    Some(other) => return invalid(format!("unknown interpolation {}", other)),
+                };
+                let times = self.accessor(sampler["input"].as_usize().unwrap_or(usize::MAX))?;
+                let values = self.accessor(sampler["output"].as_usize().unwrap_or(usize::MAX))?;
+                let per_key = if interpolation == Interpolation::CubicSpline {
+                    3
+                } else {
+                    1
+                };
+                // weights have as many numbers per keyframe as there are targets
+                let keys = times.count() * per_key;
+                let width = values.values.len().checked_div(keys).unwrap_or(0);
+                let expected = match property {
+                    Property::Translation | Property::Scale => width == 3,
+                    Property::Rotation => width == 4,
+                    Property::Weights => width > 0,
+                };
+                if !expected || values.values.len() != keys * width {
+                    return invalid("keyframes without values");
+                }
+                channels.push(Channel {
+                    node: node_index(&target["node"])?,
+                    property,
+                    interpolation,
+                    times: times.values.iter().map(|&t| t as f32).collect(),
+                    values: values.values.iter().map(|&v| v as f32).collect(),
+                    width,
+                });
+            }
+            let duration = channels
+                .iter()
+                .filter_map(|channel| channel.times.last().copied())
+                .fold(0.0, f32::max);
+            clips.push(Clip {
+                name: animation["name"]
+                    .as_str()
+                    .map_or_else(|| format!("Animation {}", i), str::to_string),
+                duration,
+                channels,
+            });
+        }
+
+        Ok(Skeleton {
+            nodes,
+            skins,
+            clips,
+        })
+    }
+
+    /// Decodes an element of `images`.
+    fn image(&self, dir: &Path, image: &Json) -> Result<Image, GltfError> {
+        let bytes = match (image["uri"].as_str(), image["bufferView"].as_usize()) {
+            (Some(uri), _) => read_uri(dir, uri)?,
+            (None, Some(view)) => self.buffer_view(view)?.0.to_vec(),
+            (None, None) => return invalid("image without data"),
+        };
+        // in whatever format the bytes are, mime types are often wrong
+        let image = image::load_from_memory(&bytes)
+            .map_err(|err| GltfError::Invalid(err.to_string()))?
+            .to_rgba8();
+        Ok(Image {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.into_raw(),
+        })
+    }
+
+    fn material(&self, material: &Json) -> Result<Material, GltfError> {
+        let defaults = Material::default();
+        let pbr = &material["pbrMetallicRoughness"];
+        let normal = &material["normalTexture"];
+        let occlusion = &material["occlusionTexture"];
+        let alpha_mode = match material["alphaMode"].as_str() {
+            None | Some("OPAQUE") => AlphaMode::Opaque,
+            Some("MASK") => AlphaMode::Mask(material["alphaCutoff"].as_f32().unwrap_or(0.5)),
+            Some("BLEND") => AlphaMode::Blend,
+            Some(mode) => return invalid(format!("unknown alpha mode {}", mode)),
+        };
+        Ok(Material {
+            pbr: PbrMaterial {
+                base_color: pbr["baseColorFactor"]
+                    .as_f32_array()
+                    .unwrap_or(defaults.pbr.base_color),
+                metallic: pbr["metallicFactor"]
+                    .as_f32()
+                    .unwrap_or(defaults.pbr.metallic),
+                roughness: pbr["roughnessFactor"]
+                    .as_f32()
+                    .unwrap_or(defaults.pbr.roughness),
+                emissive: material["emissiveFactor"]
+                    .as_f32_array()
+                    .unwrap_or(defaults.pbr.emissive),
+                alpha_mode,
+                double_sided: material["doubleSided"].as_bool().unwrap_or(false),
+                normal_scale: normal["scale"].as_f32().unwrap_or(1.0),
+                occlusion_strength: occlusion["strength"].as_f32().unwrap_or(1.0),
+                ..defaults.pbr
+            },
+            base_color_texture: self.texture_image(&pbr["baseColorTexture"]),
+            metallic_roughness_texture: self.texture_image(&pbr["metallicRoughnessTexture"]),
+            normal_texture: self.texture_image(normal),
+            emissive_texture: self.texture_image(&material["emissiveTexture"]),
+            occlusion_texture: self.texture_image(occlusion),
+        })
+    }
+
+    /// Image of a texture info object.
+    fn texture_image(&self, info: &Json) -> Option<usize> {
+        let texture = self.json["textures"]
+            .elements()
+            .get(info["index"].as_usize()?)?;
+        if info["texCoord"].as_usize().unwrap_or(0) != 0 {
+            warn!("Only the first set of texture coordinates is supported");
+        }
+        texture["source"].as_usize()
+    }
+
+    /// Bytes of a buffer view and its stride, if any.
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), GltfError> {
+        let view = self.json["bufferViews"].elements().get(index);
+        let view = view.ok_or_else(|| GltfError::Invalid("missing buffer view".into()))?;
+        let buffer = view["buffer"].as_usize().and_then(|i| self.buffers.get(i));
+        let buffer = buffer.ok_or_else(|| GltfError::Invalid("missing buffer".into()))?;
+        let offset = view["byteOffset"].as_usize().unwrap_or(0);
+        let length = view["byteLength"].as_usize().unwrap_or(0);
+        let bytes = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| GltfError::Invalid("buffer view out of bounds".into()))?;
+        Ok((bytes, view["byteStride"].as_usize()))
+    }
+
+    /// Reads every element of an accessor, normalizing integers if it says
+    /// so.
+    fn accessor(&self, index: usize) -> Result<Accessor, GltfError> {
+        let accessor = self.json["accessors"].elements().get(index);
+        let accessor = accessor.ok_or_else(|| GltfError::Invalid("missing accessor".into()))?;
+        if !accessor["sparse"].is_null() {
+            return invalid("sparse accessors aren't supported");
+        }
+        let count = accessor["count"].as_usize().unwrap_or(0);
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            Some("MAT4") => 16,
+            _ => return invalid("unsupported accessor type"),
+        };
+        let component_type = accessor["componentType"].as_usize().unwrap_or(0);
+        // in bytes, and the value normalized integers are divided by
+        let (size, max) = match component_type {
+            5120 => (1, i8::MAX as f64),
+            5121 => (1, u8::MAX as f64),
+            5122 => (2, i16::MAX as f64),
+            5123 => (2, u16::MAX as f64),
+            5125 | 5126 => (4, 1.0),
+            _ => return invalid("unknown component type"),
+        };
+        let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+
+        // accessors without a buffer view are all zeros
+        let view = match accessor["bufferView"].as_usize() {
+            Some(view) => view,
+            None => {
+                let len = count
+                    .checked_mul(components)
+                    .filter(|&len| len <= MAX_ZEROS)
+                    .ok_or_else(|| GltfError::Invalid("accessor too large".into()))?;
+                return Ok(Accessor {
+                    components,
+                    values: vec![0.0; len],
+                });
+            }
+        };
+        let (bytes, stride) = self.buffer_view(view)?;
+        let element = size * components;
+        let stride = stride.unwrap_or(element);
+        if stride < element {
+            return invalid("byte stride smaller than an element");
+        }
+        let offset = accessor["byteOffset"].as_usize().unwrap_or(0);
+        // the count is checked against the view before allocating anything
+        let end = stride
+            .checked_mul(count.saturating_sub(1))
+            .and_then(|end| end.checked_add(element))
+            .and_then(|end| end.checked_add(offset));
+        if count > 0 && end.is_none_or(|end| end > bytes.len()) {
+            return invalid("accessor out of bounds");
+        }
+
+        let mut values = Vec::with_capacity(count * components);
+        for i in 0..count {
+            for c in 0..components {
+                let at = offset + stride * i + size * c;
+                let b = &bytes[at..at + size];
+                let value = match component_type {
+                    5120 => b[0] as i8 as f64,
+                    5121 => b[0] as f64,
+                    5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                };
+                values.push(if normalized {
+                    (value / max).max(-1.0)
+                } else {
+                    value
+                });
+            }
+        }
+        Ok(Accessor { components, values })
+    }
+}
+
+struct Accessor {
+    components: usize,
+    values: Vec<f64>,
+}
+
+impl Accessor {
+    fn count(&self) -> usize {
+        self.values.len() / self.components
+    }
+
+    /// Values of element `i`, an error past the last one.
+    fn values(&self, i: usize) -> Result<&[f64], GltfError> {
+        let start = self.components * i;
+        self.values
+            .get(start..start + self.components)
+            .ok_or_else(|| GltfError::Invalid("accessor element out of bounds".into()))
+    }
+
+    /// Components of element `i`, up to four of as many as the accessor has.
+    fn element(&self, i: usize) -> Result<[f32; 4], GltfError> {
+        let mut element = [0.0; 4];
+        for (e, v) in element.iter_mut().zip(self.values(i)?) {
+            *e = *v as f32;
+        }
+        Ok(element)
+    }
+
+    /// Element `i` of a `MAT4` accessor.
+    fn mat4(&self, i: usize) -> Result<Mat4, GltfError> {
+        if self.components != 16 {
+            return invalid("inverse bind matrices that aren't MAT4");
+        }
+        let mut matrix = [0.0; 16];
+        for (m, v) in matrix.iter_mut().zip(self.values(i)?) {
+            *m = *v as f32;
+        }
+        Ok(Mat4::from_cols_array(&matrix))
+    }
+
+    fn vec3(&self, i: usize) -> Result<[f32; 3], GltfError> {
+        let [x, y, z, _] = self.element(i)?;
+        Ok([x, y, z])
+    }
+
+    /// Element `i`, with `w` if the accessor only has three components.
+    fn vec4(&self, i: usize, w: f32) -> Result<[f32; 4], GltfError> {
+        let [x, y, z, e] = self.element(i)?;
+        Ok([x, y, z, if self.components == 4 { e } else { w }])
+    }
+}
+
+/// Reads of the JSON of a file, where values of the wrong type are the same
+/// as missing ones.
+trait JsonExt {
+    /// Elements of an array, empty if this isn't one.
+    fn elements(&self) -> &[Json];
+    /// Numbers that are non-negative integers, even if written as `1.0`.
+    fn as_usize(&self) -> Option<usize>;
+    fn as_f32(&self) -> Option<f32>;
+    /// Array of exactly `N` numbers.
+    fn as_f32_array<const N: usize>(&self) -> Option<[f32; N]>;
+}
+
+impl JsonExt for Json {
+    fn elements(&self) -> &[Json] {
+        self.as_array().map_or(&[], Vec::as_slice)
+    }
+
+    fn as_usize(&self) -> Option<usize> {
+        self.as_f64()
+            .filter(|n| *n >= 0.0 && n.fract() == 0.0)
+            .map(|n| n as usize)
+    }
+
+    fn as_f32(&self) -> Option<f32> {
+        self.as_f64().map(|n| n as f32)
+    }
+
+    fn as_f32_array<const N: usize>(&self) -> Option<[f32; N]> {
+        let elements = self.elements();
+        if elements.len() != N {
+            return None;
+        }
+        let mut array = [0.0; N];
+        for (value, element) in array.iter_mut().zip(elements) {
+            *value = element.as_f32()?;
+        }
+        Some(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_uri, File, Gltf, GltfError, MAX_ZEROS};
+    use serde_json::{json, Value as Json};
+    use std::path::Path;
+
+    /// A file with one 8 byte buffer and views of all of it, packed, with a
+    /// stride of two bytes and with a stride of one byte.
+    fn file(json: &Json) -> File<'_> {
+        File {
+            json,
+            buffers: vec![vec![0, 0, 0, 0x3f, 0xff, 0, 0, 0]],
+        }
+    }
+
+    fn accessors(accessors: Json) -> Json {
+        json!({
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 8 },
+                { "buffer": 0, "byteLength": 8, "byteStride": 2 },
+                { "buffer": 0, "byteLength": 8, "byteStride": 1 },
+            ],
+            "accessors": accessors,
+        })
+    }
+
+    #[test]
+    fn data_uris_are_base64() {
+        let dir = Path::new("");
+        let bytes = read_uri(dir, "data:application/octet-stream;base64,AAECAw==").unwrap();
+        assert_eq!(bytes, [0, 1, 2, 3]);
+        assert_eq!(read_uri(dir, "data:;base64,_-8").unwrap(), [0xff, 0xef]);
+        assert!(matches!(
+            read_uri(dir, "data:application/octet-stream,0123"),
+            Err(GltfError::Invalid(_))
+        ));
+        assert!(matches!(
+            read_uri(dir, "data:;base64,AA*A"),
+            Err(GltfError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn accessors_read_strided_and_normalized() {
+        let json = accessors(json!([
+            { "bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR" },
+            {
+                "bufferView": 1, "byteOffset": 3, "componentType": 5121, "normalized": true,
+                "count": 2, "type": "SCALAR",
+            },
+            { "componentType": 5123, "count": 4, "type": "VEC2" },
+        ]));
+        let file = file(&json);
+        let floats = file.accessor(0).unwrap();
+        assert_eq!(floats.values, [0.5, f32::from_bits(0xff) as f64]);
+        let bytes = file.accessor(1).unwrap();
+        assert_eq!(bytes.values, [63.0 / 255.0, 0.0]);
+        let zeros = file.accessor(2).unwrap();
+        assert_eq!((zeros.count(), zeros.values.len()), (4, 8));
+    }
+
+    #[test]
+    fn malformed_accessors_are_invalid() {
+        let json = accessors(json!([
+            // the stride is smaller than a VEC2
+            { "bufferView": 2, "componentType": 5121, "count": 4, "type": "VEC2" },
+            // more elements than the view has room for
+            { "bufferView": 0, "componentType": 5126, "count": 1e12, "type": "VEC4" },
+            { "componentType": 5126, "count": MAX_ZEROS, "type": "MAT4" },
+            { "bufferView": 3, "componentType": 5126, "count": 1, "type": "SCALAR" },
+            { "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC5" },
+        ]));
+        let file = file(&json);
+        for i in 0..6 {
+            assert!(
+                matches!(file.accessor(i), Err(GltfError::Invalid(_))),
+                "accessor {}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn fixtures_load() {
+        for name in &["triangle.gltf", "triangle.glb"] {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("assets")
+                .join(name);
+            let gltf = Gltf::load(&path).unwrap();
+            assert_eq!(gltf.primitives.len(), 1, "{}", name);
+            let mesh = &gltf.primitives[0].mesh;
+            assert_eq!(mesh.indices, [0, 1, 2]);
+            assert_eq!(mesh.vertices[1].position, [1.0, 0.0, -2.0]);
+            assert_eq!(gltf.materials[0].pbr.base_color, [1.0, 0.5, 0.25, 1.0]);
+            assert!(gltf.skeleton.is_none());
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Golden image regression testing.
+//!
+//! Frames are rendered offscreen with a fixed simulation timestep and compared
+//! against reference PNGs. When a frame doesn't match, the rendered image and
+//! a diff image are saved next to the reference. References are only written
+//! when asked for, with `UPDATE_GOLDEN=1`.
+use crate::{
+    memory::{self, Category, Tracked},
+    readback,
+};
+use log::{info, warn};
+use std::path::Path;
+use wgpu::{
+    Device, Extent3d, Queue, Texture, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsage, TextureView, TextureViewDescriptor,
+};
+
+/// Frames that are compared, counting from the first one.
+pub const FRAMES: [u32; 3] = [0, 30, 120];
+
+/// Largest difference of a channel for two pixels to be considered equal.
+const CHANNEL_TOLERANCE: u8 = 8;
+
+/// Fraction of the pixels that may differ before the frame fails.
+const PIXEL_TOLERANCE: f32 = 0.001;
+
+/// Environment variable that saves the rendered frames as the new references
+/// when it's `1`, instead of comparing them.
+pub const UPDATE_VAR: &str = "UPDATE_GOLDEN";
+
+/// Offscreen color target that can be read back to the CPU.
+pub struct Capture {
+    width: u32,
+    height: u32,
+    texture: Tracked<Texture>,
+    view: TextureView,
+}
+
+impl Capture {
+    /// `format` must be a 4 byte BGRA format, the one of the swap chain.
+    pub fn new(device: &Device, width: u32, height: u32, format: TextureFormat) -> Self {
+        let texture = memory::create_texture(
+            device,
+            Category::RenderTargets,
+            &TextureDescriptor {
+                label: Some("Offscreen frame"),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::COPY_SRC,
+            },
+        );
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        Self {
+            width,
+            height,
+            texture,
+            view,
+        }
+    }
+
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    /// Waits for the frame to be rendered and returns it as tightly packed
+    /// RGBA8 pixels.
+    pub fn read(&self, device: &Device, queue: &Queue) -> Vec<u8> {
+        let mut pixels = futures::executor::block_on(readback::read_texture(
+            device,
+            queue,
+            &self.texture,
+            self.width,
+            self.height,
+        ));
+        for bgra in pixels.chunks_mut(4) {
+            bgra.swap(0, 2);
+        }
+        pixels
+    }
+}
+
+/// Compares a rendered frame with its reference in `dir`, returning whether
+/// they match. Frames without a reference are skipped and don't fail,
+/// references are only saved with [`UPDATE_VAR`] set to `1`.
+pub fn compare(dir: &Path, frame: u32, width: u32, height: u32, pixels: &[u8]) -> bool {
+    let path = |suffix| dir.join(format!("frame_{:03}{}.png", frame, suffix));
+    let save = |suffix, pixels: &[u8]| {
+        let path = path(suffix);
+        std::fs::create_dir_all(dir)
+            .unwrap_or_else(|err| panic!("Error creating {}: {}", dir.display(), err));
+        image::save_buffer(&path, pixels, width, height, image::ColorType::Rgba8)
+            .unwrap_or_else(|err| panic!("Error saving {}: {}", path.display(), err));
+    };
+
+    if std::env::var(UPDATE_VAR).is_ok_and(|value| value == "1") {
+        info!("Saving frame {} as its reference", frame);
+        save("", pixels);
+        return true;
+    }
+    let reference = match crate::texture::load_rgba8(path("")) {
+        Ok(reference) => reference,
+        Err(err) => {
+            warn!(
+                "Skipping frame {}, no reference at {} ({}), run with {}=1 to save it",
+                frame,
+                path("").display(),
+                err,
+                UPDATE_VAR
+            );
+            return true;
+        }
+    };
+    if (reference.0, reference.1) != (width, height) {
+        warn!(
+            "Frame {} is {}x{}, the reference is {}x{}",
+            frame, width, height, reference.0, reference.1
+        );
+        save(".actual", pixels);
+        return false;
+    }
+
+    // differing pixels are red, the rest a faded gray version of the frame
+    let mut diff = Vec::with_capacity(pixels.len());
+    let mut differing = 0;
+    for (actual, expected) in pixels.chunks(4).zip(reference.2.chunks(4)) {
+        let differs = actual
+            .iter()
+            .zip(expected)
+            .any(|(a, e)| a.abs_diff(*e) > CHANNEL_TOLERANCE);
+        if differs {
+            differing += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let gray = ((actual[0] as u32 + actual[1] as u32 + actual[2] as u32) / 12) as u8;
+            diff.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+
+    let fraction = differing as f32 / (width * height) as f32;
+    if fraction > PIXEL_TOLERANCE {
+        warn!(
+            "Frame {} differs from the reference ({} pixels, {:.3}%)",
+            frame,
+            differing,
+            fraction * 100.0
+        );
+        save(".actual", pixels);
+        save(".diff", &diff);
+        false
+    } else {
+        info!("Frame {} matches the reference", frame);
+        true
+    }
+}
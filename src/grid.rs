@@ -0,0 +1,161 @@
+//! Infinite grid over the ground plane, drawn with a single fullscreen
+//! triangle.
+//!
+//! Every pixel finds where its view ray crosses the plane, and writes the
+//! depth of that point so the scene hides the grid. Lines are a pixel wide at
+//! any distance, and fade out along with the grid before they alias. The X
+//! axis is drawn red and the Z axis blue.
+use crate::{
+    draw_stats::{self, CountedPass},
+    layout::Layout,
+    memory::{self, Category, Tracked},
+    scene_pipeline::DEPTH_FORMAT,
+    shaders,
+};
+use glam::{Mat4, Vec3};
+use wgpu::{
+    util::make_spirv, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor, BlendFactor,
+    BlendOperation, Buffer, BufferDescriptor, BufferSize, BufferUsage, ColorStateDescriptor,
+    ColorWrite, CompareFunction, DepthStencilStateDescriptor, Device, IndexFormat,
+    PipelineLayoutDescriptor, PrimitiveTopology, ProgrammableStageDescriptor, Queue,
+    RenderPipeline, RenderPipelineDescriptor, ShaderStage, StencilStateDescriptor, TextureFormat,
+    VertexStateDescriptor,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct GridParams {
+    /// Distance between the minor lines, the major ones are ten times apart.
+    pub spacing: f32,
+    /// Distance from the camera where the grid is faded out.
+    pub fade_distance: f32,
+}
+
+impl Default for GridParams {
+    fn default() -> Self {
+        Self {
+            spacing: 1.0,
+            fade_distance: 60.0,
+        }
+    }
+}
+
+gpu_struct! {
+    struct GridUniforms {
+        view_projection: Mat4,
+        inverse_view_projection: Mat4,
+        eye: Vec3,
+        spacing: f32,
+        fade_distance: f32,
+    }
+}
+
+pub struct Grid {
+    uniform: Tracked<Buffer>,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+}
+
+impl Grid {
+    /// Grid drawn into passes of the scene, with its depth buffer.
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::GRID_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Grid uniforms"),
+                size: GridUniforms::STD140.size as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Grid bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: BufferSize::new(GridUniforms::STD140.size as _),
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Grid bind group"),
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(uniform.slice(..)),
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Grid pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Grid pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex_stage: ProgrammableStageDescriptor {
+                module: &vert_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(ProgrammableStageDescriptor {
+                module: &frag_module,
+                entry_point: "main",
+            }),
+            rasterization_state: None,
+            primitive_topology: PrimitiveTopology::TriangleList,
+            color_states: &[ColorStateDescriptor {
+                format,
+                color_blend: BlendDescriptor {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::OneMinusSrcAlpha,
+                    operation: BlendOperation::Add,
+                },
+                alpha_blend: BlendDescriptor::REPLACE,
+                write_mask: ColorWrite::ALL,
+            }],
+            // tested against the scene, but nothing is hidden by it
+            depth_stencil_state: Some(DepthStencilStateDescriptor {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilStateDescriptor::default(),
+            }),
+            vertex_state: VertexStateDescriptor {
+                index_format: IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        Self {
+            uniform,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn update(&self, queue: &Queue, view_projection: Mat4, eye: Vec3, params: &GridParams) {
+        let uniforms = GridUniforms {
+            view_projection,
+            inverse_view_projection: view_projection.inverse(),
+            eye,
+            spacing: params.spacing,
+            fade_distance: params.fade_distance,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, &uniforms.std140_bytes());
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut CountedPass<'a>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
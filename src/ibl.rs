@@ -0,0 +1,651 @@
+//! Image based lighting.
+//!
+//! All the maps are generated once at load time from an equirectangular HDR
+//! environment, following the split sum approximation:
+//!
+//! - An irradiance cubemap for the diffuse term.
+//! - A cubemap prefiltered for increasing roughness in each mip level, for the
+//!   specular term.
+//! - A 2D lookup table of the scale and bias applied to F0 by the BRDF.
+use crate::{
+    exr::{self, ExrError},
+    memory::{self, Category, Tracked},
+    profiler, shaders,
+};
+use bytemuck::{Pod, Zeroable};
+use image::{codecs::hdr::HdrDecoder, ImageError, ImageResult};
+use std::{fmt, fs::File, io::BufReader, num::NonZeroU32, path::Path};
+use wgpu::{
+    util::{make_spirv, BufferInitDescriptor},
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferUsage, Color, ColorStateDescriptor, ColorWrite, CommandEncoder,
+    CommandEncoderDescriptor, Device, Extent3d, FilterMode, IndexFormat, LoadOp, Operations,
+    PipelineLayoutDescriptor, PrimitiveTopology, ProgrammableStageDescriptor, Queue,
+    RenderPassColorAttachmentDescriptor, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, Sampler, SamplerDescriptor, ShaderModule, ShaderStage, Texture,
+    TextureComponentType, TextureDescriptor, TextureDimension, TextureFormat, TextureUsage,
+    TextureView, TextureViewDescriptor, TextureViewDimension, VertexStateDescriptor,
+};
+
+/// Format of every cubemap, and of the targets given to [`Prefilter`].
+pub const CUBE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+const ENVIRONMENT_SIZE: u32 = 256;
+const ENVIRONMENT_MIPS: u32 = 6;
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+const BRDF_SIZE: u32 = 256;
+
+/// Number of mip levels of the prefiltered cubemap. Roughness 1.0 maps to the
+/// last one.
+pub const PREFILTERED_MIPS: u32 = 5;
+
+#[derive(Debug)]
+pub enum EnvironmentError {
+    Image(ImageError),
+    Exr(ExrError),
+    UnknownExtension,
+}
+
+impl fmt::Display for EnvironmentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EnvironmentError::Image(err) => write!(f, "{}", err),
+            EnvironmentError::Exr(err) => write!(f, "{}", err),
+            EnvironmentError::UnknownExtension => write!(f, "Not an .hdr or .exr file"),
+        }
+    }
+}
+
+/// HDR image in equirectangular projection.
+pub struct Equirect {
+    pub width: u32,
+    pub height: u32,
+    /// Linear RGB pixels, row by row, starting at the top.
+    pub pixels: Vec<[f32; 3]>,
+}
+
+impl Equirect {
+    /// Loads a Radiance HDR (`.hdr`) or OpenEXR (`.exr`) image.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, EnvironmentError> {
+        let _scope = profiler::scope("Load environment");
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("hdr") => Self::load_hdr(path).map_err(EnvironmentError::Image),
+            Some("exr") => {
+                let (width, height, pixels) = exr::load(path).map_err(EnvironmentError::Exr)?;
+                Ok(Self {
+                    width,
+                    height,
+                    pixels,
+                })
+            }
+            _ => Err(EnvironmentError::UnknownExtension),
+        }
+    }
+
+    /// Texture format the image is uploaded with. Half floats unless some
+    /// value doesn't fit in one.
+    pub fn format(&self) -> TextureFormat {
+        let max = self
+            .pixels
+            .iter()
+            .flatten()
+            .fold(0.0f32, |max, &c| max.max(c));
+        if max > half::f16::MAX.to_f32() {
+            TextureFormat::Rgba32Float
+        } else {
+            TextureFormat::Rgba16Float
+        }
+    }
+
+    /// Loads a Radiance HDR (`.hdr`) image.
+    pub fn load_hdr<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+        let meta = decoder.metadata();
+        let pixels = decoder.read_image_hdr()?.into_iter().map(|p| p.0).collect();
+        Ok(Self {
+            width: meta.width,
+            height: meta.height,
+            pixels,
+        })
+    }
+
+    /// Procedural sky with a bright sun, used when no image is available.
+    pub fn sky(width: u32, height: u32) -> Self {
+        let sun = glam::Vec3::new(-0.5, 1.0, 0.7).normalize();
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let phi = ((x as f32 + 0.5) / width as f32 - 0.5) * 2.0 * std::f32::consts::PI;
+                let theta = (y as f32 + 0.5) / height as f32 * std::f32::consts::PI;
+                let dir = glam::Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+
+                let color = if dir.y > 0.0 {
+                    let horizon = glam::Vec3::new(0.9, 0.9, 1.0);
+                    let zenith = glam::Vec3::new(0.2, 0.4, 0.9);
+                    horizon.lerp(zenith, dir.y.powf(0.5))
+                } else {
+                    glam::Vec3::new(0.25, 0.2, 0.15)
+                };
+                let sun = if dir.dot(sun) > 0.999 { 50.0 } else { 0.0 };
+                pixels.push((color + glam::Vec3::splat(sun)).into());
+            }
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Face {
+    face: u32,
+    roughness: f32,
+    resolution: f32,
+    _pad: f32,
+}
+
+pub struct Ibl {
+    _equirect: Tracked<Texture>,
+    _environment: Tracked<Texture>,
+    _irradiance: Tracked<Texture>,
+    _prefiltered: Tracked<Texture>,
+    _brdf: Tracked<Texture>,
+    /// Source image, with the format of `Equirect::format`.
+    pub equirect_view: TextureView,
+    /// Cubemap of the source at full resolution, with mips.
+    pub environment_view: TextureView,
+    pub irradiance_view: TextureView,
+    pub prefiltered_view: TextureView,
+    pub brdf_view: TextureView,
+    /// Linear sampler, clamped to the edges.
+    pub sampler: Sampler,
+}
+
+impl Ibl {
+    /// Generates all the lighting maps from an equirectangular environment.
+    pub fn new(device: &Device, queue: &Queue, environment: &Equirect) -> Self {
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("IBL sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let equirect = upload_equirect(device, queue, environment);
+        let equirect_view = equirect.create_view(&TextureViewDescriptor::default());
+        let environment = create_cube(
+            device,
+            "IBL environment",
+            ENVIRONMENT_SIZE,
+            ENVIRONMENT_MIPS,
+        );
+        let environment_view = cube_view(&environment);
+        let irradiance = create_cube(device, "IBL irradiance", IRRADIANCE_SIZE, 1);
+        let prefiltered = create_cube(
+            device,
+            "IBL prefiltered",
+            PREFILTERED_SIZE,
+            PREFILTERED_MIPS,
+        );
+        let brdf = memory::create_texture(
+            device,
+            Category::Textures,
+            &TextureDescriptor {
+                label: Some("IBL BRDF LUT"),
+                size: Extent3d {
+                    width: BRDF_SIZE,
+                    height: BRDF_SIZE,
+                    depth: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rg16Float,
+                usage: TextureUsage::SAMPLED | TextureUsage::OUTPUT_ATTACHMENT,
+            },
+        );
+
+        let vert = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let equirect_frag = device.create_shader_module(make_spirv(shaders::IBL_EQUIRECT_FRAG));
+        let irradiance_frag = device.create_shader_module(make_spirv(shaders::IBL_IRRADIANCE_FRAG));
+        let brdf_frag = device.create_shader_module(make_spirv(shaders::IBL_BRDF_FRAG));
+
+        let equirect_layout =
+            source_layout(device, "IBL equirect layout", TextureViewDimension::D2);
+        let cube_layout = source_layout(device, "IBL cube layout", TextureViewDimension::Cube);
+        let equirect_pipeline = create_pipeline(
+            device,
+            "IBL equirect pipeline",
+            Some(&equirect_layout),
+            &vert,
+            &equirect_frag,
+            CUBE_FORMAT,
+        );
+        let irradiance_pipeline = create_pipeline(
+            device,
+            "IBL irradiance pipeline",
+            Some(&cube_layout),
+            &vert,
+            &irradiance_frag,
+            CUBE_FORMAT,
+        );
+        let brdf_pipeline = create_pipeline(
+            device,
+            "IBL BRDF pipeline",
+            None,
+            &vert,
+            &brdf_frag,
+            TextureFormat::Rg16Float,
+        );
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("IBL generation"),
+        });
+        let draw_faces = |encoder: &mut CommandEncoder,
+                          name: &str,
+                          pipeline: &RenderPipeline,
+                          layout: &BindGroupLayout,
+                          source: &TextureView,
+                          target: &Texture,
+                          mip: u32,
+                          roughness: f32| {
+            for face in 0..6 {
+                let uniform = memory::create_buffer_init(
+                    device,
+                    Category::Uniforms,
+                    &BufferInitDescriptor {
+                        label: Some("IBL face uniforms"),
+                        contents: bytemuck::bytes_of(&Face {
+                            face,
+                            roughness,
+                            resolution: ENVIRONMENT_SIZE as _,
+                            _pad: 0.0,
+                        }),
+                        usage: BufferUsage::UNIFORM,
+                    },
+                );
+                let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("IBL face bind group"),
+                    layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::Buffer(uniform.slice(..)),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::TextureView(source),
+                        },
+                        BindGroupEntry {
+                            binding: 2,
+                            resource: BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                });
+                let view = face_view(target, face, mip);
+                let label = format!("{} face {} mip {}", name, face, mip);
+                draw(encoder, &label, pipeline, Some(&bind_group), &view);
+            }
+        };
+
+        for mip in 0..ENVIRONMENT_MIPS {
+            draw_faces(
+                &mut encoder,
+                "IBL equirect",
+                &equirect_pipeline,
+                &equirect_layout,
+                &equirect_view,
+                &environment,
+                mip,
+                0.0,
+            );
+        }
+        draw_faces(
+            &mut encoder,
+            "IBL irradiance",
+            &irradiance_pipeline,
+            &cube_layout,
+            &environment_view,
+            &irradiance,
+            0,
+            0.0,
+        );
+        Prefilter::new(
+            device,
+            "IBL prefilter",
+            &environment_view,
+            ENVIRONMENT_SIZE,
+            &prefiltered,
+            PREFILTERED_MIPS,
+            &sampler,
+        )
+        .run(&mut encoder);
+        let brdf_view = brdf.create_view(&TextureViewDescriptor::default());
+        draw(&mut encoder, "IBL BRDF", &brdf_pipeline, None, &brdf_view);
+        queue.submit(Some(encoder.finish()));
+
+        Self {
+            equirect_view,
+            environment_view,
+            irradiance_view: cube_view(&irradiance),
+            prefiltered_view: cube_view(&prefiltered),
+            brdf_view,
+            _equirect: equirect,
+            _environment: environment,
+            _irradiance: irradiance,
+            _prefiltered: prefiltered,
+            _brdf: brdf,
+            sampler,
+        }
+    }
+}
+
+/// GGX prefiltering of a cubemap into the mips of another one, for
+/// increasing roughness. Everything is created up front, so it can be run
+/// every frame.
+pub struct Prefilter {
+    label: String,
+    pipeline: RenderPipeline,
+    /// Uniforms, bind group and target of every face of every mip, mip by
+    /// mip.
+    faces: Vec<(Tracked<Buffer>, BindGroup, TextureView)>,
+}
+
+impl Prefilter {
+    /// Prefiltering of `source`, whose faces are `resolution` wide, into the
+    /// first `mips` levels of the `CUBE_FORMAT` cubemap `target`.
+    pub fn new(
+        device: &Device,
+        label: &str,
+        source: &TextureView,
+        resolution: u32,
+        target: &Texture,
+        mips: u32,
+        sampler: &Sampler,
+    ) -> Self {
+        let vert = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag = device.create_shader_module(make_spirv(shaders::IBL_PREFILTER_FRAG));
+        let layout = source_layout(
+            device,
+            &format!("{} layout", label),
+            TextureViewDimension::Cube,
+        );
+        let pipeline = create_pipeline(
+            device,
+            &format!("{} pipeline", label),
+            Some(&layout),
+            &vert,
+            &frag,
+            CUBE_FORMAT,
+        );
+        let mut faces = Vec::with_capacity(6 * mips as usize);
+        for mip in 0..mips {
+            let roughness = mip as f32 / (mips - 1).max(1) as f32;
+            for face in 0..6 {
+                let uniform = memory::create_buffer_init(
+                    device,
+                    Category::Uniforms,
+                    &BufferInitDescriptor {
+                        label: Some(&format!("{} face uniforms", label)),
+                        contents: bytemuck::bytes_of(&Face {
+                            face,
+                            roughness,
+                            resolution: resolution as _,
+                            _pad: 0.0,
+                        }),
+                        usage: BufferUsage::UNIFORM,
+                    },
+                );
+                let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                    label: Some(&format!("{} face bind group", label)),
+                    layout: &layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::Buffer(uniform.slice(..)),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::TextureView(source),
+                        },
+                        BindGroupEntry {
+                            binding: 2,
+                            resource: BindingResource::Sampler(sampler),
+                        },
+                    ],
+                });
+                faces.push((uniform, bind_group, face_view(target, face, mip)));
+            }
+        }
+        Self {
+            label: label.to_string(),
+            pipeline,
+            faces,
+        }
+    }
+
+    /// Records the prefiltering of every face.
+    pub fn run(&self, encoder: &mut CommandEncoder) {
+        for face in 0..6 {
+            self.run_face(encoder, face);
+        }
+    }
+
+    /// Records the prefiltering of `face`, into every mip.
+    pub fn run_face(&self, encoder: &mut CommandEncoder, face: u32) {
+        let mips = self.faces.chunks(6);
+        for (mip, faces) in mips.enumerate() {
+            let (_, bind_group, view) = &faces[face as usize];
+            let label = format!("{} face {} mip {}", self.label, face, mip);
+            draw(encoder, &label, &self.pipeline, Some(bind_group), view);
+        }
+    }
+}
+
+fn upload_equirect(device: &Device, queue: &Queue, environment: &Equirect) -> Tracked<Texture> {
+    let format = environment.format();
+    let rgba = environment
+        .pixels
+        .iter()
+        .flat_map(|&[r, g, b]| vec![r, g, b, 1.0]);
+    let (data, bytes_per_pixel): (Vec<u8>, _) = if format == TextureFormat::Rgba32Float {
+        let data: Vec<f32> = rgba.collect();
+        (bytemuck::cast_slice(&data).to_vec(), 16)
+    } else {
+        let data: Vec<u16> = rgba.map(|c| half::f16::from_f32(c).to_bits()).collect();
+        (bytemuck::cast_slice(&data).to_vec(), 8)
+    };
+    let size = Extent3d {
+        width: environment.width,
+        height: environment.height,
+        depth: 1,
+    };
+    let texture = memory::create_texture(
+        device,
+        Category::Textures,
+        &TextureDescriptor {
+            label: Some("IBL equirect source"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+        },
+    );
+    queue.write_texture(
+        wgpu::TextureCopyView {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        &data,
+        wgpu::TextureDataLayout {
+            offset: 0,
+            bytes_per_row: bytes_per_pixel * environment.width,
+            rows_per_image: environment.height,
+        },
+        size,
+    );
+    texture
+}
+
+fn create_cube(device: &Device, label: &str, size: u32, mips: u32) -> Tracked<Texture> {
+    memory::create_texture(
+        device,
+        Category::Textures,
+        &TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width: size,
+                height: size,
+                depth: 6,
+            },
+            mip_level_count: mips,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: CUBE_FORMAT,
+            usage: TextureUsage::SAMPLED | TextureUsage::OUTPUT_ATTACHMENT,
+        },
+    )
+}
+
+fn cube_view(texture: &Texture) -> TextureView {
+    texture.create_view(&TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        array_layer_count: NonZeroU32::new(6),
+        ..Default::default()
+    })
+}
+
+fn face_view(texture: &Texture, face: u32, mip: u32) -> TextureView {
+    texture.create_view(&TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::D2),
+        base_mip_level: mip,
+        level_count: NonZeroU32::new(1),
+        base_array_layer: face,
+        array_layer_count: NonZeroU32::new(1),
+        ..Default::default()
+    })
+}
+
+fn source_layout(device: &Device, label: &str, dimension: TextureViewDimension) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some(label),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::SampledTexture {
+                    dimension,
+                    component_type: TextureComponentType::Float,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Sampler { comparison: false },
+                count: None,
+            },
+        ],
+    })
+}
+
+fn create_pipeline(
+    device: &Device,
+    label: &str,
+    layout: Option<&BindGroupLayout>,
+    vert: &ShaderModule,
+    frag: &ShaderModule,
+    format: TextureFormat,
+) -> RenderPipeline {
+    let bind_group_layouts: Vec<_> = layout.into_iter().collect();
+    let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some(label),
+        bind_group_layouts: &bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&layout),
+        vertex_stage: ProgrammableStageDescriptor {
+            module: vert,
+            entry_point: "main",
+        },
+        fragment_stage: Some(ProgrammableStageDescriptor {
+            module: frag,
+            entry_point: "main",
+        }),
+        rasterization_state: None,
+        primitive_topology: PrimitiveTopology::TriangleList,
+        color_states: &[ColorStateDescriptor {
+            format,
+            alpha_blend: BlendDescriptor::REPLACE,
+            color_blend: BlendDescriptor::REPLACE,
+            write_mask: ColorWrite::ALL,
+        }],
+        depth_stencil_state: None,
+        vertex_state: VertexStateDescriptor {
+            index_format: IndexFormat::Uint16,
+            vertex_buffers: &[],
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
+
+fn draw(
+    encoder: &mut CommandEncoder,
+    label: &str,
+    pipeline: &RenderPipeline,
+    bind_group: Option<&wgpu::BindGroup>,
+    target: &TextureView,
+) {
+    let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+        color_attachments: &[RenderPassColorAttachmentDescriptor {
+            attachment: target,
+            resolve_target: None,
+            ops: Operations {
+                load: LoadOp::Clear(Color::BLACK),
+                store: true,
+            },
+        }],
+        depth_stencil_attachment: None,
+    });
+    pass.push_debug_group(label);
+    pass.set_pipeline(pipeline);
+    if let Some(bind_group) = bind_group {
+        pass.set_bind_group(0, bind_group, &[]);
+    }
+    pass.draw(0..3, 0..1);
+    pass.pop_debug_group();
+}
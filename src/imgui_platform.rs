@@ -0,0 +1,190 @@
+//! Input and cursor of imgui on the render thread.
+//!
+//! Mirrors what imgui-sdl2 does with the window, which only the event thread
+//! has, through the [`WindowProxy`] instead.
+use crate::render_thread::{Clipboard, WindowProxy};
+use imgui::{ClipboardBackend, ConfigFlags, Context, ImStr, ImString, Io, Key, Ui};
+use sdl2::{
+    event::Event,
+    keyboard::{Mod, Scancode},
+    mouse::MouseButton,
+};
+
+const KEY_MAP: [(Key, Scancode); 21] = [
+    (Key::Tab, Scancode::Tab),
+    (Key::LeftArrow, Scancode::Left),
+    (Key::RightArrow, Scancode::Right),
+    (Key::UpArrow, Scancode::Up),
+    (Key::DownArrow, Scancode::Down),
+    (Key::PageUp, Scancode::PageUp),
+    (Key::PageDown, Scancode::PageDown),
+    (Key::Home, Scancode::Home),
+    (Key::End, Scancode::End),
+    (Key::Insert, Scancode::Insert),
+    (Key::Delete, Scancode::Delete),
+    (Key::Backspace, Scancode::Backspace),
+    (Key::Enter, Scancode::Return),
+    (Key::Escape, Scancode::Escape),
+    (Key::Space, Scancode::Space),
+    (Key::A, Scancode::A),
+    (Key::C, Scancode::C),
+    (Key::V, Scancode::V),
+    (Key::X, Scancode::X),
+    (Key::Y, Scancode::Y),
+    (Key::Z, Scancode::Z),
+];
+
+struct ProxyClipboard(Clipboard);
+
+impl ClipboardBackend for ProxyClipboard {
+    fn get(&mut self) -> Option<ImString> {
+        self.0.get().map(ImString::new)
+    }
+
+    fn set(&mut self, value: &ImStr) {
+        self.0.set(value.to_str());
+    }
+}
+
+pub struct ImguiPlatform {
+    /// Buttons pressed since the last frame, so clicks shorter than a frame
+    /// aren't missed.
+    mouse_press: [bool; 5],
+    ignore_mouse: bool,
+    ignore_keyboard: bool,
+    /// What imgui wants the cursor to be, `None` for hidden.
+    cursor: Option<Option<imgui::MouseCursor>>,
+    mouse_captured: bool,
+}
+
+impl ImguiPlatform {
+    pub fn new(imgui: &mut Context, window: &WindowProxy) -> Self {
+        imgui.set_clipboard_backend(Box::new(ProxyClipboard(window.clipboard())));
+        let io = imgui.io_mut();
+        for &(key, scancode) in &KEY_MAP {
+            io.key_map[key as usize] = scancode as u32;
+        }
+        Self {
+            mouse_press: [false; 5],
+            ignore_mouse: false,
+            ignore_keyboard: false,
+            cursor: None,
+            mouse_captured: false,
+        }
+    }
+
+    /// Whether imgui takes `event`, so the app should ignore it.
+    pub fn ignore_event(&self, event: &Event) -> bool {
+        match event {
+            Event::KeyDown { .. }
+            | Event::KeyUp { .. }
+            | Event::TextEditing { .. }
+            | Event::TextInput { .. } => self.ignore_keyboard,
+            Event::MouseMotion { .. }
+            | Event::MouseButtonDown { .. }
+            | Event::MouseButtonUp { .. }
+            | Event::MouseWheel { .. }
+            | Event::FingerDown { .. }
+            | Event::FingerUp { .. }
+            | Event::FingerMotion { .. }
+            | Event::DollarGesture { .. }
+            | Event::DollarRecord { .. }
+            | Event::MultiGesture { .. } => self.ignore_mouse,
+            _ => false,
+        }
+    }
+
+    pub fn handle_event(&mut self, imgui: &mut Context, event: &Event) {
+        let io = imgui.io_mut();
+        match *event {
+            Event::MouseWheel { y, .. } => io.mouse_wheel = y as f32,
+            Event::MouseButtonDown { mouse_btn, .. } => {
+                if let Some(index) = button_index(mouse_btn) {
+                    self.mouse_press[index] = true;
+                }
+            }
+            Event::TextInput { ref text, .. } => {
+                text.chars().for_each(|c| io.add_input_character(c))
+            }
+            Event::KeyDown {
+                scancode, keymod, ..
+            } => {
+                set_modifiers(io, keymod);
+                if let Some(scancode) = scancode {
+                    io.keys_down[scancode as usize] = true;
+                }
+            }
+            Event::KeyUp {
+                scancode, keymod, ..
+            } => {
+                set_modifiers(io, keymod);
+                if let Some(scancode) = scancode {
+                    io.keys_down[scancode as usize] = false;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn prepare_frame(&mut self, io: &mut Io, window: &WindowProxy) {
+        let (width, height) = window.size();
+        let (drawable_width, drawable_height) = window.drawable_size();
+        io.display_size = [width as f32, height as f32];
+        io.display_framebuffer_scale = [
+            drawable_width as f32 / width.max(1) as f32,
+            drawable_height as f32 / height.max(1) as f32,
+        ];
+        let mouse = window.mouse_state();
+        io.mouse_down = [
+            self.mouse_press[0] || mouse.left(),
+            self.mouse_press[1] || mouse.right(),
+            self.mouse_press[2] || mouse.middle(),
+            self.mouse_press[3] || mouse.x1(),
+            self.mouse_press[4] || mouse.x2(),
+        ];
+        self.mouse_press = [false; 5];
+        // dragging out of the window keeps dragging
+        let captured = io.mouse_down.iter().any(|&down| down);
+        if captured != self.mouse_captured {
+            window.capture_mouse(captured);
+            self.mouse_captured = captured;
+        }
+        io.mouse_pos = [mouse.x() as f32, mouse.y() as f32];
+        self.ignore_keyboard = io.want_capture_keyboard;
+        self.ignore_mouse = io.want_capture_mouse;
+    }
+
+    pub fn prepare_render(&mut self, ui: &Ui, window: &WindowProxy) {
+        let io = ui.io();
+        if io
+            .config_flags
+            .contains(ConfigFlags::NO_MOUSE_CURSOR_CHANGE)
+        {
+            return;
+        }
+        let cursor = ui.mouse_cursor().filter(|_| !io.mouse_draw_cursor);
+        // only changes are sent to the event thread
+        if self.cursor != Some(cursor) {
+            window.set_cursor(cursor);
+            self.cursor = Some(cursor);
+        }
+    }
+}
+
+fn button_index(button: MouseButton) -> Option<usize> {
+    match button {
+        MouseButton::Left => Some(0),
+        MouseButton::Right => Some(1),
+        MouseButton::Middle => Some(2),
+        MouseButton::X1 => Some(3),
+        MouseButton::X2 => Some(4),
+        MouseButton::Unknown => None,
+    }
+}
+
+fn set_modifiers(io: &mut Io, keymod: Mod) {
+    io.key_ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+    io.key_alt = keymod.intersects(Mod::LALTMOD | Mod::RALTMOD);
+    io.key_shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+    io.key_super = keymod.intersects(Mod::LGUIMOD | Mod::RGUIMOD);
+}
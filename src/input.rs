@@ -0,0 +1,193 @@
+//! Keyboard bindings of the demo controls.
+//!
+//! Every action is bound to a single key, by scancode so bindings follow the
+//! physical layout. Nothing stops two actions from sharing a key, the first
+//! one wins and the controls window points out the conflict.
+use crate::render_thread::KeyboardState;
+use imgui::{im_str, Ui};
+use sdl2::keyboard::Scancode;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveDown,
+    MoveUp,
+    ToggleMouseLook,
+    ReleaseMouse,
+    Screenshot,
+    SaveBookmark,
+    NextBookmark,
+}
+
+impl Action {
+    pub const ALL: [Action; 11] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveDown,
+        Action::MoveUp,
+        Action::ToggleMouseLook,
+        Action::ReleaseMouse,
+        Action::Screenshot,
+        Action::SaveBookmark,
+        Action::NextBookmark,
+    ];
+
+    /// Name shown in the UI.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveForward => "Move forward",
+            Action::MoveBack => "Move back",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::MoveDown => "Move down",
+            Action::MoveUp => "Move up",
+            Action::ToggleMouseLook => "Toggle mouse look",
+            Action::ReleaseMouse => "Release mouse",
+            Action::Screenshot => "Screenshot (+Shift supersampled)",
+            Action::SaveBookmark => "Save camera bookmark",
+            Action::NextBookmark => "Next camera bookmark",
+        }
+    }
+
+    /// Name in the settings file.
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBack => "move_back",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::MoveDown => "move_down",
+            Action::MoveUp => "move_up",
+            Action::ToggleMouseLook => "toggle_mouse_look",
+            Action::ReleaseMouse => "release_mouse",
+            Action::Screenshot => "screenshot",
+            Action::SaveBookmark => "save_bookmark",
+            Action::NextBookmark => "next_bookmark",
+        }
+    }
+
+    fn default_key(self) -> Scancode {
+        match self {
+            Action::MoveForward => Scancode::W,
+            Action::MoveBack => Scancode::S,
+            Action::MoveLeft => Scancode::A,
+            Action::MoveRight => Scancode::D,
+            Action::MoveDown => Scancode::Q,
+            Action::MoveUp => Scancode::E,
+            Action::ToggleMouseLook => Scancode::Tab,
+            Action::ReleaseMouse => Scancode::Escape,
+            Action::Screenshot => Scancode::F12,
+            Action::SaveBookmark => Scancode::B,
+            Action::NextBookmark => Scancode::N,
+        }
+    }
+
+    fn index(self) -> usize {
+        Action::ALL.iter().position(|&a| a == self).unwrap()
+    }
+}
+
+#[derive(Clone)]
+pub struct KeyBindings {
+    keys: [Scancode; Action::ALL.len()],
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: Action::ALL.map(Action::default_key),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn key(&self, action: Action) -> Scancode {
+        self.keys[action.index()]
+    }
+
+    pub fn set(&mut self, action: Action, key: Scancode) {
+        self.keys[action.index()] = key;
+    }
+
+    /// First action bound to `key`.
+    pub fn action(&self, key: Scancode) -> Option<Action> {
+        Action::ALL.iter().copied().find(|&a| self.key(a) == key)
+    }
+
+    pub fn is_pressed(&self, keys: &KeyboardState, action: Action) -> bool {
+        keys.is_scancode_pressed(self.key(action))
+    }
+
+    /// Other actions bound to the same key as `action`.
+    pub fn conflicts(&self, action: Action) -> impl Iterator<Item = Action> + '_ {
+        let key = self.key(action);
+        Action::ALL
+            .iter()
+            .copied()
+            .filter(move |&a| a != action && self.key(a) == key)
+    }
+
+    /// Binding as saved in the settings file, the action and the name of the
+    /// key.
+    pub fn to_line(&self, action: Action) -> String {
+        format!("{} {}", action.id(), self.key(action).name())
+    }
+
+    /// Parses a line of [`KeyBindings::to_line`], returning whether it was
+    /// valid. Key names can contain spaces.
+    pub fn parse_line(&mut self, line: &str) -> bool {
+        let mut parts = line.splitn(2, ' ');
+        let action = parts
+            .next()
+            .and_then(|id| Action::ALL.iter().copied().find(|a| a.id() == id));
+        let key = parts.next().and_then(Scancode::from_name);
+        match (action, key) {
+            (Some(action), Some(key)) => {
+                self.set(action, key);
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Lists the bindings, `rebinding` holds the action waiting for a key
+    /// after its button is pressed.
+    pub fn ui(&mut self, ui: &Ui, rebinding: &mut Option<Action>) {
+        for &action in Action::ALL.iter() {
+            ui.text(action.name());
+            ui.same_line(240.0);
+            let label = if *rebinding == Some(action) {
+                im_str!("Press a key...##{}", action.id())
+            } else {
+                im_str!("{}##{}", self.key(action).name(), action.id())
+            };
+            if ui.button(&label, [120.0, 0.0]) {
+                *rebinding = Some(action);
+            }
+            let conflicts: Vec<_> = self.conflicts(action).map(|other| other.name()).collect();
+            if !conflicts.is_empty() {
+                ui.same_line(0.0);
+                ui.text_colored(
+                    [1.0, 0.4, 0.4, 1.0],
+                    format!("Also bound to {}", conflicts.join(", ")),
+                );
+            }
+        }
+        ui.separator();
+        if rebinding.is_some() {
+            if ui.button(im_str!("Cancel"), [0.0, 0.0]) {
+                *rebinding = None;
+            }
+            ui.same_line(0.0);
+        }
+        if ui.button(im_str!("Reset bindings"), [0.0, 0.0]) {
+            *self = Default::default();
+            *rebinding = None;
+        }
+        ui.text("Bindings are saved with the settings on exit");
+    }
+}
@@ -0,0 +1,199 @@
+//! Preview of HDR textures with adjustable exposure.
+//!
+//! Float textures can't be shown by imgui as they are, so they are drawn into
+//! an 8 bit target with the exposure (and optionally tone mapping) applied.
+use crate::{
+    draw_stats,
+    memory::{self, Category, Tracked},
+    shaders,
+};
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    util::make_spirv, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferDescriptor, BufferSize, BufferUsage, Color, ColorStateDescriptor, ColorWrite,
+    CommandEncoder, Device, FilterMode, IndexFormat, LoadOp, Operations, PipelineLayoutDescriptor,
+    PrimitiveTopology, ProgrammableStageDescriptor, Queue, RenderPassColorAttachmentDescriptor,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, SamplerDescriptor, ShaderStage,
+    TextureComponentType, TextureFormat, TextureView, TextureViewDimension, VertexStateDescriptor,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct InspectorUniforms {
+    exposure: f32,
+    tone_mapping: u32,
+    _pad: [u32; 2],
+}
+
+/// How the texture is previewed, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct InspectorParams {
+    /// In stops, added to the texture values.
+    pub exposure: f32,
+    pub tone_mapping: bool,
+}
+
+impl Default for InspectorParams {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_mapping: false,
+        }
+    }
+}
+
+pub struct TextureInspector {
+    uniform: Tracked<Buffer>,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+}
+
+impl TextureInspector {
+    /// Previews the 2D float texture of `source` into targets of `format`.
+    pub fn new(device: &Device, source: &TextureView, format: TextureFormat) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::INSPECTOR_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Inspector uniforms"),
+                size: std::mem::size_of::<InspectorUniforms>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Inspector sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Inspector bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(
+                            std::mem::size_of::<InspectorUniforms>() as _
+                        ),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::SampledTexture {
+                        dimension: TextureViewDimension::D2,
+                        component_type: TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Inspector bind group"),
+            layout: &layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(uniform.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(source),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Inspector pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Inspector pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex_stage: ProgrammableStageDescriptor {
+                module: &vert_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(ProgrammableStageDescriptor {
+                module: &frag_module,
+                entry_point: "main",
+            }),
+            rasterization_state: None,
+            primitive_topology: PrimitiveTopology::TriangleList,
+            color_states: &[ColorStateDescriptor {
+                format,
+                alpha_blend: BlendDescriptor::REPLACE,
+                color_blend: BlendDescriptor::REPLACE,
+                write_mask: ColorWrite::ALL,
+            }],
+            depth_stencil_state: None,
+            vertex_state: VertexStateDescriptor {
+                index_format: IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        Self {
+            uniform,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    /// Records the preview of the source into `target`.
+    pub fn run(
+        &self,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        target: &TextureView,
+        params: &InspectorParams,
+    ) {
+        let uniforms = InspectorUniforms {
+            exposure: params.exposure,
+            tone_mapping: params.tone_mapping as u32,
+            _pad: [0; 2],
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, bytemuck::bytes_of(&uniforms));
+
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            color_attachments: &[RenderPassColorAttachmentDescriptor {
+                attachment: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        pass.push_debug_group("Texture inspector");
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+        pass.pop_debug_group();
+    }
+}
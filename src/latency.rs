@@ -0,0 +1,177 @@
+//! Frame latency and present statistics.
+//!
+//! Times are measured on the CPU: how long acquiring the next swap chain frame
+//! blocks, how long presenting it takes, and how long ago the oldest input
+//! event handled in the frame happened when it's presented. Frames in flight
+//! are counted with a small buffer per submission that's mapped once the GPU
+//! is done with it.
+use crate::memory::{self, Category, Tracked};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use wgpu::{
+    util::BufferInitDescriptor, Buffer, BufferAsyncError, BufferDescriptor, BufferUsage,
+    CommandEncoder, Device, Maintain, MapMode,
+};
+
+/// Number of frames averaged.
+const SAMPLES: usize = 120;
+
+/// Frames in flight are only counted up to this many.
+const MAX_FRAMES_IN_FLIGHT: usize = 8;
+
+/// Recent measurements of something.
+#[derive(Default)]
+pub struct Samples {
+    samples: VecDeque<f32>,
+}
+
+impl Samples {
+    pub(crate) fn push(&mut self, sample: f32) {
+        if self.samples.len() == SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn average(&self) -> f32 {
+        if self.samples.is_empty() {
+            0.0
+        } else {
+            self.samples.iter().sum::<f32>() / self.samples.len() as f32
+        }
+    }
+
+    pub fn max(&self) -> f32 {
+        self.samples.iter().copied().fold(0.0, f32::max)
+    }
+
+    /// Samples in the order they were measured, to plot them.
+    pub fn values(&self) -> Vec<f32> {
+        self.samples.iter().copied().collect()
+    }
+}
+
+#[derive(Default)]
+pub struct FrameStats {
+    /// Milliseconds from the oldest input event of a frame to its present.
+    pub input_to_present: Samples,
+    /// Milliseconds waiting for the next swap chain frame.
+    pub acquire: Samples,
+    /// Milliseconds presenting a frame.
+    pub present: Samples,
+    /// Submitted frames the GPU hadn't finished at the start of a frame.
+    pub in_flight: Samples,
+    /// SDL timestamp of the oldest input event since the last present.
+    input: Option<u32>,
+}
+
+impl FrameStats {
+    /// Records an input event with the given SDL timestamp, in milliseconds.
+    pub fn input(&mut self, timestamp: u32) {
+        self.input.get_or_insert(timestamp);
+    }
+
+    pub fn acquired(&mut self, wait: Duration) {
+        self.acquire.push(wait.as_secs_f32() * 1000.0);
+    }
+
+    /// Records a present that took `duration` and finished at the SDL
+    /// timestamp `ticks`.
+    pub fn presented(&mut self, duration: Duration, ticks: u32) {
+        self.present.push(duration.as_secs_f32() * 1000.0);
+        if let Some(input) = self.input.take() {
+            self.input_to_present.push(ticks.wrapping_sub(input) as f32);
+        }
+    }
+
+    /// Counts the frames in flight at the start of a frame.
+    pub fn count_in_flight(&mut self, device: &Device, frames: &mut FramesInFlight) {
+        let count = frames.count(device);
+        self.in_flight.push(count as f32);
+    }
+}
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), BufferAsyncError>> + Send>>;
+
+/// Counts the submissions the GPU is still working on.
+pub struct FramesInFlight {
+    source: Tracked<Buffer>,
+    free: Vec<Tracked<Buffer>>,
+    /// Buffers copied into by a submission, mapped once it completes.
+    pending: VecDeque<(Tracked<Buffer>, Option<MapFuture>)>,
+}
+
+impl FramesInFlight {
+    pub fn new(device: &Device) -> Self {
+        let source = memory::create_buffer_init(
+            device,
+            Category::Staging,
+            &BufferInitDescriptor {
+                label: Some("Frame fence source"),
+                contents: &[0; 4],
+                usage: BufferUsage::COPY_SRC,
+            },
+        );
+        let free = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| {
+                memory::create_buffer(
+                    device,
+                    Category::Staging,
+                    &BufferDescriptor {
+                        label: Some("Frame fence"),
+                        size: 4,
+                        usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
+                        mapped_at_creation: false,
+                    },
+                )
+            })
+            .collect();
+        Self {
+            source,
+            free,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Records a copy into a fence buffer, to know when the submission of
+    /// `encoder` completes. Does nothing if all of them are in flight.
+    pub fn signal(&mut self, encoder: &mut CommandEncoder) {
+        if let Some(fence) = self.free.pop() {
+            encoder.copy_buffer_to_buffer(&self.source, 0, &fence, 0, 4);
+            self.pending.push_back((fence, None));
+        }
+    }
+
+    /// Starts mapping the fence of the last submission. Must be called after
+    /// submitting the encoder passed to [`FramesInFlight::signal`].
+    pub fn submitted(&mut self) {
+        if let Some((fence, mapping @ None)) = self.pending.back_mut() {
+            *mapping = Some(Box::pin(fence.slice(..).map_async(MapMode::Read)));
+        }
+    }
+
+    /// Polls the device and returns how many submissions are still running.
+    pub fn count(&mut self, device: &Device) -> usize {
+        device.poll(Maintain::Poll);
+        let waker = futures::task::noop_waker();
+        let mut context = Context::from_waker(&waker);
+        // submissions complete in order
+        while let Some((_, Some(mapping))) = self.pending.front_mut() {
+            let mapped = match mapping.as_mut().poll(&mut context) {
+                Poll::Pending => break,
+                Poll::Ready(result) => result.is_ok(),
+            };
+            let (fence, _) = self.pending.pop_front().unwrap();
+            if mapped {
+                fence.unmap();
+            }
+            self.free.push(fence);
+        }
+        self.pending.len()
+    }
+}
@@ -0,0 +1,283 @@
+//! Structs laid out by the std140 and std430 rules of GLSL blocks.
+//!
+//! Instead of `#[repr(C)]` structs padded by hand to match the shaders,
+//! structs declared with `gpu_struct!` are written field by field with the
+//! offsets the rules give them, so a `vec3` followed by a `float` or an array
+//! of scalars can't silently be misaligned.
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Rules {
+    /// Uniform blocks.
+    Std140,
+    /// Storage blocks.
+    Std430,
+}
+
+/// Alignment and size of a type under one of the rules.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Shape {
+    pub align: usize,
+    pub size: usize,
+}
+
+impl Shape {
+    const fn new(align: usize, size: usize) -> Self {
+        Self { align, size }
+    }
+
+    /// Shape of an array of `len` elements of `self`.
+    pub const fn array(self, rules: Rules, len: usize) -> Self {
+        let align = match rules {
+            Rules::Std140 => max(self.align, 16),
+            Rules::Std430 => self.align,
+        };
+        Self::new(align, round_up(self.size, align) * len)
+    }
+
+    /// Shape of a struct with `fields` in order.
+    pub const fn of_struct(rules: Rules, fields: &[Shape]) -> Self {
+        let mut align = match rules {
+            Rules::Std140 => 16,
+            Rules::Std430 => 1,
+        };
+        let mut size = 0;
+        let mut i = 0;
+        while i < fields.len() {
+            align = max(align, fields[i].align);
+            size = round_up(size, fields[i].align) + fields[i].size;
+            i += 1;
+        }
+        Self::new(align, round_up(size, align))
+    }
+}
+
+const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+const fn round_up(value: usize, align: usize) -> usize {
+    value.div_ceil(align) * align
+}
+
+/// Type that can be a member of a GLSL block.
+pub trait Layout {
+    const STD140: Shape;
+    const STD430: Shape;
+
+    /// Appends the bytes of `self`, starting at an offset already aligned to
+    /// its shape under `rules`.
+    fn write(&self, rules: Rules, bytes: &mut Vec<u8>);
+
+    fn shape(rules: Rules) -> Shape {
+        match rules {
+            Rules::Std140 => Self::STD140,
+            Rules::Std430 => Self::STD430,
+        }
+    }
+
+    /// Contents of a uniform buffer holding `self`.
+    fn std140_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::STD140.size);
+        self.write(Rules::Std140, &mut bytes);
+        bytes
+    }
+}
+
+/// Contents of a storage buffer holding the runtime sized array `items`.
+pub fn std430_array<T: Layout>(items: &[T]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(items.len() * T::STD430.size);
+    for item in items {
+        let start = bytes.len();
+        item.write(Rules::Std430, &mut bytes);
+        pad(&mut bytes, start, T::STD430.align);
+    }
+    bytes
+}
+
+/// Pads `bytes` with zeros so whatever follows `start` ends up a multiple of
+/// `align` long.
+pub fn pad(bytes: &mut Vec<u8>, start: usize, align: usize) {
+    let len = start + round_up(bytes.len() - start, align);
+    bytes.resize(len, 0);
+}
+
+macro_rules! impl_scalar {
+    ($($ty:ty),*) => {
+        $(impl Layout for $ty {
+            const STD140: Shape = Shape::new(4, 4);
+            const STD430: Shape = Shape::new(4, 4);
+
+            fn write(&self, _: Rules, bytes: &mut Vec<u8>) {
+                bytes.extend_from_slice(&self.to_ne_bytes());
+            }
+        })*
+    };
+}
+
+impl_scalar!(f32, u32, i32);
+
+/// GLSL `bool`, 4 bytes like the rest of the scalars.
+impl Layout for bool {
+    const STD140: Shape = Shape::new(4, 4);
+    const STD430: Shape = Shape::new(4, 4);
+
+    fn write(&self, rules: Rules, bytes: &mut Vec<u8>) {
+        (*self as u32).write(rules, bytes);
+    }
+}
+
+macro_rules! impl_vector {
+    ($($ty:ty => $len:expr, $align:expr;)*) => {
+        $(impl Layout for $ty {
+            const STD140: Shape = Shape::new($align, 4 * $len);
+            const STD430: Shape = Shape::new($align, 4 * $len);
+
+            fn write(&self, _: Rules, bytes: &mut Vec<u8>) {
+                let array: [f32; $len] = (*self).into();
+                bytes.extend_from_slice(bytemuck::cast_slice(&array));
+            }
+        })*
+    };
+}
+
+impl_vector! {
+    Vec2 => 2, 8;
+    Vec3 => 3, 16;
+    Vec4 => 4, 16;
+}
+
+/// Column major, as four `vec4` columns.
+impl Layout for Mat4 {
+    const STD140: Shape = Shape::new(16, 64);
+    const STD430: Shape = Shape::new(16, 64);
+
+    fn write(&self, _: Rules, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.to_cols_array()));
+    }
+}
+
+impl<T: Layout, const N: usize> Layout for [T; N] {
+    const STD140: Shape = T::STD140.array(Rules::Std140, N);
+    const STD430: Shape = T::STD430.array(Rules::Std430, N);
+
+    fn write(&self, rules: Rules, bytes: &mut Vec<u8>) {
+        let align = Self::shape(rules).align;
+        for element in self {
+            let start = bytes.len();
+            element.write(rules, bytes);
+            pad(bytes, start, align);
+        }
+    }
+}
+
+/// Declares a struct whose fields are laid out as the members of a GLSL
+/// block, the same struct then works in uniform and storage buffers.
+///
+/// ```ignore
+/// gpu_struct! {
+///     struct Uniforms {
+///         view_projection: Mat4,
+///         position: Vec3,
+///         radius: f32,
+///     }
+/// }
+/// queue.write_buffer(&buffer, 0, &uniforms.std140_bytes());
+/// ```
+macro_rules! gpu_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident: $ty:ty,)*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $ty,)*
+        }
+
+        impl $crate::layout::Layout for $name {
+            const STD140: $crate::layout::Shape = $crate::layout::Shape::of_struct(
+                $crate::layout::Rules::Std140,
+                &[$(<$ty as $crate::layout::Layout>::STD140),*],
+            );
+            const STD430: $crate::layout::Shape = $crate::layout::Shape::of_struct(
+                $crate::layout::Rules::Std430,
+                &[$(<$ty as $crate::layout::Layout>::STD430),*],
+            );
+
+            fn write(&self, rules: $crate::layout::Rules, bytes: &mut Vec<u8>) {
+                let start = bytes.len();
+                $(
+                    $crate::layout::pad(
+                        bytes,
+                        start,
+                        <$ty as $crate::layout::Layout>::shape(rules).align,
+                    );
+                    $crate::layout::Layout::write(&self.$field, rules, bytes);
+                )*
+                $crate::layout::pad(bytes, start, Self::shape(rules).align);
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Layout, Rules, Shape};
+    use glam::{Vec3, Vec4};
+
+    gpu_struct! {
+        struct Block {
+            position: Vec3,
+            range: f32,
+            weights: [f32; 3],
+            color: Vec4,
+            flag: bool,
+        }
+    }
+
+    #[test]
+    fn offsets_follow_the_rules() {
+        let block = Block {
+            position: Vec3::new(1.0, 2.0, 3.0),
+            range: 4.0,
+            weights: [5.0, 6.0, 7.0],
+            color: Vec4::splat(8.0),
+            flag: true,
+        };
+        // the float packs after the vec3, array elements are vec4 aligned in
+        // std140 only
+        assert_eq!(
+            Block::STD140,
+            Shape {
+                align: 16,
+                size: 96
+            }
+        );
+        assert_eq!(
+            Block::STD430,
+            Shape {
+                align: 16,
+                size: 64
+            }
+        );
+
+        let floats = |bytes: Vec<u8>| -> Vec<f32> { bytemuck::cast_slice(&bytes).to_vec() };
+        let std140 = floats(block.std140_bytes());
+        assert_eq!(std140.len(), 24);
+        assert_eq!(&std140[..5], &[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!((std140[8], std140[12], std140[16]), (6.0, 7.0, 8.0));
+        assert_eq!(std140[20].to_bits(), 1);
+
+        let std430 = floats(super::std430_array(&[block]));
+        assert_eq!(std430.len(), 16);
+        assert_eq!(&std430[..7], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        assert_eq!((std430[8], std430[12].to_bits()), (8.0, 1));
+        assert_eq!(Block::shape(Rules::Std430), Block::STD430);
+    }
+}
@@ -0,0 +1,104 @@
+//! Renderer of the demo, split from the binary so the integration tests and
+//! benchmarks can use the same modules.
+// macros first, so every module can use them
+#[macro_use]
+pub mod layout;
+#[macro_use]
+pub mod vertex;
+
+pub mod adapter;
+pub mod animation;
+pub mod args;
+pub mod assets;
+pub mod bench;
+pub mod blend;
+pub mod bloom;
+pub mod bookmarks;
+pub mod camera;
+pub mod capabilities;
+pub mod clusters;
+pub mod compressed;
+pub mod console;
+#[cfg(feature = "sdl")]
+pub mod context;
+pub mod culling;
+pub mod debug_view;
+pub mod decals;
+pub mod dialog;
+pub mod dof;
+pub mod draw_queue;
+pub mod draw_stats;
+pub mod exposure;
+pub mod exr;
+pub mod filter;
+pub mod fog;
+pub mod frame_ring;
+pub mod fxaa;
+pub mod gif;
+pub mod gizmos;
+pub mod gltf;
+pub mod golden;
+pub mod grid;
+pub mod ibl;
+#[cfg(feature = "sdl")]
+pub mod imgui_platform;
+#[cfg(feature = "sdl")]
+pub mod input;
+pub mod inspector;
+pub mod latency;
+pub mod lights;
+pub mod lod;
+pub mod lut;
+pub mod material;
+pub mod memory;
+pub mod mesh;
+pub mod motion_blur;
+pub mod msaa;
+pub mod nbody;
+pub mod oit;
+pub mod parallel_encoding;
+pub mod pass;
+pub mod physics;
+pub mod picking;
+pub mod ping_pong;
+pub mod point_shadow;
+pub mod post;
+pub mod profiler;
+pub mod raymarch;
+pub mod readback;
+pub mod reflection_probe;
+#[cfg(feature = "sdl")]
+pub mod render_thread;
+#[cfg(feature = "sdl")]
+pub mod replay;
+pub mod sampler;
+pub mod scan;
+pub mod scene;
+pub mod scene_bundles;
+pub mod scene_file;
+pub mod scene_pipeline;
+pub mod script;
+#[cfg(feature = "sdl")]
+pub mod settings;
+pub mod shader_compiler;
+pub mod shaders;
+pub mod shadow;
+pub mod shadow_inspector;
+pub mod sky;
+pub mod skybox;
+pub mod sort;
+pub mod style;
+pub mod supersample;
+pub mod taa;
+pub mod text;
+pub mod texture;
+pub mod texture_browser;
+pub mod time;
+#[cfg(feature = "sdl")]
+pub mod title;
+pub mod transform_gizmo;
+pub mod variants;
+pub mod vertex_editor;
+pub mod vertex_pulling;
+pub mod viewports;
+pub mod volumetric;
@@ -0,0 +1,281 @@
+//! Lights without shadows, on top of the shadowed sun and point light.
+//!
+//! Any number of them, up to `MAX_LIGHTS`, is read by the lit shader from a
+//! storage buffer rewritten every frame, only those reaching the cluster of
+//! the fragment (see `clusters`).
+use crate::{
+    clusters::{CLUSTERS, MAX_CLUSTER_LIGHTS},
+    gizmos::Gizmos,
+};
+use glam::{Vec3, Vec4};
+use imgui::{
+    im_str, AngleSlider, CollapsingHeader, ColorEdit, ComboBox, Drag, ImString, Slider, Ui, Window,
+};
+use serde::{Deserialize, Serialize};
+
+/// Capacity of the storage buffer.
+pub const MAX_LIGHTS: usize = 512;
+
+/// Kinds of lights selectable from the UI.
+pub const LIGHT_KINDS: [LightKind; 3] = [LightKind::Directional, LightKind::Point, LightKind::Spot];
+
+/// Same values as the defines of the shader.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LightKind {
+    Directional = 0,
+    Point = 1,
+    Spot = 2,
+}
+
+impl LightKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            LightKind::Directional => "Directional",
+            LightKind::Point => "Point",
+            LightKind::Spot => "Spot",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Light {
+    pub kind: LightKind,
+    /// Ignored by directional lights.
+    pub position: Vec3,
+    /// Ignored by point lights.
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    /// Distance at which point and spot lights no longer have any effect.
+    pub range: f32,
+    /// Angles from the direction of a spot light, in radians, where its cone
+    /// starts fading and where it ends.
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+}
+
+impl Light {
+    /// White light of the given kind, pointing down from above the origin.
+    pub fn new(kind: LightKind) -> Self {
+        Self {
+            kind,
+            position: Vec3::new(0.0, 2.0, 0.0),
+            direction: -Vec3::unit_y(),
+            color: Vec3::one(),
+            intensity: 2.0,
+            range: 5.0,
+            inner_angle: 20f32.to_radians(),
+            outer_angle: 30f32.to_radians(),
+        }
+    }
+
+    pub fn to_gpu(&self) -> GpuLight {
+        GpuLight {
+            position: self.position.extend(self.range),
+            direction: self.direction.normalize(),
+            color: self.color * self.intensity,
+            cos_inner: self.inner_angle.min(self.outer_angle).cos(),
+            cos_outer: self.outer_angle.cos(),
+            kind: self.kind as u32,
+        }
+    }
+
+    /// Controls of the light, only those its kind uses.
+    pub fn ui(&mut self, ui: &Ui) {
+        let names: Vec<_> = LIGHT_KINDS
+            .iter()
+            .map(|k| ImString::new(k.name()))
+            .collect();
+        let names: Vec<_> = names.iter().collect();
+        let mut index = LIGHT_KINDS.iter().position(|&k| k == self.kind).unwrap();
+        if ComboBox::new(im_str!("Kind")).build_simple_string(ui, &mut index, &names) {
+            self.kind = LIGHT_KINDS[index];
+        }
+        let mut color: [f32; 3] = self.color.into();
+        if ColorEdit::new(im_str!("Color"), &mut color).build(ui) {
+            self.color = color.into();
+        }
+        Slider::new(im_str!("Intensity"))
+            .range(0.0..=20.0)
+            .build(ui, &mut self.intensity);
+        if self.kind != LightKind::Directional {
+            let mut position: [f32; 3] = self.position.into();
+            if Drag::new(im_str!("Position"))
+                .speed(0.05)
+                .build_array(ui, &mut position)
+            {
+                self.position = position.into();
+            }
+            Slider::new(im_str!("Range"))
+                .range(0.5..=30.0)
+                .build(ui, &mut self.range);
+        }
+        if self.kind != LightKind::Point {
+            let mut direction: [f32; 3] = self.direction.into();
+            if Drag::new(im_str!("Direction"))
+                .speed(0.01)
+                .build_array(ui, &mut direction)
+                && Vec3::from(direction).length_squared() > 0.0
+            {
+                self.direction = direction.into();
+            }
+        }
+        if self.kind == LightKind::Spot {
+            AngleSlider::new(im_str!("Inner angle"))
+                .range_degrees(0.0..=89.0)
+                .build(ui, &mut self.inner_angle);
+            AngleSlider::new(im_str!("Outer angle"))
+                .range_degrees(1.0..=89.0)
+                .build(ui, &mut self.outer_angle);
+        }
+    }
+
+    /// Queues the outline of the volume the light reaches.
+    pub fn gizmo(&self, gizmos: &mut Gizmos) {
+        let color = self.color.extend(1.0).into();
+        let direction = self.direction.normalize();
+        match self.kind {
+            LightKind::Directional => {
+                // arrow pointing along the light
+                let tip = self.position + direction;
+                let (side, up) = perpendiculars(direction);
+                gizmos.line(self.position, tip, color);
+                for &offset in &[side, -side, up, -up] {
+                    gizmos.line(tip, tip - direction * 0.2 + offset * 0.1, color);
+                }
+            }
+            LightKind::Point => {
+                for &axis in &[Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()] {
+                    gizmos.circle(self.position, axis, self.range, color);
+                }
+            }
+            LightKind::Spot => {
+                let (side, up) = perpendiculars(direction);
+                for &(angle, color) in &[
+                    (self.outer_angle, color),
+                    (self.inner_angle, [color[0], color[1], color[2], 0.4]),
+                ] {
+                    let center = self.position + direction * self.range * angle.cos();
+                    let radius = self.range * angle.sin();
+                    gizmos.circle(center, direction, radius, color);
+                    for &offset in &[side, -side, up, -up] {
+                        gizmos.line(self.position, center + offset * radius, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Window adding, removing and editing `lights`, with whether they are
+/// `clustered` and their gizmos shown.
+pub fn window(ui: &Ui, lights: &mut Vec<Light>, show_gizmos: &mut bool, clustered: &mut bool) {
+    Window::new(im_str!("Lights"))
+        .always_auto_resize(true)
+        .build(ui, || {
+            ui.text("Without shadows, the sun and the point light are under Lighting");
+            for (i, &kind) in LIGHT_KINDS.iter().enumerate() {
+                if i > 0 {
+                    ui.same_line(0.0);
+                }
+                if ui.button(&im_str!("Add {}", kind.name().to_lowercase()), [0.0, 0.0])
+                    && lights.len() < MAX_LIGHTS
+                {
+                    lights.push(Light::new(kind));
+                }
+            }
+            if ui.button(im_str!("Scatter 100 point lights"), [0.0, 0.0]) {
+                let count = 100.min(MAX_LIGHTS - lights.len());
+                let (min, max) = (Vec3::new(-10.0, 0.2, -10.0), Vec3::new(10.0, 3.0, 10.0));
+                let seed = lights.len() as u32;
+                lights.extend(scatter_points(count, min, max, seed));
+            }
+            ui.same_line(0.0);
+            if ui.button(im_str!("Remove all"), [0.0, 0.0]) {
+                lights.clear();
+            }
+            ui.checkbox(im_str!("Show gizmos"), show_gizmos);
+            ui.checkbox(im_str!("Clustered culling"), clustered);
+            let [x, y, z] = CLUSTERS;
+            ui.text(format!(
+                "{} of {} lights, in {}x{}x{} clusters of up to {} each",
+                lights.len(),
+                MAX_LIGHTS,
+                x,
+                y,
+                z,
+                MAX_CLUSTER_LIGHTS
+            ));
+
+            let mut removed = None;
+            if CollapsingHeader::new(im_str!("Each light"))
+                .default_open(true)
+                .build(ui)
+            {
+                for (i, light) in lights.iter_mut().enumerate() {
+                    let id = ui.push_id(i as i32);
+                    ui.separator();
+                    light.ui(ui);
+                    if ui.button(im_str!("Remove"), [0.0, 0.0]) {
+                        removed = Some(i);
+                    }
+                    id.pop(ui);
+                }
+            }
+            if let Some(i) = removed {
+                lights.remove(i);
+            }
+        });
+}
+
+/// Scatters `count` point lights of random colors randomly inside of a box,
+/// differently for every `seed`.
+pub fn scatter_points(count: usize, min: Vec3, max: Vec3, seed: u32) -> Vec<Light> {
+    // xorshift, as for the instances of the field
+    let mut state = 0x2545_f491u32 ^ seed.wrapping_mul(0x9e37_79b9);
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32
+    };
+    (0..count)
+        .map(|_| {
+            let t = Vec3::new(random(), random(), random());
+            Light {
+                position: min + (max - min) * t,
+                color: (Vec3::new(random(), random(), random()) + Vec3::splat(0.1)).normalize(),
+                range: 1.0 + random() * 3.0,
+                ..Light::new(LightKind::Point)
+            }
+        })
+        .collect()
+}
+
+/// Two unit vectors perpendicular to `direction` and to each other.
+fn perpendiculars(direction: Vec3) -> (Vec3, Vec3) {
+    let other = if direction.y.abs() < 0.99 {
+        Vec3::unit_y()
+    } else {
+        Vec3::unit_x()
+    };
+    let side = direction.cross(other).normalize();
+    (side, side.cross(direction))
+}
+
+gpu_struct! {
+    /// Light as read by the shader.
+    #[derive(Clone, Copy)]
+    pub struct GpuLight {
+        /// Range in `w`.
+        position: Vec4,
+        direction: Vec3,
+        /// Already multiplied by the intensity.
+        color: Vec3,
+        cos_inner: f32,
+        cos_outer: f32,
+        kind: u32,
+    }
+}
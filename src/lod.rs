@@ -0,0 +1,257 @@
+//! Levels of detail of meshes, switched by their size on screen.
+//!
+//! Coarser levels are generated by vertex clustering: vertices are snapped to
+//! a grid, split by the direction of their normals so hard edges stay, every
+//! cell collapses into its vertex closest to the average, and triangles that
+//! degenerate are dropped. Only indices are generated, levels share the
+//! vertices of the mesh so skinning, morph targets and edits apply to all of
+//! them. The grid is the finest that leaves at most half the triangles of
+//! the previous level.
+use crate::{mesh::Mesh, mesh::MeshData, scene::Scene};
+use glam::Vec3;
+use imgui::{im_str, Slider, Ui};
+use std::collections::{HashMap, HashSet};
+
+/// Levels of a mesh, with the full one.
+pub const MAX_LODS: usize = 4;
+
+/// Fewest triangles of a generated level.
+const MIN_TRIANGLES: usize = 16;
+
+/// Finest grid tried, in cells along the longest side of the bounds.
+const MAX_RESOLUTION: u32 = 1024;
+
+/// Indices of the levels coarser than `mesh`, each with about half the
+/// triangles of the one before. Empty for meshes too small to simplify.
+pub fn generate(mesh: &MeshData) -> Vec<Vec<u32>> {
+    let mut levels: Vec<Vec<u32>> = Vec::new();
+    let mut triangles = mesh.indices.len() / 3;
+    while levels.len() + 1 < MAX_LODS && triangles / 2 >= MIN_TRIANGLES {
+        let indices = match simplify(mesh, triangles / 2) {
+            Some(indices) => indices,
+            None => break,
+        };
+        triangles = indices.len() / 3;
+        levels.push(indices);
+    }
+    levels
+}
+
+/// Indices of `mesh` clustered on the finest grid that leaves at most
+/// `target` triangles, `None` if none leaves any.
+fn simplify(mesh: &MeshData, target: usize) -> Option<Vec<u32>> {
+    let (mut low, mut high) = (1, MAX_RESOLUTION);
+    let mut best = None;
+    // triangles only roughly grow with the resolution, close enough
+    while low <= high {
+        let resolution = (low + high) / 2;
+        let indices = cluster(mesh, resolution);
+        if indices.len() / 3 <= target {
+            best = Some(indices);
+            low = resolution + 1;
+        } else {
+            high = resolution - 1;
+        }
+    }
+    best.filter(|indices| !indices.is_empty())
+}
+
+/// Indices of `mesh` with its vertices collapsed on a grid of `resolution`
+/// cells along the longest side of its bounds.
+fn cluster(mesh: &MeshData, resolution: u32) -> Vec<u32> {
+    let (min, max) = mesh.bounds();
+    let cell = ((max - min).max_element() / resolution as f32).max(f32::EPSILON);
+    let mut cells = HashMap::new();
+    // sum of the positions of every cluster, and how many there are
+    let mut clusters: Vec<(Vec3, f32)> = Vec::new();
+    let cluster_of: Vec<usize> = mesh
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let position = Vec3::from(vertex.position);
+            let c = ((position - min) / cell).floor();
+            let key = (
+                c.x as i32,
+                c.y as i32,
+                c.z as i32,
+                normal_axis(vertex.normal),
+            );
+            let next = clusters.len();
+            let i = *cells.entry(key).or_insert(next);
+            if i == next {
+                clusters.push((Vec3::zero(), 0.0));
+            }
+            clusters[i].0 += position;
+            clusters[i].1 += 1.0;
+            i
+        })
+        .collect();
+
+    let mut representatives = vec![(0, f32::MAX); clusters.len()];
+    for (i, (vertex, &c)) in mesh.vertices.iter().zip(&cluster_of).enumerate() {
+        let (sum, count) = clusters[c];
+        let distance = (Vec3::from(vertex.position) - sum / count).length_squared();
+        if distance < representatives[c].1 {
+            representatives[c] = (i as u32, distance);
+        }
+    }
+
+    let mut faces = HashSet::new();
+    let mut indices = Vec::new();
+    for face in mesh.indices.chunks_exact(3) {
+        let vertex = |k: usize| representatives[cluster_of[face[k] as usize]].0;
+        let (a, b, c) = (vertex(0), vertex(1), vertex(2));
+        if a == b || b == c || c == a {
+            continue;
+        }
+        // rotated to start at the smallest index, so duplicates keep winding
+        let key = if a < b && a < c {
+            (a, b, c)
+        } else if b < c {
+            (b, c, a)
+        } else {
+            (c, a, b)
+        };
+        if faces.insert(key) {
+            indices.extend_from_slice(&[a, b, c]);
+        }
+    }
+    indices
+}
+
+/// Which of the six axis directions `normal` is closest to.
+fn normal_axis(normal: [f32; 3]) -> u8 {
+    let [x, y, z] = normal;
+    let (axis, value) = if x.abs() >= y.abs() && x.abs() >= z.abs() {
+        (0, x)
+    } else if y.abs() >= z.abs() {
+        (1, y)
+    } else {
+        (2, z)
+    };
+    axis * 2 + (value < 0.0) as u8
+}
+
+pub struct LodParams {
+    pub enabled: bool,
+    /// Fraction of the height of the screen covered by the bounding sphere
+    /// of an object under which its second level is drawn, halved for every
+    /// level after it.
+    pub threshold: f32,
+    /// Margin around the thresholds, relative to them, so objects right at
+    /// one don't switch levels back and forth.
+    pub hysteresis: f32,
+}
+
+impl Default for LodParams {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 0.3,
+            hysteresis: 0.1,
+        }
+    }
+}
+
+impl LodParams {
+    /// Controls of the selection, with how many objects are drawn at every
+    /// level of `lods`.
+    pub fn ui(&mut self, ui: &Ui, lods: &[usize]) {
+        ui.checkbox(im_str!("Enabled"), &mut self.enabled);
+        Slider::new(im_str!("Threshold"))
+            .range(0.05..=1.0)
+            .build(ui, &mut self.threshold);
+        Slider::new(im_str!("Hysteresis"))
+            .range(0.0..=0.5)
+            .build(ui, &mut self.hysteresis);
+        ui.text("Coarser levels are generated when meshes load");
+        for level in 0..MAX_LODS {
+            let objects = lods.iter().filter(|&&l| l == level).count();
+            ui.text(format!("Level {}: {} objects", level, objects));
+        }
+    }
+}
+
+/// Level drawn for every object, kept between frames for the hysteresis.
+#[derive(Default)]
+pub struct LodSelector {
+    levels: Vec<usize>,
+}
+
+impl LodSelector {
+    /// Levels of the objects of `scene` seen from `eye`, with a vertical
+    /// field of view of `fov_y`, moved up or down from the last ones.
+    pub fn select(
+        &mut self,
+        params: &LodParams,
+        scene: &Scene,
+        meshes: &[Mesh],
+        eye: Vec3,
+        fov_y: f32,
+    ) -> &[usize] {
+        self.levels.resize(scene.objects.len(), 0);
+        let threshold = |level: usize| params.threshold * 0.5f32.powi(level as i32);
+        for (object, level) in scene.objects.iter().zip(&mut self.levels) {
+            let mesh = &meshes[object.mesh];
+            let last = if params.enabled {
+                mesh.lod_count() - 1
+            } else {
+                0
+            };
+            let model = object.model();
+            let (center, radius) = mesh.bounding_sphere();
+            let scale = model.x_axis.truncate().length();
+            let scale = scale.max(model.y_axis.truncate().length());
+            let scale = scale.max(model.z_axis.truncate().length());
+            let distance = (model.transform_point3(center) - eye).length();
+            let size = radius * scale / (distance.max(f32::EPSILON) * (fov_y * 0.5).tan());
+            *level = (*level).min(last);
+            while *level < last && size < threshold(*level) * (1.0 - params.hysteresis) {
+                *level += 1;
+            }
+            while *level > 0 && size > threshold(*level - 1) * (1.0 + params.hysteresis) {
+                *level -= 1;
+            }
+        }
+        &self.levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate;
+    use crate::mesh::{MeshData, Vertex};
+
+    #[test]
+    fn levels_halve_the_triangles() {
+        // 32 by 32 quads, facing up
+        let size = 33;
+        let mut mesh = MeshData::default();
+        for z in 0..size {
+            for x in 0..size {
+                mesh.vertices.push(Vertex {
+                    position: [x as f32, 0.0, z as f32],
+                    normal: [0.0, 1.0, 0.0],
+                    ..Default::default()
+                });
+            }
+        }
+        for z in 0..size - 1 {
+            for x in 0..size - 1 {
+                let i = z * size + x;
+                let quad = [i, i + size, i + 1, i + 1, i + size, i + size + 1];
+                mesh.indices.extend(quad.iter().map(|&i| i as u32));
+            }
+        }
+
+        let levels = generate(&mesh);
+        assert_eq!(levels.len(), 3);
+        let mut triangles = mesh.indices.len() / 3;
+        for indices in &levels {
+            let count = indices.len() / 3;
+            assert!(count > 0 && count <= triangles / 2);
+            assert!(indices.iter().all(|&i| (i as usize) < mesh.vertices.len()));
+            triangles = count;
+        }
+    }
+}
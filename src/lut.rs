@@ -0,0 +1,262 @@
+//! 3D lookup tables for color grading, from `.cube` files or strip images.
+//!
+//! Tables map display colors (sRGB encoded, after tone mapping) to graded
+//! ones, and are uploaded as 3D textures sampled with the color itself.
+//! Strip images lay the blue slices of the cube side by side, left to right,
+//! each slice having red along x and green along y: a 16³ table is 256x16.
+use crate::{
+    memory::{self, Category, Tracked},
+    texture,
+};
+use glam::Vec3;
+use log::warn;
+use std::{fmt, io, path::Path};
+use wgpu::{
+    Device, Extent3d, Origin3d, Queue, Texture, TextureCopyView, TextureDataLayout,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsage,
+};
+
+/// Size of generated tables.
+const GENERATED_SIZE: u32 = 16;
+
+/// Largest table loaded, 3D textures get big quickly.
+const MAX_SIZE: u32 = 128;
+
+#[derive(Debug)]
+pub enum LutError {
+    Io(io::Error),
+    Image(image::ImageError),
+    /// Line of a `.cube` file, counting from 1, and what's wrong with it.
+    Parse(usize, &'static str),
+    Invalid(&'static str),
+    UnknownExtension,
+}
+
+impl fmt::Display for LutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LutError::Io(err) => write!(f, "{}", err),
+            LutError::Image(err) => write!(f, "{}", err),
+            LutError::Parse(line, reason) => write!(f, "Line {}: {}", line, reason),
+            LutError::Invalid(reason) => write!(f, "{}", reason),
+            LutError::UnknownExtension => write!(f, "Unknown file extension"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Lut {
+    /// Texels along every side of the cube.
+    pub size: u32,
+    /// RGBA8 texels, red changing fastest and blue slowest.
+    pub texels: Vec<u8>,
+}
+
+impl Lut {
+    /// Table mapping every color to `f` of it, components in `[0, 1]`.
+    pub fn from_fn(size: u32, f: impl Fn(Vec3) -> Vec3) -> Self {
+        let max = (size - 1) as f32;
+        let mut texels = Vec::with_capacity((size * size * size * 4) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let color = f(Vec3::new(r as f32, g as f32, b as f32) / max);
+                    texels.extend_from_slice(&rgba8(color));
+                }
+            }
+        }
+        Self { size, texels }
+    }
+
+    pub fn identity() -> Self {
+        Self::from_fn(GENERATED_SIZE, |color| color)
+    }
+
+    /// Tables that don't need any file.
+    pub fn generated() -> Vec<(&'static str, Lut)> {
+        let luma = |color: Vec3| color.dot(Vec3::new(0.2126, 0.7152, 0.0722));
+        vec![
+            (
+                "Warm",
+                Self::from_fn(GENERATED_SIZE, |color| color * Vec3::new(1.1, 1.0, 0.85)),
+            ),
+            (
+                "Cool",
+                Self::from_fn(GENERATED_SIZE, |color| color * Vec3::new(0.9, 1.0, 1.1)),
+            ),
+            (
+                "Monochrome",
+                Self::from_fn(GENERATED_SIZE, |color| Vec3::splat(luma(color))),
+            ),
+            (
+                "Contrast",
+                // smoothstep, darker shadows and brighter highlights
+                Self::from_fn(GENERATED_SIZE, |color| {
+                    color * color * (Vec3::splat(3.0) - color * 2.0)
+                }),
+            ),
+        ]
+    }
+
+    /// Loads every table of `dir`, in file name order and named after their
+    /// files. Files that aren't tables are skipped.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<Vec<(String, Lut)>, LutError> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .map_err(LutError::Io)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        paths.sort();
+        let mut luts = Vec::new();
+        for path in paths {
+            match Self::load(&path) {
+                Ok(lut) => luts.push((name(&path), lut)),
+                Err(err) => warn!("Error loading {}: {}", path.display(), err),
+            }
+        }
+        Ok(luts)
+    }
+
+    /// Loads a `.cube` file or a strip image, by file extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LutError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("cube") => {
+                let text = std::fs::read_to_string(path).map_err(LutError::Io)?;
+                Self::parse_cube(&text)
+            }
+            Some("png") => {
+                let (width, height, pixels) = texture::load_rgba8(path).map_err(LutError::Image)?;
+                Self::from_strip(width, height, pixels)
+            }
+            _ => Err(LutError::UnknownExtension),
+        }
+    }
+
+    /// Parses the text of a `.cube` file with a 3D table over the default
+    /// `[0, 1]` domain.
+    pub fn parse_cube(text: &str) -> Result<Self, LutError> {
+        let mut size = None;
+        let mut texels = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let parse = |reason| LutError::Parse(i + 1, reason);
+            let mut words = line.split_whitespace();
+            match words.next() {
+                None => continue,
+                Some(word) if word.starts_with('#') || word == "TITLE" => continue,
+                Some("LUT_3D_SIZE") => {
+                    let n = words
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .filter(|&n| (2..=MAX_SIZE).contains(&n))
+                        .ok_or_else(|| parse("Invalid size"))?;
+                    size = Some(n);
+                    texels.reserve((n * n * n * 4) as usize);
+                }
+                Some("LUT_1D_SIZE") => return Err(parse("1D tables aren't supported")),
+                Some(keyword @ "DOMAIN_MIN") | Some(keyword @ "DOMAIN_MAX") => {
+                    let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    let values: Vec<f32> = words.filter_map(|w| w.parse().ok()).collect();
+                    if values.len() != 3 {
+                        return Err(parse("Invalid domain"));
+                    }
+                    if values.iter().any(|&v| v != expected) {
+                        return Err(parse("Only the [0, 1] domain is supported"));
+                    }
+                }
+                Some(first) => {
+                    if size.is_none() {
+                        return Err(parse("Values before LUT_3D_SIZE"));
+                    }
+                    let values: Vec<f32> = std::iter::once(first)
+                        .chain(words)
+                        .map(|w| w.parse().map_err(|_| parse("Invalid value")))
+                        .collect::<Result<_, _>>()?;
+                    if values.len() != 3 {
+                        return Err(parse("Expected 3 values"));
+                    }
+                    texels.extend_from_slice(&rgba8(Vec3::new(values[0], values[1], values[2])));
+                }
+            }
+        }
+
+        let size = size.ok_or(LutError::Invalid("Missing LUT_3D_SIZE"))?;
+        if texels.len() != (size * size * size * 4) as usize {
+            return Err(LutError::Invalid("Wrong number of values for LUT_3D_SIZE"));
+        }
+        Ok(Self { size, texels })
+    }
+
+    /// Reads a strip of RGBA8 `pixels`, `height` being the size of the table.
+    pub fn from_strip(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, LutError> {
+        let size = height;
+        if width != size * size || !(2..=MAX_SIZE).contains(&size) {
+            return Err(LutError::Invalid("Strips must be N² by N pixels"));
+        }
+        // rows of the strip go through every slice, the table is slice by slice
+        let row = (width * 4) as usize;
+        let slice_row = (size * 4) as usize;
+        let mut texels = Vec::with_capacity(pixels.len());
+        for b in 0..size as usize {
+            for g in 0..size as usize {
+                let start = g * row + b * slice_row;
+                texels.extend_from_slice(&pixels[start..start + slice_row]);
+            }
+        }
+        Ok(Self { size, texels })
+    }
+
+    /// Uploads the table, to be sampled with linear filtering.
+    pub fn create_texture(&self, device: &Device, queue: &Queue, label: &str) -> Tracked<Texture> {
+        let size = Extent3d {
+            width: self.size,
+            height: self.size,
+            depth: self.size,
+        };
+        let texture = memory::create_texture(
+            device,
+            Category::Textures,
+            &TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D3,
+                format: TextureFormat::Rgba8Unorm,
+                usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+            },
+        );
+        queue.write_texture(
+            TextureCopyView {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            &self.texels,
+            TextureDataLayout {
+                offset: 0,
+                bytes_per_row: 4 * self.size,
+                rows_per_image: self.size,
+            },
+            size,
+        );
+        texture
+    }
+}
+
+/// Name of the table at `path`, for the UI.
+pub fn name(path: &Path) -> String {
+    path.file_stem().map_or_else(
+        || path.display().to_string(),
+        |stem| stem.to_string_lossy().into_owned(),
+    )
+}
+
+fn rgba8(color: Vec3) -> [u8; 4] {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(color.x), channel(color.y), channel(color.z), 255]
+}
@@ -1,116 +1,1100 @@
 use bytemuck::{Pod, Zeroable};
-use log::{info, LevelFilter};
-use sdl2::event::{Event, WindowEvent};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use imgui::{
+    im_str, ComboBox, ConfigFlags, ImString, Image, MenuItem, Slider, SliderFlags, TextureId,
+    Window,
+};
+use log::{error, info, warn, LevelFilter};
+use sdl2::{
+    event::{Event, WindowEvent},
+    keyboard::Mod,
+    mouse::MouseButton,
+};
+use std::{
+    collections::HashMap,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 use wgpu::{
-    include_spirv,
-    util::{BufferInitDescriptor, DeviceExt},
-    vertex_attr_array, BackendBit, BlendDescriptor, BufferUsage, Color, ColorStateDescriptor,
-    ColorWrite, CommandEncoderDescriptor, CullMode, DeviceDescriptor, FrontFace, IndexFormat,
-    InputStepMode, Instance, LoadOp, Operations, PipelineLayoutDescriptor, PowerPreference,
-    PresentMode, PrimitiveTopology, ProgrammableStageDescriptor, RasterizationStateDescriptor,
-    RenderPassColorAttachmentDescriptor, RenderPassDescriptor, RenderPipelineDescriptor,
-    RequestAdapterOptions, SwapChainDescriptor, TextureFormat, TextureUsage,
+    util::{make_spirv, BufferInitDescriptor},
+    vertex_attr_array, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor, BlendFactor,
+    BlendOperation, Buffer, BufferDescriptor, BufferSize, BufferUsage, Color, ColorStateDescriptor,
+    ColorWrite, CommandEncoderDescriptor, CompareFunction, CullMode, DepthStencilStateDescriptor,
+    Device, Extent3d, FilterMode, FrontFace, InputStepMode, Instance, LoadOp,
+    PipelineLayoutDescriptor, PresentMode, PrimitiveTopology, ProgrammableStageDescriptor, Queue,
+    RasterizationStateDescriptor, RenderPipelineDescriptor, Sampler, SamplerDescriptor,
+    ShaderModule, ShaderStage, StencilOperation, StencilStateDescriptor,
+    StencilStateFaceDescriptor, SwapChainError, Texture, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsage, TextureView, TextureViewDescriptor, TextureViewDimension,
     VertexBufferDescriptor, VertexStateDescriptor,
 };
+use wgpu_test::{
+    adapter,
+    animation::{MorphDelta, Player, Skeleton, SkinVertex, Timeline},
+    args::Args,
+    assets::{self, Asset},
+    bench::{self, Bench},
+    blend::BlendPlayground,
+    bookmarks::{Bookmark, Bookmarks},
+    capabilities::Capabilities,
+    clusters::Clusters,
+    console::Console,
+    context::{self, RendererContext},
+    culling::{self, GpuCulling, INDIRECT_SIZE},
+    debug_view::{DebugView, DEBUG_VIEWS},
+    decals::{self, Decal, Decals},
+    dialog,
+    draw_queue::DrawQueues,
+    draw_stats::{self, RenderEncoder},
+    filter::{FilterParams, ImageFilter, FILTER_FORMAT},
+    frame_ring::FrameRing,
+    gif::{self, GifRecorder, GifSettings},
+    gizmos::Gizmos,
+    gltf,
+    golden::{self, Capture},
+    grid::{Grid, GridParams},
+    ibl::{self, Equirect, Ibl},
+    imgui_platform::ImguiPlatform,
+    input::Action,
+    inspector::{InspectorParams, TextureInspector},
+    latency::{FrameStats, FramesInFlight, Samples},
+    layout::{self, Layout},
+    lights::{self, GpuLight, Light, MAX_LIGHTS},
+    lod::{LodParams, LodSelector},
+    lut::{self, Lut},
+    material::{
+        AlphaMode, MaterialArray, MaterialArrayBuilder, PbrMaterial, TextureSlot,
+        LINEAR_MATERIAL_FORMAT, MATERIAL_FORMAT, THUMBNAIL_SIZE,
+    },
+    memory::{self, Category, Tracked},
+    mesh::{self, Mesh, MeshData, Vertex},
+    msaa::{self, MsaaTargets},
+    nbody::{NBody, NBodyParams},
+    oit::Oit,
+    parallel_encoding::ParallelEncoding,
+    pass::PassBuilder,
+    physics::{BodyKind, Physics, BODY_KINDS},
+    picking::{self, Ray},
+    point_shadow::{PointLight, POINT_SHADOW_SIZE},
+    post::{Antialiasing, PostFrame, PostParams, PostProcess, ANTIALIASING, HDR_FORMAT},
+    profiler,
+    raymarch::{self, Raymarch},
+    reflection_probe::ReflectionProbe,
+    render_thread::{self, WindowProxy},
+    replay::{Recorder, Replay},
+    sampler::{SamplerCache, SamplerSettings},
+    scan::{self, Scan},
+    scene::{Object, Scene},
+    scene_bundles::SceneBundles,
+    scene_file::{MeshSource, SceneFile, Sources, TextureSource, BUILTIN_MESHES},
+    scene_pipeline::{
+        self, Deformation, Lighting, LightingBindings, ObjectBindings, ObjectUniforms,
+        PipelineState, Rasterization, DEPTH_FORMAT,
+    },
+    script::{CommandKind, Script, ScriptError},
+    settings::Settings,
+    shader_compiler::{self, CompileError, Severity},
+    shaders,
+    shadow::{Cascades, Frustum, ShadowParams, CASCADES, SHADOW_MAP_SIZE},
+    shadow_inspector::ShadowInspector,
+    sky::{Sky, SkyParams},
+    skybox::Skybox,
+    sort::{self, BitonicSort},
+    supersample::{self, Supersample},
+    taa,
+    text::{TextRenderer, TextStyle},
+    texture,
+    texture_browser::{TextureBrowser, TextureInfo},
+    time::{FixedTimestep, GpuTimer, Time},
+    title::{self, TitleStatus},
+    transform_gizmo::TransformGizmo,
+    variants::ShaderVariants,
+    vertex::VertexLayout,
+    vertex_editor::VertexEditor,
+    vertex_pulling::{PullingParams, VertexPulling},
+    viewports::ViewportParams,
+};
 
 const WIDTH: usize = 640;
 const HEIGHT: usize = 480;
 
+/// Simulation updates per second.
+const UPDATE_RATE: u32 = 60;
+
+/// Time between frames while the window is minimized or unfocused.
+const BACKGROUND_FRAME_TIME: Duration = Duration::from_millis(100);
+
+/// Speed of the fly camera, in units per second.
+const CAMERA_SPEED: f32 = 2.0;
+
+/// Distance to the far plane of the camera.
+const FAR_PLANE: f32 = 100.0;
+const SHADOW_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+const POINT_SHADOW_FORMAT: TextureFormat = TextureFormat::R32Float;
+
+/// Depth offset between the stacked copies of every object.
+const LAYER_SPACING: f32 = 0.002;
+
+/// Scale of the silhouette drawn behind selected objects.
+const OUTLINE_SCALE: f32 = 1.08;
+
+const NORMAL_MAP_SIZE: u32 = 256;
+
+/// Number of cubes in the instanced field.
+const FIELD_INSTANCES: usize = 20_000;
+
+/// Frame contexts the per frame uniforms and readbacks cycle through.
+const FRAME_CONTEXTS: usize = 3;
+
+/// File the settings are kept in between runs.
+const SETTINGS_PATH: &str = "settings.txt";
+/// Script in the editor on startup.
+const SCRIPT_PATH: &str = "assets/scripts/orbit.script";
+
+/// Present modes selectable from the UI, in the order of their names.
+const PRESENT_MODES: [PresentMode; 3] = [
+    PresentMode::Fifo,
+    PresentMode::Mailbox,
+    PresentMode::Immediate,
+];
+
+/// Albedos of the instanced field, one layer of an array texture each.
+/// Generated patterns are used if the directory doesn't exist.
+const MATERIALS_PATH: &str = "assets/materials";
+const MATERIAL_SIZE: u32 = 256;
+
+/// Image processed by the compute filters, a test pattern is used if the file
+/// doesn't exist.
+const FILTER_IMAGE_PATH: &str = "assets/filter.png";
+const FILTER_IMAGE_SIZE: u32 = 256;
+
+/// Width of the previews of dropped images.
+const PREVIEW_WIDTH: f32 = 256.0;
+
+/// HDR environment used without `--environment`, a procedural sky is used if
+/// the file doesn't exist.
+const ENVIRONMENT_PATH: &str = "assets/environment.hdr";
+
+/// Color grading tables (`.cube` files and strip images), listed after the
+/// generated ones.
+const LUTS_PATH: &str = "assets/luts";
+
+/// Width of the environment map preview.
+const INSPECTOR_WIDTH: u32 = 512;
+
+/// Size of every layer in the shadow map previews.
+const SHADOW_PREVIEW_SIZE: u32 = 128;
+
+/// What is drawn behind the UI.
+#[derive(Clone, Copy, PartialEq)]
+enum Demo {
+    Scene,
+    Raymarch,
+    NBody,
+    Blend,
+    Pulling,
+}
+
+/// Skeleton of a loaded scene and the objects it deforms.
+struct Animated {
+    name: String,
+    skeleton: Skeleton,
+    player: Player,
+    skinned: Vec<SkinnedObject>,
+    /// Objects with morph targets and the nodes whose weights they take.
+    morphed: Vec<(usize, usize)>,
+}
+
+struct SkinnedObject {
+    object: usize,
+    skin: usize,
+    joints: Tracked<Buffer>,
+    _vertices: Tracked<Buffer>,
+}
+
+/// Object with morph targets, whose weights are uploaded every frame.
+struct MorphObject {
+    object: usize,
+    vertex_count: usize,
+    weights: Tracked<Buffer>,
+    _deltas: Tracked<Buffer>,
+}
+
+/// Uniform block of a single face of the point light shadow cubemap.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct PointShadowFace {
+    view_projection: [[f32; 4]; 4],
+    /// Position in `xyz`, range in `w`.
+    light_position: [f32; 4],
+}
+
+/// What the renderer runs again with after a restart.
+struct Restart {
+    args: Args,
+    /// Scene of the previous run, opened instead of the one in `args`.
+    scene: SceneFile,
+}
+
 fn main() {
-    env_logger::builder()
+    let mut builder = env_logger::builder();
+    builder
         .filter(Some("gfx_backend_vulkan"), LevelFilter::Warn)
-        .filter(Some("gfx_memory"), LevelFilter::Warn)
-        .init();
+        .filter(Some("gfx_memory"), LevelFilter::Warn);
+    let mut console = Console::init(builder);
+    let mut args = Args::parse();
+    let mut scene = None;
+    // everything is created for the device, so starting a trace or switching
+    // adapters rebuilds it all, without leaving the process
+    while let Some(next) = run(&mut console, &args, scene) {
+        info!("Restarting the renderer");
+        args = next.args;
+        scene = Some(next.scene);
+    }
+}
+
+/// Creates the window and the renderer for `args` and runs until the window
+/// is closed, starting with `scene` if it's given. Returns what to run again
+/// with, if a restart was asked for.
+fn run(console: &mut Console, args: &Args, scene: Option<SceneFile>) -> Option<Restart> {
+    let bench = args.bench.map(Bench::new);
+    // golden frames, benchmarks and input recordings must not depend on
+    // previous runs
+    let persist_settings = args.compare.is_none()
+        && args.bench.is_none()
+        && args.record.is_none()
+        && args.replay.is_none();
+    let settings = if persist_settings {
+        Settings::load(SETTINGS_PATH)
+    } else {
+        Settings::default()
+    };
+    // the first frame of the profiler is the startup
+    profiler::new_frame();
+    if args.list_adapters {
+        let instance = Instance::new(adapter::BACKENDS);
+        for (i, info) in adapter::enumerate(&instance).iter().enumerate() {
+            println!("{}: {}", i, adapter::describe(info));
+        }
+        return None;
+    }
 
     let sdl = sdl2::init().unwrap();
     let mut events = sdl.event_pump().unwrap();
 
     // init window
     let video = sdl.video().unwrap();
-    let window = video
-        .window("wgpu", WIDTH as _, HEIGHT as _)
-        .position_centered()
-        .build()
-        .unwrap();
-
-    // init web gpu
-    let instance = Instance::new(BackendBit::VULKAN);
-    let surface = unsafe { instance.create_surface(&window) };
-    let adapter = futures::executor::block_on(instance.request_adapter(&RequestAdapterOptions {
-        power_preference: PowerPreference::Default,
-        compatible_surface: Some(&surface),
-    }))
-    .expect("Couldn't create adapter");
-    info!("Adapter info: {:?}", adapter.get_info());
-    info!("Adapter features: {:?}", adapter.features());
-    info!("Adapter limits: {:?}", adapter.limits());
-
-    // init device and swap chain.
-    let (device, queue) = futures::executor::block_on(adapter.request_device(
-        &DeviceDescriptor {
-            shader_validation: true,
-            ..Default::default()
-        },
-        None,
-    ))
-    .expect("Error requesting device");
-    info!("Device limits: {:?}", device.limits());
-    info!("Device features: {:?}", device.features());
-
-    let mut swap_chain = device.create_swap_chain(
-        &surface,
-        &SwapChainDescriptor {
-            usage: TextureUsage::OUTPUT_ATTACHMENT,
-            format: TextureFormat::Bgra8UnormSrgb,
-            width: WIDTH as _,
-            height: HEIGHT as _,
-            present_mode: PresentMode::Fifo,
+    let (window_width, window_height) = settings.window_size.unwrap_or((WIDTH as _, HEIGHT as _));
+    let mut window = video.window(title::TITLE, window_width, window_height);
+    match settings.window_position {
+        Some((x, y)) => window.position(x, y),
+        None => window.position_centered(),
+    };
+    window.allow_highdpi().resizable();
+    if args.compare.is_some() {
+        // frames are rendered offscreen
+        window.hidden();
+    }
+    let mut window = window.build().unwrap();
+
+    // no vsync while benchmarking
+    let present_mode = if bench.is_some() {
+        PresentMode::Immediate
+    } else {
+        settings.present_mode
+    };
+    let context = RendererContext::new(&window, args.adapter, args.trace.as_deref(), present_mode);
+    // events are pumped on this thread while another one renders
+    render_thread::run(&sdl, &mut window, &mut events, move |window| {
+        render(
+            console,
+            args,
+            scene,
+            settings,
+            bench,
+            persist_settings,
+            present_mode,
+            context,
+            window,
+        )
+    })
+}
+
+/// Renders into `window` from the render thread until it's closed, with what
+/// `run` set up. Returns what to run again with, like `run`.
+#[allow(clippy::too_many_arguments)]
+fn render(
+    console: &mut Console,
+    args: &Args,
+    restart_scene: Option<SceneFile>,
+    mut settings: Settings,
+    mut bench: Option<Bench>,
+    persist_settings: bool,
+    mut present_mode: PresentMode,
+    context: RendererContext,
+    window: WindowProxy,
+) -> Option<Restart> {
+    let (device, queue) = (&context.device, &context.queue);
+    let adapters = adapter::enumerate(&context.instance);
+    let adapter_index = context.adapter_index;
+
+    // render targets are created at the drawable size, which is larger than
+    // the window on HiDPI displays, and recreated when it's resized
+    let (mut width, mut height) = context.size();
+    let hidpi_factor = width as f32 / window.size().0 as f32;
+    info!(
+        "Drawable size: {}x{} (HiDPI factor {})",
+        width, height, hidpi_factor
+    );
+
+    let (mut depth, mut depth_view) = create_depth(device, width, height);
+    // the second viewport is drawn after the effects, and the depth of the
+    // main one is still needed by the overlays
+    let mut viewport_depth = create_viewport_depth(device, width, height);
+    // Shadow cascades, one layer of the array each.
+    let shadow_map = memory::create_texture(
+        device,
+        Category::RenderTargets,
+        &TextureDescriptor {
+            label: Some("Shadow cascades"),
+            size: Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth: CASCADES as _,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::SAMPLED,
         },
     );
+    let shadow_map_view = shadow_map.create_view(&TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    // the scene is drawn in HDR, then goes through the effects onto the output
+    let mut post = PostProcess::new(device, queue, context::FORMAT, &shadow_map_view);
+    let mut post_targets = post.targets(device, width, height, &depth);
+    let oit = Oit::new(device);
+    let mut oit_targets = oit.targets(device, width, height);
+    let mut msaa_targets = MsaaTargets::new(device, width, height, HDR_FORMAT, DEPTH_FORMAT);
+    let mut post_params = PostParams {
+        antialiasing: settings.antialiasing,
+        ..Default::default()
+    };
+    // the first table is no color grading
+    let mut luts: Vec<_> = std::iter::once((ImString::new("None"), None))
+        .chain(
+            Lut::generated()
+                .into_iter()
+                .map(|(name, lut)| (ImString::new(name), Some(lut))),
+        )
+        .collect();
+    match Lut::load_dir(LUTS_PATH) {
+        Ok(loaded) => luts.extend(
+            loaded
+                .into_iter()
+                .map(|(name, lut)| (ImString::new(name), Some(lut))),
+        ),
+        Err(err) => info!(
+            "Using generated color grading tables ({}: {})",
+            LUTS_PATH, err
+        ),
+    }
+    let mut selected_lut = 0;
 
     // Mesh data buffers.
-    #[repr(C)]
-    #[derive(Clone, Copy, Pod, Zeroable)]
-    struct Vertex {
-        _pos: [f32; 2],
-        _color: [f32; 3],
+    let mut meshes = vec![
+        MeshData::triangle(),
+        MeshData::load_obj("assets/cube.obj").expect("Error loading cube mesh"),
+        MeshData::plane(200.0),
+    ];
+    let mut gpu_meshes: Vec<_> = meshes
+        .iter()
+        .enumerate()
+        .map(|(i, mesh)| Mesh::new(device, &format!("Mesh {}", i), mesh))
+        .collect();
+    // of the triangle
+    let mut vertex_editor = VertexEditor::new(0);
+    let mut transform_gizmo = TransformGizmo::default();
+
+    let mut scene = Scene {
+        objects: vec![
+            Object::new("Left", 0, Vec3::new(-1.3, 0.0, 0.0), Vec3::unit_z(), 1.0),
+            Object::new("Cube", 1, Vec3::new(0.0, 0.0, 0.0), Vec3::one(), -0.7),
+            Object::new("Right", 0, Vec3::new(1.3, 0.0, 0.0), Vec3::unit_z(), 1.5),
+        ],
+    };
+    let mut ground = Object::new("Ground", 2, Vec3::new(0.0, -1.2, 0.0), Vec3::unit_y(), 0.0);
+    ground.pulse = 0.0;
+    // so there's something for rigid bodies to land on
+    ground.body = BodyKind::Static;
+    scene.objects.push(ground);
+    // a row of cubes receding into the distance, to cover all the cascades
+    for i in 1..=8 {
+        let x = if i % 2 == 0 { 2.5 } else { -2.5 };
+        let position = Vec3::new(x, -0.2, -6.0 * i as f32);
+        let name = format!("Pillar {}", i);
+        scene
+            .objects
+            .push(Object::new(&name, 1, position, Vec3::unit_y(), 0.3));
+    }
+    let mut prev_scene = scene.clone();
+    let mut interpolated = scene.clone();
+    let mut physics = Physics::new();
+    let mut gravity = true;
+    let mut show_colliders = false;
+
+    // shaders
+    let vert_module = device.create_shader_module(make_spirv(shaders::SHADER_VERT));
+    let frag_module = device.create_shader_module(make_spirv(shaders::SHADER_FRAG));
+    let oit_frag_module = device.create_shader_module(make_spirv(shaders::SHADER_OIT_FRAG));
+    let outline_vert_module = device.create_shader_module(make_spirv(shaders::OUTLINE_VERT));
+    let outline_frag_module = device.create_shader_module(make_spirv(shaders::OUTLINE_FRAG));
+    let instanced_vert_module = device.create_shader_module(make_spirv(shaders::INSTANCED_VERT));
+    let shadow_vert_module = device.create_shader_module(make_spirv(shaders::SHADOW_VERT));
+    let point_shadow_vert_module =
+        device.create_shader_module(make_spirv(shaders::POINT_SHADOW_VERT));
+    let point_shadow_frag_module =
+        device.create_shader_module(make_spirv(shaders::POINT_SHADOW_FRAG));
+    let probe_vert_module = device.create_shader_module(make_spirv(shaders::PROBE_VERT));
+    // render pipeline and bind groups
+    let bind_group_layout = scene_pipeline::object_layout(device);
+    // bound to objects that aren't skinned or morphed, which never read them
+    let no_joints = memory::create_buffer_init(
+        device,
+        Category::Storage,
+        &BufferInitDescriptor {
+            label: Some("No joints"),
+            contents: bytemuck::bytes_of(&Mat4::identity().to_cols_array()),
+            usage: BufferUsage::STORAGE,
+        },
+    );
+    let no_skin = memory::create_buffer_init(
+        device,
+        Category::Storage,
+        &BufferInitDescriptor {
+            label: Some("No skin"),
+            contents: bytemuck::bytes_of(&SkinVertex::default()),
+            usage: BufferUsage::STORAGE,
+        },
+    );
+    let no_deltas = memory::create_buffer_init(
+        device,
+        Category::Storage,
+        &BufferInitDescriptor {
+            label: Some("No morph targets"),
+            contents: bytemuck::bytes_of(&MorphDelta::default()),
+            usage: BufferUsage::STORAGE,
+        },
+    );
+    let no_weights = memory::create_buffer_init(
+        device,
+        Category::Storage,
+        &BufferInitDescriptor {
+            label: Some("No morph weights"),
+            contents: bytemuck::bytes_of(&0.0f32),
+            usage: BufferUsage::STORAGE,
+        },
+    );
+    let undeformed = || Deformation {
+        joints: &no_joints,
+        skin: &no_skin,
+        deltas: &no_deltas,
+        weights: &no_weights,
+    };
+    // Uniforms of the objects in one buffer, rewritten every frame with the
+    // interpolated transforms and materials.
+    let mut bindings = ObjectBindings::new("Objects");
+    // and other ones with the camera of the second viewport
+    let mut viewport_bindings = ObjectBindings::new("Objects (second viewport)");
+    for object in &scene.objects {
+        let layout = &bind_group_layout;
+        bindings.push(device, layout, &object.name, undeformed(), None);
+        viewport_bindings.push(device, layout, &object.name, undeformed(), None);
     }
+    // loaded scenes with skins or animations, and objects with morph targets
+    let mut animated: Vec<Animated> = Vec::new();
+    let mut morphed: Vec<MorphObject> = Vec::new();
+    // plays the clips of every loaded scene
+    let mut timeline = Timeline::default();
+
+    // Image based lighting maps, generated once.
+    let environment_path = args
+        .environment
+        .clone()
+        .unwrap_or_else(|| ENVIRONMENT_PATH.into());
+    let environment = Equirect::load(&environment_path).unwrap_or_else(|err| {
+        info!(
+            "Using procedural sky ({}: {})",
+            environment_path.display(),
+            err
+        );
+        Equirect::sky(512, 256)
+    });
+    let ibl = Ibl::new(device, queue, &environment);
+    let skybox = Skybox::new(device, &ibl, HDR_FORMAT, DEPTH_FORMAT);
+    let sky = Sky::new(device, HDR_FORMAT, DEPTH_FORMAT);
+    // the sky of the second viewport, with its own camera
+    let viewport_skybox = Skybox::new(device, &ibl, HDR_FORMAT, DEPTH_FORMAT);
+    let viewport_sky = Sky::new(device, HDR_FORMAT, DEPTH_FORMAT);
 
-    #[rustfmt::skip]
-    let vertex = device.create_buffer_init(&BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::bytes_of(&[
-            Vertex { _pos: [0.0, 0.0], _color: [1.0, 0.0, 0.0] },
-            Vertex { _pos: [1.0, 0.0], _color: [0.0, 1.0, 0.0] },
-            Vertex { _pos: [0.0, 1.0], _color: [0.0, 0.0, 1.0] },
-        ]),
-        usage: BufferUsage::VERTEX,
+    // Lighting uniforms, normal map and IBL maps, shared by all objects.
+    let lighting_uniform = memory::create_buffer(
+        device,
+        Category::Uniforms,
+        &BufferDescriptor {
+            label: Some("Lighting uniforms"),
+            size: Lighting::STD140.size as _,
+            usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        },
+    );
+    // the reflection probe is lit without itself
+    let probe_lighting_uniform = memory::create_buffer(
+        device,
+        Category::Uniforms,
+        &BufferDescriptor {
+            label: Some("Reflection probe lighting uniforms"),
+            size: Lighting::STD140.size as _,
+            usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        },
+    );
+    let mut reflection_probe = ReflectionProbe::new(device, &ibl, SHADOW_FORMAT);
+    let lights_buffer = memory::create_buffer(
+        device,
+        Category::Storage,
+        &BufferDescriptor {
+            label: Some("Lights"),
+            size: (MAX_LIGHTS * GpuLight::STD430.size) as _,
+            usage: BufferUsage::STORAGE | BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        },
+    );
+    let mut clusters = Clusters::new(device, &lights_buffer);
+    // mipmapped, so the sampler settings have visible effects on minification
+    let normal_map = texture::create_rgba8_mipmapped(
+        device,
+        queue,
+        "Normal map",
+        TextureFormat::Rgba8Unorm,
+        NORMAL_MAP_SIZE,
+        NORMAL_MAP_SIZE,
+        &texture::bumps_normal_map(NORMAL_MAP_SIZE, 4),
+    );
+    let normal_map_view = normal_map.create_view(&TextureViewDescriptor::default());
+    // kept to add the textures of loaded scenes
+    let mut material_layers = MaterialArrayBuilder::new(MATERIAL_SIZE, MATERIAL_FORMAT);
+    if let Err(err) = material_layers.add_dir(MATERIALS_PATH) {
+        info!("Using generated materials ({}: {})", MATERIALS_PATH, err);
+    }
+    if material_layers.is_empty() {
+        let size = MATERIAL_SIZE;
+        material_layers.add(size, size, texture::test_pattern(size));
+        material_layers.add(
+            size,
+            size,
+            texture::checker(size, 8, [230, 230, 230], [40, 40, 40]),
+        );
+        material_layers.add(
+            size,
+            size,
+            texture::checker(size, 4, [200, 60, 40], [240, 180, 60]),
+        );
+        material_layers.add(
+            size,
+            size,
+            texture::checker(size, 16, [40, 90, 200], [120, 200, 230]),
+        );
+    }
+    let mut materials = material_layers.build(device, queue, "Materials");
+    // arrays can't be empty, the first layer is never sampled
+    let mut linear_layers = MaterialArrayBuilder::new(MATERIAL_SIZE, LINEAR_MATERIAL_FORMAT);
+    linear_layers.add(1, 1, vec![255; 4]);
+    let mut linear_materials = linear_layers.build(device, queue, "Linear materials");
+    // what scene files refer to meshes and layers by
+    let mut sources = Sources::new(material_layers.len(), linear_layers.len());
+    let mut sampler_settings = SamplerSettings::default();
+    let mut samplers = SamplerCache::new("Material sampler");
+    let shadow_sampler = device.create_sampler(&SamplerDescriptor {
+        label: Some("Shadow sampler"),
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        compare: Some(CompareFunction::LessEqual),
+        ..Default::default()
     });
+    // the cascade uniforms are uploaded every frame
+    let mut frame_ring = FrameRing::new(device, FRAME_CONTEXTS);
+    let mut frame_contexts = FRAME_CONTEXTS as u32;
+    // the contexts are created again before the next frame starts on one
+    let mut frame_contexts_dirty = false;
+    let cascades: Vec<_> = (0..CASCADES)
+        .map(|i| {
+            shadow_map.create_view(&TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::D2),
+                base_array_layer: i as _,
+                array_layer_count: NonZeroU32::new(1),
+                ..Default::default()
+            })
+        })
+        .collect();
 
-    let index = device.create_buffer_init(&BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::bytes_of(&[0u16, 1, 2]),
-        usage: BufferUsage::INDEX,
+    // Point light distance cubemap, rendered one face at a time.
+    let point_shadow_map = memory::create_texture(
+        device,
+        Category::RenderTargets,
+        &TextureDescriptor {
+            label: Some("Point shadow map"),
+            size: Extent3d {
+                width: POINT_SHADOW_SIZE,
+                height: POINT_SHADOW_SIZE,
+                depth: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: POINT_SHADOW_FORMAT,
+            usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::SAMPLED,
+        },
+    );
+    let point_shadow_view = point_shadow_map.create_view(&TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        array_layer_count: NonZeroU32::new(6),
+        ..Default::default()
     });
+    let point_shadow_depth = memory::create_texture(
+        device,
+        Category::RenderTargets,
+        &TextureDescriptor {
+            label: Some("Point shadow depth"),
+            size: Extent3d {
+                width: POINT_SHADOW_SIZE,
+                height: POINT_SHADOW_SIZE,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: TextureUsage::OUTPUT_ATTACHMENT,
+        },
+    );
+    let point_shadow_depth_view = point_shadow_depth.create_view(&TextureViewDescriptor::default());
+    // 32 bit float textures can't be filtered
+    let point_shadow_sampler = device.create_sampler(&SamplerDescriptor::default());
+    let point_face_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Point shadow face bind group layout"),
+        entries: &[BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStage::VERTEX | ShaderStage::FRAGMENT,
+            ty: BindingType::UniformBuffer {
+                dynamic: false,
+                min_binding_size: BufferSize::new(std::mem::size_of::<PointShadowFace>() as _),
+            },
+            count: None,
+        }],
+    });
+    let point_faces: Vec<_> = (0..6)
+        .map(|face| {
+            let view = point_shadow_map.create_view(&TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::D2),
+                base_array_layer: face,
+                array_layer_count: NonZeroU32::new(1),
+                ..Default::default()
+            });
+            let uniform = memory::create_buffer(
+                device,
+                Category::Uniforms,
+                &BufferDescriptor {
+                    label: Some(&format!("Point shadow face {} uniforms", face)),
+                    size: std::mem::size_of::<PointShadowFace>() as _,
+                    usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            );
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some(&format!("Point shadow face {} bind group", face)),
+                layout: &point_face_layout,
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(uniform.slice(..)),
+                }],
+            });
+            (view, uniform, bind_group)
+        })
+        .collect();
 
-    // shaders
-    let vert_module = device.create_shader_module(include_spirv!("shader.vert.spv"));
-    let frag_module = device.create_shader_module(include_spirv!("shader.frag.spv"));
-    // render pipeline and bind groups
-    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-        label: None,
-        bind_group_layouts: &[],
+    let lighting_layout = scene_pipeline::lighting_layout(device);
+    // the buffers only, clustering is toggled from the UI
+    let (cluster_uniform, cluster_counts, cluster_indices) =
+        (&clusters.uniform, &clusters.counts, &clusters.indices);
+    // recreated whenever the normal map sampler or the material arrays change
+    let create_lighting_bind_group =
+        |uniform: &Buffer,
+         probe: &TextureView,
+         sampler: &Sampler,
+         materials: &MaterialArray,
+         linear_materials: &MaterialArray| {
+            LightingBindings {
+                uniform,
+                normal_map: &normal_map_view,
+                sampler,
+                irradiance: &ibl.irradiance_view,
+                prefiltered: &ibl.prefiltered_view,
+                brdf: &ibl.brdf_view,
+                ibl_sampler: &ibl.sampler,
+                shadow_map: &shadow_map_view,
+                shadow_sampler: &shadow_sampler,
+                point_shadow: &point_shadow_view,
+                point_shadow_sampler: &point_shadow_sampler,
+                materials: &materials.view,
+                linear_materials: &linear_materials.view,
+                lights: &lights_buffer,
+                probe,
+                cluster_uniform,
+                cluster_counts,
+                cluster_indices,
+            }
+            .create(device, &lighting_layout)
+        };
+    let mut lighting_bind_group = create_lighting_bind_group(
+        &lighting_uniform,
+        &reflection_probe.view,
+        samplers.get(device, &sampler_settings),
+        &materials,
+        &linear_materials,
+    );
+    // the probe can't be sampled while it's rendered, the environment stands
+    // in for it
+    let mut probe_lighting_bind_group = create_lighting_bind_group(
+        &probe_lighting_uniform,
+        &ibl.prefiltered_view,
+        samplers.get(device, &sampler_settings),
+        &materials,
+        &linear_materials,
+    );
+
+    let pipeline_layout =
+        scene_pipeline::pipeline_layout(device, &bind_group_layout, &lighting_layout);
+    // The depth pre-pass shares the vertex stage with the color pass so both
+    // produce the exact same depth values, which is what makes the `Equal`
+    // compare in the color pass work.
+    let color_states = [ColorStateDescriptor {
+        format: HDR_FORMAT,
+        alpha_blend: BlendDescriptor::default(),
+        color_blend: BlendDescriptor::default(),
+        write_mask: ColorWrite::default(),
+    }];
+    let blend_color_states = [ColorStateDescriptor {
+        color_blend: BlendDescriptor {
+            src_factor: BlendFactor::SrcAlpha,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add,
+        },
+        ..color_states[0].clone()
+    }];
+    let oit_color_states = Oit::color_states();
+    // overdraw adds up the fragments of every layer
+    let additive_color_states = [ColorStateDescriptor {
+        color_blend: BlendDescriptor {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        },
+        ..color_states[0].clone()
+    }];
+    // outlines are drawn after tone mapping, so they keep their color
+    let output_color_states = [ColorStateDescriptor {
+        format: context::FORMAT,
+        ..color_states[0].clone()
+    }];
+    let create_pipeline = |label,
+                           vert_module,
+                           frag_module: Option<&ShaderModule>,
+                           color_states,
+                           depth_compare,
+                           depth_write_enabled,
+                           stencil| {
+        scene_pipeline::create_pipeline(
+            device,
+            &pipeline_layout,
+            label,
+            vert_module,
+            frag_module,
+            color_states,
+            depth_compare,
+            depth_write_enabled,
+            stencil,
+            1,
+            Rasterization::default(),
+        )
+    };
+    // Objects write the stencil reference wherever they cover the screen, even
+    // if they are occluded, so outlines are only drawn around the silhouette.
+    let stencil_face = StencilStateFaceDescriptor {
+        compare: CompareFunction::Always,
+        fail_op: StencilOperation::Keep,
+        depth_fail_op: StencilOperation::Replace,
+        pass_op: StencilOperation::Replace,
+    };
+    let stencil_write = StencilStateDescriptor {
+        front: stencil_face.clone(),
+        back: stencil_face,
+        read_mask: !0,
+        write_mask: !0,
+    };
+    let outline_face = StencilStateFaceDescriptor {
+        compare: CompareFunction::NotEqual,
+        ..Default::default()
+    };
+    let stencil_outline = StencilStateDescriptor {
+        front: outline_face.clone(),
+        back: outline_face,
+        read_mask: !0,
+        write_mask: 0,
+    };
+    // Field of instanced cubes, culled on the GPU.
+    let field_instances = culling::scatter(
+        FIELD_INSTANCES,
+        Vec4::new(-80.0, 2.0, -120.0, 0.2),
+        Vec4::new(80.0, 20.0, 40.0, 0.8),
+        materials.layers,
+    );
+    // radius of the sphere around the unit cube
+    let field = GpuCulling::new(device, &field_instances, gpu_meshes[1].index_count(), 0.87);
+    let field_uniform = memory::create_buffer(
+        device,
+        Category::Uniforms,
+        &BufferDescriptor {
+            label: Some("Field uniforms"),
+            size: std::mem::size_of::<ObjectUniforms>() as _,
+            usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        },
+    );
+    let field_bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Field bind group"),
+        layout: &bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(field_uniform.slice(..)),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Buffer(no_joints.slice(..)),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::Buffer(no_skin.slice(..)),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::Buffer(no_deltas.slice(..)),
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: BindingResource::Buffer(no_weights.slice(..)),
+            },
+        ],
+    });
+    let create_instanced_pipeline = |label, color_states, depth_compare, depth_write_enabled| {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex_stage: ProgrammableStageDescriptor {
+                module: &instanced_vert_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(ProgrammableStageDescriptor {
+                module: &frag_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(RasterizationStateDescriptor {
+                front_face: FrontFace::Ccw,
+                cull_mode: CullMode::None,
+                clamp_depth: false,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
+                depth_bias_clamp: 0.0,
+            }),
+            primitive_topology: PrimitiveTopology::TriangleList,
+            color_states,
+            depth_stencil_state: Some(DepthStencilStateDescriptor {
+                format: DEPTH_FORMAT,
+                depth_write_enabled,
+                depth_compare,
+                stencil: StencilStateDescriptor::default(),
+            }),
+            vertex_state: VertexStateDescriptor {
+                index_format: mesh::INDEX_FORMAT,
+                vertex_buffers: &[
+                    Vertex::buffer_descriptor(InputStepMode::Vertex),
+                    VertexBufferDescriptor {
+                        stride: std::mem::size_of::<culling::Instance>() as _,
+                        step_mode: InputStepMode::Instance,
+                        attributes: &vertex_attr_array![5 => Float4, 6 => Uint],
+                    },
+                ],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        })
+    };
+    let instanced_pipeline =
+        create_instanced_pipeline("Field pipeline", &color_states, CompareFunction::Less, true);
+    let instanced_overdraw_pipeline = create_instanced_pipeline(
+        "Field pipeline (overdraw)",
+        &additive_color_states,
+        CompareFunction::Always,
+        false,
+    );
+
+    let render_pipeline = create_pipeline(
+        "Scene pipeline",
+        &vert_module,
+        Some(&frag_module),
+        &color_states,
+        CompareFunction::Less,
+        true,
+        stencil_write.clone(),
+    );
+    // blended objects are sorted instead
+    let blend_pipeline = create_pipeline(
+        "Scene pipeline (blended)",
+        &vert_module,
+        Some(&frag_module),
+        &blend_color_states,
+        CompareFunction::Less,
+        false,
+        stencil_write.clone(),
+    );
+    // or added up in any order into the targets of the OIT pass
+    let oit_pipeline = create_pipeline(
+        "Scene pipeline (OIT)",
+        &vert_module,
+        Some(&oit_frag_module),
+        &oit_color_states,
+        CompareFunction::Less,
+        false,
+        stencil_write.clone(),
+    );
+    let overdraw_pipeline = create_pipeline(
+        "Scene pipeline (overdraw)",
+        &vert_module,
+        Some(&frag_module),
+        &additive_color_states,
+        CompareFunction::Always,
+        false,
+        stencil_write.clone(),
+    );
+    // with the stencil too, which the multisampled pass doesn't resolve
+    let prepass_pipeline = create_pipeline(
+        "Depth pre-pass pipeline",
+        &vert_module,
+        None,
+        &[],
+        CompareFunction::Less,
+        true,
+        stencil_write.clone(),
+    );
+    let render_pipeline_equal = create_pipeline(
+        "Scene pipeline (depth equal)",
+        &vert_module,
+        Some(&frag_module),
+        &color_states,
+        CompareFunction::Equal,
+        false,
+        stencil_write.clone(),
+    );
+    let msaa_pipeline = scene_pipeline::create_pipeline(
+        device,
+        &pipeline_layout,
+        "Scene pipeline (multisampled)",
+        &vert_module,
+        Some(&frag_module),
+        &color_states,
+        CompareFunction::Less,
+        true,
+        StencilStateDescriptor::default(),
+        msaa::SAMPLE_COUNT,
+        Rasterization::default(),
+    );
+    // opaque pipelines of variants of the lit shader, without the code of
+    // the features toggled off
+    let mut scene_variants =
+        ShaderVariants::new("shader.frag", include_str!("shaders/shader.frag"));
+    let outline_pipeline = create_pipeline(
+        "Outline pipeline",
+        &outline_vert_module,
+        Some(&outline_frag_module),
+        &output_color_states,
+        CompareFunction::Always,
+        false,
+        stencil_outline,
+    );
+
+    let shadow_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Shadow pipeline layout"),
+        bind_group_layouts: &[&bind_group_layout, frame_ring.layout()],
+        push_constant_ranges: &[],
+    });
+    let shadow_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Shadow pipeline"),
+        layout: Some(&shadow_pipeline_layout),
+        vertex_stage: ProgrammableStageDescriptor {
+            module: &shadow_vert_module,
+            entry_point: "main",
+        },
+        fragment_stage: None,
+        rasterization_state: Some(RasterizationStateDescriptor {
+            front_face: FrontFace::Ccw,
+            cull_mode: CullMode::None,
+            clamp_depth: false,
+            depth_bias: 2,
+            depth_bias_slope_scale: 2.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: PrimitiveTopology::TriangleList,
+        color_states: &[],
+        depth_stencil_state: Some(DepthStencilStateDescriptor {
+            format: SHADOW_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::LessEqual,
+            stencil: StencilStateDescriptor::default(),
+        }),
+        vertex_state: VertexStateDescriptor {
+            index_format: mesh::INDEX_FORMAT,
+            vertex_buffers: &[VertexBufferDescriptor {
+                // only the position
+                attributes: &Vertex::ATTRIBUTES[..1],
+                ..Vertex::buffer_descriptor(InputStepMode::Vertex)
+            }],
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    });
+
+    let point_shadow_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Point shadow pipeline layout"),
+        bind_group_layouts: &[&bind_group_layout, &point_face_layout],
         push_constant_ranges: &[],
     });
-    let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-        label: None,
-        layout: Some(&pipeline_layout),
+    let point_shadow_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Point shadow pipeline"),
+        layout: Some(&point_shadow_pipeline_layout),
         vertex_stage: ProgrammableStageDescriptor {
-            module: &vert_module,
+            module: &point_shadow_vert_module,
             entry_point: "main",
         },
         fragment_stage: Some(ProgrammableStageDescriptor {
-            module: &frag_module,
+            module: &point_shadow_frag_module,
             entry_point: "main",
         }),
         rasterization_state: Some(RasterizationStateDescriptor {
@@ -123,18 +1107,23 @@ fn main() {
         }),
         primitive_topology: PrimitiveTopology::TriangleList,
         color_states: &[ColorStateDescriptor {
-            format: TextureFormat::Bgra8UnormSrgb,
-            alpha_blend: BlendDescriptor::default(),
-            color_blend: BlendDescriptor::default(),
-            write_mask: ColorWrite::default(),
+            format: POINT_SHADOW_FORMAT,
+            alpha_blend: BlendDescriptor::REPLACE,
+            color_blend: BlendDescriptor::REPLACE,
+            write_mask: ColorWrite::ALL,
         }],
-        depth_stencil_state: None,
+        depth_stencil_state: Some(DepthStencilStateDescriptor {
+            format: SHADOW_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::Less,
+            stencil: StencilStateDescriptor::default(),
+        }),
         vertex_state: VertexStateDescriptor {
-            index_format: IndexFormat::Uint16,
+            index_format: mesh::INDEX_FORMAT,
             vertex_buffers: &[VertexBufferDescriptor {
-                stride: std::mem::size_of::<[f32; 2]>() as _,
-                step_mode: InputStepMode::Vertex,
-                attributes: &vertex_attr_array![0 => Float2, 1 => Float3][..],
+                // only the position
+                attributes: &Vertex::ATTRIBUTES[..1],
+                ..Vertex::buffer_descriptor(InputStepMode::Vertex)
             }],
         },
         sample_count: 1,
@@ -142,91 +1131,3458 @@ fn main() {
         alpha_to_coverage_enabled: false,
     });
 
+    let probe_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Reflection probe pipeline layout"),
+        bind_group_layouts: &[
+            &bind_group_layout,
+            &lighting_layout,
+            &reflection_probe.face_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+    let probe_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Reflection probe pipeline"),
+        layout: Some(&probe_pipeline_layout),
+        vertex_stage: ProgrammableStageDescriptor {
+            module: &probe_vert_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(ProgrammableStageDescriptor {
+            module: &frag_module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(RasterizationStateDescriptor {
+            // the faces are flipped vertically, and so is the winding one-sided
+            // materials are culled by
+            front_face: FrontFace::Cw,
+            cull_mode: CullMode::None,
+            clamp_depth: false,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: PrimitiveTopology::TriangleList,
+        color_states: &[ColorStateDescriptor {
+            format: ibl::CUBE_FORMAT,
+            alpha_blend: BlendDescriptor::REPLACE,
+            color_blend: BlendDescriptor::REPLACE,
+            write_mask: ColorWrite::ALL,
+        }],
+        depth_stencil_state: Some(DepthStencilStateDescriptor {
+            format: SHADOW_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::Less,
+            stencil: StencilStateDescriptor::default(),
+        }),
+        vertex_state: VertexStateDescriptor {
+            index_format: mesh::INDEX_FORMAT,
+            vertex_buffers: &[Vertex::buffer_descriptor(InputStepMode::Vertex)],
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    });
+
+    let mut raymarch = Raymarch::new(device, context::FORMAT);
+    let mut blend_playground = BlendPlayground::new(device, context::FORMAT);
+    let mut nbody_params = NBodyParams::default();
+    let mut nbody = NBody::new(device, context::FORMAT, nbody_params.gravity);
+    let mut pulling_params = PullingParams::default();
+    let mut vertex_pulling = VertexPulling::new(device, context::FORMAT, &pulling_params);
+    let mut reset_nbody = false;
+    let sort = BitonicSort::new(device);
+    let mut sort_len = 100_000;
+    let mut test_sort = false;
+    // whether the last self test passed, and how long it took
+    let mut sort_result: Option<(bool, Duration)> = None;
+    let scan = Scan::new(device);
+    let mut scan_len = 100_000;
+    let mut test_scan = false;
+    let mut scan_result: Option<(bool, Duration)> = None;
+    let mut text = TextRenderer::new(device, queue, context::FORMAT, DEPTH_FORMAT);
+    let mut gizmos = Gizmos::new(device, context::FORMAT, DEPTH_FORMAT);
+    let grid = Grid::new(device, HDR_FORMAT);
+    let mut decal_renderer = Decals::new(device, queue, HDR_FORMAT);
+    let mut decal_depth = decal_renderer.depth_bind_group(device, &depth_view);
+
     // init imgui
     let mut imgui = imgui::Context::create();
-    let mut imgui_sdl2 = imgui_sdl2::ImguiSdl2::new(&mut imgui, &window);
+    // the layout is saved along with the rest of the settings
+    imgui.set_ini_filename(None);
+    imgui.load_ini_settings(&settings.imgui_ini);
+    let mut imgui_platform = ImguiPlatform::new(&mut imgui, &window);
+    // rasterize the font at the drawable resolution and scale it back down to
+    // window coordinates, so the text stays sharp. The platform already sets the
+    // framebuffer scale.
+    settings.style.load_font(&mut imgui, hidpi_factor);
+    imgui.io_mut().font_global_scale = 1.0 / hidpi_factor;
+    settings.style.apply_colors(imgui.style_mut());
+    // applied between imgui frames
+    let mut style_dirty = false;
+    let mut font_dirty = false;
+    let mut font_path = ImString::with_capacity(256);
+    font_path.push_str(&settings.style.font_path);
     let mut imgui_wgpu = imgui_wgpu::Renderer::new(
         &mut imgui,
-        &device,
-        &queue,
+        device,
+        queue,
         imgui_wgpu::RendererConfig {
-            texture_format: TextureFormat::Bgra8UnormSrgb,
+            texture_format: context::FORMAT,
             ..Default::default()
         },
     );
 
+    // Compute filter playground, displayed through imgui.
+    let (filter_width, filter_height, filter_pixels) = texture::load_rgba8(FILTER_IMAGE_PATH)
+        .unwrap_or_else(|err| {
+            info!("Using test pattern ({}: {})", FILTER_IMAGE_PATH, err);
+            let size = FILTER_IMAGE_SIZE;
+            (size, size, texture::test_pattern(size))
+        });
+    let filter_config = |label, usage| imgui_wgpu::TextureConfig {
+        label: Some(label),
+        size: Extent3d {
+            width: filter_width,
+            height: filter_height,
+            depth: 1,
+        },
+        format: Some(FILTER_FORMAT),
+        usage,
+        ..Default::default()
+    };
+    let mut texture_browser = TextureBrowser::new(device, &mut imgui_wgpu);
+    let filter_info = TextureInfo::of(&filter_config("Filter source", TextureUsage::SAMPLED));
+    let filter_source = imgui_wgpu::Texture::new(
+        device,
+        &imgui_wgpu,
+        filter_config(
+            "Filter source",
+            TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+        ),
+    );
+    filter_source.write(queue, &filter_pixels, filter_width, filter_height);
+    let filter_output = imgui_wgpu::Texture::new(
+        device,
+        &imgui_wgpu,
+        filter_config(
+            "Filter output",
+            TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+        ),
+    );
+    let mut image_filter =
+        ImageFilter::new(device, filter_source.view(), filter_width, filter_height);
+    // imgui textures are owned by the renderer, only their size is recorded
+    let _filter_memory = memory::track(
+        (),
+        Category::Textures,
+        Some("Filter images"),
+        2 * 4 * filter_width as u64 * filter_height as u64,
+    );
+    let filter_source_id = imgui_wgpu.textures.insert(filter_source);
+    let filter_output_id = imgui_wgpu.textures.insert(filter_output);
+    texture_browser.register("Filter source", filter_source_id, filter_info);
+    texture_browser.register("Filter output", filter_output_id, filter_info);
+    // images are shown at a fixed width
+    let filter_display = [
+        FILTER_IMAGE_SIZE as f32,
+        FILTER_IMAGE_SIZE as f32 * filter_height as f32 / filter_width as f32,
+    ];
+    // environment map preview
+    let inspector_size = [
+        INSPECTOR_WIDTH,
+        (INSPECTOR_WIDTH * environment.height / environment.width).max(1),
+    ];
+    let inspector_config = imgui_wgpu::TextureConfig {
+        label: Some("Environment preview"),
+        size: Extent3d {
+            width: inspector_size[0],
+            height: inspector_size[1],
+            depth: 1,
+        },
+        format: Some(TextureFormat::Rgba8UnormSrgb),
+        usage: TextureUsage::SAMPLED | TextureUsage::OUTPUT_ATTACHMENT,
+        ..Default::default()
+    };
+    let inspector_info = TextureInfo::of(&inspector_config);
+    let inspector_target = imgui_wgpu::Texture::new(device, &imgui_wgpu, inspector_config);
+    let inspector =
+        TextureInspector::new(device, &ibl.equirect_view, TextureFormat::Rgba8UnormSrgb);
+    let _inspector_memory = memory::track(
+        (),
+        Category::RenderTargets,
+        Some("Environment preview"),
+        4 * inspector_size[0] as u64 * inspector_size[1] as u64,
+    );
+    let inspector_id = imgui_wgpu.textures.insert(inspector_target);
+    texture_browser.register("Environment preview", inspector_id, inspector_info);
+    // shadow map previews, every layer side by side
+    let point_shadow_layers = point_shadow_map.create_view(&TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    let shadow_previews: Vec<_> = [
+        ("Shadow cascades preview", &shadow_map_view, CASCADES as u32),
+        ("Point shadow preview", &point_shadow_layers, 6),
+    ]
+    .iter()
+    .map(|&(label, view, layers)| {
+        let size = [SHADOW_PREVIEW_SIZE * layers, SHADOW_PREVIEW_SIZE];
+        let config = imgui_wgpu::TextureConfig {
+            label: Some(label),
+            size: Extent3d {
+                width: size[0],
+                height: size[1],
+                depth: 1,
+            },
+            format: Some(TextureFormat::Rgba8UnormSrgb),
+            usage: TextureUsage::SAMPLED | TextureUsage::OUTPUT_ATTACHMENT,
+            ..Default::default()
+        };
+        let info = TextureInfo::of(&config);
+        let target = imgui_wgpu::Texture::new(device, &imgui_wgpu, config);
+        let inspector = ShadowInspector::new(device, view, layers, TextureFormat::Rgba8UnormSrgb);
+        let bytes = 4 * size[0] as u64 * size[1] as u64;
+        let memory = memory::track((), Category::RenderTargets, Some(label), bytes);
+        let size = [size[0] as f32, size[1] as f32];
+        let id = imgui_wgpu.textures.insert(target);
+        texture_browser.register(label, id, info);
+        (id, inspector, size, memory)
+    })
+    .collect();
+    // previews of the material layers, added along with the layers
+    let mut color_thumbnails = Vec::new();
+    let mut linear_thumbnails = Vec::new();
+    add_thumbnails(
+        device,
+        queue,
+        &mut imgui_wgpu,
+        &mut texture_browser,
+        &material_layers,
+        &mut color_thumbnails,
+        "Materials",
+    );
+    add_thumbnails(
+        device,
+        queue,
+        &mut imgui_wgpu,
+        &mut texture_browser,
+        &linear_layers,
+        &mut linear_thumbnails,
+        "Linear materials",
+    );
+    let debug_view_names: Vec<_> = DEBUG_VIEWS
+        .iter()
+        .map(|v| ImString::new(v.name()))
+        .collect();
+    let debug_view_names: Vec<_> = debug_view_names.iter().collect();
+    let antialiasing_names: Vec<_> = ANTIALIASING
+        .iter()
+        .map(|a| ImString::new(a.name()))
+        .collect();
+    let antialiasing_names: Vec<_> = antialiasing_names.iter().collect();
+    let body_kind_names: Vec<_> = BODY_KINDS.iter().map(|k| ImString::new(k.name())).collect();
+    let body_kind_names: Vec<_> = body_kind_names.iter().collect();
+
+    // images dropped onto the window, with their display size
+    let mut dropped_images = Vec::new();
+
+    let mut camera = settings.camera.clone();
+    let mut bookmarks = Bookmarks::new(settings.bookmarks.clone());
+    let mut bookmark_name = ImString::with_capacity(64);
+    // cursor captured in relative mouse mode to look around
+    let mut mouse_look = false;
+    let mut frustum = Frustum {
+        view: camera.view(),
+        fov_y: settings.fov_y,
+        aspect: WIDTH as f32 / HEIGHT as f32,
+        near: 0.1,
+    };
+    let mut projection =
+        Mat4::perspective_rh(frustum.fov_y, frustum.aspect, frustum.near, FAR_PLANE);
+    // of the last frame drawn, for motion blur
+    let mut previous_view_projection = projection * frustum.view;
+    // unless the sun of the procedural sky is the light
+    let fixed_light_direction = Vec3::new(-0.5, -1.0, -0.7);
+
+    let mut demo = Demo::Scene;
+    let mut time = Time::new();
+    let mut timestep = FixedTimestep::new(UPDATE_RATE);
+    let mut gpu_timer = GpuTimer::default();
+    let mut measure_gpu = bench.is_some();
+    // offscreen target of golden frames and screenshots
+    let mut capture = Capture::new(device, width, height, context::FORMAT);
+    let golden = args.compare.clone();
+    // the app runs without them if they can't be opened
+    let mut recorder = args
+        .record
+        .as_ref()
+        .and_then(|path| match Recorder::create(path) {
+            Ok(recorder) => Some(recorder),
+            Err(err) => {
+                error!("Error creating input recording {}: {}", path.display(), err);
+                None
+            }
+        });
+    let mut replay = args
+        .replay
+        .as_ref()
+        .and_then(|path| match Replay::load(path, window.id()) {
+            Ok(replay) => Some(replay),
+            Err(err) => {
+                error!("Error loading input recording {}: {}", path.display(), err);
+                None
+            }
+        });
+    // recorded input is replayed on the same frames, which must then be as long
+    let fixed_delta = golden.is_some() || recorder.is_some() || replay.is_some();
+    let mut screenshot = false;
+    // rendered at a multiple of the window size on the next frame
+    let mut supersampled_screenshot = false;
+    let mut supersample_factor = supersample::FACTORS.len() - 1;
+    let mut gif_settings = GifSettings::default();
+    let mut gif_recorder: Option<GifRecorder> = None;
+    let mut golden_failures = 0;
+    let mut frame_index = 0;
+    // tracing starts with the device, so a new trace needs a restart
+    let mut trace_dir = ImString::with_capacity(256);
+    trace_dir.push_str(match &args.trace {
+        Some(dir) => dir.to_str().unwrap_or("trace"),
+        None => "trace",
+    });
+    let mut restart = false;
+    let mut restart_with = None;
+    // recent files are opened again through the same path as dropped ones
+    let mut reopen: Option<String> = None;
+    // shown between frames, the dialog blocks until it's closed
+    let mut open_dialog: Option<(&str, &dialog::Filter)> = None;
+    // texture slot of the selected material to load a texture into
+    let mut texture_dialog: Option<TextureSlot> = None;
+    let mut lut_dialog = false;
+    let mut save_scene_dialog = false;
+    // replaces the scene after the events of the frame
+    let mut opened_scene = restart_scene.or_else(|| {
+        args.scene
+            .as_ref()
+            .and_then(|path| match SceneFile::load(path) {
+                Ok(file) => Some(file),
+                Err(err) => {
+                    let path = path.display();
+                    error!("Error opening {}, keeping the default scene: {}", path, err);
+                    None
+                }
+            })
+    });
+    let mut reset_settings = false;
+    let mut reset_layout = false;
+    let mut minimized = false;
+    let mut window_size = window_size_f32(&window);
+    let mut focused = true;
+    let mut throttle_unfocused = bench.is_none();
+    // action waiting for a key to be bound to
+    let mut rebinding: Option<Action> = None;
+    // switching adapters restarts the renderer too
+    let mut selected_adapter = adapter_index.unwrap_or(0);
+    let mut switch_adapter = false;
+    let adapter_name = adapter_index.map_or("Unknown adapter", |i| &adapters[i].name);
+    let mut title_status = TitleStatus::new(adapter_name);
+
+    // Copies of the triangle stacked back to front, to produce overdraw.
+    let mut layers = 1u32;
+    let mut depth_prepass = false;
+    let mut normal_mapping = true;
+    let mut image_based_lighting = true;
+    let mut show_skybox = true;
+    let mut show_grid = false;
+    let mut grid_params = GridParams::default();
+    let mut viewport_params = ViewportParams::default();
+    let mut sky_params = SkyParams::default();
+    let mut inspector_params = InspectorParams::default();
+    // the preview is only drawn again when its parameters change
+    let mut inspector_dirty = true;
+    let mut light_intensity = 3.0;
+    let mut shadows = true;
+    let mut shader_variants = false;
+    // pipelines of the opaque objects, for each rasterization other than the
+    // default one that was asked for
+    let mut rasterization = Rasterization::default();
+    let mut rasterization_pipelines = HashMap::new();
+    let mut shadow_params = ShadowParams::default();
+    // depths stretched from black to white in the shadow map previews
+    let mut shadow_depth_range = [0.0, 1.0];
+    let mut cascade_debug = false;
+    let mut debug_view = DebugView::Final;
+    let mut debug_depth_range = 20.0;
+    let mut point_light = PointLight {
+        position: Vec3::new(0.0, 0.3, 1.2),
+        range: 10.0,
+        color: Vec3::new(1.0, 0.7, 0.4),
+        intensity: 4.0,
+    };
+    let mut point_shadows = true;
+    // unshadowed lights, edited in their own window
+    let mut lights: Vec<Light> = Vec::new();
+    let mut decals: Vec<Decal> = Vec::new();
+    let mut selected_decal = None;
+    let mut show_decals = true;
+    let mut show_gizmos = true;
+    let mut lod_params = LodParams::default();
+    let mut lod_selector = LodSelector::default();
+    let mut parallel_encoding = ParallelEncoding::default();
+    let mut scene_bundles = SceneBundles::default();
+    let mut show_field = true;
+    let mut gpu_culling = true;
+    // visible instances counted by the GPU and the CPU
+    let mut verify_culling = false;
+    let mut culling_counts = None;
+    // CPU count of the last verification still being read back
+    let mut culling_expected: Option<u32> = None;
+    let mut filter_params = FilterParams::default();
+    // filters only run when their parameters change
+    let mut filter_dirty = true;
+    let mut sampler_dirty = false;
+    // the arrays grow with the textures of loaded scenes
+    let mut materials_dirty = false;
+    let mut frame_stats = FrameStats::default();
+    let mut frames_in_flight = FramesInFlight::new(device);
+    // the swap chain is recreated before acquiring the next frame
+    let mut present_mode_dirty = false;
+    let present_mode_names = [im_str!("Fifo"), im_str!("Mailbox"), im_str!("Immediate")];
+    let mut show_labels = true;
+    // the scene script, edited in its window and run from there
+    let mut show_script = false;
+    let mut script_path = ImString::with_capacity(256);
+    script_path.push_str(SCRIPT_PATH);
+    let mut script_source = ImString::new(std::fs::read_to_string(SCRIPT_PATH).unwrap_or_default());
+    let mut script: Option<Script> = None;
+    let mut script_error: Option<ScriptError> = None;
+    let mut script_time = 0.0;
+    let mut run_script = false;
+    let mut load_script = false;
+    let mut save_script = false;
+    // run again when the file changes
+    let mut watch_script = false;
+    let mut script_modified = std::fs::metadata(SCRIPT_PATH)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    // removed when the script runs again
+    let mut script_objects: Vec<String> = Vec::new();
+    // the ray marching shader, edited and compiled in the app
+    let mut shader_source = ImString::new(raymarch::SOURCE);
+    // the pipeline keeps the last shader that compiled
+    let mut shader_error: Option<CompileError> = None;
+    let mut compile_shader = false;
+    let mut label_size = 0.15;
+    let mut text_style = TextStyle::default();
+
     'main: loop {
-        for event in events.poll_iter() {
+        profiler::new_frame();
+        draw_stats::new_frame();
+        let _frame_scope = profiler::scope("Frame");
+        let frame_start = Instant::now();
+        let events_scope = profiler::scope("Events");
+        frame_stats.count_in_flight(device, &mut frames_in_flight);
+        if frame_contexts_dirty {
+            frame_ring.resize(device, frame_contexts as _);
+            culling_expected = None;
+            frame_contexts_dirty = false;
+        }
+        // only culling reads back
+        if let (Some(indirect), Some(cpu)) = (frame_ring.begin(device), culling_expected) {
+            let gpu = GpuCulling::visible_count(&indirect);
+            if gpu != cpu {
+                warn!(
+                    "GPU culling found {} visible instances, expected {}",
+                    gpu, cpu
+                );
+            }
+            culling_counts = Some((gpu, cpu));
+            culling_expected = None;
+        }
+        if let Some((title, filter)) = open_dialog.take() {
+            if let Some(path) = dialog::open_file(title, filter) {
+                reopen = Some(path.to_string_lossy().into_owned());
+            }
+        }
+        if let Some(slot) = texture_dialog.take() {
+            let path = dialog::open_file("Open material texture", &dialog::TEXTURES);
+            // compressed textures are decompressed, layers are all RGBA8
+            match path.map(|path| (assets::load(&path, &Capabilities::default()), path)) {
+                Some((
+                    Ok(Asset::Image {
+                        width,
+                        height,
+                        pixels,
+                    }),
+                    path,
+                )) => {
+                    let (layers, array, thumbnails, label) = if slot.is_color() {
+                        (
+                            &mut material_layers,
+                            &mut materials,
+                            &mut color_thumbnails,
+                            "Materials",
+                        )
+                    } else {
+                        (
+                            &mut linear_layers,
+                            &mut linear_materials,
+                            &mut linear_thumbnails,
+                            "Linear materials",
+                        )
+                    };
+                    let layer = layers.add(width, height, pixels);
+                    sources.images.insert((slot.is_color(), layer), path);
+                    *array = layers.build(device, queue, label);
+                    add_thumbnails(
+                        device,
+                        queue,
+                        &mut imgui_wgpu,
+                        &mut texture_browser,
+                        layers,
+                        thumbnails,
+                        label,
+                    );
+                    materials_dirty = true;
+                    if let Some(object) = scene.objects.iter_mut().find(|o| o.selected) {
+                        *object.material.texture_mut(slot) = Some(layer);
+                    }
+                }
+                Some((Ok(_), path)) => warn!("{} isn't an image", path.display()),
+                Some((Err(err), path)) => warn!("Error loading {}: {}", path.display(), err),
+                None => {}
+            }
+        }
+        if std::mem::take(&mut save_scene_dialog) {
+            if let Some(path) = dialog::save_file("Save scene", &dialog::SCENE_FILES) {
+                let pose = camera.pose(frustum.fov_y);
+                let file = SceneFile::new(&scene.objects, &lights, pose, &sources);
+                match file.save(&path) {
+                    Ok(()) => {
+                        info!("Saved {}", path.display());
+                        settings.add_recent_file(&path.to_string_lossy());
+                    }
+                    Err(err) => warn!("Error saving {}: {}", path.display(), err),
+                }
+            }
+        }
+        if std::mem::take(&mut lut_dialog) {
+            // strips are images, so tables aren't opened as dropped files
+            if let Some(path) = dialog::open_file("Open color grading table", &dialog::LUTS) {
+                match Lut::load(&path) {
+                    Ok(lut) => {
+                        luts.push((ImString::new(lut::name(&path)), Some(lut)));
+                        selected_lut = luts.len() - 1;
+                        post.set_lut(device, queue, luts[selected_lut].1.as_ref());
+                    }
+                    Err(err) => warn!("Error loading {}: {}", path.display(), err),
+                }
+            }
+        }
+        let reopened = reopen.take().map(|filename| Event::DropFile {
+            timestamp: 0,
+            window_id: window.id(),
+            filename,
+        });
+        let replayed = replay.as_mut().map(|replay| replay.events(frame_index));
+        let is_input = |event: &Event| event.is_keyboard() || event.is_mouse() || event.is_text();
+        // a replay ignores the input of the user
+        let replaying = replayed.is_some();
+        let mut resized = false;
+        let polled = window
+            .poll_iter()
+            .filter(|event| !replaying || !is_input(event));
+        for event in polled.chain(reopened).chain(replayed.into_iter().flatten()) {
+            if let Some(Err(err)) = recorder.as_mut().map(|r| r.record(frame_index, &event)) {
+                error!("Error recording input, stopping the recording: {}", err);
+                recorder = None;
+            }
+            // replayed input happened in another run
+            if (event.is_keyboard() || event.is_mouse()) && replay.is_none() {
+                frame_stats.input(event.get_timestamp());
+            }
+            if let (
+                Some(action),
+                Event::KeyDown {
+                    scancode: Some(key),
+                    ..
+                },
+            ) = (rebinding, &event)
+            {
+                settings.bindings.set(action, *key);
+                rebinding = None;
+                continue;
+            }
+            // imgui doesn't get any input while the cursor is captured
+            if !mouse_look {
+                imgui_platform.handle_event(&mut imgui, &event);
+                if imgui_platform.ignore_event(&event) {
+                    continue;
+                }
+            }
+
             match event {
                 Event::Window {
                     win_event: WindowEvent::Close,
                     ..
                 } => break 'main,
-                _ if !imgui_sdl2.ignore_event(&event) => {
-                    imgui_sdl2.handle_event(&mut imgui, &event);
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
+                    ..
+                } => {
+                    mouse_look = false;
+                    focused = false;
+                }
+                Event::Window {
+                    win_event: WindowEvent::FocusGained,
+                    ..
+                } => focused = true,
+                Event::Window {
+                    win_event: WindowEvent::Minimized,
+                    ..
+                } => minimized = true,
+                Event::Window {
+                    win_event: WindowEvent::Restored | WindowEvent::Maximized,
+                    ..
+                } => minimized = false,
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                } => resized = true,
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Right,
+                    ..
+                } => mouse_look = true,
+                Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Right,
+                    ..
+                } => mouse_look = false,
+                Event::KeyDown {
+                    scancode: Some(key),
+                    repeat: false,
+                    keymod,
+                    ..
+                } => match settings.bindings.action(key) {
+                    Some(Action::ToggleMouseLook) => mouse_look = !mouse_look,
+                    Some(Action::ReleaseMouse) => mouse_look = false,
+                    Some(Action::Screenshot)
+                        if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) =>
+                    {
+                        supersampled_screenshot = true
+                    }
+                    Some(Action::Screenshot) => screenshot = true,
+                    Some(Action::SaveBookmark) => bookmarks.list.push(Bookmark {
+                        name: format!("Bookmark {}", bookmarks.list.len() + 1),
+                        pose: camera.pose(frustum.fov_y),
+                    }),
+                    Some(Action::NextBookmark) => bookmarks.go_to_next(camera.pose(frustum.fov_y)),
+                    _ => {}
+                },
+                Event::MouseMotion { xrel, yrel, .. } if mouse_look => camera.look(xrel, yrel),
+                Event::DropFile { filename, .. } => {
+                    settings.add_recent_file(&filename);
+                    match assets::load(&filename, &context.capabilities) {
+                        Ok(Asset::Image {
+                            width,
+                            height,
+                            pixels,
+                        }) => {
+                            let config = imgui_wgpu::TextureConfig {
+                                label: Some(&filename),
+                                size: Extent3d {
+                                    width,
+                                    height,
+                                    depth: 1,
+                                },
+                                format: Some(TextureFormat::Rgba8UnormSrgb),
+                                usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+                                ..Default::default()
+                            };
+                            let info = TextureInfo::of(&config);
+                            let texture = imgui_wgpu::Texture::new(device, &imgui_wgpu, config);
+                            texture.write(queue, &pixels, width, height);
+                            let size =
+                                [PREVIEW_WIDTH, PREVIEW_WIDTH * height as f32 / width as f32];
+                            let id = imgui_wgpu.textures.insert(texture);
+                            texture_browser.register(&filename, id, info);
+                            let bytes = pixels.len() as u64;
+                            let memory =
+                                memory::track((), Category::Textures, Some(&filename), bytes);
+                            dropped_images.push((ImString::new(filename), id, size, memory));
+                        }
+                        Ok(Asset::Compressed(image)) => {
+                            let config = imgui_wgpu::TextureConfig {
+                                label: Some(&filename),
+                                size: Extent3d {
+                                    width: image.width,
+                                    height: image.height,
+                                    depth: 1,
+                                },
+                                format: Some(image.texture_format()),
+                                usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+                                mip_level_count: image.gpu_levels(),
+                                ..Default::default()
+                            };
+                            let info = TextureInfo::of(&config);
+                            let texture = imgui_wgpu::Texture::new(device, &imgui_wgpu, config);
+                            image.upload(queue, texture.texture());
+                            let aspect = image.height as f32 / image.width as f32;
+                            let size = [PREVIEW_WIDTH, PREVIEW_WIDTH * aspect];
+                            let id = imgui_wgpu.textures.insert(texture);
+                            texture_browser.register(&filename, id, info);
+                            let bytes = image.gpu_size();
+                            let memory =
+                                memory::track((), Category::Textures, Some(&filename), bytes);
+                            dropped_images.push((ImString::new(filename), id, size, memory));
+                        }
+                        Ok(Asset::Mesh(mesh)) => {
+                            // placed in front of the camera
+                            let position = camera.position + camera.forward() * 3.0;
+                            sources.add_mesh(MeshSource::Obj(PathBuf::from(&filename)), None);
+                            let name = Path::new(&filename)
+                                .file_stem()
+                                .map(|stem| stem.to_string_lossy().into_owned())
+                                .unwrap_or(filename);
+                            let mut object =
+                                Object::new(&name, meshes.len(), position, Vec3::unit_y(), 0.5);
+                            object.pulse = 0.0;
+                            gpu_meshes.push(Mesh::new(device, &name, &mesh));
+                            meshes.push(mesh);
+                            let layout = &bind_group_layout;
+                            bindings.push(device, layout, &name, undeformed(), None);
+                            viewport_bindings.push(device, layout, &name, undeformed(), None);
+                            scene.objects.push(object.clone());
+                            prev_scene.objects.push(object);
+                        }
+                        Ok(Asset::Scene(gltf)) => {
+                            let colors = material_layers.len();
+                            let data = linear_layers.len();
+                            let imported =
+                                gltf.import_materials(&mut material_layers, &mut linear_layers);
+                            if material_layers.len() != colors {
+                                materials = material_layers.build(device, queue, "Materials");
+                                materials_dirty = true;
+                            }
+                            if linear_layers.len() != data {
+                                linear_materials =
+                                    linear_layers.build(device, queue, "Linear materials");
+                                materials_dirty = true;
+                            }
+                            add_thumbnails(
+                                device,
+                                queue,
+                                &mut imgui_wgpu,
+                                &mut texture_browser,
+                                &material_layers,
+                                &mut color_thumbnails,
+                                "Materials",
+                            );
+                            add_thumbnails(
+                                device,
+                                queue,
+                                &mut imgui_wgpu,
+                                &mut texture_browser,
+                                &linear_layers,
+                                &mut linear_thumbnails,
+                                "Linear materials",
+                            );
+                            // the whole scene is placed in front of the camera
+                            let position = camera.position + camera.forward() * 3.0;
+                            let mut skinned = Vec::new();
+                            let mut weighted = Vec::new();
+                            for (i, primitive) in gltf.primitives.into_iter().enumerate() {
+                                let name = &primitive.name;
+                                let mut object =
+                                    Object::new(name, meshes.len(), position, Vec3::unit_y(), 0.0);
+                                object.pulse = 0.0;
+                                object.material = primitive
+                                    .material
+                                    .and_then(|material| imported.get(material))
+                                    .cloned()
+                                    .unwrap_or_else(|| gltf::Material::default().pbr);
+                                let source = MeshSource::Gltf {
+                                    path: PathBuf::from(&filename),
+                                    primitive: i,
+                                };
+                                sources.add_mesh(source, Some(object.material.clone()));
+                                let mesh = primitive.mesh;
+                                gpu_meshes.push(Mesh::new(device, name, &mesh));
+                                let vertex_count = mesh.vertices.len();
+                                meshes.push(mesh);
+                                let skin = primitive.skin.zip(gltf.skeleton.as_ref());
+                                let skin_vertices = &primitive.skin_vertices;
+                                let skin_buffers = skin.map(|(skin, skeleton)| {
+                                    let joints = memory::create_buffer(
+                                        device,
+                                        Category::Storage,
+                                        &BufferDescriptor {
+                                            label: Some(&format!("{} joints", name)),
+                                            size: (skeleton.skins[skin].joints.len().max(1)
+                                                * std::mem::size_of::<Mat4>())
+                                                as _,
+                                            usage: BufferUsage::STORAGE | BufferUsage::COPY_DST,
+                                            mapped_at_creation: false,
+                                        },
+                                    );
+                                    let vertices = memory::create_buffer_init(
+                                        device,
+                                        Category::Storage,
+                                        &BufferInitDescriptor {
+                                            label: Some(&format!("{} skin", name)),
+                                            contents: bytemuck::cast_slice(skin_vertices),
+                                            usage: BufferUsage::STORAGE,
+                                        },
+                                    );
+                                    (skin, joints, vertices)
+                                });
+                                let morph_buffers = if primitive.morph_names.is_empty() {
+                                    None
+                                } else {
+                                    let deltas = memory::create_buffer_init(
+                                        device,
+                                        Category::Storage,
+                                        &BufferInitDescriptor {
+                                            label: Some(&format!("{} morph targets", name)),
+                                            contents: bytemuck::cast_slice(&primitive.morph_deltas),
+                                            usage: BufferUsage::STORAGE,
+                                        },
+                                    );
+                                    let weights = memory::create_buffer_init(
+                                        device,
+                                        Category::Storage,
+                                        &BufferInitDescriptor {
+                                            label: Some(&format!("{} morph weights", name)),
+                                            contents: bytemuck::cast_slice(
+                                                &primitive.morph_weights,
+                                            ),
+                                            usage: BufferUsage::STORAGE | BufferUsage::COPY_DST,
+                                        },
+                                    );
+                                    Some((deltas, weights))
+                                };
+                                let skinning = skin_buffers.as_ref();
+                                let morphing = morph_buffers.as_ref();
+                                let deformation = Deformation {
+                                    joints: skinning.map_or(&no_joints, |(_, j, _)| j),
+                                    skin: skinning.map_or(&no_skin, |(_, _, v)| v),
+                                    deltas: morphing.map_or(&no_deltas, |(d, _)| d),
+                                    weights: morphing.map_or(&no_weights, |(_, w)| w),
+                                };
+                                let deformation = Some(deformation)
+                                    .filter(|_| skinning.is_some() || morphing.is_some());
+                                let layout = &bind_group_layout;
+                                bindings.push(device, layout, name, undeformed(), deformation);
+                                viewport_bindings.push(
+                                    device,
+                                    layout,
+                                    name,
+                                    undeformed(),
+                                    deformation,
+                                );
+                                let index = scene.objects.len();
+                                if let Some((skin, joints, vertices)) = skin_buffers {
+                                    skinned.push(SkinnedObject {
+                                        object: index,
+                                        skin,
+                                        joints,
+                                        _vertices: vertices,
+                                    });
+                                }
+                                if let Some((deltas, weights)) = morph_buffers {
+                                    weighted.push((index, primitive.node));
+                                    morphed.push(MorphObject {
+                                        object: index,
+                                        vertex_count,
+                                        weights,
+                                        _deltas: deltas,
+                                    });
+                                    object.morph_targets = primitive.morph_names;
+                                    object.morph_weights = primitive.morph_weights;
+                                }
+                                scene.objects.push(object.clone());
+                                prev_scene.objects.push(object);
+                            }
+                            if let Some(skeleton) = gltf.skeleton {
+                                animated.push(Animated {
+                                    name: Path::new(&filename)
+                                        .file_name()
+                                        .map_or(filename.clone(), |name| {
+                                            name.to_string_lossy().into_owned()
+                                        }),
+                                    player: Player::new(&skeleton),
+                                    skeleton,
+                                    skinned,
+                                    morphed: weighted,
+                                });
+                            }
+                        }
+                        Ok(Asset::Lut(lut)) => {
+                            let name = lut::name(Path::new(&filename));
+                            luts.push((ImString::new(name), Some(lut)));
+                            selected_lut = luts.len() - 1;
+                            post.set_lut(device, queue, luts[selected_lut].1.as_ref());
+                        }
+                        Ok(Asset::SceneFile(file)) => opened_scene = Some(file),
+                        Err(err) => warn!("Error loading {}: {}", filename, err),
+                    }
+                }
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } if demo == Demo::Scene && !mouse_look => {
+                    // pick against what was displayed on the last frame. mouse
+                    // events are in window coordinates, not drawable pixels.
+                    let (x, y) = (x as _, y as _);
+                    let view_projection = projection * frustum.view;
+                    let (width, height) = window_size;
+                    // handles are in front of the objects
+                    let selected = scene.objects.iter().position(|o| o.selected);
+                    let grabbed = selected.is_some_and(|i| {
+                        transform_gizmo.grab(
+                            x,
+                            y,
+                            width,
+                            height,
+                            view_projection,
+                            &interpolated.objects[i],
+                        )
+                    }) || vertex_editor.grab(
+                        x,
+                        y,
+                        width,
+                        height,
+                        view_projection,
+                        &interpolated,
+                        &meshes,
+                    ) || reflection_probe.grab(x, y, width, height, view_projection);
+                    if !grabbed {
+                        let ray = Ray::from_screen(x, y, width, height, view_projection);
+                        scene.select(picking::pick(&ray, &interpolated, &meshes));
+                    }
+                }
+                Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Left,
+                    ..
+                } => {
+                    vertex_editor.release();
+                    transform_gizmo.release();
+                    reflection_probe.release();
+                }
+                Event::MouseMotion { x, y, .. } if transform_gizmo.is_dragging() => {
+                    let view_projection = projection * frustum.view;
+                    let (width, height) = window_size;
+                    if let Some(object) = scene.objects.iter_mut().find(|o| o.selected) {
+                        transform_gizmo.drag(
+                            x as _,
+                            y as _,
+                            width,
+                            height,
+                            view_projection,
+                            object,
+                        );
+                    }
+                }
+                Event::MouseMotion { x, y, .. } if vertex_editor.is_dragging() => {
+                    let view_projection = projection * frustum.view;
+                    vertex_editor.drag(
+                        x as _,
+                        y as _,
+                        window_size.0,
+                        window_size.1,
+                        view_projection,
+                        &interpolated,
+                        &mut meshes,
+                    );
+                }
+                Event::MouseMotion { x, y, .. } if reflection_probe.is_dragging() => {
+                    let view_projection = projection * frustum.view;
+                    let (width, height) = window_size;
+                    reflection_probe.drag(x as _, y as _, width, height, view_projection);
                 }
                 _ => {}
             }
         }
 
-        let frame = swap_chain
-            .get_current_frame()
-            .expect("Error getting current frame");
+        if let Some(file) = opened_scene.take() {
+            let missing = file.missing_files();
+            for file in &missing {
+                error!("{}", file);
+            }
+            let exists = |path: &PathBuf| missing.iter().all(|file| &file.path != path);
+            let colors = material_layers.len();
+            let data = linear_layers.len();
+            // meshes and images that aren't loaded yet
+            for object in &file.objects {
+                if sources.mesh(&object.mesh).is_none() {
+                    match &object.mesh {
+                        MeshSource::Obj(path) | MeshSource::Gltf { path, .. } if !exists(path) => {}
+                        MeshSource::Builtin(_) => {}
+                        MeshSource::Obj(path) => match MeshData::load_obj(path) {
+                            Ok(mesh) => {
+                                let name = path.to_string_lossy();
+                                gpu_meshes.push(Mesh::new(device, &name, &mesh));
+                                meshes.push(mesh);
+                                sources.add_mesh(object.mesh.clone(), None);
+                            }
+                            Err(err) => warn!("Error loading {}: {}", path.display(), err),
+                        },
+                        MeshSource::Gltf { path, .. } => match gltf::Gltf::load(path) {
+                            Ok(gltf) => {
+                                let imported =
+                                    gltf.import_materials(&mut material_layers, &mut linear_layers);
+                                for (i, primitive) in gltf.primitives.into_iter().enumerate() {
+                                    let material = primitive
+                                        .material
+                                        .and_then(|material| imported.get(material))
+                                        .cloned()
+                                        .unwrap_or_else(|| gltf::Material::default().pbr);
+                                    let source = MeshSource::Gltf {
+                                        path: path.clone(),
+                                        primitive: i,
+                                    };
+                                    sources.add_mesh(source, Some(material));
+                                    gpu_meshes.push(Mesh::new(
+                                        device,
+                                        &primitive.name,
+                                        &primitive.mesh,
+                                    ));
+                                    meshes.push(primitive.mesh);
+                                }
+                            }
+                            Err(err) => warn!("Error loading {}: {}", path.display(), err),
+                        },
+                    }
+                }
+                for (slot, texture) in &object.textures {
+                    let path = match texture {
+                        TextureSource::Image(path) => path,
+                        _ => continue,
+                    };
+                    if !exists(path) || sources.image(slot.is_color(), path).is_some() {
+                        continue;
+                    }
+                    match assets::load(path, &Capabilities::default()) {
+                        Ok(Asset::Image {
+                            width,
+                            height,
+                            pixels,
+                        }) => {
+                            let layers = if slot.is_color() {
+                                &mut material_layers
+                            } else {
+                                &mut linear_layers
+                            };
+                            let layer = layers.add(width, height, pixels);
+                            sources
+                                .images
+                                .insert((slot.is_color(), layer), path.clone());
+                        }
+                        Ok(_) => warn!("{} isn't an image", path.display()),
+                        Err(err) => warn!("Error loading {}: {}", path.display(), err),
+                    }
+                }
+            }
+            if material_layers.len() != colors {
+                materials = material_layers.build(device, queue, "Materials");
+                add_thumbnails(
+                    device,
+                    queue,
+                    &mut imgui_wgpu,
+                    &mut texture_browser,
+                    &material_layers,
+                    &mut color_thumbnails,
+                    "Materials",
+                );
+                materials_dirty = true;
+            }
+            if linear_layers.len() != data {
+                linear_materials = linear_layers.build(device, queue, "Linear materials");
+                add_thumbnails(
+                    device,
+                    queue,
+                    &mut imgui_wgpu,
+                    &mut texture_browser,
+                    &linear_layers,
+                    &mut linear_thumbnails,
+                    "Linear materials",
+                );
+                materials_dirty = true;
+            }
 
-        // draw wgpu
+            // objects whose mesh didn't load are left out
+            scene.objects = file
+                .objects
+                .into_iter()
+                .filter_map(|saved| {
+                    let mesh = match sources.mesh(&saved.mesh) {
+                        Some(mesh) => mesh,
+                        None => {
+                            error!("Leaving out {}, its mesh didn't load", saved.object.name);
+                            return None;
+                        }
+                    };
+                    let mut object = saved.object;
+                    object.mesh = mesh;
+                    for (slot, texture) in &saved.textures {
+                        let layer = sources.layer(mesh, *slot, texture);
+                        if layer.is_none() {
+                            let slot = slot.name().to_lowercase();
+                            error!(
+                                "Leaving out the {} texture of {}, it didn't load",
+                                slot, object.name
+                            );
+                        }
+                        *object.material.texture_mut(*slot) = layer;
+                    }
+                    Some(object)
+                })
+                .collect();
+            prev_scene = scene.clone();
+            interpolated = scene.clone();
+            scene_bundles.invalidate();
+            bindings = ObjectBindings::new("Objects");
+            viewport_bindings = ObjectBindings::new("Objects (second viewport)");
+            for object in &scene.objects {
+                let layout = &bind_group_layout;
+                bindings.push(device, layout, &object.name, undeformed(), None);
+                viewport_bindings.push(device, layout, &object.name, undeformed(), None);
+            }
+            // skins and morph targets aren't saved
+            animated.clear();
+            morphed.clear();
+            physics = Physics::new();
+            transform_gizmo.release();
+            script_objects.clear();
+            lights = file.lights;
+            camera.set_pose(&file.camera);
+            frustum.fov_y = file.camera.fov_y;
+            bookmarks.stop();
+        }
+        drop(events_scope);
+        if resized {
+            window_size = window_size_f32(&window);
+            let (drawable_width, drawable_height) = window.drawable_size();
+            context.resize(drawable_width, drawable_height);
+            if context.size() != (width, height) {
+                let (new_width, new_height) = context.size();
+                width = new_width;
+                height = new_height;
+                let (new_depth, new_depth_view) = create_depth(device, width, height);
+                depth = new_depth;
+                depth_view = new_depth_view;
+                decal_depth = decal_renderer.depth_bind_group(device, &depth_view);
+                viewport_depth = create_viewport_depth(device, width, height);
+                post_targets = post.resize(device, post_targets, width, height, &depth);
+                oit_targets = oit.targets(device, width, height);
+                msaa_targets = MsaaTargets::new(device, width, height, HDR_FORMAT, DEPTH_FORMAT);
+                capture = Capture::new(device, width, height, context::FORMAT);
+                // its frames are all the same size
+                if gif_recorder.take().is_some() {
+                    warn!("Recording cancelled, the window was resized");
+                }
+            }
+        }
+        // golden frames are rendered offscreen, they don't need a visible window
+        if golden.is_none() {
+            if minimized {
+                // nothing to present to, the surface might not even have a size
+                std::thread::sleep(BACKGROUND_FRAME_TIME);
+                continue;
+            }
+            if !focused && throttle_unfocused {
+                std::thread::sleep(BACKGROUND_FRAME_TIME.saturating_sub(time.since_tick()));
+            }
+        }
+        window.set_relative_mouse_mode(mouse_look);
+        let update_scope = profiler::scope("Update");
 
-        let mut cmd = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        // fixed timestep simulation
+        time.tick();
+        // golden frames must not depend on how long they take to render
+        let delta = if fixed_delta {
+            Duration::from_secs(1) / UPDATE_RATE
+        } else {
+            time.delta()
+        };
+        title_status.update(&window, time.delta(), settings.title_status);
+
+        if mouse_look {
+            let keys = window.keyboard_state();
+            let pressed = |action| match &replay {
+                Some(replay) => replay.is_pressed(settings.bindings.key(action)),
+                None => settings.bindings.is_pressed(&keys, action),
+            };
+            let axis = |positive, negative| pressed(positive) as i32 - pressed(negative) as i32;
+            let direction = Vec3::new(
+                axis(Action::MoveRight, Action::MoveLeft) as _,
+                axis(Action::MoveUp, Action::MoveDown) as _,
+                axis(Action::MoveForward, Action::MoveBack) as _,
+            );
+            camera.fly(direction, CAMERA_SPEED * delta.as_secs_f32());
+            // taking control of the camera stops any transition
+            bookmarks.stop();
+        }
+        if let Some(pose) = bookmarks.update(delta.as_secs_f32()) {
+            camera.set_pose(&pose);
+            frustum.fov_y = pose.fov_y;
+        }
+        if let Some(bench) = &bench {
+            camera = bench.camera();
+        }
+        frustum.view = camera.view();
+        let main_rect = viewport_params.layout.rects(width, height).0;
+        frustum.aspect = main_rect.aspect();
+        projection = main_rect.fit(
+            Mat4::perspective_rh(frustum.fov_y, frustum.aspect, frustum.near, FAR_PLANE),
+            width,
+            height,
+        );
+        let view = frustum.view;
+        // only the scene is jittered, what's drawn over it after post-processing isn't
+        let jitter = if post_params.antialiasing == Antialiasing::Taa
+            && debug_view == DebugView::Final
+            && demo == Demo::Scene
         {
-            let mut pass = cmd.begin_render_pass(&RenderPassDescriptor {
-                color_attachments: &[RenderPassColorAttachmentDescriptor {
-                    attachment: &frame.output.view,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Clear(Color {
-                            r: 0.5,
-                            g: 0.5,
-                            b: 0.5,
-                            a: 1.0,
+            taa::jitter(frame_index)
+        } else {
+            Vec2::zero()
+        };
+        let scene_projection = taa::jittered(projection, jitter, width, height);
+        let eye = camera.position;
+        let target = eye + camera.forward();
+        let modified = || {
+            std::fs::metadata(script_path.to_str())
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        };
+        if std::mem::take(&mut save_script) {
+            match std::fs::write(script_path.to_str(), script_source.to_str()) {
+                Ok(()) => script_modified = modified(),
+                Err(err) => warn!("Error saving {}: {}", script_path, err),
+            }
+        }
+        let reload = watch_script && modified().is_some_and(|time| Some(time) != script_modified);
+        if std::mem::take(&mut load_script) || reload {
+            match std::fs::read_to_string(script_path.to_str()) {
+                Ok(source) => {
+                    script_source = ImString::new(source);
+                    run_script = true;
+                }
+                Err(err) => warn!("Error loading {}: {}", script_path, err),
+            }
+            script_modified = modified();
+        }
+        let mut commands = Vec::new();
+        // errors are logged once
+        let mut failed = script_error.is_some();
+        if std::mem::take(&mut run_script) {
+            failed = false;
+            // the objects of the last run are spawned again
+            let removed = !script_objects.is_empty();
+            for name in script_objects.drain(..) {
+                let i = match scene.objects.iter().position(|object| object.name == name) {
+                    Some(i) => i,
+                    None => continue,
+                };
+                scene.objects.remove(i);
+                prev_scene.objects.remove(i);
+                bindings.remove(i);
+                viewport_bindings.remove(i);
+                scene_bundles.invalidate();
+                let shift = |object: &mut usize| {
+                    if *object > i {
+                        *object -= 1;
+                    }
+                };
+                morphed.iter_mut().for_each(|m| shift(&mut m.object));
+                for animated in &mut animated {
+                    animated
+                        .skinned
+                        .iter_mut()
+                        .for_each(|s| shift(&mut s.object));
+                    animated.morphed.iter_mut().for_each(|(o, _)| shift(o));
+                }
+            }
+            if removed {
+                // bodies are by index
+                physics = Physics::new();
+                transform_gizmo.release();
+            }
+            script = None;
+            script_error = None;
+            match Script::parse(script_source.to_str()) {
+                Ok(mut parsed) => match parsed.setup() {
+                    Ok(setup) => {
+                        commands = setup;
+                        script = Some(parsed);
+                        script_time = 0.0;
+                    }
+                    Err(err) => script_error = Some(err),
+                },
+                Err(err) => script_error = Some(err),
+            }
+        } else if let Some(running) = script.as_mut().filter(|script| script.animates()) {
+            script_time += delta.as_secs_f32();
+            match running.frame(script_time, delta.as_secs_f32()) {
+                Ok(frame) => commands = frame,
+                Err(err) => script_error = Some(err),
+            }
+        }
+        for command in commands {
+            let result = match &command.kind {
+                _ if script_error.is_some() => break,
+                CommandKind::Spawn { mesh } => {
+                    let name = &command.object;
+                    let index = BUILTIN_MESHES.iter().position(|builtin| builtin == mesh);
+                    match index {
+                        _ if scene.objects.iter().any(|object| &object.name == name) => {
+                            Err(ScriptError {
+                                line: command.line,
+                                message: format!("there's already an object {:?}", name),
+                            })
+                        }
+                        Some(mesh) => {
+                            let mut object =
+                                Object::new(name, mesh, Vec3::zero(), Vec3::unit_y(), 0.0);
+                            object.pulse = 0.0;
+                            let layout = &bind_group_layout;
+                            bindings.push(device, layout, name, undeformed(), None);
+                            viewport_bindings.push(device, layout, name, undeformed(), None);
+                            scene.objects.push(object.clone());
+                            prev_scene.objects.push(object);
+                            script_objects.push(name.clone());
+                            Ok(())
+                        }
+                        None => Err(ScriptError {
+                            line: command.line,
+                            message: format!(
+                                "there's no mesh {:?}, only {}",
+                                mesh,
+                                BUILTIN_MESHES.join(", ")
+                            ),
                         }),
-                        store: true,
-                    },
-                }],
-                depth_stencil_attachment: None,
-            });
-            pass.set_pipeline(&render_pipeline);
-            pass.set_vertex_buffer(0, vertex.slice(..));
-            pass.set_index_buffer(index.slice(..));
-            pass.draw(0..3, 0..1);
+                    }
+                }
+                // set on both so they aren't interpolated from where they were
+                _ => command
+                    .apply(&mut scene.objects)
+                    .and_then(|()| command.apply(&mut prev_scene.objects)),
+            };
+            if let Err(err) = result {
+                script_error = Some(err);
+            }
+        }
+        if let Some(err) = script_error.as_ref().filter(|_| !failed) {
+            error!("Script stopped at {}", err);
+            script = None;
+        }
+        for _ in 0..timestep.advance(delta) {
+            prev_scene = scene.clone();
+            scene.update(timestep.step());
+            physics.step(&mut scene, &meshes, timestep.step(), gravity);
         }
 
+        interpolated = prev_scene.lerp(&scene, timestep.alpha());
+        vertex_editor.upload(queue, &mut meshes, &gpu_meshes);
+        if post_params.dof.autofocus && demo == Demo::Scene {
+            // focus on what is under the cursor, or ahead when looking around
+            let mouse = window.mouse_state();
+            let (x, y) = if mouse_look {
+                (window_size.0 as i32 / 2, window_size.1 as i32 / 2)
+            } else {
+                replay
+                    .as_ref()
+                    .map_or((mouse.x(), mouse.y()), Replay::mouse)
+            };
+            let (width, height) = window_size;
+            let ray = Ray::from_screen(x as _, y as _, width, height, projection * view);
+            if let Some((_, distance)) = picking::hit(&ray, &interpolated, &meshes) {
+                let point = ray.origin + ray.direction * distance;
+                post_params.dof.focus_distance = -view.transform_point3(point).z;
+            }
+        }
+        let lods = lod_selector.select(&lod_params, &interpolated, &gpu_meshes, eye, frustum.fov_y);
+        // kept for the second viewport, which only changes the mvp
+        let mut object_uniforms = Vec::with_capacity(bindings.len());
+        for (i, object) in interpolated.objects.iter().enumerate() {
+            let mut uniforms = ObjectUniforms::new(
+                scene_projection * view * object.model(),
+                object.model(),
+                LAYER_SPACING,
+                OUTLINE_SCALE,
+                &object.material,
+            );
+            let mut skinned = animated.iter().flat_map(|a| &a.skinned);
+            uniforms.skinned = skinned.any(|s| s.object == i) as u32;
+            if let Some(morph) = morphed.iter().find(|m| m.object == i) {
+                uniforms.morph_targets = object.morph_weights.len() as u32;
+                uniforms.vertex_count = morph.vertex_count as u32;
+            }
+            uniforms.lod = lods[i] as u32;
+            object_uniforms.push(uniforms);
+        }
+        bindings.write(queue, &object_uniforms);
+        timeline.advance(delta.as_secs_f32(), timeline_length(&animated));
+        for animated in &mut animated {
+            let time = animated.player.time(&animated.skeleton, &timeline);
+            let pose = animated.skeleton.pose(animated.player.clip, time);
+            for skinned in &animated.skinned {
+                let joints = animated.skeleton.joint_matrices(skinned.skin, &pose.worlds);
+                let joints: Vec<_> = joints.iter().map(Mat4::to_cols_array).collect();
+                draw_stats::write_buffer(queue, &skinned.joints, 0, bytemuck::cast_slice(&joints));
+            }
+            // animated weights replace the ones set in the inspector
+            for &(object, node) in &animated.morphed {
+                if let Some(weights) = &pose.weights[node] {
+                    let object = &mut scene.objects[object];
+                    for (weight, value) in object.morph_weights.iter_mut().zip(weights) {
+                        *weight = *value;
+                    }
+                }
+            }
+        }
+        for morph in &morphed {
+            let weights = &scene.objects[morph.object].morph_weights;
+            draw_stats::write_buffer(queue, &morph.weights, 0, bytemuck::cast_slice(weights));
+        }
+
+        let (light_direction, light_color) = if sky_params.procedural && sky_params.drive_light {
+            let sunlight = sky_params.sunlight() * light_intensity;
+            (-sky_params.sun_direction(), sunlight)
+        } else {
+            (fixed_light_direction, Vec3::splat(light_intensity))
+        };
+        let cascades_fit = Cascades::fit(&frustum, light_direction);
+        let cascade_offsets: Vec<_> = cascades_fit
+            .view_projections
+            .iter()
+            .map(|view_projection| {
+                frame_ring.upload(bytemuck::bytes_of(&view_projection.to_cols_array()))
+            })
+            .collect();
+
+        // the second viewport isn't jittered, it doesn't go through TAA
+        let second_camera = viewport_params.layout.rects(width, height).1.map(|rect| {
+            let params = &viewport_params;
+            let aspect = rect.aspect();
+            let (_, view, projection) =
+                params
+                    .camera
+                    .matrices(eye, light_direction, params.extent, aspect, FAR_PLANE);
+            let objects = interpolated.objects.iter().zip(&object_uniforms);
+            let uniforms: Vec<_> = objects
+                .map(|(object, uniforms)| ObjectUniforms {
+                    mvp: (projection * view * object.model()).to_cols_array_2d(),
+                    ..*uniforms
+                })
+                .collect();
+            viewport_bindings.write(queue, &uniforms);
+            if sky_params.procedural {
+                viewport_sky.update(queue, projection, view, &sky_params);
+            } else {
+                viewport_skybox.update(queue, projection, view);
+            }
+            view
+        });
+
+        for (view_projection, (_, uniform, _)) in
+            point_light.face_view_projections().iter().zip(&point_faces)
         {
-            let mut pass = cmd.begin_render_pass(&RenderPassDescriptor {
-                color_attachments: &[RenderPassColorAttachmentDescriptor {
-                    attachment: &frame.output.view,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Load,
-                        store: true,
-                    },
-                }],
-                depth_stencil_attachment: None,
-            });
+            let face = PointShadowFace {
+                view_projection: view_projection.to_cols_array_2d(),
+                light_position: point_light.position.extend(point_light.range).into(),
+            };
+            draw_stats::write_buffer(queue, uniform, 0, bytemuck::bytes_of(&face));
+        }
+
+        if show_field {
+            let material = PbrMaterial {
+                roughness: 0.6,
+                ..Default::default()
+            };
+            let uniforms = ObjectUniforms::new(
+                scene_projection * view,
+                Mat4::identity(),
+                0.0,
+                1.0,
+                &material,
+            );
+            draw_stats::write_buffer(queue, &field_uniform, 0, bytemuck::bytes_of(&uniforms));
+            field.update(queue, scene_projection * view);
+        }
+        if show_grid {
+            grid.update(queue, scene_projection * view, eye, &grid_params);
+        }
+        if show_decals {
+            decal_renderer.update(queue, scene_projection * view, width, height, &decals);
+        }
+
+        // labels face the camera
+        if show_labels {
+            let inverse_view = view.inverse();
+            let right = inverse_view.x_axis.truncate();
+            let up = inverse_view.y_axis.truncate();
+            for object in &interpolated.objects {
+                let anchor = object.position + Vec3::unit_y() * 0.8;
+                text.label(&object.name, anchor, right, up, label_size);
+            }
+        }
+        text.prepare(queue, projection * view, &text_style);
+        if show_gizmos {
+            for light in &lights {
+                light.gizmo(&mut gizmos);
+            }
+        }
+        if show_colliders {
+            physics.draw_colliders(&mut gizmos);
+        }
+        if let Some(decal) = selected_decal.and_then(|i| decals.get(i)) {
+            let decal: &Decal = decal;
+            gizmos.cuboid(decal.transform(), Vec3::splat(0.5), [1.0, 1.0, 0.0, 1.0]);
+        }
+        vertex_editor.draw_handles(&mut gizmos, &interpolated, &meshes, eye);
+        if let Some(object) = interpolated.objects.iter().find(|o| o.selected) {
+            transform_gizmo.gizmo(&mut gizmos, projection * view, object);
+        }
+        reflection_probe.gizmo(&mut gizmos, eye);
+        gizmos.prepare(queue, projection * view);
+
+        if demo == Demo::Scene && sky_params.procedural {
+            sky.update(queue, scene_projection, view, &sky_params);
+        } else if demo == Demo::Scene {
+            skybox.update(queue, scene_projection, view);
+        }
+        if demo == Demo::NBody {
+            nbody.update(queue, &nbody_params, projection * view, view);
+        }
+        if demo == Demo::Pulling {
+            vertex_pulling.update(
+                device,
+                queue,
+                &pulling_params,
+                projection * view,
+                time.elapsed().as_secs_f32(),
+            );
+        }
+        if demo == Demo::Raymarch {
+            raymarch.update(
+                queue,
+                projection * view,
+                eye,
+                light_direction,
+                time.elapsed().as_secs_f32(),
+            );
+        }
+
+        let lighting = Lighting {
+            light_direction,
+            light_color,
+            camera_position: eye,
+            camera_forward: (target - eye).normalize(),
+            cascade_view_projections: cascades_fit.view_projections,
+            cascade_splits: cascades_fit.splits.into(),
+            point_light_position: point_light.position,
+            point_light_range: point_light.range,
+            point_light_color: point_light.color * point_light.intensity,
+            normal_mapping,
+            ibl: image_based_lighting,
+            prefiltered_mips: ibl::PREFILTERED_MIPS as f32,
+            shadows,
+            cascade_debug,
+            point_shadows,
+            debug_view: debug_view as u32,
+            debug_depth_range,
+            shadow_bias: shadow_params.bias,
+            shadow_normal_offset: shadow_params.normal_offset,
+            shadow_pcf_radius: shadow_params.pcf_radius,
+            light_count: lights.len() as u32,
+            probe: reflection_probe.position.extend(reflection_probe.radius),
+            reflection_probe: reflection_probe.enabled,
+        };
+        let probe_faces = reflection_probe.prepare(queue);
+        if !probe_faces.is_empty() {
+            let lighting = Lighting {
+                reflection_probe: false,
+                ..lighting
+            };
+            let bytes = lighting.std140_bytes();
+            draw_stats::write_buffer(queue, &probe_lighting_uniform, 0, &bytes);
+        }
+        draw_stats::write_buffer(queue, &lighting_uniform, 0, &lighting.std140_bytes());
+        if !lights.is_empty() {
+            let gpu_lights: Vec<_> = lights.iter().map(Light::to_gpu).collect();
+            draw_stats::write_buffer(queue, &lights_buffer, 0, &layout::std430_array(&gpu_lights));
+        }
+        // culled for the main camera, as jittered as the scene
+        clusters.update(
+            queue,
+            view,
+            scene_projection,
+            frustum.near,
+            FAR_PLANE,
+            lights.len() as u32,
+        );
+
+        let queues = DrawQueues::new(&scene, &meshes, view, true);
+
+        // offscreen frames aren't presented, the window keeps the last one
+        if present_mode_dirty {
+            context.set_present_mode(present_mode);
+            present_mode_dirty = false;
+        }
+        drop(update_scope);
+        let supersampled = if supersampled_screenshot {
+            Some(Supersample::new(
+                device,
+                width,
+                height,
+                supersample::FACTORS[supersample_factor],
+                context::FORMAT,
+                DEPTH_FORMAT,
+            ))
+        } else {
+            None
+        };
+        // supersampled screenshots get effects at their own size
+        let mut supersampled_post = supersampled.as_ref().map(|supersampled| {
+            let (width, height) = supersampled.size();
+            post.targets(device, width, height, supersampled.depth())
+        });
+        let hdr_targets = supersampled_post.as_mut().unwrap_or(&mut post_targets);
+        let supersampled_oit = supersampled.as_ref().map(|supersampled| {
+            let (width, height) = supersampled.size();
+            oit.targets(device, width, height)
+        });
+        let oit_targets = supersampled_oit.as_ref().unwrap_or(&oit_targets);
+        // the viewports of the frame drawn, maybe supersampled
+        let (draw_width, draw_height) = supersampled.as_ref().map_or((width, height), |s| s.size());
+        let (main_rect, second_rect) = viewport_params.layout.rects(draw_width, draw_height);
+        let supersampled_viewport_depth = supersampled
+            .as_ref()
+            .filter(|_| second_rect.is_some())
+            .map(|_| create_viewport_depth(device, draw_width, draw_height));
+        let second_depth_view = match &supersampled_viewport_depth {
+            Some((_, view)) => view,
+            None => &viewport_depth.1,
+        };
+        let capture_gif = gif_recorder
+            .as_ref()
+            .is_some_and(|recorder| recorder.wants_frame(Instant::now()));
+        let frame = if golden.is_some() || screenshot || supersampled.is_some() || capture_gif {
+            None
+        } else {
+            let _scope = profiler::scope("Acquire frame");
+            let acquire_start = Instant::now();
+            let frame = match context.begin_frame() {
+                Ok(frame) => frame,
+                // recreated by the context, the next frame gets a new one
+                Err(
+                    err @ (SwapChainError::Timeout
+                    | SwapChainError::Outdated
+                    | SwapChainError::Lost),
+                ) => {
+                    warn!("Skipping frame ({})", err);
+                    continue 'main;
+                }
+                Err(SwapChainError::OutOfMemory) => panic!("Out of memory acquiring a frame"),
+            };
+            frame_stats.acquired(acquire_start.elapsed());
+            Some(frame)
+        };
+        let output_view = match &frame {
+            Some(frame) => &frame.output.view,
+            None => capture.view(),
+        };
+        // supersampled screenshots only contain the scene
+        let (scene_view, scene_depth_view) = match &supersampled {
+            Some(supersampled) => (supersampled.view(), supersampled.depth_view()),
+            None => (output_view, &depth_view),
+        };
+
+        // draw wgpu
+
+        let encode_scope = profiler::scope("Encode");
+        let mut cmd = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Frame"),
+        });
+        frame_ring.flush(&mut cmd);
+        if sampler_dirty || materials_dirty {
+            lighting_bind_group = create_lighting_bind_group(
+                &lighting_uniform,
+                &reflection_probe.view,
+                samplers.get(device, &sampler_settings),
+                &materials,
+                &linear_materials,
+            );
+            probe_lighting_bind_group = create_lighting_bind_group(
+                &probe_lighting_uniform,
+                &ibl.prefiltered_view,
+                samplers.get(device, &sampler_settings),
+                &materials,
+                &linear_materials,
+            );
+            sampler_dirty = false;
+            materials_dirty = false;
+            scene_bundles.invalidate();
+        }
+        if compile_shader {
+            match shader_compiler::compile("raymarch.frag", shader_source.to_str()) {
+                Ok(spirv) => {
+                    raymarch.set_fragment_shader(device, &spirv);
+                    shader_error = None;
+                }
+                Err(CompileError::Source(diagnostics)) => {
+                    for diagnostic in &diagnostics {
+                        match diagnostic.severity {
+                            Severity::Error => error!("{}", diagnostic),
+                            Severity::Warning => warn!("{}", diagnostic),
+                        }
+                    }
+                    shader_error = Some(CompileError::Source(diagnostics));
+                }
+                Err(err) => {
+                    error!("{}", err);
+                    shader_error = Some(err);
+                }
+            }
+            compile_shader = false;
+        }
+        if filter_dirty {
+            let output = imgui_wgpu.textures.get(filter_output_id).unwrap().texture();
+            image_filter.run(&mut cmd, queue, &filter_params, output);
+            filter_dirty = false;
+        }
+        if inspector_dirty {
+            let target = imgui_wgpu.textures.get(inspector_id).unwrap().view();
+            inspector.run(&mut cmd, queue, target, &inspector_params);
+            inspector_dirty = false;
+        }
+        texture_browser.run(device, &mut cmd, queue, &imgui_wgpu);
+        if demo == Demo::NBody && !nbody_params.paused {
+            nbody.step(&mut cmd, nbody_params.count);
+        }
+        let passes_scope = profiler::scope("Render passes");
+        if demo == Demo::Scene {
+            if show_field && gpu_culling {
+                field.cull(&mut cmd);
+                // compared once the counts are read back, frames later
+                if verify_culling {
+                    frame_ring.read_back(&mut cmd, &field.indirect, 0, INDIRECT_SIZE);
+                    let expected =
+                        field.cpu_visible_count(&field_instances, scene_projection * view);
+                    culling_expected = Some(expected);
+                    verify_culling = false;
+                }
+            }
+            clusters.cull(&mut cmd);
+            for (i, view) in cascades.iter().enumerate() {
+                let mut pass = PassBuilder::new()
+                    .depth(view, SHADOW_FORMAT, LoadOp::Clear(1.0), true)
+                    .begin(&mut cmd);
+                if !shadows {
+                    continue;
+                }
+                pass.push_debug_group(&format!("Shadow cascade {}", i));
+                pass.set_pipeline(&shadow_pipeline);
+                pass.set_bind_group(1, frame_ring.bind_group(), &[cascade_offsets[i]]);
+                // shadows are cast by the full meshes, whatever level is drawn
+                for (i, object) in scene.objects.iter().enumerate() {
+                    bindings.bind(&mut pass, i);
+                    gpu_meshes[object.mesh].draw(&mut pass, 0..1);
+                }
+                pass.pop_debug_group();
+            }
+            for (face, (view, _, face_bind_group)) in point_faces.iter().enumerate() {
+                let mut pass = PassBuilder::new()
+                    .color(view, POINT_SHADOW_FORMAT, LoadOp::Clear(Color::WHITE), true)
+                    .depth(
+                        &point_shadow_depth_view,
+                        SHADOW_FORMAT,
+                        LoadOp::Clear(1.0),
+                        false,
+                    )
+                    .begin(&mut cmd);
+                if !point_shadows {
+                    continue;
+                }
+                pass.push_debug_group(&format!("Point shadow face {}", face));
+                pass.set_pipeline(&point_shadow_pipeline);
+                pass.set_bind_group(1, face_bind_group, &[]);
+                for (i, object) in scene.objects.iter().enumerate() {
+                    bindings.bind(&mut pass, i);
+                    gpu_meshes[object.mesh].draw(&mut pass, 0..1);
+                }
+                pass.pop_debug_group();
+            }
+            for face in probe_faces.clone() {
+                let (color, depth) = reflection_probe.face_targets(face);
+                let mut pass = PassBuilder::new()
+                    .color(color, ibl::CUBE_FORMAT, LoadOp::Clear(Color::BLACK), true)
+                    .depth(depth, SHADOW_FORMAT, LoadOp::Clear(1.0), false)
+                    .begin(&mut cmd);
+                pass.push_debug_group(&format!("Reflection probe face {}", face));
+                reflection_probe.draw_background(&mut pass, face);
+                pass.set_pipeline(&probe_pipeline);
+                pass.set_bind_group(1, &probe_lighting_bind_group, &[]);
+                pass.set_bind_group(2, reflection_probe.face_bind_group(face), &[]);
+                // blended objects would need sorting for every face
+                let objects = scene.objects.iter().enumerate();
+                let objects = objects.filter(|(_, o)| o.material.alpha_mode != AlphaMode::Blend);
+                for (i, object) in objects {
+                    bindings.bind(&mut pass, i);
+                    gpu_meshes[object.mesh].draw(&mut pass, 0..1);
+                }
+                pass.pop_debug_group();
+            }
+            reflection_probe.prefilter(&mut cmd, probe_faces);
+            for (id, inspector, _, _) in &shadow_previews {
+                let target = imgui_wgpu.textures.get(*id).unwrap().view();
+                let [min_depth, max_depth] = shadow_depth_range;
+                inspector.run(&mut cmd, queue, target, min_depth, max_depth);
+            }
+            // overdraw counts every fragment, whatever is in front of it
+            let overdraw = debug_view == DebugView::Overdraw;
+            // the multisampled opaque objects leave the single sampled depth
+            // to the pre-pass, and supersampled screenshots are antialiased
+            // already
+            // the pre-pass and the multisampled pass draw filled triangles,
+            // the topologies of the rasterization are left to the main pass
+            let rasterized = rasterization != Rasterization::default() && !overdraw;
+            let msaa = post_params.antialiasing == Antialiasing::Msaa
+                && !overdraw
+                && !rasterized
+                && supersampled.is_none();
+            let depth_prepass = (depth_prepass || msaa) && !overdraw && !rasterized;
+            if rasterized {
+                rasterization_pipelines
+                    .entry(rasterization)
+                    .or_insert_with(|| {
+                        scene_pipeline::create_pipeline(
+                            device,
+                            &pipeline_layout,
+                            "Scene pipeline (rasterization)",
+                            &vert_module,
+                            Some(&frag_module),
+                            &color_states,
+                            CompareFunction::Less,
+                            true,
+                            stencil_write.clone(),
+                            1,
+                            rasterization,
+                        )
+                    });
+            }
+            if depth_prepass {
+                let mut pass = PassBuilder::new()
+                    .depth(scene_depth_view, DEPTH_FORMAT, LoadOp::Clear(1.0), true)
+                    .stencil(LoadOp::Clear(0), true)
+                    .expect("Error beginning the depth pre-pass")
+                    .begin(&mut cmd);
+                pass.push_debug_group("Depth pre-pass");
+                pass.set_pipeline(&prepass_pipeline);
+                pass.set_bind_group(1, &lighting_bind_group, &[]);
+                // only opaque objects, the pre-pass can't discard fragments
+                let opaque = scene.objects.iter().zip(lods).enumerate();
+                let opaque =
+                    opaque.filter(|(_, (o, _))| o.material.alpha_mode == AlphaMode::Opaque);
+                for (i, (object, &lod)) in opaque {
+                    pass.set_stencil_reference(object.selected as u32);
+                    bindings.bind(&mut pass, i);
+                    gpu_meshes[object.mesh].draw_lod(&mut pass, lod, 0..layers);
+                }
+                pass.pop_debug_group();
+            }
+
+            let defines = [
+                ("NORMAL_MAPPING", normal_mapping as i32),
+                ("SHADOWS", shadows as i32),
+            ];
+            // the variants are built for the state of the pipelines they
+            // replace, the multisampled one included
+            let state = PipelineState {
+                color_states: color_states.to_vec(),
+                sample_count: 1,
+                rasterization: if rasterized {
+                    rasterization
+                } else {
+                    Rasterization::default()
+                },
+            };
+            let msaa_state = PipelineState {
+                sample_count: msaa::SAMPLE_COUNT,
+                rasterization: Rasterization::default(),
+                ..state.clone()
+            };
+            let build_variant = |module: &ShaderModule, state: &PipelineState| {
+                // the multisampled pipeline doesn't write the stencil, the
+                // pre-pass does
+                let stencil = if state.sample_count == 1 {
+                    stencil_write.clone()
+                } else {
+                    StencilStateDescriptor::default()
+                };
+                let pipeline = |label, depth_compare, depth_write_enabled| {
+                    scene_pipeline::create_pipeline(
+                        device,
+                        &pipeline_layout,
+                        label,
+                        &vert_module,
+                        Some(module),
+                        &state.color_states,
+                        depth_compare,
+                        depth_write_enabled,
+                        stencil.clone(),
+                        state.sample_count,
+                        state.rasterization,
+                    )
+                };
+                (
+                    pipeline("Scene pipeline (variant)", CompareFunction::Less, true),
+                    pipeline(
+                        "Scene pipeline (variant, depth equal)",
+                        CompareFunction::Equal,
+                        false,
+                    ),
+                )
+            };
+            if shader_variants
+                && scene_variants.build(device, &defines, &state, build_variant)
+                && msaa
+            {
+                scene_variants.build(device, &defines, &msaa_state, build_variant);
+            }
+            let variant = Some(&scene_variants)
+                .filter(|_| shader_variants)
+                .and_then(|variants| variants.get(&defines, &state));
+            let msaa_variant = Some(&scene_variants)
+                .filter(|_| shader_variants && msaa)
+                .and_then(|variants| variants.get(&defines, &msaa_state));
+            let (scene_pipeline, scene_pipeline_equal) = match variant {
+                Some((pipeline, pipeline_equal)) => (pipeline, pipeline_equal),
+                None if rasterized => {
+                    let pipeline = &rasterization_pipelines[&rasterization];
+                    (pipeline, pipeline)
+                }
+                None => (&render_pipeline, &render_pipeline_equal),
+            };
+            let msaa_pipeline = msaa_variant.map_or(&msaa_pipeline, |(pipeline, _)| pipeline);
+
+            {
+                let clear_color = if overdraw {
+                    Color::BLACK
+                } else {
+                    Color {
+                        r: 0.5,
+                        g: 0.5,
+                        b: 0.5,
+                        a: 1.0,
+                    }
+                };
+                let depth_load = if depth_prepass {
+                    LoadOp::Load
+                } else {
+                    LoadOp::Clear(1.0)
+                };
+                if msaa {
+                    let mut pass = PassBuilder::new()
+                        .color(
+                            msaa_targets.color(),
+                            HDR_FORMAT,
+                            LoadOp::Clear(clear_color),
+                            false,
+                        )
+                        .depth(
+                            msaa_targets.depth(),
+                            DEPTH_FORMAT,
+                            LoadOp::Clear(1.0),
+                            false,
+                        )
+                        .resolve(hdr_targets.view())
+                        .expect("Error beginning the multisampled scene pass")
+                        .begin(&mut cmd);
+                    if show_skybox {
+                        pass.push_debug_group("Skybox");
+                        if sky_params.procedural {
+                            sky.draw_multisampled(&mut pass);
+                        } else {
+                            skybox.draw_multisampled(&mut pass);
+                        }
+                        pass.pop_debug_group();
+                    }
+                    // masked objects are drawn single sampled, like the
+                    // pre-pass skips them
+                    pass.push_debug_group("Multisampled objects");
+                    pass.set_pipeline(msaa_pipeline);
+                    pass.set_bind_group(1, &lighting_bind_group, &[]);
+                    for &i in &queues.opaque {
+                        let object = &scene.objects[i];
+                        if object.material.alpha_mode == AlphaMode::Opaque {
+                            bindings.bind(&mut pass, i);
+                            gpu_meshes[object.mesh].draw_lod(&mut pass, lods[i], 0..layers);
+                        }
+                    }
+                    pass.pop_debug_group();
+                }
+                // what the multisampled pass resolved, and the stencil of the
+                // pre-pass, are drawn over
+                let (color_load, stencil_load) = if msaa {
+                    (LoadOp::Load, LoadOp::Load)
+                } else {
+                    (LoadOp::Clear(clear_color), LoadOp::Clear(0))
+                };
+                let object_pipeline = |object: &Object| match object.material.alpha_mode {
+                    _ if overdraw => &overdraw_pipeline,
+                    AlphaMode::Opaque if depth_prepass => scene_pipeline_equal,
+                    _ => scene_pipeline,
+                };
+                let drawn =
+                    |i: usize| !(msaa && scene.objects[i].material.alpha_mode == AlphaMode::Opaque);
+                // selected objects set the stencil reference, which bundles
+                // can't, so they're still drawn in the pass
+                let encode_start = Instant::now();
+                let cached = scene_bundles.enabled;
+                let bundled = parallel_encoding.enabled || cached;
+                let mut draws: Vec<usize> = queues
+                    .opaque
+                    .iter()
+                    .copied()
+                    .filter(|&i| drawn(i) && !scene.objects[i].selected)
+                    .collect();
+                let record = |draws: &[usize]| {
+                    parallel_encoding.record(
+                        device,
+                        color_states[0].format,
+                        DEPTH_FORMAT,
+                        draws,
+                        |bundle, chunk| {
+                            bundle.set_bind_group(1, &lighting_bind_group, &[]);
+                            for &i in chunk {
+                                let object = &scene.objects[i];
+                                bundle.set_pipeline(object_pipeline(object));
+                                bindings.bind(bundle, i);
+                                gpu_meshes[object.mesh].draw_lod(bundle, lods[i], 0..layers);
+                            }
+                        },
+                    )
+                };
+                let recorded;
+                let bundles = if cached {
+                    // in a fixed order, so moving the camera doesn't record
+                    // them again
+                    draws.sort_unstable();
+                    let pipelines = (
+                        overdraw,
+                        Some(rasterization).filter(|_| rasterized),
+                        depth_prepass,
+                        variant.is_some().then_some(defines),
+                        layers,
+                    );
+                    let objects = draws
+                        .iter()
+                        .map(|&i| {
+                            let object = &scene.objects[i];
+                            (i, object.mesh, lods[i], object.material.alpha_mode)
+                        })
+                        .collect::<Vec<_>>();
+                    scene_bundles.get((pipelines, objects), || record(&draws))
+                } else if bundled {
+                    recorded = record(&draws);
+                    &recorded[..]
+                } else {
+                    &[]
+                };
+                let mut encode_time = encode_start.elapsed();
+
+                // depth and stencil are kept for the overlays
+                let mut pass = PassBuilder::new()
+                    .color(hdr_targets.view(), HDR_FORMAT, color_load, true)
+                    .depth(scene_depth_view, DEPTH_FORMAT, depth_load, true)
+                    .stencil(stencil_load, true)
+                    .and_then(|pass| pass.targets(&[color_states[0].format], Some(DEPTH_FORMAT)))
+                    .expect("Error beginning the scene pass")
+                    .begin(&mut cmd);
+                if show_skybox && !overdraw && !msaa {
+                    pass.push_debug_group("Skybox");
+                    if sky_params.procedural {
+                        sky.draw(&mut pass);
+                    } else {
+                        skybox.draw(&mut pass);
+                    }
+                    pass.pop_debug_group();
+                }
+
+                pass.push_debug_group("Objects");
+                let encode_start = Instant::now();
+                if bundled {
+                    pass.set_stencil_reference(0);
+                    pass.execute_bundles(bundles);
+                }
+                pass.set_bind_group(1, &lighting_bind_group, &[]);
+                for &i in &queues.opaque {
+                    let object = &scene.objects[i];
+                    if !drawn(i) || (bundled && !object.selected) {
+                        continue;
+                    }
+                    pass.set_pipeline(object_pipeline(object));
+                    pass.set_stencil_reference(object.selected as u32);
+                    bindings.bind(&mut pass, i);
+                    gpu_meshes[object.mesh].draw_lod(&mut pass, lods[i], 0..layers);
+                }
+                encode_time += encode_start.elapsed();
+                parallel_encoding.measure(encode_time, cached);
+                pass.pop_debug_group();
+
+                if show_field {
+                    let cube = &gpu_meshes[1];
+                    pass.push_debug_group("Instanced field");
+                    pass.set_pipeline(if overdraw {
+                        &instanced_overdraw_pipeline
+                    } else {
+                        &instanced_pipeline
+                    });
+                    pass.set_stencil_reference(0);
+                    pass.set_bind_group(0, &field_bind_group, &[]);
+                    cube.bind(&mut pass);
+                    if gpu_culling {
+                        pass.set_vertex_buffer(1, field.visible.slice(..));
+                        pass.draw_indexed_indirect(&field.indirect, 0);
+                    } else {
+                        pass.set_vertex_buffer(1, field.instances.slice(..));
+                        pass.draw_indexed(0..cube.index_count(), 0, 0..field.count());
+                    }
+                    pass.pop_debug_group();
+                }
+
+                if show_grid && !overdraw {
+                    pass.push_debug_group("Grid");
+                    grid.draw(&mut pass);
+                    pass.pop_debug_group();
+                }
+
+                // decals sample the depth of the opaque objects, so the pass
+                // is broken up around them, and they aren't drawn over the
+                // blended objects. The supersampled depth isn't sampled.
+                if show_decals && !decals.is_empty() && !overdraw && supersampled.is_none() {
+                    drop(pass);
+                    let mut decal_pass = PassBuilder::new()
+                        .color(hdr_targets.view(), HDR_FORMAT, LoadOp::Load, true)
+                        .begin(&mut cmd);
+                    decal_pass.push_debug_group("Decals");
+                    decal_renderer.draw(&mut decal_pass, &decal_depth);
+                    decal_pass.pop_debug_group();
+                    drop(decal_pass);
+                    pass = PassBuilder::new()
+                        .color(hdr_targets.view(), HDR_FORMAT, LoadOp::Load, true)
+                        .depth(scene_depth_view, DEPTH_FORMAT, LoadOp::Load, true)
+                        .stencil(LoadOp::Load, true)
+                        .and_then(|pass| {
+                            pass.targets(&[color_states[0].format], Some(DEPTH_FORMAT))
+                        })
+                        .expect("Error beginning the blended objects pass")
+                        .begin(&mut cmd);
+                }
+
+                // blended objects, back to front over everything else, but
+                // those blended in any order after the pass (or added up with
+                // the rest for overdraw)
+                let blended = if overdraw {
+                    &queues.order_independent[..]
+                } else {
+                    &[]
+                };
+                pass.push_debug_group("Blended objects");
+                pass.set_pipeline(if overdraw {
+                    &overdraw_pipeline
+                } else {
+                    &blend_pipeline
+                });
+                pass.set_bind_group(1, &lighting_bind_group, &[]);
+                for &i in queues.transparent.iter().chain(blended) {
+                    let object = &scene.objects[i];
+                    pass.set_stencil_reference(object.selected as u32);
+                    bindings.bind(&mut pass, i);
+                    gpu_meshes[object.mesh].draw_lod(&mut pass, lods[i], 0..layers);
+                }
+                pass.pop_debug_group();
+                drop(pass);
+
+                if !overdraw && !queues.order_independent.is_empty() {
+                    let mut pass = oit.begin(&mut cmd, oit_targets, scene_depth_view, DEPTH_FORMAT);
+                    pass.push_debug_group("Order independent objects");
+                    pass.set_pipeline(&oit_pipeline);
+                    pass.set_bind_group(1, &lighting_bind_group, &[]);
+                    for &i in &queues.order_independent {
+                        let object = &scene.objects[i];
+                        pass.set_stencil_reference(object.selected as u32);
+                        bindings.bind(&mut pass, i);
+                        gpu_meshes[object.mesh].draw_lod(&mut pass, lods[i], 0..layers);
+                    }
+                    pass.pop_debug_group();
+                    drop(pass);
+                    oit.composite(&mut cmd, oit_targets, hdr_targets.view());
+                }
+            }
+
+            let frame = PostFrame {
+                near: frustum.near,
+                far: FAR_PLANE,
+                view_projection: projection * view,
+                previous_view_projection,
+                eye,
+                forward: (target - eye).normalize(),
+                light_direction,
+                light_color,
+                cascades: cascades_fit,
+                time_delta: delta.as_secs_f32(),
+                lit: debug_view == DebugView::Final,
+            };
+            post.effects(&mut cmd, queue, &post_params, frame, hdr_targets);
+            previous_view_projection = projection * view;
+
+            if let (Some(rect), Some(second_view)) = (second_rect, second_camera) {
+                let mut pass = PassBuilder::new()
+                    .color(hdr_targets.result(), HDR_FORMAT, LoadOp::Load, true)
+                    .depth(second_depth_view, DEPTH_FORMAT, LoadOp::Clear(1.0), true)
+                    .stencil(LoadOp::Clear(0), true)
+                    .and_then(|pass| pass.targets(&[color_states[0].format], Some(DEPTH_FORMAT)))
+                    .expect("Error beginning the second viewport pass")
+                    .begin(&mut cmd);
+                let (x, y) = (rect.x as f32, rect.y as f32);
+                pass.set_viewport(x, y, rect.width as f32, rect.height as f32, 0.0, 1.0);
+                pass.set_scissor_rect(rect.x, rect.y, rect.width, rect.height);
+                pass.push_debug_group("Second viewport");
+                if show_skybox && !overdraw {
+                    if sky_params.procedural {
+                        viewport_sky.draw(&mut pass);
+                    } else {
+                        viewport_skybox.draw(&mut pass);
+                    }
+                }
+                pass.set_bind_group(1, &lighting_bind_group, &[]);
+                pass.set_pipeline(if overdraw {
+                    &overdraw_pipeline
+                } else {
+                    scene_pipeline
+                });
+                for &i in &queues.opaque {
+                    viewport_bindings.bind(&mut pass, i);
+                    gpu_meshes[scene.objects[i].mesh].draw_lod(&mut pass, lods[i], 0..layers);
+                }
+                // OIT objects are sorted with the rest, the second viewport
+                // has no targets for them
+                let second_queues = DrawQueues::new(&scene, &meshes, second_view, false);
+                pass.set_pipeline(if overdraw {
+                    &overdraw_pipeline
+                } else {
+                    &blend_pipeline
+                });
+                for &i in &second_queues.transparent {
+                    viewport_bindings.bind(&mut pass, i);
+                    gpu_meshes[scene.objects[i].mesh].draw_lod(&mut pass, lods[i], 0..layers);
+                }
+                pass.pop_debug_group();
+            }
+            post.composite(
+                &mut cmd,
+                queue,
+                &post_params,
+                frame,
+                hdr_targets,
+                scene_view,
+            );
+
+            {
+                let mut pass = PassBuilder::new()
+                    .color(scene_view, context::FORMAT, LoadOp::Load, true)
+                    .depth(scene_depth_view, DEPTH_FORMAT, LoadOp::Load, false)
+                    .stencil(LoadOp::Load, false)
+                    .and_then(|pass| {
+                        pass.targets(&[output_color_states[0].format], Some(DEPTH_FORMAT))
+                    })
+                    .expect("Error beginning the overlay pass")
+                    .begin(&mut cmd);
+                // overlays of the main camera stay in its viewport
+                let rect = main_rect;
+                pass.set_scissor_rect(rect.x, rect.y, rect.width, rect.height);
+
+                // outlines, wherever the stencil wasn't written by the object
+                pass.push_debug_group("Outlines");
+                pass.set_pipeline(&outline_pipeline);
+                pass.set_bind_group(1, &lighting_bind_group, &[]);
+                pass.set_stencil_reference(1);
+                // at the level drawn, to match the stencil
+                let objects = scene.objects.iter().zip(lods).enumerate();
+                for (i, (object, &lod)) in objects.filter(|(_, (o, _))| o.selected) {
+                    bindings.bind(&mut pass, i);
+                    gpu_meshes[object.mesh].draw_lod(&mut pass, lod, 0..layers);
+                }
+                pass.pop_debug_group();
+
+                pass.push_debug_group("Gizmos");
+                gizmos.draw(&mut pass);
+                pass.pop_debug_group();
+
+                pass.push_debug_group("Labels");
+                text.draw(&mut pass);
+                pass.pop_debug_group();
+            }
+        } else if demo == Demo::Pulling {
+            let mut pass = PassBuilder::new()
+                .color(
+                    scene_view,
+                    context::FORMAT,
+                    LoadOp::Clear(Color::BLACK),
+                    true,
+                )
+                .depth(scene_depth_view, DEPTH_FORMAT, LoadOp::Clear(1.0), false)
+                .begin(&mut cmd);
+            pass.push_debug_group("Vertex pulling");
+            vertex_pulling.draw(&mut pass, &pulling_params);
+            pass.pop_debug_group();
+        } else {
+            let mut pass = PassBuilder::new()
+                .color(
+                    scene_view,
+                    context::FORMAT,
+                    LoadOp::Clear(Color::BLACK),
+                    true,
+                )
+                .begin(&mut cmd);
+            if demo == Demo::Raymarch {
+                pass.push_debug_group("Ray marching");
+                raymarch.draw(&mut pass);
+                pass.pop_debug_group();
+            } else if demo == Demo::Blend {
+                pass.push_debug_group("Blend playground");
+                blend_playground.draw(&mut pass);
+                pass.pop_debug_group();
+            } else {
+                pass.push_debug_group("N-body");
+                nbody.draw(&mut pass, nbody_params.count);
+                pass.pop_debug_group();
+            }
+        }
+
+        drop(passes_scope);
+
+        {
+            let _scope = profiler::scope("UI");
+            if style_dirty {
+                settings.style.apply_colors(imgui.style_mut());
+                style_dirty = false;
+            }
+            if font_dirty {
+                settings.style.load_font(&mut imgui, hidpi_factor);
+                imgui_wgpu.reload_font_texture(&mut imgui, device, queue);
+                font_dirty = false;
+            }
+            let mut pass = PassBuilder::new()
+                .color(output_view, context::FORMAT, LoadOp::Load, true)
+                .begin(&mut cmd);
 
             // draw imgui
-            imgui_sdl2.prepare_frame(imgui.io_mut(), &window, &events.mouse_state());
+            imgui_platform.prepare_frame(imgui.io_mut(), &window);
+            if let Some(replay) = &replay {
+                // the cursor of the recording, not the one over the window
+                let io = imgui.io_mut();
+                let (x, y) = replay.mouse();
+                io.mouse_pos = [x as f32, y as f32];
+                io.mouse_down = replay.buttons();
+            }
+            if mouse_look {
+                // hide the cursor from imgui so nothing is hovered either
+                let io = imgui.io_mut();
+                io.mouse_pos = [-f32::MAX, -f32::MAX];
+                io.mouse_down = [false; 5];
+                io.config_flags |= ConfigFlags::NO_MOUSE_CURSOR_CHANGE;
+            } else {
+                imgui.io_mut().config_flags -= ConfigFlags::NO_MOUSE_CURSOR_CHANGE;
+            }
             let ui = imgui.frame();
 
             ui.show_demo_window(&mut true);
             ui.show_metrics_window(&mut true);
 
-            imgui_sdl2.prepare_render(&ui, &window);
-            imgui_wgpu
-                .render(ui.render(), &queue, &device, &mut pass)
-                .expect("Error rendering imgui");
+            Window::new(im_str!("Demo"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    ui.radio_button(im_str!("Scene"), &mut demo, Demo::Scene);
+                    ui.radio_button(im_str!("Ray marching"), &mut demo, Demo::Raymarch);
+                    ui.radio_button(im_str!("N-body"), &mut demo, Demo::NBody);
+                    ui.radio_button(im_str!("Blending"), &mut demo, Demo::Blend);
+                    ui.radio_button(im_str!("Vertex pulling"), &mut demo, Demo::Pulling);
+                    let key = |action| settings.bindings.key(action).name();
+                    ui.text(format!(
+                        "Hold right click or press {} to look around",
+                        key(Action::ToggleMouseLook)
+                    ));
+                    ui.text(format!(
+                        "{}{}{}{} to move, {}/{} down and up",
+                        key(Action::MoveForward),
+                        key(Action::MoveLeft),
+                        key(Action::MoveBack),
+                        key(Action::MoveRight),
+                        key(Action::MoveDown),
+                        key(Action::MoveUp)
+                    ));
+                    ui.text("Controls can be changed in the Controls window");
+                });
+
+            ui.main_menu_bar(|| {
+                ui.menu(im_str!("File"), true, || {
+                    if MenuItem::new(im_str!("Open model...")).build(&ui) {
+                        open_dialog = Some(("Open model", &dialog::MODELS));
+                    }
+                    if MenuItem::new(im_str!("Open texture...")).build(&ui) {
+                        open_dialog = Some(("Open texture", &dialog::TEXTURES));
+                    }
+                    if MenuItem::new(im_str!("Open scene...")).build(&ui) {
+                        open_dialog = Some(("Open scene", &dialog::SCENES));
+                    }
+                    if MenuItem::new(im_str!("Save scene...")).build(&ui) {
+                        save_scene_dialog = true;
+                    }
+                    ui.menu(
+                        im_str!("Open recent"),
+                        !settings.recent_files.is_empty(),
+                        || {
+                            for file in &settings.recent_files {
+                                if MenuItem::new(&ImString::new(file)).build(&ui) {
+                                    reopen = Some(file.clone());
+                                }
+                            }
+                        },
+                    );
+                });
+                ui.menu(im_str!("View"), true, || {
+                    MenuItem::new(im_str!("Grid")).build_with_ref(&ui, &mut show_grid);
+                    if show_grid {
+                        Slider::new(im_str!("Grid spacing"))
+                            .range(0.1..=10.0)
+                            .flags(SliderFlags::LOGARITHMIC)
+                            .build(&ui, &mut grid_params.spacing);
+                        Slider::new(im_str!("Grid fade distance"))
+                            .range(5.0..=FAR_PLANE)
+                            .build(&ui, &mut grid_params.fade_distance);
+                    }
+                    MenuItem::new(im_str!("Skybox")).build_with_ref(&ui, &mut show_skybox);
+                    MenuItem::new(im_str!("Gizmos")).build_with_ref(&ui, &mut show_gizmos);
+                    MenuItem::new(im_str!("Labels")).build_with_ref(&ui, &mut show_labels);
+                    ui.separator();
+                    MenuItem::new(im_str!("Script editor")).build_with_ref(&ui, &mut show_script);
+                });
+            });
+
+            console.window(&ui);
+
+            let changes = settings.style.ui(&ui, &mut font_path);
+            style_dirty |= changes.colors;
+            font_dirty |= changes.font;
+
+            Window::new(im_str!("Camera"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    let mut fov = frustum.fov_y.to_degrees();
+                    if Slider::new(im_str!("Field of view"))
+                        .range(20.0..=120.0)
+                        .display_format(im_str!("%.0f deg"))
+                        .build(&ui, &mut fov)
+                    {
+                        frustum.fov_y = fov.to_radians();
+                    }
+                    ui.separator();
+                    viewport_params.ui(&ui);
+                    ui.separator();
+                    let key = settings.bindings.key(Action::SaveBookmark).name();
+                    let pose = camera.pose(frustum.fov_y);
+                    bookmarks.ui(&ui, &mut bookmark_name, pose, key);
+                });
+
+            Window::new(im_str!("Controls"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    settings.bindings.ui(&ui, &mut rebinding);
+                });
+            memory::window(&ui);
+            profiler::window(&ui);
+
+            Window::new(im_str!("Settings"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    match &args.trace {
+                        Some(dir) => ui.text(format!("Recording API trace to {}", dir.display())),
+                        None => ui.text("API trace disabled"),
+                    }
+                    ui.input_text(im_str!("Trace directory"), &mut trace_dir)
+                        .build();
+                    restart = ui.button(im_str!("Restart with API trace"), [0.0, 0.0]);
+                    ui.same_line(0.0);
+                    ui.text_disabled("(rebuilds the renderer and the window)");
+                    ui.separator();
+                    let key = settings.bindings.key(Action::Screenshot).name();
+                    if ui.button(&im_str!("Screenshot ({})", key), [0.0, 0.0]) {
+                        screenshot = true;
+                    }
+                    if ui.button(
+                        &im_str!("Supersampled screenshot (Shift+{})", key),
+                        [0.0, 0.0],
+                    ) {
+                        supersampled_screenshot = true;
+                    }
+                    for (i, factor) in supersample::FACTORS.iter().enumerate() {
+                        ui.same_line(0.0);
+                        ui.radio_button(&im_str!("{}x", factor), &mut supersample_factor, i);
+                    }
+                    ui.text(format!(
+                        "Rendered at {}x{}",
+                        width * supersample::FACTORS[supersample_factor],
+                        height * supersample::FACTORS[supersample_factor]
+                    ));
+                    ui.separator();
+                    match &gif_recorder {
+                        Some(recorder) => {
+                            let (captured, total) = recorder.progress();
+                            ui.text(format!("Recording GIF: {}/{} frames", captured, total));
+                            if ui.button(im_str!("Cancel"), [0.0, 0.0]) {
+                                gif_recorder = None;
+                            }
+                        }
+                        None => {
+                            Slider::new(im_str!("GIF fps"))
+                                .range(5..=30)
+                                .build(&ui, &mut gif_settings.fps);
+                            Slider::new(im_str!("GIF seconds"))
+                                .range(1.0..=10.0)
+                                .build(&ui, &mut gif_settings.seconds);
+                            ui.text("Downscale");
+                            for (i, scale) in gif::SCALES.iter().enumerate() {
+                                ui.same_line(0.0);
+                                ui.radio_button(
+                                    &im_str!("1/{}", scale),
+                                    &mut gif_settings.scale,
+                                    i,
+                                );
+                            }
+                            if ui.button(im_str!("Record GIF"), [0.0, 0.0]) {
+                                gif_recorder = Some(GifRecorder::new(width, height, &gif_settings));
+                            }
+                        }
+                    }
+                    ui.separator();
+                    ui.text("Recent files");
+                    for file in &settings.recent_files {
+                        if ui.small_button(&ImString::new(file)) {
+                            reopen = Some(file.clone());
+                        }
+                    }
+                    ui.checkbox(im_str!("Throttle when unfocused"), &mut throttle_unfocused);
+                    ui.checkbox(
+                        im_str!("Status in window title"),
+                        &mut settings.title_status,
+                    );
+                    reset_settings = ui.button(im_str!("Reset to defaults"), [0.0, 0.0]);
+                });
+
+            Window::new(im_str!("Adapters"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    for (i, info) in adapters.iter().enumerate() {
+                        let mut label = ImString::new(adapter::describe(info));
+                        if Some(i) == adapter_index {
+                            label.push_str(" (current)");
+                        }
+                        ui.radio_button(&label, &mut selected_adapter, i);
+                    }
+                    if Some(selected_adapter) != adapter_index {
+                        switch_adapter = ui.button(im_str!("Switch adapter"), [0.0, 0.0]);
+                        ui.same_line(0.0);
+                        ui.text_disabled("(rebuilds the renderer and the window)");
+                    }
+                    ui.separator();
+                    for (name, granted) in &context.capabilities.list() {
+                        let state = if *granted { "yes" } else { "no" };
+                        ui.text(format!("{}: {}", name, state));
+                    }
+                });
+
+            Window::new(im_str!("Latency"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    let mut index = PRESENT_MODES
+                        .iter()
+                        .position(|&mode| mode == present_mode)
+                        .unwrap();
+                    if ComboBox::new(im_str!("Present mode")).build_simple_string(
+                        &ui,
+                        &mut index,
+                        &present_mode_names,
+                    ) {
+                        present_mode = PRESENT_MODES[index];
+                        present_mode_dirty = true;
+                        frame_stats = FrameStats::default();
+                    }
+                    let stat = |label, samples: &Samples, unit| {
+                        ui.text(format!(
+                            "{}: {:.2} {} (max {:.2})",
+                            label,
+                            samples.average(),
+                            unit,
+                            samples.max()
+                        ));
+                    };
+                    stat("Input to present", &frame_stats.input_to_present, "ms");
+                    stat("Acquire wait", &frame_stats.acquire, "ms");
+                    stat("Present", &frame_stats.present, "ms");
+                    stat("Frames in flight", &frame_stats.in_flight, "");
+                    if Slider::new(im_str!("Frame contexts"))
+                        .range(1..=4)
+                        .build(&ui, &mut frame_contexts)
+                    {
+                        frame_contexts_dirty = true;
+                    }
+                    ui.same_line(0.0);
+                    ui.text_disabled("(per frame uniforms and readbacks)");
+                    stat("Context wait", &frame_ring.waits, "ms");
+                    ui.text(format!("Frames that waited: {}", frame_ring.stalls));
+                    ui.plot_lines(
+                        im_str!("Input to present##plot"),
+                        &frame_stats.input_to_present.values(),
+                    )
+                    .scale_min(0.0)
+                    .graph_size([0.0, 60.0])
+                    .build();
+                });
+
+            Window::new(im_str!("Time"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    ui.text(format!("Elapsed: {:.2} s", time.elapsed().as_secs_f32()));
+                    ui.text(format!(
+                        "Frame time: {:.2} ms",
+                        time.delta().as_secs_f32() * 1000.0
+                    ));
+                    let stats = draw_stats::last_frame();
+                    ui.separator();
+                    ui.text(format!("Draw calls: {}", stats.draws));
+                    ui.text(format!("Triangles: {}", stats.triangles));
+                    ui.text(format!("Pipeline switches: {}", stats.pipelines));
+                    ui.text(format!("Bind group switches: {}", stats.bind_groups));
+                    ui.text(format!(
+                        "Buffer uploads: {} ({:.1} KiB)",
+                        stats.uploads,
+                        stats.upload_bytes as f32 / 1024.0
+                    ));
+                    if scene_bundles.enabled {
+                        let cached = parallel_encoding.cached.average();
+                        ui.text(format!("Scene recording: {:.3} ms", cached));
+                        // against the last time the draws were recorded into
+                        // bundles, drawing in the pass leaves the encoding
+                        // out
+                        match parallel_encoding.parallel.average() {
+                            parallel if parallel > 0.0 => ui.text(format!(
+                                "Saved by static bundles: {:.3} ms",
+                                parallel - cached
+                            )),
+                            _ => ui.text_disabled(
+                                "Record in parallel without static bundles to compare",
+                            ),
+                        }
+                    }
+                });
+
+            Window::new(im_str!("Scene"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    for object in &mut scene.objects {
+                        ui.checkbox(&im_str!("{}", object.name), &mut object.selected);
+                        Slider::new(&im_str!("Metallic##{}", object.name))
+                            .range(0.0..=1.0)
+                            .build(&ui, &mut object.material.metallic);
+                        Slider::new(&im_str!("Roughness##{}", object.name))
+                            .range(0.0..=1.0)
+                            .build(&ui, &mut object.material.roughness);
+                        let targets = object.morph_targets.iter();
+                        for (target, weight) in targets.zip(&mut object.morph_weights) {
+                            Slider::new(&im_str!("{}##{}", target, object.name))
+                                .range(0.0..=1.0)
+                                .build(&ui, weight);
+                        }
+                    }
+                });
+
+            if show_script {
+                Window::new(im_str!("Script"))
+                    .always_auto_resize(true)
+                    .build(&ui, || {
+                        ui.input_text(im_str!("File"), &mut script_path).build();
+                        ui.same_line(0.0);
+                        load_script = ui.button(im_str!("Load"), [0.0, 0.0]);
+                        ui.same_line(0.0);
+                        save_script = ui.button(im_str!("Save"), [0.0, 0.0]);
+                        ui.checkbox(
+                            im_str!("Run again when the file changes"),
+                            &mut watch_script,
+                        );
+                        ui.input_text_multiline(
+                            im_str!("##Script"),
+                            &mut script_source,
+                            [640.0, 400.0],
+                        )
+                        .resize_buffer(true)
+                        .allow_tab_input(true)
+                        .build();
+                        run_script = ui.button(im_str!("Run"), [0.0, 0.0]);
+                        ui.same_line(0.0);
+                        if ui.button(im_str!("Stop"), [0.0, 0.0]) {
+                            script = None;
+                        }
+                        ui.same_line(0.0);
+                        match (&script_error, &script) {
+                            (Some(err), _) => {
+                                ui.text_colored([1.0, 0.3, 0.3, 1.0], err.to_string())
+                            }
+                            (None, Some(script)) if script.animates() => {
+                                ui.text(format!("Running for {:.1} s", script_time))
+                            }
+                            (None, Some(_)) => ui.text("Set up"),
+                            (None, None) => ui.text_disabled("Not running"),
+                        }
+                        ui.text_disabled(
+                            "(objects spawned by the last run are removed when it runs again)",
+                        );
+                    });
+            }
+
+            transform_gizmo.ui(&ui, scene.objects.iter_mut().find(|o| o.selected));
+
+            Window::new(im_str!("Physics"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    ui.checkbox(im_str!("Gravity"), &mut gravity);
+                    ui.checkbox(im_str!("Show colliders"), &mut show_colliders);
+                    ui.separator();
+                    match scene.objects.iter_mut().find(|o| o.selected) {
+                        Some(object) => {
+                            ui.text(&object.name);
+                            let mut index =
+                                BODY_KINDS.iter().position(|&k| k == object.body).unwrap();
+                            if ComboBox::new(im_str!("Rigid body")).build_simple_string(
+                                &ui,
+                                &mut index,
+                                &body_kind_names,
+                            ) {
+                                object.body = BODY_KINDS[index];
+                            }
+                        }
+                        None => ui.text("Select an object to give it a rigid body"),
+                    }
+                });
+
+            vertex_editor.ui(&ui, &mut meshes);
+            reflection_probe.ui(&ui);
+
+            if let Some(object) = scene.objects.iter_mut().find(|o| o.selected) {
+                Window::new(im_str!("Material"))
+                    .always_auto_resize(true)
+                    .build(&ui, || {
+                        ui.text(&object.name);
+                        object.material.ui(&ui);
+                        ui.separator();
+                        let ids = |thumbnails: &[(TextureId, _)]| -> Vec<_> {
+                            thumbnails.iter().map(|&(id, _)| id).collect()
+                        };
+                        let (color, linear) = (ids(&color_thumbnails), ids(&linear_thumbnails));
+                        if let Some(slot) = object.material.textures_ui(&ui, &color, &linear) {
+                            texture_dialog = Some(slot);
+                        }
+                    });
+            }
+
+            if !animated.is_empty() {
+                Window::new(im_str!("Timeline"))
+                    .always_auto_resize(true)
+                    .build(&ui, || {
+                        let length = timeline_length(&animated);
+                        let keyframes: Vec<_> = animated
+                            .iter()
+                            .filter_map(|a| a.player.clip(&a.skeleton).map(|c| (a, c)))
+                            .flat_map(|(animated, clip)| animated.skeleton.keyframes(clip))
+                            .collect();
+                        timeline.ui(&ui, length, &keyframes);
+
+                        ui.separator();
+                        for (i, animated) in animated.iter_mut().enumerate() {
+                            let id = ui.push_id(i as i32);
+                            let skeleton = &animated.skeleton;
+                            // the first entry is the rest pose
+                            let names: Vec<_> = std::iter::once(ImString::new("Rest pose"))
+                                .chain(skeleton.clips.iter().map(|c| ImString::new(&c.name)))
+                                .collect();
+                            let names: Vec<_> = names.iter().collect();
+                            let mut index = animated.player.clip.map_or(0, |clip| clip + 1);
+                            if ComboBox::new(&im_str!("{}", animated.name))
+                                .build_simple_string(&ui, &mut index, &names)
+                            {
+                                animated.player.clip = index.checked_sub(1);
+                            }
+                            id.pop(&ui);
+                        }
+                    });
+            }
+
+            Window::new(im_str!("Shadow maps"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    shadow_params.ui(&ui);
+
+                    ui.separator();
+                    let [min_depth, max_depth] = &mut shadow_depth_range;
+                    Slider::new(im_str!("Min depth"))
+                        .range(0.0..=1.0)
+                        .display_format(im_str!("%.4f"))
+                        .build(&ui, min_depth);
+                    Slider::new(im_str!("Max depth"))
+                        .range(0.0..=1.0)
+                        .display_format(im_str!("%.4f"))
+                        .build(&ui, max_depth);
+                    for ((id, _, size, _), name) in shadow_previews
+                        .iter()
+                        .zip(&["Cascades", "Point light (+X, -X, +Y, -Y, +Z, -Z)"])
+                    {
+                        ui.text(name);
+                        Image::new(*id, *size).build(&ui);
+                    }
+                });
+
+            Window::new(im_str!("Lighting"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    ui.checkbox(im_str!("Normal mapping"), &mut normal_mapping);
+                    ui.checkbox(im_str!("Image based lighting"), &mut image_based_lighting);
+                    ui.checkbox(im_str!("Skybox"), &mut show_skybox);
+                    Slider::new(im_str!("Light intensity"))
+                        .range(0.0..=10.0)
+                        .build(&ui, &mut light_intensity);
+                    sky_params.ui(&ui);
+                    ui.checkbox(im_str!("Shadows"), &mut shadows);
+                    ui.checkbox(im_str!("Shader variants"), &mut shader_variants);
+                    if shader_variants {
+                        ui.same_line(0.0);
+                        ui.text(format!("({} compiled)", scene_variants.len()));
+                    }
+                    ui.checkbox(im_str!("Show cascades"), &mut cascade_debug);
+                    let mut index = DEBUG_VIEWS.iter().position(|&v| v == debug_view).unwrap();
+                    if ComboBox::new(im_str!("Debug view")).build_simple_string(
+                        &ui,
+                        &mut index,
+                        &debug_view_names,
+                    ) {
+                        debug_view = DEBUG_VIEWS[index];
+                    }
+                    // against the final view, debug views aren't antialiased
+                    let mut index = ANTIALIASING
+                        .iter()
+                        .position(|&a| a == post_params.antialiasing)
+                        .unwrap();
+                    if ComboBox::new(im_str!("Antialiasing")).build_simple_string(
+                        &ui,
+                        &mut index,
+                        &antialiasing_names,
+                    ) {
+                        post_params.antialiasing = ANTIALIASING[index];
+                    }
+                    if post_params.antialiasing == Antialiasing::Taa {
+                        Slider::new(im_str!("History feedback"))
+                            .range(0.5..=0.98)
+                            .build(&ui, &mut post_params.taa.feedback);
+                    }
+                    if debug_view == DebugView::Depth {
+                        Slider::new(im_str!("Depth range"))
+                            .range(1.0..=100.0)
+                            .display_format(im_str!("%.1f"))
+                            .build(&ui, &mut debug_depth_range);
+                    }
+
+                    ui.separator();
+                    ui.text("Point light");
+                    point_light.ui(&ui);
+                    ui.checkbox(im_str!("Point shadows"), &mut point_shadows);
+                });
+
+            Window::new(im_str!("Post-processing"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    post_params.fog.ui(&ui, FAR_PLANE);
+                    ui.separator();
+                    post_params.volumetric.ui(&ui, FAR_PLANE);
+                    ui.separator();
+                    post_params.dof.ui(&ui, FAR_PLANE);
+                    ui.separator();
+                    post_params.motion_blur.ui(&ui);
+                    ui.separator();
+                    post_params.bloom.ui(&ui);
+                    ui.separator();
+                    post_params.exposure.ui(&ui);
+                    ui.separator();
+                    let names: Vec<_> = luts.iter().map(|(name, _)| name).collect();
+                    if ComboBox::new(im_str!("Color grading")).build_simple_string(
+                        &ui,
+                        &mut selected_lut,
+                        &names,
+                    ) {
+                        post.set_lut(device, queue, luts[selected_lut].1.as_ref());
+                    }
+                    Slider::new(im_str!("Blend"))
+                        .range(0.0..=1.0)
+                        .build(&ui, &mut post_params.grading.blend);
+                    if ui.button(im_str!("Load..."), [0.0, 0.0]) {
+                        lut_dialog = true;
+                    }
+                });
+
+            // the bind groups keep the rest of it borrowed
+            lights::window(&ui, &mut lights, &mut show_gizmos, &mut clusters.enabled);
+
+            decals::window(
+                &ui,
+                &mut decals,
+                &mut selected_decal,
+                &mut show_decals,
+                target,
+                debug_view == DebugView::Overdraw,
+            );
+
+            Window::new(im_str!("Labels"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    ui.checkbox(im_str!("Show labels"), &mut show_labels);
+                    Slider::new(im_str!("Size"))
+                        .range(0.05..=0.5)
+                        .build(&ui, &mut label_size);
+                    text_style.ui(&ui);
+                });
+
+            if demo == Demo::Raymarch {
+                Window::new(im_str!("Shader editor"))
+                    .always_auto_resize(true)
+                    .build(&ui, || {
+                        ui.text("raymarch.frag");
+                        ui.input_text_multiline(
+                            im_str!("##Source"),
+                            &mut shader_source,
+                            [640.0, 400.0],
+                        )
+                        .resize_buffer(true)
+                        .allow_tab_input(true)
+                        .build();
+                        compile_shader = ui.button(im_str!("Compile"), [0.0, 0.0]);
+                        ui.same_line(0.0);
+                        if ui.button(im_str!("Revert"), [0.0, 0.0]) {
+                            shader_source = ImString::new(raymarch::SOURCE);
+                            compile_shader = true;
+                        }
+                        match &shader_error {
+                            Some(CompileError::Source(_)) => ui.text_colored(
+                                [1.0, 0.3, 0.3, 1.0],
+                                "Errors, see Diagnostics. Drawing the last shader that compiled",
+                            ),
+                            Some(err) => ui.text_colored([1.0, 0.3, 0.3, 1.0], err.to_string()),
+                            None => {}
+                        }
+                    });
+                if let Some(CompileError::Source(diagnostics)) = &shader_error {
+                    Window::new(im_str!("Diagnostics"))
+                        .always_auto_resize(true)
+                        .build(&ui, || {
+                            for diagnostic in diagnostics {
+                                let color = match diagnostic.severity {
+                                    Severity::Error => [1.0, 0.3, 0.3, 1.0],
+                                    Severity::Warning => [1.0, 0.8, 0.3, 1.0],
+                                };
+                                ui.text_colored(color, diagnostic.to_string());
+                                // the line, with a caret under the column
+                                if let Some(line) = diagnostic.source_line(shader_source.to_str()) {
+                                    ui.text(format!("    {}", line));
+                                    if let Some(column) = diagnostic.column {
+                                        let caret = " ".repeat(column.saturating_sub(1) as usize);
+                                        ui.text(format!("    {}^", caret));
+                                    }
+                                }
+                            }
+                        });
+                }
+            }
+
+            if demo == Demo::Blend {
+                blend_playground.ui(&ui, device);
+            }
+
+            if demo == Demo::NBody {
+                Window::new(im_str!("N-body"))
+                    .always_auto_resize(true)
+                    .build(&ui, || {
+                        nbody_params.ui(&ui);
+                        ui.same_line(0.0);
+                        reset_nbody = ui.button(im_str!("Reset"), [0.0, 0.0]);
+                    });
+            }
+
+            if demo == Demo::Pulling {
+                Window::new(im_str!("Vertex pulling"))
+                    .always_auto_resize(true)
+                    .build(&ui, || {
+                        pulling_params.ui(&ui);
+                        ui.text(format!(
+                            "{} vertices, {} indices a sphere",
+                            vertex_pulling.vertex_count(),
+                            vertex_pulling.index_count()
+                        ));
+                        ui.text_disabled("Compare the GPU time of both in the Time window");
+                    });
+            }
+
+            Window::new(im_str!("Bitonic sort"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    Slider::new(im_str!("Keys"))
+                        .range(1..=sort::MAX_LEN)
+                        .build(&ui, &mut sort_len);
+                    test_sort = ui.button(im_str!("Compare with CPU sort"), [0.0, 0.0]);
+                    if let Some((passed, elapsed)) = sort_result {
+                        let (color, result) = if passed {
+                            ([0.3, 1.0, 0.3, 1.0], "Passed")
+                        } else {
+                            ([1.0, 0.3, 0.3, 1.0], "Failed")
+                        };
+                        let elapsed = elapsed.as_secs_f32() * 1000.0;
+                        ui.text_colored(color, format!("{} in {:.2} ms", result, elapsed));
+                    }
+                });
+
+            Window::new(im_str!("Reduce and scan"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    Slider::new(im_str!("Values"))
+                        .range(1..=scan::MAX_LEN)
+                        .build(&ui, &mut scan_len);
+                    test_scan = ui.button(im_str!("Compare with CPU"), [0.0, 0.0]);
+                    if let Some((passed, elapsed)) = scan_result {
+                        let (color, result) = if passed {
+                            ([0.3, 1.0, 0.3, 1.0], "Passed")
+                        } else {
+                            ([1.0, 0.3, 0.3, 1.0], "Failed")
+                        };
+                        let elapsed = elapsed.as_secs_f32() * 1000.0;
+                        ui.text_colored(color, format!("{} in {:.2} ms", result, elapsed));
+                    }
+                });
+
+            Window::new(im_str!("Environment"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    let params = inspector_params;
+                    ui.text(format!(
+                        "{}x{} {:?}",
+                        environment.width,
+                        environment.height,
+                        environment.format()
+                    ));
+                    Slider::new(im_str!("Exposure"))
+                        .range(-8.0..=8.0)
+                        .build(&ui, &mut inspector_params.exposure);
+                    ui.checkbox(im_str!("Tone mapping"), &mut inspector_params.tone_mapping);
+                    inspector_dirty |= params != inspector_params;
+                    let size = [inspector_size[0] as f32, inspector_size[1] as f32];
+                    Image::new(inspector_id, size).build(&ui);
+                });
+
+            Window::new(im_str!("Sampler"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    let settings = sampler_settings;
+                    ui.text("Normal map and materials");
+                    sampler_settings.ui(&ui);
+                    if ui.button(im_str!("Reset"), [0.0, 0.0]) {
+                        sampler_settings = SamplerSettings::default();
+                    }
+                    ui.same_line(0.0);
+                    ui.text(format!("{} samplers cached", samplers.len()));
+                    sampler_dirty |= settings != sampler_settings;
+                });
+
+            Window::new(im_str!("Image filter"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    let params = filter_params;
+                    filter_params.ui(&ui);
+                    filter_dirty |= params != filter_params;
+
+                    Image::new(filter_source_id, filter_display).build(&ui);
+                    ui.same_line(0.0);
+                    Image::new(filter_output_id, filter_display).build(&ui);
+                });
+
+            if !dropped_images.is_empty() {
+                Window::new(im_str!("Dropped images"))
+                    .always_auto_resize(true)
+                    .build(&ui, || {
+                        for (name, id, size, _) in &dropped_images {
+                            ui.text(name);
+                            Image::new(*id, *size).build(&ui);
+                        }
+                    });
+            }
+            texture_browser.ui(&ui);
+
+            Window::new(im_str!("Rasterization"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    rasterization.ui(&ui);
+                    ui.text(format!("{} pipelines", rasterization_pipelines.len()));
+                    if rasterization != Rasterization::default() {
+                        ui.text_disabled("(opaque objects, without the depth pre-pass or MSAA)");
+                    }
+                });
+
+            Window::new(im_str!("Culling"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    ui.checkbox(im_str!("Instanced field"), &mut show_field);
+                    ui.checkbox(im_str!("GPU culling"), &mut gpu_culling);
+                    ui.text(format!("Instances: {}", field.count()));
+                    ui.text(format!(
+                        "Materials: {} layers of {}x{}, {} linear",
+                        materials.layers, materials.size, materials.size, linear_materials.layers
+                    ));
+                    if show_field && gpu_culling {
+                        verify_culling = ui.button(im_str!("Verify"), [0.0, 0.0]);
+                    }
+                    if culling_expected.is_some() {
+                        ui.same_line(0.0);
+                        ui.text_disabled("reading back");
+                    }
+                    if let Some((gpu, cpu)) = culling_counts {
+                        let color = if gpu == cpu {
+                            [0.3, 1.0, 0.3, 1.0]
+                        } else {
+                            [1.0, 0.3, 0.3, 1.0]
+                        };
+                        ui.text_colored(color, format!("Visible: {} (CPU: {})", gpu, cpu));
+                    }
+                });
+
+            Window::new(im_str!("Depth pre-pass"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    if ui.checkbox(im_str!("Enabled"), &mut depth_prepass) {
+                        gpu_timer.reset();
+                    }
+                    if Slider::new(im_str!("Layers"))
+                        .range(1..=1024)
+                        .build(&ui, &mut layers)
+                    {
+                        gpu_timer.reset();
+                    }
+                    ui.checkbox(im_str!("Measure GPU time"), &mut measure_gpu);
+                    if measure_gpu {
+                        ui.text(format!(
+                            "GPU time: {:.2} ms",
+                            gpu_timer.average().as_secs_f32() * 1000.0
+                        ));
+                    }
+                });
+
+            parallel_encoding.ui(&ui, &mut scene_bundles);
+
+            Window::new(im_str!("Levels of detail"))
+                .always_auto_resize(true)
+                .build(&ui, || {
+                    lod_params.ui(&ui, lods);
+                });
+
+            imgui_platform.prepare_render(&ui, &window);
+            let draw_data = ui.render();
+            // golden frames only contain the scene
+            if golden.is_none() {
+                pass.push_debug_group("imgui");
+                imgui_wgpu
+                    .render(draw_data, queue, device, pass.inner())
+                    .expect("Error rendering imgui");
+                pass.pop_debug_group();
+            }
         }
 
+        frames_in_flight.signal(&mut cmd);
+        drop(encode_scope);
+        let submit_scope = profiler::scope("Submit");
+        let submitted = Instant::now();
         queue.submit(Some(cmd.finish()));
+        frames_in_flight.submitted();
+        frame_ring.submitted();
+        drop(submit_scope);
+        if let Some(frame) = frame {
+            let _scope = profiler::scope("Present");
+            let present_start = Instant::now();
+            context.end_frame(frame);
+            frame_stats.presented(present_start.elapsed(), window.ticks());
+        }
+        let cpu_time = frame_start.elapsed();
+        if measure_gpu {
+            gpu_timer.measure(device);
+            profiler::gpu_zone(submitted, submitted.elapsed());
+        }
+
+        if let (Some(dir), true) = (&golden, golden::FRAMES.contains(&frame_index)) {
+            let pixels = capture.read(device, queue);
+            if !golden::compare(dir, frame_index, width, height, &pixels) {
+                golden_failures += 1;
+            }
+        }
+        if screenshot || supersampled.is_some() {
+            let pixels = match &supersampled {
+                Some(supersampled) => supersampled.read(device, queue),
+                None => capture.read(device, queue),
+            };
+            let secs = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Error reading system time")
+                .as_secs();
+            let path = format!("screenshot_{}.png", secs);
+            match image::save_buffer(&path, &pixels, width, height, image::ColorType::Rgba8) {
+                Ok(()) => info!("Saved screenshot to {}", path),
+                Err(err) => error!("Error saving screenshot to {}: {}", path, err),
+            }
+            screenshot = false;
+            supersampled_screenshot = false;
+        }
+        if let (Some(recorder), true) = (&mut gif_recorder, capture_gif) {
+            let pixels = capture.read(device, queue);
+            recorder.add_frame(Instant::now(), width, height, pixels);
+            if recorder.is_done() {
+                let secs = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Error reading system time")
+                    .as_secs();
+                let path = format!("recording_{}.gif", secs);
+                match std::fs::write(&path, recorder.encode()) {
+                    Ok(()) => info!("Saved recording to {}", path),
+                    Err(err) => error!("Error saving recording to {}: {}", path, err),
+                }
+                gif_recorder = None;
+            }
+        }
+        if reset_nbody {
+            nbody.reset(queue, nbody_params.gravity);
+            reset_nbody = false;
+        }
+        if test_sort {
+            let start = Instant::now();
+            let passed = sort.self_test(device, queue, sort_len);
+            if !passed {
+                warn!("GPU sort of {} keys doesn't match the CPU sort", sort_len);
+            }
+            sort_result = Some((passed, start.elapsed()));
+            test_sort = false;
+        }
+        if test_scan {
+            let start = Instant::now();
+            let passed = scan.self_test(device, queue, scan_len);
+            if !passed {
+                warn!("GPU scan of {} values doesn't match the CPU", scan_len);
+            }
+            scan_result = Some((passed, start.elapsed()));
+            test_scan = false;
+        }
+        frame_index += 1;
+        if golden.is_some() && golden::FRAMES.iter().all(|&frame| frame < frame_index) {
+            break 'main;
+        }
+        // golden frames of a replay are compared until the last one
+        if golden.is_none() && replay.as_ref().is_some_and(|replay| !replay.is_playing()) {
+            info!("Replay finished after {} frames", frame_index);
+            break 'main;
+        }
+
+        if restart || switch_adapter {
+            let mut args = args.clone();
+            if restart {
+                args.trace = Some(trace_dir.to_str().into());
+            }
+            if switch_adapter {
+                args.adapter = Some(selected_adapter);
+            }
+            // the scene carries over to the next run
+            let scene = SceneFile::new(
+                &scene.objects,
+                &lights,
+                camera.pose(frustum.fov_y),
+                &sources,
+            );
+            // run again once the settings are saved
+            restart_with = Some(Restart { args, scene });
+            break 'main;
+        }
+        if reset_settings {
+            // the layout of the imgui windows is reset on the next run
+            settings = Settings::default();
+            camera = settings.camera.clone();
+            frustum.fov_y = settings.fov_y;
+            bookmarks = Bookmarks::new(Vec::new());
+            font_path.clear();
+            style_dirty = true;
+            font_dirty = true;
+            present_mode = settings.present_mode;
+            present_mode_dirty = true;
+            post_params.antialiasing = settings.antialiasing;
+            // resized like any other time, once the event arrives
+            window.set_size(WIDTH as _, HEIGHT as _);
+            window.center();
+            reset_settings = false;
+            reset_layout = true;
+        }
+
+        if let Some(bench) = &mut bench {
+            bench.record(cpu_time, gpu_timer.last().filter(|_| measure_gpu));
+            if bench.finished() {
+                break 'main;
+            }
+        }
 
         //std::thread::sleep(std::time::Duration::new(0, 1_000_000_000 / 60));
     }
+    // before exiting on golden failures
+    drop(recorder);
+
+    if persist_settings {
+        settings.imgui_ini.clear();
+        if !reset_layout {
+            imgui.save_ini_settings(&mut settings.imgui_ini);
+        }
+        settings.window_position = Some(window.position());
+        settings.window_size = Some(window.size());
+        settings.present_mode = present_mode;
+        settings.antialiasing = post_params.antialiasing;
+        settings.camera = camera;
+        settings.fov_y = frustum.fov_y;
+        settings.bookmarks = bookmarks.list;
+        settings.save(SETTINGS_PATH);
+    }
+    if let Some(bench) = bench {
+        let report = bench.report();
+        print!("{}", report);
+        if let Err(err) = std::fs::write(bench::OUTPUT_PATH, report) {
+            error!(
+                "Error saving benchmark results to {}: {}",
+                bench::OUTPUT_PATH,
+                err
+            );
+        }
+    }
+    if golden_failures > 0 {
+        error!("{} frames differ from their golden images", golden_failures);
+        std::process::exit(1);
+    }
+    restart_with
+}
+
+/// Adds previews of the layers of `layers` that don't have one yet.
+fn add_thumbnails(
+    device: &Device,
+    queue: &Queue,
+    renderer: &mut imgui_wgpu::Renderer,
+    browser: &mut TextureBrowser,
+    layers: &MaterialArrayBuilder,
+    thumbnails: &mut Vec<(TextureId, Tracked<()>)>,
+    name: &str,
+) {
+    for layer in thumbnails.len() as u32..layers.len() {
+        let label = format!("{} layer {}", name, layer);
+        let config = imgui_wgpu::TextureConfig {
+            label: Some(&label),
+            size: Extent3d {
+                width: THUMBNAIL_SIZE,
+                height: THUMBNAIL_SIZE,
+                depth: 1,
+            },
+            format: Some(layers.format()),
+            usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+            ..Default::default()
+        };
+        let info = TextureInfo::of(&config);
+        let texture = imgui_wgpu::Texture::new(device, renderer, config);
+        let pixels = layers.thumbnail(layer);
+        texture.write(queue, &pixels, THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+        let bytes = pixels.len() as u64;
+        let memory = memory::track((), Category::Textures, Some(&label), bytes);
+        let id = renderer.textures.insert(texture);
+        browser.register(&label, id, info);
+        thumbnails.push((id, memory));
+    }
+}
+
+/// Duration of the longest clip being played.
+fn timeline_length(animated: &[Animated]) -> f32 {
+    animated
+        .iter()
+        .filter_map(|animated| animated.player.clip(&animated.skeleton))
+        .map(|clip| clip.duration)
+        .fold(0.0, f32::max)
+}
+
+/// Size of `window` in window coordinates, which the mouse is in.
+fn window_size_f32(window: &WindowProxy) -> (f32, f32) {
+    let (width, height) = window.size();
+    (width as f32, height as f32)
+}
+
+/// Depth buffer of the main viewport, for a `width`x`height` frame.
+fn create_depth(device: &Device, width: u32, height: u32) -> (Tracked<Texture>, TextureView) {
+    let depth = memory::create_texture(
+        device,
+        Category::RenderTargets,
+        &TextureDescriptor {
+            label: Some("Depth buffer"),
+            size: Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            // sampled by depth of field
+            usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::SAMPLED,
+        },
+    );
+    let view = depth.create_view(&TextureViewDescriptor::default());
+    (depth, view)
+}
+
+/// Depth buffer of the second viewport, for a `width`x`height` frame.
+fn create_viewport_depth(
+    device: &Device,
+    width: u32,
+    height: u32,
+) -> (Tracked<Texture>, TextureView) {
+    let depth = memory::create_texture(
+        device,
+        Category::RenderTargets,
+        &TextureDescriptor {
+            label: Some("Second viewport depth buffer"),
+            size: Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: TextureUsage::OUTPUT_ATTACHMENT,
+        },
+    );
+    let view = depth.create_view(&TextureViewDescriptor::default());
+    (depth, view)
 }
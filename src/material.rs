@@ -0,0 +1,384 @@
+//! Material texture arrays.
+//!
+//! Albedos of any size are resampled to a common size and stacked into the
+//! layers of a single array texture, so instances with different materials
+//! can be drawn with the same bind group, selecting their layer by index.
+//!
+//! Textures holding colors go into an sRGB array and textures holding data
+//! (roughness, normals...) into a linear one, so only colors are decoded
+//! when sampled.
+use crate::{
+    assets::{self, Asset, AssetError},
+    capabilities::Capabilities,
+    memory::{self, Category, Tracked},
+    profiler, texture,
+};
+use image::{imageops::FilterType, RgbaImage};
+use imgui::{im_str, ColorEdit, ComboBox, ImString, Image, Slider, TextureId, Ui};
+use log::warn;
+use std::path::Path;
+use wgpu::{
+    Device, Extent3d, Origin3d, Queue, Texture, TextureCopyView, TextureDataLayout,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsage, TextureView,
+    TextureViewDescriptor, TextureViewDimension,
+};
+
+/// Format of the array of colors, albedos are sRGB encoded.
+pub const MATERIAL_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// Format of the array of non-color data.
+pub const LINEAR_MATERIAL_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// Layer index of textures that aren't there, as in the shaders.
+pub const NO_MATERIAL: u32 = u32::MAX;
+
+/// Size of the layer previews of the material inspector.
+pub const THUMBNAIL_SIZE: u32 = 64;
+
+/// Texture slots of a material, as listed by the inspector.
+pub const TEXTURE_SLOTS: [TextureSlot; 5] = [
+    TextureSlot::BaseColor,
+    TextureSlot::MetallicRoughness,
+    TextureSlot::Normal,
+    TextureSlot::Emissive,
+    TextureSlot::Occlusion,
+];
+
+/// How the alpha of the base color is used.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AlphaMode {
+    /// Alpha is ignored.
+    Opaque,
+    /// Fragments with alpha below the cutoff are discarded.
+    Mask(f32),
+    /// Alpha blended over what's behind.
+    Blend,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextureSlot {
+    BaseColor,
+    MetallicRoughness,
+    Normal,
+    Emissive,
+    Occlusion,
+}
+
+impl TextureSlot {
+    pub fn name(self) -> &'static str {
+        match self {
+            TextureSlot::BaseColor => "Base color",
+            TextureSlot::MetallicRoughness => "Metallic-roughness",
+            TextureSlot::Normal => "Normal",
+            TextureSlot::Emissive => "Emissive",
+            TextureSlot::Occlusion => "Occlusion",
+        }
+    }
+
+    /// Whether the slot holds a layer of the sRGB array, or of the linear one.
+    pub fn is_color(self) -> bool {
+        matches!(self, TextureSlot::BaseColor | TextureSlot::Emissive)
+    }
+}
+
+/// Metallic-roughness material, as in glTF.
+///
+/// Textures are layers of the material arrays: base color and emissive of
+/// the sRGB array, the rest of the linear one. Factors multiply the textures,
+/// or are used as is without them.
+#[derive(Clone, Debug)]
+pub struct PbrMaterial {
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+    pub alpha_mode: AlphaMode,
+    /// Back faces are culled if not.
+    pub double_sided: bool,
+    /// Blended objects are drawn with weighted blended transparency if set,
+    /// which doesn't depend on their order, and sorted back to front if not.
+    pub order_independent: bool,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub base_color_texture: Option<u32>,
+    /// Roughness in green and metallic in blue.
+    pub metallic_roughness_texture: Option<u32>,
+    pub normal_texture: Option<u32>,
+    pub emissive_texture: Option<u32>,
+    /// Occlusion in red.
+    pub occlusion_texture: Option<u32>,
+}
+
+impl PbrMaterial {
+    pub fn texture(&self, slot: TextureSlot) -> Option<u32> {
+        match slot {
+            TextureSlot::BaseColor => self.base_color_texture,
+            TextureSlot::MetallicRoughness => self.metallic_roughness_texture,
+            TextureSlot::Normal => self.normal_texture,
+            TextureSlot::Emissive => self.emissive_texture,
+            TextureSlot::Occlusion => self.occlusion_texture,
+        }
+    }
+
+    pub fn texture_mut(&mut self, slot: TextureSlot) -> &mut Option<u32> {
+        match slot {
+            TextureSlot::BaseColor => &mut self.base_color_texture,
+            TextureSlot::MetallicRoughness => &mut self.metallic_roughness_texture,
+            TextureSlot::Normal => &mut self.normal_texture,
+            TextureSlot::Emissive => &mut self.emissive_texture,
+            TextureSlot::Occlusion => &mut self.occlusion_texture,
+        }
+    }
+
+    /// Controls of the factors and the alpha of the material.
+    pub fn ui(&mut self, ui: &Ui) {
+        ColorEdit::new(im_str!("Base color"), &mut self.base_color).build(ui);
+        Slider::new(im_str!("Metallic"))
+            .range(0.0..=1.0)
+            .build(ui, &mut self.metallic);
+        Slider::new(im_str!("Roughness"))
+            .range(0.0..=1.0)
+            .build(ui, &mut self.roughness);
+        ColorEdit::new(im_str!("Emissive"), &mut self.emissive).build(ui);
+        let names = [im_str!("Opaque"), im_str!("Mask"), im_str!("Blend")];
+        let mut index = match self.alpha_mode {
+            AlphaMode::Opaque => 0,
+            AlphaMode::Mask(_) => 1,
+            AlphaMode::Blend => 2,
+        };
+        if ComboBox::new(im_str!("Alpha mode")).build_simple_string(ui, &mut index, &names) {
+            self.alpha_mode = match index {
+                0 => AlphaMode::Opaque,
+                1 => AlphaMode::Mask(0.5),
+                _ => AlphaMode::Blend,
+            };
+        }
+        if let AlphaMode::Mask(cutoff) = &mut self.alpha_mode {
+            Slider::new(im_str!("Alpha cutoff"))
+                .range(0.0..=1.0)
+                .build(ui, cutoff);
+        }
+        if self.alpha_mode == AlphaMode::Blend {
+            ui.checkbox(im_str!("Order independent"), &mut self.order_independent);
+        }
+        ui.checkbox(im_str!("Double sided"), &mut self.double_sided);
+        Slider::new(im_str!("Normal scale"))
+            .range(0.0..=2.0)
+            .build(ui, &mut self.normal_scale);
+        Slider::new(im_str!("Occlusion strength"))
+            .range(0.0..=1.0)
+            .build(ui, &mut self.occlusion_strength);
+    }
+
+    /// Layer of every texture slot, among the `color` and `linear`
+    /// thumbnails of the layers. Returns the slot to load a texture into,
+    /// if asked to.
+    pub fn textures_ui(
+        &mut self,
+        ui: &Ui,
+        color: &[TextureId],
+        linear: &[TextureId],
+    ) -> Option<TextureSlot> {
+        let mut load = None;
+        let size = THUMBNAIL_SIZE as f32;
+        for &slot in TEXTURE_SLOTS.iter() {
+            let id = ui.push_id(slot.name());
+            let thumbnails = if slot.is_color() { color } else { linear };
+            let texture = self.texture_mut(slot);
+            match texture.and_then(|layer| thumbnails.get(layer as usize)) {
+                Some(&thumbnail) => Image::new(thumbnail, [size; 2]).build(ui),
+                None => ui.dummy([size; 2]),
+            }
+            ui.same_line(0.0);
+            ui.group(|| {
+                ui.text(slot.name());
+                // the first entry is no texture
+                let names: Vec<_> = std::iter::once(ImString::new("None"))
+                    .chain((0..thumbnails.len()).map(|i| im_str!("Layer {}", i)))
+                    .collect();
+                let names: Vec<_> = names.iter().collect();
+                let mut index = texture.map_or(0, |layer| layer as usize + 1);
+                if ComboBox::new(im_str!("Layer")).build_simple_string(ui, &mut index, &names) {
+                    *texture = index.checked_sub(1).map(|layer| layer as u32);
+                }
+                if ui.button(im_str!("Load..."), [0.0, 0.0]) {
+                    load = Some(slot);
+                }
+            });
+            id.pop(ui);
+        }
+        load
+    }
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self {
+            base_color: [1.0; 4],
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: [0.0; 3],
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: true,
+            order_independent: false,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            emissive_texture: None,
+            occlusion_texture: None,
+        }
+    }
+}
+
+/// Layers of a material array, before they are uploaded.
+pub struct MaterialArrayBuilder {
+    size: u32,
+    format: TextureFormat,
+    layers: Vec<Vec<u8>>,
+}
+
+impl MaterialArrayBuilder {
+    /// Starts an array of `size`x`size` layers, of RGBA8 `format`.
+    pub fn new(size: u32, format: TextureFormat) -> Self {
+        Self {
+            size,
+            format,
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn len(&self) -> u32 {
+        self.layers.len() as _
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Adds a layer from RGBA8 pixels, resampling them if they aren't the size
+    /// of the array. Returns the index of the layer.
+    pub fn add(&mut self, width: u32, height: u32, pixels: Vec<u8>) -> u32 {
+        let pixels = if width == self.size && height == self.size {
+            pixels
+        } else {
+            let image = RgbaImage::from_raw(width, height, pixels).expect("Error reading pixels");
+            image::imageops::resize(&image, self.size, self.size, FilterType::Triangle).into_raw()
+        };
+        self.layers.push(pixels);
+        self.layers.len() as u32 - 1
+    }
+
+    /// Pixels of `layer`, resampled to `THUMBNAIL_SIZE`.
+    pub fn thumbnail(&self, layer: u32) -> Vec<u8> {
+        let pixels = self.layers[layer as usize].clone();
+        let image =
+            RgbaImage::from_raw(self.size, self.size, pixels).expect("Error reading pixels");
+        let size = THUMBNAIL_SIZE;
+        image::imageops::resize(&image, size, size, FilterType::Triangle).into_raw()
+    }
+
+    /// Adds every image of `dir`, in file name order. Files that aren't images
+    /// are skipped.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), AssetError> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .map_err(AssetError::Io)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        paths.sort();
+        for path in paths {
+            // compressed files are decompressed, layers are all RGBA8
+            match assets::load(&path, &Capabilities::default()) {
+                Ok(Asset::Image {
+                    width,
+                    height,
+                    pixels,
+                }) => {
+                    self.add(width, height, pixels);
+                }
+                Ok(_) => warn!("{} isn't an image", path.display()),
+                Err(err) => warn!("Error loading {}: {}", path.display(), err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Uploads the layers, with a mip chain each. Layers can still be added
+    /// and the array built again.
+    pub fn build(&self, device: &Device, queue: &Queue, label: &str) -> MaterialArray {
+        let _scope = profiler::scope("Build material array");
+        assert!(!self.layers.is_empty(), "Material array without layers");
+        let levels: Vec<_> = self
+            .layers
+            .iter()
+            .map(|layer| texture::mip_chain(self.size, self.size, layer))
+            .collect();
+        let texture = memory::create_texture(
+            device,
+            Category::Textures,
+            &TextureDescriptor {
+                label: Some(label),
+                size: Extent3d {
+                    width: self.size,
+                    height: self.size,
+                    depth: self.layers.len() as _,
+                },
+                mip_level_count: levels[0].len() as _,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: self.format,
+                usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+            },
+        );
+        for (layer, levels) in levels.iter().enumerate() {
+            for (mip, level) in levels.iter().enumerate() {
+                let size = (self.size >> mip).max(1);
+                queue.write_texture(
+                    TextureCopyView {
+                        texture: &texture,
+                        mip_level: mip as _,
+                        origin: Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as _,
+                        },
+                    },
+                    level,
+                    TextureDataLayout {
+                        offset: 0,
+                        bytes_per_row: 4 * size,
+                        rows_per_image: size,
+                    },
+                    Extent3d {
+                        width: size,
+                        height: size,
+                        depth: 1,
+                    },
+                );
+            }
+        }
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        MaterialArray {
+            layers: self.layers.len() as _,
+            size: self.size,
+            _texture: texture,
+            view,
+        }
+    }
+}
+
+pub struct MaterialArray {
+    pub layers: u32,
+    pub size: u32,
+    _texture: Tracked<Texture>,
+    pub view: TextureView,
+}
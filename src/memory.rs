@@ -0,0 +1,187 @@
+//! GPU memory tracking.
+//!
+//! wgpu doesn't report how much memory is in use, so the size of every buffer
+//! and texture is estimated from its descriptor when it's created and recorded
+//! until the returned [`Tracked`] handle is dropped.
+use imgui::{im_str, Ui, Window};
+use std::{
+    collections::BTreeMap,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Buffer, BufferDescriptor, Device, Texture, TextureDescriptor, TextureFormat,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Category {
+    /// Vertex and index buffers.
+    Meshes,
+    Uniforms,
+    /// Storage and indirect buffers.
+    Storage,
+    /// Sampled textures.
+    Textures,
+    /// Textures rendered to.
+    RenderTargets,
+    /// Buffers mapped to move data between the CPU and the GPU.
+    Staging,
+}
+
+impl Category {
+    const ALL: [Category; 6] = [
+        Category::Meshes,
+        Category::Uniforms,
+        Category::Storage,
+        Category::Textures,
+        Category::RenderTargets,
+        Category::Staging,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Category::Meshes => "Meshes",
+            Category::Uniforms => "Uniforms",
+            Category::Storage => "Storage",
+            Category::Textures => "Textures",
+            Category::RenderTargets => "Render targets",
+            Category::Staging => "Staging",
+        }
+    }
+}
+
+/// Number of allocations listed in the panel, largest first.
+const LARGEST_ALLOCATIONS: usize = 10;
+
+struct Allocation {
+    category: Category,
+    label: String,
+    size: u64,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static ALLOCATIONS: Mutex<BTreeMap<u64, Allocation>> = Mutex::new(BTreeMap::new());
+
+/// GPU resource whose memory is recorded for as long as it's alive.
+pub struct Tracked<T> {
+    resource: T,
+    id: u64,
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.resource
+    }
+}
+
+impl<T> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        ALLOCATIONS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Records `size` bytes of memory used by `resource`.
+pub fn track<T>(resource: T, category: Category, label: Option<&str>, size: u64) -> Tracked<T> {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let allocation = Allocation {
+        category,
+        label: label.unwrap_or("Unnamed").to_string(),
+        size,
+    };
+    ALLOCATIONS.lock().unwrap().insert(id, allocation);
+    Tracked { resource, id }
+}
+
+pub fn create_buffer(
+    device: &Device,
+    category: Category,
+    desc: &BufferDescriptor,
+) -> Tracked<Buffer> {
+    track(device.create_buffer(desc), category, desc.label, desc.size)
+}
+
+pub fn create_buffer_init(
+    device: &Device,
+    category: Category,
+    desc: &BufferInitDescriptor,
+) -> Tracked<Buffer> {
+    let size = desc.contents.len() as _;
+    track(device.create_buffer_init(desc), category, desc.label, size)
+}
+
+pub fn create_texture(
+    device: &Device,
+    category: Category,
+    desc: &TextureDescriptor,
+) -> Tracked<Texture> {
+    // array layers (and cube faces) don't shrink with the mip level
+    let size = (0..desc.mip_level_count)
+        .map(|mip| {
+            let width = (desc.size.width >> mip).max(1) as u64;
+            let height = (desc.size.height >> mip).max(1) as u64;
+            width * height * desc.size.depth as u64
+        })
+        .sum::<u64>()
+        * bytes_per_texel(desc.format)
+        * desc.sample_count as u64;
+    track(device.create_texture(desc), category, desc.label, size)
+}
+
+fn bytes_per_texel(format: TextureFormat) -> u64 {
+    match format {
+        TextureFormat::R8Unorm => 1,
+        TextureFormat::Rgba16Float => 8,
+        TextureFormat::Rgba32Float => 16,
+        // all the other formats used by the demo
+        _ => 4,
+    }
+}
+
+fn megabytes(bytes: u64) -> f32 {
+    bytes as f32 / (1024.0 * 1024.0)
+}
+
+/// Displays the memory in use, by category and the largest allocations.
+pub fn window(ui: &Ui) {
+    let allocations = ALLOCATIONS.lock().unwrap();
+    Window::new(im_str!("GPU memory"))
+        .always_auto_resize(true)
+        .build(ui, || {
+            let total: u64 = allocations.values().map(|a| a.size).sum();
+            ui.text(format!(
+                "Total: {:.2} MiB in {} resources",
+                megabytes(total),
+                allocations.len()
+            ));
+            ui.separator();
+            for &category in &Category::ALL {
+                let (count, size) = allocations
+                    .values()
+                    .filter(|a| a.category == category)
+                    .fold((0, 0), |(count, size), a| (count + 1, size + a.size));
+                ui.text(format!(
+                    "{:<16}{:>4} {:>10.2} MiB",
+                    category.name(),
+                    count,
+                    megabytes(size)
+                ));
+            }
+            ui.separator();
+            let mut largest: Vec<_> = allocations.values().collect();
+            largest.sort_by_key(|a| std::cmp::Reverse(a.size));
+            for allocation in largest.iter().take(LARGEST_ALLOCATIONS) {
+                ui.text(format!(
+                    "{:>8.2} MiB  {} ({})",
+                    megabytes(allocation.size),
+                    allocation.label,
+                    allocation.category.name()
+                ));
+            }
+        });
+}
@@ -0,0 +1,330 @@
+use crate::{
+    draw_stats::{self, RenderEncoder},
+    lod,
+    memory::{self, Category, Tracked},
+};
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use std::{fmt::Debug, ops::Range, path::Path};
+use wgpu::{util::BufferInitDescriptor, Buffer, BufferUsage, Device, IndexFormat, Queue};
+
+vertex_struct! {
+    #[repr(C)]
+    #[derive(Clone, Copy, Default, Pod, Zeroable)]
+    pub struct Vertex {
+        pub position: [f32; 3],
+        pub normal: [f32; 3],
+        /// Tangent in `xyz` and the sign of the bitangent in `w`.
+        pub tangent: [f32; 4],
+        pub uv: [f32; 2],
+        pub color: [f32; 3],
+    }
+}
+
+/// Indexed triangle mesh in CPU memory.
+#[derive(Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    /// The demo triangle, facing +Z.
+    pub fn triangle() -> Self {
+        let corner = |position: [f32; 2], color| Vertex {
+            position: [position[0], position[1], 0.0],
+            normal: [0.0, 0.0, 1.0],
+            uv: [position[0] + 0.5, 0.5 - position[1]],
+            color,
+            ..Default::default()
+        };
+        let mut mesh = Self {
+            vertices: vec![
+                corner([-0.5, -0.2887], [1.0, 0.0, 0.0]),
+                corner([0.5, -0.2887], [0.0, 1.0, 0.0]),
+                corner([0.0, 0.5774], [0.0, 0.0, 1.0]),
+            ],
+            indices: vec![0, 1, 2],
+        };
+        mesh.compute_tangents();
+        mesh
+    }
+
+    /// Square on the XZ plane, facing +Y. Texture coordinates repeat once
+    /// per unit.
+    pub fn plane(size: f32) -> Self {
+        let half = size * 0.5;
+        let corner = |x: f32, z: f32| Vertex {
+            position: [x, 0.0, z],
+            normal: [0.0, 1.0, 0.0],
+            uv: [x, z],
+            color: [0.8, 0.8, 0.8],
+            ..Default::default()
+        };
+        let mut mesh = Self {
+            vertices: vec![
+                corner(-half, half),
+                corner(half, half),
+                corner(half, -half),
+                corner(-half, -half),
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+        };
+        mesh.compute_tangents();
+        mesh
+    }
+
+    /// Loads all the models of an OBJ file into a single mesh.
+    ///
+    /// Normals are computed if the file doesn't have them, and tangents are
+    /// always generated from the normals and texture coordinates.
+    pub fn load_obj<P: AsRef<Path> + Debug>(path: P) -> Result<Self, tobj::LoadError> {
+        let (models, _) = tobj::load_obj(path, true)?;
+
+        let mut mesh = Self::default();
+        let mut has_normals = true;
+        for model in models {
+            let obj = model.mesh;
+            let base = mesh.vertices.len() as u32;
+            has_normals &= !obj.normals.is_empty();
+
+            mesh.vertices
+                .extend((0..obj.positions.len() / 3).map(|i| Vertex {
+                    position: [
+                        obj.positions[3 * i],
+                        obj.positions[3 * i + 1],
+                        obj.positions[3 * i + 2],
+                    ],
+                    normal: if obj.normals.is_empty() {
+                        [0.0; 3]
+                    } else {
+                        [
+                            obj.normals[3 * i],
+                            obj.normals[3 * i + 1],
+                            obj.normals[3 * i + 2],
+                        ]
+                    },
+                    // OBJ texture coordinates have the origin at the bottom
+                    uv: if obj.texcoords.is_empty() {
+                        [0.0; 2]
+                    } else {
+                        [obj.texcoords[2 * i], 1.0 - obj.texcoords[2 * i + 1]]
+                    },
+                    color: [1.0; 3],
+                    ..Default::default()
+                }));
+            mesh.indices.extend(obj.indices.iter().map(|i| base + i));
+        }
+
+        if !has_normals {
+            mesh.compute_normals();
+        }
+        mesh.compute_tangents();
+        Ok(mesh)
+    }
+
+    /// Iterates over the object-space positions of every triangle.
+    pub fn triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.indices.chunks_exact(3).map(move |f| {
+            let position = |i: u32| Vec3::from(self.vertices[i as usize].position);
+            [position(f[0]), position(f[1]), position(f[2])]
+        })
+    }
+
+    /// Object-space bounding box, as its minimum and maximum corners.
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        let positions = self.vertices.iter().map(|v| Vec3::from(v.position));
+        let min = positions.clone().fold(Vec3::splat(f32::MAX), Vec3::min);
+        let max = positions.fold(Vec3::splat(f32::MIN), Vec3::max);
+        (min, max)
+    }
+
+    /// Computes smooth vertex normals, weighted by the area of the faces.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vec3::zero(); self.vertices.len()];
+        for (face, [a, b, c]) in self.indices.chunks_exact(3).zip(self.triangles()) {
+            let normal = (b - a).cross(c - a);
+            for &i in face {
+                normals[i as usize] += normal;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = normal.normalize().into();
+        }
+    }
+
+    /// Generates MikkTSpace tangents from the normals and texture coordinates.
+    pub fn compute_tangents(&mut self) {
+        if !mikktspace::generate_tangents(self) {
+            log::warn!("Error generating mesh tangents");
+        }
+    }
+
+    fn vertex(&self, face: usize, vert: usize) -> &Vertex {
+        &self.vertices[self.indices[3 * face + vert] as usize]
+    }
+}
+
+impl mikktspace::Geometry for MeshData {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).position
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).normal
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertex(face, vert).uv
+    }
+
+    // vertices shared between faces get the tangent of the last face, which
+    // is the same as long as the mesh doesn't share vertices across UV seams.
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        let index = self.indices[3 * face + vert] as usize;
+        self.vertices[index].tangent = tangent;
+    }
+}
+
+/// Format of the indices of every `Mesh`.
+pub const INDEX_FORMAT: IndexFormat = IndexFormat::Uint32;
+
+/// Vertex and index buffers of a mesh, with indices of its coarser levels
+/// of detail.
+pub struct Mesh {
+    vertices: Tracked<Buffer>,
+    indices: Tracked<Buffer>,
+    index_count: u32,
+    lods: Vec<(Tracked<Buffer>, u32)>,
+    /// Center and radius, in object space.
+    bounding_sphere: (Vec3, f32),
+}
+
+impl Mesh {
+    pub fn new(device: &Device, name: &str, data: &MeshData) -> Self {
+        let vertices = memory::create_buffer_init(
+            device,
+            Category::Meshes,
+            &BufferInitDescriptor {
+                label: Some(&format!("{} vertices", name)),
+                contents: bytemuck::cast_slice(&data.vertices),
+                // rewritten when the vertices are edited, and read as storage
+                // by shaders pulling them
+                usage: BufferUsage::VERTEX | BufferUsage::STORAGE | BufferUsage::COPY_DST,
+            },
+        );
+        let indices = memory::create_buffer_init(
+            device,
+            Category::Meshes,
+            &BufferInitDescriptor {
+                label: Some(&format!("{} indices", name)),
+                contents: bytemuck::cast_slice(&data.indices),
+                usage: BufferUsage::INDEX,
+            },
+        );
+        let lods = lod::generate(data)
+            .iter()
+            .enumerate()
+            .map(|(i, indices)| {
+                let buffer = memory::create_buffer_init(
+                    device,
+                    Category::Meshes,
+                    &BufferInitDescriptor {
+                        label: Some(&format!("{} LOD {} indices", name, i + 1)),
+                        contents: bytemuck::cast_slice(indices),
+                        usage: BufferUsage::INDEX,
+                    },
+                );
+                (buffer, indices.len() as _)
+            })
+            .collect();
+        let bounding_sphere = if data.vertices.is_empty() {
+            (Vec3::zero(), 0.0)
+        } else {
+            let (min, max) = data.bounds();
+            ((min + max) * 0.5, (max - min).length() * 0.5)
+        };
+        Self {
+            vertices,
+            indices,
+            index_count: data.indices.len() as _,
+            lods,
+            bounding_sphere,
+        }
+    }
+
+    /// Replaces the vertices with those of `data`, which must have as many.
+    pub fn write_vertices(&self, queue: &Queue, data: &MeshData) {
+        let vertices = bytemuck::cast_slice(&data.vertices);
+        draw_stats::write_buffer(queue, &self.vertices, 0, vertices);
+    }
+
+    /// The vertices, for shaders reading them from a storage buffer.
+    pub fn vertex_buffer(&self) -> &Buffer {
+        &self.vertices
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    /// Levels of detail, with the full mesh.
+    pub fn lod_count(&self) -> usize {
+        self.lods.len() + 1
+    }
+
+    /// Indices drawn at `lod`, the coarsest level past the last.
+    pub fn lod_index_count(&self, lod: usize) -> u32 {
+        match lod.min(self.lods.len()) {
+            0 => self.index_count,
+            lod => self.lods[lod - 1].1,
+        }
+    }
+
+    pub fn bounding_sphere(&self) -> (Vec3, f32) {
+        self.bounding_sphere
+    }
+
+    /// Sets the vertices to slot 0 and the indices, for draws other than
+    /// those of `draw`.
+    pub fn bind<'a>(&'a self, pass: &mut impl RenderEncoder<'a>) {
+        pass.set_vertex_buffer(0, self.vertices.slice(..));
+        pass.set_index_buffer(self.indices.slice(..));
+    }
+
+    /// Draws `instances` of the whole mesh.
+    pub fn draw<'a>(&'a self, pass: &mut impl RenderEncoder<'a>, instances: Range<u32>) {
+        self.draw_lod(pass, 0, instances);
+    }
+
+    /// Draws `instances` of the whole mesh, with only the indices set. The
+    /// shader reads the vertices from `vertex_buffer` itself.
+    pub fn draw_pulled<'a>(&'a self, pass: &mut impl RenderEncoder<'a>, instances: Range<u32>) {
+        pass.set_index_buffer(self.indices.slice(..));
+        pass.draw_indexed(0..self.index_count, 0, instances);
+    }
+
+    /// Draws `instances` of the mesh at `lod`, the coarsest level past the
+    /// last.
+    pub fn draw_lod<'a>(
+        &'a self,
+        pass: &mut impl RenderEncoder<'a>,
+        lod: usize,
+        instances: Range<u32>,
+    ) {
+        pass.set_vertex_buffer(0, self.vertices.slice(..));
+        match lod.min(self.lods.len()) {
+            0 => pass.set_index_buffer(self.indices.slice(..)),
+            lod => pass.set_index_buffer(self.lods[lod - 1].0.slice(..)),
+        }
+        pass.draw_indexed(0..self.lod_index_count(lod), 0, instances);
+    }
+}
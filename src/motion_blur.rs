@@ -0,0 +1,215 @@
+//! Motion blur of the camera, from the depth buffer of the scene.
+//!
+//! Every pixel is taken back to the world with the depth it was drawn at,
+//! then projected with the view-projection of the previous frame to find
+//! where it was on screen. The scene is averaged along the way, scaled to
+//! how long the shutter is open. Only the camera blurs the scene this way,
+//! objects moving on their own are taken to stand still.
+use crate::{
+    draw_stats,
+    layout::Layout,
+    memory::{self, Category, Tracked},
+    pass::PassBuilder,
+    ping_pong::PingPong,
+    post::{self, PostFrame},
+    shaders,
+};
+use glam::Mat4;
+use imgui::{im_str, Slider, Ui};
+use wgpu::{
+    util::make_spirv, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferDescriptor, BufferSize, BufferUsage, Color, CommandEncoder, Device, FilterMode,
+    LoadOp, Queue, RenderPipeline, Sampler, SamplerDescriptor, ShaderStage, TextureView,
+};
+
+/// Most samples taken along the motion of every pixel.
+pub const MAX_SAMPLES: u32 = 32;
+
+gpu_struct! {
+    struct MotionBlurUniforms {
+        inverse_view_projection: Mat4,
+        previous_view_projection: Mat4,
+        shutter: f32,
+        samples: u32,
+    }
+}
+
+/// Shutter of the camera, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct MotionBlurParams {
+    pub enabled: bool,
+    /// How long the shutter is open, in degrees of a frame: 360 blurs over
+    /// the whole way from the previous frame.
+    pub shutter_angle: f32,
+    /// Samples along the motion of every pixel, up to `MAX_SAMPLES`.
+    pub samples: u32,
+}
+
+impl Default for MotionBlurParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            shutter_angle: 180.0,
+            samples: 8,
+        }
+    }
+}
+
+impl MotionBlurParams {
+    pub fn ui(&mut self, ui: &Ui) {
+        ui.checkbox(im_str!("Motion blur"), &mut self.enabled);
+        Slider::new(im_str!("Shutter angle"))
+            .range(0.0..=360.0)
+            .build(ui, &mut self.shutter_angle);
+        Slider::new(im_str!("Samples"))
+            .range(2..=MAX_SAMPLES)
+            .build(ui, &mut self.samples);
+    }
+}
+
+pub struct MotionBlurTargets {
+    /// Bind groups of the blur, reading either HDR target.
+    blur: [BindGroup; 2],
+}
+
+pub struct MotionBlur {
+    uniform: Tracked<Buffer>,
+    sampler: Sampler,
+    layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+}
+
+impl MotionBlur {
+    pub fn new(device: &Device) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::MOTION_BLUR_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Motion blur uniforms"),
+                size: MotionBlurUniforms::STD140.size as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Motion blur sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Motion blur bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(MotionBlurUniforms::STD140.size as _),
+                    },
+                    count: None,
+                },
+                post::texture_entry(1),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+                // the depth
+                post::texture_entry(3),
+            ],
+        });
+        let pipeline = post::fullscreen_pipeline(
+            device,
+            "Motion blur pipeline",
+            &[&layout],
+            &vert_module,
+            &frag_module,
+            post::HDR_FORMAT,
+            BlendDescriptor::REPLACE,
+        );
+
+        Self {
+            uniform,
+            sampler,
+            layout,
+            pipeline,
+        }
+    }
+
+    /// Targets for the scene drawn into `hdr`, with `depth` as the view of
+    /// its depth buffer.
+    pub fn targets(
+        &self,
+        device: &Device,
+        hdr: &PingPong,
+        depth: &TextureView,
+    ) -> MotionBlurTargets {
+        let blur = hdr.directions(|source, _| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Motion blur bind group"),
+                layout: &self.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(self.uniform.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(depth),
+                    },
+                ],
+            })
+        });
+        MotionBlurTargets { blur }
+    }
+
+    /// Records the scene last written to `hdr` with motion blur into the
+    /// other target.
+    pub fn run(
+        &self,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        params: &MotionBlurParams,
+        frame: PostFrame,
+        targets: &MotionBlurTargets,
+        hdr: &PingPong,
+    ) {
+        let uniforms = MotionBlurUniforms {
+            inverse_view_projection: frame.view_projection.inverse(),
+            previous_view_projection: frame.previous_view_projection,
+            shutter: params.shutter_angle / 360.0,
+            samples: params.samples.clamp(1, MAX_SAMPLES),
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, &uniforms.std140_bytes());
+
+        let mut pass = PassBuilder::new()
+            .color(
+                hdr.write(),
+                post::HDR_FORMAT,
+                LoadOp::Clear(Color::BLACK),
+                true,
+            )
+            .begin(encoder);
+        pass.push_debug_group("Motion blur");
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, hdr.current(&targets.blur), &[]);
+        pass.draw(0..3, 0..1);
+        pass.pop_debug_group();
+    }
+}
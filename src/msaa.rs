@@ -0,0 +1,72 @@
+//! Multisampled targets of the opaque objects of the scene, for comparing
+//! MSAA against the antialiasing done by the effects.
+//!
+//! Only the color is resolved, into the HDR target. The effects sample the
+//! depth buffer, which can't be multisampled, so the depth pre-pass fills the
+//! single sampled one and the multisampled one is thrown away after the pass.
+use crate::memory::{self, Category, Tracked};
+use wgpu::{
+    Device, Extent3d, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsage,
+    TextureView, TextureViewDescriptor,
+};
+
+/// Samples per pixel, the count wgpu supports for every render target format.
+pub const SAMPLE_COUNT: u32 = 4;
+
+pub struct MsaaTargets {
+    _color: Tracked<Texture>,
+    color_view: TextureView,
+    _depth: Tracked<Texture>,
+    depth_view: TextureView,
+}
+
+impl MsaaTargets {
+    /// Targets for a `width`x`height` scene, of the same formats as the
+    /// single sampled ones.
+    pub fn new(
+        device: &Device,
+        width: u32,
+        height: u32,
+        color_format: TextureFormat,
+        depth_format: TextureFormat,
+    ) -> Self {
+        let target = |label, format| {
+            let texture = memory::create_texture(
+                device,
+                Category::RenderTargets,
+                &TextureDescriptor {
+                    label: Some(label),
+                    size: Extent3d {
+                        width,
+                        height,
+                        depth: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: SAMPLE_COUNT,
+                    dimension: TextureDimension::D2,
+                    format,
+                    usage: TextureUsage::OUTPUT_ATTACHMENT,
+                },
+            );
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            (texture, view)
+        };
+        let (color, color_view) = target("Multisampled color", color_format);
+        let (depth, depth_view) = target("Multisampled depth buffer", depth_format);
+        Self {
+            _color: color,
+            color_view,
+            _depth: depth,
+            depth_view,
+        }
+    }
+
+    /// Drawn into, then resolved into the HDR target.
+    pub fn color(&self) -> &TextureView {
+        &self.color_view
+    }
+
+    pub fn depth(&self) -> &TextureView {
+        &self.depth_view
+    }
+}
@@ -0,0 +1,372 @@
+//! Gravitational N-body simulation.
+//!
+//! Every particle is attracted by all the others in a compute pass, which
+//! reads the particles of the last step from one storage buffer and writes the
+//! next step into another. The latest buffer is then drawn as instanced
+//! camera facing billboards.
+use crate::{
+    draw_stats::{self, CountedPass},
+    memory::{self, Category, Tracked},
+    shaders,
+    vertex::VertexLayout,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use imgui::{im_str, Slider, Ui};
+use wgpu::{
+    util::{make_spirv, BufferInitDescriptor},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor, BlendFactor,
+    BlendOperation, Buffer, BufferDescriptor, BufferSize, BufferUsage, ColorStateDescriptor,
+    ColorWrite, CommandEncoder, ComputePipeline, ComputePipelineDescriptor, Device, IndexFormat,
+    InputStepMode, PipelineLayoutDescriptor, PrimitiveTopology, ProgrammableStageDescriptor, Queue,
+    RenderPipeline, RenderPipelineDescriptor, ShaderStage, TextureFormat, VertexStateDescriptor,
+};
+
+/// Must match the `local_size_x` of `nbody.comp`.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Particles allocated, the simulated ones are the first `NBodyParams::count`.
+pub const MAX_PARTICLES: u32 = 16384;
+
+/// Radius of the initial disc of particles.
+const DISC_RADIUS: f32 = 4.0;
+
+vertex_struct! {
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    struct Particle {
+        position: [f32; 4],
+        velocity: [f32; 4],
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Simulation {
+    count: u32,
+    dt: f32,
+    gravity: f32,
+    softening: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct ParticleUniforms {
+    view_projection: [[f32; 4]; 4],
+    camera_right: [f32; 4],
+    camera_up: [f32; 4],
+    size: f32,
+    _pad: [f32; 3],
+}
+
+/// Parameters of the simulation, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct NBodyParams {
+    /// Number of simulated particles, up to `MAX_PARTICLES`.
+    pub count: u32,
+    /// Simulated time per step, in seconds.
+    pub dt: f32,
+    pub gravity: f32,
+    /// Keeps the force bounded when two particles get close.
+    pub softening: f32,
+    /// Radius of the billboards.
+    pub size: f32,
+    pub paused: bool,
+}
+
+impl Default for NBodyParams {
+    fn default() -> Self {
+        Self {
+            count: 4096,
+            dt: 0.005,
+            gravity: 1.0,
+            softening: 0.1,
+            size: 0.02,
+            paused: false,
+        }
+    }
+}
+
+impl NBodyParams {
+    pub fn ui(&mut self, ui: &Ui) {
+        Slider::new(im_str!("Particles"))
+            .range(64..=MAX_PARTICLES)
+            .build(ui, &mut self.count);
+        Slider::new(im_str!("Timestep"))
+            .range(0.0..=0.02)
+            .build(ui, &mut self.dt);
+        Slider::new(im_str!("Gravity"))
+            .range(0.0..=4.0)
+            .build(ui, &mut self.gravity);
+        Slider::new(im_str!("Softening"))
+            .range(0.01..=0.5)
+            .build(ui, &mut self.softening);
+        Slider::new(im_str!("Size"))
+            .range(0.005..=0.1)
+            .build(ui, &mut self.size);
+        ui.checkbox(im_str!("Paused"), &mut self.paused);
+    }
+}
+
+pub struct NBody {
+    simulation: Tracked<Buffer>,
+    uniform: Tracked<Buffer>,
+    /// Particles of the last two steps.
+    particles: [Tracked<Buffer>; 2],
+    /// Bind group reading from `particles[i]` and writing to the other one.
+    step_bind_groups: [BindGroup; 2],
+    /// Index of the buffer with the latest step.
+    current: usize,
+    step_pipeline: ComputePipeline,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+}
+
+impl NBody {
+    pub fn new(device: &Device, format: TextureFormat, gravity: f32) -> Self {
+        let comp_module = device.create_shader_module(make_spirv(shaders::NBODY_COMP));
+        let vert_module = device.create_shader_module(make_spirv(shaders::NBODY_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::NBODY_FRAG));
+
+        let simulation = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("N-body simulation uniforms"),
+                size: std::mem::size_of::<Simulation>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("N-body particle uniforms"),
+                size: std::mem::size_of::<ParticleUniforms>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let initial = disc(gravity);
+        let particles = [0, 1].map(|i| {
+            memory::create_buffer_init(
+                device,
+                Category::Storage,
+                &BufferInitDescriptor {
+                    label: Some(&format!("N-body particles {}", i)),
+                    contents: bytemuck::cast_slice(&initial),
+                    usage: BufferUsage::VERTEX | BufferUsage::STORAGE | BufferUsage::COPY_DST,
+                },
+            )
+        });
+
+        let storage = |binding, readonly| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStage::COMPUTE,
+            ty: BindingType::StorageBuffer {
+                dynamic: false,
+                min_binding_size: None,
+                readonly,
+            },
+            count: None,
+        };
+        let step_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("N-body step bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(std::mem::size_of::<Simulation>() as _),
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, false),
+            ],
+        });
+        let step_bind_groups = [0, 1].map(|i| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some(&format!("N-body step bind group {}", i)),
+                layout: &step_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(simulation.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Buffer(particles[i].slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Buffer(particles[1 - i].slice(..)),
+                    },
+                ],
+            })
+        });
+        let step_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("N-body step pipeline layout"),
+            bind_group_layouts: &[&step_layout],
+            push_constant_ranges: &[],
+        });
+        let step_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("N-body step pipeline"),
+            layout: Some(&step_pipeline_layout),
+            compute_stage: ProgrammableStageDescriptor {
+                module: &comp_module,
+                entry_point: "main",
+            },
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("N-body bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::VERTEX,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: BufferSize::new(std::mem::size_of::<ParticleUniforms>() as _),
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("N-body bind group"),
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(uniform.slice(..)),
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("N-body pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        // particles glow, so they are added up instead of depth tested
+        let additive = BlendDescriptor {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("N-body pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex_stage: ProgrammableStageDescriptor {
+                module: &vert_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(ProgrammableStageDescriptor {
+                module: &frag_module,
+                entry_point: "main",
+            }),
+            rasterization_state: None,
+            primitive_topology: PrimitiveTopology::TriangleList,
+            color_states: &[ColorStateDescriptor {
+                format,
+                alpha_blend: additive.clone(),
+                color_blend: additive,
+                write_mask: ColorWrite::ALL,
+            }],
+            depth_stencil_state: None,
+            vertex_state: VertexStateDescriptor {
+                index_format: IndexFormat::Uint16,
+                vertex_buffers: &[Particle::buffer_descriptor(InputStepMode::Instance)],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        Self {
+            simulation,
+            uniform,
+            particles,
+            step_bind_groups,
+            current: 0,
+            step_pipeline,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    /// Puts all the particles back into their initial disc.
+    pub fn reset(&self, queue: &Queue, gravity: f32) {
+        let initial = disc(gravity);
+        for particles in &self.particles {
+            draw_stats::write_buffer(queue, particles, 0, bytemuck::cast_slice(&initial));
+        }
+    }
+
+    /// Uploads the simulation parameters and the camera.
+    pub fn update(&self, queue: &Queue, params: &NBodyParams, view_projection: Mat4, view: Mat4) {
+        let simulation = Simulation {
+            count: params.count,
+            dt: params.dt,
+            gravity: params.gravity,
+            softening: params.softening,
+        };
+        // the rows of the view rotation are the camera axes in world space
+        let rotation = view.transpose();
+        let uniforms = ParticleUniforms {
+            view_projection: view_projection.to_cols_array_2d(),
+            camera_right: rotation.x_axis.truncate().extend(0.0).into(),
+            camera_up: rotation.y_axis.truncate().extend(0.0).into(),
+            size: params.size,
+            _pad: [0.0; 3],
+        };
+        draw_stats::write_buffer(queue, &self.simulation, 0, bytemuck::bytes_of(&simulation));
+        draw_stats::write_buffer(queue, &self.uniform, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Records a simulation step of `count` particles.
+    pub fn step(&mut self, encoder: &mut CommandEncoder, count: u32) {
+        let mut pass = encoder.begin_compute_pass();
+        pass.push_debug_group("N-body step");
+        pass.set_pipeline(&self.step_pipeline);
+        pass.set_bind_group(0, &self.step_bind_groups[self.current], &[]);
+        pass.dispatch(count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        pass.pop_debug_group();
+        self.current = 1 - self.current;
+    }
+
+    /// Draws the first `count` particles of the latest step.
+    pub fn draw<'a>(&'a self, pass: &mut CountedPass<'a>, count: u32) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.particles[self.current].slice(..));
+        pass.draw(0..6, 0..count);
+    }
+}
+
+/// Particles in a thin disc, orbiting its center at the speed that balances the
+/// mass closer to the center (assuming it's spread evenly over the disc).
+fn disc(gravity: f32) -> Vec<Particle> {
+    // xorshift, there's no need for anything better here
+    let mut state = 0x9e37_79b9u32;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32
+    };
+    (0..MAX_PARTICLES)
+        .map(|_| {
+            let enclosed = random().max(0.01);
+            let radius = DISC_RADIUS * enclosed.sqrt();
+            let (sin, cos) = (random() * std::f32::consts::TAU).sin_cos();
+            let height = (random() - 0.5) * 0.1;
+            let position = Vec3::new(radius * cos, height, radius * sin);
+            let tangent = Vec3::new(-sin, 0.0, cos);
+            let velocity = tangent * (gravity * enclosed / radius).sqrt();
+            Particle {
+                position: position.extend(1.0).into(),
+                velocity: velocity.extend(0.0).into(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,215 @@
+//! Weighted blended order independent transparency.
+//!
+//! Transparent fragments are added up in any order into two targets: their
+//! premultiplied colors, weighted to favour those close to the camera, and
+//! the product of their transparencies (the revealage). A fullscreen pass
+//! then blends the weighted average of the colors over the scene, by how
+//! much of it they cover. Intersecting objects blend where sorting them
+//! can't, though layers of close colors are only approximately in order.
+use crate::{
+    draw_stats::CountedPass,
+    memory::{self, Category, Tracked},
+    pass::PassBuilder,
+    post, shaders,
+};
+use wgpu::{
+    util::make_spirv, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    BlendFactor, BlendOperation, Color, ColorStateDescriptor, ColorWrite, CommandEncoder, Device,
+    Extent3d, LoadOp, RenderPipeline, Sampler, SamplerDescriptor, ShaderStage, Texture,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsage, TextureView,
+    TextureViewDescriptor,
+};
+
+/// Format of the sum of the weighted colors, with the sum of the weights.
+pub const ACCUMULATION_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+/// Format of the product of the transparencies.
+pub const REVEALAGE_FORMAT: TextureFormat = TextureFormat::R16Float;
+
+pub struct OitTargets {
+    _textures: [Tracked<Texture>; 2],
+    accumulation: TextureView,
+    revealage: TextureView,
+    bind_group: BindGroup,
+}
+
+pub struct Oit {
+    sampler: Sampler,
+    layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+}
+
+impl Oit {
+    pub fn new(device: &Device) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::OIT_COMPOSITE_FRAG));
+        // texels are fetched, it's never filtered
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("OIT sampler"),
+            ..Default::default()
+        });
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("OIT bind group layout"),
+            entries: &[
+                post::texture_entry(0),
+                post::texture_entry(1),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline = post::fullscreen_pipeline(
+            device,
+            "OIT composite pipeline",
+            &[&layout],
+            &vert_module,
+            &frag_module,
+            post::HDR_FORMAT,
+            BlendDescriptor {
+                src_factor: BlendFactor::SrcAlpha,
+                dst_factor: BlendFactor::OneMinusSrcAlpha,
+                operation: BlendOperation::Add,
+            },
+        );
+
+        Self {
+            sampler,
+            layout,
+            pipeline,
+        }
+    }
+
+    /// Color states of the pipelines drawing transparent objects into the
+    /// targets: colors and weights are added up, and the revealage is
+    /// multiplied by one minus the alpha of each fragment.
+    pub fn color_states() -> [ColorStateDescriptor; 2] {
+        let additive = BlendDescriptor {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        [
+            ColorStateDescriptor {
+                format: ACCUMULATION_FORMAT,
+                color_blend: additive.clone(),
+                alpha_blend: additive,
+                write_mask: ColorWrite::ALL,
+            },
+            ColorStateDescriptor {
+                format: REVEALAGE_FORMAT,
+                color_blend: BlendDescriptor {
+                    src_factor: BlendFactor::Zero,
+                    dst_factor: BlendFactor::OneMinusSrcColor,
+                    operation: BlendOperation::Add,
+                },
+                alpha_blend: BlendDescriptor::REPLACE,
+                write_mask: ColorWrite::ALL,
+            },
+        ]
+    }
+
+    /// Targets for a `width` by `height` scene.
+    pub fn targets(&self, device: &Device, width: u32, height: u32) -> OitTargets {
+        let create_target = |label, format| {
+            let texture = memory::create_texture(
+                device,
+                Category::RenderTargets,
+                &TextureDescriptor {
+                    label: Some(label),
+                    size: Extent3d {
+                        width,
+                        height,
+                        depth: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format,
+                    usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::SAMPLED,
+                },
+            );
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            (texture, view)
+        };
+        let (accumulation_texture, accumulation) =
+            create_target("OIT accumulation", ACCUMULATION_FORMAT);
+        let (revealage_texture, revealage) = create_target("OIT revealage", REVEALAGE_FORMAT);
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("OIT bind group"),
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&accumulation),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&revealage),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        OitTargets {
+            _textures: [accumulation_texture, revealage_texture],
+            accumulation,
+            revealage,
+            bind_group,
+        }
+    }
+
+    /// Begins the pass drawing transparent objects into `targets`, tested
+    /// against the depth buffer of the opaque scene, `depth`. Its stencil is
+    /// written to, so they are outlined like the rest.
+    pub fn begin<'a>(
+        &self,
+        encoder: &'a mut CommandEncoder,
+        targets: &'a OitTargets,
+        depth: &'a TextureView,
+        depth_format: TextureFormat,
+    ) -> CountedPass<'a> {
+        PassBuilder::new()
+            .color(
+                &targets.accumulation,
+                ACCUMULATION_FORMAT,
+                LoadOp::Clear(Color::TRANSPARENT),
+                true,
+            )
+            .color(
+                &targets.revealage,
+                REVEALAGE_FORMAT,
+                LoadOp::Clear(Color::WHITE),
+                true,
+            )
+            .depth(depth, depth_format, LoadOp::Load, true)
+            .stencil(LoadOp::Load, true)
+            .and_then(|pass| {
+                pass.targets(&[ACCUMULATION_FORMAT, REVEALAGE_FORMAT], Some(depth_format))
+            })
+            .expect("Error beginning the transparency pass")
+            .begin(encoder)
+    }
+
+    /// Records the transparent objects drawn into `targets` blended over the
+    /// scene in `target`.
+    pub fn composite(
+        &self,
+        encoder: &mut CommandEncoder,
+        targets: &OitTargets,
+        target: &TextureView,
+    ) {
+        let mut pass = PassBuilder::new()
+            .color(target, post::HDR_FORMAT, LoadOp::Load, true)
+            .begin(encoder);
+        pass.push_debug_group("OIT composite");
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &targets.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+        pass.pop_debug_group();
+    }
+}
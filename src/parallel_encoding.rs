@@ -0,0 +1,140 @@
+//! Records the draws of the scene on several threads.
+//!
+//! The list of draws is split into chunks, and each of them is recorded into a
+//! render bundle on a thread of the rayon pool. The bundles are executed in
+//! the order of the chunks, so the result is the same as recording the list in
+//! the pass. Bundles start with nothing set, so every chunk sets everything it
+//! draws with, and the pass has to set it again after them.
+//!
+//! How long recording takes each way is kept, along with executing the
+//! bundles kept by [`SceneBundles`](crate::scene_bundles::SceneBundles). Only
+//! recording into bundles includes encoding the draws: wgpu validates and
+//! encodes a pass when it ends, so drawing in the pass only queues them.
+use crate::{
+    draw_stats::{CountedBundle, FinishedBundle},
+    latency::Samples,
+    scene_bundles::SceneBundles,
+};
+use imgui::{im_str, Slider, Ui, Window};
+use rayon::prelude::*;
+use std::time::Duration;
+use wgpu::{Device, RenderBundleEncoderDescriptor, TextureFormat};
+
+pub struct ParallelEncoding {
+    pub enabled: bool,
+    /// Draws recorded into every bundle.
+    pub chunk_size: usize,
+    /// Milliseconds spent queuing the draws in the pass, without encoding
+    /// them, which happens once the pass ends along with the rest of it.
+    pub serial: Samples,
+    /// Milliseconds spent recording the draws into bundles and executing
+    /// them.
+    pub parallel: Samples,
+    /// Milliseconds spent executing bundles kept between frames, recording
+    /// them when they change.
+    pub cached: Samples,
+}
+
+impl Default for ParallelEncoding {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            chunk_size: 64,
+            serial: Samples::default(),
+            parallel: Samples::default(),
+            cached: Samples::default(),
+        }
+    }
+}
+
+impl ParallelEncoding {
+    /// Records `draws` into bundles for single sampled passes into `format`
+    /// and `depth_format`, calling `record` on every chunk from any thread.
+    /// Everything goes into a single bundle if it isn't `enabled`.
+    pub fn record<'a, F>(
+        &self,
+        device: &'a Device,
+        format: TextureFormat,
+        depth_format: TextureFormat,
+        draws: &[usize],
+        record: F,
+    ) -> Vec<FinishedBundle>
+    where
+        F: Fn(&mut CountedBundle<'a>, &[usize]) + Sync,
+    {
+        let desc = RenderBundleEncoderDescriptor {
+            label: Some("Scene draws"),
+            color_formats: &[format],
+            depth_stencil_format: Some(depth_format),
+            sample_count: 1,
+        };
+        let chunk_size = if self.enabled {
+            self.chunk_size
+        } else {
+            draws.len()
+        };
+        draws
+            .par_chunks(chunk_size.max(1))
+            .map(|chunk| {
+                let mut bundle = CountedBundle::new(device, &desc);
+                record(&mut bundle, chunk);
+                bundle.finish(Some("Scene draws"))
+            })
+            .collect()
+    }
+
+    /// Adds how long recording took this frame, the way it's `enabled`, or
+    /// with bundles that were `cached`.
+    pub fn measure(&mut self, elapsed: Duration, cached: bool) {
+        let ms = elapsed.as_secs_f32() * 1000.0;
+        if cached {
+            self.cached.push(ms);
+        } else if self.enabled {
+            self.parallel.push(ms);
+        } else {
+            self.serial.push(ms);
+        }
+    }
+
+    /// Threads the chunks are recorded on.
+    pub fn threads(&self) -> usize {
+        rayon::current_num_threads()
+    }
+
+    /// Window choosing how the draws are recorded, with the times of each
+    /// way, and whether `bundles` are kept between frames.
+    pub fn ui<K>(&mut self, ui: &Ui, bundles: &mut SceneBundles<K>) {
+        Window::new(im_str!("Parallel encoding"))
+            .always_auto_resize(true)
+            .build(ui, || {
+                ui.checkbox(im_str!("Enabled"), &mut self.enabled);
+                ui.same_line(0.0);
+                ui.text_disabled(format!("({} threads)", self.threads()));
+                let mut chunk_size = self.chunk_size as u32;
+                if Slider::new(im_str!("Draws per bundle"))
+                    .range(1..=1024)
+                    .build(ui, &mut chunk_size)
+                {
+                    self.chunk_size = chunk_size as usize;
+                }
+                let stat = |label, samples: &Samples| {
+                    ui.text(format!(
+                        "{}: {:.3} ms (max {:.3})",
+                        label,
+                        samples.average(),
+                        samples.max()
+                    ));
+                };
+                stat("Serial (queued, not encoded)", &self.serial);
+                stat("Parallel recording", &self.parallel);
+                stat("Static bundles", &self.cached);
+                ui.text_disabled("Only the opaque objects of the scene pass are split");
+                ui.text_disabled("The pass encodes serial draws once it ends");
+                ui.separator();
+                ui.checkbox(im_str!("Static bundles"), &mut bundles.enabled);
+                ui.same_line(0.0);
+                ui.text_disabled("(kept until what's drawn changes)");
+                ui.text(format!("Recorded {} times", bundles.recordings));
+            });
+    }
+}
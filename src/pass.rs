@@ -0,0 +1,132 @@
+//! Render passes begun from their attachments, instead of spelling out a
+//! `RenderPassDescriptor` for each of them.
+use crate::draw_stats::CountedPass;
+use std::{error::Error, fmt};
+use wgpu::{
+    Color, CommandEncoder, LoadOp, Operations, RenderPassColorAttachmentDescriptor,
+    RenderPassDepthStencilAttachmentDescriptor, RenderPassDescriptor, TextureFormat, TextureView,
+};
+
+#[derive(Debug)]
+pub enum PassError {
+    /// `resolve` before any color attachment.
+    NoColorAttachment,
+    /// `stencil` before the depth attachment.
+    NoDepthAttachment,
+    /// Formats of the attachments, then of the pipeline targets.
+    TargetMismatch {
+        attachments: (Vec<TextureFormat>, Option<TextureFormat>),
+        targets: (Vec<TextureFormat>, Option<TextureFormat>),
+    },
+}
+
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PassError::NoColorAttachment => write!(f, "There's no color attachment to resolve"),
+            PassError::NoDepthAttachment => {
+                write!(f, "There's no depth attachment to set the stencil of")
+            }
+            PassError::TargetMismatch {
+                attachments,
+                targets,
+            } => write!(
+                f,
+                "Pass attachments {:?} don't match the pipeline targets {:?}",
+                attachments, targets
+            ),
+        }
+    }
+}
+
+impl Error for PassError {}
+
+#[derive(Default)]
+pub struct PassBuilder<'a> {
+    colors: Vec<RenderPassColorAttachmentDescriptor<'a>>,
+    color_formats: Vec<TextureFormat>,
+    depth: Option<RenderPassDepthStencilAttachmentDescriptor<'a>>,
+    depth_format: Option<TextureFormat>,
+}
+
+impl<'a> PassBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a color attachment of `format`, not resolved until `resolve`.
+    /// Multisampled targets that are only resolved don't need `store`.
+    pub fn color(
+        mut self,
+        view: &'a TextureView,
+        format: TextureFormat,
+        load: LoadOp<Color>,
+        store: bool,
+    ) -> Self {
+        self.colors.push(RenderPassColorAttachmentDescriptor {
+            attachment: view,
+            resolve_target: None,
+            ops: Operations { load, store },
+        });
+        self.color_formats.push(format);
+        self
+    }
+
+    /// Resolves the last color attachment, which is multisampled, into
+    /// `target` at the end of the pass.
+    pub fn resolve(mut self, target: &'a TextureView) -> Result<Self, PassError> {
+        let color = self.colors.last_mut().ok_or(PassError::NoColorAttachment)?;
+        color.resolve_target = Some(target);
+        Ok(self)
+    }
+
+    /// Sets the depth attachment, with no stencil operations until `stencil`.
+    pub fn depth(
+        mut self,
+        view: &'a TextureView,
+        format: TextureFormat,
+        load: LoadOp<f32>,
+        store: bool,
+    ) -> Self {
+        self.depth = Some(RenderPassDepthStencilAttachmentDescriptor {
+            attachment: view,
+            depth_ops: Some(Operations { load, store }),
+            stencil_ops: None,
+        });
+        self.depth_format = Some(format);
+        self
+    }
+
+    /// Sets the stencil operations of the depth attachment.
+    pub fn stencil(mut self, load: LoadOp<u32>, store: bool) -> Result<Self, PassError> {
+        let depth = self.depth.as_mut().ok_or(PassError::NoDepthAttachment)?;
+        depth.stencil_ops = Some(Operations { load, store });
+        Ok(self)
+    }
+
+    /// Checks the attachments against `colors` and `depth`, the formats the
+    /// pipelines drawn in the pass were created with. wgpu doesn't give the
+    /// formats of a pipeline back, so it's up to the caller to pass the ones
+    /// of its descriptor.
+    pub fn targets(
+        self,
+        colors: &[TextureFormat],
+        depth: Option<TextureFormat>,
+    ) -> Result<Self, PassError> {
+        if self.color_formats != colors || self.depth_format != depth {
+            return Err(PassError::TargetMismatch {
+                attachments: (self.color_formats, self.depth_format),
+                targets: (colors.to_vec(), depth),
+            });
+        }
+        Ok(self)
+    }
+
+    /// Begins the pass, counted in the draw statistics of the frame.
+    pub fn begin(self, encoder: &'a mut CommandEncoder) -> CountedPass<'a> {
+        CountedPass::new(encoder.begin_render_pass(&RenderPassDescriptor {
+            color_attachments: &self.colors,
+            depth_stencil_attachment: self.depth,
+        }))
+    }
+}
@@ -0,0 +1,222 @@
+//! Rigid bodies of the scene objects, simulated with rapier.
+//!
+//! Bodies are created, removed and moved to follow the objects before every
+//! step, so the body of an object can be changed from the UI and objects can
+//! still be moved around. Dynamic bodies then write where they ended up back
+//! into their objects, which don't spin or pulse while they have a body.
+//! Colliders are boxes around the meshes of the objects.
+use crate::{gizmos::Gizmos, mesh::MeshData, scene::Scene};
+use glam::{Mat4, Quat, Vec3};
+use rapier3d::{
+    dynamics::{
+        BodyStatus, IntegrationParameters, JointSet, RigidBodyBuilder, RigidBodyHandle,
+        RigidBodySet,
+    },
+    geometry::{BroadPhase, ColliderBuilder, ColliderSet, NarrowPhase},
+    na::{Isometry3, Quaternion, Translation3, UnitQuaternion, Vector3},
+    pipeline::PhysicsPipeline,
+};
+use serde::{Deserialize, Serialize};
+
+/// Gravity, when enabled, in meters per second squared.
+const GRAVITY: f32 = -9.81;
+
+/// Colliders are at least this thick, or flat meshes would let bodies through.
+const MIN_HALF_EXTENT: f32 = 0.05;
+
+/// Kinds of rigid body selectable from the UI.
+pub const BODY_KINDS: [BodyKind; 3] = [BodyKind::None, BodyKind::Static, BodyKind::Dynamic];
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyKind {
+    /// Not simulated, the object spins and pulses.
+    None,
+    /// Collides, but only moves when the object is moved.
+    Static,
+    /// Falls and collides.
+    Dynamic,
+}
+
+impl BodyKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            BodyKind::None => "None",
+            BodyKind::Static => "Static",
+            BodyKind::Dynamic => "Dynamic",
+        }
+    }
+}
+
+/// Body of an object and where the object was when they were last in sync.
+struct Body {
+    handle: RigidBodyHandle,
+    kind: BodyKind,
+    position: Vec3,
+    rotation: Quat,
+    /// Of the object its collider was made for.
+    scale: Vec3,
+}
+
+pub struct Physics {
+    pipeline: PhysicsPipeline,
+    parameters: IntegrationParameters,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    joints: JointSet,
+    /// Body of every object of the scene, by index.
+    objects: Vec<Option<Body>>,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Physics {
+    pub fn new() -> Self {
+        Self {
+            pipeline: PhysicsPipeline::new(),
+            parameters: IntegrationParameters::default(),
+            broad_phase: BroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            bodies: RigidBodySet::new(),
+            colliders: ColliderSet::new(),
+            joints: JointSet::new(),
+            objects: Vec::new(),
+        }
+    }
+
+    /// Advances the simulation of `scene` by one fixed step of `dt` seconds.
+    pub fn step(&mut self, scene: &mut Scene, meshes: &[MeshData], dt: f32, gravity: bool) {
+        self.sync(scene, meshes);
+        self.parameters.set_dt(dt);
+        let gravity = Vector3::new(0.0, if gravity { GRAVITY } else { 0.0 }, 0.0);
+        self.pipeline.step(
+            &gravity,
+            &self.parameters,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.joints,
+            None,
+            None,
+            &(),
+        );
+
+        let objects = scene.objects.iter_mut().zip(&mut self.objects);
+        for (object, body) in objects.filter_map(|(o, b)| Some(o).zip(b.as_mut())) {
+            if body.kind != BodyKind::Dynamic {
+                continue;
+            }
+            let position = self.bodies[body.handle].position();
+            body.position = vec3(&position.translation.vector);
+            body.rotation = quat(&position.rotation);
+            object.position = body.position;
+            object.rotation = body.rotation;
+        }
+    }
+
+    /// Queues the wireframe of every collider, dynamic ones that are awake
+    /// in green, sleeping ones in grey and static ones in blue.
+    pub fn draw_colliders(&self, gizmos: &mut Gizmos) {
+        for (_, collider) in self.colliders.iter() {
+            let body = &self.bodies[collider.parent()];
+            let color = if body.is_static() {
+                [0.3, 0.5, 1.0, 1.0]
+            } else if body.is_sleeping() {
+                [0.5, 0.5, 0.5, 1.0]
+            } else {
+                [0.3, 1.0, 0.4, 1.0]
+            };
+            let cuboid = match collider.shape().as_cuboid() {
+                Some(cuboid) => cuboid,
+                None => continue,
+            };
+            let position = collider.position();
+            let transform = Mat4::from_rotation_translation(
+                quat(&position.rotation),
+                vec3(&position.translation.vector),
+            );
+            gizmos.cuboid(transform, vec3(&cuboid.half_extents), color);
+        }
+    }
+
+    /// Creates, removes and moves bodies to match the objects of `scene`.
+    fn sync(&mut self, scene: &Scene, meshes: &[MeshData]) {
+        self.objects.resize_with(scene.objects.len(), || None);
+        for (object, body) in scene.objects.iter().zip(&mut self.objects) {
+            match body {
+                // the collider is sized for the scale
+                Some(current) if current.kind != object.body || current.scale != object.scale => {
+                    self.bodies
+                        .remove(current.handle, &mut self.colliders, &mut self.joints);
+                    *body = None;
+                }
+                // moved from the UI
+                Some(current)
+                    if current.position != object.position
+                        || current.rotation != object.rotation =>
+                {
+                    let rigid_body = &mut self.bodies[current.handle];
+                    rigid_body.set_position(isometry(object.position, object.rotation), true);
+                    rigid_body.set_linvel(Vector3::zeros(), true);
+                    rigid_body.set_angvel(Vector3::zeros(), true);
+                    current.position = object.position;
+                    current.rotation = object.rotation;
+                }
+                _ => {}
+            }
+            if body.is_some() || object.body == BodyKind::None {
+                continue;
+            }
+
+            let status = match object.body {
+                BodyKind::Static => BodyStatus::Static,
+                _ => BodyStatus::Dynamic,
+            };
+            let rigid_body = RigidBodyBuilder::new(status)
+                .position(isometry(object.position, object.rotation))
+                .build();
+            let handle = self.bodies.insert(rigid_body);
+            // a box around the mesh as it's drawn, relative to the body
+            let (min, max) = meshes[object.mesh].bounds();
+            let local = Mat4::from_scale(object.scale) * object.local();
+            let (scale, rotation, _) = local.to_scale_rotation_translation();
+            let half_extents = ((max - min) * 0.5 * scale).max(Vec3::splat(MIN_HALF_EXTENT));
+            let center = local.transform_point3((min + max) * 0.5);
+            let collider = ColliderBuilder::cuboid(half_extents.x, half_extents.y, half_extents.z)
+                .position(isometry(center, rotation))
+                .build();
+            self.colliders.insert(collider, handle, &mut self.bodies);
+            *body = Some(Body {
+                handle,
+                kind: object.body,
+                position: object.position,
+                rotation: object.rotation,
+                scale: object.scale,
+            });
+        }
+    }
+}
+
+fn isometry(position: Vec3, rotation: Quat) -> Isometry3<f32> {
+    let [x, y, z, w] = <[f32; 4]>::from(rotation);
+    Isometry3::from_parts(
+        Translation3::new(position.x, position.y, position.z),
+        UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z)),
+    )
+}
+
+fn vec3(vector: &Vector3<f32>) -> Vec3 {
+    Vec3::new(vector.x, vector.y, vector.z)
+}
+
+fn quat(rotation: &UnitQuaternion<f32>) -> Quat {
+    let coords = rotation.coords;
+    Quat::from_xyzw(coords.x, coords.y, coords.z, coords.w)
+}
@@ -0,0 +1,81 @@
+use crate::{mesh::MeshData, scene::Scene};
+use glam::{Mat4, Vec3};
+
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// Ray going through the pixel at `(x, y)` of a `width` by `height`
+    /// viewport, from the near to the far plane of `view_projection`.
+    pub fn from_screen(x: f32, y: f32, width: f32, height: f32, view_projection: Mat4) -> Self {
+        let ndc_x = 2.0 * x / width - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height;
+        let inverse = view_projection.inverse();
+        let near = inverse.transform_point3(Vec3::new(ndc_x, ndc_y, 0.0));
+        let far = inverse.transform_point3(Vec3::new(ndc_x, ndc_y, 1.0));
+        Self {
+            origin: near,
+            direction: (far - near).normalize(),
+        }
+    }
+
+    /// Distance along the ray to the intersection with a triangle, if any.
+    ///
+    /// Both faces of the triangle are considered (Möller–Trumbore).
+    pub fn intersect_triangle(&self, [a, b, c]: [Vec3; 3]) -> Option<f32> {
+        let ab = b - a;
+        let ac = c - a;
+        let p = self.direction.cross(ac);
+        let det = ab.dot(p);
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let inv_det = det.recip();
+        let t = self.origin - a;
+        let u = t.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = t.cross(ab);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = ac.dot(q) * inv_det;
+        if distance > 0.0 {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the index of the closest object of the scene hit by the ray.
+pub fn pick(ray: &Ray, scene: &Scene, meshes: &[MeshData]) -> Option<usize> {
+    hit(ray, scene, meshes).map(|(i, _)| i)
+}
+
+/// Index of the closest object hit by the ray, and distance along the ray.
+pub fn hit(ray: &Ray, scene: &Scene, meshes: &[MeshData]) -> Option<(usize, f32)> {
+    scene
+        .objects
+        .iter()
+        .enumerate()
+        .filter_map(|(i, object)| {
+            let model = object.model();
+            meshes[object.mesh]
+                .triangles()
+                .filter_map(|[a, b, c]| {
+                    ray.intersect_triangle([
+                        model.transform_point3(a),
+                        model.transform_point3(b),
+                        model.transform_point3(c),
+                    ])
+                })
+                .min_by(|a, b| a.partial_cmp(b).unwrap())
+                .map(|distance| (i, distance))
+        })
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+}
@@ -0,0 +1,129 @@
+//! Pairs of offscreen targets for effects that run several passes in a row,
+//! each one reading what the previous one wrote.
+use crate::memory::{self, Category, Tracked};
+use wgpu::{
+    Device, Extent3d, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsage,
+    TextureView, TextureViewDescriptor,
+};
+
+pub struct PingPong {
+    targets: [(Tracked<Texture>, TextureView); 2],
+    size: (u32, u32),
+    // kept to recreate the targets at another size
+    label: String,
+    format: TextureFormat,
+    usage: TextureUsage,
+    /// Index of the target read by the next pass, the other one is written.
+    read: usize,
+}
+
+impl PingPong {
+    /// Both targets are `usage` as well, so passes can read and write them.
+    pub fn new(
+        device: &Device,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        usage: TextureUsage,
+    ) -> Self {
+        Self {
+            targets: create_targets(device, label, width, height, format, usage),
+            size: (width, height),
+            label: label.to_string(),
+            format,
+            usage,
+            read: 1,
+        }
+    }
+
+    /// Recreates both targets at `width`x`height`, unless they're that size
+    /// already. What they held is lost, and the first one is written next.
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        if (width, height) == self.size {
+            return;
+        }
+        self.targets = create_targets(device, &self.label, width, height, self.format, self.usage);
+        self.size = (width, height);
+        self.reset();
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Texture written by the last pass.
+    pub fn read_texture(&self) -> &Texture {
+        &self.targets[self.read].0
+    }
+
+    /// View of `read_texture`.
+    pub fn read(&self) -> &TextureView {
+        &self.targets[self.read].1
+    }
+
+    /// Target the next pass writes to.
+    pub fn write(&self) -> &TextureView {
+        &self.targets[1 - self.read].1
+    }
+
+    /// Texture of `write`.
+    pub fn write_texture(&self) -> &Texture {
+        &self.targets[1 - self.read].0
+    }
+
+    /// Has the next pass read what the last one wrote.
+    pub fn swap(&mut self) {
+        self.read = 1 - self.read;
+    }
+
+    /// Makes the first target the one written next, so chains of passes
+    /// always start from the same one.
+    pub fn reset(&mut self) {
+        self.read = 1;
+    }
+
+    /// Whatever `f` makes of each direction, like the bind groups of a pass,
+    /// the first reading the first target and writing the second.
+    pub fn directions<T>(&self, mut f: impl FnMut(&TextureView, &TextureView) -> T) -> [T; 2] {
+        let [(_, first), (_, second)] = &self.targets;
+        [f(first, second), f(second, first)]
+    }
+
+    /// The one of `directions` for the next pass.
+    pub fn current<'a, T>(&self, directions: &'a [T; 2]) -> &'a T {
+        &directions[self.read]
+    }
+}
+
+fn create_targets(
+    device: &Device,
+    label: &str,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    usage: TextureUsage,
+) -> [(Tracked<Texture>, TextureView); 2] {
+    let target = |i| {
+        let texture = memory::create_texture(
+            device,
+            Category::RenderTargets,
+            &TextureDescriptor {
+                label: Some(&format!("{} {}", label, i)),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage,
+            },
+        );
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        (texture, view)
+    };
+    [target(0), target(1)]
+}
@@ -0,0 +1,72 @@
+//! Omnidirectional shadows of point lights.
+//!
+//! The distance from the light to the closest surface is rendered to the six
+//! faces of a cubemap, then compared against the distance of every fragment
+//! in the lighting pass.
+use glam::{Mat4, Vec3};
+use imgui::{im_str, ColorEdit, Drag, Slider, Ui};
+
+/// Resolution of every face of the distance cubemap.
+pub const POINT_SHADOW_SIZE: u32 = 512;
+
+/// Near plane of the face projections.
+const NEAR: f32 = 0.05;
+
+pub struct PointLight {
+    pub position: Vec3,
+    /// Distance at which the light no longer has any effect.
+    pub range: f32,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+impl PointLight {
+    /// View-projection of every cubemap face, in the order of the array
+    /// layers (+X, -X, +Y, -Y, +Z, -Z).
+    pub fn face_view_projections(&self) -> [Mat4; 6] {
+        cube_face_view_projections(self.position, NEAR, self.range)
+    }
+
+    pub fn ui(&mut self, ui: &Ui) {
+        let mut position: [f32; 3] = self.position.into();
+        if Drag::new(im_str!("Position"))
+            .speed(0.05)
+            .build_array(ui, &mut position)
+        {
+            self.position = position.into();
+        }
+        Slider::new(im_str!("Range"))
+            .range(0.5..=30.0)
+            .build(ui, &mut self.range);
+        Slider::new(im_str!("Intensity"))
+            .range(0.0..=20.0)
+            .build(ui, &mut self.intensity);
+        let mut color: [f32; 3] = self.color.into();
+        if ColorEdit::new(im_str!("Color"), &mut color).build(ui) {
+            self.color = color.into();
+        }
+    }
+}
+
+/// View-projection of every face of a cubemap rendered from `position`, in
+/// the order of the array layers (+X, -X, +Y, -Y, +Z, -Z).
+pub fn cube_face_view_projections(position: Vec3, near: f32, far: f32) -> [Mat4; 6] {
+    let faces = [
+        (Vec3::unit_x(), -Vec3::unit_y()),
+        (-Vec3::unit_x(), -Vec3::unit_y()),
+        (Vec3::unit_y(), Vec3::unit_z()),
+        (-Vec3::unit_y(), -Vec3::unit_z()),
+        (Vec3::unit_z(), -Vec3::unit_y()),
+        (-Vec3::unit_z(), -Vec3::unit_y()),
+    ];
+    // faces are looked up top to bottom, so the projection is flipped
+    // vertically with respect to a regular camera
+    let projection = Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0))
+        * Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, near, far);
+    let mut view_projections = [Mat4::identity(); 6];
+    for (view_projection, &(forward, up)) in view_projections.iter_mut().zip(&faces) {
+        let view = Mat4::look_at_rh(position, position + forward, up);
+        *view_projection = projection * view;
+    }
+    view_projections
+}
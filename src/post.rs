@@ -0,0 +1,597 @@
+//! Effects applied to the HDR scene on its way to the output.
+//!
+//! The scene is drawn into the first of a pair of HDR targets, then every
+//! effect reads the one written last and writes the other. The last one
+//! written is composited onto the output with the bloom added to it, exposed,
+//! tone mapped and color graded on the way.
+//! Targets are sized after the output, so supersampled screenshots get
+//! their own.
+use crate::{
+    bloom::{Bloom, BloomParams, BloomTargets},
+    dof::{DepthOfField, DofParams, DofTargets},
+    draw_stats,
+    exposure::{AutoExposure, ExposureParams, ExposureTargets},
+    fog::{Fog, FogParams, FogTargets},
+    fxaa::{Fxaa, FxaaTargets},
+    lut::Lut,
+    memory::{self, Category, Tracked},
+    motion_blur::{MotionBlur, MotionBlurParams, MotionBlurTargets},
+    pass::PassBuilder,
+    ping_pong::PingPong,
+    shaders,
+    shadow::Cascades,
+    taa::{Taa, TaaParams, TaaTargets},
+    volumetric::{Volumetric, VolumetricParams, VolumetricTargets},
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use wgpu::{
+    util::make_spirv, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferDescriptor, BufferSize, BufferUsage, Color, ColorStateDescriptor, ColorWrite,
+    CommandEncoder, Device, FilterMode, IndexFormat, LoadOp, PipelineLayoutDescriptor,
+    PrimitiveTopology, ProgrammableStageDescriptor, Queue, RenderPipeline,
+    RenderPipelineDescriptor, Sampler, SamplerDescriptor, ShaderModule, ShaderStage, Texture,
+    TextureAspect, TextureComponentType, TextureFormat, TextureUsage, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexStateDescriptor,
+};
+
+/// Format of the scene before tone mapping, and of the targets of effects.
+pub const HDR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct CompositeUniforms {
+    bloom_intensity: f32,
+    tone_mapping: u32,
+    lut_blend: f32,
+    _pad: u32,
+}
+
+/// Antialiasing methods selectable from the UI.
+pub const ANTIALIASING: [Antialiasing; 4] = [
+    Antialiasing::None,
+    Antialiasing::Msaa,
+    Antialiasing::Fxaa,
+    Antialiasing::Taa,
+];
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Antialiasing {
+    #[default]
+    None,
+    /// Done before the effects, by drawing the scene into `msaa::MsaaTargets`.
+    Msaa,
+    Fxaa,
+    /// Also needs the projection of the scene jittered, see `taa::jittered`.
+    Taa,
+}
+
+impl Antialiasing {
+    pub fn name(self) -> &'static str {
+        match self {
+            Antialiasing::None => "None",
+            Antialiasing::Msaa => "MSAA",
+            Antialiasing::Fxaa => "FXAA",
+            Antialiasing::Taa => "TAA",
+        }
+    }
+}
+
+/// Parameters of every effect, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct PostParams {
+    pub antialiasing: Antialiasing,
+    pub taa: TaaParams,
+    pub fog: FogParams,
+    pub volumetric: VolumetricParams,
+    pub dof: DofParams,
+    pub motion_blur: MotionBlurParams,
+    pub bloom: BloomParams,
+    pub exposure: ExposureParams,
+    pub grading: GradingParams,
+}
+
+/// Color grading of the tone mapped scene, with the table of
+/// [`PostProcess::set_lut`].
+#[derive(Clone, Copy, PartialEq)]
+pub struct GradingParams {
+    /// How much of the graded color replaces the tone mapped one.
+    pub blend: f32,
+}
+
+impl Default for GradingParams {
+    fn default() -> Self {
+        Self { blend: 1.0 }
+    }
+}
+
+/// What the scene of a frame was drawn with.
+#[derive(Clone, Copy)]
+pub struct PostFrame {
+    /// Planes of the projection, for effects reading the depth buffer.
+    pub near: f32,
+    pub far: f32,
+    /// View-projection of the camera, and the one of the frame before, for
+    /// effects following the motion of the camera. Neither is jittered.
+    pub view_projection: Mat4,
+    pub previous_view_projection: Mat4,
+    /// Position and view direction of the camera.
+    pub eye: Vec3,
+    pub forward: Vec3,
+    /// Directional light, towards where it shines and in HDR units, and the
+    /// cascades of its shadow map.
+    pub light_direction: Vec3,
+    pub light_color: Vec3,
+    pub cascades: Cascades,
+    /// Seconds since the previous frame, for effects adapting over time.
+    pub time_delta: f32,
+    /// Unset for debug views, which are copied to the output as they are.
+    pub lit: bool,
+}
+
+pub struct PostTargets {
+    hdr: PingPong,
+    _depth: TextureView,
+    taa: TaaTargets,
+    fog: FogTargets,
+    volumetric: VolumetricTargets,
+    dof: DofTargets,
+    motion_blur: MotionBlurTargets,
+    fxaa: FxaaTargets,
+    bloom: BloomTargets,
+    exposure: ExposureTargets,
+    /// Bind groups of the composite, reading either HDR target.
+    composite: [BindGroup; 2],
+}
+
+impl PostTargets {
+    /// Target the scene is drawn to.
+    pub fn view(&self) -> &TextureView {
+        self.hdr.write()
+    }
+
+    /// Target holding the scene with every effect, between
+    /// [`PostProcess::effects`] and [`PostProcess::composite`].
+    pub fn result(&self) -> &TextureView {
+        self.hdr.read()
+    }
+}
+
+pub struct PostProcess {
+    taa: Taa,
+    fog: Fog,
+    volumetric: Volumetric,
+    dof: DepthOfField,
+    motion_blur: MotionBlur,
+    fxaa: Fxaa,
+    bloom: Bloom,
+    exposure: AutoExposure,
+    uniform: Tracked<Buffer>,
+    layout: BindGroupLayout,
+    lut_sampler: Sampler,
+    lut_layout: BindGroupLayout,
+    /// Table of the color grading, unset without grading.
+    lut: Option<(Tracked<Texture>, BindGroup)>,
+    /// Identity table bound without grading.
+    no_lut: (Tracked<Texture>, BindGroup),
+    pipeline: RenderPipeline,
+    format: TextureFormat,
+}
+
+impl PostProcess {
+    /// Composites onto targets of `format`, without color grading. The
+    /// light scatters around the shadows of `shadow_map`, the depth array of
+    /// the cascades.
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        format: TextureFormat,
+        shadow_map: &TextureView,
+    ) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::COMPOSITE_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Composite uniforms"),
+                size: std::mem::size_of::<CompositeUniforms>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Composite bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(
+                            std::mem::size_of::<CompositeUniforms>() as _
+                        ),
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+                // the bloom
+                texture_entry(3),
+                // the exposure
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::StorageBuffer {
+                        dynamic: false,
+                        min_binding_size: None,
+                        readonly: true,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let lut_sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Color grading sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let lut_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Color grading bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::SampledTexture {
+                        dimension: TextureViewDimension::D3,
+                        component_type: TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let no_lut = lut_bind_group(device, queue, &lut_layout, &lut_sampler, &Lut::identity());
+        let pipeline = fullscreen_pipeline(
+            device,
+            "Composite pipeline",
+            &[&layout, &lut_layout],
+            &vert_module,
+            &frag_module,
+            format,
+            BlendDescriptor::REPLACE,
+        );
+
+        Self {
+            taa: Taa::new(device),
+            fog: Fog::new(device),
+            volumetric: Volumetric::new(device, shadow_map),
+            dof: DepthOfField::new(device),
+            motion_blur: MotionBlur::new(device),
+            fxaa: Fxaa::new(device),
+            bloom: Bloom::new(device),
+            exposure: AutoExposure::new(device),
+            uniform,
+            layout,
+            lut_sampler,
+            lut_layout,
+            lut: None,
+            no_lut,
+            pipeline,
+            format,
+        }
+    }
+
+    /// Grades the scene with `lut`, or not at all.
+    pub fn set_lut(&mut self, device: &Device, queue: &Queue, lut: Option<&Lut>) {
+        self.lut =
+            lut.map(|lut| lut_bind_group(device, queue, &self.lut_layout, &self.lut_sampler, lut));
+    }
+
+    /// Targets for a `width`x`height` output, whose scene is drawn with
+    /// `depth` as its depth buffer.
+    pub fn targets(
+        &self,
+        device: &Device,
+        width: u32,
+        height: u32,
+        depth: &Texture,
+    ) -> PostTargets {
+        let hdr = PingPong::new(
+            device,
+            "HDR scene",
+            width,
+            height,
+            HDR_FORMAT,
+            // copied to the history of TAA
+            TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::SAMPLED | TextureUsage::COPY_SRC,
+        );
+        self.effect_targets(device, hdr, depth)
+    }
+
+    /// `targets` at `width`x`height`, whose scene is now drawn with `depth`.
+    /// The HDR targets are resized in place, and the targets of the effects,
+    /// which read them, are created again.
+    pub fn resize(
+        &self,
+        device: &Device,
+        targets: PostTargets,
+        width: u32,
+        height: u32,
+        depth: &Texture,
+    ) -> PostTargets {
+        let mut hdr = targets.hdr;
+        hdr.resize(device, width, height);
+        self.effect_targets(device, hdr, depth)
+    }
+
+    fn effect_targets(&self, device: &Device, hdr: PingPong, depth: &Texture) -> PostTargets {
+        let depth = depth.create_view(&TextureViewDescriptor {
+            aspect: TextureAspect::DepthOnly,
+            ..Default::default()
+        });
+        let taa = self.taa.targets(device, &hdr, &depth);
+        let fog = self.fog.targets(device, &hdr, &depth);
+        let volumetric = self.volumetric.targets(device, &hdr, &depth);
+        let dof = self.dof.targets(device, &hdr, &depth);
+        let motion_blur = self.motion_blur.targets(device, &hdr, &depth);
+        let fxaa = self.fxaa.targets(device, &hdr);
+        let bloom = self.bloom.targets(device, &hdr);
+        let exposure = self.exposure.targets(device, &hdr);
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Composite sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let composite = hdr.directions(|source, _| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Composite bind group"),
+                layout: &self.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(self.uniform.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(bloom.view()),
+                    },
+                    BindGroupEntry {
+                        binding: 4,
+                        resource: BindingResource::Buffer(self.exposure.buffer().slice(..)),
+                    },
+                ],
+            })
+        });
+
+        PostTargets {
+            hdr,
+            _depth: depth,
+            taa,
+            fog,
+            volumetric,
+            dof,
+            motion_blur,
+            fxaa,
+            bloom,
+            exposure,
+            composite,
+        }
+    }
+
+    /// Records every effect over the scene drawn in `targets`. What's drawn
+    /// into [`PostTargets::result`] afterwards is only composited.
+    pub fn effects(
+        &self,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        params: &PostParams,
+        frame: PostFrame,
+        targets: &mut PostTargets,
+    ) {
+        let hdr = &mut targets.hdr;
+        // the scene was the last one written
+        hdr.swap();
+        // first, as the history is of the scene without other effects
+        if params.antialiasing == Antialiasing::Taa && frame.lit {
+            let taa = &mut targets.taa;
+            self.taa.run(encoder, queue, &params.taa, frame, taa, hdr);
+            hdr.swap();
+        } else {
+            targets.taa.valid = false;
+        }
+        if params.fog.enabled && frame.lit {
+            let fog = &targets.fog;
+            self.fog.run(encoder, queue, &params.fog, frame, fog, hdr);
+            hdr.swap();
+        }
+        if params.volumetric.enabled && frame.lit {
+            let targets = &targets.volumetric;
+            let volumetric = &params.volumetric;
+            self.volumetric
+                .run(encoder, queue, volumetric, frame, targets, hdr);
+            hdr.swap();
+        }
+        if params.dof.enabled && frame.lit {
+            let dof = &targets.dof;
+            self.dof.run(encoder, queue, &params.dof, frame, dof, hdr);
+            hdr.swap();
+        }
+        if params.motion_blur.enabled && frame.lit {
+            let targets = &targets.motion_blur;
+            let motion_blur = &params.motion_blur;
+            self.motion_blur
+                .run(encoder, queue, motion_blur, frame, targets, hdr);
+            hdr.swap();
+        }
+        if params.antialiasing == Antialiasing::Fxaa && frame.lit {
+            self.fxaa.run(encoder, &targets.fxaa, hdr);
+            hdr.swap();
+        }
+        if params.bloom.enabled && frame.lit {
+            let targets = &targets.bloom;
+            self.bloom.run(encoder, queue, &params.bloom, targets, hdr);
+        }
+        if frame.lit {
+            let targets = &targets.exposure;
+            let (exposure, time_delta) = (&params.exposure, frame.time_delta);
+            self.exposure
+                .run(encoder, queue, exposure, time_delta, targets, hdr);
+        }
+    }
+
+    /// Records the composite of the result of `effects` onto `output`.
+    pub fn composite(
+        &self,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        params: &PostParams,
+        frame: PostFrame,
+        targets: &mut PostTargets,
+        output: &TextureView,
+    ) {
+        let bloom = params.bloom.enabled && frame.lit;
+
+        let uniforms = CompositeUniforms {
+            bloom_intensity: if bloom {
+                targets.bloom.intensity(&params.bloom)
+            } else {
+                0.0
+            },
+            tone_mapping: frame.lit as u32,
+            lut_blend: match self.lut {
+                Some(_) if frame.lit => params.grading.blend,
+                _ => 0.0,
+            },
+            _pad: 0,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, bytemuck::bytes_of(&uniforms));
+        let mut pass = PassBuilder::new()
+            .color(output, self.format, LoadOp::Clear(Color::BLACK), true)
+            .begin(encoder);
+        pass.push_debug_group("Composite");
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, targets.hdr.current(&targets.composite), &[]);
+        let (_, lut) = self.lut.as_ref().unwrap_or(&self.no_lut);
+        pass.set_bind_group(1, lut, &[]);
+        pass.draw(0..3, 0..1);
+        pass.pop_debug_group();
+        drop(pass);
+        targets.hdr.reset();
+    }
+}
+
+fn lut_bind_group(
+    device: &Device,
+    queue: &Queue,
+    layout: &BindGroupLayout,
+    sampler: &Sampler,
+    lut: &Lut,
+) -> (Tracked<Texture>, BindGroup) {
+    let texture = lut.create_texture(device, queue, "Color grading LUT");
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Color grading bind group"),
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Sampler(sampler),
+            },
+        ],
+    });
+    (texture, bind_group)
+}
+
+/// Layout entry of a 2D float texture read by fragment shaders.
+pub fn texture_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStage::FRAGMENT,
+        ty: BindingType::SampledTexture {
+            dimension: TextureViewDimension::D2,
+            component_type: TextureComponentType::Float,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+/// Pipeline drawing a fullscreen triangle with `frag_module` into a target
+/// of `format`, with a bind group of each of `layouts`.
+pub fn fullscreen_pipeline(
+    device: &Device,
+    label: &str,
+    layouts: &[&BindGroupLayout],
+    vert_module: &ShaderModule,
+    frag_module: &ShaderModule,
+    format: TextureFormat,
+    color_blend: BlendDescriptor,
+) -> RenderPipeline {
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some(label),
+        bind_group_layouts: layouts,
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&pipeline_layout),
+        vertex_stage: ProgrammableStageDescriptor {
+            module: vert_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(ProgrammableStageDescriptor {
+            module: frag_module,
+            entry_point: "main",
+        }),
+        rasterization_state: None,
+        primitive_topology: PrimitiveTopology::TriangleList,
+        color_states: &[ColorStateDescriptor {
+            format,
+            alpha_blend: BlendDescriptor::REPLACE,
+            color_blend,
+            write_mask: ColorWrite::ALL,
+        }],
+        depth_stencil_state: None,
+        vertex_state: VertexStateDescriptor {
+            index_format: IndexFormat::Uint16,
+            vertex_buffers: &[],
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
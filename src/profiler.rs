@@ -0,0 +1,294 @@
+//! Hierarchical CPU profiler.
+//!
+//! Scopes are opened with [`scope`] and closed when the returned guard is
+//! dropped, nesting inside the scopes that are still open. Every call to
+//! [`new_frame`] closes the frame being recorded and keeps it in a short
+//! history, which the profiler window shows as a flame graph. Scopes are
+//! meant to be opened only from the main thread.
+//!
+//! The history can be exported as a Chrome trace, to look at it in
+//! `chrome://tracing`, Perfetto, or Tracy after converting it with its
+//! `import-chrome` tool. There's no Tracy client, the profiler only writes
+//! trace files. GPU zones would need timestamp queries, which wgpu doesn't
+//! expose yet, so while the GPU time is measured each frame gets a single
+//! zone on a GPU track instead, from the submission until the device is idle.
+use imgui::{im_str, Slider, Ui, Window};
+use log::{error, info};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Number of frames kept.
+const HISTORY: usize = 120;
+
+/// Height of a row of the flame graph, one per nesting level.
+const ROW_HEIGHT: f32 = 18.0;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static PROFILER: Mutex<Profiler> = Mutex::new(Profiler::new());
+
+struct Scope {
+    name: &'static str,
+    depth: u32,
+    start: Instant,
+    duration: Duration,
+}
+
+struct Frame {
+    /// Number of the frame, counting from the first recorded one.
+    index: u64,
+    start: Instant,
+    duration: Duration,
+    scopes: Vec<Scope>,
+    /// Start and duration of the GPU work, if it was measured.
+    gpu: Option<(Instant, Duration)>,
+}
+
+struct Profiler {
+    /// Frame being recorded, `None` until the first call to `new_frame`.
+    current: Option<Frame>,
+    depth: u32,
+    history: VecDeque<Frame>,
+    paused: bool,
+    /// Frame shown, counting back from the most recent one.
+    selected: i32,
+}
+
+impl Profiler {
+    const fn new() -> Self {
+        Self {
+            current: None,
+            depth: 0,
+            history: VecDeque::new(),
+            paused: false,
+            selected: 0,
+        }
+    }
+}
+
+/// Closes its scope when dropped.
+pub struct ScopeGuard {
+    /// Frame and index of the scope, `None` if the profiler is disabled.
+    scope: Option<(u64, usize)>,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        if let Some((frame, index)) = self.scope {
+            let mut profiler = PROFILER.lock().unwrap();
+            profiler.depth = profiler.depth.saturating_sub(1);
+            // scopes still open at the end of a frame are discarded with it
+            if let Some(current) = profiler.current.as_mut().filter(|f| f.index == frame) {
+                let scope = &mut current.scopes[index];
+                scope.duration = scope.start.elapsed();
+            }
+        }
+    }
+}
+
+/// Opens a scope of the current frame named `name`.
+pub fn scope(name: &'static str) -> ScopeGuard {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return ScopeGuard { scope: None };
+    }
+    let mut profiler = PROFILER.lock().unwrap();
+    let depth = profiler.depth;
+    let current = match profiler.current.as_mut() {
+        Some(current) => current,
+        None => return ScopeGuard { scope: None },
+    };
+    current.scopes.push(Scope {
+        name,
+        depth,
+        start: Instant::now(),
+        duration: Duration::default(),
+    });
+    let scope = (current.index, current.scopes.len() - 1);
+    profiler.depth += 1;
+    ScopeGuard { scope: Some(scope) }
+}
+
+/// Ends the frame being recorded and starts the next one.
+pub fn new_frame() {
+    let mut profiler = PROFILER.lock().unwrap();
+    let now = Instant::now();
+    let index = match profiler.current.take() {
+        Some(mut frame) => {
+            frame.duration = now - frame.start;
+            let index = frame.index + 1;
+            if !profiler.paused {
+                if profiler.history.len() == HISTORY {
+                    profiler.history.pop_front();
+                }
+                profiler.history.push_back(frame);
+            }
+            index
+        }
+        None => 0,
+    };
+    profiler.depth = 0;
+    if ENABLED.load(Ordering::Relaxed) {
+        profiler.current = Some(Frame {
+            index,
+            start: now,
+            duration: Duration::default(),
+            scopes: Vec::new(),
+            gpu: None,
+        });
+    }
+}
+
+/// Records the GPU work of the current frame, from `start` until `duration`
+/// later.
+pub fn gpu_zone(start: Instant, duration: Duration) {
+    let mut profiler = PROFILER.lock().unwrap();
+    if let Some(current) = profiler.current.as_mut() {
+        current.gpu = Some((start, duration));
+    }
+}
+
+pub fn window(ui: &Ui) {
+    let mut profiler = PROFILER.lock().unwrap();
+    Window::new(im_str!("Profiler")).build(ui, || {
+        let mut enabled = ENABLED.load(Ordering::Relaxed);
+        if ui.checkbox(im_str!("Enabled"), &mut enabled) {
+            ENABLED.store(enabled, Ordering::Relaxed);
+        }
+        ui.same_line(0.0);
+        ui.checkbox(im_str!("Paused"), &mut profiler.paused);
+        ui.same_line(0.0);
+        if ui.button(im_str!("Export trace"), [0.0, 0.0]) {
+            let secs = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Error reading system time")
+                .as_secs();
+            let path = format!("profile_{}.json", secs);
+            match std::fs::write(&path, chrome_trace(&profiler.history)) {
+                Ok(()) => info!("Saved profile to {}", path),
+                Err(err) => error!("Error saving profile to {}: {}", path, err),
+            }
+        }
+
+        let durations: Vec<f32> = profiler
+            .history
+            .iter()
+            .map(|frame| frame.duration.as_secs_f32() * 1000.0)
+            .collect();
+        ui.plot_histogram(im_str!("##Frames"), &durations)
+            .scale_min(0.0)
+            .graph_size([ui.content_region_avail()[0], 40.0])
+            .build();
+        let last = profiler.history.len() as i32 - 1;
+        Slider::new(im_str!("Frames ago"))
+            .range(0..=last.max(0))
+            .build(ui, &mut profiler.selected);
+        let selected = profiler.selected.min(last.max(0));
+        let frame = match profiler.history.iter().rev().nth(selected as usize) {
+            Some(frame) => frame,
+            None => return,
+        };
+        ui.text(format!(
+            "Frame {}: {:.2} ms, {} scopes",
+            frame.index,
+            frame.duration.as_secs_f32() * 1000.0,
+            frame.scopes.len()
+        ));
+        flame_graph(ui, frame);
+    });
+}
+
+/// Scopes of `frames` as complete events of the Chrome trace event format,
+/// relative to the start of the first frame. CPU scopes are on thread 1 and
+/// GPU zones on thread 2.
+fn chrome_trace<'a>(frames: impl IntoIterator<Item = &'a Frame>) -> String {
+    let mut frames = frames.into_iter().peekable();
+    let origin = match frames.peek() {
+        Some(frame) => frame.start,
+        None => return "[]".to_string(),
+    };
+    let micros = |d: Duration| d.as_secs_f64() * 1e6;
+    let mut events = vec![
+        r#"{"name":"thread_name","ph":"M","pid":1,"tid":1,"args":{"name":"CPU"}}"#.to_string(),
+        r#"{"name":"thread_name","ph":"M","pid":1,"tid":2,"args":{"name":"GPU"}}"#.to_string(),
+    ];
+    for frame in frames {
+        if let Some((start, duration)) = frame.gpu {
+            events.push(format!(
+                r#"{{"name":"Frame","ph":"X","ts":{:.3},"dur":{:.3},"pid":1,"tid":2,"args":{{"frame":{}}}}}"#,
+                micros(start.saturating_duration_since(origin)),
+                micros(duration),
+                frame.index
+            ));
+        }
+        for scope in &frame.scopes {
+            events.push(format!(
+                r#"{{"name":"{}","ph":"X","ts":{:.3},"dur":{:.3},"pid":1,"tid":1,"args":{{"frame":{}}}}}"#,
+                scope.name.replace('"', "\\\""),
+                micros(scope.start - origin),
+                micros(scope.duration),
+                frame.index
+            ));
+        }
+    }
+    format!("[\n{}\n]\n", events.join(",\n"))
+}
+
+/// Draws the scopes of `frame` as nested bars, with the time of the frame
+/// across the width of the window.
+fn flame_graph(ui: &Ui, frame: &Frame) {
+    let origin = ui.cursor_screen_pos();
+    let width = ui.content_region_avail()[0].max(1.0);
+    let rows = frame.scopes.iter().map(|s| s.depth + 1).max().unwrap_or(1);
+    let height = rows as f32 * ROW_HEIGHT;
+    ui.dummy([width, height]);
+
+    let draw_list = ui.get_window_draw_list();
+    let frame_time = frame.duration.as_secs_f32().max(f32::EPSILON);
+    let mut hovered = None;
+    for scope in &frame.scopes {
+        let start = (scope.start - frame.start).as_secs_f32() / frame_time;
+        let end = start + scope.duration.as_secs_f32() / frame_time;
+        let min = [
+            origin[0] + start * width,
+            origin[1] + scope.depth as f32 * ROW_HEIGHT,
+        ];
+        let max = [
+            (origin[0] + end * width).max(min[0] + 1.0),
+            min[1] + ROW_HEIGHT - 1.0,
+        ];
+        // color by name, so a scope keeps its color from frame to frame
+        let hash = scope
+            .name
+            .bytes()
+            .fold(0u32, |h, b| h.wrapping_mul(31).wrapping_add(b as u32));
+        let color = [
+            0.4 + 0.4 * (hash & 0xff) as f32 / 255.0,
+            0.4 + 0.4 * (hash >> 8 & 0xff) as f32 / 255.0,
+            0.3,
+            1.0,
+        ];
+        draw_list.add_rect(min, max, color).filled(true).build();
+        draw_list.with_clip_rect_intersect(min, max, || {
+            draw_list.add_text(
+                [min[0] + 2.0, min[1] + 1.0],
+                [0.0, 0.0, 0.0, 1.0],
+                scope.name,
+            );
+        });
+        if ui.is_mouse_hovering_rect(min, max) {
+            hovered = Some(scope);
+        }
+    }
+    if let Some(scope) = hovered {
+        ui.tooltip_text(format!(
+            "{}: {:.3} ms",
+            scope.name,
+            scope.duration.as_secs_f32() * 1000.0
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+//! Ray marched signed distance field scene, drawn with a single fullscreen
+//! triangle.
+//!
+//! The fragment shader can be replaced at runtime, from the shader editor.
+use crate::{
+    draw_stats::{self, CountedPass},
+    memory::{self, Category, Tracked},
+    shaders,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use wgpu::{
+    util::{self, make_spirv},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor, Buffer, BufferDescriptor,
+    BufferSize, BufferUsage, ColorStateDescriptor, ColorWrite, Device, IndexFormat, PipelineLayout,
+    PipelineLayoutDescriptor, PrimitiveTopology, ProgrammableStageDescriptor, Queue,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModule, ShaderStage, TextureFormat,
+    VertexStateDescriptor,
+};
+
+/// GLSL of the fragment shader built in.
+pub const SOURCE: &str = include_str!("shaders/raymarch.frag");
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct RaymarchUniforms {
+    inverse_view_projection: [[f32; 4]; 4],
+    camera_position: [f32; 4],
+    light_direction: [f32; 4],
+    time: f32,
+    _pad: [f32; 3],
+}
+
+pub struct Raymarch {
+    uniform: Tracked<Buffer>,
+    bind_group: BindGroup,
+    format: TextureFormat,
+    vert_module: ShaderModule,
+    pipeline_layout: PipelineLayout,
+    pipeline: RenderPipeline,
+}
+
+impl Raymarch {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::RAYMARCH_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Raymarch uniforms"),
+                size: std::mem::size_of::<RaymarchUniforms>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Raymarch bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: BufferSize::new(std::mem::size_of::<RaymarchUniforms>() as _),
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Raymarch bind group"),
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(uniform.slice(..)),
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Raymarch pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline =
+            create_pipeline(device, &pipeline_layout, &vert_module, &frag_module, format);
+
+        Self {
+            uniform,
+            bind_group,
+            format,
+            vert_module,
+            pipeline_layout,
+            pipeline,
+        }
+    }
+
+    /// Replaces the fragment shader with compiled SPIR-V, which must have the
+    /// same interface as the built in one.
+    pub fn set_fragment_shader(&mut self, device: &Device, spirv: &[u8]) {
+        let frag_module = device.create_shader_module(util::make_spirv(spirv));
+        self.pipeline = create_pipeline(
+            device,
+            &self.pipeline_layout,
+            &self.vert_module,
+            &frag_module,
+            self.format,
+        );
+    }
+
+    /// Updates the camera and animation time (in seconds).
+    pub fn update(
+        &self,
+        queue: &Queue,
+        view_projection: Mat4,
+        eye: Vec3,
+        light_direction: Vec3,
+        time: f32,
+    ) {
+        let uniforms = RaymarchUniforms {
+            inverse_view_projection: view_projection.inverse().to_cols_array_2d(),
+            camera_position: eye.extend(1.0).into(),
+            light_direction: light_direction.extend(0.0).into(),
+            time,
+            _pad: [0.0; 3],
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Draws the scene over the whole color target of the pass.
+    pub fn draw<'a>(&'a self, pass: &mut CountedPass<'a>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    vert_module: &ShaderModule,
+    frag_module: &ShaderModule,
+    format: TextureFormat,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Raymarch pipeline"),
+        layout: Some(layout),
+        vertex_stage: ProgrammableStageDescriptor {
+            module: vert_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(ProgrammableStageDescriptor {
+            module: frag_module,
+            entry_point: "main",
+        }),
+        rasterization_state: None,
+        primitive_topology: PrimitiveTopology::TriangleList,
+        color_states: &[ColorStateDescriptor {
+            format,
+            alpha_blend: BlendDescriptor::REPLACE,
+            color_blend: BlendDescriptor::REPLACE,
+            write_mask: ColorWrite::ALL,
+        }],
+        depth_stencil_state: None,
+        vertex_state: VertexStateDescriptor {
+            index_format: IndexFormat::Uint16,
+            vertex_buffers: &[],
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
@@ -0,0 +1,105 @@
+//! Reading GPU buffers and textures back to the CPU.
+//!
+//! The resource is copied into a mappable staging buffer, which is mapped once
+//! the copy is done. The device is polled until then, so the futures returned
+//! here are ready as soon as they are polled.
+use crate::memory::{self, Category, Tracked};
+use bytemuck::Pod;
+use wgpu::{
+    Buffer, BufferCopyView, BufferDescriptor, BufferUsage, CommandEncoderDescriptor, Device,
+    Extent3d, Maintain, MapMode, Origin3d, Queue, Texture, TextureCopyView, TextureDataLayout,
+    COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+/// Reads the first `len` elements of `buffer`, which needs `COPY_SRC` usage.
+pub async fn read_buffer<T: Pod>(
+    device: &Device,
+    queue: &Queue,
+    buffer: &Buffer,
+    len: usize,
+) -> Vec<T> {
+    let size = (len * std::mem::size_of::<T>()) as _;
+    let staging = staging_buffer(device, size);
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Buffer readback"),
+    });
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, size);
+    queue.submit(Some(encoder.finish()));
+
+    map(device, &staging).await;
+    let values = bytemuck::cast_slice(&staging.slice(..).get_mapped_range()).to_vec();
+    staging.unmap();
+    values
+}
+
+/// Reads the first mip of a 2D `texture` with 4 byte texels as tightly packed
+/// rows. The texture needs `COPY_SRC` usage.
+pub async fn read_texture(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+    width: u32,
+    height: u32,
+) -> Vec<u8> {
+    // rows of the copy are padded to the alignment
+    let bytes_per_row = 4 * width;
+    let padded_bytes_per_row =
+        bytes_per_row.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+    let staging = staging_buffer(device, (padded_bytes_per_row * height) as _);
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Texture readback"),
+    });
+    encoder.copy_texture_to_buffer(
+        TextureCopyView {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+        },
+        BufferCopyView {
+            buffer: &staging,
+            layout: TextureDataLayout {
+                offset: 0,
+                bytes_per_row: padded_bytes_per_row,
+                rows_per_image: height,
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth: 1,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    map(device, &staging).await;
+    let mut pixels = Vec::with_capacity((bytes_per_row * height) as _);
+    for row in staging
+        .slice(..)
+        .get_mapped_range()
+        .chunks(padded_bytes_per_row as _)
+    {
+        pixels.extend_from_slice(&row[..bytes_per_row as usize]);
+    }
+    staging.unmap();
+    pixels
+}
+
+fn staging_buffer(device: &Device, size: u64) -> Tracked<Buffer> {
+    memory::create_buffer(
+        device,
+        Category::Staging,
+        &BufferDescriptor {
+            label: Some("Readback"),
+            size,
+            usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        },
+    )
+}
+
+/// Maps the whole `buffer` for reading, once the GPU is done with it.
+async fn map(device: &Device, buffer: &Buffer) {
+    let mapping = buffer.slice(..).map_async(MapMode::Read);
+    device.poll(Maintain::Wait);
+    mapping.await.expect("Error mapping readback buffer");
+}
@@ -0,0 +1,483 @@
+//! Local reflections, from a cubemap of the scene rendered at a point.
+//!
+//! The environment and the objects are drawn into the six faces of a
+//! radiance cubemap from the position of the probe, which is then
+//! prefiltered like the environment is. Lit surfaces within the radius of the
+//! probe reflect it instead of the environment, projected on the sphere of
+//! the radius. Faces are either all rendered when the probe changes, or one
+//! of them every frame.
+//!
+//! The lit pipeline drawing the objects into the faces is made along with
+//! the others, from [`ReflectionProbe::face_layout`].
+use crate::{
+    draw_stats::{self, CountedPass},
+    gizmos::Gizmos,
+    ibl::{self, Ibl, Prefilter},
+    memory::{self, Category, Tracked},
+    picking::Ray,
+    point_shadow, shaders,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use imgui::{im_str, ComboBox, Drag, ImString, Slider, Ui, Window};
+use std::{num::NonZeroU32, ops::Range};
+use wgpu::{
+    util::make_spirv, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferDescriptor, BufferSize, BufferUsage, ColorStateDescriptor, ColorWrite,
+    CommandEncoder, CompareFunction, DepthStencilStateDescriptor, Device, Extent3d, IndexFormat,
+    PipelineLayoutDescriptor, PrimitiveTopology, ProgrammableStageDescriptor, Queue,
+    RenderPipeline, RenderPipelineDescriptor, ShaderStage, StencilStateDescriptor, Texture,
+    TextureComponentType, TextureDescriptor, TextureDimension, TextureFormat, TextureUsage,
+    TextureView, TextureViewDescriptor, TextureViewDimension, VertexStateDescriptor,
+};
+
+/// Resolution of every face of the cubemaps.
+pub const PROBE_SIZE: u32 = 128;
+
+/// Near and far planes of the face projections.
+const NEAR: f32 = 0.05;
+const FAR: f32 = 100.0;
+
+/// Distance in pixels from the handle at which it can be grabbed.
+const GRAB_RADIUS: f32 = 10.0;
+
+/// Modes selectable from the UI.
+pub const PROBE_UPDATES: [ProbeUpdate; 2] = [ProbeUpdate::Once, ProbeUpdate::Amortized];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ProbeUpdate {
+    /// Every face, when the probe is moved or asked to.
+    Once,
+    /// A face every frame, so the whole probe takes six.
+    Amortized,
+}
+
+impl ProbeUpdate {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProbeUpdate::Once => "Once",
+            ProbeUpdate::Amortized => "Amortized",
+        }
+    }
+}
+
+/// Uniform block of a face, for both the objects and the background.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct ProbeFace {
+    view_projection: [[f32; 4]; 4],
+    face: u32,
+    _pad: [u32; 3],
+}
+
+pub struct ReflectionProbe {
+    pub enabled: bool,
+    pub position: Vec3,
+    /// Distance up to which surfaces reflect the probe.
+    pub radius: f32,
+    pub update: ProbeUpdate,
+    pub show_handle: bool,
+    /// Whether every face has to be rendered again, in `Once` mode.
+    dirty: bool,
+    /// Face rendered next in `Amortized` mode.
+    next_face: u32,
+    /// Normal of the plane the handle is dragged on.
+    grab: Option<Vec3>,
+    _radiance: Tracked<Texture>,
+    radiance_faces: Vec<TextureView>,
+    _depth: Tracked<Texture>,
+    depth_view: TextureView,
+    _prefiltered: Tracked<Texture>,
+    /// Prefiltered cubemap sampled by the lit shader.
+    pub view: TextureView,
+    prefilter: Prefilter,
+    /// Bind group of the face uniforms for the objects.
+    pub face_layout: BindGroupLayout,
+    /// Uniforms of every face, with their bind groups for the objects and
+    /// for the background.
+    faces: Vec<(Tracked<Buffer>, BindGroup, BindGroup)>,
+    background_pipeline: RenderPipeline,
+}
+
+impl ReflectionProbe {
+    /// Probe drawing the environment of `ibl` behind the objects, with a
+    /// depth buffer of `depth_format`.
+    pub fn new(device: &Device, ibl: &Ibl, depth_format: TextureFormat) -> Self {
+        let radiance = create_cube(device, "Reflection probe radiance", 1);
+        let radiance_faces = (0..6)
+            .map(|face| {
+                radiance.create_view(&TextureViewDescriptor {
+                    dimension: Some(TextureViewDimension::D2),
+                    base_array_layer: face,
+                    array_layer_count: NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let radiance_view = cube_view(&radiance);
+        let prefiltered = create_cube(
+            device,
+            "Reflection probe prefiltered",
+            ibl::PREFILTERED_MIPS,
+        );
+        let prefilter = Prefilter::new(
+            device,
+            "Reflection probe prefilter",
+            &radiance_view,
+            PROBE_SIZE,
+            &prefiltered,
+            ibl::PREFILTERED_MIPS,
+            &ibl.sampler,
+        );
+        let depth = memory::create_texture(
+            device,
+            Category::RenderTargets,
+            &TextureDescriptor {
+                label: Some("Reflection probe depth"),
+                size: Extent3d {
+                    width: PROBE_SIZE,
+                    height: PROBE_SIZE,
+                    depth: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: depth_format,
+                usage: TextureUsage::OUTPUT_ATTACHMENT,
+            },
+        );
+        let depth_view = depth.create_view(&TextureViewDescriptor::default());
+
+        let face_size = BufferSize::new(std::mem::size_of::<ProbeFace>() as _);
+        let face_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Reflection probe face bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::VERTEX,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: face_size,
+                },
+                count: None,
+            }],
+        });
+        let background_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Reflection probe background bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: face_size,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::SampledTexture {
+                        dimension: TextureViewDimension::Cube,
+                        component_type: TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let faces = (0..6)
+            .map(|face| {
+                let uniform = memory::create_buffer(
+                    device,
+                    Category::Uniforms,
+                    &BufferDescriptor {
+                        label: Some(&format!("Reflection probe face {} uniforms", face)),
+                        size: std::mem::size_of::<ProbeFace>() as _,
+                        usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                        mapped_at_creation: false,
+                    },
+                );
+                let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                    label: Some(&format!("Reflection probe face {} bind group", face)),
+                    layout: &face_layout,
+                    entries: &[BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(uniform.slice(..)),
+                    }],
+                });
+                let background = device.create_bind_group(&BindGroupDescriptor {
+                    label: Some(&format!("Reflection probe face {} background", face)),
+                    layout: &background_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::Buffer(uniform.slice(..)),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::TextureView(&ibl.environment_view),
+                        },
+                        BindGroupEntry {
+                            binding: 2,
+                            resource: BindingResource::Sampler(&ibl.sampler),
+                        },
+                    ],
+                });
+                (uniform, bind_group, background)
+            })
+            .collect();
+
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::PROBE_BACKGROUND_FRAG));
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Reflection probe background pipeline layout"),
+            bind_group_layouts: &[&background_layout],
+            push_constant_ranges: &[],
+        });
+        let background_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Reflection probe background pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex_stage: ProgrammableStageDescriptor {
+                module: &vert_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(ProgrammableStageDescriptor {
+                module: &frag_module,
+                entry_point: "main",
+            }),
+            rasterization_state: None,
+            primitive_topology: PrimitiveTopology::TriangleList,
+            color_states: &[ColorStateDescriptor {
+                format: ibl::CUBE_FORMAT,
+                alpha_blend: BlendDescriptor::REPLACE,
+                color_blend: BlendDescriptor::REPLACE,
+                write_mask: ColorWrite::ALL,
+            }],
+            // behind everything, the objects are drawn over it
+            depth_stencil_state: Some(DepthStencilStateDescriptor {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilStateDescriptor::default(),
+            }),
+            vertex_state: VertexStateDescriptor {
+                index_format: IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        Self {
+            enabled: false,
+            position: Vec3::new(0.0, 1.0, 0.0),
+            radius: 5.0,
+            update: ProbeUpdate::Once,
+            show_handle: true,
+            dirty: true,
+            next_face: 0,
+            grab: None,
+            _radiance: radiance,
+            radiance_faces,
+            _depth: depth,
+            depth_view,
+            view: cube_view(&prefiltered),
+            _prefiltered: prefiltered,
+            prefilter,
+            face_layout,
+            faces,
+            background_pipeline,
+        }
+    }
+
+    /// Writes the uniforms of the faces to render this frame, and returns
+    /// them.
+    pub fn prepare(&mut self, queue: &Queue) -> Range<u32> {
+        if !self.enabled {
+            return 0..0;
+        }
+        let faces = match self.update {
+            ProbeUpdate::Once if self.dirty => 0..6,
+            ProbeUpdate::Once => 0..0,
+            ProbeUpdate::Amortized => {
+                let face = self.next_face;
+                self.next_face = (face + 1) % 6;
+                face..face + 1
+            }
+        };
+        self.dirty = false;
+        let view_projections = point_shadow::cube_face_view_projections(self.position, NEAR, FAR);
+        for face in faces.clone() {
+            let uniforms = ProbeFace {
+                view_projection: view_projections[face as usize].to_cols_array_2d(),
+                face,
+                _pad: [0; 3],
+            };
+            let uniform = &self.faces[face as usize].0;
+            draw_stats::write_buffer(queue, uniform, 0, bytemuck::bytes_of(&uniforms));
+        }
+        faces
+    }
+
+    /// Color and depth targets of `face`.
+    pub fn face_targets(&self, face: u32) -> (&TextureView, &TextureView) {
+        (&self.radiance_faces[face as usize], &self.depth_view)
+    }
+
+    /// Bind group of the uniforms of `face`, for the objects.
+    pub fn face_bind_group(&self, face: u32) -> &BindGroup {
+        &self.faces[face as usize].1
+    }
+
+    /// Draws the environment into `face`, behind the objects.
+    pub fn draw_background<'a>(&'a self, pass: &mut CountedPass<'a>, face: u32) {
+        pass.set_pipeline(&self.background_pipeline);
+        pass.set_bind_group(0, &self.faces[face as usize].2, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    /// Records the prefiltering of the rendered `faces`.
+    pub fn prefilter(&self, encoder: &mut CommandEncoder, faces: Range<u32>) {
+        for face in faces {
+            self.prefilter.run_face(encoder, face);
+        }
+    }
+
+    /// Starts dragging the handle if it's under `(x, y)`, of a `width` by
+    /// `height` viewport. Returns whether it was.
+    pub fn grab(&mut self, x: f32, y: f32, width: f32, height: f32, view_projection: Mat4) -> bool {
+        if !self.enabled || !self.show_handle {
+            return false;
+        }
+        let ndc = view_projection.transform_point3(self.position);
+        if !(0.0..=1.0).contains(&ndc.z) {
+            return false;
+        }
+        let screen_x = (ndc.x + 1.0) * 0.5 * width;
+        let screen_y = (1.0 - ndc.y) * 0.5 * height;
+        if (screen_x - x).hypot(screen_y - y) >= GRAB_RADIUS {
+            return false;
+        }
+        let ray = Ray::from_screen(x, y, width, height, view_projection);
+        self.grab = Some(ray.direction);
+        true
+    }
+
+    /// Moves the probe under `(x, y)`, on the plane facing the ray it was
+    /// grabbed with.
+    pub fn drag(&mut self, x: f32, y: f32, width: f32, height: f32, view_projection: Mat4) {
+        let normal = match self.grab {
+            Some(normal) => normal,
+            None => return,
+        };
+        let ray = Ray::from_screen(x, y, width, height, view_projection);
+        let facing = ray.direction.dot(normal);
+        if facing.abs() < f32::EPSILON {
+            return;
+        }
+        let distance = (self.position - ray.origin).dot(normal) / facing;
+        self.position = ray.origin + ray.direction * distance;
+        self.dirty = true;
+    }
+
+    pub fn release(&mut self) {
+        self.grab = None;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.grab.is_some()
+    }
+
+    /// Queues the handle at the position of the probe, facing the camera at
+    /// `eye`, and the sphere of its radius.
+    pub fn gizmo(&self, gizmos: &mut Gizmos, eye: Vec3) {
+        if !self.enabled || !self.show_handle {
+            return;
+        }
+        let color = [0.3, 0.8, 1.0, 1.0];
+        let to_eye = eye - self.position;
+        let mut handle = to_eye.length() * 0.015;
+        if self.grab.is_some() {
+            handle *= 1.5;
+        }
+        gizmos.circle(self.position, to_eye, handle, color);
+        let faint = [color[0], color[1], color[2], 0.4];
+        for &axis in &[Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()] {
+            gizmos.circle(self.position, axis, self.radius, faint);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &Ui) {
+        let mut changed = false;
+        Window::new(im_str!("Reflection probe"))
+            .always_auto_resize(true)
+            .build(ui, || {
+                changed |= ui.checkbox(im_str!("Enabled"), &mut self.enabled);
+                ui.checkbox(im_str!("Show handle"), &mut self.show_handle);
+                let mut position: [f32; 3] = self.position.into();
+                if Drag::new(im_str!("Position"))
+                    .speed(0.05)
+                    .build_array(ui, &mut position)
+                {
+                    self.position = position.into();
+                    changed = true;
+                }
+                changed |= Slider::new(im_str!("Radius"))
+                    .range(0.5..=50.0)
+                    .build(ui, &mut self.radius);
+                let names: Vec<_> = PROBE_UPDATES
+                    .iter()
+                    .map(|update| ImString::new(update.name()))
+                    .collect();
+                let names: Vec<_> = names.iter().collect();
+                let mut index = PROBE_UPDATES
+                    .iter()
+                    .position(|&update| update == self.update)
+                    .unwrap();
+                if ComboBox::new(im_str!("Update")).build_simple_string(ui, &mut index, &names) {
+                    self.update = PROBE_UPDATES[index];
+                    changed = true;
+                }
+                if self.update == ProbeUpdate::Once && ui.button(im_str!("Render"), [0.0, 0.0]) {
+                    changed = true;
+                }
+            });
+        self.dirty |= changed;
+    }
+}
+
+fn create_cube(device: &Device, label: &str, mips: u32) -> Tracked<Texture> {
+    memory::create_texture(
+        device,
+        Category::RenderTargets,
+        &TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width: PROBE_SIZE,
+                height: PROBE_SIZE,
+                depth: 6,
+            },
+            mip_level_count: mips,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: ibl::CUBE_FORMAT,
+            usage: TextureUsage::SAMPLED | TextureUsage::OUTPUT_ATTACHMENT,
+        },
+    )
+}
+
+fn cube_view(texture: &Texture) -> TextureView {
+    texture.create_view(&TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        array_layer_count: NonZeroU32::new(6),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,346 @@
+//! Renders on a thread of its own, apart from the one the events of the
+//! window are pumped on.
+//!
+//! SDL is only used from the thread that initialized it, which waits for
+//! events and forwards them to the render thread over a channel. It also
+//! keeps what the render thread polls (the sizes of the window, the mouse and
+//! the keyboard), and does what the render thread asks of the window through
+//! [`WindowProxy`]: setting the title or the cursor, capturing the mouse,
+//! using the clipboard. A long frame doesn't keep the window from responding,
+//! and input is timestamped when it happens, not when a frame gets to it.
+//!
+//! The render thread returning is the shutdown, the event thread keeps
+//! forwarding events (like closing the window) until it does.
+use imgui::MouseCursor;
+use log::warn;
+use sdl2::{
+    event::Event,
+    keyboard::Scancode,
+    mouse::{Cursor, SystemCursor},
+    video::{Window, WindowPos},
+    EventPump, Sdl,
+};
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// How long the event thread waits for events before looking at the requests
+/// of the render thread again.
+const WAIT_MS: u32 = 4;
+
+/// How long reading the clipboard waits for the event thread.
+const CLIPBOARD_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Event sent to the render thread.
+struct SentEvent(Event);
+
+// the only events with pointers are user events, which aren't pushed, and
+// nothing reads their pointers
+unsafe impl Send for SentEvent {}
+
+/// What the render thread asks of the window.
+enum Request {
+    Title(String),
+    RelativeMouse(bool),
+    CaptureMouse(bool),
+    /// `None` hides the cursor.
+    Cursor(Option<MouseCursor>),
+    Size(u32, u32),
+    Center,
+    SetClipboard(String),
+    GetClipboard(Sender<Option<String>>),
+}
+
+/// Mouse as of the last events pumped.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct MouseState {
+    x: i32,
+    y: i32,
+    buttons: [bool; 5],
+}
+
+impl MouseState {
+    pub fn x(&self) -> i32 {
+        self.x
+    }
+
+    pub fn y(&self) -> i32 {
+        self.y
+    }
+
+    pub fn left(&self) -> bool {
+        self.buttons[0]
+    }
+
+    pub fn right(&self) -> bool {
+        self.buttons[1]
+    }
+
+    pub fn middle(&self) -> bool {
+        self.buttons[2]
+    }
+
+    pub fn x1(&self) -> bool {
+        self.buttons[3]
+    }
+
+    pub fn x2(&self) -> bool {
+        self.buttons[4]
+    }
+}
+
+/// Keys pressed as of the last events pumped.
+#[derive(Clone, Default, Debug)]
+pub struct KeyboardState {
+    pressed: Vec<Scancode>,
+}
+
+impl KeyboardState {
+    pub fn is_scancode_pressed(&self, scancode: Scancode) -> bool {
+        self.pressed.contains(&scancode)
+    }
+}
+
+#[derive(Default)]
+struct WindowState {
+    size: (u32, u32),
+    drawable_size: (u32, u32),
+    position: (i32, i32),
+    mouse: MouseState,
+    keyboard: KeyboardState,
+}
+
+impl WindowState {
+    fn update(&mut self, window: &Window, events: &EventPump) {
+        self.size = window.size();
+        self.drawable_size = window.drawable_size();
+        self.position = window.position();
+        let mouse = events.mouse_state();
+        self.mouse = MouseState {
+            x: mouse.x(),
+            y: mouse.y(),
+            buttons: [
+                mouse.left(),
+                mouse.right(),
+                mouse.middle(),
+                mouse.x1(),
+                mouse.x2(),
+            ],
+        };
+        self.keyboard.pressed = events.keyboard_state().pressed_scancodes().collect();
+    }
+}
+
+/// The window, seen from the render thread.
+pub struct WindowProxy {
+    id: u32,
+    state: Arc<Mutex<WindowState>>,
+    events: Receiver<SentEvent>,
+    requests: Sender<Request>,
+}
+
+impl WindowProxy {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Size in window coordinates, which the mouse is in.
+    pub fn size(&self) -> (u32, u32) {
+        self.state.lock().unwrap().size
+    }
+
+    /// Size in pixels, larger than the window on HiDPI displays.
+    pub fn drawable_size(&self) -> (u32, u32) {
+        self.state.lock().unwrap().drawable_size
+    }
+
+    pub fn position(&self) -> (i32, i32) {
+        self.state.lock().unwrap().position
+    }
+
+    pub fn mouse_state(&self) -> MouseState {
+        self.state.lock().unwrap().mouse
+    }
+
+    pub fn keyboard_state(&self) -> KeyboardState {
+        self.state.lock().unwrap().keyboard.clone()
+    }
+
+    /// Events forwarded since the last call.
+    pub fn poll_iter(&self) -> impl Iterator<Item = Event> + '_ {
+        self.events.try_iter().map(|event| event.0)
+    }
+
+    /// Milliseconds since SDL was initialized, the time events are stamped
+    /// with.
+    pub fn ticks(&self) -> u32 {
+        // unlike the timer subsystem, SDL_GetTicks can be called from any
+        // thread
+        unsafe { sdl2::sys::SDL_GetTicks() }
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.request(Request::Title(title.to_string()));
+    }
+
+    pub fn set_relative_mouse_mode(&self, on: bool) {
+        self.request(Request::RelativeMouse(on));
+    }
+
+    /// Keeps getting mouse events while the cursor is out of the window.
+    pub fn capture_mouse(&self, on: bool) {
+        self.request(Request::CaptureMouse(on));
+    }
+
+    /// Changes the cursor, or hides it.
+    pub fn set_cursor(&self, cursor: Option<MouseCursor>) {
+        self.request(Request::Cursor(cursor));
+    }
+
+    /// Resizes the window, with the size changing once the event arrives.
+    pub fn set_size(&self, width: u32, height: u32) {
+        self.request(Request::Size(width, height));
+    }
+
+    pub fn center(&self) {
+        self.request(Request::Center);
+    }
+
+    /// The clipboard, which can be used from any thread.
+    pub fn clipboard(&self) -> Clipboard {
+        Clipboard {
+            requests: self.requests.clone(),
+        }
+    }
+
+    fn request(&self, request: Request) {
+        // the event thread only stops after this one does
+        let _ = self.requests.send(request);
+    }
+}
+
+pub struct Clipboard {
+    requests: Sender<Request>,
+}
+
+impl Clipboard {
+    /// The text in the clipboard, waiting for the event thread to read it.
+    pub fn get(&self) -> Option<String> {
+        let (sender, receiver) = mpsc::channel();
+        self.requests.send(Request::GetClipboard(sender)).ok()?;
+        receiver.recv_timeout(CLIPBOARD_TIMEOUT).ok().flatten()
+    }
+
+    pub fn set(&self, text: &str) {
+        let _ = self.requests.send(Request::SetClipboard(text.to_string()));
+    }
+}
+
+/// Runs `render` on a new thread, with events of `window` pumped from this
+/// one until it returns.
+pub fn run<R, F>(sdl: &Sdl, window: &mut Window, events: &mut EventPump, render: F) -> R
+where
+    R: Send,
+    F: FnOnce(WindowProxy) -> R + Send,
+{
+    let (event_sender, event_receiver) = mpsc::channel();
+    let (request_sender, request_receiver) = mpsc::channel();
+    let mut state = WindowState::default();
+    state.update(window, events);
+    let state = Arc::new(Mutex::new(state));
+    let proxy = WindowProxy {
+        id: window.id(),
+        state: state.clone(),
+        events: event_receiver,
+        requests: request_sender,
+    };
+    let mouse = sdl.mouse();
+    let clipboard = window.subsystem().clipboard();
+    // kept alive while it's the cursor
+    let mut cursor: Option<(MouseCursor, Cursor)> = None;
+
+    std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new()
+            .name("Render".to_string())
+            .spawn_scoped(scope, move || render(proxy))
+            .expect("Error starting the render thread");
+        while !thread.is_finished() {
+            let mut pumped: Vec<Event> = events.wait_event_timeout(WAIT_MS).into_iter().collect();
+            pumped.extend(events.poll_iter());
+            // the state is up to date by the time the events arrive
+            if !pumped.is_empty() {
+                state.lock().unwrap().update(window, events);
+            }
+            for event in pumped {
+                let _ = event_sender.send(SentEvent(event));
+            }
+
+            for request in request_receiver.try_iter() {
+                match request {
+                    Request::Title(title) => window
+                        .set_title(&title)
+                        .expect("Error setting window title"),
+                    Request::RelativeMouse(on) => mouse.set_relative_mouse_mode(on),
+                    Request::CaptureMouse(on) => mouse.capture(on),
+                    Request::Cursor(Some(new)) => {
+                        mouse.show_cursor(true);
+                        if cursor.as_ref().map(|(current, _)| *current) != Some(new) {
+                            match Cursor::from_system(system_cursor(new)) {
+                                Ok(sdl_cursor) => {
+                                    sdl_cursor.set();
+                                    cursor = Some((new, sdl_cursor));
+                                }
+                                Err(err) => warn!("Error creating a cursor: {}", err),
+                            }
+                        }
+                    }
+                    Request::Cursor(None) => {
+                        mouse.show_cursor(false);
+                        cursor = None;
+                    }
+                    Request::Size(width, height) => {
+                        if let Err(err) = window.set_size(width, height) {
+                            warn!("Error resizing the window: {}", err);
+                        }
+                    }
+                    Request::Center => {
+                        window.set_position(WindowPos::Centered, WindowPos::Centered)
+                    }
+                    Request::SetClipboard(text) => {
+                        let _ = clipboard.set_clipboard_text(&text);
+                    }
+                    Request::GetClipboard(reply) => {
+                        let text = if clipboard.has_clipboard_text() {
+                            clipboard.clipboard_text().ok()
+                        } else {
+                            None
+                        };
+                        let _ = reply.send(text);
+                    }
+                }
+            }
+        }
+        match thread.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    })
+}
+
+fn system_cursor(cursor: MouseCursor) -> SystemCursor {
+    match cursor {
+        MouseCursor::Arrow => SystemCursor::Arrow,
+        MouseCursor::TextInput => SystemCursor::IBeam,
+        MouseCursor::ResizeAll => SystemCursor::SizeAll,
+        MouseCursor::ResizeNS => SystemCursor::SizeNS,
+        MouseCursor::ResizeEW => SystemCursor::SizeWE,
+        MouseCursor::ResizeNESW => SystemCursor::SizeNESW,
+        MouseCursor::ResizeNWSE => SystemCursor::SizeNWSE,
+        MouseCursor::Hand => SystemCursor::Hand,
+        MouseCursor::NotAllowed => SystemCursor::No,
+    }
+}
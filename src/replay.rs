@@ -0,0 +1,398 @@
+//! Input recording and playback, for `--record` and `--replay`.
+//!
+//! Keyboard, mouse and text input events are written one per line, after
+//! the frame they were handled on and their timestamp, and fed back on the
+//! same frames. Both run from the default settings with the fixed timestep
+//! of golden frames, so a replay sees the same input at the same simulated
+//! time as the recording did, and renders the same frames.
+use sdl2::{
+    event::Event,
+    keyboard::{Keycode, Mod, Scancode},
+    mouse::{MouseButton, MouseState, MouseWheelDirection},
+};
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+pub struct Recorder {
+    writer: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(Self { writer })
+    }
+
+    /// Writes `event`, handled on `frame`, if it's input that can be replayed.
+    pub fn record(&mut self, frame: u32, event: &Event) -> io::Result<()> {
+        match to_line(event) {
+            Some(line) => {
+                let timestamp = event.get_timestamp();
+                writeln!(self.writer, "{} {} {}", frame, timestamp, line)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Input of a recording, and what it leaves pressed.
+pub struct Replay {
+    events: VecDeque<(u32, Event)>,
+    keys: HashSet<Scancode>,
+    mouse: (i32, i32),
+    buttons: [bool; 5],
+}
+
+impl Replay {
+    /// Reads a recording, with its events sent to the window of `window_id`.
+    pub fn load<P: AsRef<Path>>(path: P, window_id: u32) -> io::Result<Self> {
+        let mut events = VecDeque::new();
+        for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = parse_line(&line, window_id).ok_or_else(|| {
+                let message = format!("invalid event on line {}: {}", i + 1, line);
+                io::Error::new(io::ErrorKind::InvalidData, message)
+            })?;
+            events.push_back(event);
+        }
+        Ok(Self {
+            events,
+            keys: HashSet::new(),
+            mouse: (0, 0),
+            buttons: [false; 5],
+        })
+    }
+
+    /// Events handled on `frame`, which must come after the previous one.
+    pub fn events(&mut self, frame: u32) -> Vec<Event> {
+        let mut events = Vec::new();
+        while self.events.front().is_some_and(|&(f, _)| f <= frame) {
+            let (_, event) = self.events.pop_front().unwrap();
+            self.track(&event);
+            events.push(event);
+        }
+        events
+    }
+
+    /// Unset once every event has been handed out.
+    pub fn is_playing(&self) -> bool {
+        !self.events.is_empty()
+    }
+
+    /// Whether `key` is held, in place of the keyboard state of SDL.
+    pub fn is_pressed(&self, key: Scancode) -> bool {
+        self.keys.contains(&key)
+    }
+
+    /// Position of the cursor in the window.
+    pub fn mouse(&self) -> (i32, i32) {
+        self.mouse
+    }
+
+    /// Left, right, middle, X1 and X2 buttons held, in the order of imgui.
+    pub fn buttons(&self) -> [bool; 5] {
+        self.buttons
+    }
+
+    fn track(&mut self, event: &Event) {
+        let button = |button| match button {
+            MouseButton::Left => Some(0),
+            MouseButton::Right => Some(1),
+            MouseButton::Middle => Some(2),
+            MouseButton::X1 => Some(3),
+            MouseButton::X2 => Some(4),
+            MouseButton::Unknown => None,
+        };
+        match *event {
+            Event::KeyDown {
+                scancode: Some(key),
+                ..
+            } => {
+                self.keys.insert(key);
+            }
+            Event::KeyUp {
+                scancode: Some(key),
+                ..
+            } => {
+                self.keys.remove(&key);
+            }
+            Event::MouseMotion { x, y, .. } => self.mouse = (x, y),
+            Event::MouseButtonDown {
+                mouse_btn, x, y, ..
+            } => {
+                self.mouse = (x, y);
+                if let Some(i) = button(mouse_btn) {
+                    self.buttons[i] = true;
+                }
+            }
+            Event::MouseButtonUp {
+                mouse_btn, x, y, ..
+            } => {
+                self.mouse = (x, y);
+                if let Some(i) = button(mouse_btn) {
+                    self.buttons[i] = false;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The fields of `event` after its timestamp, if it's replayable input.
+fn to_line(event: &Event) -> Option<String> {
+    let line = match event {
+        Event::KeyDown {
+            scancode: Some(key),
+            keymod,
+            repeat,
+            ..
+        } => format!("key_down {} {} {}", *key as i32, keymod.bits(), repeat),
+        Event::KeyUp {
+            scancode: Some(key),
+            keymod,
+            repeat,
+            ..
+        } => format!("key_up {} {} {}", *key as i32, keymod.bits(), repeat),
+        Event::TextInput { text, .. } => format!("text {}", text),
+        Event::MouseMotion {
+            mousestate,
+            x,
+            y,
+            xrel,
+            yrel,
+            ..
+        } => format!(
+            "mouse_motion {} {} {} {} {}",
+            mousestate.to_sdl_state(),
+            x,
+            y,
+            xrel,
+            yrel
+        ),
+        Event::MouseButtonDown {
+            mouse_btn,
+            clicks,
+            x,
+            y,
+            ..
+        } => format!("mouse_down {} {} {} {}", *mouse_btn as u8, clicks, x, y),
+        Event::MouseButtonUp {
+            mouse_btn,
+            clicks,
+            x,
+            y,
+            ..
+        } => format!("mouse_up {} {} {} {}", *mouse_btn as u8, clicks, x, y),
+        Event::MouseWheel {
+            x, y, direction, ..
+        } => format!("mouse_wheel {} {} {}", x, y, direction.to_ll()),
+        _ => return None,
+    };
+    Some(line)
+}
+
+/// Frame and event of a line written by [`Recorder::record`].
+fn parse_line(line: &str, window_id: u32) -> Option<(u32, Event)> {
+    let mut fields = line.splitn(4, ' ');
+    let frame = fields.next()?.parse().ok()?;
+    let timestamp = fields.next()?.parse().ok()?;
+    let kind = fields.next()?;
+    let rest = fields.next().unwrap_or("");
+    if kind == "text" {
+        let text = rest.to_string();
+        return Some((
+            frame,
+            Event::TextInput {
+                timestamp,
+                window_id,
+                text,
+            },
+        ));
+    }
+
+    let values: Vec<i64> = rest
+        .split_whitespace()
+        .map(|value| match value {
+            "true" => Some(1),
+            "false" => Some(0),
+            _ => value.parse().ok(),
+        })
+        .collect::<Option<_>>()?;
+    let event = match (kind, values.as_slice()) {
+        ("key_down", &[key, keymod, repeat]) | ("key_up", &[key, keymod, repeat]) => {
+            let scancode = Scancode::from_i32(key as i32)?;
+            let keycode = Keycode::from_scancode(scancode);
+            let keymod = Mod::from_bits_truncate(keymod as u16);
+            let repeat = repeat != 0;
+            if kind == "key_down" {
+                Event::KeyDown {
+                    timestamp,
+                    window_id,
+                    keycode,
+                    scancode: Some(scancode),
+                    keymod,
+                    repeat,
+                }
+            } else {
+                Event::KeyUp {
+                    timestamp,
+                    window_id,
+                    keycode,
+                    scancode: Some(scancode),
+                    keymod,
+                    repeat,
+                }
+            }
+        }
+        ("mouse_motion", &[state, x, y, xrel, yrel]) => Event::MouseMotion {
+            timestamp,
+            window_id,
+            which: 0,
+            mousestate: MouseState::from_sdl_state(state as u32),
+            x: x as i32,
+            y: y as i32,
+            xrel: xrel as i32,
+            yrel: yrel as i32,
+        },
+        ("mouse_down", &[button, clicks, x, y]) | ("mouse_up", &[button, clicks, x, y]) => {
+            let mouse_btn = MouseButton::from_ll(button as u8);
+            let (clicks, x, y) = (clicks as u8, x as i32, y as i32);
+            if kind == "mouse_down" {
+                Event::MouseButtonDown {
+                    timestamp,
+                    window_id,
+                    which: 0,
+                    mouse_btn,
+                    clicks,
+                    x,
+                    y,
+                }
+            } else {
+                Event::MouseButtonUp {
+                    timestamp,
+                    window_id,
+                    which: 0,
+                    mouse_btn,
+                    clicks,
+                    x,
+                    y,
+                }
+            }
+        }
+        ("mouse_wheel", &[x, y, direction]) => Event::MouseWheel {
+            timestamp,
+            window_id,
+            which: 0,
+            x: x as i32,
+            y: y as i32,
+            direction: MouseWheelDirection::from_ll(direction as u32),
+        },
+        _ => return None,
+    };
+    Some((frame, event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_lines_parse_back() {
+        let scancode = Scancode::W;
+        let events = [
+            Event::KeyDown {
+                timestamp: 10,
+                window_id: 1,
+                keycode: Keycode::from_scancode(scancode),
+                scancode: Some(scancode),
+                keymod: Mod::LSHIFTMOD | Mod::NUMMOD,
+                repeat: true,
+            },
+            Event::KeyUp {
+                timestamp: 11,
+                window_id: 1,
+                keycode: Keycode::from_scancode(scancode),
+                scancode: Some(scancode),
+                keymod: Mod::NOMOD,
+                repeat: false,
+            },
+            Event::TextInput {
+                timestamp: 12,
+                window_id: 1,
+                text: "a b ".to_string(),
+            },
+            Event::MouseMotion {
+                timestamp: 13,
+                window_id: 1,
+                which: 0,
+                mousestate: MouseState::from_sdl_state(0b101),
+                x: 320,
+                y: 240,
+                xrel: -3,
+                yrel: 7,
+            },
+            Event::MouseButtonDown {
+                timestamp: 14,
+                window_id: 1,
+                which: 0,
+                mouse_btn: MouseButton::Right,
+                clicks: 2,
+                x: 1,
+                y: 2,
+            },
+            Event::MouseButtonUp {
+                timestamp: 15,
+                window_id: 1,
+                which: 0,
+                mouse_btn: MouseButton::X2,
+                clicks: 1,
+                x: 3,
+                y: 4,
+            },
+            Event::MouseWheel {
+                timestamp: 16,
+                window_id: 1,
+                which: 0,
+                x: 0,
+                y: -1,
+                direction: MouseWheelDirection::Flipped,
+            },
+        ];
+        for (frame, event) in events.iter().enumerate() {
+            let line = format!(
+                "{} {} {}",
+                frame,
+                event.get_timestamp(),
+                to_line(event).unwrap()
+            );
+            assert_eq!(parse_line(&line, 1), Some((frame as u32, event.clone())));
+        }
+
+        let quit = Event::Quit { timestamp: 0 };
+        assert_eq!(to_line(&quit), None);
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        for line in [
+            "",
+            "1",
+            "1 2",
+            "x 2 key_down 26 0 false",
+            "1 2 key_down 26 0",
+            "1 2 key_down 26 0 maybe",
+            "1 2 key_down 9999 0 false",
+            "1 2 mouse_wheel 0 1",
+            "1 2 jump 1 2 3",
+        ] {
+            assert_eq!(parse_line(line, 1), None, "{:?}", line);
+        }
+    }
+}
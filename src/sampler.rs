@@ -0,0 +1,160 @@
+//! Sampler cache and live sampler settings.
+//!
+//! Samplers are immutable, so changing how a texture is sampled means creating
+//! another one (and the bind groups using it). The cache keeps every sampler
+//! created so far, so going back to previous settings doesn't create new ones.
+use imgui::{im_str, ComboBox, ImString, Slider, Ui};
+use std::num::NonZeroU8;
+use wgpu::{AddressMode, Device, FilterMode, Sampler, SamplerDescriptor};
+
+/// Largest anisotropy clamp accepted by wgpu.
+pub const MAX_ANISOTROPY: u8 = 16;
+
+pub const ADDRESS_MODES: [AddressMode; 3] = [
+    AddressMode::ClampToEdge,
+    AddressMode::Repeat,
+    AddressMode::MirrorRepeat,
+];
+
+pub const FILTER_MODES: [FilterMode; 2] = [FilterMode::Nearest, FilterMode::Linear];
+
+/// Sampling settings of a 2D texture, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SamplerSettings {
+    pub address_mode_u: AddressMode,
+    pub address_mode_v: AddressMode,
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mipmap_filter: FilterMode,
+    /// Power of two up to `MAX_ANISOTROPY`, 1 disables anisotropic filtering.
+    /// Ignored when the adapter doesn't support it.
+    pub anisotropy: u8,
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            anisotropy: 1,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+        }
+    }
+}
+
+impl SamplerSettings {
+    pub fn ui(&mut self, ui: &Ui) {
+        let names: Vec<_> = ADDRESS_MODES
+            .iter()
+            .map(|&m| ImString::new(address_mode_name(m)))
+            .collect();
+        let names: Vec<_> = names.iter().collect();
+        let address_mode = |label, mode: &mut AddressMode| {
+            let mut index = ADDRESS_MODES.iter().position(|m| m == mode).unwrap();
+            if ComboBox::new(label).build_simple_string(ui, &mut index, &names) {
+                *mode = ADDRESS_MODES[index];
+            }
+        };
+        address_mode(im_str!("Address U"), &mut self.address_mode_u);
+        address_mode(im_str!("Address V"), &mut self.address_mode_v);
+        let names: Vec<_> = FILTER_MODES
+            .iter()
+            .map(|&m| ImString::new(filter_mode_name(m)))
+            .collect();
+        let names: Vec<_> = names.iter().collect();
+        let filter_mode = |label, mode: &mut FilterMode| {
+            let mut index = FILTER_MODES.iter().position(|m| m == mode).unwrap();
+            if ComboBox::new(label).build_simple_string(ui, &mut index, &names) {
+                *mode = FILTER_MODES[index];
+            }
+        };
+        filter_mode(im_str!("Mag filter"), &mut self.mag_filter);
+        filter_mode(im_str!("Min filter"), &mut self.min_filter);
+        filter_mode(im_str!("Mipmap filter"), &mut self.mipmap_filter);
+        // powers of two only
+        let mut log2 = self.anisotropy.trailing_zeros() as i32;
+        let max_log2 = MAX_ANISOTROPY.trailing_zeros() as i32;
+        Slider::new(im_str!("Anisotropy"))
+            .range(0..=max_log2)
+            .display_format(&im_str!("{}x", self.anisotropy))
+            .build(ui, &mut log2);
+        self.anisotropy = 1 << log2;
+        Slider::new(im_str!("LOD min"))
+            .range(0.0..=8.0)
+            .build(ui, &mut self.lod_min_clamp);
+        Slider::new(im_str!("LOD max"))
+            .range(0.0..=8.0)
+            .build(ui, &mut self.lod_max_clamp);
+        self.lod_max_clamp = self.lod_max_clamp.max(self.lod_min_clamp);
+    }
+}
+
+pub fn address_mode_name(mode: AddressMode) -> &'static str {
+    match mode {
+        AddressMode::ClampToEdge => "Clamp to edge",
+        AddressMode::Repeat => "Repeat",
+        AddressMode::MirrorRepeat => "Mirror repeat",
+    }
+}
+
+pub fn filter_mode_name(mode: FilterMode) -> &'static str {
+    match mode {
+        FilterMode::Nearest => "Nearest",
+        FilterMode::Linear => "Linear",
+    }
+}
+
+pub struct SamplerCache {
+    label: &'static str,
+    // a handful of entries at most, not worth hashing floats for
+    samplers: Vec<(SamplerSettings, Sampler)>,
+}
+
+impl SamplerCache {
+    pub fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            samplers: Vec::new(),
+        }
+    }
+
+    /// Number of samplers created so far.
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+
+    /// Returns the sampler with the given `settings`, creating it the first
+    /// time they are used.
+    pub fn get(&mut self, device: &Device, settings: &SamplerSettings) -> &Sampler {
+        let index = match self.samplers.iter().position(|(s, _)| s == settings) {
+            Some(index) => index,
+            None => {
+                let sampler = device.create_sampler(&SamplerDescriptor {
+                    label: Some(self.label),
+                    address_mode_u: settings.address_mode_u,
+                    address_mode_v: settings.address_mode_v,
+                    mag_filter: settings.mag_filter,
+                    min_filter: settings.min_filter,
+                    mipmap_filter: settings.mipmap_filter,
+                    lod_min_clamp: settings.lod_min_clamp,
+                    lod_max_clamp: settings.lod_max_clamp,
+                    anisotropy_clamp: NonZeroU8::new(settings.anisotropy).filter(|&a| a.get() > 1),
+                    ..Default::default()
+                });
+                self.samplers.push((*settings, sampler));
+                self.samplers.len() - 1
+            }
+        };
+        &self.samplers[index].1
+    }
+}
@@ -0,0 +1,336 @@
+//! GPU reduction and exclusive prefix sum of `u32` values in storage buffers.
+//!
+//! Both work on workgroups of 256 values, each producing one value per
+//! workgroup into the next level, until a level fits in a single workgroup.
+//! The reduction stops there with the total, the scan goes back down adding
+//! the scanned totals of the workgroups to the values of each one. Every
+//! level is a dispatch, with its length at a dynamic offset of a uniform
+//! buffer.
+use crate::{
+    draw_stats,
+    memory::{self, Category, Tracked},
+    readback, shaders,
+};
+use wgpu::{
+    util::{make_spirv, BufferInitDescriptor},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferDescriptor, BufferSize,
+    BufferUsage, CommandEncoder, CommandEncoderDescriptor, ComputePipeline,
+    ComputePipelineDescriptor, Device, PipelineLayoutDescriptor, ProgrammableStageDescriptor,
+    Queue, ShaderModule, ShaderStage, BIND_BUFFER_ALIGNMENT,
+};
+
+/// Must match the `local_size_x` of the shaders.
+const WORKGROUP_SIZE: u32 = 256;
+
+/// Largest number of values that can be reduced or scanned.
+pub const MAX_LEN: u32 = 1 << 22;
+
+/// Bytes of the uniform block with the length of a level.
+const LENGTH_SIZE: u64 = 16;
+
+/// Levels of workgroup totals for `MAX_LEN` values, the last holding one.
+const MAX_LEVELS: usize = 3;
+
+pub struct Scan {
+    /// Length of every level, `BIND_BUFFER_ALIGNMENT` bytes apart.
+    lengths: Tracked<Buffer>,
+    /// Totals of the workgroups of the level before, all sized for `MAX_LEN`.
+    levels: Vec<Tracked<Buffer>>,
+    layout: BindGroupLayout,
+    reduce: ComputePipeline,
+    scan: ComputePipeline,
+    add: ComputePipeline,
+}
+
+impl Scan {
+    pub fn new(device: &Device) -> Self {
+        let lengths = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Scan lengths"),
+                size: MAX_LEVELS as u64 * BIND_BUFFER_ALIGNMENT,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let mut len = MAX_LEN;
+        let levels = (0..MAX_LEVELS)
+            .map(|i| {
+                len = len.div_ceil(WORKGROUP_SIZE);
+                memory::create_buffer(
+                    device,
+                    Category::Storage,
+                    &BufferDescriptor {
+                        label: Some(&format!("Scan level {}", i)),
+                        size: len as u64 * 4,
+                        usage: BufferUsage::STORAGE | BufferUsage::COPY_SRC,
+                        mapped_at_creation: false,
+                    },
+                )
+            })
+            .collect();
+
+        let storage = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStage::COMPUTE,
+            ty: BindingType::StorageBuffer {
+                dynamic: false,
+                min_binding_size: None,
+                readonly: false,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Scan bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: true,
+                        min_binding_size: BufferSize::new(LENGTH_SIZE),
+                    },
+                    count: None,
+                },
+                storage(1),
+                storage(2),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Scan pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, module: &ShaderModule| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                compute_stage: ProgrammableStageDescriptor {
+                    module,
+                    entry_point: "main",
+                },
+            })
+        };
+        let reduce_module = device.create_shader_module(make_spirv(shaders::REDUCE_COMP));
+        let scan_module = device.create_shader_module(make_spirv(shaders::SCAN_COMP));
+        let add_module = device.create_shader_module(make_spirv(shaders::SCAN_ADD_COMP));
+
+        Self {
+            lengths,
+            levels,
+            reduce: pipeline("Reduce pipeline", &reduce_module),
+            scan: pipeline("Scan pipeline", &scan_module),
+            add: pipeline("Scan add pipeline", &add_module),
+            layout,
+        }
+    }
+
+    /// Records the sum of the first `len` `values`, returning the buffer it
+    /// ends up first in. Lengths are uploaded through `queue`, so there can
+    /// only be one reduction or scan per submission.
+    pub fn reduce<'a>(
+        &'a self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        values: &Buffer,
+        len: u32,
+    ) -> &'a Buffer {
+        let (lengths, bind_groups) = self.levels(device, queue, values, len);
+        for (i, (len, bind_group)) in lengths.iter().zip(&bind_groups).enumerate() {
+            let mut pass = encoder.begin_compute_pass();
+            pass.push_debug_group("Reduce level");
+            pass.set_pipeline(&self.reduce);
+            let offset = (i as u64 * BIND_BUFFER_ALIGNMENT) as u32;
+            pass.set_bind_group(0, bind_group, &[offset]);
+            pass.dispatch(workgroups(*len), 1, 1);
+            pass.pop_debug_group();
+        }
+        &self.levels[bind_groups.len() - 1]
+    }
+
+    /// Records the exclusive prefix sum of the first `len` `values`, in
+    /// place. Like [`Scan::reduce`] there can only be one per submission.
+    pub fn exclusive_scan(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        values: &Buffer,
+        len: u32,
+    ) {
+        let (lengths, bind_groups) = self.levels(device, queue, values, len);
+        let levels = lengths.iter().zip(&bind_groups).enumerate();
+        // a pass per dispatch, so each one sees the writes of the previous one
+        for (i, (len, bind_group)) in levels.clone() {
+            let mut pass = encoder.begin_compute_pass();
+            pass.push_debug_group("Scan level");
+            pass.set_pipeline(&self.scan);
+            let offset = (i as u64 * BIND_BUFFER_ALIGNMENT) as u32;
+            pass.set_bind_group(0, bind_group, &[offset]);
+            pass.dispatch(workgroups(*len), 1, 1);
+            pass.pop_debug_group();
+        }
+        // the last level is a single workgroup, already scanned
+        for (i, (len, bind_group)) in levels.rev().skip(1) {
+            let mut pass = encoder.begin_compute_pass();
+            pass.push_debug_group("Scan add level");
+            pass.set_pipeline(&self.add);
+            let offset = (i as u64 * BIND_BUFFER_ALIGNMENT) as u32;
+            pass.set_bind_group(0, bind_group, &[offset]);
+            pass.dispatch(workgroups(*len), 1, 1);
+            pass.pop_debug_group();
+        }
+    }
+
+    /// Uploads the length of every level down to a single workgroup, and
+    /// returns them with the bind groups reading each one and writing the
+    /// totals of its workgroups into the next.
+    fn levels(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        values: &Buffer,
+        len: u32,
+    ) -> (Vec<u32>, Vec<BindGroup>) {
+        assert!(len <= MAX_LEN, "Scanning more than {} values", MAX_LEN);
+        let mut lengths = vec![len];
+        while *lengths.last().unwrap() > WORKGROUP_SIZE {
+            lengths.push(lengths.last().unwrap().div_ceil(WORKGROUP_SIZE));
+        }
+        let mut data = vec![0; lengths.len() * BIND_BUFFER_ALIGNMENT as usize];
+        for (len, chunk) in lengths
+            .iter()
+            .zip(data.chunks_mut(BIND_BUFFER_ALIGNMENT as _))
+        {
+            chunk[..4].copy_from_slice(&len.to_ne_bytes());
+        }
+        draw_stats::write_buffer(queue, &self.lengths, 0, &data);
+
+        let inputs = std::iter::once(values).chain(self.levels.iter().map(|level| &**level));
+        let bind_groups = inputs
+            .zip(&self.levels)
+            .take(lengths.len())
+            .map(|(input, output)| {
+                device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("Scan bind group"),
+                    layout: &self.layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::Buffer(self.lengths.slice(..LENGTH_SIZE)),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::Buffer(input.slice(..)),
+                        },
+                        BindGroupEntry {
+                            binding: 2,
+                            resource: BindingResource::Buffer(output.slice(..)),
+                        },
+                    ],
+                })
+            })
+            .collect();
+        (lengths, bind_groups)
+    }
+
+    /// Reduces and scans `len` pseudo-random values and checks them against
+    /// the same on the CPU.
+    pub fn self_test(&self, device: &Device, queue: &Queue, len: u32) -> bool {
+        // xorshift, small enough for the sums not to overflow
+        let mut state = 0x1234_5678u32;
+        let values: Vec<u32> = (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state % 1000
+            })
+            .collect();
+        let buffer = memory::create_buffer_init(
+            device,
+            Category::Storage,
+            &BufferInitDescriptor {
+                label: Some("Scan test values"),
+                contents: bytemuck::cast_slice(&values),
+                usage: BufferUsage::STORAGE | BufferUsage::COPY_SRC,
+            },
+        );
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Reduce test"),
+        });
+        let total = self.reduce(device, queue, &mut encoder, &buffer, len);
+        queue.submit(Some(encoder.finish()));
+        let sum: Vec<u32> =
+            futures::executor::block_on(readback::read_buffer(device, queue, total, 1));
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Scan test"),
+        });
+        self.exclusive_scan(device, queue, &mut encoder, &buffer, len);
+        queue.submit(Some(encoder.finish()));
+        let scanned: Vec<u32> =
+            futures::executor::block_on(readback::read_buffer(device, queue, &buffer, len as _));
+
+        sum[0] == reduce_cpu(&values) && scanned == exclusive_scan_cpu(&values)
+    }
+}
+
+/// Workgroups of a level of `len` values, at least one so the total of an
+/// empty level is still written.
+fn workgroups(len: u32) -> u32 {
+    len.div_ceil(WORKGROUP_SIZE).max(1)
+}
+
+/// Sum of `values`, what [`Scan::reduce`] computes.
+fn reduce_cpu(values: &[u32]) -> u32 {
+    values.iter().fold(0, |sum, &value| sum.wrapping_add(value))
+}
+
+/// Exclusive prefix sum of `values`, what [`Scan::exclusive_scan`] computes.
+fn exclusive_scan_cpu(values: &[u32]) -> Vec<u32> {
+    let mut sum = 0u32;
+    values
+        .iter()
+        .map(|&value| {
+            let before = sum;
+            sum = sum.wrapping_add(value);
+            before
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{exclusive_scan_cpu, reduce_cpu, Scan, MAX_LEN};
+    use crate::adapter;
+
+    #[test]
+    fn cpu_reference() {
+        assert_eq!(reduce_cpu(&[]), 0);
+        assert_eq!(reduce_cpu(&[3, 1, 4, 1, 5]), 14);
+        assert_eq!(exclusive_scan_cpu(&[]), Vec::<u32>::new());
+        assert_eq!(exclusive_scan_cpu(&[3, 1, 4, 1, 5]), vec![0, 3, 4, 8, 9]);
+    }
+
+    #[test]
+    fn scans_like_the_cpu() {
+        let (device, queue) = match adapter::headless_device() {
+            Some(device) => device,
+            None => {
+                eprintln!("No GPU adapter, skipping");
+                return;
+            }
+        };
+
+        let scan = Scan::new(&device);
+        // around the size of a workgroup and of two levels of them
+        for &len in &[1, 255, 256, 257, 1000, 65536, 65537, 100_000, MAX_LEN] {
+            assert!(scan.self_test(&device, &queue, len), "{} values", len);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{material::PbrMaterial, physics::BodyKind};
+use glam::{Mat4, Quat, Vec3};
+
+/// Object of the demo scene.
+///
+/// Objects spin around an axis while they pulse in size, unless they have a
+/// rigid body.
+#[derive(Clone)]
+pub struct Object {
+    pub name: String,
+    /// Index of the mesh drawn by the object.
+    pub mesh: usize,
+    pub position: Vec3,
+    /// Orientation, before spinning. Only rigid bodies change it.
+    pub rotation: Quat,
+    /// Scale along the axes of the object, before the pulse.
+    pub scale: Vec3,
+    /// Rotation axis in object space.
+    pub axis: Vec3,
+    /// Rotation speed in radians per second.
+    pub spin: f32,
+    /// Amplitude of the size pulse, relative to the original size.
+    pub pulse: f32,
+    pub selected: bool,
+    pub material: PbrMaterial,
+    /// Names and weights of the morph targets of the mesh.
+    pub morph_targets: Vec<String>,
+    pub morph_weights: Vec<f32>,
+    pub body: BodyKind,
+    angle: f32,
+    time: f32,
+}
+
+impl Object {
+    pub fn new(name: &str, mesh: usize, position: Vec3, axis: Vec3, spin: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            mesh,
+            position,
+            rotation: Quat::identity(),
+            scale: Vec3::one(),
+            axis: axis.normalize(),
+            spin,
+            pulse: 0.25,
+            selected: false,
+            material: PbrMaterial::default(),
+            morph_targets: Vec::new(),
+            morph_weights: Vec::new(),
+            body: BodyKind::None,
+            angle: 0.0,
+            time: 0.0,
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        // rigid bodies are moved by the physics instead
+        if self.body != BodyKind::None {
+            return;
+        }
+        self.angle += self.spin * dt;
+        self.time += dt;
+    }
+
+    fn lerp(&self, other: &Self, alpha: f32) -> Self {
+        Self {
+            position: self.position.lerp(other.position, alpha),
+            rotation: self.rotation.slerp(other.rotation, alpha),
+            angle: self.angle + (other.angle - self.angle) * alpha,
+            time: self.time + (other.time - self.time) * alpha,
+            ..other.clone()
+        }
+    }
+
+    pub fn model(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
+            * self.local()
+    }
+
+    /// Spin and pulse of the object, the part of the model a rigid body
+    /// doesn't move.
+    pub fn local(&self) -> Mat4 {
+        let scale = 1.0 + self.pulse * (self.time * 2.0).sin();
+        Mat4::from_axis_angle(self.axis, self.angle) * Mat4::from_scale(Vec3::splat(scale))
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Scene {
+    pub objects: Vec<Object>,
+}
+
+impl Scene {
+    /// Advances the simulation by one fixed step.
+    pub fn update(&mut self, dt: f32) {
+        for object in &mut self.objects {
+            object.update(dt);
+        }
+    }
+
+    /// Interpolates the simulated state between `self` and `other`.
+    ///
+    /// Anything that isn't simulated (names, selection...) is taken from
+    /// `other`, which is expected to be the most recent state.
+    pub fn lerp(&self, other: &Self, alpha: f32) -> Self {
+        let objects = self
+            .objects
+            .iter()
+            .zip(&other.objects)
+            .map(|(a, b)| a.lerp(b, alpha))
+            .collect();
+        Self { objects }
+    }
+
+    /// Selects only the object at `index`, or clears the selection if `None`.
+    pub fn select(&mut self, index: Option<usize>) {
+        for (i, object) in self.objects.iter_mut().enumerate() {
+            object.selected = Some(i) == index;
+        }
+    }
+}
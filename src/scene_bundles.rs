@@ -0,0 +1,51 @@
+//! Render bundles of the scene draws, recorded once and executed every frame
+//! until the scene changes.
+//!
+//! The bundles only reference the bind groups of the objects, so moving them,
+//! which writes their uniforms, keeps the bundles valid. They're recorded
+//! again when what's drawn changes (culled objects, levels of detail,
+//! pipelines), found by comparing a key of the draws with the one they were
+//! recorded for, and when the resources they reference are created again,
+//! which has to be told with [`SceneBundles::invalidate`].
+use crate::draw_stats::FinishedBundle;
+
+pub struct SceneBundles<K> {
+    pub enabled: bool,
+    key: Option<K>,
+    bundles: Vec<FinishedBundle>,
+    /// Times the bundles were recorded.
+    pub recordings: u32,
+}
+
+impl<K> Default for SceneBundles<K> {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key: None,
+            bundles: Vec::new(),
+            recordings: 0,
+        }
+    }
+}
+
+impl<K: PartialEq> SceneBundles<K> {
+    /// Records the bundles again next time, after the objects or the
+    /// resources they're drawn with are created again.
+    pub fn invalidate(&mut self) {
+        self.key = None;
+    }
+
+    /// The bundles drawing what `key` describes, made by `record` if the last
+    /// ones were of another key.
+    pub fn get<F>(&mut self, key: K, record: F) -> &[FinishedBundle]
+    where
+        F: FnOnce() -> Vec<FinishedBundle>,
+    {
+        if self.key.as_ref() != Some(&key) {
+            self.bundles = record();
+            self.key = Some(key);
+            self.recordings += 1;
+        }
+        &self.bundles
+    }
+}
@@ -0,0 +1,671 @@
+//! Scenes saved to JSON files, and opened again.
+//!
+//! A scene file has the objects, with their transforms, animation, material
+//! and rigid body, the lights and the camera. Meshes are referred to by name if
+//! they're built in, or by the file they were loaded from, along with the
+//! index of the primitive for glTF files. Texture layers are the built-in ones
+//! by index, the image files they were opened from, or the textures of the
+//! material of the glTF primitive of the mesh. Skins, morph targets and clips
+//! aren't saved, glTF meshes are opened in their rest pose.
+//!
+//! Relative paths are looked up next to the scene file first, then in the
+//! working directory.
+use crate::{
+    camera::CameraPose,
+    lights::Light,
+    material::{AlphaMode, PbrMaterial, TextureSlot, TEXTURE_SLOTS},
+    physics::BodyKind,
+    scene::Object,
+};
+use glam::{Quat, Vec3};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Names of the meshes there at startup, in order.
+pub const BUILTIN_MESHES: [&str; 3] = ["triangle", "cube", "plane"];
+
+const VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneFileError::Io(err) => write!(f, "{}", err),
+            SceneFileError::Json(err) => write!(f, "{}", err),
+            SceneFileError::Invalid(what) => write!(f, "Invalid scene file: {}", what),
+        }
+    }
+}
+
+fn invalid<T>(what: impl Into<String>) -> Result<T, SceneFileError> {
+    Err(SceneFileError::Invalid(what.into()))
+}
+
+/// Where a mesh came from.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum MeshSource {
+    /// Index of [`BUILTIN_MESHES`].
+    Builtin(usize),
+    Obj(PathBuf),
+    Gltf {
+        path: PathBuf,
+        primitive: usize,
+    },
+}
+
+/// Where a layer of the material arrays came from.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum TextureSource {
+    /// Layer there at startup.
+    Builtin(u32),
+    Image(PathBuf),
+    /// The texture of the same slot in the material of the glTF primitive the
+    /// mesh was loaded from.
+    Mesh,
+}
+
+/// Sources of the meshes and layers of the running app, to refer to them
+/// from scene files.
+pub struct Sources {
+    /// Source of each mesh, by index.
+    pub meshes: Vec<MeshSource>,
+    /// Material of each mesh loaded from a glTF file, with its layers.
+    pub mesh_materials: Vec<Option<PbrMaterial>>,
+    /// Layers of the sRGB and the linear array there at startup.
+    pub builtin_colors: u32,
+    pub builtin_linear: u32,
+    /// Layers opened from image files, by whether they're of the sRGB array
+    /// and by index.
+    pub images: HashMap<(bool, u32), PathBuf>,
+}
+
+impl Sources {
+    /// Sources of the built-in meshes and the layers of the arrays at startup.
+    pub fn new(builtin_colors: u32, builtin_linear: u32) -> Self {
+        Self {
+            meshes: (0..BUILTIN_MESHES.len()).map(MeshSource::Builtin).collect(),
+            mesh_materials: vec![None; BUILTIN_MESHES.len()],
+            builtin_colors,
+            builtin_linear,
+            images: HashMap::new(),
+        }
+    }
+
+    /// Records the source of the next mesh, and the material it came with.
+    pub fn add_mesh(&mut self, source: MeshSource, material: Option<PbrMaterial>) {
+        self.meshes.push(source);
+        self.mesh_materials.push(material);
+    }
+
+    /// Index of the mesh loaded from `source`, if it was.
+    pub fn mesh(&self, source: &MeshSource) -> Option<usize> {
+        self.meshes.iter().position(|s| s == source)
+    }
+
+    /// Layer opened from the image at `path`, if it was.
+    pub fn image(&self, color: bool, path: &Path) -> Option<u32> {
+        self.images
+            .iter()
+            .find(|((c, _), p)| *c == color && p.as_path() == path)
+            .map(|((_, layer), _)| *layer)
+    }
+
+    fn builtin_layers(&self, slot: TextureSlot) -> u32 {
+        if slot.is_color() {
+            self.builtin_colors
+        } else {
+            self.builtin_linear
+        }
+    }
+
+    fn texture(&self, mesh: usize, slot: TextureSlot, layer: u32) -> Option<TextureSource> {
+        let from_mesh = self
+            .mesh_material(mesh)
+            .is_some_and(|material| material.texture(slot) == Some(layer));
+        if from_mesh {
+            Some(TextureSource::Mesh)
+        } else if let Some(path) = self.images.get(&(slot.is_color(), layer)) {
+            Some(TextureSource::Image(path.clone()))
+        } else if layer < self.builtin_layers(slot) {
+            Some(TextureSource::Builtin(layer))
+        } else {
+            None
+        }
+    }
+
+    /// Layer of `source` in the slot, `None` if it isn't loaded.
+    pub fn layer(&self, mesh: usize, slot: TextureSlot, source: &TextureSource) -> Option<u32> {
+        match source {
+            TextureSource::Builtin(layer) => {
+                Some(*layer).filter(|&l| l < self.builtin_layers(slot))
+            }
+            TextureSource::Image(path) => self.image(slot.is_color(), path),
+            TextureSource::Mesh => self
+                .mesh_material(mesh)
+                .and_then(|material| material.texture(slot)),
+        }
+    }
+
+    fn mesh_material(&self, mesh: usize) -> Option<&PbrMaterial> {
+        self.mesh_materials.get(mesh)?.as_ref()
+    }
+}
+
+/// Object of a scene file, with the sources of its mesh and textures.
+pub struct ObjectFile {
+    /// The object, with any mesh and without textures.
+    pub object: Object,
+    pub mesh: MeshSource,
+    pub textures: Vec<(TextureSlot, TextureSource)>,
+}
+
+pub struct SceneFile {
+    pub objects: Vec<ObjectFile>,
+    pub lights: Vec<Light>,
+    pub camera: CameraPose,
+    /// Directory of the file it was opened from.
+    dir: Option<PathBuf>,
+}
+
+/// File a scene refers to that doesn't exist.
+pub struct MissingFile {
+    pub path: PathBuf,
+    /// What it is, like `mesh of Cube`.
+    pub what: String,
+    /// Directory of the scene file, if the path is relative.
+    dir: Option<PathBuf>,
+}
+
+impl fmt::Display for MissingFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}) doesn't exist", self.path.display(), self.what)?;
+        if let Some(dir) = &self.dir {
+            let dir = match dir.to_str() {
+                Some("") => Path::new("."),
+                _ => dir,
+            };
+            write!(
+                f,
+                ", it was looked for in {} and in the working directory",
+                dir.display()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl SceneFile {
+    /// Scene of `objects`, whose meshes and textures are in `sources`.
+    /// Textures without a known source are left out.
+    pub fn new(
+        objects: &[Object],
+        lights: &[Light],
+        camera: CameraPose,
+        sources: &Sources,
+    ) -> Self {
+        let objects = objects
+            .iter()
+            .map(|object| {
+                let mut saved = object.clone();
+                saved.selected = false;
+                let mut textures = Vec::new();
+                for &slot in &TEXTURE_SLOTS {
+                    let layer = match saved.material.texture_mut(slot).take() {
+                        Some(layer) => layer,
+                        None => continue,
+                    };
+                    match sources.texture(object.mesh, slot, layer) {
+                        Some(source) => textures.push((slot, source)),
+                        None => warn!(
+                            "Not saving the {} texture of {}, it wasn't opened from a file",
+                            slot.name().to_lowercase(),
+                            object.name
+                        ),
+                    }
+                }
+                ObjectFile {
+                    object: saved,
+                    mesh: sources.meshes[object.mesh].clone(),
+                    textures,
+                }
+            })
+            .collect();
+        Self {
+            objects,
+            lights: lights.to_vec(),
+            camera,
+            dir: None,
+        }
+    }
+
+    /// Meshes and images the scene refers to that aren't there, each once.
+    pub fn missing_files(&self) -> Vec<MissingFile> {
+        let mut missing: Vec<MissingFile> = Vec::new();
+        for file in &self.objects {
+            let name = &file.object.name;
+            let mesh = match &file.mesh {
+                MeshSource::Builtin(_) => None,
+                MeshSource::Obj(path) | MeshSource::Gltf { path, .. } => {
+                    Some((path, format!("mesh of {}", name)))
+                }
+            };
+            let images = file
+                .textures
+                .iter()
+                .filter_map(|(slot, source)| match source {
+                    TextureSource::Image(path) => Some((
+                        path,
+                        format!("{} texture of {}", slot.name().to_lowercase(), name),
+                    )),
+                    _ => None,
+                });
+            for (path, what) in mesh.into_iter().chain(images) {
+                if path.exists() || missing.iter().any(|m| &m.path == path) {
+                    continue;
+                }
+                missing.push(MissingFile {
+                    path: path.clone(),
+                    what,
+                    dir: self.dir.clone().filter(|_| path.is_relative()),
+                });
+            }
+        }
+        missing
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SceneFileError> {
+        let json = SceneJson {
+            version: VERSION,
+            objects: self.objects.iter().map(object_json).collect(),
+            lights: self.lights.clone(),
+            camera: self.camera,
+        };
+        let text = serde_json::to_string_pretty(&json).map_err(SceneFileError::Json)?;
+        std::fs::write(path, text).map_err(SceneFileError::Io)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneFileError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(SceneFileError::Io)?;
+        // the version first, files of other versions may not parse at all
+        let json: Json = serde_json::from_slice(&bytes).map_err(SceneFileError::Json)?;
+        match json["version"].as_u64() {
+            Some(version) if version == VERSION as u64 => {}
+            Some(version) => return invalid(format!("unknown version {}", version)),
+            None => return invalid("missing version"),
+        }
+        let json: SceneJson = serde_json::from_value(json).map_err(SceneFileError::Json)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let objects = json
+            .objects
+            .into_iter()
+            .map(|object| parse_object(object, dir))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            objects,
+            lights: json.lights,
+            camera: json.camera,
+            dir: Some(dir.to_path_buf()),
+        })
+    }
+}
+
+/// Contents of a scene file, as they are written.
+#[derive(Serialize, Deserialize)]
+struct SceneJson {
+    version: u32,
+    #[serde(default)]
+    objects: Vec<ObjectJson>,
+    #[serde(default)]
+    lights: Vec<Light>,
+    #[serde(default = "default_camera")]
+    camera: CameraPose,
+}
+
+fn default_camera() -> CameraPose {
+    CameraPose {
+        position: Vec3::new(0.0, 0.0, 3.0),
+        yaw: 0.0,
+        pitch: 0.0,
+        fov_y: std::f32::consts::FRAC_PI_3,
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ObjectJson {
+    name: String,
+    mesh: MeshJson,
+    #[serde(default = "Vec3::zero")]
+    position: Vec3,
+    #[serde(default = "Quat::identity")]
+    rotation: Quat,
+    #[serde(default = "Vec3::one")]
+    scale: Vec3,
+    #[serde(default = "Vec3::unit_y")]
+    axis: Vec3,
+    #[serde(default)]
+    spin: f32,
+    #[serde(default)]
+    pulse: f32,
+    #[serde(default = "no_body")]
+    body: BodyKind,
+    #[serde(default)]
+    material: MaterialJson,
+}
+
+fn no_body() -> BodyKind {
+    BodyKind::None
+}
+
+/// Built-in meshes by name, the others by the file they're in.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum MeshJson {
+    Builtin {
+        builtin: String,
+    },
+    Obj {
+        obj: PathBuf,
+    },
+    Gltf {
+        gltf: PathBuf,
+        #[serde(default)]
+        primitive: usize,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum TextureJson {
+    Builtin { builtin: u32 },
+    Image { image: PathBuf },
+    Mesh { mesh: bool },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AlphaModeJson {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+/// Material without its layers, which are in `textures` by slot.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct MaterialJson {
+    base_color: [f32; 4],
+    metallic: f32,
+    roughness: f32,
+    emissive: [f32; 3],
+    alpha_mode: AlphaModeJson,
+    /// Only for masked materials.
+    alpha_cutoff: Option<f32>,
+    double_sided: bool,
+    order_independent: bool,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    textures: BTreeMap<String, TextureJson>,
+}
+
+impl Default for MaterialJson {
+    /// The default material, single sided like in glTF.
+    fn default() -> Self {
+        let defaults = PbrMaterial::default();
+        Self {
+            base_color: defaults.base_color,
+            metallic: defaults.metallic,
+            roughness: defaults.roughness,
+            emissive: defaults.emissive,
+            alpha_mode: AlphaModeJson::Opaque,
+            alpha_cutoff: None,
+            double_sided: false,
+            order_independent: false,
+            normal_scale: defaults.normal_scale,
+            occlusion_strength: defaults.occlusion_strength,
+            textures: BTreeMap::new(),
+        }
+    }
+}
+
+/// `path` of a scene file in `dir`, next to it if there's a file there.
+fn resolve(dir: &Path, path: &Path) -> PathBuf {
+    let next_to = dir.join(path);
+    if path.is_relative() && next_to.exists() {
+        next_to
+    } else {
+        path.to_path_buf()
+    }
+}
+
+fn slot_key(slot: TextureSlot) -> &'static str {
+    match slot {
+        TextureSlot::BaseColor => "baseColor",
+        TextureSlot::MetallicRoughness => "metallicRoughness",
+        TextureSlot::Normal => "normal",
+        TextureSlot::Emissive => "emissive",
+        TextureSlot::Occlusion => "occlusion",
+    }
+}
+
+fn object_json(file: &ObjectFile) -> ObjectJson {
+    let object = &file.object;
+    let material = &object.material;
+    let mesh = match &file.mesh {
+        MeshSource::Builtin(i) => MeshJson::Builtin {
+            builtin: BUILTIN_MESHES[*i].to_string(),
+        },
+        MeshSource::Obj(path) => MeshJson::Obj { obj: path.clone() },
+        MeshSource::Gltf { path, primitive } => MeshJson::Gltf {
+            gltf: path.clone(),
+            primitive: *primitive,
+        },
+    };
+    let textures = file
+        .textures
+        .iter()
+        .map(|(slot, source)| {
+            let source = match source {
+                TextureSource::Builtin(layer) => TextureJson::Builtin { builtin: *layer },
+                TextureSource::Image(path) => TextureJson::Image {
+                    image: path.clone(),
+                },
+                TextureSource::Mesh => TextureJson::Mesh { mesh: true },
+            };
+            (slot_key(*slot).to_string(), source)
+        })
+        .collect();
+    let (alpha_mode, alpha_cutoff) = match material.alpha_mode {
+        AlphaMode::Opaque => (AlphaModeJson::Opaque, None),
+        AlphaMode::Mask(cutoff) => (AlphaModeJson::Mask, Some(cutoff)),
+        AlphaMode::Blend => (AlphaModeJson::Blend, None),
+    };
+    ObjectJson {
+        name: object.name.clone(),
+        mesh,
+        position: object.position,
+        rotation: object.rotation,
+        scale: object.scale,
+        axis: object.axis,
+        spin: object.spin,
+        pulse: object.pulse,
+        body: object.body,
+        material: MaterialJson {
+            base_color: material.base_color,
+            metallic: material.metallic,
+            roughness: material.roughness,
+            emissive: material.emissive,
+            alpha_mode,
+            alpha_cutoff,
+            double_sided: material.double_sided,
+            order_independent: material.order_independent,
+            normal_scale: material.normal_scale,
+            occlusion_strength: material.occlusion_strength,
+            textures,
+        },
+    }
+}
+
+fn parse_object(json: ObjectJson, dir: &Path) -> Result<ObjectFile, SceneFileError> {
+    let name = json.name;
+    let mesh = match json.mesh {
+        MeshJson::Builtin { builtin } => {
+            match BUILTIN_MESHES.iter().position(|&name| name == builtin) {
+                Some(i) => MeshSource::Builtin(i),
+                None => return invalid(format!("unknown built-in mesh {}", builtin)),
+            }
+        }
+        MeshJson::Obj { obj } => MeshSource::Obj(resolve(dir, &obj)),
+        MeshJson::Gltf { gltf, primitive } => MeshSource::Gltf {
+            path: resolve(dir, &gltf),
+            primitive,
+        },
+    };
+
+    let material = json.material;
+    let alpha_mode = match material.alpha_mode {
+        AlphaModeJson::Opaque => AlphaMode::Opaque,
+        AlphaModeJson::Mask => AlphaMode::Mask(material.alpha_cutoff.unwrap_or(0.5)),
+        AlphaModeJson::Blend => AlphaMode::Blend,
+    };
+    let mut textures = Vec::new();
+    for &slot in &TEXTURE_SLOTS {
+        let source = match material.textures.get(slot_key(slot)) {
+            Some(TextureJson::Builtin { builtin }) => TextureSource::Builtin(*builtin),
+            Some(TextureJson::Image { image }) => TextureSource::Image(resolve(dir, image)),
+            Some(TextureJson::Mesh { mesh: true }) => TextureSource::Mesh,
+            Some(TextureJson::Mesh { mesh: false }) => {
+                return invalid(format!("unknown texture of {}", name))
+            }
+            None => continue,
+        };
+        textures.push((slot, source));
+    }
+
+    let mut object = Object::new(&name, 0, json.position, json.axis, json.spin);
+    object.rotation = json.rotation.normalize();
+    object.scale = json.scale;
+    object.pulse = json.pulse;
+    object.body = json.body;
+    object.material = PbrMaterial {
+        base_color: material.base_color,
+        metallic: material.metallic,
+        roughness: material.roughness,
+        emissive: material.emissive,
+        alpha_mode,
+        double_sided: material.double_sided,
+        order_independent: material.order_independent,
+        normal_scale: material.normal_scale,
+        occlusion_strength: material.occlusion_strength,
+        ..PbrMaterial::default()
+    };
+    Ok(ObjectFile {
+        object,
+        mesh,
+        textures,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MeshSource, SceneFile, Sources, TextureSource};
+    use crate::{
+        camera::CameraPose,
+        lights::{Light, LightKind},
+        material::{AlphaMode, TextureSlot},
+        physics::BodyKind,
+        scene::Object,
+    };
+    use glam::{Quat, Vec3};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn saved_scenes_load_the_same() {
+        let gltf = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/triangle.gltf");
+        let image = PathBuf::from("missing.png");
+        let mut sources = Sources::new(2, 1);
+        sources.add_mesh(
+            MeshSource::Gltf {
+                path: gltf.clone(),
+                primitive: 0,
+            },
+            None,
+        );
+        sources.images.insert((true, 2), image.clone());
+
+        let mut cube = Object::new("Cube", 1, Vec3::new(1.0, 2.0, 3.0), Vec3::unit_x(), 0.5);
+        cube.rotation = Quat::from_rotation_y(1.0);
+        cube.scale = Vec3::new(2.0, 1.0, 1.0);
+        cube.body = BodyKind::Dynamic;
+        cube.material.alpha_mode = AlphaMode::Mask(0.25);
+        cube.material.base_color_texture = Some(1);
+        cube.material.normal_texture = Some(0);
+        let mut triangle = Object::new("Triangle", 3, Vec3::zero(), Vec3::unit_y(), 0.0);
+        triangle.material.base_color_texture = Some(2);
+        triangle.selected = true;
+        let lights = [Light::new(LightKind::Spot)];
+        let camera = CameraPose {
+            position: Vec3::new(0.0, 1.0, 5.0),
+            yaw: 0.25,
+            pitch: -0.5,
+            fov_y: 1.0,
+        };
+
+        let path = std::env::temp_dir().join(format!("scene-{}.json", std::process::id()));
+        let saved = SceneFile::new(&[cube, triangle], &lights, camera, &sources);
+        saved.save(&path).unwrap();
+        let loaded = SceneFile::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.camera, camera);
+        assert_eq!(loaded.lights, lights);
+        assert_eq!(loaded.objects.len(), 2);
+        for (saved, loaded) in saved.objects.iter().zip(&loaded.objects) {
+            assert_eq!(loaded.mesh, saved.mesh);
+            assert_eq!(loaded.textures, saved.textures);
+            let (saved, loaded) = (&saved.object, &loaded.object);
+            assert_eq!(loaded.name, saved.name);
+            assert_eq!(loaded.position, saved.position);
+            assert!(loaded.rotation.abs_diff_eq(saved.rotation, 1e-6));
+            assert_eq!((loaded.scale, loaded.axis), (saved.scale, saved.axis));
+            assert_eq!((loaded.spin, loaded.body), (saved.spin, saved.body));
+            assert_eq!(loaded.material.alpha_mode, saved.material.alpha_mode);
+            assert!(!loaded.selected);
+        }
+        let cube = &loaded.objects[0];
+        assert_eq!(cube.mesh, MeshSource::Builtin(1));
+        assert_eq!(
+            cube.textures,
+            [
+                (TextureSlot::BaseColor, TextureSource::Builtin(1)),
+                (TextureSlot::Normal, TextureSource::Builtin(0)),
+            ]
+        );
+        let triangle = &loaded.objects[1];
+        assert_eq!(
+            triangle.mesh,
+            MeshSource::Gltf {
+                path: gltf,
+                primitive: 0
+            }
+        );
+        assert_eq!(
+            triangle.textures,
+            [(TextureSlot::BaseColor, TextureSource::Image(image))]
+        );
+        assert_eq!(loaded.missing_files().len(), 1);
+    }
+}
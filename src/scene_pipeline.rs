@@ -0,0 +1,857 @@
+//! Layouts and pipelines of the lit scene.
+//!
+//! Every pipeline drawing the scene shader shares one layout: the uniforms
+//! and deformation buffers of the object in group 0, and the lighting in
+//! group 1. They're created here rather than in `main` so the headless test
+//! can draw through them.
+use crate::{
+    draw_stats::{self, RenderEncoder},
+    layout::Layout,
+    material::{AlphaMode, PbrMaterial, NO_MATERIAL},
+    memory::{self, Category, Tracked},
+    mesh::{self, Vertex},
+    shadow::CASCADES,
+    vertex::VertexLayout,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3, Vec4};
+use imgui::{im_str, ComboBox, ImStr, ImString, Ui};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferAddress, BufferDescriptor,
+    BufferSize, BufferUsage, ColorStateDescriptor, CompareFunction, CullMode,
+    DepthStencilStateDescriptor, Device, DynamicOffset, FrontFace, InputStepMode, PipelineLayout,
+    PipelineLayoutDescriptor, PrimitiveTopology, ProgrammableStageDescriptor, Queue,
+    RasterizationStateDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, ShaderModule,
+    ShaderStage, StencilStateDescriptor, TextureComponentType, TextureFormat, TextureView,
+    TextureViewDimension, VertexStateDescriptor, BIND_BUFFER_ALIGNMENT,
+};
+
+pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth24PlusStencil8;
+
+/// Per-object uniform block.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct ObjectUniforms {
+    pub mvp: [[f32; 4]; 4],
+    pub model: [[f32; 4]; 4],
+    pub layer_spacing: f32,
+    pub outline_scale: f32,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub base_color: [f32; 4],
+    /// Alpha cutoff in `w`.
+    pub emissive: [f32; 4],
+    /// Layers of the base color, metallic-roughness, normal and emissive
+    /// textures, `NO_MATERIAL` if there's none.
+    pub textures: [u32; 4],
+    pub occlusion_texture: u32,
+    pub alpha_mode: u32,
+    pub double_sided: u32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    /// Whether the vertices are blended with the joint matrices.
+    pub skinned: u32,
+    /// Number of morph targets blended, and of vertices in each of them.
+    pub morph_targets: u32,
+    pub vertex_count: u32,
+    /// Level of detail drawn, for its debug view.
+    pub lod: u32,
+    pub _pad: [u32; 3],
+}
+
+impl ObjectUniforms {
+    pub fn new(
+        mvp: Mat4,
+        model: Mat4,
+        layer_spacing: f32,
+        outline_scale: f32,
+        material: &PbrMaterial,
+    ) -> Self {
+        let layer = |layer: Option<u32>| layer.unwrap_or(NO_MATERIAL);
+        // same values as the defines of the shader
+        let (alpha_mode, alpha_cutoff) = match material.alpha_mode {
+            AlphaMode::Opaque => (0, 0.0),
+            AlphaMode::Mask(cutoff) => (1, cutoff),
+            AlphaMode::Blend => (2, 0.0),
+        };
+        let [r, g, b] = material.emissive;
+        Self {
+            mvp: mvp.to_cols_array_2d(),
+            model: model.to_cols_array_2d(),
+            layer_spacing,
+            outline_scale,
+            metallic: material.metallic,
+            roughness: material.roughness,
+            base_color: material.base_color,
+            emissive: [r, g, b, alpha_cutoff],
+            textures: [
+                layer(material.base_color_texture),
+                layer(material.metallic_roughness_texture),
+                layer(material.normal_texture),
+                layer(material.emissive_texture),
+            ],
+            occlusion_texture: layer(material.occlusion_texture),
+            alpha_mode,
+            double_sided: material.double_sided as u32,
+            normal_scale: material.normal_scale,
+            occlusion_strength: material.occlusion_strength,
+            skinned: 0,
+            morph_targets: 0,
+            vertex_count: 0,
+            lod: 0,
+            _pad: [0; 3],
+        }
+    }
+}
+
+/// Storage buffers an object's vertices are deformed with.
+#[derive(Clone, Copy)]
+pub struct Deformation<'a> {
+    pub joints: &'a Buffer,
+    pub skin: &'a Buffer,
+    pub deltas: &'a Buffer,
+    pub weights: &'a Buffer,
+}
+
+gpu_struct! {
+    /// Per-frame uniform block of the lit shader.
+    #[derive(Default)]
+    pub struct Lighting {
+        pub light_direction: Vec3,
+        pub light_color: Vec3,
+        pub camera_position: Vec3,
+        pub camera_forward: Vec3,
+        pub cascade_view_projections: [Mat4; CASCADES],
+        pub cascade_splits: Vec4,
+        pub point_light_position: Vec3,
+        pub point_light_range: f32,
+        pub point_light_color: Vec3,
+        pub normal_mapping: bool,
+        pub ibl: bool,
+        pub prefiltered_mips: f32,
+        pub shadows: bool,
+        pub cascade_debug: bool,
+        pub point_shadows: bool,
+        pub debug_view: u32,
+        pub debug_depth_range: f32,
+        pub shadow_bias: f32,
+        pub shadow_normal_offset: f32,
+        pub shadow_pcf_radius: u32,
+        pub light_count: u32,
+        pub probe: Vec4,
+        pub reflection_probe: bool,
+    }
+}
+
+/// Resources of the lighting bind group, in the order of its bindings.
+#[derive(Clone, Copy)]
+pub struct LightingBindings<'a> {
+    pub uniform: &'a Buffer,
+    pub normal_map: &'a TextureView,
+    pub sampler: &'a Sampler,
+    pub irradiance: &'a TextureView,
+    pub prefiltered: &'a TextureView,
+    pub brdf: &'a TextureView,
+    pub ibl_sampler: &'a Sampler,
+    pub shadow_map: &'a TextureView,
+    pub shadow_sampler: &'a Sampler,
+    pub point_shadow: &'a TextureView,
+    pub point_shadow_sampler: &'a Sampler,
+    pub materials: &'a TextureView,
+    pub linear_materials: &'a TextureView,
+    pub lights: &'a Buffer,
+    pub probe: &'a TextureView,
+    pub cluster_uniform: &'a Buffer,
+    pub cluster_counts: &'a Buffer,
+    pub cluster_indices: &'a Buffer,
+}
+
+impl LightingBindings<'_> {
+    pub fn create(&self, device: &Device, layout: &BindGroupLayout) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Lighting bind group"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(self.uniform.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(self.normal_map),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(self.sampler),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(self.irradiance),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::TextureView(self.prefiltered),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: BindingResource::TextureView(self.brdf),
+                },
+                BindGroupEntry {
+                    binding: 6,
+                    resource: BindingResource::Sampler(self.ibl_sampler),
+                },
+                BindGroupEntry {
+                    binding: 7,
+                    resource: BindingResource::TextureView(self.shadow_map),
+                },
+                BindGroupEntry {
+                    binding: 8,
+                    resource: BindingResource::Sampler(self.shadow_sampler),
+                },
+                BindGroupEntry {
+                    binding: 9,
+                    resource: BindingResource::TextureView(self.point_shadow),
+                },
+                BindGroupEntry {
+                    binding: 10,
+                    resource: BindingResource::Sampler(self.point_shadow_sampler),
+                },
+                BindGroupEntry {
+                    binding: 11,
+                    resource: BindingResource::TextureView(self.materials),
+                },
+                BindGroupEntry {
+                    binding: 12,
+                    resource: BindingResource::TextureView(self.linear_materials),
+                },
+                BindGroupEntry {
+                    binding: 13,
+                    resource: BindingResource::Buffer(self.lights.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 14,
+                    resource: BindingResource::TextureView(self.probe),
+                },
+                BindGroupEntry {
+                    binding: 15,
+                    resource: BindingResource::Buffer(self.cluster_uniform.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 16,
+                    resource: BindingResource::Buffer(self.cluster_counts.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 17,
+                    resource: BindingResource::Buffer(self.cluster_indices.slice(..)),
+                },
+            ],
+        })
+    }
+}
+
+pub fn object_layout(device: &Device) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Object bind group layout"),
+        entries: &[
+            // at the offset of the object, see `ObjectBindings`
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::VERTEX | ShaderStage::FRAGMENT,
+                ty: BindingType::UniformBuffer {
+                    dynamic: true,
+                    min_binding_size: BufferSize::new(std::mem::size_of::<ObjectUniforms>() as _),
+                },
+                count: None,
+            },
+            // joint matrices and skinned vertices
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStage::VERTEX,
+                ty: BindingType::StorageBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                    readonly: true,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStage::VERTEX,
+                ty: BindingType::StorageBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                    readonly: true,
+                },
+                count: None,
+            },
+            // morph target deltas and weights
+            BindGroupLayoutEntry {
+                binding: 3,
+                visibility: ShaderStage::VERTEX,
+                ty: BindingType::StorageBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                    readonly: true,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 4,
+                visibility: ShaderStage::VERTEX,
+                ty: BindingType::StorageBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                    readonly: true,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+pub fn lighting_layout(device: &Device) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Lighting bind group layout"),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: BufferSize::new(Lighting::STD140.size as _),
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::SampledTexture {
+                    dimension: TextureViewDimension::D2,
+                    component_type: TextureComponentType::Float,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Sampler { comparison: false },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 3,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::SampledTexture {
+                    dimension: TextureViewDimension::Cube,
+                    component_type: TextureComponentType::Float,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 4,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::SampledTexture {
+                    dimension: TextureViewDimension::Cube,
+                    component_type: TextureComponentType::Float,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 5,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::SampledTexture {
+                    dimension: TextureViewDimension::D2,
+                    component_type: TextureComponentType::Float,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 6,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Sampler { comparison: false },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 7,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::SampledTexture {
+                    dimension: TextureViewDimension::D2Array,
+                    component_type: TextureComponentType::Float,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 8,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Sampler { comparison: true },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 9,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::SampledTexture {
+                    dimension: TextureViewDimension::Cube,
+                    component_type: TextureComponentType::Float,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 10,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::Sampler { comparison: false },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 11,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::SampledTexture {
+                    dimension: TextureViewDimension::D2Array,
+                    component_type: TextureComponentType::Float,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 12,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::SampledTexture {
+                    dimension: TextureViewDimension::D2Array,
+                    component_type: TextureComponentType::Float,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 13,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::StorageBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                    readonly: true,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 14,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::SampledTexture {
+                    dimension: TextureViewDimension::Cube,
+                    component_type: TextureComponentType::Float,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 15,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 16,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::StorageBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                    readonly: true,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 17,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::StorageBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                    readonly: true,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+pub fn pipeline_layout(
+    device: &Device,
+    object_layout: &BindGroupLayout,
+    lighting_layout: &BindGroupLayout,
+) -> PipelineLayout {
+    device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Scene pipeline layout"),
+        bind_group_layouts: &[object_layout, lighting_layout],
+        push_constant_ranges: &[],
+    })
+}
+
+/// How the vertices of the scene pipelines are assembled and culled.
+///
+/// The default draws triangles without culling. The other topologies reuse
+/// the triangle indices, so they show the vertices and edges of the meshes
+/// rather than a wireframe.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Rasterization {
+    pub topology: PrimitiveTopology,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+}
+
+impl Default for Rasterization {
+    fn default() -> Self {
+        Self {
+            topology: PrimitiveTopology::TriangleList,
+            cull_mode: CullMode::None,
+            front_face: FrontFace::Ccw,
+        }
+    }
+}
+
+impl Rasterization {
+    pub fn ui(&mut self, ui: &Ui) {
+        combo(
+            ui,
+            im_str!("Topology"),
+            &TOPOLOGIES,
+            topology_name,
+            &mut self.topology,
+        );
+        combo(
+            ui,
+            im_str!("Cull mode"),
+            &CULL_MODES,
+            cull_mode_name,
+            &mut self.cull_mode,
+        );
+        combo(
+            ui,
+            im_str!("Front face"),
+            &FRONT_FACES,
+            front_face_name,
+            &mut self.front_face,
+        );
+        if ui.button(im_str!("Reset"), [0.0, 0.0]) {
+            *self = Self::default();
+        }
+    }
+}
+
+/// Combo box choosing `value` among `values`, the first one if it isn't any.
+fn combo<T: Copy + PartialEq>(
+    ui: &Ui,
+    label: &ImStr,
+    values: &[T],
+    name: fn(T) -> &'static str,
+    value: &mut T,
+) {
+    let names: Vec<_> = values.iter().map(|&v| ImString::new(name(v))).collect();
+    let names: Vec<_> = names.iter().collect();
+    let mut index = values.iter().position(|v| v == value).unwrap_or(0);
+    if ComboBox::new(label).build_simple_string(ui, &mut index, &names) {
+        *value = values[index];
+    }
+}
+
+/// State of the pipelines drawing the scene that changes at runtime, besides
+/// the shader.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PipelineState {
+    pub color_states: Vec<ColorStateDescriptor>,
+    pub sample_count: u32,
+    pub rasterization: Rasterization,
+}
+
+pub const TOPOLOGIES: [PrimitiveTopology; 3] = [
+    PrimitiveTopology::TriangleList,
+    PrimitiveTopology::LineList,
+    PrimitiveTopology::PointList,
+];
+
+pub const CULL_MODES: [CullMode; 3] = [CullMode::None, CullMode::Front, CullMode::Back];
+
+pub const FRONT_FACES: [FrontFace; 2] = [FrontFace::Ccw, FrontFace::Cw];
+
+pub fn topology_name(topology: PrimitiveTopology) -> &'static str {
+    match topology {
+        PrimitiveTopology::PointList => "Points",
+        PrimitiveTopology::LineList => "Lines",
+        PrimitiveTopology::LineStrip => "Line strip",
+        PrimitiveTopology::TriangleList => "Triangles",
+        PrimitiveTopology::TriangleStrip => "Triangle strip",
+    }
+}
+
+pub fn cull_mode_name(cull_mode: CullMode) -> &'static str {
+    match cull_mode {
+        CullMode::None => "None",
+        CullMode::Front => "Front",
+        CullMode::Back => "Back",
+    }
+}
+
+pub fn front_face_name(front_face: FrontFace) -> &'static str {
+    match front_face {
+        FrontFace::Ccw => "Counter-clockwise",
+        FrontFace::Cw => "Clockwise",
+    }
+}
+
+/// Pipeline of the scene vertex layout with `layout`. The depth pre-pass has
+/// no fragment stage, and only the passes of `msaa` have more than one sample
+/// per pixel.
+#[allow(clippy::too_many_arguments)]
+pub fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    label: &str,
+    vert_module: &ShaderModule,
+    frag_module: Option<&ShaderModule>,
+    color_states: &[ColorStateDescriptor],
+    depth_compare: CompareFunction,
+    depth_write_enabled: bool,
+    stencil: StencilStateDescriptor,
+    sample_count: u32,
+    rasterization: Rasterization,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex_stage: ProgrammableStageDescriptor {
+            module: vert_module,
+            entry_point: "main",
+        },
+        fragment_stage: frag_module.map(|module| ProgrammableStageDescriptor {
+            module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(RasterizationStateDescriptor {
+            front_face: rasterization.front_face,
+            cull_mode: rasterization.cull_mode,
+            clamp_depth: false,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: rasterization.topology,
+        color_states,
+        depth_stencil_state: Some(DepthStencilStateDescriptor {
+            format: DEPTH_FORMAT,
+            depth_write_enabled,
+            depth_compare,
+            stencil,
+        }),
+        vertex_state: VertexStateDescriptor {
+            index_format: mesh::INDEX_FORMAT,
+            vertex_buffers: &[Vertex::buffer_descriptor(InputStepMode::Vertex)],
+        },
+        sample_count,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
+
+/// Objects whose uniforms share a buffer, and a bind group for those that
+/// aren't deformed.
+pub const PAGE_OBJECTS: usize = 256;
+
+/// Distance between the uniforms of two objects in their buffer, the
+/// alignment of dynamic offsets.
+pub const OBJECT_STRIDE: BufferAddress = (std::mem::size_of::<ObjectUniforms>() as BufferAddress)
+    .div_ceil(BIND_BUFFER_ALIGNMENT)
+    * BIND_BUFFER_ALIGNMENT;
+
+struct Page {
+    uniforms: Tracked<Buffer>,
+    undeformed: BindGroup,
+    /// Slots given out, the free ones included.
+    used: usize,
+}
+
+/// Where the uniforms of an object are, and the bind group of its own if
+/// it's deformed.
+struct Slot {
+    page: usize,
+    slot: usize,
+    deformed: Option<BindGroup>,
+}
+
+/// Uniforms of every object, packed `OBJECT_STRIDE` apart into buffers of
+/// `PAGE_OBJECTS` objects and drawn at their dynamic offset.
+///
+/// Objects that aren't deformed share the bind group of their page, so
+/// drawing them only changes the offset, and a scene up to `PAGE_OBJECTS`
+/// objects uploads every uniform at once. Pages are only added, which keeps
+/// the bind groups of deformed objects valid; slots left by removed objects
+/// are reused.
+pub struct ObjectBindings {
+    name: String,
+    pages: Vec<Page>,
+    objects: Vec<Slot>,
+    free: Vec<(usize, usize)>,
+}
+
+impl ObjectBindings {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            pages: Vec::new(),
+            objects: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Adds an object named `name` after the others, with a bind group of
+    /// its own if it has a `deformation`. The bind group of a new page is
+    /// created with the `undeformed` buffers.
+    pub fn push(
+        &mut self,
+        device: &Device,
+        layout: &BindGroupLayout,
+        name: &str,
+        undeformed: Deformation,
+        deformation: Option<Deformation>,
+    ) {
+        let (page, slot) = match self.free.pop() {
+            Some(free) => free,
+            None => {
+                if self
+                    .pages
+                    .last()
+                    .is_none_or(|page| page.used == PAGE_OBJECTS)
+                {
+                    self.add_page(device, layout, undeformed);
+                }
+                let page = self.pages.len() - 1;
+                let slot = self.pages[page].used;
+                self.pages[page].used += 1;
+                (page, slot)
+            }
+        };
+        let deformed = deformation.map(|deformation| {
+            let label = format!("{} bind group", name);
+            create_bind_group(
+                device,
+                &label,
+                layout,
+                &self.pages[page].uniforms,
+                deformation,
+            )
+        });
+        self.objects.push(Slot {
+            page,
+            slot,
+            deformed,
+        });
+    }
+
+    /// Removes the object at `index`, moving the ones after it back.
+    pub fn remove(&mut self, index: usize) {
+        let Slot { page, slot, .. } = self.objects.remove(index);
+        self.free.push((page, slot));
+    }
+
+    fn add_page(&mut self, device: &Device, layout: &BindGroupLayout, undeformed: Deformation) {
+        let label = format!("{} uniforms {}", self.name, self.pages.len());
+        let uniforms = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some(&label),
+                size: OBJECT_STRIDE * PAGE_OBJECTS as BufferAddress,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let label = format!("{} bind group {}", self.name, self.pages.len());
+        let undeformed = create_bind_group(device, &label, layout, &uniforms, undeformed);
+        self.pages.push(Page {
+            uniforms,
+            undeformed,
+            used: 0,
+        });
+    }
+
+    /// Uploads the `uniforms` of every object, in order, with one write for
+    /// each page.
+    pub fn write(&self, queue: &Queue, uniforms: &[ObjectUniforms]) {
+        let mut pages = vec![Vec::new(); self.pages.len()];
+        for (object, uniforms) in self.objects.iter().zip(uniforms) {
+            let page = &mut pages[object.page];
+            let offset = object.slot * OBJECT_STRIDE as usize;
+            let end = offset + std::mem::size_of::<ObjectUniforms>();
+            if page.len() < end {
+                page.resize(end, 0);
+            }
+            page[offset..end].copy_from_slice(bytemuck::bytes_of(uniforms));
+        }
+        for (page, data) in self.pages.iter().zip(&pages) {
+            if !data.is_empty() {
+                draw_stats::write_buffer(queue, &page.uniforms, 0, data);
+            }
+        }
+    }
+
+    /// Sets group 0 to the object at `index`.
+    pub fn bind<'a>(&'a self, pass: &mut impl RenderEncoder<'a>, index: usize) {
+        let object = &self.objects[index];
+        let bind_group = match &object.deformed {
+            Some(bind_group) => bind_group,
+            None => &self.pages[object.page].undeformed,
+        };
+        let offset = object.slot as BufferAddress * OBJECT_STRIDE;
+        pass.set_bind_group(0, bind_group, &[offset as DynamicOffset]);
+    }
+}
+
+/// Bind group of an object with its uniforms in `uniforms`, at the offset
+/// it's bound with.
+fn create_bind_group(
+    device: &Device,
+    label: &str,
+    layout: &BindGroupLayout,
+    uniforms: &Buffer,
+    deformation: Deformation,
+) -> BindGroup {
+    let size = std::mem::size_of::<ObjectUniforms>() as BufferAddress;
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some(label),
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(uniforms.slice(..size)),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Buffer(deformation.joints.slice(..)),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::Buffer(deformation.skin.slice(..)),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::Buffer(deformation.deltas.slice(..)),
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: BindingResource::Buffer(deformation.weights.slice(..)),
+            },
+        ],
+    })
+}
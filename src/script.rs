@@ -0,0 +1,765 @@
+//! Scripts setting up and animating the scene, run without recompiling.
+//!
+//! The language is a small one of its own. Statements at the top level run
+//! once, when the script is (re)loaded, and those in a `frame { ... }` block
+//! every frame after that, with the seconds since loading in `t` and since the
+//! last frame in `dt`. Variables of the top level are still there in the
+//! frame block.
+//!
+//! ```text
+//! # a row of spinning cubes
+//! let count = 5
+//! for i in 0..count {
+//!     spawn("Box " + i, "cube")
+//!     position("Box " + i, i * 1.5 - 3, 1, -2)
+//! }
+//! frame {
+//!     for i in 0..count {
+//!         rotation("Box " + i, t * 90 + i * 20, 0, 0)
+//!     }
+//! }
+//! ```
+//!
+//! Values are numbers or strings, `+` joins strings with anything. There's
+//! `let`, assignment, `for` over ranges of numbers, `if` and `else`, the math
+//! functions `sin`, `cos`, `tan`, `abs`, `sqrt`, `floor`, `min` and `max`, and
+//! `pi`. Commands name the object they change: `spawn(name, mesh)` with a
+//! built-in mesh, `position`, `scale`, `rotation` (yaw, pitch and roll in
+//! degrees), `color` (with an optional alpha) and `spin`. `print` logs its
+//! arguments.
+use crate::scene::Object;
+use glam::{Quat, Vec3};
+use log::info;
+use std::fmt;
+
+/// Statements and loop iterations run at most by each setup or frame, so
+/// loops that never end don't hang the app.
+const MAX_STEPS: usize = 1_000_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, ScriptError> {
+    Err(ScriptError {
+        line,
+        message: message.into(),
+    })
+}
+
+/// Change to an object of the scene.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    /// Line of the script it comes from.
+    pub line: usize,
+    /// Name of the object.
+    pub object: String,
+    pub kind: CommandKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandKind {
+    /// Creates the object with a built-in mesh, names must be unique.
+    Spawn {
+        mesh: String,
+    },
+    Position(Vec3),
+    Rotation(Quat),
+    Scale(Vec3),
+    Color([f32; 4]),
+    Spin(f32),
+}
+
+impl Command {
+    /// Changes the object of `objects` the command names. Spawning is left to
+    /// the caller, which creates the buffers of the object.
+    pub fn apply(&self, objects: &mut [Object]) -> Result<(), ScriptError> {
+        let object = match objects.iter_mut().find(|o| o.name == self.object) {
+            Some(object) => object,
+            None => return error(self.line, format!("there's no object {:?}", self.object)),
+        };
+        match &self.kind {
+            CommandKind::Spawn { .. } => {}
+            CommandKind::Position(position) => object.position = *position,
+            CommandKind::Rotation(rotation) => object.rotation = *rotation,
+            CommandKind::Scale(scale) => object.scale = *scale,
+            CommandKind::Color(color) => object.material.base_color = *color,
+            CommandKind::Spin(spin) => object.spin = *spin,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Number(f32),
+    Str(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            // whole numbers are written without the decimals, for names
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e9 => write!(f, "{}", *n as i64),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Str(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Str(String),
+    Ident(String),
+    Symbol(&'static str),
+    End,
+}
+
+/// Longer symbols first, so they aren't taken for their prefixes.
+const SYMBOLS: [&str; 18] = [
+    "..", "<=", ">=", "==", "!=", "(", ")", "{", "}", ",", "+", "-", "*", "/", "%", "<", ">", "=",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ScriptError> {
+    let mut tokens = Vec::new();
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let chars: Vec<char> = text.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c == '#' {
+                break;
+            } else if c.is_whitespace() {
+                i += 1;
+            } else if c.is_ascii_digit() {
+                let start = i;
+                let mut fraction = false;
+                // a dot only if a digit follows, `0..4` is a range
+                while i < chars.len()
+                    && (chars[i].is_ascii_digit()
+                        || chars[i] == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+                {
+                    if chars[i] == '.' {
+                        if fraction {
+                            return error(line, "number with more than one dot");
+                        }
+                        fraction = true;
+                    }
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().collect();
+                tokens.push((Token::Number(number.parse().unwrap()), line));
+            } else if c.is_alphabetic() || c == '_' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push((Token::Ident(chars[start..i].iter().collect()), line));
+            } else if c == '"' {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i] != '"' {
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return error(line, "unterminated string");
+                }
+                tokens.push((Token::Str(chars[start..i].iter().collect()), line));
+                i += 1;
+            } else {
+                let rest: String = chars[i..].iter().collect();
+                match SYMBOLS.iter().find(|&&symbol| rest.starts_with(symbol)) {
+                    Some(symbol) => {
+                        tokens.push((Token::Symbol(symbol), line));
+                        i += symbol.len();
+                    }
+                    None => return error(line, format!("unexpected {:?}", c)),
+                }
+            }
+        }
+    }
+    let last = source.lines().count().max(1);
+    tokens.push((Token::End, last));
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Number(f32),
+    Str(String),
+    Var(String, usize),
+    Neg(Box<Expr>, usize),
+    Binary(Op, Box<Expr>, Box<Expr>, usize),
+    Call(String, Vec<Expr>, usize),
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Let(String, Expr),
+    Assign(String, Expr, usize),
+    Call(String, Vec<Expr>, usize),
+    For(String, Expr, Expr, Vec<Stmt>, usize),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.next].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].0.clone();
+        if token != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn accept(&mut self, symbol: &str) -> bool {
+        if *self.peek() == Token::Symbol(Self::symbol(symbol)) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn symbol(symbol: &str) -> &'static str {
+        SYMBOLS.iter().find(|&&s| s == symbol).unwrap()
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ScriptError> {
+        if self.accept(symbol) {
+            Ok(())
+        } else {
+            error(self.line(), format!("expected {:?}", symbol))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ScriptError> {
+        match self.advance() {
+            Token::Ident(name) => Ok(name),
+            _ => error(self.line(), "expected a name"),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Token::Ident(name) if name == keyword) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Statements up to the closing brace of a block.
+    fn block(&mut self) -> Result<Vec<Stmt>, ScriptError> {
+        self.expect("{")?;
+        let mut stmts = Vec::new();
+        while !self.accept("}") {
+            if *self.peek() == Token::End {
+                return error(self.line(), "missing \"}\"");
+            }
+            stmts.push(self.stmt()?);
+        }
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt, ScriptError> {
+        let line = self.line();
+        if self.keyword("let") {
+            let name = self.ident()?;
+            self.expect("=")?;
+            return Ok(Stmt::Let(name, self.expr()?));
+        }
+        if self.keyword("for") {
+            let name = self.ident()?;
+            if !self.keyword("in") {
+                return error(self.line(), "expected \"in\"");
+            }
+            let from = self.expr()?;
+            self.expect("..")?;
+            let to = self.expr()?;
+            return Ok(Stmt::For(name, from, to, self.block()?, line));
+        }
+        if self.keyword("if") {
+            return self.if_stmt();
+        }
+        if self.keyword("frame") {
+            return error(line, "frame blocks must be at the top level, and only one");
+        }
+        let name = self.ident()?;
+        if self.accept("=") {
+            Ok(Stmt::Assign(name, self.expr()?, line))
+        } else if self.accept("(") {
+            Ok(Stmt::Call(name, self.args()?, line))
+        } else {
+            error(line, format!("expected \"=\" or \"(\" after {}", name))
+        }
+    }
+
+    fn if_stmt(&mut self) -> Result<Stmt, ScriptError> {
+        let condition = self.expr()?;
+        let then = self.block()?;
+        let otherwise = if !self.keyword("else") {
+            Vec::new()
+        } else if self.keyword("if") {
+            vec![self.if_stmt()?]
+        } else {
+            self.block()?
+        };
+        Ok(Stmt::If(condition, then, otherwise))
+    }
+
+    /// Arguments after the opening parenthesis.
+    fn args(&mut self) -> Result<Vec<Expr>, ScriptError> {
+        let mut args = Vec::new();
+        if self.accept(")") {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expr()?);
+            if self.accept(")") {
+                return Ok(args);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ScriptError> {
+        let lhs = self.sum()?;
+        let line = self.line();
+        let op = match self.peek() {
+            Token::Symbol("<") => Op::Less,
+            Token::Symbol("<=") => Op::LessEqual,
+            Token::Symbol(">") => Op::Greater,
+            Token::Symbol(">=") => Op::GreaterEqual,
+            Token::Symbol("==") => Op::Equal,
+            Token::Symbol("!=") => Op::NotEqual,
+            _ => return Ok(lhs),
+        };
+        self.advance();
+        Ok(Expr::Binary(op, Box::new(lhs), Box::new(self.sum()?), line))
+    }
+
+    fn sum(&mut self) -> Result<Expr, ScriptError> {
+        let mut lhs = self.product()?;
+        loop {
+            let line = self.line();
+            let op = match self.peek() {
+                Token::Symbol("+") => Op::Add,
+                Token::Symbol("-") => Op::Sub,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.product()?), line);
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, ScriptError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let line = self.line();
+            let op = match self.peek() {
+                Token::Symbol("*") => Op::Mul,
+                Token::Symbol("/") => Op::Div,
+                Token::Symbol("%") => Op::Rem,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?), line);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ScriptError> {
+        let line = self.line();
+        if self.accept("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?), line));
+        }
+        match self.advance() {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Str(s) => Ok(Expr::Str(s)),
+            Token::Ident(name) if self.accept("(") => Ok(Expr::Call(name, self.args()?, line)),
+            Token::Ident(name) => Ok(Expr::Var(name, line)),
+            Token::Symbol("(") => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            _ => error(line, "expected a value"),
+        }
+    }
+}
+
+/// Variables in scope and the commands so far.
+struct Interpreter {
+    vars: Vec<(String, Value)>,
+    commands: Vec<Command>,
+    steps: usize,
+}
+
+impl Interpreter {
+    fn var(&self, name: &str) -> Option<&Value> {
+        self.vars
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v)
+    }
+
+    fn number(&mut self, expr: &Expr, line: usize) -> Result<f32, ScriptError> {
+        match self.eval(expr)? {
+            Value::Number(n) => Ok(n),
+            Value::Str(s) => error(line, format!("expected a number, not {:?}", s)),
+        }
+    }
+
+    fn string(&mut self, expr: &Expr, line: usize) -> Result<String, ScriptError> {
+        match self.eval(expr)? {
+            Value::Str(s) => Ok(s),
+            Value::Number(n) => error(line, format!("expected a string, not {}", n)),
+        }
+    }
+
+    /// Runs `stmts` in a scope of their own.
+    fn block(&mut self, stmts: &[Stmt]) -> Result<(), ScriptError> {
+        let scope = self.vars.len();
+        let result = stmts.iter().try_for_each(|stmt| self.exec(stmt));
+        self.vars.truncate(scope);
+        result
+    }
+
+    fn exec(&mut self, stmt: &Stmt) -> Result<(), ScriptError> {
+        self.steps += 1;
+        match stmt {
+            Stmt::Let(name, value) => {
+                let value = self.eval(value)?;
+                self.vars.push((name.clone(), value));
+            }
+            Stmt::Assign(name, value, line) => {
+                let value = self.eval(value)?;
+                match self.vars.iter_mut().rev().find(|(n, _)| n == name) {
+                    Some((_, var)) => *var = value,
+                    None => return error(*line, format!("there's no variable {}", name)),
+                }
+            }
+            Stmt::Call(name, args, line) => self.command(name, args, *line)?,
+            Stmt::For(name, from, to, body, line) => {
+                let line = *line;
+                let from = self.number(from, line)?;
+                let to = self.number(to, line)?;
+                let mut i = from;
+                while i < to {
+                    self.steps += 1;
+                    if self.steps > MAX_STEPS {
+                        return error(line, "the script runs for too long");
+                    }
+                    self.vars.push((name.clone(), Value::Number(i)));
+                    let result = self.block(body);
+                    self.vars.pop();
+                    result?;
+                    i += 1.0;
+                }
+            }
+            Stmt::If(condition, then, otherwise) => {
+                let value = self.eval(condition)?;
+                let truthy = value != Value::Number(0.0) && value != Value::Str(String::new());
+                self.block(if truthy { then } else { otherwise })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, name: &str, args: &[Expr], line: usize) -> Result<(), ScriptError> {
+        if name == "print" {
+            let values = args
+                .iter()
+                .map(|arg| self.eval(arg).map(|value| value.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            info!("Script: {}", values.join(" "));
+            return Ok(());
+        }
+        let arity = match name {
+            "spawn" => 2..=2,
+            "position" | "rotation" | "scale" => 4..=4,
+            "color" => 4..=5,
+            "spin" => 2..=2,
+            _ => return error(line, format!("there's no command {}", name)),
+        };
+        if !arity.contains(&args.len()) {
+            return error(line, format!("wrong number of arguments to {}", name));
+        }
+        let object = self.string(&args[0], line)?;
+        let numbers = |interpreter: &mut Self| {
+            args[1..]
+                .iter()
+                .map(|arg| interpreter.number(arg, line))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let kind = match name {
+            "spawn" => CommandKind::Spawn {
+                mesh: self.string(&args[1], line)?,
+            },
+            "position" => CommandKind::Position(Vec3::from_slice_unaligned(&numbers(self)?)),
+            "scale" => CommandKind::Scale(Vec3::from_slice_unaligned(&numbers(self)?)),
+            "rotation" => {
+                let angles = numbers(self)?;
+                let [yaw, pitch, roll] = [angles[0], angles[1], angles[2]];
+                CommandKind::Rotation(Quat::from_rotation_ypr(
+                    yaw.to_radians(),
+                    pitch.to_radians(),
+                    roll.to_radians(),
+                ))
+            }
+            "color" => {
+                let channels = numbers(self)?;
+                let alpha = channels.get(3).copied().unwrap_or(1.0);
+                CommandKind::Color([channels[0], channels[1], channels[2], alpha])
+            }
+            _ => CommandKind::Spin(numbers(self)?[0]),
+        };
+        self.commands.push(Command { line, object, kind });
+        Ok(())
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value, ScriptError> {
+        match expr {
+            Expr::Number(n) => Ok(Value::Number(*n)),
+            Expr::Str(s) => Ok(Value::Str(s.clone())),
+            Expr::Var(name, line) => match self.var(name) {
+                Some(value) => Ok(value.clone()),
+                None if name == "pi" => Ok(Value::Number(std::f32::consts::PI)),
+                None => error(*line, format!("there's no variable {}", name)),
+            },
+            Expr::Neg(expr, line) => Ok(Value::Number(-self.number(expr, *line)?)),
+            Expr::Binary(op, lhs, rhs, line) => {
+                let (lhs, rhs) = (self.eval(lhs)?, self.eval(rhs)?);
+                binary(*op, lhs, rhs, *line)
+            }
+            Expr::Call(name, args, line) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.number(arg, *line))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let result = match (name.as_str(), args.as_slice()) {
+                    ("sin", [x]) => x.sin(),
+                    ("cos", [x]) => x.cos(),
+                    ("tan", [x]) => x.tan(),
+                    ("abs", [x]) => x.abs(),
+                    ("sqrt", [x]) => x.sqrt(),
+                    ("floor", [x]) => x.floor(),
+                    ("min", [x, y]) => x.min(*y),
+                    ("max", [x, y]) => x.max(*y),
+                    ("sin", _)
+                    | ("cos", _)
+                    | ("tan", _)
+                    | ("abs", _)
+                    | ("sqrt", _)
+                    | ("floor", _)
+                    | ("min", _)
+                    | ("max", _) => {
+                        return error(*line, format!("wrong number of arguments to {}", name))
+                    }
+                    _ => return error(*line, format!("there's no function {}", name)),
+                };
+                Ok(Value::Number(result))
+            }
+        }
+    }
+}
+
+fn binary(op: Op, lhs: Value, rhs: Value, line: usize) -> Result<Value, ScriptError> {
+    let (a, b) = match (op, &lhs, &rhs) {
+        (Op::Add, Value::Str(_), _) | (Op::Add, _, Value::Str(_)) => {
+            return Ok(Value::Str(format!("{}{}", lhs, rhs)))
+        }
+        (Op::Equal, _, _) => return Ok(Value::Number((lhs == rhs) as u32 as f32)),
+        (Op::NotEqual, _, _) => return Ok(Value::Number((lhs != rhs) as u32 as f32)),
+        (_, Value::Number(a), Value::Number(b)) => (*a, *b),
+        _ => return error(line, "expected numbers"),
+    };
+    let result = match op {
+        Op::Add => a + b,
+        Op::Sub => a - b,
+        Op::Mul => a * b,
+        Op::Div => a / b,
+        Op::Rem => a % b,
+        Op::Less => (a < b) as u32 as f32,
+        Op::LessEqual => (a <= b) as u32 as f32,
+        Op::Greater => (a > b) as u32 as f32,
+        Op::GreaterEqual => (a >= b) as u32 as f32,
+        Op::Equal | Op::NotEqual => unreachable!(),
+    };
+    Ok(Value::Number(result))
+}
+
+/// Parsed script, and the variables of its top level once it's set up.
+pub struct Script {
+    setup: Vec<Stmt>,
+    frame: Vec<Stmt>,
+    globals: Vec<(String, Value)>,
+}
+
+impl Script {
+    pub fn parse(source: &str) -> Result<Self, ScriptError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            next: 0,
+        };
+        let mut setup = Vec::new();
+        let mut frame = None;
+        while *parser.peek() != Token::End {
+            let line = parser.line();
+            if parser.keyword("frame") {
+                if frame.is_some() {
+                    return error(line, "there's already a frame block");
+                }
+                frame = Some(parser.block()?);
+            } else {
+                setup.push(parser.stmt()?);
+            }
+        }
+        Ok(Self {
+            setup,
+            frame: frame.unwrap_or_default(),
+            globals: Vec::new(),
+        })
+    }
+
+    /// Whether there's a frame block.
+    pub fn animates(&self) -> bool {
+        !self.frame.is_empty()
+    }
+
+    /// Runs the top level, keeping its variables for the frames.
+    pub fn setup(&mut self) -> Result<Vec<Command>, ScriptError> {
+        let mut interpreter = Interpreter {
+            vars: Vec::new(),
+            commands: Vec::new(),
+            steps: 0,
+        };
+        self.setup
+            .iter()
+            .try_for_each(|stmt| interpreter.exec(stmt))?;
+        self.globals = interpreter.vars;
+        Ok(interpreter.commands)
+    }
+
+    /// Runs the frame block, `time` seconds after the setup and `delta` after
+    /// the last frame. Assignments to the variables of the top level are kept.
+    pub fn frame(&mut self, time: f32, delta: f32) -> Result<Vec<Command>, ScriptError> {
+        let globals = self.globals.len();
+        let mut vars = std::mem::take(&mut self.globals);
+        vars.push(("t".into(), Value::Number(time)));
+        vars.push(("dt".into(), Value::Number(delta)));
+        let mut interpreter = Interpreter {
+            vars,
+            commands: Vec::new(),
+            steps: 0,
+        };
+        let result = self
+            .frame
+            .iter()
+            .try_for_each(|stmt| interpreter.exec(stmt));
+        interpreter.vars.truncate(globals);
+        self.globals = interpreter.vars;
+        let commands = interpreter.commands;
+        result.map(|()| commands)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandKind, Script};
+    use glam::Vec3;
+
+    #[test]
+    fn loops_spawn_named_objects() {
+        let mut script = Script::parse(
+            "let count = 3 # objects\n\
+             for i in 0..count {\n\
+                 spawn(\"Box \" + i, \"cube\")\n\
+                 position(\"Box \" + i, i * 2, -(1 + 1), 0.5)\n\
+             }",
+        )
+        .unwrap();
+        let commands = script.setup().unwrap();
+        assert_eq!(commands.len(), 6);
+        assert_eq!(commands[4].object, "Box 2");
+        assert_eq!(
+            commands[5].kind,
+            CommandKind::Position(Vec3::new(4.0, -2.0, 0.5))
+        );
+        assert_eq!(commands[5].line, 4);
+        assert!(!script.animates());
+    }
+
+    #[test]
+    fn frames_keep_assigned_globals() {
+        let mut script = Script::parse(
+            "let frames = 0\n\
+             frame {\n\
+                 frames = frames + 1\n\
+                 let local = frames\n\
+                 if local > 1 { spin(\"Cube\", t) } else { spin(\"Cube\", dt) }\n\
+             }",
+        )
+        .unwrap();
+        assert!(script.setup().unwrap().is_empty());
+        let first = script.frame(1.0, 0.5).unwrap();
+        assert_eq!(first[0].kind, CommandKind::Spin(0.5));
+        let second = script.frame(2.0, 0.5).unwrap();
+        assert_eq!(second[0].kind, CommandKind::Spin(2.0));
+    }
+
+    #[test]
+    fn errors_have_lines() {
+        let error = Script::parse("spawn(\"A\", \"cube\")\nlet = 2")
+            .err()
+            .unwrap();
+        assert_eq!(error.line, 2);
+        let mut script = Script::parse("\n\nposition(\"A\", 1, 2)").unwrap();
+        let error = script.setup().unwrap_err();
+        assert_eq!(error.line, 3);
+        assert!(error.to_string().starts_with("line 3: "));
+        let mut script = Script::parse("for i in 0..1000000000 { }").unwrap();
+        assert_eq!(script.setup().unwrap_err().line, 1);
+        let error = Script::parse("let a = 1\nlet b = 1.2.3").err().unwrap();
+        assert_eq!(error.line, 2);
+        assert!(Script::parse("let a = 1.5\nfor i in 0..2 { }").is_ok());
+    }
+}
@@ -0,0 +1,333 @@
+//! Settings kept between runs.
+//!
+//! Saved on exit as `key value` lines, followed by the imgui window layout
+//! (in imgui's own ini format) after a `[imgui]` line. Unknown keys and lines
+//! that don't parse are ignored, so old files keep loading.
+use crate::{
+    bookmarks::Bookmark,
+    camera::FlyCamera,
+    input::{Action, KeyBindings},
+    post::{Antialiasing, ANTIALIASING},
+    style::{Theme, UiStyle},
+};
+use glam::Vec3;
+use log::{info, warn};
+use std::{fmt::Write, path::Path};
+use wgpu::PresentMode;
+
+/// Recent files remembered, most recent first.
+pub const MAX_RECENT_FILES: usize = 10;
+
+/// Line after which the imgui layout starts.
+const IMGUI_SECTION: &str = "[imgui]";
+
+#[derive(Clone)]
+pub struct Settings {
+    /// Position of the window on the screen, centered if `None`.
+    pub window_position: Option<(i32, i32)>,
+    /// Size of the window in window coordinates, the default one if `None`.
+    pub window_size: Option<(u32, u32)>,
+    pub present_mode: PresentMode,
+    pub antialiasing: Antialiasing,
+    pub camera: FlyCamera,
+    /// Vertical field of view of the camera, in radians.
+    pub fov_y: f32,
+    pub bookmarks: Vec<Bookmark>,
+    /// Files dropped onto the window, most recent first.
+    pub recent_files: Vec<String>,
+    pub bindings: KeyBindings,
+    pub style: UiStyle,
+    /// Frame rate, frame time, adapter and resolution in the window title.
+    pub title_status: bool,
+    /// Positions and sizes of the imgui windows.
+    pub imgui_ini: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window_position: None,
+            window_size: None,
+            present_mode: PresentMode::Fifo,
+            antialiasing: Antialiasing::default(),
+            camera: FlyCamera::looking_at(Vec3::new(0.0, 0.0, 3.0), Vec3::zero()),
+            fov_y: std::f32::consts::FRAC_PI_3,
+            bookmarks: Vec::new(),
+            recent_files: Vec::new(),
+            bindings: KeyBindings::default(),
+            style: UiStyle::default(),
+            title_status: false,
+            imgui_ini: String::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Loads the settings saved at `path`, or the defaults if there aren't
+    /// any.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let mut settings = Self::default();
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                info!("Using default settings ({}: {})", path.display(), err);
+                return settings;
+            }
+        };
+        let (values, imgui_ini) = match contents.find(IMGUI_SECTION) {
+            Some(start) => (&contents[..start], &contents[start + IMGUI_SECTION.len()..]),
+            None => (&contents[..], ""),
+        };
+        settings.imgui_ini = imgui_ini.trim_start().to_string();
+
+        let (mut yaw, mut pitch) = settings.camera.angles();
+        let mut position = settings.camera.position;
+        for line in values.lines() {
+            let mut parts = line.splitn(2, ' ');
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key, value.trim()),
+                _ => continue,
+            };
+            let floats: Vec<f32> = value
+                .split_whitespace()
+                .filter_map(|v| v.parse().ok())
+                .collect();
+            match (key, floats.as_slice()) {
+                ("window_position", &[x, y]) => settings.window_position = Some((x as _, y as _)),
+                ("window_size", &[width, height]) if width >= 1.0 && height >= 1.0 => {
+                    settings.window_size = Some((width as _, height as _))
+                }
+                ("camera_position", &[x, y, z]) => position = Vec3::new(x, y, z),
+                ("camera_angles", &[y, p]) => {
+                    yaw = y;
+                    pitch = p;
+                }
+                ("present_mode", _) => match present_mode(value) {
+                    Some(mode) => settings.present_mode = mode,
+                    None => warn!("Unknown present mode {:?}", value),
+                },
+                ("antialiasing", _) => match ANTIALIASING.iter().find(|a| a.name() == value) {
+                    Some(&antialiasing) => settings.antialiasing = antialiasing,
+                    None => warn!("Unknown antialiasing {:?}", value),
+                },
+                ("camera_fov", &[fov_y]) => settings.fov_y = fov_y,
+                ("bookmark", _) => match Bookmark::parse_line(value) {
+                    Some(bookmark) => settings.bookmarks.push(bookmark),
+                    None => warn!("Invalid camera bookmark {:?}", value),
+                },
+                ("ui_theme", _) => match Theme::from_name(value) {
+                    Some(theme) => settings.style.theme = theme,
+                    None => warn!("Unknown theme {:?}", value),
+                },
+                ("ui_accent", &[r, g, b, a]) => settings.style.accent = [r, g, b, a],
+                ("ui_background", &[r, g, b, a]) => settings.style.background = [r, g, b, a],
+                ("ui_text", &[r, g, b, a]) => settings.style.text = [r, g, b, a],
+                ("ui_font", _) => settings.style.font_path = value.to_string(),
+                ("ui_font_size", &[size]) => settings.style.font_size = size,
+                ("title_status", _) => settings.title_status = value == "true",
+                ("bind", _) if !settings.bindings.parse_line(value) => {
+                    warn!("Invalid key binding {:?}", value)
+                }
+                ("recent_file", _) if settings.recent_files.len() < MAX_RECENT_FILES => {
+                    settings.recent_files.push(value.to_string())
+                }
+                _ => {}
+            }
+        }
+        settings.camera = FlyCamera::new(position, yaw, pitch);
+        info!("Loaded settings from {}", path.display());
+        settings
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        if let Some((x, y)) = self.window_position {
+            writeln!(contents, "window_position {} {}", x, y).unwrap();
+        }
+        if let Some((width, height)) = self.window_size {
+            writeln!(contents, "window_size {} {}", width, height).unwrap();
+        }
+        writeln!(contents, "present_mode {:?}", self.present_mode).unwrap();
+        writeln!(contents, "antialiasing {}", self.antialiasing.name()).unwrap();
+        let position = self.camera.position;
+        let (yaw, pitch) = self.camera.angles();
+        writeln!(
+            contents,
+            "camera_position {} {} {}",
+            position.x, position.y, position.z
+        )
+        .unwrap();
+        writeln!(contents, "camera_angles {} {}", yaw, pitch).unwrap();
+        writeln!(contents, "camera_fov {}", self.fov_y).unwrap();
+        for bookmark in &self.bookmarks {
+            writeln!(contents, "bookmark {}", bookmark.to_line()).unwrap();
+        }
+        for file in &self.recent_files {
+            writeln!(contents, "recent_file {}", file).unwrap();
+        }
+        let style = &self.style;
+        let color = |c: [f32; 4]| format!("{} {} {} {}", c[0], c[1], c[2], c[3]);
+        writeln!(contents, "ui_theme {}", style.theme.name()).unwrap();
+        writeln!(contents, "ui_accent {}", color(style.accent)).unwrap();
+        writeln!(contents, "ui_background {}", color(style.background)).unwrap();
+        writeln!(contents, "ui_text {}", color(style.text)).unwrap();
+        if !style.font_path.is_empty() {
+            writeln!(contents, "ui_font {}", style.font_path).unwrap();
+        }
+        writeln!(contents, "ui_font_size {}", style.font_size).unwrap();
+        writeln!(contents, "title_status {}", self.title_status).unwrap();
+        for &action in Action::ALL.iter() {
+            writeln!(contents, "bind {}", self.bindings.to_line(action)).unwrap();
+        }
+        writeln!(contents, "{}", IMGUI_SECTION).unwrap();
+        contents.push_str(&self.imgui_ini);
+        match std::fs::write(path, contents) {
+            Ok(()) => info!("Saved settings to {}", path.display()),
+            Err(err) => warn!("Error saving settings to {}: {}", path.display(), err),
+        }
+    }
+
+    /// Moves `file` to the top of the recent files.
+    pub fn add_recent_file(&mut self, file: &str) {
+        self.recent_files.retain(|f| f != file);
+        self.recent_files.insert(0, file.to_string());
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+}
+
+fn present_mode(name: &str) -> Option<PresentMode> {
+    match name {
+        "Immediate" => Some(PresentMode::Immediate),
+        "Mailbox" => Some(PresentMode::Mailbox),
+        "Fifo" => Some(PresentMode::Fifo),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraPose;
+    use sdl2::keyboard::Scancode;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}-{}.txt", name, std::process::id()))
+    }
+
+    #[test]
+    fn saved_settings_load_the_same() {
+        let mut saved = Settings {
+            window_position: Some((-20, 40)),
+            window_size: Some((800, 600)),
+            present_mode: PresentMode::Mailbox,
+            antialiasing: Antialiasing::Taa,
+            camera: FlyCamera::new(Vec3::new(1.5, -2.0, 0.25), 0.75, -0.5),
+            fov_y: 1.2,
+            bookmarks: vec![Bookmark {
+                name: "Over the door".to_string(),
+                pose: CameraPose {
+                    position: Vec3::new(0.1, 2.0, -3.0),
+                    yaw: 3.0,
+                    pitch: 0.2,
+                    fov_y: 0.9,
+                },
+            }],
+            title_status: true,
+            imgui_ini: "[Window][Debug]\nPos=60,60\n".to_string(),
+            ..Settings::default()
+        };
+        saved.add_recent_file("assets/triangle.gltf");
+        saved.add_recent_file("my scene.json");
+        saved.bindings.set(Action::ALL[0], Scancode::Kp8);
+        saved.style = UiStyle {
+            theme: Theme::Custom,
+            accent: [0.5, 0.25, 0.125, 1.0],
+            font_path: "fonts/Some Font.ttf".to_string(),
+            font_size: 15.5,
+            ..UiStyle::default()
+        };
+
+        let path = temp_path("settings");
+        saved.save(&path);
+        let loaded = Settings::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.window_position, saved.window_position);
+        assert_eq!(loaded.window_size, saved.window_size);
+        assert_eq!(loaded.present_mode, saved.present_mode);
+        assert_eq!(loaded.antialiasing, saved.antialiasing);
+        assert_eq!(loaded.camera.position, saved.camera.position);
+        assert_eq!(loaded.camera.angles(), saved.camera.angles());
+        assert_eq!(loaded.fov_y, saved.fov_y);
+        assert_eq!(loaded.bookmarks.len(), 1);
+        assert_eq!(loaded.bookmarks[0].name, saved.bookmarks[0].name);
+        assert_eq!(loaded.bookmarks[0].pose, saved.bookmarks[0].pose);
+        assert_eq!(
+            loaded.recent_files,
+            ["my scene.json", "assets/triangle.gltf"]
+        );
+        for &action in Action::ALL.iter() {
+            assert_eq!(loaded.bindings.key(action), saved.bindings.key(action));
+        }
+        assert_eq!(loaded.style.theme, saved.style.theme);
+        assert_eq!(loaded.style.accent, saved.style.accent);
+        assert_eq!(loaded.style.background, saved.style.background);
+        assert_eq!(loaded.style.text, saved.style.text);
+        assert_eq!(loaded.style.font_path, saved.style.font_path);
+        assert_eq!(loaded.style.font_size, saved.style.font_size);
+        assert!(loaded.title_status);
+        assert_eq!(loaded.imgui_ini, saved.imgui_ini);
+    }
+
+    #[test]
+    fn unknown_and_malformed_lines_are_ignored() {
+        let path = temp_path("settings-malformed");
+        std::fs::write(
+            &path,
+            "from_a_newer_version 1 2 3\n\
+             window_size 0 600\n\
+             window_position 10\n\
+             camera_fov wide\n\
+             present_mode Sometimes\n\
+             antialiasing SSAA\n\
+             ui_theme Neon\n\
+             bookmark 1 2 three\n\
+             bind no_such_action W\n\
+             bind move_forward\n\
+             no_value\n\
+             \n\
+             camera_position 1 2 3\n\
+             [imgui]\n\
+             camera_fov 0.5\n",
+        )
+        .unwrap();
+        let loaded = Settings::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let default = Settings::default();
+        assert_eq!(loaded.window_size, None);
+        assert_eq!(loaded.window_position, None);
+        assert_eq!(loaded.fov_y, default.fov_y);
+        assert_eq!(loaded.present_mode, default.present_mode);
+        assert_eq!(loaded.antialiasing, default.antialiasing);
+        assert_eq!(loaded.style.theme, default.style.theme);
+        assert!(loaded.bookmarks.is_empty());
+        for &action in Action::ALL.iter() {
+            assert_eq!(loaded.bindings.key(action), default.bindings.key(action));
+        }
+        // lines after the bad ones still load, and the imgui section is
+        // kept as it is
+        assert_eq!(loaded.camera.position, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(loaded.imgui_ini, "camera_fov 0.5\n");
+    }
+
+    #[test]
+    fn missing_files_load_the_defaults() {
+        let loaded = Settings::load(temp_path("settings-missing"));
+        assert_eq!(loaded.window_size, None);
+        assert!(loaded.recent_files.is_empty());
+        assert!(loaded.imgui_ini.is_empty());
+    }
+}
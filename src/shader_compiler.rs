@@ -0,0 +1,191 @@
+//! Shaders compiled at runtime, for editing them without a rebuild.
+//!
+//! GLSL goes through the same `glslangValidator` the build script runs, and
+//! HLSL through `dxc`, so they have to be in the PATH. Sources are round
+//! tripped through a temporary directory, neither writes SPIR-V to stdout.
+use std::{fmt, fs, path::Path, process::Command};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Message of the validator, located in the source.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Name given to the source, not the temporary file.
+    pub file: String,
+    /// 1-based, 0 if the message isn't about a line.
+    pub line: u32,
+    /// 1-based, only reported by recent validators.
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Line of `source` the message is about.
+    pub fn source_line<'a>(&self, source: &'a str) -> Option<&'a str> {
+        let line = self.line.checked_sub(1)?;
+        source.lines().nth(line as usize)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.column {
+            Some(column) => write!(f, "{}:{}:{}: ", self.file, self.line, column)?,
+            None => write!(f, "{}:{}: ", self.file, self.line)?,
+        }
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum CompileError {
+    /// The compiler couldn't be run, or its files couldn't be accessed.
+    Io(std::io::Error),
+    /// The name doesn't end in the extension of a stage.
+    UnknownStage,
+    /// Errors and warnings of the source, there's at least one error.
+    Source(Vec<Diagnostic>),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::Io(err) => write!(f, "Error running the shader compiler: {}", err),
+            CompileError::UnknownStage => write!(f, "Unknown shader stage"),
+            CompileError::Source(diagnostics) => {
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", diagnostic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Compiles GLSL or HLSL `source` into SPIR-V. The stage is taken from the
+/// extension of `name`, as in `raymarch.frag`, followed by `.hlsl` for HLSL
+/// like in the shaders directory.
+pub fn compile(name: &str, source: &str) -> Result<Vec<u8>, CompileError> {
+    compile_with_defines(name, source, &[])
+}
+
+/// Compiles `source` with preprocessor `defines` set to their values.
+pub fn compile_with_defines(
+    name: &str,
+    source: &str,
+    defines: &[(&str, i32)],
+) -> Result<Vec<u8>, CompileError> {
+    let (hlsl, stage) = match name.strip_suffix(".hlsl") {
+        Some(name) => (true, Path::new(name).extension()),
+        None => (false, Path::new(name).extension()),
+    };
+    let profile = match stage.and_then(|stage| stage.to_str()) {
+        Some("vert") => "vs_6_0",
+        Some("frag") => "ps_6_0",
+        Some("comp") => "cs_6_0",
+        _ => return Err(CompileError::UnknownStage),
+    };
+
+    let dir = std::env::temp_dir().join("wgpu-test-shaders");
+    fs::create_dir_all(&dir).map_err(CompileError::Io)?;
+    let input = dir.join(name);
+    let output = dir.join(format!("{}.spv", name));
+    fs::write(&input, source).map_err(CompileError::Io)?;
+    let (mut command, output_flag) = if hlsl {
+        let mut command = Command::new("dxc");
+        command.args(["-spirv", "-T", profile, "-E", "main"]);
+        (command, "-Fo")
+    } else {
+        let mut command = Command::new("glslangValidator");
+        command.args(["-V", "--error-column"]);
+        (command, "-o")
+    };
+    for (define, value) in defines {
+        command.arg(format!("-D{}={}", define, value));
+    }
+    let result = command
+        .arg(output_flag)
+        .arg(&output)
+        .arg(&input)
+        .output()
+        .map_err(CompileError::Io)?;
+    if !result.status.success() {
+        // glslangValidator writes messages to stdout, dxc to stderr
+        let mut log = String::from_utf8_lossy(&result.stdout).into_owned();
+        log.push_str(&String::from_utf8_lossy(&result.stderr));
+        let input = input.to_string_lossy();
+        return Err(CompileError::Source(parse_log(&log, &input, name)));
+    }
+    fs::read(&output).map_err(CompileError::Io)
+}
+
+/// Parses messages such as `ERROR: /tmp/x.frag:4:9: 'foo' : undeclared
+/// identifier` of glslangValidator or `/tmp/x.frag.hlsl:4:9: error: use of
+/// undeclared identifier 'foo'` of dxc, with `path` renamed to `name`. The
+/// whole log is a single error if no message can be located.
+fn parse_log(log: &str, path: &str, name: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<_> = log
+        .lines()
+        .filter_map(|line| {
+            // glslangValidator starts with the severity, dxc has it after the
+            // location
+            let (severity, rest) = if let Some(rest) = line.strip_prefix("ERROR: ") {
+                (Some(Severity::Error), rest)
+            } else if let Some(rest) = line.strip_prefix("WARNING: ") {
+                (Some(Severity::Warning), rest)
+            } else {
+                (None, line)
+            };
+            let rest = rest.strip_prefix(path)?.strip_prefix(':')?;
+            let (line, rest) = rest.split_once(':')?;
+            let line = line.trim().parse().ok()?;
+            let (column, message) = match rest.split_once(':') {
+                Some((column, message)) if column.trim().parse::<u32>().is_ok() => {
+                    (column.trim().parse().ok(), message)
+                }
+                _ => (None, rest),
+            };
+            let message = message.trim();
+            let (severity, message) = match severity {
+                Some(severity) => (severity, message),
+                None => match message.strip_prefix("error: ") {
+                    Some(message) => (Severity::Error, message),
+                    None => (Severity::Warning, message.strip_prefix("warning: ")?),
+                },
+            };
+            // follows the first error, without saying anything else
+            if message == "'' : compilation terminated" {
+                return None;
+            }
+            Some(Diagnostic {
+                severity,
+                file: name.to_string(),
+                line,
+                column,
+                message: message.to_string(),
+            })
+        })
+        .collect();
+    if !diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            file: name.to_string(),
+            line: 0,
+            column: None,
+            message: log.trim().to_string(),
+        });
+    }
+    diagnostics
+}
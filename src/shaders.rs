@@ -0,0 +1,7 @@
+//! SPIR-V of every shader under `src/shaders`, compiled by the build script.
+//!
+//! Constants are named after the path of the source, so `shader.frag` is
+//! `SHADER_FRAG` and the HLSL `blur.comp.hlsl` is `BLUR_COMP_HLSL`, and are
+//! turned into modules with `make_spirv`. Variants the build script compiles
+//! with defines are named after the variant, as `SHADER_OIT_FRAG`.
+include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
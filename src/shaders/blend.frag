@@ -0,0 +1,17 @@
+#version 450
+
+layout(location = 0) in vec4 v_color;
+layout(location = 1) in vec2 v_uv;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    if (v_color.a == 1.0) {
+        // checkerboard showing through the quads
+        vec2 cell = floor(v_uv * 16.0);
+        float checker = mod(cell.x + cell.y, 2.0);
+        out_color = vec4(vec3(mix(0.25, 0.75, checker)), 1.0);
+    } else {
+        out_color = v_color;
+    }
+}
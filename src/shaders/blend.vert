@@ -0,0 +1,29 @@
+#version 450
+
+layout(location = 0) out vec4 v_color;
+layout(location = 1) out vec2 v_uv;
+
+// instance 0 is the checkerboard covering the viewport, the others are the
+// translucent quads blended over it
+const vec4 COLORS[4] = vec4[4](
+    vec4(1.0),
+    vec4(1.0, 0.1, 0.1, 0.5),
+    vec4(0.1, 1.0, 0.1, 0.5),
+    vec4(0.1, 0.1, 1.0, 0.5)
+);
+const vec2 CENTERS[4] = vec2[4](
+    vec2(0.0, 0.0),
+    vec2(-0.2, 0.15),
+    vec2(0.2, 0.15),
+    vec2(0.0, -0.2)
+);
+
+void main() {
+    // triangle strip of the corners
+    vec2 corner = vec2(gl_VertexIndex & 1, (gl_VertexIndex >> 1) & 1) * 2.0 - 1.0;
+    float size = gl_InstanceIndex == 0 ? 1.0 : 0.35;
+    gl_Position = vec4(CENTERS[gl_InstanceIndex] + corner * size, 0.0, 1.0);
+
+    v_color = COLORS[gl_InstanceIndex];
+    v_uv = corner * 0.5 + 0.5;
+}
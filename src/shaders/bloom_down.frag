@@ -0,0 +1,37 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Bloom {
+    float threshold;
+    // of the upsampling filter, in texels of the source
+    float radius;
+} u_bloom;
+layout(set = 0, binding = 1) uniform texture2D t_source;
+layout(set = 0, binding = 2) uniform sampler s_source;
+
+vec3 tap(vec2 texel, float x, float y) {
+    return texture(sampler2D(t_source, s_source), v_uv + vec2(x, y) * texel).rgb;
+}
+
+// 13 tap filter, as four overlapping boxes around the center and one on it,
+// so the glow doesn't shimmer as bright spots move across texels
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(sampler2D(t_source, s_source), 0));
+    vec3 color = tap(texel, 0.0, 0.0) * 0.125;
+    color += (tap(texel, -2.0, 2.0) + tap(texel, 2.0, 2.0)
+            + tap(texel, -2.0, -2.0) + tap(texel, 2.0, -2.0)) * 0.03125;
+    color += (tap(texel, 0.0, 2.0) + tap(texel, -2.0, 0.0)
+            + tap(texel, 2.0, 0.0) + tap(texel, 0.0, -2.0)) * 0.0625;
+    color += (tap(texel, -1.0, 1.0) + tap(texel, 1.0, 1.0)
+            + tap(texel, -1.0, -1.0) + tap(texel, 1.0, -1.0)) * 0.125;
+
+    // only what is brighter than the threshold glows, a threshold of zero
+    // keeps everything
+    float brightness = max(color.r, max(color.g, color.b));
+    color *= max(brightness - u_bloom.threshold, 0.0) / max(brightness, 1e-4);
+
+    frag_color = vec4(color, 1.0);
+}
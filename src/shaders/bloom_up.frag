@@ -0,0 +1,29 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Bloom {
+    float threshold;
+    // of the upsampling filter, in texels of the source
+    float radius;
+} u_bloom;
+layout(set = 0, binding = 1) uniform texture2D t_source;
+layout(set = 0, binding = 2) uniform sampler s_source;
+
+vec3 tap(vec2 texel, float x, float y) {
+    return texture(sampler2D(t_source, s_source), v_uv + vec2(x, y) * texel).rgb;
+}
+
+// 3x3 tent filter, added by blending over the level downsampled into the target
+void main() {
+    vec2 texel = u_bloom.radius / vec2(textureSize(sampler2D(t_source, s_source), 0));
+    vec3 color = tap(texel, 0.0, 0.0) * 4.0;
+    color += (tap(texel, 0.0, 1.0) + tap(texel, -1.0, 0.0)
+            + tap(texel, 1.0, 0.0) + tap(texel, 0.0, -1.0)) * 2.0;
+    color += tap(texel, -1.0, 1.0) + tap(texel, 1.0, 1.0)
+            + tap(texel, -1.0, -1.0) + tap(texel, 1.0, -1.0);
+
+    frag_color = vec4(color / 16.0, 1.0);
+}
@@ -0,0 +1,52 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Composite {
+    // zero without bloom, already divided by the number of levels
+    float bloom_intensity;
+    uint tone_mapping;
+    // zero without color grading
+    float lut_blend;
+} u_composite;
+layout(set = 0, binding = 1) uniform texture2D t_scene;
+layout(set = 0, binding = 2) uniform sampler s_source;
+layout(set = 0, binding = 3) uniform texture2D t_bloom;
+layout(set = 0, binding = 4) readonly buffer Exposure {
+    float luminance;
+    float exposure;
+} s_exposure;
+
+layout(set = 1, binding = 0) uniform texture3D t_lut;
+layout(set = 1, binding = 1) uniform sampler s_lut;
+
+vec3 to_srgb(vec3 color) {
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, color));
+}
+
+vec3 to_linear(vec3 color) {
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(0.04045, color));
+}
+
+void main() {
+    vec3 color = texture(sampler2D(t_scene, s_source), v_uv).rgb;
+    if (u_composite.bloom_intensity > 0.0) {
+        color += texture(sampler2D(t_bloom, s_source), v_uv).rgb * u_composite.bloom_intensity;
+    }
+    // exposed, then reinhard tone mapping, the output is already sRGB
+    if (u_composite.tone_mapping != 0) {
+        color *= s_exposure.exposure;
+        color = color / (color + 1.0);
+    }
+    // tables map display colors, sampled between the centers of their edge texels
+    if (u_composite.lut_blend > 0.0) {
+        vec3 display = to_srgb(clamp(color, 0.0, 1.0));
+        float size = float(textureSize(sampler3D(t_lut, s_lut), 0).x);
+        vec3 uvw = display * (size - 1.0) / size + 0.5 / size;
+        vec3 graded = texture(sampler3D(t_lut, s_lut), uvw).rgb;
+        color = to_linear(mix(display, graded, u_composite.lut_blend));
+    }
+    frag_color = vec4(color, 1.0);
+}
@@ -0,0 +1,41 @@
+#version 450
+
+struct Decal {
+    mat4 model;
+    mat4 inverse_model;
+    vec4 color;
+};
+
+layout(location = 0) flat in uint v_instance;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Decals {
+    mat4 view_projection;
+    mat4 inverse_view_projection;
+    vec2 viewport;
+} u_decals;
+layout(set = 0, binding = 1) readonly buffer Instances {
+    Decal decals[];
+};
+layout(set = 0, binding = 2) uniform texture2D t_decal;
+layout(set = 0, binding = 3) uniform sampler s_decal;
+layout(set = 1, binding = 0) uniform texture2D t_depth;
+
+void main() {
+    // the surface behind the fragment of the box
+    float depth = texelFetch(sampler2D(t_depth, s_decal), ivec2(gl_FragCoord.xy), 0).r;
+    vec2 uv = gl_FragCoord.xy / u_decals.viewport;
+    vec2 ndc = vec2(uv.x, 1.0 - uv.y) * 2.0 - 1.0;
+    vec4 world = u_decals.inverse_view_projection * vec4(ndc, depth, 1.0);
+    Decal decal = decals[v_instance];
+    vec3 local = (decal.inverse_model * vec4(world.xyz / world.w, 1.0)).xyz;
+    if (any(greaterThan(abs(local), vec3(0.5)))) {
+        discard;
+    }
+
+    // projected down the Y axis of the box, fading out towards its ends
+    vec4 color = texture(sampler2D(t_decal, s_decal), local.xz + 0.5) * decal.color;
+    color.a *= 1.0 - smoothstep(0.3, 0.5, abs(local.y));
+    frag_color = color;
+}
@@ -0,0 +1,44 @@
+#version 450
+
+struct Decal {
+    mat4 model;
+    mat4 inverse_model;
+    vec4 color;
+};
+
+layout(set = 0, binding = 0) uniform Decals {
+    mat4 view_projection;
+    mat4 inverse_view_projection;
+    vec2 viewport;
+} u_decals;
+layout(set = 0, binding = 1) readonly buffer Instances {
+    Decal decals[];
+};
+
+layout(location = 0) flat out uint v_instance;
+
+// unit cube, as triangles of the corners
+const vec3 CORNERS[8] = vec3[8](
+    vec3(-0.5, -0.5, -0.5),
+    vec3(0.5, -0.5, -0.5),
+    vec3(-0.5, 0.5, -0.5),
+    vec3(0.5, 0.5, -0.5),
+    vec3(-0.5, -0.5, 0.5),
+    vec3(0.5, -0.5, 0.5),
+    vec3(-0.5, 0.5, 0.5),
+    vec3(0.5, 0.5, 0.5)
+);
+const uint INDICES[36] = uint[36](
+    0, 2, 1, 1, 2, 3,
+    4, 5, 6, 5, 7, 6,
+    0, 4, 2, 2, 4, 6,
+    1, 3, 5, 3, 7, 5,
+    0, 1, 4, 1, 5, 4,
+    2, 6, 3, 3, 6, 7
+);
+
+void main() {
+    vec3 corner = CORNERS[INDICES[gl_VertexIndex]];
+    gl_Position = u_decals.view_projection * decals[gl_InstanceIndex].model * vec4(corner, 1.0);
+    v_instance = gl_InstanceIndex;
+}
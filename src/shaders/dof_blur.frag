@@ -0,0 +1,55 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+// alpha is how far the blur in front of the focus plane spreads, in pixels
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform DepthOfField {
+    float near;
+    float far;
+    float focus_distance;
+    float aperture;
+    // in pixels of the scene
+    float max_radius;
+} u_dof;
+layout(set = 0, binding = 1) uniform texture2D t_color;
+layout(set = 0, binding = 2) uniform sampler s_source;
+layout(set = 0, binding = 3) uniform texture2D t_coc;
+
+const float GOLDEN_ANGLE = 2.39996323;
+// distance between samples near the center, they get sparser further out
+const float RADIUS_STEP = 1.0;
+
+// samples along a spiral gathered by how much their circle of confusion
+// covers the center, so what is out of focus spreads over its neighbors
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(sampler2D(t_color, s_source), 0));
+    float center_coc = texture(sampler2D(t_coc, s_source), v_uv).r;
+    float center_size = abs(center_coc);
+
+    vec3 color = texture(sampler2D(t_color, s_source), v_uv).rgb;
+    float total = 1.0;
+    float near_spread = 0.0;
+    float radius = RADIUS_STEP;
+    for (float angle = 0.0; radius < u_dof.max_radius; angle += GOLDEN_ANGLE) {
+        vec2 uv = v_uv + vec2(cos(angle), sin(angle)) * radius * texel;
+        vec3 sample_color = texture(sampler2D(t_color, s_source), uv).rgb;
+        float sample_coc = texture(sampler2D(t_coc, s_source), uv).r;
+        float size = abs(sample_coc);
+        // what is behind the center can't blur over it much more than the
+        // center itself is blurred
+        if (sample_coc > center_coc) {
+            size = min(size, center_size * 2.0);
+        }
+        float weight = smoothstep(radius - 0.5, radius + 0.5, size);
+        color += mix(color / total, sample_color, weight);
+        total += 1.0;
+        if (sample_coc < 0.0) {
+            near_spread = max(near_spread, weight * size);
+        }
+        radius += RADIUS_STEP / radius;
+    }
+
+    frag_color = vec4(color / total, near_spread);
+}
@@ -0,0 +1,26 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out float frag_coc;
+
+layout(set = 0, binding = 0) uniform DepthOfField {
+    float near;
+    float far;
+    float focus_distance;
+    float aperture;
+    // in pixels of the scene
+    float max_radius;
+} u_dof;
+layout(set = 0, binding = 1) uniform texture2D t_depth;
+layout(set = 0, binding = 2) uniform sampler s_source;
+
+void main() {
+    float depth = texelFetch(sampler2D(t_depth, s_source), ivec2(gl_FragCoord.xy), 0).r;
+    // distance along the view direction, from the [0, 1] depth of the projection
+    float z = u_dof.near * u_dof.far / (u_dof.far - depth * (u_dof.far - u_dof.near));
+
+    // radius of the circle of confusion, negative in front of the focus plane
+    float coc = clamp(u_dof.aperture * (z - u_dof.focus_distance) / z, -1.0, 1.0);
+    frag_coc = coc * u_dof.max_radius;
+}
@@ -0,0 +1,28 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform DepthOfField {
+    float near;
+    float far;
+    float focus_distance;
+    float aperture;
+    // in pixels of the scene
+    float max_radius;
+} u_dof;
+layout(set = 0, binding = 1) uniform texture2D t_color;
+layout(set = 0, binding = 2) uniform sampler s_source;
+layout(set = 0, binding = 3) uniform texture2D t_coc;
+layout(set = 0, binding = 4) uniform texture2D t_blurred;
+
+void main() {
+    vec3 sharp = texture(sampler2D(t_color, s_source), v_uv).rgb;
+    vec4 blurred = texture(sampler2D(t_blurred, s_source), v_uv);
+    float coc = abs(texture(sampler2D(t_coc, s_source), v_uv).r);
+
+    // blur in front of the focus plane also covers what is sharp behind it
+    float size = max(coc, blurred.a);
+    frag_color = vec4(mix(sharp, blurred.rgb, smoothstep(0.5, 2.0, size)), 1.0);
+}
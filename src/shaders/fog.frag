@@ -0,0 +1,66 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+#define FOG_LINEAR 0
+#define FOG_EXP 1
+#define FOG_EXP2 2
+
+layout(set = 0, binding = 0) uniform Fog {
+    mat4 inverse_view_projection;
+    vec3 eye;
+    uint mode;
+    vec3 color;
+    float density;
+    // distances the fog starts at, and is full at for linear fog
+    float start;
+    float end;
+    // height the density is as set at, and how quickly it thins out above it
+    float height;
+    float height_falloff;
+    // zero to leave the sky (at the far plane) clear
+    uint sky;
+} u_fog;
+layout(set = 0, binding = 1) uniform texture2D t_scene;
+layout(set = 0, binding = 2) uniform sampler s_source;
+layout(set = 0, binding = 3) uniform texture2D t_depth;
+
+// average of the height falloff between two heights, analytically
+float height_density(float from, float to) {
+    float k = u_fog.height_falloff;
+    float a = exp(-k * (from - u_fog.height));
+    float delta = to - from;
+    if (abs(k * delta) < 0.001) {
+        return a;
+    }
+    float b = exp(-k * (to - u_fog.height));
+    return (a - b) / (k * delta);
+}
+
+void main() {
+    ivec2 coord = ivec2(gl_FragCoord.xy);
+    vec3 color = texelFetch(sampler2D(t_scene, s_source), coord, 0).rgb;
+    float depth = texelFetch(sampler2D(t_depth, s_source), coord, 0).r;
+    if (depth >= 1.0 && u_fog.sky == 0u) {
+        frag_color = vec4(color, 1.0);
+        return;
+    }
+
+    vec2 ndc = vec2(v_uv.x, 1.0 - v_uv.y) * 2.0 - 1.0;
+    vec4 world = u_fog.inverse_view_projection * vec4(ndc, depth, 1.0);
+    vec3 position = world.xyz / world.w;
+    float distance = length(position - u_fog.eye);
+    float height = u_fog.height_falloff > 0.0 ? height_density(u_fog.eye.y, position.y) : 1.0;
+
+    float fog;
+    if (u_fog.mode == FOG_LINEAR) {
+        fog = clamp((distance - u_fog.start) / max(u_fog.end - u_fog.start, 0.0001), 0.0, 1.0);
+        fog *= min(height, 1.0);
+    } else {
+        float optical_depth = max(distance - u_fog.start, 0.0) * u_fog.density * height;
+        fog = 1.0 - exp(u_fog.mode == FOG_EXP ? -optical_depth : -optical_depth * optical_depth);
+    }
+    frag_color = vec4(mix(color, u_fog.color, fog), 1.0);
+}
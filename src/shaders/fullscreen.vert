@@ -0,0 +1,11 @@
+#version 450
+
+layout(location = 0) out vec2 v_uv;
+
+// single triangle covering the whole viewport
+void main() {
+    vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+    gl_Position = vec4(position, 0.0, 1.0);
+
+    v_uv = vec2(position.x, -position.y) * 0.5 + 0.5;
+}
\ No newline at end of file
@@ -0,0 +1,49 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_scene;
+layout(set = 0, binding = 1) uniform sampler s_scene;
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+// longest blur along an edge, in texels
+const float SPAN_MAX = 8.0;
+
+vec3 sample_scene(vec2 uv) {
+    return texture(sampler2D(t_scene, s_scene), uv).rgb;
+}
+
+// of the tone mapped color, edges are found as they'll be seen
+float luma(vec3 color) {
+    float l = dot(color, vec3(0.299, 0.587, 0.114));
+    return l / (1.0 + l);
+}
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(sampler2D(t_scene, s_scene), 0));
+    float nw = luma(sample_scene(v_uv + vec2(-1.0, -1.0) * texel));
+    float ne = luma(sample_scene(v_uv + vec2(1.0, -1.0) * texel));
+    float sw = luma(sample_scene(v_uv + vec2(-1.0, 1.0) * texel));
+    float se = luma(sample_scene(v_uv + vec2(1.0, 1.0) * texel));
+    float m = luma(sample_scene(v_uv));
+    float low = min(m, min(min(nw, ne), min(sw, se)));
+    float high = max(m, max(max(nw, ne), max(sw, se)));
+
+    // across the luma gradient is along the edge
+    vec2 direction = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+    float reduce = max((nw + ne + sw + se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, -SPAN_MAX, SPAN_MAX) * texel;
+
+    vec3 inner = 0.5 * (sample_scene(v_uv + direction * (1.0 / 3.0 - 0.5))
+        + sample_scene(v_uv + direction * (2.0 / 3.0 - 0.5)));
+    vec3 outer = inner * 0.5 + 0.25 * (sample_scene(v_uv - direction * 0.5)
+        + sample_scene(v_uv + direction * 0.5));
+    // the longer blur when it doesn't cross into another edge
+    float outer_luma = luma(outer);
+    vec3 color = outer_luma < low || outer_luma > high ? inner : outer;
+    frag_color = vec4(color, 1.0);
+}
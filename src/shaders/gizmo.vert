@@ -0,0 +1,15 @@
+#version 450
+
+layout(location = 0) in vec3 a_position;
+layout(location = 1) in vec4 a_color;
+
+layout(location = 0) out vec4 v_color;
+
+layout(set = 0, binding = 0) uniform Gizmos {
+    mat4 view_projection;
+} u_gizmos;
+
+void main() {
+    gl_Position = u_gizmos.view_projection * vec4(a_position, 1.0);
+    v_color = a_color;
+}
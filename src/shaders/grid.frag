@@ -0,0 +1,60 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Grid {
+    mat4 view_projection;
+    mat4 inverse_view_projection;
+    vec3 eye;
+    // distance between the minor lines, the major ones are ten times apart
+    float spacing;
+    float fade_distance;
+} u_grid;
+
+vec3 unproject(vec2 ndc, float depth) {
+    vec4 position = u_grid.inverse_view_projection * vec4(ndc, depth, 1.0);
+    return position.xyz / position.w;
+}
+
+// coverage of the lines `spacing` apart, a pixel wide wherever they are
+float lines(vec2 grid) {
+    vec2 width = fwidth(grid);
+    vec2 line = abs(fract(grid - 0.5) - 0.5) / width;
+    // lines thinner than a pixel fade out instead of aliasing
+    float density = clamp(1.0 - max(width.x, width.y), 0.0, 1.0);
+    return (1.0 - min(min(line.x, line.y), 1.0)) * density;
+}
+
+void main() {
+    // the ground plane under the view ray of the pixel
+    vec2 ndc = vec2(v_uv.x, 1.0 - v_uv.y) * 2.0 - 1.0;
+    vec3 near = unproject(ndc, 0.0);
+    vec3 far = unproject(ndc, 1.0);
+    float t = -near.y / (far.y - near.y);
+    vec3 position = near + t * (far - near);
+    vec2 coord = position.xz;
+
+    float minor = lines(coord / u_grid.spacing);
+    float major = lines(coord / (u_grid.spacing * 10.0));
+    vec2 width = fwidth(coord);
+    float x_axis = 1.0 - min(abs(coord.y) / width.y, 1.0);
+    float z_axis = 1.0 - min(abs(coord.x) / width.x, 1.0);
+    if (t <= 0.0 || t > 1.0) {
+        discard;
+    }
+
+    vec4 color = vec4(vec3(0.5), 0.4 * minor);
+    color = mix(color, vec4(vec3(0.7), 0.8), major);
+    color = mix(color, vec4(1.0, 0.2, 0.2, 1.0), x_axis);
+    color = mix(color, vec4(0.2, 0.3, 1.0, 1.0), z_axis);
+    color.a *= 1.0 - smoothstep(0.0, u_grid.fade_distance, distance(position, u_grid.eye));
+    if (color.a <= 0.0) {
+        discard;
+    }
+
+    vec4 clip = u_grid.view_projection * vec4(position, 1.0);
+    gl_FragDepth = clip.z / clip.w;
+    frag_color = color;
+}
@@ -0,0 +1,58 @@
+#version 450
+
+#define PI 3.1415926535897932384626433832795
+#define SAMPLES 1024u
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec2 frag_color;
+
+float radical_inverse(uint bits) {
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10;
+}
+
+vec3 importance_sample_ggx(vec2 xi, float roughness) {
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+float geometry_schlick_ggx(float n_dot_v, float roughness) {
+    float k = roughness * roughness / 2.0;
+    return n_dot_v / (n_dot_v * (1.0 - k) + k);
+}
+
+// split sum scale (red) and bias (green) to F0, indexed by n.v and roughness
+void main() {
+    float n_dot_v = max(v_uv.x, 0.001);
+    float roughness = v_uv.y;
+    vec3 v = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+
+    float scale = 0.0;
+    float bias = 0.0;
+    for (uint i = 0u; i < SAMPLES; i++) {
+        vec2 xi = vec2(float(i) / float(SAMPLES), radical_inverse(i));
+        vec3 h = importance_sample_ggx(xi, roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+
+        float n_dot_l = max(l.z, 0.0);
+        float n_dot_h = max(h.z, 0.0);
+        float v_dot_h = max(dot(v, h), 0.0);
+        if (n_dot_l > 0.0) {
+            float g = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+            float g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+            float fc = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+
+    frag_color = vec2(scale, bias) / float(SAMPLES);
+}
\ No newline at end of file
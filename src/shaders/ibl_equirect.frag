@@ -0,0 +1,33 @@
+#version 450
+
+#define PI 3.1415926535897932384626433832795
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Face {
+    uint face;
+    float roughness;
+} u_face;
+layout(set = 0, binding = 1) uniform texture2D t_equirect;
+layout(set = 0, binding = 2) uniform sampler s_equirect;
+
+vec3 cube_direction(uint face, vec2 uv) {
+    vec2 st = uv * 2.0 - 1.0;
+    vec3 dirs[6] = vec3[6](
+        vec3(1.0, -st.y, -st.x),
+        vec3(-1.0, -st.y, st.x),
+        vec3(st.x, 1.0, st.y),
+        vec3(st.x, -1.0, -st.y),
+        vec3(st.x, -st.y, 1.0),
+        vec3(-st.x, -st.y, -1.0)
+    );
+    return normalize(dirs[face]);
+}
+
+void main() {
+    vec3 dir = cube_direction(u_face.face, v_uv);
+    vec2 uv = vec2(atan(dir.z, dir.x) / (2.0 * PI) + 0.5, acos(clamp(dir.y, -1.0, 1.0)) / PI);
+    frag_color = vec4(texture(sampler2D(t_equirect, s_equirect), uv).rgb, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,50 @@
+#version 450
+
+#define PI 3.1415926535897932384626433832795
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Face {
+    uint face;
+    float roughness;
+} u_face;
+layout(set = 0, binding = 1) uniform textureCube t_environment;
+layout(set = 0, binding = 2) uniform sampler s_environment;
+
+vec3 cube_direction(uint face, vec2 uv) {
+    vec2 st = uv * 2.0 - 1.0;
+    vec3 dirs[6] = vec3[6](
+        vec3(1.0, -st.y, -st.x),
+        vec3(-1.0, -st.y, st.x),
+        vec3(st.x, 1.0, st.y),
+        vec3(st.x, -1.0, -st.y),
+        vec3(st.x, -st.y, 1.0),
+        vec3(-st.x, -st.y, -1.0)
+    );
+    return normalize(dirs[face]);
+}
+
+// cosine weighted convolution of the hemisphere around the normal
+void main() {
+    vec3 normal = cube_direction(u_face.face, v_uv);
+    vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 right = normalize(cross(up, normal));
+    up = cross(normal, right);
+
+    const float delta = 0.05;
+    vec3 irradiance = vec3(0.0);
+    float samples = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += delta) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += delta) {
+            vec3 tangent = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 dir = tangent.x * right + tangent.y * up + tangent.z * normal;
+            vec3 radiance = textureLod(samplerCube(t_environment, s_environment), dir, 2.0).rgb;
+            irradiance += radiance * cos(theta) * sin(theta);
+            samples += 1.0;
+        }
+    }
+
+    frag_color = vec4(PI * irradiance / samples, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,86 @@
+#version 450
+
+#define PI 3.1415926535897932384626433832795
+#define SAMPLES 512u
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Face {
+    uint face;
+    float roughness;
+    float resolution;
+} u_face;
+layout(set = 0, binding = 1) uniform textureCube t_environment;
+layout(set = 0, binding = 2) uniform sampler s_environment;
+
+vec3 cube_direction(uint face, vec2 uv) {
+    vec2 st = uv * 2.0 - 1.0;
+    vec3 dirs[6] = vec3[6](
+        vec3(1.0, -st.y, -st.x),
+        vec3(-1.0, -st.y, st.x),
+        vec3(st.x, 1.0, st.y),
+        vec3(st.x, -1.0, -st.y),
+        vec3(st.x, -st.y, 1.0),
+        vec3(-st.x, -st.y, -1.0)
+    );
+    return normalize(dirs[face]);
+}
+
+float radical_inverse(uint bits) {
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10;
+}
+
+vec3 importance_sample_ggx(vec2 xi, vec3 normal, float roughness) {
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    vec3 h = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    vec3 up = abs(normal.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+    return normalize(tangent * h.x + bitangent * h.y + normal * h.z);
+}
+
+float distribution_ggx(float n_dot_h, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+void main() {
+    // assume the view direction equals the normal (split sum approximation)
+    vec3 normal = cube_direction(u_face.face, v_uv);
+    float roughness = u_face.roughness;
+
+    vec3 color = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < SAMPLES; i++) {
+        vec2 xi = vec2(float(i) / float(SAMPLES), radical_inverse(i));
+        vec3 h = importance_sample_ggx(xi, normal, roughness);
+        vec3 l = normalize(2.0 * dot(normal, h) * h - normal);
+        float n_dot_l = dot(normal, l);
+        if (n_dot_l > 0.0) {
+            // sample a lower mip to reduce aliasing of low probability samples
+            float n_dot_h = max(dot(normal, h), 0.0);
+            float pdf = distribution_ggx(n_dot_h, roughness) * 0.25 + 0.0001;
+            float texel = 4.0 * PI / (6.0 * u_face.resolution * u_face.resolution);
+            float sample_angle = 1.0 / (float(SAMPLES) * pdf + 0.0001);
+            float lod = roughness == 0.0 ? 0.0 : 0.5 * log2(sample_angle / texel) + 1.0;
+
+            color += textureLod(samplerCube(t_environment, s_environment), l, lod).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+
+    frag_color = vec4(color / weight, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Inspector {
+    // in stops
+    float exposure;
+    uint tone_mapping;
+} u_inspector;
+layout(set = 0, binding = 1) uniform texture2D t_source;
+layout(set = 0, binding = 2) uniform sampler s_source;
+
+void main() {
+    vec3 color = texture(sampler2D(t_source, s_source), v_uv).rgb * exp2(u_inspector.exposure);
+    if (u_inspector.tone_mapping != 0) {
+        color = color / (color + 1.0);
+    }
+    frag_color = vec4(color, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,39 @@
+#version 450
+
+layout(location = 0) in vec3 a_position;
+layout(location = 1) in vec3 a_normal;
+layout(location = 2) in vec4 a_tangent;
+layout(location = 3) in vec2 a_uv;
+layout(location = 4) in vec3 a_color;
+// position in xyz, uniform scale in w
+layout(location = 5) in vec4 a_instance;
+layout(location = 6) in uint a_material;
+
+layout(location = 0) out vec3 v_position;
+layout(location = 1) out vec3 v_normal;
+layout(location = 2) out vec4 v_tangent;
+layout(location = 3) out vec2 v_uv;
+layout(location = 4) out vec3 v_color;
+layout(location = 5) flat out uint v_material;
+
+// mvp holds the view-projection, instances are already in world space
+layout(set = 0, binding = 0) uniform Object {
+    mat4 mvp;
+    mat4 model;
+    float layer_spacing;
+    float outline_scale;
+    float metallic;
+    float roughness;
+} u_object;
+
+void main() {
+    vec3 position = a_position * a_instance.w + a_instance.xyz;
+    gl_Position = u_object.mvp * vec4(position, 1.0);
+
+    v_position = position;
+    v_normal = a_normal;
+    v_tangent = a_tangent;
+    v_uv = a_uv;
+    v_color = a_color;
+    v_material = a_material;
+}
\ No newline at end of file
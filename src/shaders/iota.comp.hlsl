@@ -0,0 +1,9 @@
+// Writes its index into every element of a buffer, the smallest HLSL shader
+// that still binds something, so the dxc path of the build is covered.
+RWStructuredBuffer<uint> values : register(u0);
+
+[numthreads(64, 1, 1)]
+void main(uint3 id : SV_DispatchThreadID)
+{
+    values[id.x] = id.x;
+}
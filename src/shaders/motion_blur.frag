@@ -0,0 +1,35 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform MotionBlur {
+    mat4 inverse_view_projection;
+    mat4 previous_view_projection;
+    // fraction of the frame the shutter is open for
+    float shutter;
+    uint samples;
+} u_blur;
+layout(set = 0, binding = 1) uniform texture2D t_color;
+layout(set = 0, binding = 2) uniform sampler s_source;
+layout(set = 0, binding = 3) uniform texture2D t_depth;
+
+void main() {
+    // where the pixel was on the previous frame, with the scene standing still
+    float depth = texelFetch(sampler2D(t_depth, s_source), ivec2(gl_FragCoord.xy), 0).r;
+    vec2 ndc = vec2(v_uv.x, 1.0 - v_uv.y) * 2.0 - 1.0;
+    vec4 world = u_blur.inverse_view_projection * vec4(ndc, depth, 1.0);
+    vec4 previous = u_blur.previous_view_projection * vec4(world.xyz / world.w, 1.0);
+    vec2 previous_ndc = previous.xy / previous.w;
+    vec2 previous_uv = vec2(previous_ndc.x, -previous_ndc.y) * 0.5 + 0.5;
+
+    // averaged along the way, centered on the pixel
+    vec2 velocity = (v_uv - previous_uv) * u_blur.shutter;
+    vec3 color = vec3(0.0);
+    for (uint i = 0; i < u_blur.samples; i++) {
+        float t = (float(i) + 0.5) / float(u_blur.samples) - 0.5;
+        color += texture(sampler2D(t_color, s_source), v_uv - velocity * t).rgb;
+    }
+    frag_color = vec4(color / float(u_blur.samples), 1.0);
+}
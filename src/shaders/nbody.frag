@@ -0,0 +1,17 @@
+#version 450
+
+layout(location = 0) in vec2 v_corner;
+layout(location = 1) in float v_speed;
+
+layout(location = 0) out vec4 frag_color;
+
+void main() {
+    float d = length(v_corner);
+    if (d > 1.0) {
+        discard;
+    }
+    // slow particles are blue, fast ones orange
+    vec3 color = mix(vec3(0.2, 0.4, 1.0), vec3(1.0, 0.6, 0.2), clamp(v_speed * 0.5, 0.0, 1.0));
+    float falloff = (1.0 - d) * (1.0 - d);
+    frag_color = vec4(color * falloff, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+#version 450
+
+layout(location = 0) in vec4 a_position;
+layout(location = 1) in vec4 a_velocity;
+
+layout(location = 0) out vec2 v_corner;
+layout(location = 1) out float v_speed;
+
+layout(set = 0, binding = 0) uniform Particles {
+    mat4 view_projection;
+    vec4 camera_right;
+    vec4 camera_up;
+    float size;
+} u_particles;
+
+// two triangles per particle, facing the camera
+const vec2 CORNERS[6] = vec2[](
+    vec2(-1.0, -1.0),
+    vec2(1.0, -1.0),
+    vec2(-1.0, 1.0),
+    vec2(-1.0, 1.0),
+    vec2(1.0, -1.0),
+    vec2(1.0, 1.0)
+);
+
+void main() {
+    v_corner = CORNERS[gl_VertexIndex];
+    vec3 offset = (u_particles.camera_right.xyz * v_corner.x + u_particles.camera_up.xyz * v_corner.y) * u_particles.size;
+    gl_Position = u_particles.view_projection * vec4(a_position.xyz + offset, 1.0);
+
+    v_speed = length(a_velocity.xyz);
+}
\ No newline at end of file
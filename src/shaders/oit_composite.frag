@@ -0,0 +1,22 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_accumulation;
+layout(set = 0, binding = 1) uniform texture2D t_revealage;
+layout(set = 0, binding = 2) uniform sampler s_oit;
+
+void main() {
+    ivec2 texel = ivec2(gl_FragCoord.xy);
+    float revealage = texelFetch(sampler2D(t_revealage, s_oit), texel, 0).r;
+    // nothing transparent was drawn over the pixel
+    if (revealage >= 1.0) {
+        discard;
+    }
+    vec4 accumulation = texelFetch(sampler2D(t_accumulation, s_oit), texel, 0);
+    // weighted average of the colors, blended by how much is covered
+    vec3 color = accumulation.rgb / max(accumulation.a, 1e-5);
+    frag_color = vec4(color, 1.0 - revealage);
+}
@@ -0,0 +1,7 @@
+#version 450
+
+layout(location = 0) out vec4 frag_color;
+
+void main() {
+    frag_color = vec4(1.0, 0.6, 0.0, 1.0);
+}
\ No newline at end of file
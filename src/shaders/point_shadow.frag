@@ -0,0 +1,15 @@
+#version 450
+
+layout(location = 0) in vec3 v_position;
+
+layout(location = 0) out float frag_distance;
+
+layout(set = 1, binding = 0) uniform Face {
+    mat4 view_projection;
+    vec4 light_position;
+} u_face;
+
+// distance to the light, normalized by its range
+void main() {
+    frag_distance = length(v_position - u_face.light_position.xyz) / u_face.light_position.w;
+}
\ No newline at end of file
@@ -0,0 +1,85 @@
+#version 450
+
+layout(location = 0) in vec3 a_position;
+
+layout(location = 0) out vec3 v_position;
+
+layout(set = 0, binding = 0) uniform Object {
+    mat4 mvp;
+    mat4 model;
+    float layer_spacing;
+    float outline_scale;
+    float metallic;
+    float roughness;
+    vec4 base_color;
+    vec4 emissive;
+    uvec4 textures;
+    uint occlusion_texture;
+    uint alpha_mode;
+    uint double_sided;
+    float normal_scale;
+    float occlusion_strength;
+    uint skinned;
+    uint morph_targets;
+    uint vertex_count;
+} u_object;
+
+struct SkinVertex {
+    uvec4 joints;
+    vec4 weights;
+};
+layout(set = 0, binding = 1) readonly buffer Joints {
+    mat4 joints[];
+} b_joints;
+// indexed by vertex, the meshes are drawn without a base vertex
+layout(set = 0, binding = 2) readonly buffer Skin {
+    SkinVertex vertices[];
+} b_skin;
+
+mat4 skin_matrix() {
+    if (u_object.skinned == 0u) {
+        return mat4(1.0);
+    }
+    SkinVertex vertex = b_skin.vertices[gl_VertexIndex];
+    return vertex.weights.x * b_joints.joints[vertex.joints.x]
+        + vertex.weights.y * b_joints.joints[vertex.joints.y]
+        + vertex.weights.z * b_joints.joints[vertex.joints.z]
+        + vertex.weights.w * b_joints.joints[vertex.joints.w];
+}
+
+struct MorphDelta {
+    vec4 position;
+    vec4 normal;
+    vec4 tangent;
+};
+// deltas of every vertex in the first target, then in the second one...
+layout(set = 0, binding = 3) readonly buffer Morph {
+    MorphDelta deltas[];
+} b_morph;
+layout(set = 0, binding = 4) readonly buffer Weights {
+    float weights[];
+} b_weights;
+
+MorphDelta morph_delta() {
+    MorphDelta delta = MorphDelta(vec4(0.0), vec4(0.0), vec4(0.0));
+    for (uint t = 0u; t < u_object.morph_targets; t++) {
+        MorphDelta target = b_morph.deltas[t * u_object.vertex_count + uint(gl_VertexIndex)];
+        float weight = b_weights.weights[t];
+        delta.position += weight * target.position;
+        delta.normal += weight * target.normal;
+        delta.tangent += weight * target.tangent;
+    }
+    return delta;
+}
+
+layout(set = 1, binding = 0) uniform Face {
+    mat4 view_projection;
+    vec4 light_position;
+} u_face;
+
+void main() {
+    vec3 morphed = a_position + morph_delta().position.xyz;
+    vec4 position = u_object.model * skin_matrix() * vec4(morphed, 1.0);
+    gl_Position = u_face.view_projection * position;
+    v_position = position.xyz;
+}
\ No newline at end of file
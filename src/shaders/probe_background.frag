@@ -0,0 +1,31 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Face {
+    mat4 view_projection;
+    uint face;
+} u_face;
+layout(set = 0, binding = 1) uniform textureCube t_environment;
+layout(set = 0, binding = 2) uniform sampler s_environment;
+
+// same as the IBL shaders
+vec3 cube_direction(uint face, vec2 uv) {
+    vec2 st = uv * 2.0 - 1.0;
+    vec3 dirs[6] = vec3[6](
+        vec3(1.0, -st.y, -st.x),
+        vec3(-1.0, -st.y, st.x),
+        vec3(st.x, 1.0, st.y),
+        vec3(st.x, -1.0, -st.y),
+        vec3(st.x, -st.y, 1.0),
+        vec3(-st.x, -st.y, -1.0)
+    );
+    return normalize(dirs[face]);
+}
+
+void main() {
+    vec3 direction = cube_direction(u_face.face, v_uv);
+    frag_color = vec4(textureLod(samplerCube(t_environment, s_environment), direction, 0.0).rgb, 1.0);
+}
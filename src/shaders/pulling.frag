@@ -0,0 +1,14 @@
+#version 450
+
+layout(location = 0) in vec3 v_normal;
+layout(location = 1) in vec3 v_color;
+
+layout(location = 0) out vec4 frag_color;
+
+const vec3 LIGHT = vec3(0.4, 1.0, 0.3);
+
+void main() {
+    // both paths shade the same, only the vertex fetch differs
+    float diffuse = max(dot(normalize(v_normal), normalize(LIGHT)), 0.0);
+    frag_color = vec4(v_color * (0.15 + 0.85 * diffuse), 1.0);
+}
@@ -0,0 +1,54 @@
+#version 450
+
+#ifdef PULLED
+// vertices as the floats of `mesh::Vertex`, which has no padding a vec3 in a
+// storage buffer would add
+const uint VERTEX_FLOATS = 15u;
+layout(set = 1, binding = 0) readonly buffer Vertices {
+    float vertices[];
+} b_vertices;
+#else
+layout(location = 0) in vec3 a_position;
+layout(location = 1) in vec3 a_normal;
+layout(location = 4) in vec3 a_color;
+#endif
+
+layout(location = 0) out vec3 v_normal;
+layout(location = 1) out vec3 v_color;
+
+layout(set = 0, binding = 0) uniform Pulling {
+    mat4 view_projection;
+    float time;
+    uint columns;
+    float spacing;
+} u_pulling;
+
+#ifdef PULLED
+vec3 fetch(uint offset) {
+    // indexed draws set gl_VertexIndex to the index, there's no base vertex
+    uint base = uint(gl_VertexIndex) * VERTEX_FLOATS + offset;
+    return vec3(b_vertices.vertices[base], b_vertices.vertices[base + 1u], b_vertices.vertices[base + 2u]);
+}
+#endif
+
+void main() {
+#ifdef PULLED
+    vec3 position = fetch(0u);
+    vec3 normal = fetch(3u);
+    vec3 color = fetch(12u);
+#else
+    vec3 position = a_position;
+    vec3 normal = a_normal;
+    vec3 color = a_color;
+#endif
+    // a grid of instances on the XZ plane, each spinning at its own phase
+    uint column = uint(gl_InstanceIndex) % u_pulling.columns;
+    uint row = uint(gl_InstanceIndex) / u_pulling.columns;
+    vec2 cell = (vec2(column, row) - 0.5 * float(u_pulling.columns - 1u)) * u_pulling.spacing;
+    float angle = u_pulling.time + float(gl_InstanceIndex) * 0.37;
+    mat3 spin = mat3(cos(angle), 0.0, -sin(angle), 0.0, 1.0, 0.0, sin(angle), 0.0, cos(angle));
+    vec3 world = spin * position + vec3(cell.x, 0.0, cell.y);
+    gl_Position = u_pulling.view_projection * vec4(world, 1.0);
+    v_normal = spin * normal;
+    v_color = color;
+}
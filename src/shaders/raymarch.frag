@@ -0,0 +1,144 @@
+#version 450
+
+#define MAX_STEPS 128
+#define MAX_DISTANCE 100.0
+#define EPSILON 0.001
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Raymarch {
+    mat4 inverse_view_projection;
+    vec4 camera_position;
+    vec4 light_direction;
+    float time;
+} u_raymarch;
+
+float sd_sphere(vec3 p, float radius) {
+    return length(p) - radius;
+}
+
+float sd_round_box(vec3 p, vec3 size, float radius) {
+    vec3 q = abs(p) - size;
+    return length(max(q, 0.0)) + min(max(q.x, max(q.y, q.z)), 0.0) - radius;
+}
+
+float sd_torus(vec3 p, vec2 t) {
+    vec2 q = vec2(length(p.xz) - t.x, p.y);
+    return length(q) - t.y;
+}
+
+float smooth_union(float a, float b, float k) {
+    float h = clamp(0.5 + 0.5 * (b - a) / k, 0.0, 1.0);
+    return mix(b, a, h) - k * h * (1.0 - h);
+}
+
+mat2 rotate(float angle) {
+    float c = cos(angle);
+    float s = sin(angle);
+    return mat2(c, -s, s, c);
+}
+
+// distance in x, material in y
+vec2 scene(vec3 p) {
+    float t = u_raymarch.time;
+    float ground = p.y + 1.2;
+
+    vec3 box_p = p - vec3(0.0, 0.0, 0.0);
+    box_p.xz = rotate(t * 0.7) * box_p.xz;
+    box_p.xy = rotate(t * 0.3) * box_p.xy;
+    float blob = sd_round_box(box_p, vec3(0.35), 0.1);
+    blob = smooth_union(blob, sd_sphere(p - vec3(sin(t) * 0.8, 0.3 * cos(t * 1.3), 0.0), 0.35), 0.3);
+    blob = smooth_union(blob, sd_sphere(p - vec3(0.0, sin(t * 0.8) * 0.7, cos(t * 0.8) * 0.7), 0.25), 0.3);
+
+    vec3 torus_p = p - vec3(-1.6, -0.4, -0.5);
+    torus_p.yz = rotate(t) * torus_p.yz;
+    float torus = sd_torus(torus_p, vec2(0.45, 0.15));
+
+    vec2 result = vec2(ground, 0.0);
+    if (blob < result.x) {
+        result = vec2(blob, 1.0);
+    }
+    if (torus < result.x) {
+        result = vec2(torus, 2.0);
+    }
+    return result;
+}
+
+vec3 scene_normal(vec3 p) {
+    vec2 e = vec2(EPSILON, 0.0);
+    return normalize(vec3(
+        scene(p + e.xyy).x - scene(p - e.xyy).x,
+        scene(p + e.yxy).x - scene(p - e.yxy).x,
+        scene(p + e.yyx).x - scene(p - e.yyx).x
+    ));
+}
+
+float soft_shadow(vec3 origin, vec3 dir) {
+    float shadow = 1.0;
+    float t = 0.02;
+    for (int i = 0; i < 64 && t < 20.0; i++) {
+        float d = scene(origin + dir * t).x;
+        if (d < EPSILON) {
+            return 0.0;
+        }
+        shadow = min(shadow, 8.0 * d / t);
+        t += d;
+    }
+    return clamp(shadow, 0.0, 1.0);
+}
+
+float ambient_occlusion(vec3 p, vec3 normal) {
+    float occlusion = 0.0;
+    float scale = 1.0;
+    for (int i = 1; i <= 5; i++) {
+        float h = 0.03 * float(i);
+        occlusion += (h - scene(p + normal * h).x) * scale;
+        scale *= 0.7;
+    }
+    return clamp(1.0 - 3.0 * occlusion, 0.0, 1.0);
+}
+
+void main() {
+    // ray through the pixel, from the near to the far plane
+    vec2 ndc = vec2(v_uv.x, 1.0 - v_uv.y) * 2.0 - 1.0;
+    vec4 far = u_raymarch.inverse_view_projection * vec4(ndc, 1.0, 1.0);
+    vec3 origin = u_raymarch.camera_position.xyz;
+    vec3 dir = normalize(far.xyz / far.w - origin);
+
+    vec3 sky = mix(vec3(0.9, 0.9, 1.0), vec3(0.3, 0.5, 0.9), clamp(dir.y, 0.0, 1.0));
+    vec3 color = sky;
+
+    float t = 0.0;
+    for (int i = 0; i < MAX_STEPS && t < MAX_DISTANCE; i++) {
+        vec2 hit = scene(origin + dir * t);
+        if (hit.x < EPSILON * t) {
+            vec3 p = origin + dir * t;
+            vec3 normal = scene_normal(p);
+            vec3 light = -normalize(u_raymarch.light_direction.xyz);
+
+            vec3 albedo = vec3(0.8);
+            if (hit.y == 0.0) {
+                float checker = mod(floor(p.x) + floor(p.z), 2.0);
+                albedo = mix(vec3(0.4), vec3(0.8), checker);
+            } else if (hit.y == 1.0) {
+                albedo = vec3(0.9, 0.4, 0.2);
+            } else {
+                albedo = vec3(0.2, 0.6, 0.9);
+            }
+
+            float diffuse = max(dot(normal, light), 0.0) * soft_shadow(p + normal * 0.01, light);
+            float specular = pow(max(dot(reflect(-light, normal), -dir), 0.0), 32.0) * diffuse;
+            float ambient = 0.2 * ambient_occlusion(p, normal);
+            color = albedo * (diffuse + ambient) + vec3(specular * 0.5);
+
+            // fade into the sky with distance
+            color = mix(color, sky, 1.0 - exp(-0.002 * t * t));
+            break;
+        }
+        t += hit.x;
+    }
+
+    frag_color = vec4(color, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,520 @@
+#version 450
+
+#define PI 3.1415926535897932384626433832795
+#define CASCADES 4u
+#define NO_MATERIAL 0xffffffffu
+#define ALPHA_MASK 1u
+#define ALPHA_BLEND 2u
+// same values as DebugView
+#define DEBUG_FINAL 0u
+#define DEBUG_ALBEDO 1u
+#define DEBUG_NORMALS 2u
+#define DEBUG_ROUGHNESS_METALNESS 3u
+#define DEBUG_DEPTH 4u
+#define DEBUG_UVS 5u
+#define DEBUG_OVERDRAW 6u
+#define DEBUG_MIP_LEVEL 7u
+#define DEBUG_LIGHT_CLUSTERS 8u
+#define DEBUG_LOD 9u
+// same values as LightKind
+#define LIGHT_DIRECTIONAL 0u
+#define LIGHT_POINT 1u
+#define LIGHT_SPOT 2u
+// same values as clusters.rs
+#define CLUSTERS_X 16u
+#define CLUSTERS_Y 9u
+#define CLUSTERS_Z 24u
+#define CLUSTER_COUNT (CLUSTERS_X * CLUSTERS_Y * CLUSTERS_Z)
+#define MAX_CLUSTER_LIGHTS 64u
+// toggles of shader variants, defined as 0 to leave the code out. The
+// uniforms are still checked, the built in SPIR-V has everything in.
+#ifndef NORMAL_MAPPING
+#define NORMAL_MAPPING 1
+#endif
+#ifndef SHADOWS
+#define SHADOWS 1
+#endif
+// weighted blended order independent transparency, built as its own shader
+#ifndef OIT
+#define OIT 0
+#endif
+
+layout(location = 0) in vec3 v_position;
+layout(location = 1) in vec3 v_normal;
+layout(location = 2) in vec4 v_tangent;
+layout(location = 3) in vec2 v_uv;
+layout(location = 4) in vec3 v_color;
+layout(location = 5) flat in uint v_material;
+
+#if OIT
+// written by shade(), then weighted into both targets
+vec4 frag_color;
+layout(location = 0) out vec4 frag_accumulation;
+layout(location = 1) out float frag_revealage;
+#else
+layout(location = 0) out vec4 frag_color;
+#endif
+
+layout(set = 0, binding = 0) uniform Object {
+    mat4 mvp;
+    mat4 model;
+    float layer_spacing;
+    float outline_scale;
+    float metallic;
+    float roughness;
+    vec4 base_color;
+    // alpha cutoff in w
+    vec4 emissive;
+    // base color, metallic-roughness, normal and emissive layers
+    uvec4 textures;
+    uint occlusion_texture;
+    uint alpha_mode;
+    uint double_sided;
+    float normal_scale;
+    float occlusion_strength;
+    uint skinned;
+    uint morph_targets;
+    uint vertex_count;
+    // level of detail drawn
+    uint lod;
+} u_object;
+
+layout(set = 1, binding = 0) uniform Lighting {
+    vec3 light_direction;
+    vec3 light_color;
+    vec3 camera_position;
+    vec3 camera_forward;
+    mat4 cascade_view_projections[CASCADES];
+    vec4 cascade_splits;
+    vec3 point_light_position;
+    float point_light_range;
+    vec3 point_light_color;
+    uint normal_mapping;
+    uint ibl;
+    float prefiltered_mips;
+    uint shadows;
+    uint cascade_debug;
+    uint point_shadows;
+    uint debug_view;
+    // view depth shown as white by the depth view
+    float debug_depth_range;
+    float shadow_bias;
+    float shadow_normal_offset;
+    uint shadow_pcf_radius;
+    uint light_count;
+    // position of the reflection probe in xyz, radius in w
+    vec4 probe;
+    uint reflection_probe;
+} u_lighting;
+layout(set = 1, binding = 1) uniform texture2D t_normal;
+layout(set = 1, binding = 2) uniform sampler s_normal;
+layout(set = 1, binding = 3) uniform textureCube t_irradiance;
+layout(set = 1, binding = 4) uniform textureCube t_prefiltered;
+layout(set = 1, binding = 5) uniform texture2D t_brdf;
+layout(set = 1, binding = 6) uniform sampler s_ibl;
+layout(set = 1, binding = 7) uniform texture2DArray t_shadow;
+layout(set = 1, binding = 8) uniform samplerShadow s_shadow;
+layout(set = 1, binding = 9) uniform textureCube t_point_shadow;
+layout(set = 1, binding = 10) uniform sampler s_point_shadow;
+// albedos, sampled with the normal map sampler
+layout(set = 1, binding = 11) uniform texture2DArray t_materials;
+// metallic-roughness, normal and occlusion textures
+layout(set = 1, binding = 12) uniform texture2DArray t_linear_materials;
+
+struct Light {
+    // range in w
+    vec4 position;
+    vec3 direction;
+    // multiplied by the intensity
+    vec3 color;
+    float cos_inner;
+    float cos_outer;
+    uint kind;
+};
+layout(set = 1, binding = 13) readonly buffer Lights {
+    Light lights[];
+} b_lights;
+// prefiltered like t_prefiltered
+layout(set = 1, binding = 14) uniform textureCube t_probe;
+// camera the lights were culled for, with the culled lists
+layout(set = 1, binding = 15) uniform Clusters {
+    mat4 view;
+    mat4 projection;
+    mat4 inverse_projection;
+    float near;
+    float far;
+    uint light_count;
+    uint enabled;
+} u_clusters;
+layout(set = 1, binding = 16) readonly buffer ClusterCounts {
+    uint counts[];
+} b_cluster_counts;
+layout(set = 1, binding = 17) readonly buffer ClusterLights {
+    uint indices[];
+} b_cluster_lights;
+
+const vec3 CASCADE_COLORS[CASCADES] = vec3[CASCADES](
+    vec3(1.0, 0.0, 0.0),
+    vec3(0.0, 1.0, 0.0),
+    vec3(0.0, 0.0, 1.0),
+    vec3(1.0, 1.0, 0.0)
+);
+
+float distribution_ggx(float n_dot_h, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float roughness) {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// reflected radiance for a light of unit intensity (Cook-Torrance)
+vec3 direct_light(vec3 normal, vec3 view, vec3 light, vec3 albedo, float metallic, float roughness, vec3 f0) {
+    vec3 half_vector = normalize(light + view);
+    float n_dot_v = max(dot(normal, view), 0.0001);
+    float n_dot_l = max(dot(normal, light), 0.0);
+
+    vec3 f = fresnel_schlick(max(dot(half_vector, view), 0.0), f0);
+    float d = distribution_ggx(max(dot(normal, half_vector), 0.0), roughness);
+    float g = geometry_smith(n_dot_v, n_dot_l, roughness);
+    vec3 specular = d * g * f / (4.0 * n_dot_v * max(n_dot_l, 0.0001));
+    vec3 diffuse = (1.0 - f) * (1.0 - metallic) * albedo / PI;
+    return (diffuse + specular) * n_dot_l;
+}
+
+// first cascade containing the fragment, or CASCADES if none does
+uint select_cascade() {
+    float depth = dot(v_position - u_lighting.camera_position, u_lighting.camera_forward);
+    for (uint i = 0u; i < CASCADES; i++) {
+        if (depth < u_lighting.cascade_splits[i]) {
+            return i;
+        }
+    }
+    return CASCADES;
+}
+
+float shadow(uint cascade, vec3 normal) {
+    if (cascade == CASCADES) {
+        return 1.0;
+    }
+
+    // offset along the normal to avoid acne on surfaces facing away from the light
+    vec3 position = v_position + normal * u_lighting.shadow_normal_offset * float(cascade + 1u);
+    vec4 clip = u_lighting.cascade_view_projections[cascade] * vec4(position, 1.0);
+    vec3 ndc = clip.xyz / clip.w;
+    vec2 uv = vec2(ndc.x, -ndc.y) * 0.5 + 0.5;
+
+    // square PCF kernel on top of the hardware 2x2 filtering
+    vec2 texel = 1.0 / vec2(textureSize(sampler2DArrayShadow(t_shadow, s_shadow), 0).xy);
+    int radius = int(u_lighting.shadow_pcf_radius);
+    float lit = 0.0;
+    for (int x = -radius; x <= radius; x++) {
+        for (int y = -radius; y <= radius; y++) {
+            vec4 coords = vec4(uv + vec2(x, y) * texel, float(cascade), ndc.z - u_lighting.shadow_bias);
+            lit += texture(sampler2DArrayShadow(t_shadow, s_shadow), coords);
+        }
+    }
+    float width = float(2 * radius + 1);
+    return lit / (width * width);
+}
+
+// smoothly attenuated to zero at the range
+float range_attenuation(float distance, float range) {
+    float falloff = clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0);
+    return falloff * falloff / (distance * distance + 1.0);
+}
+
+float point_shadow(vec3 to_light, vec3 normal) {
+    float range = u_lighting.point_light_range;
+    float current = length(to_light) / range - 0.01;
+
+    // a few taps around the sample direction to soften the edges
+    vec3 dir = -to_light + normal * 0.02;
+    vec3 side = normalize(cross(dir, abs(dir.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0)));
+    vec3 up = normalize(cross(side, dir));
+    float radius = length(dir) * 0.005;
+    vec3 offsets[5] = vec3[5](vec3(0.0), side, -side, up, -up);
+
+    float lit = 0.0;
+    for (int i = 0; i < 5; i++) {
+        float closest = texture(samplerCube(t_point_shadow, s_point_shadow), dir + offsets[i] * radius).r;
+        lit += current > closest ? 0.0 : 1.0;
+    }
+    return lit / 5.0;
+}
+
+// how much the reflection probe replaces the environment, fading out at its
+// radius
+float probe_weight() {
+    float distance = length(v_position - u_lighting.probe.xyz);
+    float radius = u_lighting.probe.w;
+    return clamp((radius - distance) / (radius * 0.2), 0.0, 1.0);
+}
+
+// reflection from the probe, projected on the sphere of its radius so
+// reflections of objects around it line up
+vec3 probe_reflection(vec3 reflected, float lod) {
+    vec3 from_center = v_position - u_lighting.probe.xyz;
+    float radius = u_lighting.probe.w;
+    // from inside the sphere the ray always leaves it ahead
+    float b = dot(from_center, reflected);
+    float c = dot(from_center, from_center) - radius * radius;
+    float t = -b + sqrt(max(b * b - c, 0.0));
+    vec3 direction = from_center + reflected * t;
+    return textureLod(samplerCube(t_probe, s_ibl), direction, lod).rgb;
+}
+
+// cluster of the fragment, CLUSTER_COUNT if it's outside of the grid
+uint cluster_index() {
+    if (u_clusters.enabled == 0u) {
+        return CLUSTER_COUNT;
+    }
+    vec4 view_position = u_clusters.view * vec4(v_position, 1.0);
+    vec4 clip = u_clusters.projection * view_position;
+    float depth = -view_position.z;
+    if (clip.w <= 0.0 || depth < u_clusters.near || depth >= u_clusters.far) {
+        return CLUSTER_COUNT;
+    }
+    vec2 ndc = clip.xy / clip.w;
+    if (any(greaterThan(abs(ndc), vec2(1.0)))) {
+        return CLUSTER_COUNT;
+    }
+    vec2 tile = (ndc * 0.5 + 0.5) * vec2(CLUSTERS_X, CLUSTERS_Y);
+    uint x = min(uint(tile.x), CLUSTERS_X - 1u);
+    uint y = min(uint(tile.y), CLUSTERS_Y - 1u);
+    float slice = log(depth / u_clusters.near) / log(u_clusters.far / u_clusters.near);
+    uint z = min(uint(slice * float(CLUSTERS_Z)), CLUSTERS_Z - 1u);
+    return x + (y + z * CLUSTERS_Y) * CLUSTERS_X;
+}
+
+const vec3 MIP_COLORS[6] = vec3[6](
+    vec3(0.0, 0.0, 1.0),
+    vec3(0.0, 1.0, 1.0),
+    vec3(0.0, 1.0, 0.0),
+    vec3(1.0, 1.0, 0.0),
+    vec3(1.0, 0.5, 0.0),
+    vec3(1.0, 0.0, 0.0)
+);
+
+// mip level sampled from a texture of the given size, from the uv derivatives
+float mip_level(vec2 size) {
+    vec2 dx = dFdx(v_uv * size);
+    vec2 dy = dFdy(v_uv * size);
+    return max(0.5 * log2(max(dot(dx, dx), dot(dy, dy))), 0.0);
+}
+
+vec4 sample_color(uint layer) {
+    return texture(sampler2DArray(t_materials, s_normal), vec3(v_uv, float(layer)));
+}
+
+vec4 sample_linear(uint layer) {
+    return texture(sampler2DArray(t_linear_materials, s_normal), vec3(v_uv, float(layer)));
+}
+
+#if OIT
+void shade() {
+#else
+void main() {
+#endif
+    // the pipelines don't cull, one-sided materials do it here
+    if (!gl_FrontFacing && u_object.double_sided == 0u) {
+        discard;
+    }
+
+    // instances select their own layer
+    vec4 base_color = u_object.base_color * vec4(v_color, 1.0);
+    uint base_color_layer = v_material != NO_MATERIAL ? v_material : u_object.textures.x;
+    if (base_color_layer != NO_MATERIAL) {
+        base_color *= sample_color(base_color_layer);
+    }
+    if (u_object.alpha_mode == ALPHA_MASK && base_color.a < u_object.emissive.w) {
+        discard;
+    }
+    vec3 albedo = base_color.rgb;
+
+    vec3 normal = normalize(v_normal);
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+
+    // the material's own normal map, or the global one
+#if NORMAL_MAPPING
+    if (u_lighting.normal_mapping != 0) {
+        vec3 tangent = normalize(v_tangent.xyz - normal * dot(normal, v_tangent.xyz));
+        vec3 bitangent = cross(normal, tangent) * v_tangent.w;
+        vec3 texel;
+        if (u_object.textures.z != NO_MATERIAL) {
+            texel = sample_linear(u_object.textures.z).xyz * 2.0 - 1.0;
+            texel.xy *= u_object.normal_scale;
+        } else {
+            texel = texture(sampler2D(t_normal, s_normal), v_uv).xyz * 2.0 - 1.0;
+        }
+        normal = normalize(mat3(tangent, bitangent, normal) * texel);
+    }
+#endif
+
+    float metallic = u_object.metallic;
+    float roughness = u_object.roughness;
+    if (u_object.textures.y != NO_MATERIAL) {
+        vec4 texel = sample_linear(u_object.textures.y);
+        roughness *= texel.g;
+        metallic *= texel.b;
+    }
+    roughness = max(roughness, 0.04);
+    float occlusion = 1.0;
+    if (u_object.occlusion_texture != NO_MATERIAL) {
+        occlusion = mix(1.0, sample_linear(u_object.occlusion_texture).r, u_object.occlusion_strength);
+    }
+    vec3 emissive = u_object.emissive.rgb;
+    if (u_object.textures.w != NO_MATERIAL) {
+        emissive *= sample_color(u_object.textures.w).rgb;
+    }
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+
+    if (u_lighting.debug_view != DEBUG_FINAL) {
+        vec3 debug = vec3(0.0);
+        if (u_lighting.debug_view == DEBUG_ALBEDO) {
+            debug = albedo;
+        } else if (u_lighting.debug_view == DEBUG_NORMALS) {
+            debug = normal * 0.5 + 0.5;
+        } else if (u_lighting.debug_view == DEBUG_ROUGHNESS_METALNESS) {
+            debug = vec3(0.0, roughness, metallic);
+        } else if (u_lighting.debug_view == DEBUG_DEPTH) {
+            float depth = dot(v_position - u_lighting.camera_position, u_lighting.camera_forward);
+            debug = vec3(clamp(depth / u_lighting.debug_depth_range, 0.0, 1.0));
+        } else if (u_lighting.debug_view == DEBUG_UVS) {
+            debug = vec3(fract(v_uv), 0.0);
+        } else if (u_lighting.debug_view == DEBUG_OVERDRAW) {
+            // added up by the overdraw pipelines, red then yellow then white
+            debug = vec3(0.1, 0.05, 0.025);
+        } else if (u_lighting.debug_view == DEBUG_MIP_LEVEL) {
+            // of the base color texture, or the global normal map without one
+            vec2 size = base_color_layer != NO_MATERIAL
+                ? vec2(textureSize(sampler2DArray(t_materials, s_normal), 0).xy)
+                : vec2(textureSize(sampler2D(t_normal, s_normal), 0));
+            debug = MIP_COLORS[min(uint(mip_level(size) + 0.5), 5u)];
+        } else if (u_lighting.debug_view == DEBUG_LIGHT_CLUSTERS) {
+            // blue for a few lights to red for a full list, black without
+            // any and grey outside of the grid
+            uint cluster = cluster_index();
+            if (cluster < CLUSTER_COUNT) {
+                float t = float(b_cluster_counts.counts[cluster]) / float(MAX_CLUSTER_LIGHTS);
+                uint color = min(uint(t * 5.0 + 0.5), 5u);
+                debug = b_cluster_counts.counts[cluster] == 0u ? vec3(0.0) : MIP_COLORS[color];
+            } else {
+                debug = vec3(0.25);
+            }
+        } else if (u_lighting.debug_view == DEBUG_LOD) {
+            debug = mix(albedo, MIP_COLORS[min(u_object.lod, 5u)], 0.6);
+        }
+        frag_color = vec4(debug, 1.0);
+        return;
+    }
+
+    vec3 view = normalize(u_lighting.camera_position - v_position);
+    float n_dot_v = max(dot(normal, view), 0.0001);
+
+    // directional light
+    vec3 light = -normalize(u_lighting.light_direction);
+    vec3 color = direct_light(normal, view, light, albedo, metallic, roughness, f0) * u_lighting.light_color;
+
+    uint cascade = select_cascade();
+#if SHADOWS
+    if (u_lighting.shadows != 0) {
+        color *= shadow(cascade, normal);
+    }
+#endif
+
+    // point light
+    vec3 to_light = u_lighting.point_light_position - v_position;
+    float distance = length(to_light);
+    float attenuation = range_attenuation(distance, u_lighting.point_light_range);
+    vec3 point = direct_light(normal, view, to_light / distance, albedo, metallic, roughness, f0);
+    point *= u_lighting.point_light_color * attenuation;
+    if (u_lighting.point_shadows != 0) {
+        point *= point_shadow(to_light, normal);
+    }
+    color += point;
+
+    // unshadowed lights, those of the cluster or all of them outside of it
+    uint cluster = cluster_index();
+    bool clustered = cluster < CLUSTER_COUNT;
+    uint count = clustered ? b_cluster_counts.counts[cluster] : u_lighting.light_count;
+    for (uint j = 0; j < count; j++) {
+        uint i = clustered ? b_cluster_lights.indices[cluster * MAX_CLUSTER_LIGHTS + j] : j;
+        Light source = b_lights.lights[i];
+        vec3 direction = normalize(source.direction);
+        vec3 l = -direction;
+        float attenuation = 1.0;
+        if (source.kind != LIGHT_DIRECTIONAL) {
+            vec3 to_light = source.position.xyz - v_position;
+            float distance = length(to_light);
+            l = to_light / distance;
+            attenuation = range_attenuation(distance, source.position.w);
+        }
+        if (source.kind == LIGHT_SPOT) {
+            attenuation *= smoothstep(source.cos_outer, source.cos_inner, dot(-l, direction));
+        }
+        if (attenuation > 0.0) {
+            color += direct_light(normal, view, l, albedo, metallic, roughness, f0) * source.color * attenuation;
+        }
+    }
+
+    // ambient light (split sum)
+    if (u_lighting.ibl != 0) {
+        vec3 f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+        vec3 irradiance = texture(samplerCube(t_irradiance, s_ibl), normal).rgb;
+        vec3 ambient_diffuse = (1.0 - f) * (1.0 - metallic) * albedo * irradiance;
+
+        vec3 reflected = reflect(-view, normal);
+        float lod = roughness * (u_lighting.prefiltered_mips - 1.0);
+        vec3 prefiltered = textureLod(samplerCube(t_prefiltered, s_ibl), reflected, lod).rgb;
+        if (u_lighting.reflection_probe != 0u) {
+            float weight = probe_weight();
+            if (weight > 0.0) {
+                prefiltered = mix(prefiltered, probe_reflection(reflected, lod), weight);
+            }
+        }
+        vec2 brdf = texture(sampler2D(t_brdf, s_ibl), vec2(n_dot_v, roughness)).rg;
+        vec3 ambient_specular = prefiltered * (f * brdf.x + brdf.y);
+
+        color += (ambient_diffuse + ambient_specular) * occlusion;
+    } else {
+        color += albedo * 0.03 * occlusion;
+    }
+    color += emissive;
+
+    if (u_lighting.cascade_debug != 0 && cascade < CASCADES) {
+        color = mix(color, CASCADE_COLORS[cascade], 0.3);
+    }
+
+    // left in HDR, bloom and tone mapping come after the scene is drawn
+    frag_color = vec4(color, u_object.alpha_mode == ALPHA_BLEND ? base_color.a : 1.0);
+}
+
+#if OIT
+// premultiplied color and alpha, weighted to favour fragments close to the
+// camera (McGuire and Bavoil), and the alpha to multiply the revealage by
+void main() {
+    shade();
+    float alpha = frag_color.a;
+    float depth = dot(v_position - u_lighting.camera_position, u_lighting.camera_forward);
+    float weight = alpha * clamp(10.0 / (1e-5 + pow(depth / 5.0, 2.0) + pow(depth / 200.0, 6.0)), 1e-2, 3e3);
+    frag_accumulation = vec4(frag_color.rgb * alpha, alpha) * weight;
+    frag_revealage = alpha;
+}
+#endif
@@ -0,0 +1,25 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform ShadowInspector {
+    uint layers;
+    // depths shown as black and white
+    float min_depth;
+    float max_depth;
+} u_inspector;
+layout(set = 0, binding = 1) uniform texture2DArray t_source;
+layout(set = 0, binding = 2) uniform sampler s_source;
+
+void main() {
+    // layers side by side, left to right
+    float layers = float(u_inspector.layers);
+    float layer = min(floor(v_uv.x * layers), layers - 1.0);
+    vec2 uv = vec2(v_uv.x * layers - layer, v_uv.y);
+    float depth = texture(sampler2DArray(t_source, s_source), vec3(uv, layer)).r;
+    float range = max(u_inspector.max_depth - u_inspector.min_depth, 0.00001);
+    float value = clamp((depth - u_inspector.min_depth) / range, 0.0, 1.0);
+    frag_color = vec4(vec3(value), 1.0);
+}
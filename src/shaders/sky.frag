@@ -0,0 +1,61 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Sky {
+    // without the translation of the view, so the sky is infinitely far
+    mat4 inverse_view_projection;
+    vec3 sun_direction;
+    // cosine of the angular radius of the disk
+    float sun_size;
+    // radiance of the disk
+    vec3 sun_color;
+    // of the whole sky, faded out at night
+    float brightness;
+    // coefficients A to E of the Perez function, of Y, x and y in xyz
+    vec4 perez[5];
+    // Y, x and y of the zenith, and of the Perez function towards it
+    vec4 zenith;
+    vec4 zenith_perez;
+    // sky left at night
+    vec3 night_color;
+} u_sky;
+
+// Perez distribution of a direction `theta` from the zenith and `gamma` from the sun
+vec3 perez(float cos_theta, float gamma, float cos_gamma) {
+    vec3 a = u_sky.perez[0].xyz;
+    vec3 b = u_sky.perez[1].xyz;
+    vec3 c = u_sky.perez[2].xyz;
+    vec3 d = u_sky.perez[3].xyz;
+    vec3 e = u_sky.perez[4].xyz;
+    return (1.0 + a * exp(b / cos_theta)) * (1.0 + c * exp(d * gamma) + e * cos_gamma * cos_gamma);
+}
+
+vec3 xyy_to_linear_srgb(vec3 xyy) {
+    float y = xyy.x;
+    vec3 xyz = vec3(xyy.y / xyy.z * y, y, (1.0 - xyy.y - xyy.z) / xyy.z * y);
+    return mat3(
+        3.2406, -0.9689, 0.0557,
+        -1.5372, 1.8758, -0.2040,
+        -0.4986, 0.0415, 1.0570
+    ) * xyz;
+}
+
+void main() {
+    vec2 ndc = vec2(v_uv.x, 1.0 - v_uv.y) * 2.0 - 1.0;
+    vec4 far = u_sky.inverse_view_projection * vec4(ndc, 1.0, 1.0);
+    vec3 dir = normalize(far.xyz / far.w);
+
+    // below the horizon is the horizon, the model has no ground
+    float cos_theta = max(dir.y, 0.01);
+    float cos_gamma = clamp(dot(dir, u_sky.sun_direction), -1.0, 1.0);
+    vec3 xyy = u_sky.zenith.xyz * perez(cos_theta, acos(cos_gamma), cos_gamma) / u_sky.zenith_perez.xyz;
+    vec3 color = max(xyy_to_linear_srgb(xyy), 0.0) * u_sky.brightness;
+
+    if (cos_gamma > u_sky.sun_size && dir.y > 0.0) {
+        color += u_sky.sun_color;
+    }
+    frag_color = vec4(color + u_sky.night_color, 1.0);
+}
@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Skybox {
+    // without the translation of the view, so the sky is infinitely far
+    mat4 inverse_view_projection;
+} u_skybox;
+layout(set = 0, binding = 1) uniform textureCube t_environment;
+layout(set = 0, binding = 2) uniform sampler s_environment;
+
+void main() {
+    vec2 ndc = vec2(v_uv.x, 1.0 - v_uv.y) * 2.0 - 1.0;
+    vec4 far = u_skybox.inverse_view_projection * vec4(ndc, 1.0, 1.0);
+    vec3 dir = normalize(far.xyz / far.w);
+    vec3 color = textureLod(samplerCube(t_environment, s_environment), dir, 0.0).rgb;
+
+    frag_color = vec4(color, 1.0);
+}
\ No newline at end of file
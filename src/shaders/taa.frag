@@ -0,0 +1,67 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Taa {
+    mat4 inverse_view_projection;
+    mat4 previous_view_projection;
+    // weight of the history, the rest is the current frame
+    float feedback;
+    // zero on the first frame, or after the history went stale
+    uint history;
+} u_taa;
+layout(set = 0, binding = 1) uniform texture2D t_current;
+layout(set = 0, binding = 2) uniform sampler s_source;
+layout(set = 0, binding = 3) uniform texture2D t_depth;
+layout(set = 0, binding = 4) uniform texture2D t_history;
+
+// weighted by the inverse of their brightness, so single bright pixels
+// don't flicker as the jitter moves them in and out of geometry
+vec3 weighted(vec3 color) {
+    return color / (1.0 + dot(color, vec3(0.2126, 0.7152, 0.0722)));
+}
+
+vec3 unweighted(vec3 color) {
+    return color / (1.0 - dot(color, vec3(0.2126, 0.7152, 0.0722)));
+}
+
+void main() {
+    ivec2 coord = ivec2(gl_FragCoord.xy);
+    ivec2 size = textureSize(sampler2D(t_current, s_source), 0);
+    vec3 current = weighted(texelFetch(sampler2D(t_current, s_source), coord, 0).rgb);
+    if (u_taa.history == 0u) {
+        frag_color = vec4(unweighted(current), 1.0);
+        return;
+    }
+
+    // the history is clamped to the colors around the pixel, what falls out
+    // of them was not there on the previous frame
+    vec3 low = current;
+    vec3 high = current;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            ivec2 neighbor = clamp(coord + ivec2(x, y), ivec2(0), size - 1);
+            vec3 color = weighted(texelFetch(sampler2D(t_current, s_source), neighbor, 0).rgb);
+            low = min(low, color);
+            high = max(high, color);
+        }
+    }
+
+    // where the pixel was on the previous frame, with the scene standing still
+    float depth = texelFetch(sampler2D(t_depth, s_source), coord, 0).r;
+    vec2 ndc = vec2(v_uv.x, 1.0 - v_uv.y) * 2.0 - 1.0;
+    vec4 world = u_taa.inverse_view_projection * vec4(ndc, depth, 1.0);
+    vec4 previous = u_taa.previous_view_projection * vec4(world.xyz / world.w, 1.0);
+    vec2 previous_ndc = previous.xy / previous.w;
+    vec2 previous_uv = vec2(previous_ndc.x, -previous_ndc.y) * 0.5 + 0.5;
+    if (any(lessThan(previous_uv, vec2(0.0))) || any(greaterThan(previous_uv, vec2(1.0)))) {
+        frag_color = vec4(unweighted(current), 1.0);
+        return;
+    }
+
+    vec3 history = weighted(texture(sampler2D(t_history, s_source), previous_uv).rgb);
+    history = clamp(history, low, high);
+    frag_color = vec4(unweighted(mix(current, history, u_taa.feedback)), 1.0);
+}
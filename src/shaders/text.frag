@@ -0,0 +1,37 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Text {
+    mat4 view_projection;
+    vec4 color;
+    vec4 outline_color;
+    vec2 shadow_offset;
+    float outline_width;
+    float shadow_alpha;
+} u_text;
+layout(set = 0, binding = 1) uniform texture2D t_atlas;
+layout(set = 0, binding = 2) uniform sampler s_atlas;
+
+// distance fields store 0.5 on the edge of the glyphs, more inside
+float coverage(float distance, float edge) {
+    float width = fwidth(distance) * 0.7;
+    return smoothstep(edge - width, edge + width, distance);
+}
+
+void main() {
+    float distance = texture(sampler2D(t_atlas, s_atlas), v_uv).r;
+    float fill = coverage(distance, 0.5);
+    float outline = coverage(distance, 0.5 - u_text.outline_width);
+    vec4 text = mix(u_text.outline_color, u_text.color, fill) * vec4(1.0, 1.0, 1.0, outline);
+
+    float shadow_distance = texture(sampler2D(t_atlas, s_atlas), v_uv - u_text.shadow_offset).r;
+    float shadow = smoothstep(0.3, 0.5, shadow_distance) * u_text.shadow_alpha;
+
+    // text over shadow, premultiplied
+    vec3 color = text.rgb * text.a;
+    float alpha = text.a + shadow * (1.0 - text.a);
+    frag_color = vec4(color, alpha);
+}
\ No newline at end of file
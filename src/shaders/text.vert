@@ -0,0 +1,20 @@
+#version 450
+
+layout(location = 0) in vec3 a_position;
+layout(location = 1) in vec2 a_uv;
+
+layout(location = 0) out vec2 v_uv;
+
+layout(set = 0, binding = 0) uniform Text {
+    mat4 view_projection;
+    vec4 color;
+    vec4 outline_color;
+    vec2 shadow_offset;
+    float outline_width;
+    float shadow_alpha;
+} u_text;
+
+void main() {
+    gl_Position = u_text.view_projection * vec4(a_position, 1.0);
+    v_uv = a_uv;
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Preview {
+    // region of the texture shown, zoomed and panned
+    vec2 offset;
+    vec2 scale;
+    // 1 for every channel shown, 0 for the others
+    vec4 channels;
+    // set when a single channel is shown, as grey
+    uint grayscale;
+} u_preview;
+layout(set = 0, binding = 1) uniform texture2D t_source;
+layout(set = 0, binding = 2) uniform sampler s_source;
+
+void main() {
+    vec2 uv = u_preview.offset + v_uv * u_preview.scale;
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+        frag_color = vec4(0.05, 0.05, 0.05, 1.0);
+        return;
+    }
+    vec4 texel = texture(sampler2D(t_source, s_source), uv) * u_preview.channels;
+    if (u_preview.grayscale != 0u) {
+        float value = dot(texel, vec4(1.0));
+        frag_color = vec4(vec3(value), 1.0);
+    } else {
+        frag_color = vec4(texel.rgb, 1.0);
+    }
+}
@@ -0,0 +1,50 @@
+#version 450
+
+#define CASCADES 4u
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+layout(set = 0, binding = 0) uniform Volumetric {
+    mat4 inverse_view_projection;
+    mat4 cascade_view_projections[CASCADES];
+    // view depth where every cascade ends
+    vec4 cascade_splits;
+    vec3 eye;
+    float density;
+    vec3 forward;
+    float anisotropy;
+    vec3 light_direction;
+    // view depth of the first slice, and of the last one
+    float near;
+    // multiplied by the intensity
+    vec3 light_color;
+    float distance;
+} u_volumetric;
+layout(set = 0, binding = 1) uniform texture2D t_scene;
+layout(set = 0, binding = 2) uniform sampler s_source;
+layout(set = 0, binding = 3) uniform texture2D t_depth;
+layout(set = 0, binding = 4) uniform texture3D t_accumulated;
+
+void main() {
+    ivec2 coord = ivec2(gl_FragCoord.xy);
+    vec3 color = texelFetch(sampler2D(t_scene, s_source), coord, 0).rgb;
+    float depth = texelFetch(sampler2D(t_depth, s_source), coord, 0).r;
+
+    // the sky is behind the whole volume, which is where the rays show best
+    float view_depth = u_volumetric.distance;
+    if (depth < 1.0) {
+        vec2 ndc = vec2(v_uv.x, 1.0 - v_uv.y) * 2.0 - 1.0;
+        vec4 world = u_volumetric.inverse_view_projection * vec4(ndc, depth, 1.0);
+        view_depth = dot(world.xyz / world.w - u_volumetric.eye, u_volumetric.forward);
+    }
+
+    // every slice holds what's up to its far side, half a slice past its center
+    float slices = float(textureSize(sampler3D(t_accumulated, s_source), 0).z);
+    float ratio = log(max(view_depth, u_volumetric.near) / u_volumetric.near);
+    float slice = ratio / log(u_volumetric.distance / u_volumetric.near);
+    vec3 uvw = vec3(v_uv, clamp(slice, 0.0, 1.0) - 0.5 / slices);
+    vec4 volume = textureLod(sampler3D(t_accumulated, s_source), uvw, 0.0);
+    frag_color = vec4(color * volume.a + volume.rgb, 1.0);
+}
@@ -0,0 +1,162 @@
+//! Cascaded shadow maps.
+//!
+//! The view frustum is split along its depth and each slice gets its own
+//! directional light projection, all of them rendered to the layers of a
+//! single depth texture array.
+use glam::{Mat4, Vec3, Vec4};
+use imgui::{im_str, Slider, Ui};
+
+/// Number of cascades. Must match the arrays in `shader.frag`.
+pub const CASCADES: usize = 4;
+
+/// Resolution of every cascade.
+pub const SHADOW_MAP_SIZE: u32 = 1024;
+
+/// Distance from the camera covered by the cascades.
+const SHADOW_DISTANCE: f32 = 60.0;
+
+/// Blend between uniform (0) and logarithmic (1) split distances.
+const SPLIT_LAMBDA: f32 = 0.75;
+
+/// Extra depth behind every cascade so objects outside of the slice can still
+/// cast shadows into it.
+const CASTER_DISTANCE: f32 = 20.0;
+
+/// How the lighting pass samples the cascades, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ShadowParams {
+    /// Subtracted from the depth of fragments before comparing it, on top of
+    /// the slope scaled bias of the shadow pass.
+    pub bias: f32,
+    /// Offset of fragments along their normal in the first cascade, in world
+    /// units. Grows with the size of the cascades.
+    pub normal_offset: f32,
+    /// The PCF kernel is `2 * pcf_radius + 1` texels wide.
+    pub pcf_radius: u32,
+}
+
+impl Default for ShadowParams {
+    fn default() -> Self {
+        Self {
+            bias: 0.0,
+            normal_offset: 0.02,
+            pcf_radius: 1,
+        }
+    }
+}
+
+impl ShadowParams {
+    pub fn ui(&mut self, ui: &Ui) {
+        Slider::new(im_str!("Bias"))
+            .range(0.0..=0.01)
+            .display_format(im_str!("%.5f"))
+            .build(ui, &mut self.bias);
+        Slider::new(im_str!("Normal offset"))
+            .range(0.0..=0.2)
+            .display_format(im_str!("%.3f"))
+            .build(ui, &mut self.normal_offset);
+        let width = 2 * self.pcf_radius + 1;
+        Slider::new(im_str!("PCF radius"))
+            .range(0..=3)
+            .display_format(&im_str!("%d ({0}x{0} texels)", width))
+            .build(ui, &mut self.pcf_radius);
+    }
+}
+
+/// Camera frustum parameters.
+pub struct Frustum {
+    pub view: Mat4,
+    pub fov_y: f32,
+    pub aspect: f32,
+    pub near: f32,
+}
+
+#[derive(Clone, Copy)]
+pub struct Cascades {
+    /// Light view-projection of every cascade.
+    pub view_projections: [Mat4; CASCADES],
+    /// View space depth where every cascade ends.
+    pub splits: [f32; CASCADES],
+}
+
+impl Cascades {
+    /// Fits one orthographic light projection to each slice of the frustum.
+    pub fn fit(frustum: &Frustum, light_direction: Vec3) -> Self {
+        let splits = split_distances(frustum.near, SHADOW_DISTANCE);
+        let inverse_view = frustum.view.inverse();
+        let light_direction = light_direction.normalize();
+        let up = if light_direction.y.abs() > 0.99 {
+            Vec3::unit_z()
+        } else {
+            Vec3::unit_y()
+        };
+
+        let mut view_projections = [Mat4::identity(); CASCADES];
+        let mut near = frustum.near;
+        for (view_projection, &far) in view_projections.iter_mut().zip(&splits) {
+            // bounding sphere of the slice, so the projection doesn't change
+            // size when the camera rotates
+            let corners = slice_corners(frustum, near, far);
+            let center = corners.iter().fold(Vec3::zero(), |acc, &c| {
+                acc + inverse_view.transform_point3(c)
+            }) / corners.len() as f32;
+            let radius = corners
+                .iter()
+                .map(|&c| (inverse_view.transform_point3(c) - center).length())
+                .fold(0.0, f32::max)
+                .ceil();
+
+            let eye = center - light_direction * (radius + CASTER_DISTANCE);
+            let light_view = Mat4::look_at_rh(eye, center, up);
+            let light_projection = Mat4::orthographic_rh(
+                -radius,
+                radius,
+                -radius,
+                radius,
+                0.0,
+                2.0 * radius + CASTER_DISTANCE,
+            );
+            *view_projection = snap_to_texels(light_projection * light_view);
+            near = far;
+        }
+
+        Self {
+            view_projections,
+            splits,
+        }
+    }
+}
+
+fn split_distances(near: f32, far: f32) -> [f32; CASCADES] {
+    let mut splits = [0.0; CASCADES];
+    for (i, split) in splits.iter_mut().enumerate() {
+        let p = (i + 1) as f32 / CASCADES as f32;
+        let log = near * (far / near).powf(p);
+        let uniform = near + (far - near) * p;
+        *split = SPLIT_LAMBDA * log + (1.0 - SPLIT_LAMBDA) * uniform;
+    }
+    splits
+}
+
+/// View space corners of the frustum between two depths.
+fn slice_corners(frustum: &Frustum, near: f32, far: f32) -> [Vec3; 8] {
+    let tan = (frustum.fov_y * 0.5).tan();
+    let mut corners = [Vec3::zero(); 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let z = if i < 4 { near } else { far };
+        let x = if i & 1 == 0 { -1.0 } else { 1.0 };
+        let y = if i & 2 == 0 { -1.0 } else { 1.0 };
+        *corner = Vec3::new(x * z * tan * frustum.aspect, y * z * tan, -z);
+    }
+    corners
+}
+
+/// Moves the projection by whole texels to prevent shadow edges from
+/// shimmering as the camera moves.
+fn snap_to_texels(view_projection: Mat4) -> Mat4 {
+    let half_size = SHADOW_MAP_SIZE as f32 * 0.5;
+    let origin = view_projection * Vec4::new(0.0, 0.0, 0.0, 1.0);
+    let texel = Vec3::new(origin.x * half_size, origin.y * half_size, 0.0);
+    let rounded = Vec3::new(texel.x.round(), texel.y.round(), 0.0);
+    Mat4::from_translation((rounded - texel) / half_size) * view_projection
+}
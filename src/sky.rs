@@ -0,0 +1,344 @@
+//! Procedural sky, from the analytic model of Preetham et al.
+//!
+//! The clear sky is the Perez distribution of luminance and chromaticity
+//! around the sun, fitted to the haze of the air (its turbidity). The sun
+//! follows the time of day, and the sunlight is dimmed and reddened by the
+//! air it goes through, so the directional light can be the sun itself.
+use crate::{
+    draw_stats::{self, CountedPass},
+    layout::Layout,
+    memory::{self, Category, Tracked},
+    msaa, shaders,
+};
+use glam::{Mat3, Mat4, Vec3, Vec4};
+use imgui::{im_str, Slider, Ui};
+use std::f32::consts::PI;
+use wgpu::{
+    util::make_spirv, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor, Buffer, BufferDescriptor,
+    BufferSize, BufferUsage, ColorStateDescriptor, ColorWrite, CompareFunction,
+    DepthStencilStateDescriptor, Device, IndexFormat, PipelineLayoutDescriptor, PrimitiveTopology,
+    ProgrammableStageDescriptor, Queue, RenderPipeline, RenderPipelineDescriptor, ShaderStage,
+    StencilStateDescriptor, TextureFormat, VertexStateDescriptor,
+};
+
+/// Luminance of the model is in kcd/m², scaled to be about as bright as the
+/// environment maps.
+const SKY_SCALE: f32 = 0.1;
+
+/// Radiance of the sun disk, relative to the unit light of the sun.
+const SUN_RADIANCE: f32 = 20.0;
+
+/// Angular radius of the sun disk, in degrees, larger than the real one.
+const SUN_RADIUS: f32 = 0.6;
+
+/// Tilt of the path of the sun from the vertical, so it isn't overhead at noon.
+const SUN_TILT: f32 = 30.0;
+
+/// What's left of the sky at night.
+const NIGHT_COLOR: [f32; 3] = [0.002, 0.003, 0.008];
+
+gpu_struct! {
+    struct SkyUniforms {
+        inverse_view_projection: Mat4,
+        sun_direction: Vec3,
+        sun_size: f32,
+        sun_color: Vec3,
+        brightness: f32,
+        perez: [Vec4; 5],
+        zenith: Vec4,
+        zenith_perez: Vec4,
+        night_color: Vec3,
+    }
+}
+
+/// Sky and sun, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SkyParams {
+    /// Draws this sky instead of the environment map.
+    pub procedural: bool,
+    /// Hours since midnight, the sun rising at 6 and setting at 18.
+    pub time_of_day: f32,
+    /// Degrees around the vertical the sun rises at, from +X.
+    pub azimuth: f32,
+    /// Haze of the air, from 2 for a clear day to 10 for a hazy one.
+    pub turbidity: f32,
+    /// Makes the sun the directional light, see `sun_direction` and
+    /// `sunlight`.
+    pub drive_light: bool,
+}
+
+impl Default for SkyParams {
+    fn default() -> Self {
+        Self {
+            procedural: false,
+            time_of_day: 15.0,
+            azimuth: 30.0,
+            turbidity: 2.5,
+            drive_light: true,
+        }
+    }
+}
+
+impl SkyParams {
+    /// Direction towards the sun, below the horizon at night.
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = (self.time_of_day - 6.0) / 12.0 * PI;
+        let tilt = SUN_TILT.to_radians();
+        let path = Vec3::new(
+            angle.cos(),
+            angle.sin() * tilt.cos(),
+            angle.sin() * tilt.sin(),
+        );
+        Mat3::from_rotation_y(self.azimuth.to_radians()) * path
+    }
+
+    /// Color of the sunlight through the air, relative to the sun at the
+    /// zenith, and fading out as it sets.
+    pub fn sunlight(&self) -> Vec3 {
+        let elevation = self.sun_direction().y;
+        let zenith_angle = elevation.max(0.0).acos();
+        // optical air mass (Kasten and Young), one at the zenith
+        let air_mass = 1.0
+            / (zenith_angle.cos() + 0.50572 * (96.07995 - zenith_angle.to_degrees()).powf(-1.6364));
+        // optical depths of the air at 680, 550 and 440 nm, Rayleigh and
+        // aerosols (Ångström, with the wavelength exponent of 1.3)
+        let rayleigh = Vec3::new(0.042, 0.097, 0.236);
+        let aerosol = (0.04608 * self.turbidity - 0.04586) * Vec3::new(1.65, 2.18, 2.91);
+        let depth = (rayleigh + aerosol) * (air_mass - 1.0);
+        let transmittance = Vec3::new((-depth.x).exp(), (-depth.y).exp(), (-depth.z).exp());
+        transmittance * smoothstep(-0.05, 0.05, elevation)
+    }
+
+    /// Controls of the sun, when the sky is `procedural`.
+    pub fn ui(&mut self, ui: &Ui) {
+        ui.checkbox(im_str!("Procedural sky"), &mut self.procedural);
+        if self.procedural {
+            Slider::new(im_str!("Time of day"))
+                .range(0.0..=24.0)
+                .display_format(im_str!("%.1f h"))
+                .build(ui, &mut self.time_of_day);
+            Slider::new(im_str!("Sun azimuth"))
+                .range(-180.0..=180.0)
+                .display_format(im_str!("%.0f deg"))
+                .build(ui, &mut self.azimuth);
+            Slider::new(im_str!("Turbidity"))
+                .range(1.7..=10.0)
+                .build(ui, &mut self.turbidity);
+            ui.checkbox(im_str!("Sun is the light"), &mut self.drive_light);
+        }
+    }
+}
+
+pub struct Sky {
+    uniform: Tracked<Buffer>,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+    /// For the passes of `msaa`.
+    multisampled_pipeline: RenderPipeline,
+}
+
+impl Sky {
+    /// The pipeline is compatible with passes that have a depth attachment of
+    /// `depth_format`, but it doesn't test against it.
+    pub fn new(device: &Device, color_format: TextureFormat, depth_format: TextureFormat) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::SKY_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Sky uniforms"),
+                size: SkyUniforms::STD140.size as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Sky bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::FRAGMENT,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: BufferSize::new(SkyUniforms::STD140.size as _),
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Sky bind group"),
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(uniform.slice(..)),
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Sky pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |label, sample_count| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex_stage: ProgrammableStageDescriptor {
+                    module: &vert_module,
+                    entry_point: "main",
+                },
+                fragment_stage: Some(ProgrammableStageDescriptor {
+                    module: &frag_module,
+                    entry_point: "main",
+                }),
+                rasterization_state: None,
+                primitive_topology: PrimitiveTopology::TriangleList,
+                color_states: &[ColorStateDescriptor {
+                    format: color_format,
+                    alpha_blend: BlendDescriptor::REPLACE,
+                    color_blend: BlendDescriptor::REPLACE,
+                    write_mask: ColorWrite::ALL,
+                }],
+                // drawn first, everything else covers it
+                depth_stencil_state: Some(DepthStencilStateDescriptor {
+                    format: depth_format,
+                    depth_write_enabled: false,
+                    depth_compare: CompareFunction::Always,
+                    stencil: StencilStateDescriptor::default(),
+                }),
+                vertex_state: VertexStateDescriptor {
+                    index_format: IndexFormat::Uint16,
+                    vertex_buffers: &[],
+                },
+                sample_count,
+                sample_mask: !0,
+                alpha_to_coverage_enabled: false,
+            })
+        };
+        let pipeline = create_pipeline("Sky pipeline", 1);
+        let multisampled_pipeline =
+            create_pipeline("Sky pipeline (multisampled)", msaa::SAMPLE_COUNT);
+
+        Self {
+            uniform,
+            bind_group,
+            pipeline,
+            multisampled_pipeline,
+        }
+    }
+
+    pub fn update(&self, queue: &Queue, projection: Mat4, view: Mat4, params: &SkyParams) {
+        let mut rotation = view;
+        rotation.w_axis = Vec4::unit_w();
+        let sun = params.sun_direction();
+        // the model breaks down with the sun under the horizon, the sky just
+        // fades out instead
+        let sun_zenith_angle = sun.y.max(0.0).acos().min(1.55);
+        let coefficients = perez_coefficients(params.turbidity);
+        let zenith = zenith(params.turbidity, sun_zenith_angle);
+        let mut perez = [Vec4::zero(); 5];
+        for (i, coefficient) in perez.iter_mut().enumerate() {
+            *coefficient = Vec4::new(
+                coefficients[0][i],
+                coefficients[1][i],
+                coefficients[2][i],
+                0.0,
+            );
+        }
+        let zenith_perez =
+            |channel: usize| perez_distribution(&coefficients[channel], 1.0, sun_zenith_angle);
+        let uniforms = SkyUniforms {
+            inverse_view_projection: (projection * rotation).inverse(),
+            sun_direction: sun,
+            sun_size: SUN_RADIUS.to_radians().cos(),
+            sun_color: params.sunlight() * SUN_RADIANCE,
+            brightness: SKY_SCALE * smoothstep(-0.2, 0.05, sun.y),
+            perez,
+            zenith: zenith.extend(0.0),
+            zenith_perez: Vec4::new(zenith_perez(0), zenith_perez(1), zenith_perez(2), 0.0),
+            night_color: NIGHT_COLOR.into(),
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, &uniforms.std140_bytes());
+    }
+
+    /// Draws the sky over the whole color target of the pass.
+    pub fn draw<'a>(&'a self, pass: &mut CountedPass<'a>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    /// `draw`, into the multisampled targets of `msaa`.
+    pub fn draw_multisampled<'a>(&'a self, pass: &mut CountedPass<'a>) {
+        pass.set_pipeline(&self.multisampled_pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// Coefficients A to E of the Perez function for the luminance Y and the
+/// chromaticities x and y, at `turbidity`.
+fn perez_coefficients(turbidity: f32) -> [[f32; 5]; 3] {
+    let t = turbidity;
+    [
+        [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ],
+        [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ],
+        [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ],
+    ]
+}
+
+/// Perez function in a direction `theta` away from the zenith, with
+/// `cos_theta` given, and `gamma` away from the sun.
+fn perez_distribution(coefficients: &[f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = *coefficients;
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+/// Luminance Y and chromaticities x and y of the zenith, with the sun
+/// `sun_zenith_angle` radians away from it.
+fn zenith(turbidity: f32, sun_zenith_angle: f32) -> Vec3 {
+    let t = turbidity;
+    let theta = sun_zenith_angle;
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta);
+    let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+    // cubic polynomials in the angle of the sun, of turbidity squared, of
+    // turbidity and constant
+    let cubic = |[a, b, c, d]: [f32; 4]| ((a * theta + b) * theta + c) * theta + d;
+    let chromaticity = |coefficients: [[f32; 4]; 3]| {
+        t * t * cubic(coefficients[0]) + t * cubic(coefficients[1]) + cubic(coefficients[2])
+    };
+    let x = chromaticity([
+        [0.00166, -0.00375, 0.00209, 0.0],
+        [-0.02903, 0.06377, -0.03202, 0.00394],
+        [0.11693, -0.21196, 0.06052, 0.25886],
+    ]);
+    let y = chromaticity([
+        [0.00275, -0.00610, 0.00317, 0.0],
+        [-0.04214, 0.08970, -0.04153, 0.00516],
+        [0.15346, -0.26756, 0.06670, 0.26688],
+    ]);
+    Vec3::new(luminance, x, y)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
@@ -0,0 +1,180 @@
+//! Environment cubemap drawn behind the scene.
+use crate::{
+    draw_stats::{self, CountedPass},
+    ibl::Ibl,
+    memory::{self, Category, Tracked},
+    msaa, shaders,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec4};
+use wgpu::{
+    util::make_spirv, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor, Buffer, BufferDescriptor,
+    BufferSize, BufferUsage, ColorStateDescriptor, ColorWrite, CompareFunction,
+    DepthStencilStateDescriptor, Device, IndexFormat, PipelineLayoutDescriptor, PrimitiveTopology,
+    ProgrammableStageDescriptor, Queue, RenderPipeline, RenderPipelineDescriptor, ShaderStage,
+    StencilStateDescriptor, TextureComponentType, TextureFormat, TextureViewDimension,
+    VertexStateDescriptor,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct SkyboxUniforms {
+    inverse_view_projection: [[f32; 4]; 4],
+}
+
+pub struct Skybox {
+    uniform: Tracked<Buffer>,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+    /// For the passes of `msaa`.
+    multisampled_pipeline: RenderPipeline,
+}
+
+impl Skybox {
+    /// The pipeline is compatible with passes that have a depth attachment of
+    /// `depth_format`, but it doesn't test against it.
+    pub fn new(
+        device: &Device,
+        ibl: &Ibl,
+        color_format: TextureFormat,
+        depth_format: TextureFormat,
+    ) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::SKYBOX_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Skybox uniforms"),
+                size: std::mem::size_of::<SkyboxUniforms>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Skybox bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(
+                            std::mem::size_of::<SkyboxUniforms>() as _
+                        ),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::SampledTexture {
+                        dimension: TextureViewDimension::Cube,
+                        component_type: TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Skybox bind group"),
+            layout: &layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(uniform.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&ibl.environment_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&ibl.sampler),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Skybox pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |label, sample_count| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex_stage: ProgrammableStageDescriptor {
+                    module: &vert_module,
+                    entry_point: "main",
+                },
+                fragment_stage: Some(ProgrammableStageDescriptor {
+                    module: &frag_module,
+                    entry_point: "main",
+                }),
+                rasterization_state: None,
+                primitive_topology: PrimitiveTopology::TriangleList,
+                color_states: &[ColorStateDescriptor {
+                    format: color_format,
+                    alpha_blend: BlendDescriptor::REPLACE,
+                    color_blend: BlendDescriptor::REPLACE,
+                    write_mask: ColorWrite::ALL,
+                }],
+                // drawn first, everything else covers it
+                depth_stencil_state: Some(DepthStencilStateDescriptor {
+                    format: depth_format,
+                    depth_write_enabled: false,
+                    depth_compare: CompareFunction::Always,
+                    stencil: StencilStateDescriptor::default(),
+                }),
+                vertex_state: VertexStateDescriptor {
+                    index_format: IndexFormat::Uint16,
+                    vertex_buffers: &[],
+                },
+                sample_count,
+                sample_mask: !0,
+                alpha_to_coverage_enabled: false,
+            })
+        };
+        let pipeline = create_pipeline("Skybox pipeline", 1);
+        let multisampled_pipeline =
+            create_pipeline("Skybox pipeline (multisampled)", msaa::SAMPLE_COUNT);
+
+        Self {
+            uniform,
+            bind_group,
+            pipeline,
+            multisampled_pipeline,
+        }
+    }
+
+    pub fn update(&self, queue: &Queue, projection: Mat4, view: Mat4) {
+        let mut rotation = view;
+        rotation.w_axis = Vec4::unit_w();
+        let uniforms = SkyboxUniforms {
+            inverse_view_projection: (projection * rotation).inverse().to_cols_array_2d(),
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Draws the sky over the whole color target of the pass.
+    pub fn draw<'a>(&'a self, pass: &mut CountedPass<'a>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    /// `draw`, into the multisampled targets of `msaa`.
+    pub fn draw_multisampled<'a>(&'a self, pass: &mut CountedPass<'a>) {
+        pass.set_pipeline(&self.multisampled_pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
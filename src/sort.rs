@@ -0,0 +1,273 @@
+//! GPU bitonic sort.
+//!
+//! Sorts `u32` keys in storage buffers along with a `u32` payload (usually the
+//! index of what the key belongs to). Every step of the sorting network is a
+//! dispatch, with its parameters at a dynamic offset of a uniform buffer.
+use crate::{
+    draw_stats,
+    memory::{self, Category, Tracked},
+    readback, shaders,
+};
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    util::{make_spirv, BufferInitDescriptor},
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferDescriptor, BufferSize,
+    BufferUsage, CommandEncoder, CommandEncoderDescriptor, ComputePipeline,
+    ComputePipelineDescriptor, Device, PipelineLayoutDescriptor, ProgrammableStageDescriptor,
+    Queue, ShaderStage, BIND_BUFFER_ALIGNMENT,
+};
+
+/// Must match the `local_size_x` of `sort.comp`.
+const WORKGROUP_SIZE: u32 = 256;
+
+/// Largest number of elements that can be sorted.
+pub const MAX_LEN: u32 = 1 << 22;
+
+/// Steps of the network for `MAX_LEN` elements, `log2 * (log2 + 1) / 2`.
+const MAX_STEPS: u64 = 22 * 23 / 2;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Step {
+    block: u32,
+    distance: u32,
+    len: u32,
+    _pad: u32,
+}
+
+pub struct BitonicSort {
+    /// Parameters of every step, `BIND_BUFFER_ALIGNMENT` bytes apart.
+    steps: Tracked<Buffer>,
+    layout: BindGroupLayout,
+    pipeline: ComputePipeline,
+}
+
+impl BitonicSort {
+    pub fn new(device: &Device) -> Self {
+        let module = device.create_shader_module(make_spirv(shaders::SORT_COMP));
+        let steps = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Sort steps"),
+                size: MAX_STEPS * BIND_BUFFER_ALIGNMENT,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let storage = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStage::COMPUTE,
+            ty: BindingType::StorageBuffer {
+                dynamic: false,
+                min_binding_size: None,
+                readonly: false,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Sort bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: true,
+                        min_binding_size: BufferSize::new(std::mem::size_of::<Step>() as _),
+                    },
+                    count: None,
+                },
+                storage(1),
+                storage(2),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Sort pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Sort pipeline"),
+            layout: Some(&pipeline_layout),
+            compute_stage: ProgrammableStageDescriptor {
+                module: &module,
+                entry_point: "main",
+            },
+        });
+
+        Self {
+            steps,
+            layout,
+            pipeline,
+        }
+    }
+
+    /// Records the sort of the first `len` `keys` in ascending order, moving
+    /// `values` along with them. The steps are uploaded through `queue`, so
+    /// there can only be one sort per submission.
+    pub fn sort(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        keys: &Buffer,
+        values: &Buffer,
+        len: u32,
+    ) {
+        assert!(len <= MAX_LEN, "Sorting more than {} elements", MAX_LEN);
+        let steps = steps(len);
+        if steps.is_empty() {
+            return;
+        }
+        let mut data = vec![0; steps.len() * BIND_BUFFER_ALIGNMENT as usize];
+        for (step, chunk) in steps
+            .iter()
+            .zip(data.chunks_mut(BIND_BUFFER_ALIGNMENT as _))
+        {
+            chunk[..std::mem::size_of::<Step>()].copy_from_slice(bytemuck::bytes_of(step));
+        }
+        draw_stats::write_buffer(queue, &self.steps, 0, &data);
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Sort bind group"),
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(
+                        self.steps.slice(..std::mem::size_of::<Step>() as u64),
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Buffer(keys.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Buffer(values.slice(..)),
+                },
+            ],
+        });
+        // every comparison of a step is a thread
+        let workgroups = (len.next_power_of_two() / 2).div_ceil(WORKGROUP_SIZE);
+        for i in 0..steps.len() {
+            // a pass per step, so each one sees the writes of the previous one
+            let mut pass = encoder.begin_compute_pass();
+            pass.push_debug_group("Bitonic sort step");
+            pass.set_pipeline(&self.pipeline);
+            let offset = (i as u64 * BIND_BUFFER_ALIGNMENT) as u32;
+            pass.set_bind_group(0, &bind_group, &[offset]);
+            pass.dispatch(workgroups, 1, 1);
+            pass.pop_debug_group();
+        }
+    }
+
+    /// Sorts `len` pseudo-random keys and checks them, and where their values
+    /// ended up, against a CPU sort.
+    pub fn self_test(&self, device: &Device, queue: &Queue, len: u32) -> bool {
+        // xorshift, with plenty of repeated keys
+        let mut state = 0x1234_5678u32;
+        let keys: Vec<u32> = (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state % 1000
+            })
+            .collect();
+        let values: Vec<u32> = (0..len).collect();
+        let buffer = |label, contents: &[u32]| {
+            memory::create_buffer_init(
+                device,
+                Category::Storage,
+                &BufferInitDescriptor {
+                    label: Some(label),
+                    contents: bytemuck::cast_slice(contents),
+                    usage: BufferUsage::STORAGE | BufferUsage::COPY_SRC,
+                },
+            )
+        };
+        let keys_buffer = buffer("Sort test keys", &keys);
+        let values_buffer = buffer("Sort test values", &values);
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Sort test"),
+        });
+        self.sort(
+            device,
+            queue,
+            &mut encoder,
+            &keys_buffer,
+            &values_buffer,
+            len,
+        );
+        queue.submit(Some(encoder.finish()));
+        let sorted: Vec<u32> = futures::executor::block_on(readback::read_buffer(
+            device,
+            queue,
+            &keys_buffer,
+            len as _,
+        ));
+        let moved: Vec<u32> = futures::executor::block_on(readback::read_buffer(
+            device,
+            queue,
+            &values_buffer,
+            len as _,
+        ));
+
+        let mut expected = keys.clone();
+        expected.sort_unstable();
+        // every value must still go with its key
+        let paired = sorted
+            .iter()
+            .zip(&moved)
+            .all(|(key, value)| keys.get(*value as usize) == Some(key));
+        let mut moved = moved;
+        moved.sort_unstable();
+        sorted == expected && paired && moved == values
+    }
+}
+
+/// Steps of the network sorting `len` elements, as if there were as many as the
+/// next power of two.
+fn steps(len: u32) -> Vec<Step> {
+    let mut steps = Vec::new();
+    let mut block = 2;
+    while block <= len.next_power_of_two() && len > 1 {
+        let mut distance = block / 2;
+        while distance > 0 {
+            steps.push(Step {
+                block,
+                distance,
+                len,
+                _pad: 0,
+            });
+            distance /= 2;
+        }
+        block *= 2;
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitonicSort;
+    use crate::adapter;
+
+    #[test]
+    fn sorts_like_the_cpu() {
+        let (device, queue) = match adapter::headless_device() {
+            Some(device) => device,
+            None => {
+                eprintln!("No GPU adapter, skipping");
+                return;
+            }
+        };
+
+        let sort = BitonicSort::new(&device);
+        for &len in &[1, 2, 3, 64, 1000, 4096, 100_000] {
+            assert!(sort.self_test(&device, &queue, len), "{} keys", len);
+        }
+    }
+}
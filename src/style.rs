@@ -0,0 +1,191 @@
+//! Fonts and colors of the imgui windows.
+use imgui::{
+    im_str, ColorEdit, Context, FontConfig, FontSource, ImString, Slider, Style, StyleColor, Ui,
+    Window,
+};
+use log::warn;
+
+/// Size of imgui's own font, in window coordinates.
+pub const DEFAULT_FONT_SIZE: f32 = 13.0;
+
+/// Color themes selectable from the UI.
+pub const THEMES: [Theme; 4] = [Theme::Dark, Theme::Light, Theme::Classic, Theme::Custom];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Theme {
+    Dark,
+    Light,
+    Classic,
+    /// Dark theme with the colors of [`UiStyle`].
+    Custom,
+}
+
+impl Theme {
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+            Theme::Classic => "Classic",
+            Theme::Custom => "Custom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        THEMES.iter().copied().find(|theme| theme.name() == name)
+    }
+}
+
+/// What the style window changed, to apply it.
+#[derive(Default)]
+pub struct StyleChanges {
+    pub colors: bool,
+    pub font: bool,
+}
+
+#[derive(Clone)]
+pub struct UiStyle {
+    pub theme: Theme,
+    /// Colors of the custom theme.
+    pub accent: [f32; 4],
+    pub background: [f32; 4],
+    pub text: [f32; 4],
+    /// TTF file of the font, imgui's own font if empty.
+    pub font_path: String,
+    /// Size of the font, in window coordinates.
+    pub font_size: f32,
+}
+
+impl Default for UiStyle {
+    fn default() -> Self {
+        Self {
+            theme: Theme::Dark,
+            accent: [0.26, 0.59, 0.98, 1.0],
+            background: [0.06, 0.06, 0.06, 0.94],
+            text: [1.0, 1.0, 1.0, 1.0],
+            font_path: String::new(),
+            font_size: DEFAULT_FONT_SIZE,
+        }
+    }
+}
+
+impl UiStyle {
+    /// Replaces the colors of `style` with the ones of the theme.
+    pub fn apply_colors(&self, style: &mut Style) {
+        match self.theme {
+            Theme::Dark => {
+                style.use_dark_colors();
+            }
+            Theme::Light => {
+                style.use_light_colors();
+            }
+            Theme::Classic => {
+                style.use_classic_colors();
+            }
+            Theme::Custom => {
+                style.use_dark_colors();
+                let [r, g, b, _] = self.accent;
+                let accent = |alpha| [r, g, b, alpha];
+                let colors = &mut style.colors;
+                colors[StyleColor::Text as usize] = self.text;
+                colors[StyleColor::WindowBg as usize] = self.background;
+                colors[StyleColor::TitleBgActive as usize] = accent(0.6);
+                colors[StyleColor::FrameBg as usize] = accent(0.25);
+                colors[StyleColor::FrameBgHovered as usize] = accent(0.4);
+                colors[StyleColor::FrameBgActive as usize] = accent(0.65);
+                colors[StyleColor::Button as usize] = accent(0.4);
+                colors[StyleColor::ButtonHovered as usize] = accent(0.8);
+                colors[StyleColor::ButtonActive as usize] = accent(1.0);
+                colors[StyleColor::Header as usize] = accent(0.3);
+                colors[StyleColor::HeaderHovered as usize] = accent(0.8);
+                colors[StyleColor::HeaderActive as usize] = accent(1.0);
+                colors[StyleColor::CheckMark as usize] = accent(1.0);
+                colors[StyleColor::SliderGrab as usize] = accent(0.8);
+                colors[StyleColor::SliderGrabActive as usize] = accent(1.0);
+            }
+        }
+    }
+
+    /// Replaces the fonts of `imgui` with the one of the style, rasterized at
+    /// `hidpi_factor` times its size so text stays sharp. Falls back to
+    /// imgui's own font if the TTF file can't be read. The font texture of
+    /// the renderer needs to be reloaded afterwards.
+    pub fn load_font(&self, imgui: &mut Context, hidpi_factor: f32) {
+        let size_pixels = self.font_size * hidpi_factor;
+        let data = if self.font_path.is_empty() {
+            None
+        } else {
+            match std::fs::read(&self.font_path) {
+                // imgui asserts on anything that isn't a font
+                Ok(data) if is_font(&data) => Some(data),
+                Ok(_) => {
+                    warn!("{} isn't a TrueType or OpenType font", self.font_path);
+                    None
+                }
+                Err(err) => {
+                    warn!("Error loading font {}: {}", self.font_path, err);
+                    None
+                }
+            }
+        };
+        let mut fonts = imgui.fonts();
+        fonts.clear();
+        match &data {
+            Some(data) => fonts.add_font(&[FontSource::TtfData {
+                data,
+                size_pixels,
+                config: None,
+            }]),
+            None => fonts.add_font(&[FontSource::DefaultFontData {
+                config: Some(FontConfig {
+                    size_pixels,
+                    ..FontConfig::default()
+                }),
+            }]),
+        };
+    }
+    /// Window choosing the theme, its colors and the font, from the TTF file
+    /// at `font_path`.
+    pub fn ui(&mut self, ui: &Ui, font_path: &mut ImString) -> StyleChanges {
+        let mut changes = StyleChanges::default();
+        Window::new(im_str!("Style"))
+            .always_auto_resize(true)
+            .build(ui, || {
+                for &theme in THEMES.iter() {
+                    if ui.radio_button(&im_str!("{}", theme.name()), &mut self.theme, theme) {
+                        changes.colors = true;
+                    }
+                    ui.same_line(0.0);
+                }
+                ui.new_line();
+                if self.theme == Theme::Custom {
+                    changes.colors |= ColorEdit::new(im_str!("Accent"), &mut self.accent).build(ui);
+                    changes.colors |=
+                        ColorEdit::new(im_str!("Background"), &mut self.background).build(ui);
+                    changes.colors |= ColorEdit::new(im_str!("Text"), &mut self.text).build(ui);
+                }
+                ui.separator();
+                ui.input_text(im_str!("TTF file"), font_path).build();
+                Slider::new(im_str!("Font size"))
+                    .range(8.0..=32.0)
+                    .build(ui, &mut self.font_size);
+                if ui.button(im_str!("Load font"), [0.0, 0.0]) {
+                    self.font_path = font_path.to_str().to_string();
+                    changes.font = true;
+                }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Default font"), [0.0, 0.0]) {
+                    font_path.clear();
+                    self.font_path.clear();
+                    self.font_size = DEFAULT_FONT_SIZE;
+                    changes.font = true;
+                }
+            });
+        changes
+    }
+}
+
+fn is_font(data: &[u8]) -> bool {
+    [&[0, 1, 0, 0][..], b"true", b"OTTO"]
+        .iter()
+        .any(|magic| data.starts_with(magic))
+}
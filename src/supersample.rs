@@ -0,0 +1,141 @@
+//! Supersampled screenshots.
+//!
+//! The scene is rendered once into offscreen targets a few times the size of
+//! the window, then read back and box filtered down to the size of the window.
+//! Texels are averaged in linear space, so edges don't come out darker than
+//! they should. The targets only live until the screenshot is saved.
+use crate::{
+    golden::Capture,
+    memory::{self, Category, Tracked},
+};
+use wgpu::{
+    Device, Extent3d, Queue, Texture, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsage, TextureView, TextureViewDescriptor,
+};
+
+/// Supersampling factors selectable from the UI, per axis.
+pub const FACTORS: [u32; 3] = [2, 3, 4];
+
+pub struct Supersample {
+    width: u32,
+    height: u32,
+    factor: u32,
+    capture: Capture,
+    depth: Tracked<Texture>,
+    depth_view: TextureView,
+}
+
+impl Supersample {
+    /// Targets for a `width`x`height` screenshot rendered at `factor` times
+    /// that size. `format` is the one of the swap chain and `depth_format`
+    /// the one of the depth buffer, so the same pipelines can be used.
+    pub fn new(
+        device: &Device,
+        width: u32,
+        height: u32,
+        factor: u32,
+        format: TextureFormat,
+        depth_format: TextureFormat,
+    ) -> Self {
+        let capture = Capture::new(device, width * factor, height * factor, format);
+        let depth = memory::create_texture(
+            device,
+            Category::RenderTargets,
+            &TextureDescriptor {
+                label: Some("Supersampled depth buffer"),
+                size: Extent3d {
+                    width: width * factor,
+                    height: height * factor,
+                    depth: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: depth_format,
+                usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::SAMPLED,
+            },
+        );
+        let depth_view = depth.create_view(&TextureViewDescriptor::default());
+        Self {
+            width,
+            height,
+            factor,
+            capture,
+            depth,
+            depth_view,
+        }
+    }
+
+    /// Size the scene is rendered at, before downsampling.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width * self.factor, self.height * self.factor)
+    }
+
+    pub fn view(&self) -> &TextureView {
+        self.capture.view()
+    }
+
+    pub fn depth(&self) -> &Texture {
+        &self.depth
+    }
+
+    pub fn depth_view(&self) -> &TextureView {
+        &self.depth_view
+    }
+
+    /// Waits for the frame to be rendered and returns it downsampled, as
+    /// tightly packed RGBA8 pixels.
+    pub fn read(&self, device: &Device, queue: &Queue) -> Vec<u8> {
+        let pixels = self.capture.read(device, queue);
+        downsample(&pixels, self.width, self.height, self.factor)
+    }
+}
+
+/// Averages every `factor`x`factor` block of sRGB encoded RGBA8 `pixels` into
+/// one of a `width`x`height` image. Alpha is averaged as is.
+fn downsample(pixels: &[u8], width: u32, height: u32, factor: u32) -> Vec<u8> {
+    let to_linear: Vec<f32> = (0..=255)
+        .map(|v| srgb_to_linear(v as f32 / 255.0))
+        .collect();
+    let (width, height, factor) = (width as usize, height as usize, factor as usize);
+    let stride = width * factor;
+    let weight = 1.0 / (factor * factor) as f32;
+    let mut output = Vec::with_capacity(4 * width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0; 4];
+            for sy in 0..factor {
+                let row = (y * factor + sy) * stride;
+                for sx in 0..factor {
+                    let texel = &pixels[4 * (row + x * factor + sx)..][..4];
+                    for c in 0..3 {
+                        sum[c] += to_linear[texel[c] as usize];
+                    }
+                    sum[3] += texel[3] as f32 / 255.0;
+                }
+            }
+            for (c, sum) in sum.iter().enumerate() {
+                let value = sum * weight;
+                let value = if c < 3 { linear_to_srgb(value) } else { value };
+                output.push((value * 255.0).round().clamp(0.0, 255.0) as u8);
+            }
+        }
+    }
+    output
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
@@ -0,0 +1,266 @@
+//! Temporal antialiasing.
+//!
+//! The projection is jittered by a different sub-pixel offset every frame,
+//! and each frame is blended with the history of the previous ones, found
+//! where the pixel was on the previous frame as with motion blur. The
+//! history is clamped to the colors around the pixel first, so what moved
+//! away (or is no longer lit the same) doesn't leave trails behind.
+use crate::{
+    draw_stats,
+    layout::Layout,
+    memory::{self, Category, Tracked},
+    pass::PassBuilder,
+    ping_pong::PingPong,
+    post::{self, PostFrame},
+    shaders,
+};
+use glam::{Mat4, Vec2};
+use wgpu::{
+    util::make_spirv, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferDescriptor, BufferSize, BufferUsage, Color, CommandEncoder, Device, Extent3d,
+    FilterMode, LoadOp, Origin3d, Queue, RenderPipeline, Sampler, SamplerDescriptor, ShaderStage,
+    Texture, TextureCopyView, TextureDescriptor, TextureDimension, TextureUsage, TextureView,
+    TextureViewDescriptor,
+};
+
+/// Jitter offsets cycled through, from the Halton (2, 3) sequence.
+const JITTER_SAMPLES: u32 = 8;
+
+gpu_struct! {
+    struct TaaUniforms {
+        inverse_view_projection: Mat4,
+        previous_view_projection: Mat4,
+        feedback: f32,
+        history: bool,
+    }
+}
+
+/// How much of the history is kept, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct TaaParams {
+    /// Weight of the history in every frame, higher is smoother and blurrier.
+    pub feedback: f32,
+}
+
+impl Default for TaaParams {
+    fn default() -> Self {
+        Self { feedback: 0.9 }
+    }
+}
+
+pub struct TaaTargets {
+    history: (Tracked<Texture>, TextureView),
+    /// Unset until the history holds a frame, and whenever it's left behind.
+    pub valid: bool,
+    /// Bind groups of the resolve, reading either HDR target.
+    resolve: [BindGroup; 2],
+}
+
+pub struct Taa {
+    uniform: Tracked<Buffer>,
+    sampler: Sampler,
+    layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+}
+
+impl Taa {
+    pub fn new(device: &Device) -> Self {
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::TAA_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("TAA uniforms"),
+                size: TaaUniforms::STD140.size as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("TAA sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("TAA bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(TaaUniforms::STD140.size as _),
+                    },
+                    count: None,
+                },
+                post::texture_entry(1),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+                // the depth and the history
+                post::texture_entry(3),
+                post::texture_entry(4),
+            ],
+        });
+        let pipeline = post::fullscreen_pipeline(
+            device,
+            "TAA pipeline",
+            &[&layout],
+            &vert_module,
+            &frag_module,
+            post::HDR_FORMAT,
+            BlendDescriptor::REPLACE,
+        );
+
+        Self {
+            uniform,
+            sampler,
+            layout,
+            pipeline,
+        }
+    }
+
+    /// Targets for the scene drawn into `hdr`, with `depth` as the view of
+    /// its depth buffer.
+    pub fn targets(&self, device: &Device, hdr: &PingPong, depth: &TextureView) -> TaaTargets {
+        let (width, height) = hdr.size();
+        let history = memory::create_texture(
+            device,
+            Category::RenderTargets,
+            &TextureDescriptor {
+                label: Some("TAA history"),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: post::HDR_FORMAT,
+                usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+            },
+        );
+        let history_view = history.create_view(&TextureViewDescriptor::default());
+        let resolve = hdr.directions(|source, _| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("TAA bind group"),
+                layout: &self.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(self.uniform.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(depth),
+                    },
+                    BindGroupEntry {
+                        binding: 4,
+                        resource: BindingResource::TextureView(&history_view),
+                    },
+                ],
+            })
+        });
+
+        TaaTargets {
+            history: (history, history_view),
+            valid: false,
+            resolve,
+        }
+    }
+
+    /// Records the scene last written to `hdr`, blended with the history,
+    /// into the other target, then keeps the result as the new history.
+    pub fn run(
+        &self,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        params: &TaaParams,
+        frame: PostFrame,
+        targets: &mut TaaTargets,
+        hdr: &PingPong,
+    ) {
+        let uniforms = TaaUniforms {
+            inverse_view_projection: frame.view_projection.inverse(),
+            previous_view_projection: frame.previous_view_projection,
+            feedback: params.feedback,
+            history: targets.valid,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, &uniforms.std140_bytes());
+
+        let mut pass = PassBuilder::new()
+            .color(
+                hdr.write(),
+                post::HDR_FORMAT,
+                LoadOp::Clear(Color::BLACK),
+                true,
+            )
+            .begin(encoder);
+        pass.push_debug_group("TAA resolve");
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, hdr.current(&targets.resolve), &[]);
+        pass.draw(0..3, 0..1);
+        pass.pop_debug_group();
+        drop(pass);
+
+        // the resolve is the history of the next frame
+        let (width, height) = hdr.size();
+        let copy_view = |texture| TextureCopyView {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+        };
+        encoder.copy_texture_to_texture(
+            copy_view(hdr.write_texture()),
+            copy_view(&targets.history.0),
+            Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+        targets.valid = true;
+    }
+}
+
+/// Sub-pixel offset of the projection on `frame`, within half a pixel.
+pub fn jitter(frame: u32) -> Vec2 {
+    // the first point of the sequence is 0
+    let index = frame % JITTER_SAMPLES + 1;
+    Vec2::new(halton(index, 2), halton(index, 3)) - Vec2::splat(0.5)
+}
+
+/// `projection` offset by `jitter` pixels of a `width`x`height` target.
+pub fn jittered(projection: Mat4, jitter: Vec2, width: u32, height: u32) -> Mat4 {
+    let offset = jitter * 2.0 / Vec2::new(width as f32, height as f32);
+    Mat4::from_translation(offset.extend(0.0)) * projection
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
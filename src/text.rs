@@ -0,0 +1,411 @@
+//! Signed distance field text.
+//!
+//! Glyphs of an 8x8 bitmap font are upscaled and converted to distance fields
+//! at startup, which keeps their edges sharp at any size and makes outlines
+//! and drop shadows a matter of moving the threshold in the shader.
+use crate::{
+    draw_stats::{self, CountedPass},
+    memory::{self, Category, Tracked},
+    shaders,
+    vertex::VertexLayout,
+};
+use bytemuck::{Pod, Zeroable};
+use font8x8::UnicodeFonts;
+use glam::{Mat4, Vec3};
+use imgui::{im_str, ColorEdit, Drag, Slider, Ui};
+use wgpu::{
+    util::make_spirv, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    BlendFactor, BlendOperation, Buffer, BufferDescriptor, BufferSize, BufferUsage,
+    ColorStateDescriptor, ColorWrite, CompareFunction, DepthStencilStateDescriptor, Device,
+    FilterMode, IndexFormat, InputStepMode, PipelineLayoutDescriptor, PrimitiveTopology,
+    ProgrammableStageDescriptor, Queue, RenderPipeline, RenderPipelineDescriptor,
+    SamplerDescriptor, ShaderStage, StencilStateDescriptor, Texture, TextureComponentType,
+    TextureFormat, TextureViewDescriptor, TextureViewDimension, VertexStateDescriptor,
+};
+
+/// Characters in the atlas, printable ASCII.
+const FIRST_CHAR: u8 = b' ';
+const LAST_CHAR: u8 = b'~';
+const COLUMNS: u32 = 16;
+
+/// Upscale factor of the bitmap font.
+const SCALE: u32 = 3;
+/// Empty space around every glyph, so outlines and shadows aren't clipped.
+const PADDING: u32 = 4;
+/// Side of the square atlas cell of every glyph.
+const CELL: u32 = 8 * SCALE + 2 * PADDING;
+/// Distance (in atlas pixels) mapped to the `[0, 1]` range of the field.
+const SPREAD: f32 = 4.0;
+
+const MAX_GLYPHS: usize = 1024;
+
+/// Distance field of every glyph, laid out in a grid.
+pub struct FontAtlas {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl FontAtlas {
+    pub fn generate() -> Self {
+        let glyphs = (LAST_CHAR - FIRST_CHAR + 1) as u32;
+        let rows = glyphs.div_ceil(COLUMNS);
+        let width = COLUMNS * CELL;
+        let height = rows * CELL;
+        let mut data = vec![0; (width * height) as usize];
+
+        for glyph in 0..glyphs {
+            let bitmap = font8x8::BASIC_FONTS
+                .get((FIRST_CHAR + glyph as u8) as char)
+                .unwrap_or_default();
+            // bit 0 of every row is the leftmost pixel
+            let inside = |x: i32, y: i32| {
+                let (gx, gy) = ((x - PADDING as i32), (y - PADDING as i32));
+                if gx < 0 || gy < 0 || gx >= (8 * SCALE) as i32 || gy >= (8 * SCALE) as i32 {
+                    return false;
+                }
+                let (gx, gy) = (gx as u32 / SCALE, gy as u32 / SCALE);
+                bitmap[gy as usize] & (1 << gx) != 0
+            };
+
+            let (cell_x, cell_y) = ((glyph % COLUMNS) * CELL, (glyph / COLUMNS) * CELL);
+            let spread = SPREAD as i32;
+            for y in 0..CELL as i32 {
+                for x in 0..CELL as i32 {
+                    // brute force search of the closest pixel on the other
+                    // side of the edge, only done once at startup
+                    let state = inside(x, y);
+                    let mut closest = SPREAD;
+                    for dy in -spread..=spread {
+                        for dx in -spread..=spread {
+                            if inside(x + dx, y + dy) != state {
+                                let d = ((dx * dx + dy * dy) as f32).sqrt() - 0.5;
+                                closest = closest.min(d);
+                            }
+                        }
+                    }
+                    let signed = if state { closest } else { -closest };
+                    let value = (0.5 + signed / (2.0 * SPREAD)).clamp(0.0, 1.0);
+                    let index = (cell_y + y as u32) * width + cell_x + x as u32;
+                    data[index as usize] = (value * 255.0) as u8;
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Texture coordinates of the cell of a character, as `[u0, v0, u1, v1]`.
+    fn cell_uv(&self, c: char) -> [f32; 4] {
+        let c = c as u32;
+        let glyph = if c >= FIRST_CHAR as u32 && c <= LAST_CHAR as u32 {
+            c - FIRST_CHAR as u32
+        } else {
+            (b'?' - FIRST_CHAR) as u32
+        };
+        let x = ((glyph % COLUMNS) * CELL) as f32 / self.width as f32;
+        let y = ((glyph / COLUMNS) * CELL) as f32 / self.height as f32;
+        let w = CELL as f32 / self.width as f32;
+        let h = CELL as f32 / self.height as f32;
+        [x, y, x + w, y + h]
+    }
+}
+
+/// Appearance of the text.
+#[derive(Clone, Copy)]
+pub struct TextStyle {
+    pub color: [f32; 4],
+    pub outline_color: [f32; 4],
+    /// Width of the outline, in the units of the distance field (0 to 0.5).
+    pub outline_width: f32,
+    /// Offset of the drop shadow, in atlas pixels.
+    pub shadow_offset: [f32; 2],
+    pub shadow_alpha: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 1.0, 1.0, 1.0],
+            outline_color: [0.0, 0.0, 0.0, 1.0],
+            outline_width: 0.1,
+            shadow_offset: [1.5, 1.5],
+            shadow_alpha: 0.5,
+        }
+    }
+}
+
+impl TextStyle {
+    pub fn ui(&mut self, ui: &Ui) {
+        ColorEdit::new(im_str!("Text color"), &mut self.color).build(ui);
+        ColorEdit::new(im_str!("Outline color"), &mut self.outline_color).build(ui);
+        Slider::new(im_str!("Outline width"))
+            .range(0.0..=0.4)
+            .build(ui, &mut self.outline_width);
+        Drag::new(im_str!("Shadow offset"))
+            .speed(0.05)
+            .build_array(ui, &mut self.shadow_offset);
+        Slider::new(im_str!("Shadow alpha"))
+            .range(0.0..=1.0)
+            .build(ui, &mut self.shadow_alpha);
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct TextUniforms {
+    view_projection: [[f32; 4]; 4],
+    color: [f32; 4],
+    outline_color: [f32; 4],
+    shadow_offset: [f32; 2],
+    outline_width: f32,
+    shadow_alpha: f32,
+}
+
+vertex_struct! {
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    struct TextVertex {
+        position: [f32; 3],
+        uv: [f32; 2],
+    }
+}
+
+/// Renders world space text labels, batched into a single draw call.
+pub struct TextRenderer {
+    atlas: FontAtlas,
+    vertices: Vec<TextVertex>,
+    vertex_count: u32,
+    vertex_buffer: Tracked<Buffer>,
+    uniform: Tracked<Buffer>,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+    _texture: Tracked<Texture>,
+}
+
+impl TextRenderer {
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        color_format: TextureFormat,
+        depth_format: TextureFormat,
+    ) -> Self {
+        let atlas = FontAtlas::generate();
+        let texture = crate::texture::create_r8(
+            device,
+            queue,
+            "Font atlas",
+            atlas.width,
+            atlas.height,
+            &atlas.data,
+        );
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Font atlas sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Text uniforms"),
+                size: std::mem::size_of::<TextUniforms>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let vertex_buffer = memory::create_buffer(
+            device,
+            Category::Meshes,
+            &BufferDescriptor {
+                label: Some("Text vertices"),
+                size: (6 * MAX_GLYPHS * std::mem::size_of::<TextVertex>()) as _,
+                usage: BufferUsage::VERTEX | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Text bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::VERTEX | ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(std::mem::size_of::<TextUniforms>() as _),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::SampledTexture {
+                        dimension: TextureViewDimension::D2,
+                        component_type: TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Text bind group"),
+            layout: &layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(uniform.slice(..)),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let vert_module = device.create_shader_module(make_spirv(shaders::TEXT_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::TEXT_FRAG));
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Text pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Text pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex_stage: ProgrammableStageDescriptor {
+                module: &vert_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(ProgrammableStageDescriptor {
+                module: &frag_module,
+                entry_point: "main",
+            }),
+            rasterization_state: None,
+            primitive_topology: PrimitiveTopology::TriangleList,
+            color_states: &[ColorStateDescriptor {
+                format: color_format,
+                // premultiplied alpha
+                color_blend: BlendDescriptor {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::OneMinusSrcAlpha,
+                    operation: BlendOperation::Add,
+                },
+                alpha_blend: BlendDescriptor {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::OneMinusSrcAlpha,
+                    operation: BlendOperation::Add,
+                },
+                write_mask: ColorWrite::ALL,
+            }],
+            // labels are hidden behind geometry, but don't occlude anything
+            depth_stencil_state: Some(DepthStencilStateDescriptor {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilStateDescriptor::default(),
+            }),
+            vertex_state: VertexStateDescriptor {
+                index_format: IndexFormat::Uint16,
+                vertex_buffers: &[TextVertex::buffer_descriptor(InputStepMode::Vertex)],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        Self {
+            atlas,
+            vertices: Vec::new(),
+            vertex_count: 0,
+            vertex_buffer,
+            uniform,
+            bind_group,
+            pipeline,
+            _texture: texture,
+        }
+    }
+
+    /// Queues a line of text centered at `anchor`, on the plane spanned by
+    /// `right` and `up` (unit vectors), with glyphs `height` units tall.
+    pub fn label(&mut self, text: &str, anchor: Vec3, right: Vec3, up: Vec3, height: f32) {
+        // glyphs are square and the font is monospaced
+        let cell = height * CELL as f32 / (8 * SCALE) as f32;
+        let width = height * text.chars().count() as f32;
+        for (i, c) in text.chars().enumerate() {
+            if self.vertices.len() + 6 > 6 * MAX_GLYPHS {
+                break;
+            }
+            let center = anchor + right * (height * (i as f32 + 0.5) - width * 0.5);
+            let [u0, v0, u1, v1] = self.atlas.cell_uv(c);
+            let corner = |x: f32, y: f32, u, v| TextVertex {
+                position: (center + (right * x + up * y) * (cell * 0.5)).into(),
+                uv: [u, v],
+            };
+            self.vertices.extend_from_slice(&[
+                corner(-1.0, -1.0, u0, v1),
+                corner(1.0, -1.0, u1, v1),
+                corner(1.0, 1.0, u1, v0),
+                corner(-1.0, -1.0, u0, v1),
+                corner(1.0, 1.0, u1, v0),
+                corner(-1.0, 1.0, u0, v0),
+            ]);
+        }
+    }
+
+    /// Uploads the queued labels, which are cleared for the next frame.
+    pub fn prepare(&mut self, queue: &Queue, view_projection: Mat4, style: &TextStyle) {
+        let uniforms = TextUniforms {
+            view_projection: view_projection.to_cols_array_2d(),
+            color: style.color,
+            outline_color: style.outline_color,
+            shadow_offset: [
+                style.shadow_offset[0] / self.atlas.width as f32,
+                style.shadow_offset[1] / self.atlas.height as f32,
+            ],
+            outline_width: style.outline_width,
+            shadow_alpha: style.shadow_alpha,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, bytemuck::bytes_of(&uniforms));
+        if !self.vertices.is_empty() {
+            draw_stats::write_buffer(
+                queue,
+                &self.vertex_buffer,
+                0,
+                bytemuck::cast_slice(&self.vertices),
+            );
+        }
+        self.vertex_count = self.vertices.len() as _;
+        self.vertices.clear();
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut CountedPass<'a>) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.draw(0..self.vertex_count, 0..1);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::memory::{self, Category, Tracked};
+use image::ImageResult;
+use std::path::Path;
+use wgpu::{
+    Device, Extent3d, Origin3d, Queue, Texture, TextureCopyView, TextureDataLayout,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsage,
+};
+
+/// Creates a sampled 2D texture initialized with RGBA8 pixel data, with a full
+/// mip chain box filtered on the CPU.
+pub fn create_rgba8_mipmapped(
+    device: &Device,
+    queue: &Queue,
+    label: &str,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Tracked<Texture> {
+    let levels = mip_chain(width, height, data);
+    let texture = memory::create_texture(
+        device,
+        Category::Textures,
+        &TextureDescriptor {
+            label: Some(label),
+            size: extent(width, height),
+            mip_level_count: levels.len() as _,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+        },
+    );
+    for (mip, level) in levels.iter().enumerate() {
+        let size = extent((width >> mip).max(1), (height >> mip).max(1));
+        queue.write_texture(
+            TextureCopyView {
+                texture: &texture,
+                mip_level: mip as _,
+                origin: Origin3d::ZERO,
+            },
+            level,
+            TextureDataLayout {
+                offset: 0,
+                bytes_per_row: 4 * size.width,
+                rows_per_image: size.height,
+            },
+            size,
+        );
+    }
+    texture
+}
+
+/// Halves RGBA8 pixels down to 1x1, averaging 2x2 texels each time. The first
+/// level is `data` itself.
+pub fn mip_chain(width: u32, height: u32, data: &[u8]) -> Vec<Vec<u8>> {
+    let mut levels = vec![data.to_vec()];
+    let (mut width, mut height) = (width as usize, height as usize);
+    while width > 1 || height > 1 {
+        let (w, h) = ((width / 2).max(1), (height / 2).max(1));
+        let prev = levels.last().unwrap();
+        let texel = |x: usize, y: usize, c: usize| {
+            prev[4 * (y.min(height - 1) * width + x.min(width - 1)) + c] as u32
+        };
+        let mut level = Vec::with_capacity(4 * w * h);
+        for y in 0..h {
+            for x in 0..w {
+                for c in 0..4 {
+                    let sum = texel(2 * x, 2 * y, c)
+                        + texel(2 * x + 1, 2 * y, c)
+                        + texel(2 * x, 2 * y + 1, c)
+                        + texel(2 * x + 1, 2 * y + 1, c);
+                    level.push(((sum + 2) / 4) as u8);
+                }
+            }
+        }
+        levels.push(level);
+        width = w;
+        height = h;
+    }
+    levels
+}
+
+/// Creates a sampled single channel 2D texture.
+pub fn create_r8(
+    device: &Device,
+    queue: &Queue,
+    label: &str,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Tracked<Texture> {
+    let size = extent(width, height);
+    create_2d(device, queue, label, TextureFormat::R8Unorm, 1, size, data)
+}
+
+fn extent(width: u32, height: u32) -> Extent3d {
+    Extent3d {
+        width,
+        height,
+        depth: 1,
+    }
+}
+
+fn create_2d(
+    device: &Device,
+    queue: &Queue,
+    label: &str,
+    format: TextureFormat,
+    bytes_per_pixel: u32,
+    size: Extent3d,
+    data: &[u8],
+) -> Tracked<Texture> {
+    let texture = memory::create_texture(
+        device,
+        Category::Textures,
+        &TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+        },
+    );
+    queue.write_texture(
+        TextureCopyView {
+            texture: &texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+        },
+        data,
+        TextureDataLayout {
+            offset: 0,
+            bytes_per_row: bytes_per_pixel * size.width,
+            rows_per_image: size.height,
+        },
+        size,
+    );
+    texture
+}
+
+/// Generates a tangent-space normal map of a grid of round bumps.
+///
+/// Normals are encoded in the `[0, 255]` range and must be sampled from a
+/// linear (non sRGB) texture.
+pub fn bumps_normal_map(size: u32, tiles: u32) -> Vec<u8> {
+    let tile = size as f32 / tiles as f32;
+    let height = |x: f32, y: f32| {
+        let dx = (x / tile).fract() - 0.5;
+        let dy = (y / tile).fract() - 0.5;
+        (0.16 - dx * dx - dy * dy).max(0.0).sqrt()
+    };
+
+    let strength = tile / 4.0;
+    let mut data = Vec::with_capacity((4 * size * size) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+            let dx = (height(x + 0.5, y) - height(x - 0.5, y)) * strength;
+            let dy = (height(x, y + 0.5) - height(x, y - 0.5)) * strength;
+            let len = (dx * dx + dy * dy + 1.0).sqrt();
+            let encode = |n: f32| ((n / len * 0.5 + 0.5) * 255.0) as u8;
+            data.extend_from_slice(&[encode(-dx), encode(-dy), encode(1.0), 255]);
+        }
+    }
+    data
+}
+
+/// Generates an RGBA8 checkerboard of `tiles`x`tiles` squares of two colors.
+pub fn checker(size: u32, tiles: u32, a: [u8; 3], b: [u8; 3]) -> Vec<u8> {
+    let tile = (size / tiles).max(1);
+    let mut data = Vec::with_capacity((4 * size * size) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let [r, g, b] = [a, b][((x / tile + y / tile) % 2) as usize];
+            data.extend_from_slice(&[r, g, b, 255]);
+        }
+    }
+    data
+}
+
+/// Loads an image file as RGBA8 pixels, returning its size and data.
+pub fn load_rgba8<P: AsRef<Path>>(path: P) -> ImageResult<(u32, u32, Vec<u8>)> {
+    let image = image::open(path)?.to_rgba8();
+    Ok((image.width(), image.height(), image.into_raw()))
+}
+
+/// Generates a colorful RGBA8 test image with hard edges and smooth
+/// gradients, to have something to filter.
+pub fn test_pattern(size: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity((4 * size * size) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let (u, v) = (x as f32 / size as f32, y as f32 / size as f32);
+            let checker = ((x / 32 + y / 32) % 2) as f32;
+            let (dx, dy) = (u - 0.5, v - 0.5);
+            let circle = ((dx * dx + dy * dy).sqrt() < 0.3) as u32 as f32;
+            let r = u * (1.0 - circle) + circle;
+            let g = v * (1.0 - circle) + 0.5 * circle;
+            let b = 0.25 + 0.5 * checker * (1.0 - circle);
+            let encode = |c: f32| (c.min(1.0) * 255.0) as u8;
+            data.extend_from_slice(&[encode(r), encode(g), encode(b), 255]);
+        }
+    }
+    data
+}
+
+/// Generates an RGBA8 decal of a ring around an arrow pointing along `u`, so
+/// its orientation shows, transparent everywhere else.
+pub fn decal(size: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity((4 * size * size) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let (u, v) = (
+                (x as f32 + 0.5) / size as f32 - 0.5,
+                (y as f32 + 0.5) / size as f32 - 0.5,
+            );
+            let radius = (u * u + v * v).sqrt();
+            let ring = (0.38..0.46).contains(&radius);
+            let shaft = (-0.25..0.1).contains(&u) && v.abs() < 0.05;
+            let head = (0.1..0.3).contains(&u) && v.abs() < (0.3 - u) * 0.75;
+            let alpha = if ring || shaft || head { 255 } else { 0 };
+            data.extend_from_slice(&[255, 255, 255, alpha]);
+        }
+    }
+    data
+}
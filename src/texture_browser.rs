@@ -0,0 +1,442 @@
+//! Browser of the textures registered into imgui, with an inspector of any
+//! mip of them.
+//!
+//! imgui_wgpu only keeps the textures, so their format and mip levels are
+//! registered here along with their id. The inspected mip is drawn into a
+//! preview target of its own, zoomed and panned, with some of its channels
+//! masked out (or a single one shown as grey).
+use crate::{
+    draw_stats,
+    memory::{self, Category, Tracked},
+    shaders,
+};
+use bytemuck::{Pod, Zeroable};
+use imgui::{im_str, ImString, Image, MouseButton, Selectable, Slider, TextureId, Ui, Window};
+use std::num::NonZeroU32;
+use wgpu::{
+    util::make_spirv, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferDescriptor, BufferSize, BufferUsage, Color, ColorStateDescriptor, ColorWrite,
+    CommandEncoder, Device, Extent3d, FilterMode, IndexFormat, LoadOp, Operations,
+    PipelineLayoutDescriptor, PrimitiveTopology, ProgrammableStageDescriptor, Queue,
+    RenderPassColorAttachmentDescriptor, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, Sampler, SamplerDescriptor, ShaderStage, TextureComponentType,
+    TextureFormat, TextureUsage, TextureViewDescriptor, TextureViewDimension,
+    VertexStateDescriptor,
+};
+
+/// Size of the square preview, in pixels.
+const PREVIEW_SIZE: u32 = 256;
+
+const PREVIEW_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// Height of the thumbnails of the list.
+const THUMBNAIL_HEIGHT: f32 = 32.0;
+
+const MAX_ZOOM: f32 = 64.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct PreviewUniforms {
+    offset: [f32; 2],
+    scale: [f32; 2],
+    channels: [f32; 4],
+    grayscale: u32,
+    _pad: [u32; 3],
+}
+
+/// What imgui_wgpu doesn't keep of a texture.
+#[derive(Clone, Copy)]
+pub struct TextureInfo {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub mip_levels: u32,
+}
+
+impl TextureInfo {
+    /// Info of the texture created from `config`, which must have a format.
+    pub fn of(config: &imgui_wgpu::TextureConfig) -> Self {
+        Self {
+            width: config.size.width,
+            height: config.size.height,
+            format: config
+                .format
+                .expect("Error registering texture without a format"),
+            mip_levels: config.mip_level_count,
+        }
+    }
+}
+
+struct Entry {
+    name: ImString,
+    id: TextureId,
+    info: TextureInfo,
+}
+
+/// Inspected mip, and how it's shown.
+struct Inspected {
+    entry: usize,
+    mip: u32,
+    /// Red, green, blue and alpha shown.
+    channels: [bool; 4],
+    zoom: f32,
+    /// Point of the texture at the center of the preview.
+    center: [f32; 2],
+}
+
+pub struct TextureBrowser {
+    entries: Vec<Entry>,
+    inspected: Option<Inspected>,
+    /// Whether the window was shown, not collapsed, on the last frame.
+    visible: bool,
+    preview_id: TextureId,
+    _preview_memory: Tracked<()>,
+    uniform: Tracked<Buffer>,
+    sampler: Sampler,
+    layout: BindGroupLayout,
+    /// Bind group of the inspected entry and mip.
+    bind_group: Option<((usize, u32), BindGroup)>,
+    pipeline: RenderPipeline,
+}
+
+impl TextureBrowser {
+    pub fn new(device: &Device, renderer: &mut imgui_wgpu::Renderer) -> Self {
+        let preview = imgui_wgpu::Texture::new(
+            device,
+            renderer,
+            imgui_wgpu::TextureConfig {
+                label: Some("Texture browser preview"),
+                size: Extent3d {
+                    width: PREVIEW_SIZE,
+                    height: PREVIEW_SIZE,
+                    depth: 1,
+                },
+                format: Some(PREVIEW_FORMAT),
+                usage: TextureUsage::SAMPLED | TextureUsage::OUTPUT_ATTACHMENT,
+                ..Default::default()
+            },
+        );
+        let preview_id = renderer.textures.insert(preview);
+        let preview_memory = memory::track(
+            (),
+            Category::RenderTargets,
+            Some("Texture browser preview"),
+            4 * PREVIEW_SIZE as u64 * PREVIEW_SIZE as u64,
+        );
+
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::TEXTURE_PREVIEW_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Texture browser uniforms"),
+                size: std::mem::size_of::<PreviewUniforms>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        // texels stay sharp when zoomed in
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Texture browser sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Texture browser bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: BufferSize::new(
+                            std::mem::size_of::<PreviewUniforms>() as _
+                        ),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::SampledTexture {
+                        dimension: TextureViewDimension::D2,
+                        component_type: TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::Sampler { comparison: false },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Texture browser pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Texture browser pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex_stage: ProgrammableStageDescriptor {
+                module: &vert_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(ProgrammableStageDescriptor {
+                module: &frag_module,
+                entry_point: "main",
+            }),
+            rasterization_state: None,
+            primitive_topology: PrimitiveTopology::TriangleList,
+            color_states: &[ColorStateDescriptor {
+                format: PREVIEW_FORMAT,
+                alpha_blend: BlendDescriptor::REPLACE,
+                color_blend: BlendDescriptor::REPLACE,
+                write_mask: ColorWrite::ALL,
+            }],
+            depth_stencil_state: None,
+            vertex_state: VertexStateDescriptor {
+                index_format: IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        Self {
+            entries: Vec::new(),
+            inspected: None,
+            visible: false,
+            preview_id,
+            _preview_memory: preview_memory,
+            uniform,
+            sampler,
+            layout,
+            bind_group: None,
+            pipeline,
+        }
+    }
+
+    /// Lists the texture of imgui `id`.
+    pub fn register(&mut self, name: &str, id: TextureId, info: TextureInfo) {
+        self.entries.push(Entry {
+            name: ImString::new(name),
+            id,
+            info,
+        });
+    }
+
+    /// Records the preview of the inspected mip, if the window is open.
+    pub fn run(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        renderer: &imgui_wgpu::Renderer,
+    ) {
+        let inspected = match &self.inspected {
+            Some(inspected) if self.visible => inspected,
+            _ => return,
+        };
+        let entry = &self.entries[inspected.entry];
+        let key = (inspected.entry, inspected.mip);
+        if self.bind_group.as_ref().map(|(k, _)| *k) != Some(key) {
+            let texture = renderer.textures.get(entry.id).unwrap().texture();
+            let view = texture.create_view(&TextureViewDescriptor {
+                base_mip_level: inspected.mip,
+                level_count: NonZeroU32::new(1),
+                ..Default::default()
+            });
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Texture browser bind group"),
+                layout: &self.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(self.uniform.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(&view),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+            self.bind_group = Some((key, bind_group));
+        }
+
+        let (offset, scale) = inspected.region(&entry.info);
+        let channels = inspected.channels;
+        let uniforms = PreviewUniforms {
+            offset,
+            scale,
+            channels: channels.map(|c| c as u32 as f32),
+            grayscale: (channels.iter().filter(|&&c| c).count() == 1) as u32,
+            _pad: [0; 3],
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, bytemuck::bytes_of(&uniforms));
+
+        let target = renderer.textures.get(self.preview_id).unwrap().view();
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            color_attachments: &[RenderPassColorAttachmentDescriptor {
+                attachment: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        pass.push_debug_group("Texture browser preview");
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group.as_ref().unwrap().1, &[]);
+        pass.draw(0..3, 0..1);
+        pass.pop_debug_group();
+    }
+
+    /// Window listing the textures, and inspecting the selected one.
+    pub fn ui(&mut self, ui: &Ui) {
+        let entries = &self.entries;
+        let inspected = &mut self.inspected;
+        let preview_id = self.preview_id;
+        let mut visible = false;
+        Window::new(im_str!("Textures"))
+            .always_auto_resize(true)
+            .build(ui, || {
+                visible = true;
+                for (i, entry) in entries.iter().enumerate() {
+                    let info = &entry.info;
+                    let aspect = info.width as f32 / info.height.max(1) as f32;
+                    Image::new(entry.id, [THUMBNAIL_HEIGHT * aspect, THUMBNAIL_HEIGHT]).build(ui);
+                    ui.same_line(0.0);
+                    let selected = inspected.as_ref().map(|s| s.entry) == Some(i);
+                    if Selectable::new(&entry.name).selected(selected).build(ui) {
+                        *inspected = Some(Inspected::new(i));
+                    }
+                }
+                if entries.is_empty() {
+                    ui.text("No textures registered");
+                }
+                if let Some(inspected) = inspected {
+                    ui.separator();
+                    inspected.ui(ui, &entries[inspected.entry].info, preview_id);
+                }
+            });
+        self.visible = visible;
+    }
+}
+
+impl Inspected {
+    fn new(entry: usize) -> Self {
+        Self {
+            entry,
+            mip: 0,
+            channels: [true, true, true, false],
+            zoom: 1.0,
+            center: [0.5, 0.5],
+        }
+    }
+
+    /// Offset and scale of the region of the texture shown, in texture
+    /// coordinates, keeping the aspect of the mip.
+    fn region(&self, info: &TextureInfo) -> ([f32; 2], [f32; 2]) {
+        let aspect = info.width as f32 / info.height.max(1) as f32;
+        let scale = if aspect >= 1.0 {
+            [1.0, aspect]
+        } else {
+            [1.0 / aspect, 1.0]
+        };
+        let scale = [scale[0] / self.zoom, scale[1] / self.zoom];
+        let offset = [
+            self.center[0] - scale[0] * 0.5,
+            self.center[1] - scale[1] * 0.5,
+        ];
+        (offset, scale)
+    }
+
+    fn ui(&mut self, ui: &Ui, info: &TextureInfo, preview_id: TextureId) {
+        let width = (info.width >> self.mip).max(1);
+        let height = (info.height >> self.mip).max(1);
+        ui.text(format!("Format: {:?}", info.format));
+        ui.text(format!("Size: {}x{}", info.width, info.height));
+        ui.text(format!("Mip levels: {}", info.mip_levels));
+        if info.mip_levels > 1 {
+            Slider::new(im_str!("Mip"))
+                .range(0..=info.mip_levels - 1)
+                .build(ui, &mut self.mip);
+            ui.same_line(0.0);
+            ui.text(format!("{}x{}", width, height));
+        }
+        let labels = [im_str!("R"), im_str!("G"), im_str!("B"), im_str!("A")];
+        for (i, (label, channel)) in labels.iter().zip(&mut self.channels).enumerate() {
+            if i > 0 {
+                ui.same_line(0.0);
+            }
+            ui.checkbox(label, channel);
+        }
+        Slider::new(im_str!("Zoom"))
+            .range(1.0..=MAX_ZOOM)
+            .build(ui, &mut self.zoom);
+        ui.same_line(0.0);
+        if ui.button(im_str!("Reset view"), [0.0, 0.0]) {
+            self.zoom = 1.0;
+            self.center = [0.5, 0.5];
+        }
+
+        // an invisible button under the preview takes the drags, which would
+        // move the window otherwise
+        let size = PREVIEW_SIZE as f32;
+        let origin = ui.cursor_screen_pos();
+        ui.invisible_button(im_str!("Preview"), [size, size]);
+        let hovered = ui.is_item_hovered();
+        let dragged = ui.is_item_active() && ui.is_mouse_dragging(MouseButton::Left);
+        ui.set_cursor_screen_pos(origin);
+        Image::new(preview_id, [size, size]).build(ui);
+        if !hovered && !dragged {
+            return;
+        }
+
+        let io = ui.io();
+        let (offset, scale) = self.region(info);
+        let cursor = [
+            (io.mouse_pos[0] - origin[0]) / size,
+            (io.mouse_pos[1] - origin[1]) / size,
+        ];
+        let uv = [
+            offset[0] + cursor[0] * scale[0],
+            offset[1] + cursor[1] * scale[1],
+        ];
+        if dragged {
+            self.center[0] -= io.mouse_delta[0] / size * scale[0];
+            self.center[1] -= io.mouse_delta[1] / size * scale[1];
+        }
+        // zooming keeps the point under the cursor where it is
+        if hovered && io.mouse_wheel != 0.0 {
+            self.zoom = (self.zoom * 1.25f32.powf(io.mouse_wheel)).clamp(1.0, MAX_ZOOM);
+            let (_, scale) = self.region(info);
+            self.center = [
+                uv[0] - (cursor[0] - 0.5) * scale[0],
+                uv[1] - (cursor[1] - 0.5) * scale[1],
+            ];
+        }
+        if (0.0..1.0).contains(&uv[0]) && (0.0..1.0).contains(&uv[1]) {
+            let x = (uv[0] * width as f32) as u32;
+            let y = (uv[1] * height as f32) as u32;
+            ui.text(format!("Texel: {}, {}", x, y));
+        }
+    }
+}
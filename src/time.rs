@@ -0,0 +1,151 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+use wgpu::{Device, Maintain};
+
+/// Upper bound for the time measured in a single frame.
+///
+/// Prevents the simulation from trying to catch up with a very long frame (a
+/// breakpoint, dragging the window...) by running hundreds of updates at once.
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// Frame clock.
+///
+/// Ticked once at the beginning of every frame to measure the time it took to
+/// produce the previous one.
+pub struct Time {
+    start: Instant,
+    last: Instant,
+    delta: Duration,
+}
+
+impl Time {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            last: now,
+            delta: Duration::default(),
+        }
+    }
+
+    /// Measures the time elapsed since the last tick.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        self.delta = now - self.last;
+        self.last = now;
+    }
+
+    /// Duration of the last frame.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    /// Time elapsed since the last tick.
+    pub fn since_tick(&self) -> Duration {
+        self.last.elapsed()
+    }
+
+    /// Time elapsed since the clock was created.
+    pub fn elapsed(&self) -> Duration {
+        self.last - self.start
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fixed timestep clock.
+///
+/// Frame time is accumulated every frame and consumed in chunks of exactly
+/// `step`, so the simulation advances at the same rate regardless of how fast
+/// frames are being rendered. The leftover is exposed through
+/// [`FixedTimestep::alpha`] to interpolate between the last two simulation
+/// states.
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    /// Creates a clock producing `rate` updates per second.
+    pub fn new(rate: u32) -> Self {
+        Self {
+            step: Duration::from_secs(1) / rate,
+            accumulator: Duration::default(),
+        }
+    }
+
+    /// Duration of a single update in seconds.
+    pub fn step(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    /// Accumulates the duration of the last frame and returns the number of
+    /// simulation updates that should run this frame.
+    pub fn advance(&mut self, delta: Duration) -> u32 {
+        self.accumulator += delta.min(MAX_FRAME_TIME);
+
+        let mut updates = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            updates += 1;
+        }
+        updates
+    }
+
+    /// Blend factor in `[0, 1)` between the previous and the current
+    /// simulation states.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+/// Number of samples averaged by the [`GpuTimer`].
+const GPU_TIMER_SAMPLES: usize = 60;
+
+/// GPU frame timer.
+///
+/// Timestamp queries are not exposed by wgpu yet, so the time is approximated
+/// by blocking right after a submission until the device becomes idle. This
+/// stalls the CPU, so it should only be enabled while profiling.
+#[derive(Default)]
+pub struct GpuTimer {
+    samples: VecDeque<Duration>,
+}
+
+impl GpuTimer {
+    /// Waits for all submitted work to complete and records how long it took.
+    pub fn measure(&mut self, device: &Device) {
+        let start = Instant::now();
+        device.poll(Maintain::Wait);
+
+        if self.samples.len() == GPU_TIMER_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(start.elapsed());
+    }
+
+    /// Most recent measurement.
+    pub fn last(&self) -> Option<Duration> {
+        self.samples.back().copied()
+    }
+
+    /// Average of the last measurements.
+    pub fn average(&self) -> Duration {
+        if self.samples.is_empty() {
+            Duration::default()
+        } else {
+            self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+        }
+    }
+
+    /// Discards all the recorded measurements.
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
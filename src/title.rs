@@ -0,0 +1,62 @@
+//! Frame rate, frame time, adapter and resolution in the title of the window,
+//! for when the UI is hidden or windows are compared side by side.
+//!
+//! Frames are averaged between refreshes, a few times a second, so the title
+//! stays readable and setting it doesn't cost a frame.
+use crate::render_thread::WindowProxy;
+use std::time::Duration;
+
+/// Title of the window without the status.
+pub const TITLE: &str = "wgpu";
+
+/// Time between refreshes of the title.
+const REFRESH: Duration = Duration::from_millis(250);
+
+pub struct TitleStatus {
+    adapter: String,
+    /// Frames since the last refresh, and how long they took.
+    frames: u32,
+    elapsed: Duration,
+    /// Whether the title has a status to be cleared.
+    shown: bool,
+}
+
+impl TitleStatus {
+    pub fn new(adapter: &str) -> Self {
+        Self {
+            adapter: adapter.to_string(),
+            frames: 0,
+            elapsed: Duration::default(),
+            shown: false,
+        }
+    }
+
+    /// Counts a frame that took `delta`, refreshing the title of `window` if
+    /// it's been long enough. Clears the status if it isn't `enabled`.
+    pub fn update(&mut self, window: &WindowProxy, delta: Duration, enabled: bool) {
+        if !enabled {
+            if self.shown {
+                window.set_title(TITLE);
+                self.shown = false;
+            }
+            return;
+        }
+        self.frames += 1;
+        self.elapsed += delta;
+        if self.shown && self.elapsed < REFRESH {
+            return;
+        }
+        let seconds = self.elapsed.as_secs_f32().max(f32::EPSILON);
+        let fps = self.frames as f32 / seconds;
+        let frame_time = seconds * 1000.0 / self.frames as f32;
+        let (width, height) = window.drawable_size();
+        let title = format!(
+            "{} - {:.0} fps ({:.2} ms) - {} - {}x{}",
+            TITLE, fps, frame_time, self.adapter, width, height
+        );
+        window.set_title(&title);
+        self.frames = 0;
+        self.elapsed = Duration::default();
+        self.shown = true;
+    }
+}
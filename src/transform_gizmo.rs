@@ -0,0 +1,385 @@
+//! Handles translating, rotating and scaling the selected object.
+//!
+//! The handles are debug lines around the object, of the same size on screen
+//! at any distance. One of them is grabbed when the cursor is close enough to
+//! its lines, and dragging it moves the object along its axis, turns it
+//! around it, or scales it along it. Translation and rotation follow the axes
+//! of the world or of the object, scaling always those of the object.
+use crate::{gizmos::Gizmos, picking::Ray, scene::Object};
+use glam::{Mat4, Quat, Vec2, Vec3};
+use imgui::{im_str, AngleSlider, Drag, Slider, Ui, Window};
+
+/// Distance in pixels from the lines of a handle that grabs it.
+const GRAB_RADIUS: f32 = 8.0;
+
+/// Length of the handles, relative to their distance from the camera.
+const HANDLE_SIZE: f32 = 0.15;
+
+/// Segments of the circles the rotation handles are grabbed by.
+const CIRCLE_SEGMENTS: usize = 32;
+
+const AXIS_COLORS: [[f32; 4]; 3] = [
+    [1.0, 0.2, 0.2, 1.0],
+    [0.2, 1.0, 0.2, 1.0],
+    [0.2, 0.3, 1.0, 1.0],
+];
+
+const GRABBED_COLOR: [f32; 4] = [1.0, 1.0, 0.2, 1.0];
+
+pub const GIZMO_MODES: [GizmoMode; 3] = [GizmoMode::Translate, GizmoMode::Rotate, GizmoMode::Scale];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            GizmoMode::Translate => "Translate",
+            GizmoMode::Rotate => "Rotate",
+            GizmoMode::Scale => "Scale",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GizmoSpace {
+    World,
+    Local,
+}
+
+/// Handle being dragged, and the transform of the object when it was grabbed.
+struct Grab {
+    axis: usize,
+    direction: Vec3,
+    /// Normal of the plane the cursor is followed on, through the object.
+    normal: Vec3,
+    /// Where the cursor crossed that plane.
+    start: Vec3,
+    /// Length of the handle.
+    size: f32,
+    position: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+}
+
+pub struct TransformGizmo {
+    pub mode: GizmoMode,
+    pub space: GizmoSpace,
+    pub snap: bool,
+    /// Steps of translation, rotation (in radians) and scale when snapping.
+    pub translate_step: f32,
+    pub rotate_step: f32,
+    pub scale_step: f32,
+    grab: Option<Grab>,
+}
+
+impl Default for TransformGizmo {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            space: GizmoSpace::World,
+            snap: false,
+            translate_step: 0.25,
+            rotate_step: 15f32.to_radians(),
+            scale_step: 0.1,
+            grab: None,
+        }
+    }
+}
+
+impl TransformGizmo {
+    /// Directions of the handles of `object`.
+    fn axes(&self, object: &Object) -> [Vec3; 3] {
+        let local = self.mode == GizmoMode::Scale || self.space == GizmoSpace::Local;
+        let rotation = if local {
+            object.rotation
+        } else {
+            Quat::identity()
+        };
+        [
+            rotation * Vec3::unit_x(),
+            rotation * Vec3::unit_y(),
+            rotation * Vec3::unit_z(),
+        ]
+    }
+
+    /// Starts dragging the handle of `object` under `(x, y)`, of a `width` by
+    /// `height` viewport. Returns whether there was one.
+    pub fn grab(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        view_projection: Mat4,
+        object: &Object,
+    ) -> bool {
+        let size = match handle_size(view_projection, object.position) {
+            Some(size) => size,
+            None => return false,
+        };
+        let to_screen = |point: Vec3| {
+            let ndc = view_projection.transform_point3(point);
+            Vec2::new((ndc.x + 1.0) * 0.5 * width, (1.0 - ndc.y) * 0.5 * height)
+        };
+        let cursor = Vec2::new(x, y);
+        let axes = self.axes(object);
+        let closest = axes
+            .iter()
+            .enumerate()
+            .map(|(i, &axis)| {
+                let points: Vec<Vec2> = self
+                    .handle_points(object.position, axis, size)
+                    .into_iter()
+                    .map(to_screen)
+                    .collect();
+                let distance = points
+                    .windows(2)
+                    .map(|segment| segment_distance(cursor, segment[0], segment[1]))
+                    .fold(f32::MAX, f32::min);
+                (i, distance)
+            })
+            .filter(|&(_, distance)| distance < GRAB_RADIUS)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        let axis = match closest {
+            Some((axis, _)) => axis,
+            None => return false,
+        };
+
+        let ray = Ray::from_screen(x, y, width, height, view_projection);
+        let direction = axes[axis];
+        let normal = match self.mode {
+            GizmoMode::Rotate => direction,
+            // the plane along the axis facing the camera the most
+            _ => direction.cross(ray.direction).cross(direction),
+        };
+        if normal.length_squared() < f32::EPSILON {
+            // looking down the axis
+            return false;
+        }
+        let normal = normal.normalize();
+        let start = match intersect_plane(&ray, object.position, normal) {
+            Some(start) => start,
+            None => return false,
+        };
+        self.grab = Some(Grab {
+            axis,
+            direction,
+            normal,
+            start,
+            size,
+            position: object.position,
+            rotation: object.rotation,
+            scale: object.scale,
+        });
+        true
+    }
+
+    /// Drags the grabbed handle to `(x, y)`, writing the transform into
+    /// `object`.
+    pub fn drag(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        view_projection: Mat4,
+        object: &mut Object,
+    ) {
+        let grab = match &self.grab {
+            Some(grab) => grab,
+            None => return,
+        };
+        let ray = Ray::from_screen(x, y, width, height, view_projection);
+        let hit = match intersect_plane(&ray, grab.position, grab.normal) {
+            Some(hit) => hit,
+            None => return,
+        };
+        let snap = |value: f32, step: f32| {
+            if self.snap && step > 0.0 {
+                (value / step).round() * step
+            } else {
+                value
+            }
+        };
+        match self.mode {
+            GizmoMode::Translate => {
+                let offset = snap((hit - grab.start).dot(grab.direction), self.translate_step);
+                object.position = grab.position + grab.direction * offset;
+            }
+            GizmoMode::Rotate => {
+                let from = grab.start - grab.position;
+                let to = hit - grab.position;
+                let angle = grab.direction.dot(from.cross(to)).atan2(from.dot(to));
+                let angle = snap(angle, self.rotate_step);
+                object.rotation = Quat::from_axis_angle(grab.direction, angle) * grab.rotation;
+            }
+            GizmoMode::Scale => {
+                // dragging by the length of the handle doubles the scale
+                let offset = (hit - grab.start).dot(grab.direction) / grab.size;
+                let mut scale: [f32; 3] = grab.scale.into();
+                scale[grab.axis] = snap(scale[grab.axis] * (1.0 + offset), self.scale_step)
+                    .max(self.scale_step.max(0.01));
+                object.scale = scale.into();
+            }
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.grab = None;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.grab.is_some()
+    }
+
+    /// Queues the handles of `object`.
+    pub fn gizmo(&self, gizmos: &mut Gizmos, view_projection: Mat4, object: &Object) {
+        let size = match handle_size(view_projection, object.position) {
+            Some(size) => size,
+            None => return,
+        };
+        // the handles stay where they were grabbed while rotating
+        let axes = match (&self.grab, self.mode) {
+            (Some(grab), GizmoMode::Rotate) => {
+                let mut axes = self.axes(object);
+                axes[grab.axis] = grab.direction;
+                axes
+            }
+            _ => self.axes(object),
+        };
+        for (i, &axis) in axes.iter().enumerate() {
+            let color = match &self.grab {
+                Some(grab) if grab.axis == i => GRABBED_COLOR,
+                _ => AXIS_COLORS[i],
+            };
+            let end = object.position + axis * size;
+            match self.mode {
+                GizmoMode::Translate => {
+                    gizmos.line(object.position, end, color);
+                    gizmos.circle(end, axis, size * 0.05, color);
+                }
+                GizmoMode::Rotate => gizmos.circle(object.position, axis, size, color),
+                GizmoMode::Scale => {
+                    gizmos.line(object.position, end, color);
+                    let cube = Mat4::from_rotation_translation(object.rotation, end);
+                    gizmos.cuboid(cube, Vec3::splat(size * 0.04), color);
+                }
+            }
+        }
+    }
+
+    /// Points along the lines of the handle of `axis`.
+    fn handle_points(&self, position: Vec3, axis: Vec3, size: f32) -> Vec<Vec3> {
+        match self.mode {
+            GizmoMode::Rotate => {
+                let other = if axis.y.abs() < 0.99 {
+                    Vec3::unit_y()
+                } else {
+                    Vec3::unit_x()
+                };
+                let u = axis.cross(other).normalize() * size;
+                let v = axis.cross(u);
+                (0..=CIRCLE_SEGMENTS)
+                    .map(|i| {
+                        let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+                        position + u * angle.cos() + v * angle.sin()
+                    })
+                    .collect()
+            }
+            _ => vec![position, position + axis * size],
+        }
+    }
+
+    /// Window of the settings of the gizmo, and of the transform of the
+    /// `selected` object.
+    pub fn ui(&mut self, ui: &Ui, selected: Option<&mut Object>) {
+        Window::new(im_str!("Transform"))
+            .always_auto_resize(true)
+            .build(ui, || {
+                for &mode in GIZMO_MODES.iter() {
+                    ui.radio_button(&im_str!("{}", mode.name()), &mut self.mode, mode);
+                    ui.same_line(0.0);
+                }
+                ui.new_line();
+                ui.radio_button(im_str!("World"), &mut self.space, GizmoSpace::World);
+                ui.same_line(0.0);
+                ui.radio_button(im_str!("Local"), &mut self.space, GizmoSpace::Local);
+                if self.mode == GizmoMode::Scale {
+                    ui.same_line(0.0);
+                    ui.text_disabled("(scales along the object axes)");
+                }
+                ui.checkbox(im_str!("Snap"), &mut self.snap);
+                if self.snap {
+                    Slider::new(im_str!("Translation step"))
+                        .range(0.05..=5.0)
+                        .build(ui, &mut self.translate_step);
+                    AngleSlider::new(im_str!("Rotation step"))
+                        .range_degrees(1.0..=90.0)
+                        .build(ui, &mut self.rotate_step);
+                    Slider::new(im_str!("Scale step"))
+                        .range(0.01..=1.0)
+                        .build(ui, &mut self.scale_step);
+                }
+                ui.separator();
+                match selected {
+                    Some(object) => {
+                        ui.text(&object.name);
+                        let mut position: [f32; 3] = object.position.into();
+                        if Drag::new(im_str!("Position"))
+                            .speed(0.05)
+                            .build_array(ui, &mut position)
+                        {
+                            object.position = position.into();
+                        }
+                        let mut scale: [f32; 3] = object.scale.into();
+                        if Drag::new(im_str!("Scale"))
+                            .speed(0.01)
+                            .range(0.01..=100.0)
+                            .build_array(ui, &mut scale)
+                        {
+                            object.scale = scale.into();
+                        }
+                        if ui.button(im_str!("Reset rotation"), [0.0, 0.0]) {
+                            object.rotation = Quat::identity();
+                        }
+                    }
+                    None => ui.text_disabled("Click an object to select it"),
+                }
+            });
+    }
+}
+
+/// Length of handles at `position`, or `None` behind the camera.
+fn handle_size(view_projection: Mat4, position: Vec3) -> Option<f32> {
+    // the distance along the view direction, with a perspective projection
+    let w = (view_projection * position.extend(1.0)).w;
+    if w > 0.0 {
+        Some(w * HANDLE_SIZE)
+    } else {
+        None
+    }
+}
+
+fn intersect_plane(ray: &Ray, point: Vec3, normal: Vec3) -> Option<Vec3> {
+    let facing = ray.direction.dot(normal);
+    if facing.abs() < f32::EPSILON {
+        return None;
+    }
+    let distance = (point - ray.origin).dot(normal) / facing;
+    Some(ray.origin + ray.direction * distance)
+}
+
+fn segment_distance(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = if ab.length_squared() > 0.0 {
+        ((point - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a + ab * t - point).length()
+}
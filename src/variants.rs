@@ -0,0 +1,91 @@
+//! Permutations of a shader, compiled on demand.
+//!
+//! Each variant sets preprocessor defines of the source, so toggled off code
+//! is left out of the shader instead of branched over. Variants are compiled
+//! once, and whatever is built from their module (usually pipelines) is kept
+//! for the next time the same defines are asked for with the same key, like
+//! the pipeline state it was built for.
+use crate::shader_compiler;
+use log::{error, info};
+use std::{collections::HashMap, hash::Hash};
+use wgpu::{util::make_spirv, Device, ShaderModule};
+
+/// Values of the defines of a variant, in the order they are given.
+pub type VariantKey = Vec<(&'static str, i32)>;
+
+pub struct ShaderVariants<K, T> {
+    name: &'static str,
+    source: &'static str,
+    /// `None` for variants that failed to compile, so they aren't compiled
+    /// again every frame.
+    modules: HashMap<VariantKey, Option<ShaderModule>>,
+    built: HashMap<(VariantKey, K), T>,
+}
+
+impl<K: Clone + Eq + Hash, T> ShaderVariants<K, T> {
+    /// Variants of the GLSL `source`, named as a file with the extension of
+    /// its stage.
+    pub fn new(name: &'static str, source: &'static str) -> Self {
+        Self {
+            name,
+            source,
+            modules: HashMap::new(),
+            built: HashMap::new(),
+        }
+    }
+
+    /// Number of variants compiled successfully.
+    pub fn len(&self) -> usize {
+        self.modules.values().filter(|v| v.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Builds what `build` makes of the variant with `defines` for `key`,
+    /// compiling the variant first if it's the first time it's asked for.
+    /// Returns whether the variant compiled; if it didn't the caller keeps
+    /// using the shader built with the app.
+    pub fn build<F>(
+        &mut self,
+        device: &Device,
+        defines: &[(&'static str, i32)],
+        key: &K,
+        build: F,
+    ) -> bool
+    where
+        F: FnOnce(&ShaderModule, &K) -> T,
+    {
+        let (name, source) = (self.name, self.source);
+        let module = self.modules.entry(defines.to_vec()).or_insert_with(|| {
+            match shader_compiler::compile_with_defines(name, source, defines) {
+                Ok(spirv) => {
+                    info!("Compiled variant of {} with {:?}", name, defines);
+                    Some(device.create_shader_module(make_spirv(&spirv)))
+                }
+                Err(err) => {
+                    error!(
+                        "Error compiling variant of {} with {:?}, keeping the built-in shader: {}",
+                        name, defines, err
+                    );
+                    None
+                }
+            }
+        });
+        match module {
+            Some(module) => {
+                self.built
+                    .entry((defines.to_vec(), key.clone()))
+                    .or_insert_with(|| build(module, key));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// What was built of the variant with `defines` for `key`.
+    pub fn get(&self, defines: &[(&'static str, i32)], key: &K) -> Option<&T> {
+        self.built.get(&(defines.to_vec(), key.clone()))
+    }
+}
@@ -0,0 +1,139 @@
+//! Vertex buffer layouts derived from the structs in the buffers.
+//!
+//! Structs declared with `vertex_struct!` have an attribute per field, so
+//! strides and offsets can't go out of sync with the fields.
+use wgpu::{InputStepMode, VertexAttributeDescriptor, VertexBufferDescriptor, VertexFormat};
+
+/// Type of a field that is read as a vertex attribute.
+pub trait VertexAttribute {
+    const FORMAT: VertexFormat;
+}
+
+macro_rules! impl_attribute {
+    ($($ty:ty => $format:ident,)*) => {
+        $(impl VertexAttribute for $ty {
+            const FORMAT: VertexFormat = VertexFormat::$format;
+        })*
+    };
+}
+
+impl_attribute! {
+    f32 => Float,
+    [f32; 2] => Float2,
+    [f32; 3] => Float3,
+    [f32; 4] => Float4,
+    u32 => Uint,
+    [u32; 2] => Uint2,
+    [u32; 3] => Uint3,
+    [u32; 4] => Uint4,
+}
+
+pub trait VertexLayout: Sized {
+    /// Attributes of the fields, at locations from 0 in declaration order.
+    const ATTRIBUTES: &'static [VertexAttributeDescriptor];
+
+    fn buffer_descriptor(step_mode: InputStepMode) -> VertexBufferDescriptor<'static> {
+        VertexBufferDescriptor {
+            stride: std::mem::size_of::<Self>() as _,
+            step_mode,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Declares a vertex struct along with its `VertexLayout`.
+///
+/// ```ignore
+/// vertex_struct! {
+///     #[repr(C)]
+///     #[derive(Clone, Copy, Pod, Zeroable)]
+///     struct TextVertex {
+///         position: [f32; 3],
+///         uv: [f32; 2],
+///     }
+/// }
+/// ```
+macro_rules! vertex_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident: $ty:ty,)*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $ty,)*
+        }
+
+        impl $crate::vertex::VertexLayout for $name {
+            const ATTRIBUTES: &'static [wgpu::VertexAttributeDescriptor] =
+                vertex_struct!(@attributes $name, 0, [], $($field: $ty,)*);
+        }
+    };
+    // one attribute per field, with the location counted along the way
+    (@attributes $name:ident, $location:expr, [$($done:tt)*],) => {
+        &[$($done)*]
+    };
+    (
+        @attributes $name:ident,
+        $location:expr,
+        [$($done:tt)*],
+        $field:ident: $ty:ty,
+        $($rest:tt)*
+    ) => {
+        vertex_struct!(
+            @attributes $name,
+            $location + 1,
+            [
+                $($done)*
+                wgpu::VertexAttributeDescriptor {
+                    offset: std::mem::offset_of!($name, $field) as _,
+                    format: <$ty as $crate::vertex::VertexAttribute>::FORMAT,
+                    shader_location: $location,
+                },
+            ],
+            $($rest)*
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VertexLayout;
+    use bytemuck::{Pod, Zeroable};
+    use wgpu::{InputStepMode, VertexFormat};
+
+    vertex_struct! {
+        #[repr(C)]
+        #[derive(Clone, Copy, Pod, Zeroable)]
+        struct Mixed {
+            weight: f32,
+            position: [f32; 3],
+            joints: [u32; 4],
+            uv: [f32; 2],
+            id: u32,
+        }
+    }
+
+    #[test]
+    fn attributes_follow_the_fields() {
+        let descriptor = Mixed::buffer_descriptor(InputStepMode::Instance);
+        assert_eq!(descriptor.stride, 4 + 12 + 16 + 8 + 4);
+        assert_eq!(descriptor.step_mode, InputStepMode::Instance);
+        let attributes: Vec<_> = descriptor
+            .attributes
+            .iter()
+            .map(|a| (a.shader_location, a.offset, a.format))
+            .collect();
+        assert_eq!(
+            attributes,
+            [
+                (0, 0, VertexFormat::Float),
+                (1, 4, VertexFormat::Float3),
+                (2, 16, VertexFormat::Uint4),
+                (3, 32, VertexFormat::Float2),
+                (4, 40, VertexFormat::Uint),
+            ]
+        );
+    }
+}
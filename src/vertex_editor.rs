@@ -0,0 +1,201 @@
+//! Editing of the vertices of a mesh, from a panel listing them and by
+//! dragging their handles in the viewport.
+//!
+//! Edits are made to the CPU copy of the mesh, so picking sees them, and
+//! they are written over its vertex buffer once per frame. Handles are
+//! dragged on the plane facing the cursor ray they were grabbed with, and
+//! every object drawing the mesh has its own, moving the same vertices.
+use crate::{
+    gizmos::Gizmos,
+    mesh::{Mesh, MeshData},
+    picking::Ray,
+    scene::Scene,
+};
+use glam::{Mat4, Vec3};
+use imgui::{im_str, ColorEdit, Drag, Ui, Window};
+use wgpu::Queue;
+
+/// Distance in pixels from a handle at which it can be grabbed.
+const GRAB_RADIUS: f32 = 10.0;
+
+/// Radius of the handles, per unit of distance from the camera.
+const HANDLE_SIZE: f32 = 0.015;
+
+/// Vertex of an object being dragged.
+struct Grab {
+    object: usize,
+    vertex: usize,
+    /// Normal of the plane it's dragged on.
+    normal: Vec3,
+}
+
+pub struct VertexEditor {
+    /// Index of the edited mesh.
+    mesh: usize,
+    pub show_handles: bool,
+    grab: Option<Grab>,
+    /// Whether the vertices changed since they were last uploaded.
+    dirty: bool,
+}
+
+impl VertexEditor {
+    pub fn new(mesh: usize) -> Self {
+        Self {
+            mesh,
+            show_handles: false,
+            grab: None,
+            dirty: false,
+        }
+    }
+
+    /// Starts dragging the handle under `(x, y)`, of a `width` by `height`
+    /// viewport. Returns whether there was one.
+    #[allow(clippy::too_many_arguments)]
+    pub fn grab(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        view_projection: Mat4,
+        scene: &Scene,
+        meshes: &[MeshData],
+    ) -> bool {
+        if !self.show_handles {
+            return false;
+        }
+        let mut closest = None;
+        for (object, model) in self.models(scene) {
+            for (vertex, v) in meshes[self.mesh].vertices.iter().enumerate() {
+                let point = model.transform_point3(Vec3::from(v.position));
+                let ndc = view_projection.transform_point3(point);
+                if !(0.0..=1.0).contains(&ndc.z) {
+                    continue;
+                }
+                let screen_x = (ndc.x + 1.0) * 0.5 * width;
+                let screen_y = (1.0 - ndc.y) * 0.5 * height;
+                let distance = (screen_x - x).hypot(screen_y - y);
+                if distance < closest.map_or(GRAB_RADIUS, |(d, _, _)| d) {
+                    closest = Some((distance, object, vertex));
+                }
+            }
+        }
+        let ray = Ray::from_screen(x, y, width, height, view_projection);
+        self.grab = closest.map(|(_, object, vertex)| Grab {
+            object,
+            vertex,
+            normal: ray.direction,
+        });
+        self.grab.is_some()
+    }
+
+    /// Moves the dragged vertex under `(x, y)`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn drag(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        view_projection: Mat4,
+        scene: &Scene,
+        meshes: &mut [MeshData],
+    ) {
+        let grab = match &self.grab {
+            Some(grab) => grab,
+            None => return,
+        };
+        let model = scene.objects[grab.object].model();
+        // pulsing objects can be scaled down to nothing
+        if model.determinant().abs() < f32::EPSILON {
+            return;
+        }
+        let vertex = &mut meshes[self.mesh].vertices[grab.vertex];
+        let point = model.transform_point3(Vec3::from(vertex.position));
+        let ray = Ray::from_screen(x, y, width, height, view_projection);
+        let facing = ray.direction.dot(grab.normal);
+        if facing.abs() < f32::EPSILON {
+            return;
+        }
+        let distance = (point - ray.origin).dot(grab.normal) / facing;
+        let point = ray.origin + ray.direction * distance;
+        vertex.position = model.inverse().transform_point3(point).into();
+        self.dirty = true;
+    }
+
+    pub fn release(&mut self) {
+        self.grab = None;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.grab.is_some()
+    }
+
+    /// Queues a circle around every vertex of every object drawing the mesh,
+    /// in the color of the vertex, facing the camera at `eye`.
+    pub fn draw_handles(&self, gizmos: &mut Gizmos, scene: &Scene, meshes: &[MeshData], eye: Vec3) {
+        if !self.show_handles {
+            return;
+        }
+        for (object, model) in self.models(scene) {
+            for (vertex, v) in meshes[self.mesh].vertices.iter().enumerate() {
+                let point = model.transform_point3(Vec3::from(v.position));
+                let to_eye = eye - point;
+                let mut radius = to_eye.length() * HANDLE_SIZE;
+                let grabbed = self
+                    .grab
+                    .as_ref()
+                    .is_some_and(|g| (g.object, g.vertex) == (object, vertex));
+                if grabbed {
+                    radius *= 1.5;
+                }
+                let [r, g, b] = v.color;
+                gizmos.circle(point, to_eye, radius, [r, g, b, 1.0]);
+            }
+        }
+    }
+
+    /// Window listing the vertices, with their position, texture coordinates
+    /// and color.
+    pub fn ui(&mut self, ui: &Ui, meshes: &mut [MeshData]) {
+        let mesh = &mut meshes[self.mesh];
+        let mut dirty = false;
+        Window::new(im_str!("Vertices"))
+            .always_auto_resize(true)
+            .build(ui, || {
+                ui.checkbox(im_str!("Show handles"), &mut self.show_handles);
+                for (i, vertex) in mesh.vertices.iter_mut().enumerate() {
+                    ui.separator();
+                    ui.text(format!("Vertex {}", i));
+                    dirty |= Drag::new(&im_str!("Position##{}", i))
+                        .speed(0.01)
+                        .build_array(ui, &mut vertex.position);
+                    dirty |= Drag::new(&im_str!("UV##{}", i))
+                        .speed(0.01)
+                        .build_array(ui, &mut vertex.uv);
+                    dirty |= ColorEdit::new(&im_str!("Color##{}", i), &mut vertex.color).build(ui);
+                }
+            });
+        self.dirty |= dirty;
+    }
+
+    /// Writes the vertices over the buffer of `gpu_meshes`, if they changed.
+    pub fn upload(&mut self, queue: &Queue, meshes: &mut [MeshData], gpu_meshes: &[Mesh]) {
+        if !self.dirty {
+            return;
+        }
+        let mesh = &mut meshes[self.mesh];
+        // tangents follow the positions and texture coordinates
+        mesh.compute_tangents();
+        gpu_meshes[self.mesh].write_vertices(queue, mesh);
+        self.dirty = false;
+    }
+
+    /// Index and model of every object drawing the mesh.
+    fn models<'a>(&'a self, scene: &'a Scene) -> impl Iterator<Item = (usize, Mat4)> + 'a {
+        let objects = scene.objects.iter().enumerate();
+        objects
+            .filter(move |(_, object)| object.mesh == self.mesh)
+            .map(|(i, object)| (i, object.model()))
+    }
+}
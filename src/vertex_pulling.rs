@@ -0,0 +1,345 @@
+//! Meshes drawn with their vertices read from a storage buffer.
+//!
+//! The pulled pipeline has no vertex buffers: `pulling_storage.vert` reads the
+//! vertices of the mesh by `gl_VertexIndex`, which indexed draws set to the
+//! index read from the index buffer. The classic pipeline fetches the same
+//! vertices through a vertex buffer, and both draw a grid of spinning spheres,
+//! to compare them. A shader fetching its own vertices can read them from any
+//! buffer a compute pass wrote, which is what drawing geometry generated or
+//! compacted on the GPU needs.
+use crate::{
+    draw_stats::{self, CountedPass},
+    memory::{self, Category, Tracked},
+    mesh::{Mesh, MeshData, Vertex},
+    scene_pipeline::DEPTH_FORMAT,
+    shaders,
+    vertex::VertexLayout,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use imgui::{im_str, Slider, Ui};
+use std::f32::consts::PI;
+use wgpu::{
+    util::make_spirv, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferDescriptor, BufferSize, BufferUsage, ColorStateDescriptor, ColorWrite,
+    CompareFunction, CullMode, DepthStencilStateDescriptor, Device, FrontFace, InputStepMode,
+    PipelineLayout, PipelineLayoutDescriptor, PrimitiveTopology, ProgrammableStageDescriptor,
+    Queue, RasterizationStateDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderModule,
+    ShaderStage, StencilStateDescriptor, TextureFormat, VertexBufferDescriptor,
+    VertexStateDescriptor,
+};
+
+pub const MAX_INSTANCES: u32 = 4096;
+
+const RADIUS: f32 = 0.25;
+
+/// Distance between the centers of two spheres.
+const SPACING: f32 = 0.6;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct PullingUniforms {
+    view_projection: [[f32; 4]; 4],
+    time: f32,
+    columns: u32,
+    spacing: f32,
+    _pad: f32,
+}
+
+/// Parameters of the demo, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct PullingParams {
+    /// Reads the vertices from the storage buffer instead of a vertex buffer.
+    pub pulled: bool,
+    /// Number of spheres, up to `MAX_INSTANCES`.
+    pub instances: u32,
+    /// Segments around the spheres, which have half as many rings.
+    pub segments: u32,
+}
+
+impl Default for PullingParams {
+    fn default() -> Self {
+        Self {
+            pulled: true,
+            instances: 256,
+            segments: 64,
+        }
+    }
+}
+
+impl PullingParams {
+    pub fn ui(&mut self, ui: &Ui) {
+        ui.radio_button(im_str!("Storage buffer"), &mut self.pulled, true);
+        ui.same_line(0.0);
+        ui.radio_button(im_str!("Vertex buffer"), &mut self.pulled, false);
+        Slider::new(im_str!("Spheres"))
+            .range(1..=MAX_INSTANCES)
+            .build(ui, &mut self.instances);
+        Slider::new(im_str!("Segments"))
+            .range(8..=256)
+            .build(ui, &mut self.segments);
+    }
+}
+
+pub struct VertexPulling {
+    /// Segments the sphere was built with.
+    segments: u32,
+    sphere: Mesh,
+    vertex_count: usize,
+    uniform: Tracked<Buffer>,
+    bind_group: BindGroup,
+    vertices_layout: BindGroupLayout,
+    /// The vertices of the sphere as a storage buffer.
+    vertices_bind_group: BindGroup,
+    classic: RenderPipeline,
+    pulled: RenderPipeline,
+}
+
+impl VertexPulling {
+    /// Creates the pipelines for passes into `format`, with a depth
+    /// attachment of `DEPTH_FORMAT`.
+    pub fn new(device: &Device, format: TextureFormat, params: &PullingParams) -> Self {
+        let classic_module = device.create_shader_module(make_spirv(shaders::PULLING_VERT));
+        let pulled_module = device.create_shader_module(make_spirv(shaders::PULLING_STORAGE_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::PULLING_FRAG));
+
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Vertex pulling uniforms"),
+                size: std::mem::size_of::<PullingUniforms>() as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Vertex pulling bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::VERTEX,
+                ty: BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: BufferSize::new(std::mem::size_of::<PullingUniforms>() as _),
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Vertex pulling bind group"),
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(uniform.slice(..)),
+            }],
+        });
+        let vertices_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Pulled vertices layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStage::VERTEX,
+                ty: BindingType::StorageBuffer {
+                    dynamic: false,
+                    min_binding_size: BufferSize::new(std::mem::size_of::<Vertex>() as _),
+                    readonly: true,
+                },
+                count: None,
+            }],
+        });
+
+        let classic_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Vertex buffer pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pulled_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Vertex pulling pipeline layout"),
+            bind_group_layouts: &[&layout, &vertices_layout],
+            push_constant_ranges: &[],
+        });
+        let classic = create_pipeline(
+            device,
+            "Vertex buffer pipeline",
+            &classic_layout,
+            &classic_module,
+            &frag_module,
+            format,
+            &[Vertex::buffer_descriptor(InputStepMode::Vertex)],
+        );
+        let pulled = create_pipeline(
+            device,
+            "Vertex pulling pipeline",
+            &pulled_layout,
+            &pulled_module,
+            &frag_module,
+            format,
+            &[],
+        );
+
+        let data = sphere(params.segments);
+        let sphere = Mesh::new(device, "Pulled sphere", &data);
+        let vertices_bind_group = vertices_bind_group(device, &vertices_layout, &sphere);
+        Self {
+            segments: params.segments,
+            sphere,
+            vertex_count: data.vertices.len(),
+            uniform,
+            bind_group,
+            vertices_layout,
+            vertices_bind_group,
+            classic,
+            pulled,
+        }
+    }
+
+    /// Vertices of the sphere, fetched once per index drawn.
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.sphere.index_count()
+    }
+
+    /// Builds the sphere again if the segments of `params` changed, and
+    /// uploads the camera and the time the spheres spin with.
+    pub fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        params: &PullingParams,
+        view_projection: Mat4,
+        time: f32,
+    ) {
+        if params.segments != self.segments {
+            let data = sphere(params.segments);
+            self.sphere = Mesh::new(device, "Pulled sphere", &data);
+            self.vertex_count = data.vertices.len();
+            self.vertices_bind_group =
+                vertices_bind_group(device, &self.vertices_layout, &self.sphere);
+            self.segments = params.segments;
+        }
+        let uniforms = PullingUniforms {
+            view_projection: view_projection.to_cols_array_2d(),
+            time,
+            columns: (params.instances as f32).sqrt().ceil().max(1.0) as u32,
+            spacing: SPACING,
+            _pad: 0.0,
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Draws the spheres the way `params` picks.
+    pub fn draw<'a>(&'a self, pass: &mut CountedPass<'a>, params: &PullingParams) {
+        let instances = 0..params.instances.min(MAX_INSTANCES);
+        if params.pulled {
+            pass.set_pipeline(&self.pulled);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.set_bind_group(1, &self.vertices_bind_group, &[]);
+            self.sphere.draw_pulled(pass, instances);
+        } else {
+            pass.set_pipeline(&self.classic);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            self.sphere.draw(pass, instances);
+        }
+    }
+}
+
+fn vertices_bind_group(device: &Device, layout: &BindGroupLayout, mesh: &Mesh) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Pulled vertices bind group"),
+        layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: BindingResource::Buffer(mesh.vertex_buffer().slice(..)),
+        }],
+    })
+}
+
+fn create_pipeline(
+    device: &Device,
+    label: &str,
+    layout: &PipelineLayout,
+    vert_module: &ShaderModule,
+    frag_module: &ShaderModule,
+    format: TextureFormat,
+    vertex_buffers: &[VertexBufferDescriptor],
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex_stage: ProgrammableStageDescriptor {
+            module: vert_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(ProgrammableStageDescriptor {
+            module: frag_module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(RasterizationStateDescriptor {
+            front_face: FrontFace::Ccw,
+            cull_mode: CullMode::Back,
+            ..Default::default()
+        }),
+        primitive_topology: PrimitiveTopology::TriangleList,
+        color_states: &[ColorStateDescriptor {
+            format,
+            alpha_blend: BlendDescriptor::REPLACE,
+            color_blend: BlendDescriptor::REPLACE,
+            write_mask: ColorWrite::ALL,
+        }],
+        depth_stencil_state: Some(DepthStencilStateDescriptor {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::Less,
+            stencil: StencilStateDescriptor::default(),
+        }),
+        vertex_state: VertexStateDescriptor {
+            index_format: crate::mesh::INDEX_FORMAT,
+            vertex_buffers,
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
+
+/// UV sphere of `segments` around and half as many rings, with the seam and
+/// the poles duplicated so every vertex has its own texture coordinates.
+fn sphere(segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let rings = (segments / 2).max(2);
+    let mut mesh = MeshData::default();
+    for ring in 0..=rings {
+        let v = ring as f32 / rings as f32;
+        let theta = v * PI;
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            let phi = u * 2.0 * PI;
+            let normal = [
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            ];
+            mesh.vertices.push(Vertex {
+                position: [normal[0] * RADIUS, normal[1] * RADIUS, normal[2] * RADIUS],
+                normal,
+                uv: [u, v],
+                color: [0.4 + 0.5 * u, 0.5, 0.9 - 0.5 * v],
+                ..Default::default()
+            });
+        }
+    }
+    // counter-clockwise seen from outside
+    let row = segments + 1;
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let a = ring * row + segment;
+            let below = a + row;
+            mesh.indices
+                .extend_from_slice(&[a, a + 1, below, a + 1, below + 1, below]);
+        }
+    }
+    mesh
+}
@@ -0,0 +1,236 @@
+//! Cameras drawn side by side, or one inset in the other, in the same frame.
+//!
+//! The main camera is drawn into its rectangle first and goes through every
+//! effect. Rather than with a viewport, its projection is scaled and offset
+//! to put its image in the rectangle, so effects reading the depth buffer
+//! over the whole frame (and picking) still find the scene where it is. The
+//! second camera is drawn after the effects, with a viewport and a scissor
+//! rectangle of its own, and is only tone mapped with the rest of the frame.
+use glam::{Mat4, Vec3};
+use imgui::{im_str, ComboBox, ImString, Slider, SliderFlags, Ui};
+
+/// Height above the followed point of the orthographic cameras.
+const ORTHOGRAPHIC_HEIGHT: f32 = 50.0;
+
+/// Layouts selectable from the UI.
+pub const VIEWPORT_LAYOUTS: [ViewportLayout; 3] = [
+    ViewportLayout::Single,
+    ViewportLayout::SplitScreen,
+    ViewportLayout::Minimap,
+];
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ViewportLayout {
+    #[default]
+    Single,
+    /// The main camera on the left half, the second one on the right half.
+    SplitScreen,
+    /// The second camera in a square at the top right of the main one.
+    Minimap,
+}
+
+impl ViewportLayout {
+    pub fn name(self) -> &'static str {
+        match self {
+            ViewportLayout::Single => "Single",
+            ViewportLayout::SplitScreen => "Split screen",
+            ViewportLayout::Minimap => "Minimap",
+        }
+    }
+
+    /// Rectangles of the main camera and of the second one, if any, in a
+    /// `width`x`height` frame.
+    pub fn rects(self, width: u32, height: u32) -> (Rect, Option<Rect>) {
+        let full = Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        match self {
+            ViewportLayout::Single => (full, None),
+            ViewportLayout::SplitScreen => {
+                let half = width / 2;
+                let left = Rect {
+                    width: half,
+                    ..full
+                };
+                let right = Rect {
+                    x: half,
+                    width: width - half,
+                    ..full
+                };
+                (left, Some(right))
+            }
+            ViewportLayout::Minimap => {
+                let size = height / 3;
+                let margin = height / 40;
+                let inset = Rect {
+                    x: width.saturating_sub(size + margin),
+                    y: margin,
+                    width: size,
+                    height: size,
+                };
+                (full, Some(inset))
+            }
+        }
+    }
+}
+
+/// Rectangle of a frame, in pixels from its top left corner.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
+    }
+
+    /// `projection` drawing into the whole `width`x`height` frame, with
+    /// what it projects moved into the rectangle.
+    pub fn fit(&self, projection: Mat4, width: u32, height: u32) -> Mat4 {
+        let (width, height) = (width as f32, height as f32);
+        let scale = Vec3::new(self.width as f32 / width, self.height as f32 / height, 1.0);
+        // ndc go up, pixels go down
+        let center = Vec3::new(
+            (self.x as f32 + self.width as f32 * 0.5) / width * 2.0 - 1.0,
+            1.0 - (self.y as f32 + self.height as f32 * 0.5) / height * 2.0,
+            0.0,
+        );
+        Mat4::from_translation(center) * Mat4::from_scale(scale) * projection
+    }
+}
+
+/// Cameras of the second viewport selectable from the UI.
+pub const VIEWPORT_CAMERAS: [ViewportCamera; 3] = [
+    ViewportCamera::TopDown,
+    ViewportCamera::Light,
+    ViewportCamera::Overview,
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ViewportCamera {
+    /// Orthographic, looking down on the main camera with north up.
+    TopDown,
+    /// Orthographic, looking along the directional light at the main camera.
+    Light,
+    /// Perspective, looking at the origin from above and behind it.
+    Overview,
+}
+
+impl ViewportCamera {
+    pub fn name(self) -> &'static str {
+        match self {
+            ViewportCamera::TopDown => "Top down",
+            ViewportCamera::Light => "Light",
+            ViewportCamera::Overview => "Overview",
+        }
+    }
+
+    /// Position, view and projection of the camera for a viewport of
+    /// `aspect`, with the main camera at `eye`. Orthographic cameras show
+    /// `extent` units from top to bottom.
+    pub fn matrices(
+        self,
+        eye: Vec3,
+        light_direction: Vec3,
+        extent: f32,
+        aspect: f32,
+        far: f32,
+    ) -> (Vec3, Mat4, Mat4) {
+        let half = extent * 0.5;
+        let orthographic = Mat4::orthographic_rh(
+            -half * aspect,
+            half * aspect,
+            -half,
+            half,
+            0.1,
+            ORTHOGRAPHIC_HEIGHT * 2.0,
+        );
+        match self {
+            ViewportCamera::TopDown => {
+                let position = eye + Vec3::unit_y() * ORTHOGRAPHIC_HEIGHT;
+                let view = Mat4::look_at_rh(position, eye, -Vec3::unit_z());
+                (position, view, orthographic)
+            }
+            ViewportCamera::Light => {
+                let direction = light_direction.normalize();
+                let up = if direction.y.abs() > 0.99 {
+                    -Vec3::unit_z()
+                } else {
+                    Vec3::unit_y()
+                };
+                let position = eye - direction * ORTHOGRAPHIC_HEIGHT;
+                (position, Mat4::look_at_rh(position, eye, up), orthographic)
+            }
+            ViewportCamera::Overview => {
+                let position = Vec3::new(0.0, 12.0, 16.0);
+                let view = Mat4::look_at_rh(position, Vec3::zero(), Vec3::unit_y());
+                let projection =
+                    Mat4::perspective_rh(std::f32::consts::FRAC_PI_3, aspect, 0.1, far);
+                (position, view, projection)
+            }
+        }
+    }
+}
+
+/// Layout and second camera, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ViewportParams {
+    pub layout: ViewportLayout,
+    /// Camera of the second viewport, the first one is the main camera.
+    pub camera: ViewportCamera,
+    /// Units from top to bottom of orthographic cameras.
+    pub extent: f32,
+}
+
+impl Default for ViewportParams {
+    fn default() -> Self {
+        Self {
+            layout: ViewportLayout::Single,
+            camera: ViewportCamera::TopDown,
+            extent: 30.0,
+        }
+    }
+}
+
+impl ViewportParams {
+    pub fn ui(&mut self, ui: &Ui) {
+        let names: Vec<_> = VIEWPORT_LAYOUTS
+            .iter()
+            .map(|l| ImString::new(l.name()))
+            .collect();
+        let names: Vec<_> = names.iter().collect();
+        let mut index = VIEWPORT_LAYOUTS
+            .iter()
+            .position(|&layout| layout == self.layout)
+            .unwrap();
+        if ComboBox::new(im_str!("Layout")).build_simple_string(ui, &mut index, &names) {
+            self.layout = VIEWPORT_LAYOUTS[index];
+        }
+        if self.layout != ViewportLayout::Single {
+            let names: Vec<_> = VIEWPORT_CAMERAS
+                .iter()
+                .map(|c| ImString::new(c.name()))
+                .collect();
+            let names: Vec<_> = names.iter().collect();
+            let mut index = VIEWPORT_CAMERAS
+                .iter()
+                .position(|&camera| camera == self.camera)
+                .unwrap();
+            if ComboBox::new(im_str!("Second camera")).build_simple_string(ui, &mut index, &names) {
+                self.camera = VIEWPORT_CAMERAS[index];
+            }
+            Slider::new(im_str!("Extent"))
+                .range(5.0..=200.0)
+                .display_format(im_str!("%.0f"))
+                .flags(SliderFlags::LOGARITHMIC)
+                .build(ui, &mut self.extent);
+        }
+    }
+}
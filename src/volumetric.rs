@@ -0,0 +1,426 @@
+//! Volumetric light scattering of the directional light.
+//!
+//! The view frustum is split into froxels, a grid in screen space with
+//! slices exponentially deeper away from the camera. A first compute pass
+//! finds the light every froxel scatters towards the camera, looking up the
+//! cascades of the shadow map for the shafts of light and shadow. A second
+//! one goes through the slices front to back, adding up the scattered light
+//! and how much of what's behind is left. Every pixel of the scene is then
+//! dimmed and lit by the froxels up to its depth, the sky by all of them.
+use crate::{
+    draw_stats,
+    layout::Layout,
+    memory::{self, Category, Tracked},
+    pass::PassBuilder,
+    ping_pong::PingPong,
+    post::{self, PostFrame},
+    shaders,
+    shadow::CASCADES,
+};
+use glam::{Mat4, Vec3, Vec4};
+use imgui::{im_str, Slider, SliderFlags, Ui};
+use wgpu::{
+    util::make_spirv, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendDescriptor,
+    Buffer, BufferDescriptor, BufferSize, BufferUsage, Color, CommandEncoder, ComputePipeline,
+    ComputePipelineDescriptor, Device, Extent3d, FilterMode, LoadOp, PipelineLayoutDescriptor,
+    ProgrammableStageDescriptor, Queue, RenderPipeline, Sampler, SamplerDescriptor, ShaderModule,
+    ShaderStage, Texture, TextureComponentType, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsage, TextureView, TextureViewDescriptor, TextureViewDimension,
+};
+
+/// Must match the `local_size_x` and `local_size_y` of both compute shaders.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Froxels across, down and in depth, whatever the size of the output.
+const FROXELS: (u32, u32, u32) = (160, 90, 64);
+
+const FROXEL_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+gpu_struct! {
+    struct VolumetricUniforms {
+        inverse_view_projection: Mat4,
+        cascade_view_projections: [Mat4; CASCADES],
+        /// A `vec4` in the shader, one split per cascade.
+        cascade_splits: Vec4,
+        eye: Vec3,
+        density: f32,
+        forward: Vec3,
+        anisotropy: f32,
+        light_direction: Vec3,
+        near: f32,
+        light_color: Vec3,
+        distance: f32,
+    }
+}
+
+/// Medium the light scatters in, adjustable from the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct VolumetricParams {
+    pub enabled: bool,
+    /// Light scattered and absorbed, per unit of distance.
+    pub density: f32,
+    /// Henyey-Greenstein asymmetry: positive scatters most of the light
+    /// forward, so the shafts show best looking towards the light.
+    pub anisotropy: f32,
+    /// Scales the light scattered, without the medium getting any thicker.
+    pub intensity: f32,
+    /// Distance covered by the froxels, the last slice being that far.
+    pub distance: f32,
+}
+
+impl Default for VolumetricParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            density: 0.02,
+            anisotropy: 0.6,
+            intensity: 1.0,
+            distance: 50.0,
+        }
+    }
+}
+
+impl VolumetricParams {
+    /// Controls of the medium, covering distances up to `far_plane`.
+    pub fn ui(&mut self, ui: &Ui, far_plane: f32) {
+        ui.checkbox(im_str!("Volumetric light"), &mut self.enabled);
+        Slider::new(im_str!("Scattering density"))
+            .range(0.001..=0.2)
+            .flags(SliderFlags::LOGARITHMIC)
+            .build(ui, &mut self.density);
+        Slider::new(im_str!("Anisotropy"))
+            .range(-0.9..=0.9)
+            .build(ui, &mut self.anisotropy);
+        Slider::new(im_str!("Scattering intensity"))
+            .range(0.0..=4.0)
+            .build(ui, &mut self.intensity);
+        Slider::new(im_str!("Volume distance"))
+            .range(1.0..=far_plane)
+            .build(ui, &mut self.distance);
+    }
+}
+
+pub struct VolumetricTargets {
+    /// Bind groups of the composite, reading either HDR target.
+    composite: [BindGroup; 2],
+}
+
+pub struct Volumetric {
+    uniform: Tracked<Buffer>,
+    _scattering: (Tracked<Texture>, TextureView),
+    accumulated: (Tracked<Texture>, TextureView),
+    sampler: Sampler,
+    scatter_bind_group: BindGroup,
+    scatter_pipeline: ComputePipeline,
+    accumulate_bind_group: BindGroup,
+    accumulate_pipeline: ComputePipeline,
+    layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+}
+
+impl Volumetric {
+    /// Scatters the light shadowed by `shadow_map`, the depth array of the
+    /// cascades.
+    pub fn new(device: &Device, shadow_map: &TextureView) -> Self {
+        let scatter_module =
+            device.create_shader_module(make_spirv(shaders::VOLUMETRIC_SCATTER_COMP));
+        let accumulate_module =
+            device.create_shader_module(make_spirv(shaders::VOLUMETRIC_ACCUMULATE_COMP));
+        let vert_module = device.create_shader_module(make_spirv(shaders::FULLSCREEN_VERT));
+        let frag_module = device.create_shader_module(make_spirv(shaders::VOLUMETRIC_FRAG));
+        let uniform = memory::create_buffer(
+            device,
+            Category::Uniforms,
+            &BufferDescriptor {
+                label: Some("Volumetric uniforms"),
+                size: VolumetricUniforms::STD140.size as _,
+                usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let froxels = |label| {
+            let (width, height, depth) = FROXELS;
+            let texture = memory::create_texture(
+                device,
+                Category::RenderTargets,
+                &TextureDescriptor {
+                    label: Some(label),
+                    size: Extent3d {
+                        width,
+                        height,
+                        depth,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D3,
+                    format: FROXEL_FORMAT,
+                    usage: TextureUsage::STORAGE | TextureUsage::SAMPLED,
+                },
+            );
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            (texture, view)
+        };
+        let scattering = froxels("Volumetric scattering");
+        let accumulated = froxels("Volumetric accumulation");
+        // the shadow map is fetched and compared by hand, texel by texel
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Volumetric sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_entry = |visibility| BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: BindingType::UniformBuffer {
+                dynamic: false,
+                min_binding_size: BufferSize::new(VolumetricUniforms::STD140.size as _),
+            },
+            count: None,
+        };
+        let texture_entry = |binding, visibility, dimension| BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: BindingType::SampledTexture {
+                dimension,
+                component_type: TextureComponentType::Float,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let sampler_entry = |visibility| BindGroupLayoutEntry {
+            binding: 2,
+            visibility,
+            ty: BindingType::Sampler { comparison: false },
+            count: None,
+        };
+        let storage_entry = BindGroupLayoutEntry {
+            binding: 3,
+            visibility: ShaderStage::COMPUTE,
+            ty: BindingType::StorageTexture {
+                dimension: TextureViewDimension::D3,
+                format: FROXEL_FORMAT,
+                readonly: false,
+            },
+            count: None,
+        };
+        let compute = ShaderStage::COMPUTE;
+        let scatter_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Volumetric scatter bind group layout"),
+            entries: &[
+                uniform_entry(compute),
+                texture_entry(1, compute, TextureViewDimension::D2Array),
+                sampler_entry(compute),
+                storage_entry.clone(),
+            ],
+        });
+        let accumulate_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Volumetric accumulate bind group layout"),
+            entries: &[
+                uniform_entry(compute),
+                texture_entry(1, compute, TextureViewDimension::D3),
+                sampler_entry(compute),
+                storage_entry,
+            ],
+        });
+        let fragment = ShaderStage::FRAGMENT;
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Volumetric composite bind group layout"),
+            entries: &[
+                uniform_entry(fragment),
+                post::texture_entry(1),
+                sampler_entry(fragment),
+                // the depth and the accumulated froxels
+                post::texture_entry(3),
+                texture_entry(4, fragment, TextureViewDimension::D3),
+            ],
+        });
+
+        // the uniforms, `source`, the sampler and `output`
+        let bind_group = |layout, source, output| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Volumetric bind group"),
+                layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(uniform.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(output),
+                    },
+                ],
+            })
+        };
+        let scatter_bind_group = bind_group(&scatter_layout, shadow_map, &scattering.1);
+        let accumulate_bind_group = bind_group(&accumulate_layout, &scattering.1, &accumulated.1);
+
+        let compute_pipeline = |label, layout, module: &ShaderModule| {
+            let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                compute_stage: ProgrammableStageDescriptor {
+                    module,
+                    entry_point: "main",
+                },
+            })
+        };
+        let scatter_pipeline = compute_pipeline(
+            "Volumetric scatter pipeline",
+            &scatter_layout,
+            &scatter_module,
+        );
+        let accumulate_pipeline = compute_pipeline(
+            "Volumetric accumulate pipeline",
+            &accumulate_layout,
+            &accumulate_module,
+        );
+        let pipeline = post::fullscreen_pipeline(
+            device,
+            "Volumetric composite pipeline",
+            &[&layout],
+            &vert_module,
+            &frag_module,
+            post::HDR_FORMAT,
+            BlendDescriptor::REPLACE,
+        );
+
+        Self {
+            uniform,
+            _scattering: scattering,
+            accumulated,
+            sampler,
+            scatter_bind_group,
+            scatter_pipeline,
+            accumulate_bind_group,
+            accumulate_pipeline,
+            layout,
+            pipeline,
+        }
+    }
+
+    /// Targets for the scene drawn into `hdr`, with `depth` as the view of
+    /// its depth buffer.
+    pub fn targets(
+        &self,
+        device: &Device,
+        hdr: &PingPong,
+        depth: &TextureView,
+    ) -> VolumetricTargets {
+        let composite = hdr.directions(|source, _| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Volumetric composite bind group"),
+                layout: &self.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Buffer(self.uniform.slice(..)),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(depth),
+                    },
+                    BindGroupEntry {
+                        binding: 4,
+                        resource: BindingResource::TextureView(&self.accumulated.1),
+                    },
+                ],
+            })
+        });
+        VolumetricTargets { composite }
+    }
+
+    /// Records the scene last written to `hdr`, with the light scattered on
+    /// the way to every pixel, into the other target.
+    pub fn run(
+        &self,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        params: &VolumetricParams,
+        frame: PostFrame,
+        targets: &VolumetricTargets,
+        hdr: &PingPong,
+    ) {
+        let near = frame.near;
+        let uniforms = VolumetricUniforms {
+            inverse_view_projection: frame.view_projection.inverse(),
+            cascade_view_projections: frame.cascades.view_projections,
+            cascade_splits: frame.cascades.splits.into(),
+            eye: frame.eye,
+            density: params.density,
+            forward: frame.forward,
+            anisotropy: params.anisotropy,
+            light_direction: frame.light_direction,
+            near,
+            light_color: frame.light_color * params.intensity,
+            distance: params.distance.max(near * 2.0),
+        };
+        draw_stats::write_buffer(queue, &self.uniform, 0, &uniforms.std140_bytes());
+
+        let (width, height, depth) = FROXELS;
+        let mut pass = encoder.begin_compute_pass();
+        pass.push_debug_group("Volumetric scattering");
+        pass.set_pipeline(&self.scatter_pipeline);
+        pass.set_bind_group(0, &self.scatter_bind_group, &[]);
+        pass.dispatch(
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            depth,
+        );
+        pass.pop_debug_group();
+        drop(pass);
+        // in a pass of its own, to see every slice of the scattering
+        let mut pass = encoder.begin_compute_pass();
+        pass.push_debug_group("Volumetric accumulation");
+        pass.set_pipeline(&self.accumulate_pipeline);
+        pass.set_bind_group(0, &self.accumulate_bind_group, &[]);
+        pass.dispatch(
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+        pass.pop_debug_group();
+        drop(pass);
+
+        let mut pass = PassBuilder::new()
+            .color(
+                hdr.write(),
+                post::HDR_FORMAT,
+                LoadOp::Clear(Color::BLACK),
+                true,
+            )
+            .begin(encoder);
+        pass.push_debug_group("Volumetric composite");
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, hdr.current(&targets.composite), &[]);
+        pass.draw(0..3, 0..1);
+        pass.pop_debug_group();
+    }
+}
@@ -0,0 +1,32 @@
+//! Renders the golden frames and compares them against the reference images
+//! in `tests/golden`. The rendered and diff images of failing frames are saved
+//! next to them, frames without a reference are skipped, and `UPDATE_GOLDEN=1`
+//! saves the frames as the new references. Skipped without an adapter, like
+//! the headless test, and without the `sdl` feature, which builds the binary.
+use std::process::Command;
+use wgpu_test::adapter;
+
+#[test]
+fn golden_images() {
+    if adapter::headless_device().is_none() {
+        eprintln!("No adapter found, skipping");
+        return;
+    }
+    let exe = match option_env!("CARGO_BIN_EXE_wgpu-test") {
+        Some(exe) => exe,
+        None => {
+            eprintln!("Built without the sdl feature, skipping");
+            return;
+        }
+    };
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+    let status = Command::new(exe)
+        .args(["--compare", dir])
+        .status()
+        .expect("Error running wgpu-test");
+    assert!(
+        status.success(),
+        "Frames differ from their golden images in {}",
+        dir
+    );
+}
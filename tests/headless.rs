@@ -0,0 +1,363 @@
+//! Renders a frame offscreen without a window, and checks a few of its
+//! pixels. Unlike the golden images it only needs an adapter, so it's skipped
+//! rather than failed on machines without one.
+use futures::executor::block_on;
+use glam::{Mat4, Vec3};
+use std::num::NonZeroU32;
+use wgpu::{
+    util::{make_spirv, BufferInitDescriptor, DeviceExt},
+    BlendDescriptor, BufferDescriptor, BufferUsage, Color, ColorStateDescriptor, ColorWrite,
+    CommandEncoderDescriptor, CompareFunction, Device, Extent3d, LoadOp, Operations,
+    RenderPassColorAttachmentDescriptor, RenderPassDepthStencilAttachmentDescriptor,
+    RenderPassDescriptor, SamplerDescriptor, StencilStateDescriptor, Texture, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsage, TextureViewDescriptor, TextureViewDimension,
+};
+use wgpu_test::{
+    adapter,
+    clusters::Clusters,
+    debug_view::DebugView,
+    draw_stats::CountedPass,
+    ibl::{Equirect, Ibl},
+    layout::Layout,
+    material::{MaterialArrayBuilder, PbrMaterial, LINEAR_MATERIAL_FORMAT, MATERIAL_FORMAT},
+    mesh::Vertex,
+    readback,
+    scene_pipeline::{
+        self, Deformation, Lighting, LightingBindings, ObjectBindings, ObjectUniforms,
+        Rasterization, DEPTH_FORMAT,
+    },
+    shaders, texture,
+    vertex_pulling::{PullingParams, VertexPulling},
+};
+
+/// Size of the frame, its rows are already aligned for the readback.
+const SIZE: u32 = 64;
+
+const FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// 1x1 texture with `layers` layers, standing in for the shadow maps.
+fn placeholder(device: &Device, format: TextureFormat, layers: u32) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d {
+            width: 1,
+            height: 1,
+            depth: layers,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsage::SAMPLED,
+    })
+}
+
+#[test]
+fn render_offscreen() {
+    let (device, queue) = match adapter::headless_device() {
+        Some(device) => device,
+        None => {
+            eprintln!("No adapter found, skipping");
+            return;
+        }
+    };
+
+    // the SPIR-V of every shader is accepted
+    for (path, spirv) in shaders::ALL {
+        println!("Creating {}", path);
+        device.create_shader_module(make_spirv(spirv));
+    }
+
+    // a red triangle over the bottom left half of a blue frame, through the
+    // scene pipeline showing the albedo
+    let object_layout = scene_pipeline::object_layout(&device);
+    let lighting_layout = scene_pipeline::lighting_layout(&device);
+    let pipeline_layout =
+        scene_pipeline::pipeline_layout(&device, &object_layout, &lighting_layout);
+    let vert_module = device.create_shader_module(make_spirv(shaders::SHADER_VERT));
+    let frag_module = device.create_shader_module(make_spirv(shaders::SHADER_FRAG));
+    let pipeline = scene_pipeline::create_pipeline(
+        &device,
+        &pipeline_layout,
+        "Scene pipeline",
+        &vert_module,
+        Some(&frag_module),
+        &[ColorStateDescriptor {
+            format: FORMAT,
+            color_blend: BlendDescriptor::REPLACE,
+            alpha_blend: BlendDescriptor::REPLACE,
+            write_mask: ColorWrite::ALL,
+        }],
+        CompareFunction::Less,
+        true,
+        StencilStateDescriptor::default(),
+        1,
+        Rasterization::default(),
+    );
+
+    // large enough for one element of every deformation buffer
+    let undeformed = device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: 256,
+        usage: BufferUsage::STORAGE,
+        mapped_at_creation: false,
+    });
+    let deformation = Deformation {
+        joints: &undeformed,
+        skin: &undeformed,
+        deltas: &undeformed,
+        weights: &undeformed,
+    };
+    let mut object_bindings = ObjectBindings::new("Objects");
+    object_bindings.push(&device, &object_layout, "Triangle", deformation, None);
+    let material = PbrMaterial {
+        base_color: [1.0, 0.0, 0.0, 1.0],
+        ..Default::default()
+    };
+    let uniforms = ObjectUniforms::new(Mat4::identity(), Mat4::identity(), 0.0, 0.0, &material);
+    object_bindings.write(&queue, &[uniforms]);
+
+    let lighting = Lighting {
+        debug_view: DebugView::Albedo as u32,
+        ..Default::default()
+    };
+    let lighting_uniform = device.create_buffer_init(&BufferInitDescriptor {
+        label: None,
+        contents: &lighting.std140_bytes(),
+        usage: BufferUsage::UNIFORM,
+    });
+    let lights = device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: 256,
+        usage: BufferUsage::STORAGE,
+        mapped_at_creation: false,
+    });
+    let clusters = Clusters::new(&device, &lights);
+    let ibl = Ibl::new(&device, &queue, &Equirect::sky(16, 8));
+    let normal_map = texture::create_rgba8_mipmapped(
+        &device,
+        &queue,
+        "Normal map",
+        TextureFormat::Rgba8Unorm,
+        4,
+        4,
+        &texture::bumps_normal_map(4, 1),
+    );
+    let normal_map_view = normal_map.create_view(&TextureViewDescriptor::default());
+    let material_array = |format| {
+        let mut builder = MaterialArrayBuilder::new(4, format);
+        builder.add(1, 1, vec![255; 4]);
+        builder.build(&device, &queue, "Materials")
+    };
+    let (materials, linear_materials) = (
+        material_array(MATERIAL_FORMAT),
+        material_array(LINEAR_MATERIAL_FORMAT),
+    );
+    let shadow_map = placeholder(&device, TextureFormat::Depth32Float, 4);
+    let shadow_map_view = shadow_map.create_view(&TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    let point_shadow = placeholder(&device, TextureFormat::R32Float, 6);
+    let point_shadow_view = point_shadow.create_view(&TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        array_layer_count: NonZeroU32::new(6),
+        ..Default::default()
+    });
+    let sampler = device.create_sampler(&SamplerDescriptor::default());
+    let shadow_sampler = device.create_sampler(&SamplerDescriptor {
+        compare: Some(CompareFunction::LessEqual),
+        ..Default::default()
+    });
+    let lighting_bind_group = LightingBindings {
+        uniform: &lighting_uniform,
+        normal_map: &normal_map_view,
+        sampler: &sampler,
+        irradiance: &ibl.irradiance_view,
+        prefiltered: &ibl.prefiltered_view,
+        brdf: &ibl.brdf_view,
+        ibl_sampler: &ibl.sampler,
+        shadow_map: &shadow_map_view,
+        shadow_sampler: &shadow_sampler,
+        point_shadow: &point_shadow_view,
+        point_shadow_sampler: &sampler,
+        materials: &materials.view,
+        linear_materials: &linear_materials.view,
+        lights: &lights,
+        probe: &ibl.prefiltered_view,
+        cluster_uniform: &clusters.uniform,
+        cluster_counts: &clusters.counts,
+        cluster_indices: &clusters.indices,
+    }
+    .create(&device, &lighting_layout);
+
+    let white = [1.0, 1.0, 1.0];
+    let vertices: Vec<Vertex> = [[-1.0, -1.0, 0.5], [1.0, -1.0, 0.5], [-1.0, 1.0, 0.5]]
+        .iter()
+        .map(|&position| Vertex {
+            position,
+            normal: [0.0, 0.0, -1.0],
+            tangent: [1.0, 0.0, 0.0, 1.0],
+            uv: [0.0, 0.0],
+            color: white,
+        })
+        .collect();
+    let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&vertices),
+        usage: BufferUsage::VERTEX,
+    });
+
+    let extent = Extent3d {
+        width: SIZE,
+        height: SIZE,
+        depth: 1,
+    };
+    let target = device.create_texture(&TextureDescriptor {
+        label: None,
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: FORMAT,
+        usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::COPY_SRC,
+    });
+    let view = target.create_view(&TextureViewDescriptor::default());
+    let depth = device.create_texture(&TextureDescriptor {
+        label: None,
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: TextureUsage::OUTPUT_ATTACHMENT,
+    });
+    let depth_view = depth.create_view(&TextureViewDescriptor::default());
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+    {
+        let mut pass = CountedPass::new(encoder.begin_render_pass(&RenderPassDescriptor {
+            color_attachments: &[RenderPassColorAttachmentDescriptor {
+                attachment: &view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLUE),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachmentDescriptor {
+                attachment: &depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: false,
+                }),
+                stencil_ops: None,
+            }),
+        }));
+        pass.set_pipeline(&pipeline);
+        object_bindings.bind(&mut pass, 0);
+        pass.set_bind_group(1, &lighting_bind_group, &[]);
+        pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        pass.draw(0..3, 0..1);
+    }
+    queue.submit(Some(encoder.finish()));
+
+    let pixels = block_on(readback::read_texture(&device, &queue, &target, SIZE, SIZE));
+    let pixel = |x: u32, y: u32| {
+        let i = (4 * (y * SIZE + x)) as usize;
+        [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]]
+    };
+
+    // rows go down, the triangle is below the diagonal
+    assert_eq!(pixel(4, SIZE - 4), [255, 0, 0, 255], "bottom left");
+    assert_eq!(pixel(SIZE / 4, SIZE / 2 + 4), [255, 0, 0, 255], "left");
+    assert_eq!(pixel(SIZE - 4, 4), [0, 0, 255, 255], "top right");
+    assert_eq!(pixel(SIZE - 4, SIZE / 2 - 4), [0, 0, 255, 255], "right");
+}
+
+#[test]
+fn vertex_pulling_matches_vertex_buffers() {
+    let (device, queue) = match adapter::headless_device() {
+        Some(device) => device,
+        None => {
+            eprintln!("No adapter found, skipping");
+            return;
+        }
+    };
+
+    let extent = Extent3d {
+        width: SIZE,
+        height: SIZE,
+        depth: 1,
+    };
+    let target = device.create_texture(&TextureDescriptor {
+        label: None,
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: FORMAT,
+        usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::COPY_SRC,
+    });
+    let view = target.create_view(&TextureViewDescriptor::default());
+    let depth = device.create_texture(&TextureDescriptor {
+        label: None,
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: TextureUsage::OUTPUT_ATTACHMENT,
+    });
+    let depth_view = depth.create_view(&TextureViewDescriptor::default());
+
+    // a few spheres seen from above
+    let projection = Mat4::perspective_rh(1.0, 1.0, 0.1, 10.0);
+    let camera = Mat4::look_at_rh(Vec3::new(0.0, 2.0, 0.5), Vec3::zero(), Vec3::unit_y());
+    let mut vertex_pulling = VertexPulling::new(&device, FORMAT, &PullingParams::default());
+    let mut render = |pulled| {
+        let params = PullingParams {
+            pulled,
+            instances: 9,
+            segments: 16,
+        };
+        vertex_pulling.update(&device, &queue, &params, projection * camera, 0.5);
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = CountedPass::new(encoder.begin_render_pass(&RenderPassDescriptor {
+                color_attachments: &[RenderPassColorAttachmentDescriptor {
+                    attachment: &view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachmentDescriptor {
+                    attachment: &depth_view,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(1.0),
+                        store: false,
+                    }),
+                    stencil_ops: None,
+                }),
+            }));
+            vertex_pulling.draw(&mut pass, &params);
+        }
+        queue.submit(Some(encoder.finish()));
+        block_on(readback::read_texture(&device, &queue, &target, SIZE, SIZE))
+    };
+
+    let classic = render(false);
+    let pulled = render(true);
+    // the center sphere covers the middle of the frame
+    let center = (4 * (SIZE * SIZE / 2 + SIZE / 2)) as usize;
+    assert_ne!(classic[center..center + 3], [0, 0, 0], "nothing drawn");
+    // both fetch the same floats, only rounding of the edges may differ
+    let differing = classic
+        .chunks(4)
+        .zip(pulled.chunks(4))
+        .filter(|(a, b)| a != b)
+        .count();
+    assert!(differing < SIZE as usize, "{} pixels differ", differing);
+}
@@ -0,0 +1,96 @@
+//! HLSL shaders go through the build like the GLSL ones, and come out as
+//! SPIR-V the device runs.
+use futures::executor::block_on;
+use wgpu::{
+    util::make_spirv, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferDescriptor, BufferUsage,
+    CommandEncoderDescriptor, ComputePipelineDescriptor, PipelineLayoutDescriptor,
+    ProgrammableStageDescriptor, ShaderStage,
+};
+use wgpu_test::{adapter, readback, shaders};
+
+const LEN: usize = 256;
+
+/// SPIR-V of `iota.comp.hlsl`, as listed with every other shader.
+fn iota() -> &'static [u8] {
+    shaders::ALL
+        .iter()
+        .find(|(path, _)| *path == "iota.comp.hlsl")
+        .map(|(_, spirv)| *spirv)
+        .expect("iota.comp.hlsl isn't in shaders::ALL")
+}
+
+#[test]
+fn hlsl_is_listed() {
+    let spirv = iota();
+    assert_eq!(spirv, shaders::IOTA_COMP_HLSL);
+    // SPIR-V magic number, in little endian words
+    assert_eq!(spirv[..4], [0x03, 0x02, 0x23, 0x07]);
+    assert_eq!(spirv.len() % 4, 0);
+}
+
+#[test]
+fn hlsl_runs() {
+    let (device, queue) = match adapter::headless_device() {
+        Some(device) => device,
+        None => {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        }
+    };
+
+    let module = device.create_shader_module(make_spirv(iota()));
+    let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStage::COMPUTE,
+            ty: BindingType::StorageBuffer {
+                dynamic: false,
+                min_binding_size: None,
+                readonly: false,
+            },
+            count: None,
+        }],
+    });
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        compute_stage: ProgrammableStageDescriptor {
+            module: &module,
+            entry_point: "main",
+        },
+    });
+    let values = device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: (LEN * 4) as _,
+        usage: BufferUsage::STORAGE | BufferUsage::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout: &layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: BindingResource::Buffer(values.slice(..)),
+        }],
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+    {
+        let mut pass = encoder.begin_compute_pass();
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        // workgroups of 64
+        pass.dispatch(LEN as u32 / 64, 1, 1);
+    }
+    queue.submit(Some(encoder.finish()));
+
+    let values: Vec<u32> = block_on(readback::read_buffer(&device, &queue, &values, LEN));
+    assert_eq!(values, (0..LEN as u32).collect::<Vec<_>>());
+}